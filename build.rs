@@ -17,4 +17,13 @@ fn main() {
 
     let path = std::env::var("OUT_DIR").unwrap();
     println!("{}", path + "/../../../BasicPixelShader.hlsl");
+
+    std::fs::copy(
+        "src/MeshletShader.hlsl",
+        std::env::var("OUT_DIR").unwrap() + "/../../../MeshletShader.hlsl",
+    )
+    .expect("Copy");
+
+    let path = std::env::var("OUT_DIR").unwrap();
+    println!("{}", path + "/../../../MeshletShader.hlsl");
 }
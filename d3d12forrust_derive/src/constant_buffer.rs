@@ -0,0 +1,102 @@
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::quote;
+use syn::{Data, DeriveInput, Error, Fields, LitStr, Result};
+
+use crate::vertex_layout::has_repr_c;
+
+pub fn derive(input: DeriveInput) -> Result<TokenStream2> {
+    let name = &input.ident;
+    if !input.generics.params.is_empty() {
+        return Err(Error::new_spanned(
+            &input.generics,
+            "ConstantBufferLayout cannot be derived for generic structs",
+        ));
+    }
+    if !has_repr_c(&input) {
+        return Err(Error::new_spanned(
+            name,
+            "ConstantBufferLayout requires #[repr(C)]",
+        ));
+    }
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(Error::new_spanned(
+                    name,
+                    "ConstantBufferLayout requires a struct with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(Error::new_spanned(
+                name,
+                "ConstantBufferLayout can only be derived for structs",
+            ))
+        }
+    };
+
+    let field_count = fields.len();
+    let mut placements = Vec::new();
+    let mut writes = Vec::new();
+    for (i, field) in fields.iter().enumerate() {
+        let field_name = field.ident.as_ref().unwrap();
+        let ty = &field.ty;
+
+        let mut hlsl_name = field_name.to_string();
+        for attr in field.attrs.iter().filter(|a| a.path().is_ident("cbuffer")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("name") {
+                    hlsl_name = meta.value()?.parse::<LitStr>()?.value();
+                } else {
+                    return Err(meta.error("expected `name`"));
+                }
+                Ok(())
+            })?;
+        }
+        let hlsl_name = LitStr::new(&hlsl_name, Span::call_site());
+
+        placements.push(quote! {
+            fields[#i] = ::d3d12forrust::constant_buffer::CbField::place(
+                #hlsl_name,
+                cursor,
+                <#ty as ::d3d12forrust::constant_buffer::HlslType>::SIZE,
+                <#ty as ::d3d12forrust::constant_buffer::HlslType>::ALIGN_TO_REGISTER,
+            );
+            cursor = fields[#i].offset + fields[#i].size;
+        });
+        writes.push(quote! {
+            ::d3d12forrust::constant_buffer::HlslType::write_hlsl(
+                &self.#field_name,
+                &mut dst[Self::FIELDS[#i].offset..],
+            );
+        });
+    }
+
+    Ok(quote! {
+        impl ::d3d12forrust::constant_buffer::ConstantBufferLayout for #name {
+            const FIELDS: &'static [::d3d12forrust::constant_buffer::CbField] = &{
+                let mut fields = [::d3d12forrust::constant_buffer::CbField::EMPTY; #field_count];
+                let mut cursor = 0usize;
+                #(#placements)*
+                let _ = cursor;
+                fields
+            };
+
+            fn write_packed(&self, dst: &mut [u8]) {
+                #(#writes)*
+            }
+        }
+
+        impl ::d3d12forrust::constant_buffer::HlslType for #name {
+            const SIZE: usize =
+                ::d3d12forrust::constant_buffer::packed_size(<Self as ::d3d12forrust::constant_buffer::ConstantBufferLayout>::FIELDS);
+            const ALIGN_TO_REGISTER: bool = true;
+
+            fn write_hlsl(&self, dst: &mut [u8]) {
+                ::d3d12forrust::constant_buffer::ConstantBufferLayout::write_packed(self, dst);
+            }
+        }
+    })
+}
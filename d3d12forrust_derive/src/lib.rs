@@ -0,0 +1,21 @@
+use proc_macro::TokenStream;
+use syn::{parse_macro_input, DeriveInput, Error};
+
+mod constant_buffer;
+mod vertex_layout;
+
+#[proc_macro_derive(VertexLayout, attributes(vertex))]
+pub fn derive_vertex_layout(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    vertex_layout::derive(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+#[proc_macro_derive(ConstantBufferLayout, attributes(cbuffer))]
+pub fn derive_constant_buffer_layout(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    constant_buffer::derive(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
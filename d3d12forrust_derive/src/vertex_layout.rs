@@ -0,0 +1,227 @@
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{format_ident, quote};
+use syn::{Data, DeriveInput, Error, Fields, Ident, LitInt, LitStr, Result};
+
+#[derive(Default)]
+struct StructAttrs {
+    slot: u32,
+    per_instance: bool,
+    step_rate: Option<u32>,
+}
+
+#[derive(Default)]
+struct FieldAttrs {
+    semantic: Option<String>,
+    index: u32,
+    format: Option<Ident>,
+    normalized: bool,
+}
+
+pub fn derive(input: DeriveInput) -> Result<TokenStream2> {
+    let name = &input.ident;
+    if !input.generics.params.is_empty() {
+        return Err(Error::new_spanned(
+            &input.generics,
+            "VertexLayout cannot be derived for generic structs",
+        ));
+    }
+    if !has_repr_c(&input) {
+        return Err(Error::new_spanned(
+            name,
+            "VertexLayout requires #[repr(C)] so that field offsets are stable",
+        ));
+    }
+
+    let mut struct_attrs = StructAttrs::default();
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("vertex")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("slot") {
+                struct_attrs.slot = meta.value()?.parse::<LitInt>()?.base10_parse()?;
+            } else if meta.path.is_ident("per_instance") {
+                struct_attrs.per_instance = true;
+            } else if meta.path.is_ident("step_rate") {
+                struct_attrs.step_rate = Some(meta.value()?.parse::<LitInt>()?.base10_parse()?);
+            } else {
+                return Err(meta.error("expected `slot`, `per_instance` or `step_rate`"));
+            }
+            Ok(())
+        })?;
+    }
+    if struct_attrs.step_rate.is_some() && !struct_attrs.per_instance {
+        return Err(Error::new_spanned(
+            name,
+            "`step_rate` only applies to `per_instance` layouts",
+        ));
+    }
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(Error::new_spanned(
+                    name,
+                    "VertexLayout requires a struct with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(Error::new_spanned(
+                name,
+                "VertexLayout can only be derived for structs",
+            ))
+        }
+    };
+
+    let slot = struct_attrs.slot;
+    let (classification, step_rate) = if struct_attrs.per_instance {
+        (
+            quote!(::windows::Win32::Graphics::Direct3D12::D3D12_INPUT_CLASSIFICATION_PER_INSTANCE_DATA),
+            struct_attrs.step_rate.unwrap_or(1),
+        )
+    } else {
+        (
+            quote!(
+                ::windows::Win32::Graphics::Direct3D12::D3D12_INPUT_CLASSIFICATION_PER_VERTEX_DATA
+            ),
+            0,
+        )
+    };
+
+    let mut pushes = Vec::new();
+    for field in fields {
+        let field_name = field.ident.as_ref().unwrap();
+        let ty = &field.ty;
+
+        let mut attrs = FieldAttrs::default();
+        for attr in field.attrs.iter().filter(|a| a.path().is_ident("vertex")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("semantic") {
+                    attrs.semantic = Some(meta.value()?.parse::<LitStr>()?.value());
+                } else if meta.path.is_ident("index") {
+                    attrs.index = meta.value()?.parse::<LitInt>()?.base10_parse()?;
+                } else if meta.path.is_ident("format") {
+                    attrs.format = Some(meta.value()?.parse::<Ident>()?);
+                } else if meta.path.is_ident("normalized") {
+                    attrs.normalized = true;
+                } else {
+                    return Err(
+                        meta.error("expected `semantic`, `index`, `format` or `normalized`")
+                    );
+                }
+                Ok(())
+            })?;
+        }
+        if attrs.format.is_some() && attrs.normalized {
+            return Err(Error::new_spanned(
+                field_name,
+                "`normalized` cannot be combined with an explicit `format`",
+            ));
+        }
+
+        let semantic = attrs
+            .semantic
+            .unwrap_or_else(|| field_name.to_string().to_uppercase());
+        if semantic.is_empty() || semantic.contains('\0') {
+            return Err(Error::new_spanned(field_name, "invalid semantic name"));
+        }
+        let semantic = LitStr::new(&format!("{}\0", semantic), Span::call_site());
+        let semantic_index = attrs.index;
+
+        let format = match (&attrs.format, attrs.normalized) {
+            (Some(format), _) => {
+                let format = format_ident!("DXGI_FORMAT_{}", format);
+                quote!(::windows::Win32::Graphics::Dxgi::Common::#format)
+            }
+            (None, true) => {
+                let message = LitStr::new(
+                    &format!("field `{}` has no normalized vertex format", field_name),
+                    Span::call_site(),
+                );
+                quote! {
+                    match <#ty as ::d3d12forrust::vertex_layout::VertexAttribute>::NORMALIZED_FORMAT {
+                        Some(format) => format,
+                        None => panic!(#message),
+                    }
+                }
+            }
+            (None, false) => {
+                quote!(<#ty as ::d3d12forrust::vertex_layout::VertexAttribute>::FORMAT)
+            }
+        };
+        let size_message = LitStr::new(
+            &format!(
+                "field `{}` does not match the size of its vertex format",
+                field_name
+            ),
+            Span::call_site(),
+        );
+
+        let kind_message = LitStr::new(
+            &format!(
+                "field `{}` does not match the component kind of its vertex format",
+                field_name
+            ),
+            Span::call_site(),
+        );
+
+        pushes.push(quote! {
+            {
+                const FORMAT: ::windows::Win32::Graphics::Dxgi::Common::DXGI_FORMAT = #format;
+                const ROWS: u32 = <#ty as ::d3d12forrust::vertex_layout::VertexAttribute>::ROWS;
+                const _: () = assert!(
+                    ::std::mem::size_of::<#ty>()
+                        == ::d3d12forrust::vertex_layout::format_size(FORMAT) * ROWS as usize,
+                    #size_message
+                );
+                const _: () = assert!(
+                    ::d3d12forrust::vertex_layout::kind_matches(
+                        <#ty as ::d3d12forrust::vertex_layout::VertexAttribute>::KIND,
+                        FORMAT,
+                    ),
+                    #kind_message
+                );
+                for row in 0..ROWS {
+                    elements.push(::d3d12forrust::vertex_layout::InputElement {
+                        semantic_name: #semantic,
+                        semantic_index: #semantic_index + row,
+                        format: FORMAT,
+                        input_slot: #slot,
+                        aligned_byte_offset: (::std::mem::offset_of!(#name, #field_name)
+                            + row as usize * ::d3d12forrust::vertex_layout::format_size(FORMAT))
+                            as u32,
+                        input_slot_class: #classification,
+                        instance_data_step_rate: #step_rate,
+                    });
+                }
+            }
+        });
+    }
+
+    Ok(quote! {
+        impl ::d3d12forrust::vertex_layout::VertexLayout for #name {
+            fn input_elements() -> Vec<::d3d12forrust::vertex_layout::InputElement> {
+                let mut elements = Vec::new();
+                #(#pushes)*
+                elements
+            }
+        }
+    })
+}
+
+pub fn has_repr_c(input: &DeriveInput) -> bool {
+    input.attrs.iter().any(|attr| {
+        let mut repr_c = false;
+        if attr.path().is_ident("repr") {
+            let _ = attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("C") {
+                    repr_c = true;
+                } else if meta.input.peek(syn::token::Paren) {
+                    let _args;
+                    syn::parenthesized!(_args in meta.input);
+                }
+                Ok(())
+            });
+        }
+        repr_c
+    })
+}
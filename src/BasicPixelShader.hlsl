@@ -2,13 +2,145 @@ struct Output
 {
     float4 svpos:SV_POSITION;
     float2 uv:TEXCOORD;
+    float4 color:COLOR;
 };
 
 Texture2D<float4> tex:register(t0);
 SamplerState smp:register(s0);
+SamplerState pointSmp:register(s1);
+SamplerState anisotropicSmp:register(s2);
+
+cbuffer OutputConstants : register(b1)
+{
+    uint outputMode;
+    float paperWhiteNits;
+    uint samplerFilter;
+    uint tonemap;
+    float tonemapWhite;
+};
+
+static const float3x3 Rec709ToRec2020 =
+{
+    0.627404, 0.329283, 0.043313,
+    0.069097, 0.919540, 0.011362,
+    0.016391, 0.088013, 0.895595
+};
+
+float3
+PqEncode(float3 nits)
+{
+    const float m1 = 2610.0 / 16384.0;
+    const float m2 = 2523.0 / 4096.0 * 128.0;
+    const float c1 = 3424.0 / 4096.0;
+    const float c2 = 2413.0 / 4096.0 * 32.0;
+    const float c3 = 2392.0 / 4096.0 * 32.0;
+    float3 y = pow(saturate(nits / 10000.0), m1);
+    return pow((c1 + c2 * y) / (1.0 + c3 * y), m2);
+}
+
+float
+Luminance(float3 color)
+{
+    return dot(color, float3(0.2126, 0.7152, 0.0722));
+}
+
+float3
+HablePartial(float3 x)
+{
+    const float a = 0.15;
+    const float b = 0.50;
+    const float c = 0.10;
+    const float d = 0.20;
+    const float e = 0.02;
+    const float f = 0.30;
+    return ((x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f)) - e / f;
+}
+
+float3
+ApplyTonemap(float3 color)
+{
+    if (tonemap == 1)
+    {
+        return color / (1.0 + color);
+    }
+    else if (tonemap == 2)
+    {
+        float l = Luminance(color);
+        if (l <= 0.0)
+        {
+            return float3(0.0, 0.0, 0.0);
+        }
+        float mapped = l * (1.0 + l / (tonemapWhite * tonemapWhite)) / (1.0 + l);
+        return color * (mapped / l);
+    }
+    else if (tonemap == 3)
+    {
+        float3 c = max(color, 0.0);
+        return saturate((c * (2.51 * c + 0.03)) / (c * (2.43 * c + 0.59) + 0.14));
+    }
+    else if (tonemap == 4)
+    {
+        const float white = 11.2;
+        return HablePartial(max(color, 0.0) * 2.0) / HablePartial(white).x;
+    }
+    return saturate(color);
+}
+
+float4
+ApplyOutputMode(float4 color)
+{
+    if (outputMode == 0)
+    {
+        color.rgb = ApplyTonemap(color.rgb);
+    }
+    else if (outputMode == 1)
+    {
+        color.rgb = PqEncode(mul(Rec709ToRec2020, color.rgb) * paperWhiteNits);
+    }
+    else if (outputMode == 2)
+    {
+        color.rgb *= paperWhiteNits / 80.0;
+    }
+    return color;
+}
+
+float4
+SampleTexture(float2 uv)
+{
+    if (samplerFilter == 1)
+    {
+        return tex.Sample(pointSmp, uv);
+    }
+    else if (samplerFilter == 2)
+    {
+        return tex.Sample(anisotropicSmp, uv);
+    }
+    return tex.Sample(smp, uv);
+}
 
 float4
 BasicPS(Output input) : SV_TARGET
 {
-    return float4(tex.Sample(smp, input.uv));
-}
\ No newline at end of file
+    return ApplyOutputMode(SampleTexture(input.uv) * input.color);
+}
+
+float4
+UiPS(Output input) : SV_TARGET
+{
+    return ApplyOutputMode(tex.Sample(smp, input.uv) * input.color);
+}
+
+float4
+SdfTextPS(Output input) : SV_TARGET
+{
+    float distance = tex.Sample(smp, input.uv).r;
+    float width = max(fwidth(distance), 1e-4);
+    float alpha = smoothstep(0.5 - width, 0.5 + width, distance);
+    return ApplyOutputMode(float4(input.color.rgb, input.color.a * alpha));
+}
+
+float4
+DebugLinePS(Output input) : SV_TARGET
+{
+    return ApplyOutputMode(input.color);
+}
@@ -1,17 +1,140 @@
 struct Output {
     float4 svpos:SV_POSITION;//システム用頂点座標
     float2 uv:TEXCOORD;//UV値
+    float4 color:COLOR;
 };
 
 cbuffer cbuff0: register(b0) {
     matrix mat;
 }
 
+cbuffer BoneConstants: register(b2) {
+    matrix bones[256];
+    float4 real[256];
+    float4 dual[256];
+}
+
+float3 RotateByQuaternion(float4 q, float3 v) {
+    return v + 2 * cross(q.xyz, cross(q.xyz, v) + q.w * v);
+}
+
+float4 SkinLinear(float4 pos, uint4 boneno, float4 weight) {
+    matrix bm = bones[boneno.x] * weight.x + bones[boneno.y] * weight.y
+        + bones[boneno.z] * weight.z + bones[boneno.w] * weight.w;
+    return mul(bm, pos);
+}
+
+float4 SkinDualQuaternion(float4 pos, uint4 boneno, float4 weight) {
+    float4 pivot = real[boneno.x];
+    float4 r = float4(0, 0, 0, 0);
+    float4 d = float4(0, 0, 0, 0);
+    [unroll]
+    for (uint i = 0; i < 4; ++i) {
+        float4 ri = real[boneno[i]];
+        float w = dot(pivot, ri) < 0 ? -weight[i] : weight[i];
+        r += ri * w;
+        d += dual[boneno[i]] * w;
+    }
+    float len = length(r);
+    r /= len;
+    d /= len;
+    float3 t = 2 * (r.w * d.xyz - d.w * r.xyz + cross(r.xyz, d.xyz));
+    return float4(RotateByQuaternion(r, pos.xyz) + t, 1);
+}
+
+float4 SkinSdef(float4 pos, uint4 boneno, float4 weight, float3 c, float3 r0, float3 r1) {
+    float4 q0 = real[boneno.x];
+    float4 q1 = real[boneno.y];
+    q1 = dot(q0, q1) < 0 ? -q1 : q1;
+    float4 q = normalize(q0 * weight.x + q1 * weight.y);
+    float3 p0 = mul(bones[boneno.x], float4(r0, 1)).xyz;
+    float3 p1 = mul(bones[boneno.y], float4(r1, 1)).xyz;
+    return float4(RotateByQuaternion(q, pos.xyz - c) + p0 * weight.x + p1 * weight.y, 1);
+}
 
-Output BasicVS(float4 pos: POSITION, float2 uv: TEXCOORD) 
+Output BasicVS(
+    float4 pos: POSITION,
+    float4 normal: NORMAL,
+    float2 uv: TEXCOORD,
+    uint4 boneno: BONE_NO,
+    float4 weight: WEIGHT,
+    float3 sdefc: SDEF_C,
+    float3 sdefr0: SDEF_R0,
+    float3 sdefr1: SDEF_R1,
+    min16uint edgeflg: EDGE_FLG,
+    min16uint deform: DEFORM) 
 {
     Output output;
+    if (deform == 2) {
+        pos = SkinSdef(pos, boneno, weight, sdefc, sdefr0, sdefr1);
+    } else if (deform == 1) {
+        pos = SkinDualQuaternion(pos, boneno, weight);
+    } else {
+#if DUAL_QUATERNION_SKINNING
+        pos = SkinDualQuaternion(pos, boneno, weight);
+#else
+        pos = SkinLinear(pos, boneno, weight);
+#endif
+    }
     output.svpos = mul(mat, pos);
     output.uv = uv;
+    output.color = float4(1, 1, 1, 1);
+    return output;
+}
+
+Output SpriteVS(
+    float4 pos: POSITION,
+    float2 uv: TEXCOORD,
+    float4 color: COLOR)
+{
+    Output output;
+    output.svpos = mul(mat, float4(pos.xyz, 1));
+    output.uv = uv;
+    output.color = color;
+    return output;
+}
+
+Output DebugLineVS(
+    float4 pos: POSITION,
+    float4 color: COLOR)
+{
+    Output output;
+    output.svpos = mul(mat, float4(pos.xyz, 1));
+    output.uv = float2(0, 0);
+    output.color = color;
+    return output;
+}
+
+float3 SrgbToLinear(float3 color)
+{
+    return color <= 0.04045 ? color / 12.92 : pow((color + 0.055) / 1.055, 2.4);
+}
+
+Output UiVS(
+    float2 pos: POSITION,
+    float2 uv: TEXCOORD,
+    float4 color: COLOR)
+{
+    Output output;
+    output.svpos = mul(mat, float4(pos, 0, 1));
+    output.uv = uv;
+    output.color = float4(SrgbToLinear(color.rgb), color.a);
     return output;
-}
\ No newline at end of file
+}
+
+Output InstancedVS(
+    float4 pos: POSITION,
+    float2 uv: TEXCOORD,
+    float4 transform0: INSTANCE_TRANSFORM0,
+    float4 transform1: INSTANCE_TRANSFORM1,
+    float4 transform2: INSTANCE_TRANSFORM2,
+    float4 transform3: INSTANCE_TRANSFORM3,
+    float4 color: INSTANCE_COLOR)
+{
+    Output output;
+    float4x4 transform = float4x4(transform0, transform1, transform2, transform3);
+    output.svpos = mul(mat, mul(float4(pos.xyz, 1), transform));
+    output.uv = uv;
+    output.color = color;
+    return output;
+}
@@ -0,0 +1,110 @@
+#define GROUP_SIZE 32
+#define MAX_VERTICES 64
+#define MAX_TRIANGLES 124
+
+struct Output {
+    float4 svpos:SV_POSITION;
+    float2 uv:TEXCOORD;
+    float4 color:COLOR;
+};
+
+struct Vertex {
+    float3 position;
+    float3 normal;
+    float2 uv;
+};
+
+struct Meshlet {
+    uint vertexOffset;
+    uint vertexCount;
+    uint triangleOffset;
+    uint triangleCount;
+};
+
+struct MeshletBounds {
+    float3 center;
+    float radius;
+    float3 coneAxis;
+    float coneCutoff;
+};
+
+struct Payload {
+    uint meshletIndices[GROUP_SIZE];
+};
+
+cbuffer cbuff0: register(b0) {
+    matrix mat;
+}
+
+cbuffer MeshletConstants: register(b3) {
+    float4 planes[6];
+    float3 cameraPosition;
+    uint meshletCount;
+}
+
+StructuredBuffer<Vertex> meshVertices: register(t1);
+StructuredBuffer<Meshlet> meshlets: register(t2);
+StructuredBuffer<MeshletBounds> meshletBounds: register(t3);
+StructuredBuffer<uint> vertexIndices: register(t4);
+StructuredBuffer<uint> primitiveIndices: register(t5);
+
+groupshared Payload sharedPayload;
+groupshared uint visibleCount;
+
+bool IsVisible(MeshletBounds bounds) {
+    for (uint i = 0; i < 6; ++i) {
+        if (dot(planes[i].xyz, bounds.center) + planes[i].w < -bounds.radius) {
+            return false;
+        }
+    }
+    if (bounds.coneCutoff < 1) {
+        float3 view = bounds.center - cameraPosition;
+        if (dot(view, bounds.coneAxis) >= bounds.coneCutoff * length(view) + bounds.radius) {
+            return false;
+        }
+    }
+    return true;
+}
+
+[numthreads(GROUP_SIZE, 1, 1)]
+void MeshletAS(uint gtid: SV_GroupThreadID, uint dtid: SV_DispatchThreadID) {
+    if (gtid == 0) {
+        visibleCount = 0;
+    }
+    GroupMemoryBarrierWithGroupSync();
+
+    if (dtid < meshletCount && IsVisible(meshletBounds[dtid])) {
+        uint index;
+        InterlockedAdd(visibleCount, 1, index);
+        sharedPayload.meshletIndices[index] = dtid;
+    }
+    GroupMemoryBarrierWithGroupSync();
+
+    DispatchMesh(visibleCount, 1, 1, sharedPayload);
+}
+
+[outputtopology("triangle")]
+[numthreads(128, 1, 1)]
+void MeshletMS(
+    uint gtid: SV_GroupThreadID,
+    uint gid: SV_GroupID,
+    in payload Payload payload,
+    out vertices Output verts[MAX_VERTICES],
+    out indices uint3 tris[MAX_TRIANGLES])
+{
+    Meshlet meshlet = meshlets[payload.meshletIndices[gid]];
+    SetMeshOutputCounts(meshlet.vertexCount, meshlet.triangleCount);
+
+    if (gtid < meshlet.vertexCount) {
+        Vertex v = meshVertices[vertexIndices[meshlet.vertexOffset + gtid]];
+        Output output;
+        output.svpos = mul(mat, float4(v.position, 1));
+        output.uv = v.uv;
+        output.color = float4(1, 1, 1, 1);
+        verts[gtid] = output;
+    }
+    if (gtid < meshlet.triangleCount) {
+        uint packed = primitiveIndices[meshlet.triangleOffset + gtid];
+        tris[gtid] = uint3(packed & 0x3FF, (packed >> 10) & 0x3FF, (packed >> 20) & 0x3FF);
+    }
+}
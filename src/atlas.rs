@@ -0,0 +1,1120 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use cgmath::Vector2;
+use image::{Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+
+use crate::binary_reader::{BinaryReader, UnexpectedEof};
+use crate::sprite_batch::{Sprite, UvRect};
+
+const BINARY_MAGIC: [u8; 4] = *b"ATLS";
+const BINARY_VERSION: u32 = 1;
+
+#[derive(Debug)]
+pub enum AtlasError {
+    Io {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    Image {
+        path: PathBuf,
+        error: image::ImageError,
+    },
+    ImageTooLarge {
+        name: String,
+        width: u32,
+        height: u32,
+    },
+    Json(serde_json::Error),
+    InvalidMagic([u8; 4]),
+    UnsupportedVersion(u32),
+    InvalidName,
+    UnexpectedEof(UnexpectedEof),
+}
+
+impl std::fmt::Display for AtlasError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AtlasError::Io { path, error } => write!(f, "{}: {}", path.display(), error),
+            AtlasError::Image { path, error } => write!(f, "{}: {}", path.display(), error),
+            AtlasError::ImageTooLarge {
+                name,
+                width,
+                height,
+            } => write!(
+                f,
+                "image {} ({}x{}) does not fit on an atlas page",
+                name, width, height
+            ),
+            AtlasError::Json(error) => write!(f, "invalid atlas JSON: {}", error),
+            AtlasError::InvalidMagic(magic) => {
+                write!(f, "not an atlas layout file (magic {:?})", magic)
+            }
+            AtlasError::UnsupportedVersion(version) => {
+                write!(f, "unsupported atlas layout version {}", version)
+            }
+            AtlasError::InvalidName => write!(f, "atlas entry name is not valid UTF-8"),
+            AtlasError::UnexpectedEof(error) => error.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for AtlasError {}
+
+impl From<UnexpectedEof> for AtlasError {
+    fn from(error: UnexpectedEof) -> Self {
+        AtlasError::UnexpectedEof(error)
+    }
+}
+
+impl From<serde_json::Error> for AtlasError {
+    fn from(error: serde_json::Error) -> Self {
+        AtlasError::Json(error)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, AtlasError>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PackingAlgorithm {
+    MaxRects,
+    Skyline,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SidecarFormat {
+    Json,
+    Binary,
+}
+
+impl SidecarFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            SidecarFormat::Json => "json",
+            SidecarFormat::Binary => "atlas",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AtlasSettings {
+    pub page_width: u32,
+    pub page_height: u32,
+    pub padding: u32,
+    pub extrude: u32,
+    pub algorithm: PackingAlgorithm,
+}
+
+impl Default for AtlasSettings {
+    fn default() -> Self {
+        AtlasSettings {
+            page_width: 2048,
+            page_height: 2048,
+            padding: 2,
+            extrude: 1,
+            algorithm: PackingAlgorithm::MaxRects,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Rect {
+    fn right(&self) -> u32 {
+        self.x + self.width
+    }
+
+    fn bottom(&self) -> u32 {
+        self.y + self.height
+    }
+
+    fn contains(&self, other: &Rect) -> bool {
+        other.x >= self.x
+            && other.y >= self.y
+            && other.right() <= self.right()
+            && other.bottom() <= self.bottom()
+    }
+
+    fn intersects(&self, other: &Rect) -> bool {
+        self.x < other.right()
+            && other.x < self.right()
+            && self.y < other.bottom()
+            && other.y < self.bottom()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Placement {
+    pub page: usize,
+    pub x: u32,
+    pub y: u32,
+}
+
+struct MaxRectsPage {
+    free: Vec<Rect>,
+}
+
+impl MaxRectsPage {
+    fn new(width: u32, height: u32) -> Self {
+        MaxRectsPage {
+            free: vec![Rect {
+                x: 0,
+                y: 0,
+                width,
+                height,
+            }],
+        }
+    }
+
+    fn insert(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+        let (x, y) = self
+            .free
+            .iter()
+            .filter(|r| width <= r.width && height <= r.height)
+            .min_by_key(|r| {
+                let (dw, dh) = (r.width - width, r.height - height);
+                (dw.min(dh), dw.max(dh), r.y, r.x)
+            })
+            .map(|r| (r.x, r.y))?;
+
+        let placed = Rect {
+            x,
+            y,
+            width,
+            height,
+        };
+        let mut free = Vec::with_capacity(self.free.len() + 4);
+        for r in &self.free {
+            if !r.intersects(&placed) {
+                free.push(*r);
+                continue;
+            }
+            if placed.x > r.x {
+                free.push(Rect {
+                    width: placed.x - r.x,
+                    ..*r
+                });
+            }
+            if placed.right() < r.right() {
+                free.push(Rect {
+                    x: placed.right(),
+                    width: r.right() - placed.right(),
+                    ..*r
+                });
+            }
+            if placed.y > r.y {
+                free.push(Rect {
+                    height: placed.y - r.y,
+                    ..*r
+                });
+            }
+            if placed.bottom() < r.bottom() {
+                free.push(Rect {
+                    y: placed.bottom(),
+                    height: r.bottom() - placed.bottom(),
+                    ..*r
+                });
+            }
+        }
+        let mut pruned: Vec<Rect> = Vec::with_capacity(free.len());
+        for (i, r) in free.iter().enumerate() {
+            let redundant = free
+                .iter()
+                .enumerate()
+                .any(|(j, other)| i != j && other.contains(r) && (other != r || j < i));
+            if !redundant {
+                pruned.push(*r);
+            }
+        }
+        self.free = pruned;
+        Some((x, y))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct SkylineNode {
+    x: u32,
+    y: u32,
+    width: u32,
+}
+
+struct SkylinePage {
+    width: u32,
+    height: u32,
+    nodes: Vec<SkylineNode>,
+}
+
+impl SkylinePage {
+    fn new(width: u32, height: u32) -> Self {
+        SkylinePage {
+            width,
+            height,
+            nodes: vec![SkylineNode { x: 0, y: 0, width }],
+        }
+    }
+
+    fn fit(&self, index: usize, width: u32, height: u32) -> Option<u32> {
+        let x = self.nodes[index].x;
+        if x + width > self.width {
+            return None;
+        }
+        let mut y = 0;
+        let mut covered = 0;
+        for node in &self.nodes[index..] {
+            if covered >= width {
+                break;
+            }
+            y = y.max(node.y);
+            if y + height > self.height {
+                return None;
+            }
+            covered += node.width;
+        }
+        Some(y)
+    }
+
+    fn insert(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+        let (index, y) = (0..self.nodes.len())
+            .filter_map(|i| self.fit(i, width, height).map(|y| (i, y)))
+            .min_by_key(|&(i, y)| (y + height, self.nodes[i].width, self.nodes[i].x))?;
+        let x = self.nodes[index].x;
+
+        self.nodes.insert(
+            index,
+            SkylineNode {
+                x,
+                y: y + height,
+                width,
+            },
+        );
+        let right = x + width;
+        while index + 1 < self.nodes.len() && self.nodes[index + 1].x < right {
+            let next = &mut self.nodes[index + 1];
+            let shrink = right - next.x;
+            if next.width <= shrink {
+                self.nodes.remove(index + 1);
+            } else {
+                next.x += shrink;
+                next.width -= shrink;
+                break;
+            }
+        }
+        let mut i = 0;
+        while i + 1 < self.nodes.len() {
+            if self.nodes[i].y == self.nodes[i + 1].y {
+                self.nodes[i].width += self.nodes[i + 1].width;
+                self.nodes.remove(i + 1);
+            } else {
+                i += 1;
+            }
+        }
+        Some((x, y))
+    }
+}
+
+enum Page {
+    MaxRects(MaxRectsPage),
+    Skyline(SkylinePage),
+}
+
+impl Page {
+    fn new(settings: &AtlasSettings) -> Self {
+        match settings.algorithm {
+            PackingAlgorithm::MaxRects => {
+                Page::MaxRects(MaxRectsPage::new(settings.page_width, settings.page_height))
+            }
+            PackingAlgorithm::Skyline => {
+                Page::Skyline(SkylinePage::new(settings.page_width, settings.page_height))
+            }
+        }
+    }
+
+    fn insert(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+        match self {
+            Page::MaxRects(page) => page.insert(width, height),
+            Page::Skyline(page) => page.insert(width, height),
+        }
+    }
+}
+
+pub struct AtlasPacker {
+    settings: AtlasSettings,
+    page_limit: Option<usize>,
+    pages: Vec<Page>,
+}
+
+impl AtlasPacker {
+    pub fn new(settings: AtlasSettings) -> Self {
+        AtlasPacker {
+            settings,
+            page_limit: None,
+            pages: Vec::new(),
+        }
+    }
+
+    pub fn with_page_limit(mut self, page_limit: usize) -> Self {
+        self.page_limit = Some(page_limit);
+        self
+    }
+
+    pub fn page_count(&self) -> usize {
+        self.pages.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.pages.clear();
+    }
+
+    pub fn insert(&mut self, width: u32, height: u32) -> Option<Placement> {
+        let settings = &self.settings;
+        let border = settings.extrude * 2 + settings.padding;
+        let (width, height) = (width + border, height + border);
+        if width > settings.page_width || height > settings.page_height {
+            return None;
+        }
+        let placed = self
+            .pages
+            .iter_mut()
+            .enumerate()
+            .find_map(|(page, packer)| packer.insert(width, height).map(|p| (page, p)));
+        let (page, (x, y)) = match placed {
+            Some(placed) => placed,
+            None => {
+                if self.page_limit == Some(self.pages.len()) {
+                    return None;
+                }
+                let mut packer = Page::new(settings);
+                let position = packer.insert(width, height)?;
+                self.pages.push(packer);
+                (self.pages.len() - 1, position)
+            }
+        };
+        Some(Placement {
+            page,
+            x: x + settings.extrude,
+            y: y + settings.extrude,
+        })
+    }
+}
+
+pub fn pack(sizes: &[(u32, u32)], settings: &AtlasSettings) -> Option<Vec<Placement>> {
+    let mut order: Vec<usize> = (0..sizes.len()).collect();
+    order.sort_by_key(|&i| {
+        let (w, h) = sizes[i];
+        (std::cmp::Reverse((w.max(h), w * h)), i)
+    });
+
+    let mut packer = AtlasPacker::new(*settings);
+    let mut placements = vec![
+        Placement {
+            page: 0,
+            x: 0,
+            y: 0
+        };
+        sizes.len()
+    ];
+    for i in order {
+        placements[i] = packer.insert(sizes[i].0, sizes[i].1)?;
+    }
+    Some(placements)
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AtlasEntry {
+    pub name: String,
+    pub page: u32,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub uv_min: [f32; 2],
+    pub uv_max: [f32; 2],
+}
+
+impl AtlasEntry {
+    pub fn uv_rect(&self) -> UvRect {
+        UvRect {
+            min: self.uv_min.into(),
+            max: self.uv_max.into(),
+        }
+    }
+
+    pub fn sprite(&self, position: Vector2<f32>) -> Sprite {
+        let mut sprite = Sprite::new(
+            self.page as usize,
+            position,
+            Vector2::new(self.width as f32, self.height as f32),
+        );
+        sprite.uv = self.uv_rect();
+        sprite
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AtlasLayout {
+    pub page_width: u32,
+    pub page_height: u32,
+    pub page_count: u32,
+    pub padding: u32,
+    pub extrude: u32,
+    pub entries: Vec<AtlasEntry>,
+}
+
+impl AtlasLayout {
+    pub fn get(&self, name: &str) -> Option<&AtlasEntry> {
+        self.entries.iter().find(|entry| entry.name == name)
+    }
+
+    pub fn uv_table(&self) -> HashMap<&str, UvRect> {
+        self.entries
+            .iter()
+            .map(|entry| (entry.name.as_str(), entry.uv_rect()))
+            .collect()
+    }
+
+    pub fn efficiency(&self) -> f32 {
+        let used: u64 = self
+            .entries
+            .iter()
+            .map(|entry| entry.width as u64 * entry.height as u64)
+            .sum();
+        let total = self.page_width as u64 * self.page_height as u64 * self.page_count as u64;
+        if total == 0 {
+            return 0.0;
+        }
+        used as f32 / total as f32
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    pub fn to_binary(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&BINARY_MAGIC);
+        for value in [
+            BINARY_VERSION,
+            self.page_width,
+            self.page_height,
+            self.page_count,
+            self.padding,
+            self.extrude,
+            self.entries.len() as u32,
+        ] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        for entry in &self.entries {
+            bytes.extend_from_slice(&(entry.name.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(entry.name.as_bytes());
+            for value in [entry.page, entry.x, entry.y, entry.width, entry.height] {
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+            for value in entry.uv_min.iter().chain(&entry.uv_max) {
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        bytes
+    }
+
+    pub fn from_binary(bytes: &[u8]) -> Result<Self> {
+        let mut reader = BinaryReader::new(bytes);
+        let magic = reader.array::<4>()?;
+        if magic != BINARY_MAGIC {
+            return Err(AtlasError::InvalidMagic(magic));
+        }
+        let version = reader.u32()?;
+        if version != BINARY_VERSION {
+            return Err(AtlasError::UnsupportedVersion(version));
+        }
+        let page_width = reader.u32()?;
+        let page_height = reader.u32()?;
+        let page_count = reader.u32()?;
+        let padding = reader.u32()?;
+        let extrude = reader.u32()?;
+        let count = reader.u32()?;
+        let entries = (0..count)
+            .map(|_| {
+                let len = reader.u32()? as usize;
+                let name = std::str::from_utf8(reader.bytes(len)?)
+                    .map_err(|_| AtlasError::InvalidName)?
+                    .to_string();
+                Ok(AtlasEntry {
+                    name,
+                    page: reader.u32()?,
+                    x: reader.u32()?,
+                    y: reader.u32()?,
+                    width: reader.u32()?,
+                    height: reader.u32()?,
+                    uv_min: [reader.f32()?, reader.f32()?],
+                    uv_max: [reader.f32()?, reader.f32()?],
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(AtlasLayout {
+            page_width,
+            page_height,
+            page_count,
+            padding,
+            extrude,
+            entries,
+        })
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>, format: SidecarFormat) -> Result<()> {
+        let path = path.as_ref();
+        let bytes = match format {
+            SidecarFormat::Json => self.to_json()?.into_bytes(),
+            SidecarFormat::Binary => self.to_binary(),
+        };
+        std::fs::write(path, bytes).map_err(|error| AtlasError::Io {
+            path: path.to_path_buf(),
+            error,
+        })
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path).map_err(|error| AtlasError::Io {
+            path: path.to_path_buf(),
+            error,
+        })?;
+        if bytes.starts_with(&BINARY_MAGIC) {
+            Self::from_binary(&bytes)
+        } else {
+            Self::from_json(&String::from_utf8_lossy(&bytes))
+        }
+    }
+}
+
+pub struct Atlas {
+    pub pages: Vec<RgbaImage>,
+    pub layout: AtlasLayout,
+}
+
+impl Atlas {
+    pub fn build(images: &[(String, RgbaImage)], settings: &AtlasSettings) -> Result<Self> {
+        let sizes: Vec<(u32, u32)> = images.iter().map(|(_, image)| image.dimensions()).collect();
+        let placements = pack(&sizes, settings).ok_or_else(|| {
+            let border = settings.extrude * 2 + settings.padding;
+            let (name, image) = images
+                .iter()
+                .find(|(_, image)| {
+                    image.width() + border > settings.page_width
+                        || image.height() + border > settings.page_height
+                })
+                .unwrap_or(&images[0]);
+            AtlasError::ImageTooLarge {
+                name: name.clone(),
+                width: image.width(),
+                height: image.height(),
+            }
+        })?;
+
+        let page_count = placements.iter().map(|p| p.page + 1).max().unwrap_or(0);
+        let mut pages = vec![
+            RgbaImage::from_pixel(
+                settings.page_width,
+                settings.page_height,
+                Rgba([0, 0, 0, 0])
+            );
+            page_count
+        ];
+        let (page_width, page_height) = (settings.page_width as f32, settings.page_height as f32);
+        let mut entries = Vec::with_capacity(images.len());
+        for ((name, image), placement) in images.iter().zip(&placements) {
+            blit_extruded(
+                &mut pages[placement.page],
+                image,
+                placement.x,
+                placement.y,
+                settings.extrude,
+            );
+            entries.push(AtlasEntry {
+                name: name.clone(),
+                page: placement.page as u32,
+                x: placement.x,
+                y: placement.y,
+                width: image.width(),
+                height: image.height(),
+                uv_min: [
+                    placement.x as f32 / page_width,
+                    placement.y as f32 / page_height,
+                ],
+                uv_max: [
+                    (placement.x + image.width()) as f32 / page_width,
+                    (placement.y + image.height()) as f32 / page_height,
+                ],
+            });
+        }
+
+        Ok(Atlas {
+            pages,
+            layout: AtlasLayout {
+                page_width: settings.page_width,
+                page_height: settings.page_height,
+                page_count: page_count as u32,
+                padding: settings.padding,
+                extrude: settings.extrude,
+                entries,
+            },
+        })
+    }
+
+    pub fn load_images(paths: &[PathBuf], settings: &AtlasSettings) -> Result<Self> {
+        let images = paths
+            .iter()
+            .map(|path| {
+                let image = image::open(path).map_err(|error| AtlasError::Image {
+                    path: path.clone(),
+                    error,
+                })?;
+                let name = path
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                Ok((name, image.to_rgba8()))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Self::build(&images, settings)
+    }
+
+    pub fn save(&self, base: impl AsRef<Path>, format: SidecarFormat) -> Result<()> {
+        let base = base.as_ref();
+        for (i, page) in self.pages.iter().enumerate() {
+            let path = base.with_file_name(format!(
+                "{}_{}.png",
+                base.file_name().unwrap_or_default().to_string_lossy(),
+                i
+            ));
+            page.save(&path)
+                .map_err(|error| AtlasError::Image { path, error })?;
+        }
+        self.layout
+            .save(base.with_extension(format.extension()), format)
+    }
+}
+
+fn blit_extruded(page: &mut RgbaImage, image: &RgbaImage, x: u32, y: u32, extrude: u32) {
+    if image.width() == 0 || image.height() == 0 {
+        return;
+    }
+    let extrude = extrude as i64;
+    for dy in -extrude..image.height() as i64 + extrude {
+        for dx in -extrude..image.width() as i64 + extrude {
+            let sx = dx.clamp(0, image.width() as i64 - 1) as u32;
+            let sy = dy.clamp(0, image.height() as i64 - 1) as u32;
+            page.put_pixel(
+                (x as i64 + dx) as u32,
+                (y as i64 + dy) as u32,
+                *image.get_pixel(sx, sy),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALGORITHMS: [PackingAlgorithm; 2] =
+        [PackingAlgorithm::MaxRects, PackingAlgorithm::Skyline];
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures/atlas")
+            .join(name)
+    }
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("atlas_{}_{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn settings(
+        algorithm: PackingAlgorithm,
+        size: u32,
+        padding: u32,
+        extrude: u32,
+    ) -> AtlasSettings {
+        AtlasSettings {
+            page_width: size,
+            page_height: size,
+            padding,
+            extrude,
+            algorithm,
+        }
+    }
+
+    /// A deterministic mix of wide, tall and square images.
+    fn mixed_sizes() -> Vec<(u32, u32)> {
+        (0..40u32)
+            .map(|i| (4 + (i * 37) % 29, 4 + (i * 53) % 23))
+            .collect()
+    }
+
+    /// The rectangle an image occupies on its page, including extrusion and padding.
+    fn footprint(placement: &Placement, size: (u32, u32), settings: &AtlasSettings) -> Rect {
+        let border = settings.extrude * 2 + settings.padding;
+        Rect {
+            x: placement.x - settings.extrude,
+            y: placement.y - settings.extrude,
+            width: size.0 + border,
+            height: size.1 + border,
+        }
+    }
+
+    fn assert_valid_packing(
+        sizes: &[(u32, u32)],
+        placements: &[Placement],
+        settings: &AtlasSettings,
+    ) {
+        let page = Rect {
+            x: 0,
+            y: 0,
+            width: settings.page_width,
+            height: settings.page_height,
+        };
+        let rects: Vec<(usize, Rect)> = placements
+            .iter()
+            .zip(sizes)
+            .map(|(p, &size)| (p.page, footprint(p, size, settings)))
+            .collect();
+        for (i, (page_a, a)) in rects.iter().enumerate() {
+            assert!(page.contains(a), "{:?} leaves the page", a);
+            for (page_b, b) in &rects[i + 1..] {
+                assert!(
+                    page_a != page_b || !a.intersects(b),
+                    "{:?} overlaps {:?}",
+                    a,
+                    b
+                );
+            }
+        }
+    }
+
+    fn image(width: u32, height: u32, seed: u8) -> RgbaImage {
+        RgbaImage::from_fn(width, height, |x, y| Rgba([seed, x as u8, y as u8, 255]))
+    }
+
+    fn sample_layout() -> AtlasLayout {
+        AtlasLayout {
+            page_width: 128,
+            page_height: 128,
+            page_count: 2,
+            padding: 2,
+            extrude: 1,
+            entries: vec![
+                AtlasEntry {
+                    name: "player".to_string(),
+                    page: 0,
+                    x: 1,
+                    y: 1,
+                    width: 32,
+                    height: 48,
+                    uv_min: [1. / 128., 1. / 128.],
+                    uv_max: [33. / 128., 49. / 128.],
+                },
+                AtlasEntry {
+                    name: "コイン".to_string(),
+                    page: 1,
+                    x: 36,
+                    y: 1,
+                    width: 16,
+                    height: 16,
+                    uv_min: [36. / 128., 1. / 128.],
+                    uv_max: [52. / 128., 17. / 128.],
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn packs_without_overlap() {
+        let sizes = mixed_sizes();
+        for algorithm in ALGORITHMS {
+            let settings = settings(algorithm, 128, 2, 1);
+            let placements = pack(&sizes, &settings).unwrap();
+            assert_valid_packing(&sizes, &placements, &settings);
+        }
+    }
+
+    #[test]
+    fn packing_is_deterministic() {
+        let sizes = mixed_sizes();
+        for algorithm in ALGORITHMS {
+            let settings = settings(algorithm, 96, 1, 1);
+            let first = pack(&sizes, &settings).unwrap();
+            assert!(first.iter().any(|p| p.page > 0));
+            assert_eq!(pack(&sizes, &settings).unwrap(), first);
+        }
+    }
+
+    #[test]
+    fn fills_a_page_with_equal_tiles() {
+        let sizes = vec![(32, 32); 64];
+        for algorithm in ALGORITHMS {
+            let settings = settings(algorithm, 256, 0, 0);
+            let placements = pack(&sizes, &settings).unwrap();
+            assert!(placements.iter().all(|p| p.page == 0), "{:?}", algorithm);
+            assert_valid_packing(&sizes, &placements, &settings);
+        }
+    }
+
+    #[test]
+    fn overflows_onto_new_pages() {
+        let sizes = vec![(40, 40); 10];
+        for algorithm in ALGORITHMS {
+            let settings = settings(algorithm, 64, 0, 0);
+            let placements = pack(&sizes, &settings).unwrap();
+            let pages: Vec<usize> = placements.iter().map(|p| p.page).collect();
+            assert_eq!(pages, (0..10).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn rejects_images_larger_than_a_page() {
+        for algorithm in ALGORITHMS {
+            // 62 + 2 * 1 + 2 exceeds the 64 pixel page once the border is added.
+            let settings = settings(algorithm, 64, 2, 1);
+            assert_eq!(pack(&[(8, 8), (62, 10)], &settings), None);
+            assert!(pack(&[(8, 8), (60, 10)], &settings).is_some());
+        }
+    }
+
+    #[test]
+    fn page_limit_stops_new_pages() {
+        for algorithm in ALGORITHMS {
+            let mut packer = AtlasPacker::new(settings(algorithm, 64, 0, 0)).with_page_limit(1);
+            assert!(packer.insert(40, 40).is_some());
+            assert_eq!(packer.insert(40, 40), None);
+            assert!(packer.insert(20, 20).is_some());
+            assert_eq!(packer.page_count(), 1);
+            packer.clear();
+            assert_eq!(packer.page_count(), 0);
+        }
+    }
+
+    #[test]
+    fn placements_skip_the_extruded_border() {
+        for algorithm in ALGORITHMS {
+            let mut packer = AtlasPacker::new(settings(algorithm, 64, 2, 3));
+            assert_eq!(
+                packer.insert(10, 10),
+                Some(Placement {
+                    page: 0,
+                    x: 3,
+                    y: 3
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn build_copies_images_with_extruded_edges() {
+        let images = vec![
+            ("a".to_string(), image(6, 4, 10)),
+            ("b".to_string(), image(3, 5, 20)),
+        ];
+        let atlas = Atlas::build(&images, &settings(PackingAlgorithm::MaxRects, 32, 1, 2)).unwrap();
+        assert_eq!(atlas.pages.len(), 1);
+        assert_eq!(atlas.layout.page_count, 1);
+        for ((name, source), entry) in images.iter().zip(&atlas.layout.entries) {
+            assert_eq!(&entry.name, name);
+            assert_eq!((entry.width, entry.height), source.dimensions());
+            let page = &atlas.pages[entry.page as usize];
+            for (x, y, pixel) in source.enumerate_pixels() {
+                assert_eq!(page.get_pixel(entry.x + x, entry.y + y), pixel);
+            }
+            let right = entry.x + entry.width - 1;
+            let bottom = entry.y + entry.height - 1;
+            assert_eq!(
+                page.get_pixel(entry.x - 2, entry.y - 2),
+                source.get_pixel(0, 0)
+            );
+            assert_eq!(
+                page.get_pixel(right + 2, entry.y),
+                source.get_pixel(entry.width - 1, 0)
+            );
+            assert_eq!(
+                page.get_pixel(entry.x, bottom + 1),
+                source.get_pixel(0, entry.height - 1)
+            );
+            assert_eq!(entry.uv_min, [entry.x as f32 / 32., entry.y as f32 / 32.]);
+            assert_eq!(
+                entry.uv_max,
+                [(right + 1) as f32 / 32., (bottom + 1) as f32 / 32.]
+            );
+        }
+    }
+
+    #[test]
+    fn build_reports_oversized_images() {
+        let images = vec![
+            ("small".to_string(), image(4, 4, 0)),
+            ("huge".to_string(), image(40, 4, 0)),
+        ];
+        match Atlas::build(&images, &settings(PackingAlgorithm::Skyline, 32, 0, 0)) {
+            Err(AtlasError::ImageTooLarge {
+                name,
+                width,
+                height,
+            }) => assert_eq!((name.as_str(), width, height), ("huge", 40, 4)),
+            other => panic!("expected ImageTooLarge, got {:?}", other.map(|a| a.layout)),
+        }
+    }
+
+    #[test]
+    fn efficiency_measures_used_area() {
+        let images: Vec<(String, RgbaImage)> = (0..16)
+            .map(|i| (format!("tile{}", i), image(16, 16, i as u8)))
+            .collect();
+        let atlas = Atlas::build(&images, &settings(PackingAlgorithm::Skyline, 64, 0, 0)).unwrap();
+        assert_eq!(atlas.layout.efficiency(), 1.0);
+        let atlas = Atlas::build(
+            &images[..4],
+            &settings(PackingAlgorithm::MaxRects, 64, 0, 0),
+        )
+        .unwrap();
+        assert_eq!(atlas.layout.efficiency(), 0.25);
+        let empty = Atlas::build(&[], &AtlasSettings::default()).unwrap();
+        assert_eq!(empty.layout.efficiency(), 0.0);
+    }
+
+    #[test]
+    fn lookups_return_uv_rects_and_sprites() {
+        let layout = sample_layout();
+        let table = layout.uv_table();
+        assert_eq!(table.len(), 2);
+        assert_eq!(table["コイン"].min, Vector2::new(36. / 128., 1. / 128.));
+        assert_eq!(layout.get("missing"), None);
+
+        let sprite = layout.get("player").unwrap().sprite(Vector2::new(5., 6.));
+        assert_eq!(sprite.texture, 0);
+        assert_eq!(sprite.size, Vector2::new(32., 48.));
+        assert_eq!(sprite.uv, layout.entries[0].uv_rect());
+        assert_eq!(
+            layout
+                .get("コイン")
+                .unwrap()
+                .sprite(Vector2::new(0., 0.))
+                .texture,
+            1
+        );
+    }
+
+    #[test]
+    fn sidecars_round_trip() {
+        let layout = sample_layout();
+        assert_eq!(
+            AtlasLayout::from_json(&layout.to_json().unwrap()).unwrap(),
+            layout
+        );
+        assert_eq!(
+            AtlasLayout::from_binary(&layout.to_binary()).unwrap(),
+            layout
+        );
+
+        let dir = scratch_dir("sidecar");
+        for format in [SidecarFormat::Json, SidecarFormat::Binary] {
+            let path = dir.join("layout").with_extension(format.extension());
+            layout.save(&path, format).unwrap();
+            assert_eq!(AtlasLayout::load(&path).unwrap(), layout);
+        }
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn loads_sidecar_fixtures() {
+        let layout = sample_layout();
+        assert_eq!(AtlasLayout::load(fixture("layout.json")).unwrap(), layout);
+        assert_eq!(AtlasLayout::load(fixture("layout.atlas")).unwrap(), layout);
+        assert_eq!(
+            layout.to_binary(),
+            std::fs::read(fixture("layout.atlas")).unwrap()
+        );
+    }
+
+    #[test]
+    fn atlas_save_writes_pages_and_sidecar() {
+        let images = vec![
+            ("a".to_string(), image(8, 8, 1)),
+            ("b".to_string(), image(8, 8, 2)),
+        ];
+        let atlas = Atlas::build(&images, &settings(PackingAlgorithm::MaxRects, 12, 0, 0)).unwrap();
+        assert_eq!(atlas.pages.len(), 2);
+
+        let dir = scratch_dir("save");
+        let base = dir.join("sprites");
+        atlas.save(&base, SidecarFormat::Binary).unwrap();
+        let layout = AtlasLayout::load(dir.join("sprites.atlas")).unwrap();
+        assert_eq!(layout, atlas.layout);
+        for (i, page) in atlas.pages.iter().enumerate() {
+            let saved = image::open(dir.join(format!("sprites_{}.png", i))).unwrap();
+            assert_eq!(&saved.to_rgba8(), page);
+        }
+
+        let reloaded = Atlas::load_images(
+            &[dir.join("sprites_0.png"), dir.join("sprites_1.png")],
+            &settings(PackingAlgorithm::MaxRects, 16, 0, 0),
+        )
+        .unwrap();
+        let names: Vec<&str> = reloaded
+            .layout
+            .entries
+            .iter()
+            .map(|e| e.name.as_str())
+            .collect();
+        assert_eq!(names, ["sprites_0", "sprites_1"]);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn rejects_malformed_binary_sidecars() {
+        let bytes = sample_layout().to_binary();
+        assert!(matches!(
+            AtlasLayout::from_binary(b"NOPE"),
+            Err(AtlasError::InvalidMagic(magic)) if &magic == b"NOPE"
+        ));
+
+        let mut future = bytes.clone();
+        future[4..8].copy_from_slice(&2u32.to_le_bytes());
+        assert!(matches!(
+            AtlasLayout::from_binary(&future),
+            Err(AtlasError::UnsupportedVersion(2))
+        ));
+
+        assert!(matches!(
+            AtlasLayout::from_binary(&bytes[..bytes.len() - 1]),
+            Err(AtlasError::UnexpectedEof(_))
+        ));
+
+        // The first entry name starts after the 32 byte header and its length.
+        let mut invalid = bytes.clone();
+        invalid[36] = 0xff;
+        assert!(matches!(
+            AtlasLayout::from_binary(&invalid),
+            Err(AtlasError::InvalidName)
+        ));
+
+        assert!(matches!(
+            AtlasLayout::from_json("{}"),
+            Err(AtlasError::Json(_))
+        ));
+        assert!(matches!(
+            AtlasLayout::load(fixture("missing.json")),
+            Err(AtlasError::Io { .. })
+        ));
+    }
+}
@@ -0,0 +1,120 @@
+use cgmath::{Vector2, Vector3, Vector4};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnexpectedEof {
+    pub offset: usize,
+    pub needed: usize,
+}
+
+impl std::fmt::Display for UnexpectedEof {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "unexpected end of data at offset {} (needed {} more bytes)",
+            self.offset, self.needed
+        )
+    }
+}
+
+impl std::error::Error for UnexpectedEof {}
+
+pub struct BinaryReader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> BinaryReader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        BinaryReader { bytes, offset: 0 }
+    }
+
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.bytes.len() - self.offset
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.remaining() == 0
+    }
+
+    pub fn bytes(&mut self, len: usize) -> Result<&'a [u8], UnexpectedEof> {
+        if len > self.remaining() {
+            return Err(UnexpectedEof {
+                offset: self.offset,
+                needed: len - self.remaining(),
+            });
+        }
+        let bytes = &self.bytes[self.offset..self.offset + len];
+        self.offset += len;
+        Ok(bytes)
+    }
+
+    pub fn skip(&mut self, len: usize) -> Result<(), UnexpectedEof> {
+        self.bytes(len).map(|_| ())
+    }
+
+    pub fn array<const N: usize>(&mut self) -> Result<[u8; N], UnexpectedEof> {
+        Ok(self.bytes(N)?.try_into().unwrap())
+    }
+
+    pub fn u8(&mut self) -> Result<u8, UnexpectedEof> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    pub fn i8(&mut self) -> Result<i8, UnexpectedEof> {
+        Ok(i8::from_le_bytes(self.array()?))
+    }
+
+    pub fn u16(&mut self) -> Result<u16, UnexpectedEof> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+
+    pub fn i16(&mut self) -> Result<i16, UnexpectedEof> {
+        Ok(i16::from_le_bytes(self.array()?))
+    }
+
+    pub fn u32(&mut self) -> Result<u32, UnexpectedEof> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    pub fn i32(&mut self) -> Result<i32, UnexpectedEof> {
+        Ok(i32::from_le_bytes(self.array()?))
+    }
+
+    pub fn f32(&mut self) -> Result<f32, UnexpectedEof> {
+        Ok(f32::from_le_bytes(self.array()?))
+    }
+
+    pub fn vector2(&mut self) -> Result<Vector2<f32>, UnexpectedEof> {
+        Ok(Vector2::new(self.f32()?, self.f32()?))
+    }
+
+    pub fn vector3(&mut self) -> Result<Vector3<f32>, UnexpectedEof> {
+        Ok(Vector3::new(self.f32()?, self.f32()?, self.f32()?))
+    }
+
+    pub fn vector4(&mut self) -> Result<Vector4<f32>, UnexpectedEof> {
+        Ok(Vector4::new(
+            self.f32()?,
+            self.f32()?,
+            self.f32()?,
+            self.f32()?,
+        ))
+    }
+
+    pub fn fixed_str(&mut self, len: usize) -> Result<&'a [u8], UnexpectedEof> {
+        let bytes = self.bytes(len)?;
+        let end = bytes.iter().position(|&b| b == 0).unwrap_or(len);
+        Ok(&bytes[..end])
+    }
+}
+
+pub fn decode_shift_jis(bytes: &[u8]) -> String {
+    encoding_rs::SHIFT_JIS
+        .decode_without_bom_handling(bytes)
+        .0
+        .into_owned()
+}
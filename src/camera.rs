@@ -0,0 +1,477 @@
+use cgmath::{
+    Deg, EuclideanSpace, InnerSpace, Matrix, Matrix4, Point3, Rad, Vector3, Vector4, Zero,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    Perspective { fovy: Rad<f32> },
+    Orthographic { height: f32 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Camera {
+    pub eye: Point3<f32>,
+    pub target: Point3<f32>,
+    pub up: Vector3<f32>,
+    pub projection: Projection,
+    pub aspect: f32,
+    pub near: f32,
+    pub far: f32,
+    pub reverse_z: bool,
+}
+
+impl Camera {
+    pub fn perspective(
+        eye: Point3<f32>,
+        target: Point3<f32>,
+        fovy: impl Into<Rad<f32>>,
+        aspect: f32,
+        near: f32,
+        far: f32,
+    ) -> Self {
+        Camera {
+            eye,
+            target,
+            up: Vector3::unit_y(),
+            projection: Projection::Perspective { fovy: fovy.into() },
+            aspect,
+            near,
+            far,
+            reverse_z: false,
+        }
+    }
+
+    pub fn orthographic(
+        eye: Point3<f32>,
+        target: Point3<f32>,
+        height: f32,
+        aspect: f32,
+        near: f32,
+        far: f32,
+    ) -> Self {
+        Camera {
+            eye,
+            target,
+            up: Vector3::unit_y(),
+            projection: Projection::Orthographic { height },
+            aspect,
+            near,
+            far,
+            reverse_z: false,
+        }
+    }
+
+    pub fn set_viewport(&mut self, width: u32, height: u32) {
+        if width > 0 && height > 0 {
+            self.aspect = width as f32 / height as f32;
+        }
+    }
+
+    pub fn forward(&self) -> Vector3<f32> {
+        (self.target - self.eye).normalize()
+    }
+
+    pub fn view(&self) -> Matrix4<f32> {
+        Matrix4::look_at_lh(self.eye, self.target, self.up)
+    }
+
+    pub fn projection(&self) -> Matrix4<f32> {
+        match self.projection {
+            Projection::Perspective { fovy } => {
+                perspective_lh(fovy, self.aspect, self.near, self.far, self.reverse_z)
+            }
+            Projection::Orthographic { height } => orthographic_lh(
+                height * self.aspect,
+                height,
+                self.near,
+                self.far,
+                self.reverse_z,
+            ),
+        }
+    }
+
+    pub fn view_projection(&self) -> Matrix4<f32> {
+        self.projection() * self.view()
+    }
+
+    pub fn frustum(&self) -> Frustum {
+        Frustum::from_matrix(&self.view_projection(), self.reverse_z)
+    }
+
+    pub fn projected_size(&self, center: Point3<f32>, radius: f32, viewport_height: f32) -> f32 {
+        match self.projection {
+            Projection::Perspective { fovy } => {
+                let distance = (center - self.eye).magnitude();
+                if distance <= radius {
+                    return f32::INFINITY;
+                }
+                radius / (distance * (fovy.0 * 0.5).tan()) * viewport_height
+            }
+            Projection::Orthographic { height } => radius * 2.0 / height * viewport_height,
+        }
+    }
+
+    pub fn depth_clear_value(&self) -> f32 {
+        if self.reverse_z {
+            0.0
+        } else {
+            1.0
+        }
+    }
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Camera::perspective(
+            Point3::new(0.0, 0.0, -5.0),
+            Point3::origin(),
+            Deg(45.0),
+            16.0 / 9.0,
+            0.1,
+            1000.0,
+        )
+    }
+}
+
+pub fn perspective_lh(
+    fovy: impl Into<Rad<f32>>,
+    aspect: f32,
+    near: f32,
+    far: f32,
+    reverse_z: bool,
+) -> Matrix4<f32> {
+    let h = 1.0 / (fovy.into().0 * 0.5).tan();
+    let w = h / aspect;
+
+    let (z_scale, z_offset) = match (far.is_infinite(), reverse_z) {
+        (false, false) => {
+            let range = far / (far - near);
+            (range, -near * range)
+        }
+        (false, true) => {
+            let range = near / (near - far);
+            (range, -far * range)
+        }
+        (true, false) => (1.0, -near),
+        (true, true) => (0.0, near),
+    };
+
+    #[rustfmt::skip]
+    let m = Matrix4::new(
+        w, 0.0, 0.0, 0.0,
+        0.0, h, 0.0, 0.0,
+        0.0, 0.0, z_scale, 1.0,
+        0.0, 0.0, z_offset, 0.0,
+    );
+    m
+}
+
+pub fn orthographic_lh(
+    width: f32,
+    height: f32,
+    near: f32,
+    far: f32,
+    reverse_z: bool,
+) -> Matrix4<f32> {
+    let (near, far) = if reverse_z { (far, near) } else { (near, far) };
+    let range = 1.0 / (far - near);
+
+    #[rustfmt::skip]
+    let m = Matrix4::new(
+        2.0 / width, 0.0, 0.0, 0.0,
+        0.0, 2.0 / height, 0.0, 0.0,
+        0.0, 0.0, range, 0.0,
+        0.0, 0.0, -near * range, 1.0,
+    );
+    m
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Plane {
+    pub normal: Vector3<f32>,
+    pub d: f32,
+}
+
+impl Plane {
+    fn from_row(row: Vector4<f32>) -> Self {
+        let normal = row.truncate();
+        let length = normal.magnitude();
+        if length > f32::EPSILON {
+            Plane {
+                normal: normal / length,
+                d: row.w / length,
+            }
+        } else {
+            Plane {
+                normal: Vector3::zero(),
+                d: row.w,
+            }
+        }
+    }
+
+    pub fn distance(&self, point: Point3<f32>) -> f32 {
+        self.normal.dot(point.to_vec()) + self.d
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frustum {
+    pub left: Plane,
+    pub right: Plane,
+    pub bottom: Plane,
+    pub top: Plane,
+    pub near: Plane,
+    pub far: Plane,
+}
+
+impl Frustum {
+    /// Extracts the planes of a view-projection matrix. With `reverse_z` the
+    /// near plane maps to depth 1 and the far plane to depth 0.
+    pub fn from_matrix(m: &Matrix4<f32>, reverse_z: bool) -> Self {
+        let r0 = m.row(0);
+        let r1 = m.row(1);
+        let r2 = m.row(2);
+        let r3 = m.row(3);
+        let (depth_zero, depth_one) = (Plane::from_row(r2), Plane::from_row(r3 - r2));
+        let (near, far) = if reverse_z {
+            (depth_one, depth_zero)
+        } else {
+            (depth_zero, depth_one)
+        };
+        Frustum {
+            left: Plane::from_row(r3 + r0),
+            right: Plane::from_row(r3 - r0),
+            bottom: Plane::from_row(r3 + r1),
+            top: Plane::from_row(r3 - r1),
+            near,
+            far,
+        }
+    }
+
+    pub fn planes(&self) -> [Plane; 6] {
+        [
+            self.left,
+            self.right,
+            self.bottom,
+            self.top,
+            self.near,
+            self.far,
+        ]
+    }
+
+    pub fn contains_point(&self, point: Point3<f32>) -> bool {
+        self.planes().iter().all(|p| p.distance(point) >= 0.0)
+    }
+
+    pub fn intersects_sphere(&self, center: Point3<f32>, radius: f32) -> bool {
+        self.planes().iter().all(|p| p.distance(center) >= -radius)
+    }
+
+    pub fn intersects_aabb(&self, min: Point3<f32>, max: Point3<f32>) -> bool {
+        self.planes().iter().all(|p| {
+            let positive = Point3::new(
+                if p.normal.x >= 0.0 { max.x } else { min.x },
+                if p.normal.y >= 0.0 { max.y } else { min.y },
+                if p.normal.z >= 0.0 { max.z } else { min.z },
+            );
+            p.distance(positive) >= 0.0
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{Deg, Point3, Transform};
+
+    use super::*;
+
+    const EPSILON: f32 = 1e-4;
+
+    fn ndc(m: &Matrix4<f32>, point: Point3<f32>) -> Point3<f32> {
+        m.transform_point(point)
+    }
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < EPSILON,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn perspective_maps_near_and_far_to_unit_depth() {
+        let m = perspective_lh(Deg(90.0), 2.0, 1.0, 100.0, false);
+        assert_close(ndc(&m, Point3::new(0.0, 0.0, 1.0)).z, 0.0);
+        assert_close(ndc(&m, Point3::new(0.0, 0.0, 100.0)).z, 1.0);
+
+        let edge = ndc(&m, Point3::new(20.0, 10.0, 10.0));
+        assert_close(edge.x, 1.0);
+        assert_close(edge.y, 1.0);
+    }
+
+    #[test]
+    fn reverse_z_perspective_swaps_depth_range() {
+        let m = perspective_lh(Deg(60.0), 1.0, 0.5, 50.0, true);
+        assert_close(ndc(&m, Point3::new(0.0, 0.0, 0.5)).z, 1.0);
+        assert_close(ndc(&m, Point3::new(0.0, 0.0, 50.0)).z, 0.0);
+        let mid = ndc(&m, Point3::new(0.0, 0.0, 5.0)).z;
+        assert!(mid > 0.0 && mid < 1.0);
+    }
+
+    #[test]
+    fn infinite_far_plane_approaches_limit() {
+        let m = perspective_lh(Deg(60.0), 1.0, 0.1, f32::INFINITY, false);
+        assert_close(ndc(&m, Point3::new(0.0, 0.0, 0.1)).z, 0.0);
+        assert!(ndc(&m, Point3::new(0.0, 0.0, 1.0e6)).z > 0.999);
+
+        let m = perspective_lh(Deg(60.0), 1.0, 0.1, f32::INFINITY, true);
+        assert_close(ndc(&m, Point3::new(0.0, 0.0, 0.1)).z, 1.0);
+        assert!(ndc(&m, Point3::new(0.0, 0.0, 1.0e6)).z < 0.001);
+    }
+
+    #[test]
+    fn orthographic_is_linear_in_depth() {
+        let m = orthographic_lh(8.0, 4.0, 1.0, 11.0, false);
+        let corner = ndc(&m, Point3::new(4.0, -2.0, 1.0));
+        assert_close(corner.x, 1.0);
+        assert_close(corner.y, -1.0);
+        assert_close(corner.z, 0.0);
+        assert_close(ndc(&m, Point3::new(0.0, 0.0, 6.0)).z, 0.5);
+        assert_close(ndc(&m, Point3::new(0.0, 0.0, 11.0)).z, 1.0);
+
+        let m = orthographic_lh(8.0, 4.0, 1.0, 11.0, true);
+        assert_close(ndc(&m, Point3::new(0.0, 0.0, 1.0)).z, 1.0);
+        assert_close(ndc(&m, Point3::new(0.0, 0.0, 11.0)).z, 0.0);
+    }
+
+    #[test]
+    fn camera_projection_follows_mode() {
+        let mut camera = Camera::orthographic(
+            Point3::new(0.0, 0.0, -10.0),
+            Point3::origin(),
+            4.0,
+            2.0,
+            1.0,
+            21.0,
+        );
+        assert_eq!(
+            camera.projection(),
+            orthographic_lh(8.0, 4.0, 1.0, 21.0, false)
+        );
+        assert_close(camera.projected_size(Point3::origin(), 1.0, 100.0), 50.0);
+        assert_eq!(camera.depth_clear_value(), 1.0);
+
+        camera.reverse_z = true;
+        assert_eq!(camera.depth_clear_value(), 0.0);
+        let origin = ndc(&camera.view_projection(), Point3::origin());
+        assert_close(origin.z, 0.55);
+
+        camera.set_viewport(0, 10);
+        assert_eq!(camera.aspect, 2.0);
+        camera.set_viewport(300, 100);
+        assert_eq!(camera.aspect, 3.0);
+    }
+
+    #[test]
+    fn view_looks_down_positive_z() {
+        let camera = Camera::default();
+        let view = camera.view();
+        let target = view.transform_point(camera.target);
+        assert_close(target.x, 0.0);
+        assert_close(target.y, 0.0);
+        assert_close(target.z, 5.0);
+        assert_eq!(camera.forward(), Vector3::unit_z());
+    }
+
+    fn test_frustums() -> [Frustum; 3] {
+        let mut camera = Camera::perspective(
+            Point3::origin(),
+            Point3::new(0.0, 0.0, 1.0),
+            Deg(90.0),
+            1.0,
+            1.0,
+            100.0,
+        );
+        let standard = camera.frustum();
+        camera.reverse_z = true;
+        let reversed = camera.frustum();
+        camera.far = f32::INFINITY;
+        let infinite = camera.frustum();
+        [standard, reversed, infinite]
+    }
+
+    #[test]
+    fn frustum_planes_point_inward() {
+        for frustum in test_frustums() {
+            for plane in frustum.planes() {
+                assert!(plane.distance(Point3::new(0.0, 0.0, 10.0)) > 0.0);
+            }
+            assert_close(frustum.left.normal.magnitude(), 1.0);
+            assert_close(frustum.left.distance(Point3::new(-5.0, 0.0, 5.0)), 0.0);
+        }
+    }
+
+    #[test]
+    fn reverse_z_frustum_keeps_near_and_far_apart() {
+        let [standard, reversed, _] = test_frustums();
+        for frustum in [standard, reversed] {
+            // Past the far plane: culled by `far`, still in front of `near`.
+            let beyond = Point3::new(0.0, 0.0, 200.0);
+            assert!(frustum.far.distance(beyond) < 0.0);
+            assert!(frustum.near.distance(beyond) > 0.0);
+            assert!(!frustum.contains_point(beyond));
+            let behind_near = Point3::new(0.0, 0.0, 0.5);
+            assert!(frustum.near.distance(behind_near) < 0.0);
+            assert!(frustum.far.distance(behind_near) > 0.0);
+
+            assert!(frustum.near.distance(Point3::new(0.0, 0.0, 1.01)) > 0.0);
+            assert!(frustum.far.distance(Point3::new(0.0, 0.0, 99.0)) > 0.0);
+            assert!(frustum.far.distance(Point3::new(0.0, 0.0, 101.0)) < 0.0);
+            assert_close(frustum.near.normal.z, 1.0);
+            assert_close(frustum.far.normal.z, -1.0);
+        }
+    }
+
+    #[test]
+    fn frustum_contains_point() {
+        for (i, frustum) in test_frustums().into_iter().enumerate() {
+            assert!(frustum.contains_point(Point3::new(0.0, 0.0, 10.0)));
+            assert!(frustum.contains_point(Point3::new(4.9, -4.9, 5.0)));
+            assert!(!frustum.contains_point(Point3::new(5.1, 0.0, 5.0)));
+            assert!(!frustum.contains_point(Point3::new(0.0, 0.0, 0.5)));
+            assert!(!frustum.contains_point(Point3::new(0.0, 0.0, -10.0)));
+            assert_eq!(frustum.contains_point(Point3::new(0.0, 0.0, 200.0)), i == 2);
+        }
+    }
+
+    #[test]
+    fn frustum_intersects_sphere() {
+        for frustum in test_frustums() {
+            assert!(frustum.intersects_sphere(Point3::new(0.0, 0.0, 10.0), 1.0));
+            assert!(frustum.intersects_sphere(Point3::new(6.0, 0.0, 5.0), 1.0));
+            assert!(!frustum.intersects_sphere(Point3::new(8.0, 0.0, 5.0), 1.0));
+            assert!(frustum.intersects_sphere(Point3::new(0.0, 0.0, 0.5), 1.0));
+            assert!(!frustum.intersects_sphere(Point3::new(0.0, 0.0, -3.0), 1.0));
+        }
+    }
+
+    #[test]
+    fn frustum_intersects_aabb() {
+        for frustum in test_frustums() {
+            assert!(
+                frustum.intersects_aabb(Point3::new(-1.0, -1.0, 9.0), Point3::new(1.0, 1.0, 11.0))
+            );
+            assert!(
+                frustum.intersects_aabb(Point3::new(4.0, -1.0, 4.0), Point3::new(20.0, 1.0, 6.0))
+            );
+            assert!(
+                !frustum.intersects_aabb(Point3::new(6.0, -1.0, 4.0), Point3::new(7.0, 1.0, 5.0))
+            );
+            assert!(
+                !frustum.intersects_aabb(Point3::new(-1.0, -1.0, -5.0), Point3::new(1.0, 1.0, 0.5))
+            );
+        }
+    }
+}
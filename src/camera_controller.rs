@@ -0,0 +1,447 @@
+use std::f32::consts::FRAC_PI_2;
+
+use cgmath::{InnerSpace, Point3, Vector3, Zero};
+use winit::event::{MouseButton, VirtualKeyCode};
+
+use crate::camera::Camera;
+use crate::input::InputState;
+
+const PITCH_LIMIT: f32 = FRAC_PI_2 - 0.01;
+
+pub trait CameraController {
+    fn update(&mut self, input: &InputState, dt: f32, camera: &mut Camera);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KeyBindings {
+    pub forward: VirtualKeyCode,
+    pub backward: VirtualKeyCode,
+    pub left: VirtualKeyCode,
+    pub right: VirtualKeyCode,
+    pub up: VirtualKeyCode,
+    pub down: VirtualKeyCode,
+    pub fast: VirtualKeyCode,
+    pub look: Option<MouseButton>,
+    pub pan: Option<MouseButton>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        KeyBindings {
+            forward: VirtualKeyCode::W,
+            backward: VirtualKeyCode::S,
+            left: VirtualKeyCode::A,
+            right: VirtualKeyCode::D,
+            up: VirtualKeyCode::E,
+            down: VirtualKeyCode::Q,
+            fast: VirtualKeyCode::LShift,
+            look: Some(MouseButton::Right),
+            pan: Some(MouseButton::Middle),
+        }
+    }
+}
+
+impl KeyBindings {
+    fn axis(input: &InputState, positive: VirtualKeyCode, negative: VirtualKeyCode) -> f32 {
+        input.is_key_down(positive) as i32 as f32 - input.is_key_down(negative) as i32 as f32
+    }
+
+    fn movement(&self, input: &InputState) -> Vector3<f32> {
+        Vector3::new(
+            Self::axis(input, self.right, self.left),
+            Self::axis(input, self.up, self.down),
+            Self::axis(input, self.forward, self.backward),
+        )
+    }
+
+    fn looking(&self, input: &InputState) -> bool {
+        self.look.is_none_or(|button| input.is_button_down(button))
+    }
+
+    fn panning(&self, input: &InputState) -> bool {
+        self.pan.is_some_and(|button| input.is_button_down(button))
+    }
+}
+
+fn direction(yaw: f32, pitch: f32) -> Vector3<f32> {
+    Vector3::new(
+        pitch.cos() * yaw.sin(),
+        pitch.sin(),
+        pitch.cos() * yaw.cos(),
+    )
+}
+
+fn yaw_pitch(direction: Vector3<f32>) -> (f32, f32) {
+    let direction = direction.normalize();
+    (
+        direction.x.atan2(direction.z),
+        direction.y.clamp(-1.0, 1.0).asin(),
+    )
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OrbitController {
+    pub bindings: KeyBindings,
+    pub target: Point3<f32>,
+    pub distance: f32,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub rotate_speed: f32,
+    pub pan_speed: f32,
+    pub zoom_speed: f32,
+    pub min_distance: f32,
+    pub max_distance: f32,
+}
+
+impl OrbitController {
+    pub fn new(target: Point3<f32>, distance: f32) -> Self {
+        OrbitController {
+            bindings: KeyBindings {
+                look: Some(MouseButton::Left),
+                ..Default::default()
+            },
+            target,
+            distance,
+            yaw: 0.0,
+            pitch: 0.0,
+            rotate_speed: 0.005,
+            pan_speed: 0.002,
+            zoom_speed: 0.1,
+            min_distance: 0.1,
+            max_distance: 500.0,
+        }
+    }
+
+    pub fn from_camera(camera: &Camera) -> Self {
+        let offset = camera.eye - camera.target;
+        let (yaw, pitch) = yaw_pitch(offset);
+        OrbitController {
+            yaw,
+            pitch,
+            ..Self::new(camera.target, offset.magnitude())
+        }
+    }
+}
+
+impl CameraController for OrbitController {
+    fn update(&mut self, input: &InputState, dt: f32, camera: &mut Camera) {
+        let delta = input.mouse_delta();
+        if self.bindings.panning(input) {
+            let forward = -direction(self.yaw, self.pitch);
+            let right = camera.up.cross(forward).normalize();
+            let up = forward.cross(right);
+            let scale = self.pan_speed * self.distance;
+            self.target += (up * delta.y - right * delta.x) * scale;
+        } else if self.bindings.looking(input) {
+            self.yaw += delta.x * self.rotate_speed;
+            self.pitch =
+                (self.pitch + delta.y * self.rotate_speed).clamp(-PITCH_LIMIT, PITCH_LIMIT);
+        }
+
+        let keys = self.bindings.movement(input);
+        self.yaw += keys.x * dt;
+        self.pitch = (self.pitch + keys.y * dt).clamp(-PITCH_LIMIT, PITCH_LIMIT);
+
+        let zoom = input.scroll_delta() + keys.z * dt * 10.0;
+        self.distance = (self.distance * (1.0 - zoom * self.zoom_speed))
+            .clamp(self.min_distance, self.max_distance);
+
+        camera.target = self.target;
+        camera.eye = self.target + direction(self.yaw, self.pitch) * self.distance;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FlyController {
+    pub bindings: KeyBindings,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub speed: f32,
+    pub fast_multiplier: f32,
+    pub sensitivity: f32,
+}
+
+impl FlyController {
+    pub fn new(yaw: f32, pitch: f32) -> Self {
+        FlyController {
+            bindings: KeyBindings::default(),
+            yaw,
+            pitch,
+            speed: 5.0,
+            fast_multiplier: 4.0,
+            sensitivity: 0.003,
+        }
+    }
+
+    pub fn from_camera(camera: &Camera) -> Self {
+        let (yaw, pitch) = yaw_pitch(camera.forward());
+        Self::new(yaw, pitch)
+    }
+
+    fn look(&mut self, input: &InputState) {
+        if self.bindings.looking(input) {
+            let delta = input.mouse_delta() * self.sensitivity;
+            self.yaw += delta.x;
+            self.pitch = (self.pitch - delta.y).clamp(-PITCH_LIMIT, PITCH_LIMIT);
+        }
+        self.speed = (self.speed * (1.0 + input.scroll_delta() * 0.1)).max(0.01);
+    }
+
+    fn step(&self, input: &InputState, dt: f32) -> f32 {
+        if input.is_key_down(self.bindings.fast) {
+            self.speed * self.fast_multiplier * dt
+        } else {
+            self.speed * dt
+        }
+    }
+}
+
+impl CameraController for FlyController {
+    fn update(&mut self, input: &InputState, dt: f32, camera: &mut Camera) {
+        self.look(input);
+
+        let forward = direction(self.yaw, self.pitch);
+        let right = camera.up.cross(forward).normalize();
+        let keys = self.bindings.movement(input);
+        let movement = right * keys.x + camera.up * keys.y + forward * keys.z;
+        if movement != Vector3::zero() {
+            camera.eye += movement.normalize() * self.step(input, dt);
+        }
+        camera.target = camera.eye + forward;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FirstPersonController {
+    pub fly: FlyController,
+    pub eye_height: f32,
+}
+
+impl FirstPersonController {
+    pub fn new(yaw: f32, pitch: f32, eye_height: f32) -> Self {
+        FirstPersonController {
+            fly: FlyController::new(yaw, pitch),
+            eye_height,
+        }
+    }
+
+    pub fn from_camera(camera: &Camera) -> Self {
+        FirstPersonController {
+            fly: FlyController::from_camera(camera),
+            eye_height: camera.eye.y,
+        }
+    }
+}
+
+impl CameraController for FirstPersonController {
+    fn update(&mut self, input: &InputState, dt: f32, camera: &mut Camera) {
+        self.fly.look(input);
+
+        let forward = Vector3::new(self.fly.yaw.sin(), 0.0, self.fly.yaw.cos());
+        let right = Vector3::new(forward.z, 0.0, -forward.x);
+        let keys = self.fly.bindings.movement(input);
+        let movement = right * keys.x + forward * keys.z;
+        if movement != Vector3::zero() {
+            camera.eye += movement.normalize() * self.fly.step(input, dt);
+        }
+        camera.eye.y = self.eye_height;
+        camera.target = camera.eye + direction(self.fly.yaw, self.fly.pitch);
+        camera.up = Vector3::unit_y();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{Deg, EuclideanSpace, MetricSpace};
+
+    use super::*;
+    use crate::input::{replay, InputEvent, RecordedFrame};
+
+    const EPSILON: f32 = 1e-4;
+
+    fn frame(dt: f32, events: &[InputEvent]) -> RecordedFrame {
+        RecordedFrame {
+            dt,
+            events: events.to_vec(),
+        }
+    }
+
+    fn key(key: VirtualKeyCode, pressed: bool) -> InputEvent {
+        InputEvent::Key { key, pressed }
+    }
+
+    fn button(button: MouseButton, pressed: bool) -> InputEvent {
+        InputEvent::MouseButton { button, pressed }
+    }
+
+    fn motion(dx: f32, dy: f32) -> InputEvent {
+        InputEvent::MouseMotion { dx, dy }
+    }
+
+    fn run<C: CameraController>(controller: &mut C, camera: &mut Camera, frames: &[RecordedFrame]) {
+        replay(frames, |input, dt| controller.update(input, dt, camera));
+    }
+
+    fn camera() -> Camera {
+        Camera::perspective(
+            Point3::new(0.0, 0.0, -10.0),
+            Point3::origin(),
+            Deg(60.0),
+            1.0,
+            0.1,
+            100.0,
+        )
+    }
+
+    #[test]
+    fn orbit_from_camera_is_stable_without_input() {
+        let mut camera = camera();
+        let mut orbit = OrbitController::from_camera(&camera);
+        run(&mut orbit, &mut camera, &vec![frame(0.016, &[]); 10]);
+        assert!(camera.eye.distance(Point3::new(0.0, 0.0, -10.0)) < EPSILON);
+        assert_eq!(camera.target, Point3::origin());
+    }
+
+    #[test]
+    fn orbit_rotates_only_while_dragging() {
+        let mut camera = camera();
+        let mut orbit = OrbitController::from_camera(&camera);
+        let yaw = orbit.yaw;
+        run(
+            &mut orbit,
+            &mut camera,
+            &[
+                frame(0.0, &[motion(100.0, 0.0)]),
+                frame(0.0, &[button(MouseButton::Left, true), motion(100.0, 0.0)]),
+                frame(0.0, &[button(MouseButton::Left, false), motion(100.0, 0.0)]),
+            ],
+        );
+        assert!((orbit.yaw - (yaw + 100.0 * orbit.rotate_speed)).abs() < EPSILON);
+        assert!((camera.eye.distance(camera.target) - 10.0).abs() < EPSILON);
+    }
+
+    #[test]
+    fn orbit_clamps_pitch_and_distance() {
+        let mut camera = camera();
+        let mut orbit = OrbitController::from_camera(&camera);
+        run(
+            &mut orbit,
+            &mut camera,
+            &[
+                frame(0.0, &[button(MouseButton::Left, true), motion(0.0, 1.0e5)]),
+                frame(0.0, &[InputEvent::Scroll { lines: 1.0 }]),
+            ],
+        );
+        assert_eq!(orbit.pitch, PITCH_LIMIT);
+        assert!((orbit.distance - 9.0).abs() < EPSILON);
+
+        run(
+            &mut orbit,
+            &mut camera,
+            &[frame(0.0, &[InputEvent::Scroll { lines: -1000.0 }])],
+        );
+        assert_eq!(orbit.distance, orbit.max_distance);
+        run(
+            &mut orbit,
+            &mut camera,
+            &[frame(0.0, &[InputEvent::Scroll { lines: 1000.0 }])],
+        );
+        assert_eq!(orbit.distance, orbit.min_distance);
+    }
+
+    #[test]
+    fn orbit_pans_target_with_middle_button() {
+        let mut camera = camera();
+        let mut orbit = OrbitController::from_camera(&camera);
+        let yaw = orbit.yaw;
+        run(
+            &mut orbit,
+            &mut camera,
+            &[frame(
+                0.0,
+                &[
+                    button(MouseButton::Middle, true),
+                    button(MouseButton::Left, true),
+                    motion(50.0, 0.0),
+                ],
+            )],
+        );
+        assert_eq!(orbit.yaw, yaw);
+        let expected = -50.0 * orbit.pan_speed * orbit.distance;
+        assert!((camera.target.x - expected).abs() < EPSILON);
+        assert!(camera.target.y.abs() < EPSILON);
+        assert!((camera.eye.distance(camera.target) - 10.0).abs() < EPSILON);
+    }
+
+    #[test]
+    fn fly_moves_along_view_direction() {
+        let mut camera = camera();
+        let mut fly = FlyController::from_camera(&camera);
+        let mut frames = vec![frame(0.1, &[key(VirtualKeyCode::W, true)])];
+        frames.extend(std::iter::repeat_n(frame(0.1, &[]), 9));
+        frames.push(frame(0.1, &[key(VirtualKeyCode::W, false)]));
+        run(&mut fly, &mut camera, &frames);
+        assert!(camera.eye.distance(Point3::new(0.0, 0.0, -5.0)) < EPSILON);
+        assert!((camera.forward() - Vector3::unit_z()).magnitude() < EPSILON);
+    }
+
+    #[test]
+    fn fly_fast_modifier_and_diagonals() {
+        let mut camera = camera();
+        let mut fly = FlyController::from_camera(&camera);
+        run(
+            &mut fly,
+            &mut camera,
+            &[frame(
+                0.1,
+                &[
+                    key(VirtualKeyCode::LShift, true),
+                    key(VirtualKeyCode::W, true),
+                    key(VirtualKeyCode::D, true),
+                ],
+            )],
+        );
+        let moved = camera.eye - Point3::new(0.0, 0.0, -10.0);
+        assert!((moved.magnitude() - 2.0).abs() < EPSILON);
+        assert!((moved.x - moved.z).abs() < EPSILON && moved.x > 0.0);
+    }
+
+    #[test]
+    fn fly_looks_only_with_right_button() {
+        let mut camera = camera();
+        let mut fly = FlyController::from_camera(&camera);
+        let (yaw, pitch) = (fly.yaw, fly.pitch);
+        run(&mut fly, &mut camera, &[frame(0.0, &[motion(10.0, 10.0)])]);
+        assert_eq!((fly.yaw, fly.pitch), (yaw, pitch));
+
+        run(
+            &mut fly,
+            &mut camera,
+            &[frame(
+                0.0,
+                &[button(MouseButton::Right, true), motion(10.0, 10.0)],
+            )],
+        );
+        assert!((fly.yaw - (yaw + 10.0 * fly.sensitivity)).abs() < EPSILON);
+        assert!((fly.pitch - (pitch - 10.0 * fly.sensitivity)).abs() < EPSILON);
+    }
+
+    #[test]
+    fn first_person_stays_on_ground() {
+        let mut camera = camera();
+        camera.eye.y = 1.5;
+        let mut walker = FirstPersonController::from_camera(&camera);
+        walker.fly.pitch = 1.0;
+        run(
+            &mut walker,
+            &mut camera,
+            &[
+                frame(0.5, &[key(VirtualKeyCode::W, true)]),
+                frame(0.5, &[key(VirtualKeyCode::E, true)]),
+            ],
+        );
+        assert_eq!(camera.eye.y, 1.5);
+        assert!((camera.eye.z - -5.0).abs() < EPSILON);
+        assert!(camera.target.y > camera.eye.y);
+    }
+}
@@ -0,0 +1,579 @@
+use cgmath::{Matrix3, Vector3};
+use windows::{core::*, Win32::Graphics::Dxgi::Common::*, Win32::Graphics::Dxgi::*};
+
+use crate::sampler::SamplerFilter;
+
+pub const SDR_WHITE_NITS: f32 = 80.0;
+pub const PQ_MAX_NITS: f32 = 10000.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputMode {
+    Sdr,
+    Hdr10,
+    ScRgb,
+}
+
+impl OutputMode {
+    pub fn swap_chain_format(self) -> DXGI_FORMAT {
+        match self {
+            OutputMode::Sdr => DXGI_FORMAT_R8G8B8A8_UNORM,
+            OutputMode::Hdr10 => DXGI_FORMAT_R10G10B10A2_UNORM,
+            OutputMode::ScRgb => DXGI_FORMAT_R16G16B16A16_FLOAT,
+        }
+    }
+
+    pub fn rtv_format(self) -> DXGI_FORMAT {
+        srgb_format(self.swap_chain_format())
+    }
+
+    pub fn color_space(self) -> DXGI_COLOR_SPACE_TYPE {
+        match self {
+            OutputMode::Sdr => DXGI_COLOR_SPACE_RGB_FULL_G22_NONE_P709,
+            OutputMode::Hdr10 => DXGI_COLOR_SPACE_RGB_FULL_G2084_NONE_P2020,
+            OutputMode::ScRgb => DXGI_COLOR_SPACE_RGB_FULL_G10_NONE_P709,
+        }
+    }
+
+    pub fn shader_id(self) -> u32 {
+        match self {
+            OutputMode::Sdr => 0,
+            OutputMode::Hdr10 => 1,
+            OutputMode::ScRgb => 2,
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            OutputMode::Sdr => OutputMode::Hdr10,
+            OutputMode::Hdr10 => OutputMode::ScRgb,
+            OutputMode::ScRgb => OutputMode::Sdr,
+        }
+    }
+
+    /// CPU mirror of `ApplyOutputMode` in BasicPixelShader.hlsl: the value written through the
+    /// render target view. The SDR view is `_SRGB`, so the transfer function is left to the
+    /// hardware; the tonemap only runs there since the HDR modes keep the highlights.
+    pub fn shade(
+        self,
+        linear: Vector3<f32>,
+        paper_white_nits: f32,
+        tonemap: Tonemap,
+    ) -> Vector3<f32> {
+        match self {
+            OutputMode::Sdr => tonemap.apply(linear),
+            OutputMode::Hdr10 => (rec709_to_rec2020(linear) * paper_white_nits).map(pq_encode),
+            OutputMode::ScRgb => linear * (paper_white_nits / SDR_WHITE_NITS),
+        }
+    }
+
+    /// The value stored in the swap chain buffer.
+    pub fn encode(
+        self,
+        linear: Vector3<f32>,
+        paper_white_nits: f32,
+        tonemap: Tonemap,
+    ) -> Vector3<f32> {
+        let shaded = self.shade(linear, paper_white_nits, tonemap);
+        match self {
+            OutputMode::Sdr => shaded.map(linear_to_srgb),
+            OutputMode::Hdr10 | OutputMode::ScRgb => shaded,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OutputConstants {
+    pub mode: u32,
+    pub paper_white_nits: f32,
+    pub sampler_filter: u32,
+    pub tonemap: u32,
+    pub tonemap_white: f32,
+}
+
+impl OutputConstants {
+    pub const NUM_32BIT_VALUES: u32 = 5;
+
+    pub fn new(
+        mode: OutputMode,
+        paper_white_nits: f32,
+        sampler_filter: SamplerFilter,
+        tonemap: Tonemap,
+    ) -> Self {
+        OutputConstants {
+            mode: mode.shader_id(),
+            paper_white_nits,
+            sampler_filter: sampler_filter.shader_id(),
+            tonemap: tonemap.shader_id(),
+            tonemap_white: tonemap.white(),
+        }
+    }
+}
+
+pub fn srgb_format(format: DXGI_FORMAT) -> DXGI_FORMAT {
+    match format {
+        DXGI_FORMAT_R8G8B8A8_UNORM => DXGI_FORMAT_R8G8B8A8_UNORM_SRGB,
+        DXGI_FORMAT_B8G8R8A8_UNORM => DXGI_FORMAT_B8G8R8A8_UNORM_SRGB,
+        DXGI_FORMAT_B8G8R8X8_UNORM => DXGI_FORMAT_B8G8R8X8_UNORM_SRGB,
+        DXGI_FORMAT_BC1_UNORM => DXGI_FORMAT_BC1_UNORM_SRGB,
+        DXGI_FORMAT_BC2_UNORM => DXGI_FORMAT_BC2_UNORM_SRGB,
+        DXGI_FORMAT_BC3_UNORM => DXGI_FORMAT_BC3_UNORM_SRGB,
+        DXGI_FORMAT_BC7_UNORM => DXGI_FORMAT_BC7_UNORM_SRGB,
+        other => other,
+    }
+}
+
+pub fn typeless_format(format: DXGI_FORMAT) -> DXGI_FORMAT {
+    match format {
+        DXGI_FORMAT_R8G8B8A8_UNORM | DXGI_FORMAT_R8G8B8A8_UNORM_SRGB => {
+            DXGI_FORMAT_R8G8B8A8_TYPELESS
+        }
+        DXGI_FORMAT_B8G8R8A8_UNORM | DXGI_FORMAT_B8G8R8A8_UNORM_SRGB => {
+            DXGI_FORMAT_B8G8R8A8_TYPELESS
+        }
+        DXGI_FORMAT_R10G10B10A2_UNORM => DXGI_FORMAT_R10G10B10A2_TYPELESS,
+        DXGI_FORMAT_R16G16B16A16_FLOAT => DXGI_FORMAT_R16G16B16A16_TYPELESS,
+        other => other,
+    }
+}
+
+pub fn linear_to_srgb(c: f32) -> f32 {
+    let c = c.clamp(0.0, 1.0);
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+const PQ_M1: f32 = 2610.0 / 16384.0;
+const PQ_M2: f32 = 2523.0 / 4096.0 * 128.0;
+const PQ_C1: f32 = 3424.0 / 4096.0;
+const PQ_C2: f32 = 2413.0 / 4096.0 * 32.0;
+const PQ_C3: f32 = 2392.0 / 4096.0 * 32.0;
+
+pub fn pq_encode(nits: f32) -> f32 {
+    let y = (nits / PQ_MAX_NITS).clamp(0.0, 1.0).powf(PQ_M1);
+    ((PQ_C1 + PQ_C2 * y) / (1.0 + PQ_C3 * y)).powf(PQ_M2)
+}
+
+pub fn pq_decode(signal: f32) -> f32 {
+    let e = signal.clamp(0.0, 1.0).powf(1.0 / PQ_M2);
+    let y = ((e - PQ_C1).max(0.0) / (PQ_C2 - PQ_C3 * e)).powf(1.0 / PQ_M1);
+    y * PQ_MAX_NITS
+}
+
+#[rustfmt::skip]
+pub const REC709_TO_REC2020: Matrix3<f32> = Matrix3::new(
+    0.627_404, 0.069_097, 0.016_391,
+    0.329_283, 0.919_54, 0.088_013,
+    0.043_313, 0.011_362, 0.895_595,
+);
+
+#[rustfmt::skip]
+pub const REC2020_TO_REC709: Matrix3<f32> = Matrix3::new(
+    1.660_491, -0.124_55, -0.018_151,
+    -0.587_641, 1.132_9, -0.100_579,
+    -0.072_85, -0.008_349, 1.118_73,
+);
+
+pub fn rec709_to_rec2020(color: Vector3<f32>) -> Vector3<f32> {
+    REC709_TO_REC2020 * color
+}
+
+pub fn rec2020_to_rec709(color: Vector3<f32>) -> Vector3<f32> {
+    REC2020_TO_REC709 * color
+}
+
+pub fn luminance(color: Vector3<f32>) -> f32 {
+    0.2126 * color.x + 0.7152 * color.y + 0.0722 * color.z
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Tonemap {
+    Clamp,
+    Reinhard,
+    ReinhardExtended { white: f32 },
+    AcesFilmic,
+    Hable,
+}
+
+impl Tonemap {
+    pub const ALL: [Tonemap; 5] = [
+        Tonemap::Clamp,
+        Tonemap::Reinhard,
+        Tonemap::ReinhardExtended { white: 4.0 },
+        Tonemap::AcesFilmic,
+        Tonemap::Hable,
+    ];
+
+    pub fn shader_id(self) -> u32 {
+        match self {
+            Tonemap::Clamp => 0,
+            Tonemap::Reinhard => 1,
+            Tonemap::ReinhardExtended { .. } => 2,
+            Tonemap::AcesFilmic => 3,
+            Tonemap::Hable => 4,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Tonemap::Clamp => "clamp",
+            Tonemap::Reinhard => "reinhard",
+            Tonemap::ReinhardExtended { .. } => "reinhard extended",
+            Tonemap::AcesFilmic => "aces filmic",
+            Tonemap::Hable => "hable",
+        }
+    }
+
+    pub fn white(self) -> f32 {
+        match self {
+            Tonemap::ReinhardExtended { white } => white,
+            _ => 0.0,
+        }
+    }
+
+    pub fn apply(self, color: Vector3<f32>) -> Vector3<f32> {
+        match self {
+            Tonemap::Clamp => color.map(|c| c.clamp(0.0, 1.0)),
+            Tonemap::Reinhard => color.map(|c| c / (1.0 + c)),
+            Tonemap::ReinhardExtended { white } => {
+                let l = luminance(color);
+                if l <= 0.0 {
+                    return Vector3::new(0.0, 0.0, 0.0);
+                }
+                let mapped = l * (1.0 + l / (white * white)) / (1.0 + l);
+                color * (mapped / l)
+            }
+            Tonemap::AcesFilmic => color.map(|c| {
+                let c = c.max(0.0);
+                ((c * (2.51 * c + 0.03)) / (c * (2.43 * c + 0.59) + 0.14)).clamp(0.0, 1.0)
+            }),
+            Tonemap::Hable => {
+                const WHITE: f32 = 11.2;
+                let white_scale = 1.0 / hable_partial(WHITE);
+                color.map(|c| hable_partial(c.max(0.0) * 2.0) * white_scale)
+            }
+        }
+    }
+}
+
+fn hable_partial(x: f32) -> f32 {
+    const A: f32 = 0.15;
+    const B: f32 = 0.50;
+    const C: f32 = 0.10;
+    const D: f32 = 0.20;
+    const E: f32 = 0.02;
+    const F: f32 = 0.30;
+    ((x * (A * x + C * B) + D * E) / (x * (A * x + B) + D * F)) - E / F
+}
+
+pub fn set_output_mode(
+    swap_chain: &IDXGISwapChain4,
+    mode: OutputMode,
+    width: u32,
+    height: u32,
+    flags: u32,
+) -> Result<bool> {
+    unsafe { swap_chain.ResizeBuffers(0, width, height, mode.swap_chain_format(), flags) }?;
+
+    let support = unsafe { swap_chain.CheckColorSpaceSupport(mode.color_space()) }?;
+    if support & DXGI_SWAP_CHAIN_COLOR_SPACE_SUPPORT_FLAG_PRESENT.0 as u32 == 0 {
+        return Ok(false);
+    }
+    unsafe { swap_chain.SetColorSpace1(mode.color_space()) }?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::{Matrix, SquareMatrix};
+
+    const EPSILON: f32 = 1e-4;
+
+    fn assert_close(actual: f32, expected: f32, epsilon: f32) {
+        assert!(
+            (actual - expected).abs() <= epsilon,
+            "expected {expected}, got {actual}"
+        );
+    }
+
+    fn assert_vector_close(actual: Vector3<f32>, expected: Vector3<f32>, epsilon: f32) {
+        assert_close(actual.x, expected.x, epsilon);
+        assert_close(actual.y, expected.y, epsilon);
+        assert_close(actual.z, expected.z, epsilon);
+    }
+
+    /// Numbers between `start` and the next `}` of BasicPixelShader.hlsl, in source order.
+    fn hlsl_numbers(start: &str) -> Vec<f32> {
+        let source = include_str!("BasicPixelShader.hlsl");
+        let begin = source.find(start).expect("marker not found in shader") + start.len();
+        let block = &source[begin..];
+        let block = &block[..block.find('}').unwrap()];
+        block
+            .split(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-'))
+            .filter(|token| token.contains('.'))
+            .map(|token| token.parse().unwrap())
+            .collect()
+    }
+
+    /// Evaluates the `a / b * c` expression assigned to `const float <name>` in the shader.
+    fn hlsl_constant(name: &str) -> f32 {
+        let source = include_str!("BasicPixelShader.hlsl");
+        let marker = format!("const float {name} =");
+        let begin = source.find(&marker).expect("constant not found in shader") + marker.len();
+        let expression = &source[begin..begin + source[begin..].find(';').unwrap()];
+        let mut tokens = expression.split_whitespace();
+        let mut value: f32 = tokens.next().unwrap().parse().unwrap();
+        while let (Some(op), Some(operand)) = (tokens.next(), tokens.next()) {
+            let operand: f32 = operand.parse().unwrap();
+            match op {
+                "/" => value /= operand,
+                "*" => value *= operand,
+                _ => panic!("unsupported operator {op}"),
+            }
+        }
+        value
+    }
+
+    #[test]
+    fn pq_round_trips() {
+        for nits in [0.0, 0.1, 1.0, 80.0, 100.0, 203.0, 1000.0, 4000.0, 10000.0] {
+            let decoded = pq_decode(pq_encode(nits));
+            assert_close(decoded, nits, (nits * 1e-3).max(1e-3));
+        }
+        for signal in [0.0, 0.25, 0.5, 0.75, 1.0] {
+            assert_close(pq_encode(pq_decode(signal)), signal, EPSILON);
+        }
+    }
+
+    #[test]
+    fn pq_known_values() {
+        assert_close(pq_encode(PQ_MAX_NITS), 1.0, EPSILON);
+        assert_close(pq_encode(0.0), 0.0, 1e-6);
+        // ST 2084 reference points.
+        assert_close(pq_encode(100.0), 0.5081, 1e-3);
+        assert_close(pq_encode(1000.0), 0.7518, 1e-3);
+        // Out-of-range input is clamped.
+        assert_close(pq_encode(20000.0), 1.0, EPSILON);
+        assert_close(pq_decode(1.5), PQ_MAX_NITS, 1e-1);
+    }
+
+    #[test]
+    fn pq_is_monotonic() {
+        let mut previous = pq_encode(0.0);
+        for step in 1..=100 {
+            let signal = pq_encode(step as f32 * 100.0);
+            assert!(signal > previous);
+            previous = signal;
+        }
+    }
+
+    #[test]
+    fn rec709_to_rec2020_preserves_white() {
+        let white = Vector3::new(1.0, 1.0, 1.0);
+        assert_vector_close(rec709_to_rec2020(white), white, 1e-3);
+        assert_vector_close(rec2020_to_rec709(white), white, 1e-3);
+    }
+
+    #[test]
+    fn rec709_to_rec2020_maps_primaries() {
+        // Columns of the BT.2087 matrix: the Rec.709 primaries expressed in Rec.2020.
+        assert_vector_close(
+            rec709_to_rec2020(Vector3::new(1.0, 0.0, 0.0)),
+            Vector3::new(0.627_404, 0.069_097, 0.016_391),
+            EPSILON,
+        );
+        assert_vector_close(
+            rec709_to_rec2020(Vector3::new(0.0, 1.0, 0.0)),
+            Vector3::new(0.329_283, 0.919_54, 0.088_013),
+            EPSILON,
+        );
+        assert_vector_close(
+            rec709_to_rec2020(Vector3::new(0.0, 0.0, 1.0)),
+            Vector3::new(0.043_313, 0.011_362, 0.895_595),
+            EPSILON,
+        );
+        // Rec.709 sits inside Rec.2020, so no primary goes negative.
+        for column in 0..3 {
+            let c = REC709_TO_REC2020[column];
+            assert!(c.x >= 0.0 && c.y >= 0.0 && c.z >= 0.0);
+        }
+    }
+
+    #[test]
+    fn rec2020_to_rec709_is_the_inverse() {
+        let product = REC2020_TO_REC709 * REC709_TO_REC2020;
+        let identity = Matrix3::<f32>::identity();
+        for column in 0..3 {
+            assert_vector_close(product[column], identity[column], 1e-3);
+        }
+        let inverse = REC709_TO_REC2020.invert().unwrap();
+        for column in 0..3 {
+            assert_vector_close(inverse[column], REC2020_TO_REC709[column], 1e-3);
+        }
+    }
+
+    #[test]
+    fn rec709_to_rec2020_preserves_luminance() {
+        let rec2020_luminance = Vector3::new(0.2627, 0.6780, 0.0593);
+        for color in [
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(0.2, 0.5, 0.9),
+            Vector3::new(0.7, 0.7, 0.1),
+        ] {
+            let converted = rec709_to_rec2020(color);
+            assert_close(
+                cgmath::dot(converted, rec2020_luminance),
+                luminance(color),
+                1e-3,
+            );
+        }
+    }
+
+    #[test]
+    fn shader_matrix_matches_rust() {
+        // HLSL literals are row-major; cgmath stores columns.
+        let expected: Vec<f32> = (0..3)
+            .flat_map(|row| (0..3).map(move |column| REC709_TO_REC2020.transpose()[row][column]))
+            .collect();
+        let actual = hlsl_numbers("float3x3 Rec709ToRec2020 =");
+        assert_eq!(actual.len(), 9);
+        for (actual, expected) in actual.iter().zip(expected) {
+            assert_close(*actual, expected, 1e-6);
+        }
+    }
+
+    #[test]
+    fn shader_pq_constants_match_rust() {
+        assert_close(hlsl_constant("m1"), PQ_M1, 1e-6);
+        assert_close(hlsl_constant("m2"), PQ_M2, 1e-6);
+        assert_close(hlsl_constant("c1"), PQ_C1, 1e-6);
+        assert_close(hlsl_constant("c2"), PQ_C2, 1e-6);
+        assert_close(hlsl_constant("c3"), PQ_C3, 1e-6);
+    }
+
+    #[test]
+    fn shader_handles_every_tonemap() {
+        let source = include_str!("BasicPixelShader.hlsl");
+        assert_eq!(Tonemap::Clamp.shader_id(), 0);
+        for tonemap in &Tonemap::ALL[1..] {
+            assert!(
+                source.contains(&format!("tonemap == {}", tonemap.shader_id())),
+                "{} has no shader branch",
+                tonemap.name()
+            );
+        }
+    }
+
+    #[test]
+    fn output_constants_layout() {
+        assert_eq!(
+            std::mem::size_of::<OutputConstants>(),
+            OutputConstants::NUM_32BIT_VALUES as usize * 4
+        );
+        let constants = OutputConstants::new(
+            OutputMode::Hdr10,
+            200.0,
+            SamplerFilter::Point,
+            Tonemap::ReinhardExtended { white: 6.0 },
+        );
+        assert_eq!(constants.mode, 1);
+        assert_eq!(constants.sampler_filter, 1);
+        assert_eq!(constants.tonemap, 2);
+        assert_eq!(constants.tonemap_white, 6.0);
+    }
+
+    #[test]
+    fn tonemaps_stay_in_display_range() {
+        for tonemap in Tonemap::ALL {
+            assert_vector_close(
+                tonemap.apply(Vector3::new(0.0, 0.0, 0.0)),
+                Vector3::new(0.0, 0.0, 0.0),
+                1e-3,
+            );
+            // Up to the smallest white point; the extended curves only reach 1 there.
+            let mut previous = 0.0;
+            for step in 1..=64 {
+                let value = step as f32 * 0.0625;
+                let mapped = tonemap.apply(Vector3::new(value, value, value));
+                assert!(mapped.x <= 1.0 + EPSILON, "{} exceeded 1", tonemap.name());
+                assert!(
+                    mapped.x >= previous - EPSILON,
+                    "{} not monotonic",
+                    tonemap.name()
+                );
+                previous = mapped.x;
+            }
+        }
+    }
+
+    #[test]
+    fn tonemap_reference_points() {
+        assert_close(
+            Tonemap::Reinhard.apply(Vector3::new(1.0, 1.0, 1.0)).x,
+            0.5,
+            EPSILON,
+        );
+        // The extended curve maps `white` exactly to 1.
+        let extended = Tonemap::ReinhardExtended { white: 4.0 };
+        assert_close(extended.apply(Vector3::new(4.0, 4.0, 4.0)).y, 1.0, EPSILON);
+        // Hable is normalised so that its white point (11.2 after the 2x exposure) hits 1.
+        assert_close(
+            Tonemap::Hable.apply(Vector3::new(5.6, 5.6, 5.6)).z,
+            1.0,
+            EPSILON,
+        );
+        assert_close(
+            Tonemap::AcesFilmic
+                .apply(Vector3::new(100.0, 100.0, 100.0))
+                .x,
+            1.0,
+            EPSILON,
+        );
+        assert_close(
+            Tonemap::Clamp.apply(Vector3::new(2.0, -1.0, 0.5)).x,
+            1.0,
+            EPSILON,
+        );
+    }
+
+    #[test]
+    fn shade_and_encode_per_mode() {
+        let grey = Vector3::new(0.5, 0.5, 0.5);
+        // SDR leaves the transfer function to the sRGB view.
+        assert_vector_close(
+            OutputMode::Sdr.shade(grey, 200.0, Tonemap::Clamp),
+            grey,
+            EPSILON,
+        );
+        assert_close(
+            OutputMode::Sdr.encode(grey, 200.0, Tonemap::Clamp).x,
+            0.7354,
+            1e-3,
+        );
+        assert_close(
+            OutputMode::Sdr
+                .shade(Vector3::new(3.0, 3.0, 3.0), 200.0, Tonemap::Reinhard)
+                .x,
+            0.75,
+            EPSILON,
+        );
+        // HDR modes ignore the tonemap.
+        let white = Vector3::new(1.0, 1.0, 1.0);
+        assert_close(
+            OutputMode::Hdr10.encode(white, 200.0, Tonemap::Reinhard).x,
+            pq_encode(200.0),
+            1e-3,
+        );
+        assert_close(
+            OutputMode::ScRgb
+                .encode(Vector3::new(4.0, 4.0, 4.0), 160.0, Tonemap::Hable)
+                .x,
+            8.0,
+            EPSILON,
+        );
+    }
+}
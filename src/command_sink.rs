@@ -0,0 +1,275 @@
+use std::cell::RefCell;
+
+use windows::Win32::Foundation::RECT;
+use windows::Win32::Graphics::Direct3D12::*;
+
+pub trait CommandSink {
+    fn set_vertex_buffers(&self, start_slot: u32, views: &[D3D12_VERTEX_BUFFER_VIEW]);
+    fn set_index_buffer(&self, view: &D3D12_INDEX_BUFFER_VIEW);
+    fn set_scissor_rect(&self, rect: &RECT);
+    fn set_descriptor_table(&self, root_index: u32, handle: D3D12_GPU_DESCRIPTOR_HANDLE);
+    fn draw_instanced(
+        &self,
+        vertex_count: u32,
+        instance_count: u32,
+        start_vertex: u32,
+        start_instance: u32,
+    );
+    fn draw_indexed_instanced(
+        &self,
+        index_count: u32,
+        instance_count: u32,
+        start_index: u32,
+        base_vertex: i32,
+        start_instance: u32,
+    );
+}
+
+impl CommandSink for ID3D12GraphicsCommandList {
+    fn set_vertex_buffers(&self, start_slot: u32, views: &[D3D12_VERTEX_BUFFER_VIEW]) {
+        unsafe { self.IASetVertexBuffers(start_slot, Some(views)) };
+    }
+
+    fn set_index_buffer(&self, view: &D3D12_INDEX_BUFFER_VIEW) {
+        unsafe { self.IASetIndexBuffer(Some(view)) };
+    }
+
+    fn set_scissor_rect(&self, rect: &RECT) {
+        unsafe { self.RSSetScissorRects(std::slice::from_ref(rect)) };
+    }
+
+    fn set_descriptor_table(&self, root_index: u32, handle: D3D12_GPU_DESCRIPTOR_HANDLE) {
+        unsafe { self.SetGraphicsRootDescriptorTable(root_index, handle) };
+    }
+
+    fn draw_instanced(
+        &self,
+        vertex_count: u32,
+        instance_count: u32,
+        start_vertex: u32,
+        start_instance: u32,
+    ) {
+        unsafe { self.DrawInstanced(vertex_count, instance_count, start_vertex, start_instance) };
+    }
+
+    fn draw_indexed_instanced(
+        &self,
+        index_count: u32,
+        instance_count: u32,
+        start_index: u32,
+        base_vertex: i32,
+        start_instance: u32,
+    ) {
+        unsafe {
+            self.DrawIndexedInstanced(
+                index_count,
+                instance_count,
+                start_index,
+                base_vertex,
+                start_instance,
+            )
+        };
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecordedCommand {
+    SetVertexBuffers {
+        start_slot: u32,
+        views: Vec<D3D12_VERTEX_BUFFER_VIEW>,
+    },
+    SetIndexBuffer(D3D12_INDEX_BUFFER_VIEW),
+    SetScissorRect(RECT),
+    SetDescriptorTable {
+        root_index: u32,
+        handle: D3D12_GPU_DESCRIPTOR_HANDLE,
+    },
+    DrawInstanced {
+        vertex_count: u32,
+        instance_count: u32,
+        start_vertex: u32,
+        start_instance: u32,
+    },
+    DrawIndexedInstanced {
+        index_count: u32,
+        instance_count: u32,
+        start_index: u32,
+        base_vertex: i32,
+        start_instance: u32,
+    },
+}
+
+#[derive(Debug, Default)]
+pub struct RecordedCommands {
+    commands: RefCell<Vec<RecordedCommand>>,
+}
+
+impl RecordedCommands {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn commands(&self) -> Vec<RecordedCommand> {
+        self.commands.borrow().clone()
+    }
+
+    pub fn take(&self) -> Vec<RecordedCommand> {
+        self.commands.take()
+    }
+
+    pub fn draw_count(&self) -> usize {
+        self.commands
+            .borrow()
+            .iter()
+            .filter(|command| {
+                matches!(
+                    command,
+                    RecordedCommand::DrawInstanced { .. }
+                        | RecordedCommand::DrawIndexedInstanced { .. }
+                )
+            })
+            .count()
+    }
+
+    fn record(&self, command: RecordedCommand) {
+        self.commands.borrow_mut().push(command);
+    }
+}
+
+impl CommandSink for RecordedCommands {
+    fn set_vertex_buffers(&self, start_slot: u32, views: &[D3D12_VERTEX_BUFFER_VIEW]) {
+        self.record(RecordedCommand::SetVertexBuffers {
+            start_slot,
+            views: views.to_vec(),
+        });
+    }
+
+    fn set_index_buffer(&self, view: &D3D12_INDEX_BUFFER_VIEW) {
+        self.record(RecordedCommand::SetIndexBuffer(*view));
+    }
+
+    fn set_scissor_rect(&self, rect: &RECT) {
+        self.record(RecordedCommand::SetScissorRect(*rect));
+    }
+
+    fn set_descriptor_table(&self, root_index: u32, handle: D3D12_GPU_DESCRIPTOR_HANDLE) {
+        self.record(RecordedCommand::SetDescriptorTable { root_index, handle });
+    }
+
+    fn draw_instanced(
+        &self,
+        vertex_count: u32,
+        instance_count: u32,
+        start_vertex: u32,
+        start_instance: u32,
+    ) {
+        self.record(RecordedCommand::DrawInstanced {
+            vertex_count,
+            instance_count,
+            start_vertex,
+            start_instance,
+        });
+    }
+
+    fn draw_indexed_instanced(
+        &self,
+        index_count: u32,
+        instance_count: u32,
+        start_index: u32,
+        base_vertex: i32,
+        start_instance: u32,
+    ) {
+        self.record(RecordedCommand::DrawIndexedInstanced {
+            index_count,
+            instance_count,
+            start_index,
+            base_vertex,
+            start_instance,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vertex_buffer(address: u64) -> D3D12_VERTEX_BUFFER_VIEW {
+        D3D12_VERTEX_BUFFER_VIEW {
+            BufferLocation: address,
+            SizeInBytes: 64,
+            StrideInBytes: 16,
+        }
+    }
+
+    #[test]
+    fn records_commands_in_order() {
+        let sink = RecordedCommands::new();
+        let index_buffer = D3D12_INDEX_BUFFER_VIEW {
+            BufferLocation: 0x2000,
+            SizeInBytes: 12,
+            Format: windows::Win32::Graphics::Dxgi::Common::DXGI_FORMAT_R32_UINT,
+        };
+        let rect = RECT {
+            left: 1,
+            top: 2,
+            right: 3,
+            bottom: 4,
+        };
+        let handle = D3D12_GPU_DESCRIPTOR_HANDLE { ptr: 0x40 };
+        sink.set_vertex_buffers(1, &[vertex_buffer(0x1000), vertex_buffer(0x1100)]);
+        sink.set_index_buffer(&index_buffer);
+        sink.set_scissor_rect(&rect);
+        sink.set_descriptor_table(2, handle);
+        sink.draw_instanced(3, 1, 0, 0);
+        sink.draw_indexed_instanced(6, 2, 3, -1, 4);
+
+        assert_eq!(
+            sink.commands(),
+            [
+                RecordedCommand::SetVertexBuffers {
+                    start_slot: 1,
+                    views: vec![vertex_buffer(0x1000), vertex_buffer(0x1100)],
+                },
+                RecordedCommand::SetIndexBuffer(index_buffer),
+                RecordedCommand::SetScissorRect(rect),
+                RecordedCommand::SetDescriptorTable {
+                    root_index: 2,
+                    handle,
+                },
+                RecordedCommand::DrawInstanced {
+                    vertex_count: 3,
+                    instance_count: 1,
+                    start_vertex: 0,
+                    start_instance: 0,
+                },
+                RecordedCommand::DrawIndexedInstanced {
+                    index_count: 6,
+                    instance_count: 2,
+                    start_index: 3,
+                    base_vertex: -1,
+                    start_instance: 4,
+                },
+            ]
+        );
+        assert_eq!(sink.draw_count(), 2);
+    }
+
+    #[test]
+    fn take_drains_the_recording() {
+        let sink = RecordedCommands::new();
+        sink.draw_instanced(3, 1, 0, 0);
+        assert_eq!(sink.take().len(), 1);
+        assert!(sink.commands().is_empty());
+        assert_eq!(sink.draw_count(), 0);
+    }
+
+    #[test]
+    fn works_through_a_trait_object() {
+        let recorded = RecordedCommands::new();
+        let sink: &dyn CommandSink = &recorded;
+        sink.set_vertex_buffers(0, &[vertex_buffer(0x1000)]);
+        sink.draw_instanced(4, 1, 0, 0);
+        assert_eq!(recorded.commands().len(), 2);
+        assert_eq!(recorded.draw_count(), 1);
+    }
+}
@@ -0,0 +1,475 @@
+use std::marker::PhantomData;
+
+use cgmath::{Matrix2, Matrix3, Matrix4, Point3, Vector2, Vector3, Vector4};
+use windows::Win32::Graphics::Direct3D12::D3D12_CONSTANT_BUFFER_DATA_PLACEMENT_ALIGNMENT;
+
+use crate::shader_reflection::ConstantBufferDesc;
+use crate::upload_ring::UploadRing;
+
+pub use d3d12forrust_derive::ConstantBufferLayout;
+
+const REGISTER_SIZE: usize = 16;
+
+pub const fn align_up(size: usize, alignment: usize) -> usize {
+    (size + alignment - 1) & !(alignment - 1)
+}
+
+pub const fn hlsl_offset(cursor: usize, size: usize, align_to_register: bool) -> usize {
+    let straddles = size > 0 && cursor / REGISTER_SIZE != (cursor + size - 1) / REGISTER_SIZE;
+    if align_to_register || straddles {
+        align_up(cursor, REGISTER_SIZE)
+    } else {
+        cursor
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CbField {
+    pub name: &'static str,
+    pub offset: usize,
+    pub size: usize,
+}
+
+impl CbField {
+    pub const EMPTY: CbField = CbField {
+        name: "",
+        offset: 0,
+        size: 0,
+    };
+
+    pub const fn place(
+        name: &'static str,
+        cursor: usize,
+        size: usize,
+        align_to_register: bool,
+    ) -> CbField {
+        CbField {
+            name,
+            offset: hlsl_offset(cursor, size, align_to_register),
+            size,
+        }
+    }
+}
+
+pub const fn packed_size(fields: &[CbField]) -> usize {
+    match fields.last() {
+        Some(field) => field.offset + field.size,
+        None => 0,
+    }
+}
+
+pub trait HlslType {
+    const SIZE: usize;
+    const ALIGN_TO_REGISTER: bool;
+
+    fn write_hlsl(&self, dst: &mut [u8]);
+}
+
+pub trait ConstantBufferLayout: HlslType {
+    const FIELDS: &'static [CbField];
+
+    fn write_packed(&self, dst: &mut [u8]);
+}
+
+fn write_f32s(dst: &mut [u8], values: &[f32]) {
+    for (chunk, value) in dst.chunks_exact_mut(4).zip(values) {
+        chunk.copy_from_slice(&value.to_le_bytes());
+    }
+}
+
+impl HlslType for f32 {
+    const SIZE: usize = 4;
+    const ALIGN_TO_REGISTER: bool = false;
+
+    fn write_hlsl(&self, dst: &mut [u8]) {
+        dst[..4].copy_from_slice(&self.to_le_bytes());
+    }
+}
+
+impl HlslType for u32 {
+    const SIZE: usize = 4;
+    const ALIGN_TO_REGISTER: bool = false;
+
+    fn write_hlsl(&self, dst: &mut [u8]) {
+        dst[..4].copy_from_slice(&self.to_le_bytes());
+    }
+}
+
+impl HlslType for i32 {
+    const SIZE: usize = 4;
+    const ALIGN_TO_REGISTER: bool = false;
+
+    fn write_hlsl(&self, dst: &mut [u8]) {
+        dst[..4].copy_from_slice(&self.to_le_bytes());
+    }
+}
+
+impl HlslType for bool {
+    const SIZE: usize = 4;
+    const ALIGN_TO_REGISTER: bool = false;
+
+    fn write_hlsl(&self, dst: &mut [u8]) {
+        dst[..4].copy_from_slice(&(*self as u32).to_le_bytes());
+    }
+}
+
+impl HlslType for Vector2<f32> {
+    const SIZE: usize = 8;
+    const ALIGN_TO_REGISTER: bool = false;
+
+    fn write_hlsl(&self, dst: &mut [u8]) {
+        write_f32s(dst, &[self.x, self.y]);
+    }
+}
+
+impl HlslType for Vector3<f32> {
+    const SIZE: usize = 12;
+    const ALIGN_TO_REGISTER: bool = false;
+
+    fn write_hlsl(&self, dst: &mut [u8]) {
+        write_f32s(dst, &[self.x, self.y, self.z]);
+    }
+}
+
+impl HlslType for Point3<f32> {
+    const SIZE: usize = 12;
+    const ALIGN_TO_REGISTER: bool = false;
+
+    fn write_hlsl(&self, dst: &mut [u8]) {
+        write_f32s(dst, &[self.x, self.y, self.z]);
+    }
+}
+
+impl HlslType for Vector4<f32> {
+    const SIZE: usize = 16;
+    const ALIGN_TO_REGISTER: bool = false;
+
+    fn write_hlsl(&self, dst: &mut [u8]) {
+        write_f32s(dst, &[self.x, self.y, self.z, self.w]);
+    }
+}
+
+impl HlslType for Matrix2<f32> {
+    const SIZE: usize = REGISTER_SIZE + 8;
+    const ALIGN_TO_REGISTER: bool = true;
+
+    fn write_hlsl(&self, dst: &mut [u8]) {
+        self.x.write_hlsl(dst);
+        self.y.write_hlsl(&mut dst[REGISTER_SIZE..]);
+    }
+}
+
+impl HlslType for Matrix3<f32> {
+    const SIZE: usize = REGISTER_SIZE * 2 + 12;
+    const ALIGN_TO_REGISTER: bool = true;
+
+    fn write_hlsl(&self, dst: &mut [u8]) {
+        self.x.write_hlsl(dst);
+        self.y.write_hlsl(&mut dst[REGISTER_SIZE..]);
+        self.z.write_hlsl(&mut dst[REGISTER_SIZE * 2..]);
+    }
+}
+
+impl HlslType for Matrix4<f32> {
+    const SIZE: usize = REGISTER_SIZE * 4;
+    const ALIGN_TO_REGISTER: bool = true;
+
+    fn write_hlsl(&self, dst: &mut [u8]) {
+        self.x.write_hlsl(dst);
+        self.y.write_hlsl(&mut dst[REGISTER_SIZE..]);
+        self.z.write_hlsl(&mut dst[REGISTER_SIZE * 2..]);
+        self.w.write_hlsl(&mut dst[REGISTER_SIZE * 3..]);
+    }
+}
+
+impl<T: HlslType, const N: usize> HlslType for [T; N] {
+    const SIZE: usize = if N == 0 {
+        0
+    } else {
+        align_up(T::SIZE, REGISTER_SIZE) * (N - 1) + T::SIZE
+    };
+    const ALIGN_TO_REGISTER: bool = true;
+
+    fn write_hlsl(&self, dst: &mut [u8]) {
+        let stride = align_up(T::SIZE, REGISTER_SIZE);
+        for (i, element) in self.iter().enumerate() {
+            element.write_hlsl(&mut dst[i * stride..]);
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CbLayoutMismatch {
+    MissingInShader {
+        name: &'static str,
+    },
+    MissingInRust {
+        name: String,
+    },
+    Offset {
+        name: &'static str,
+        rust: usize,
+        shader: usize,
+    },
+    Size {
+        name: &'static str,
+        rust: usize,
+        shader: usize,
+    },
+    TotalSize {
+        rust: usize,
+        shader: usize,
+    },
+}
+
+pub struct ConstantBuffer<T> {
+    gpu_address: u64,
+    version: u64,
+    _marker: PhantomData<T>,
+}
+
+impl<T: ConstantBufferLayout> Default for ConstantBuffer<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: ConstantBufferLayout> ConstantBuffer<T> {
+    pub const SIZE: usize = align_up(
+        T::SIZE,
+        D3D12_CONSTANT_BUFFER_DATA_PLACEMENT_ALIGNMENT as usize,
+    );
+
+    pub fn new() -> Self {
+        ConstantBuffer {
+            gpu_address: 0,
+            version: 0,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn update(&mut self, ring: &mut UploadRing, value: &T) -> Option<u64> {
+        let allocation = ring.allocate(
+            Self::SIZE as u64,
+            D3D12_CONSTANT_BUFFER_DATA_PLACEMENT_ALIGNMENT as u64,
+        )?;
+        allocation.data.fill(0);
+        value.write_packed(allocation.data);
+
+        self.gpu_address = allocation.gpu_address;
+        self.version += 1;
+        Some(self.gpu_address)
+    }
+
+    pub fn gpu_address(&self) -> u64 {
+        self.gpu_address
+    }
+
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    pub fn validate(desc: &ConstantBufferDesc) -> Vec<CbLayoutMismatch> {
+        let mut mismatches = Vec::new();
+
+        for field in T::FIELDS {
+            let Some(variable) = desc.variable(field.name) else {
+                mismatches.push(CbLayoutMismatch::MissingInShader { name: field.name });
+                continue;
+            };
+            if variable.start_offset as usize != field.offset {
+                mismatches.push(CbLayoutMismatch::Offset {
+                    name: field.name,
+                    rust: field.offset,
+                    shader: variable.start_offset as usize,
+                });
+            }
+            if variable.size as usize != field.size {
+                mismatches.push(CbLayoutMismatch::Size {
+                    name: field.name,
+                    rust: field.size,
+                    shader: variable.size as usize,
+                });
+            }
+        }
+
+        for variable in &desc.variables {
+            if !T::FIELDS.iter().any(|f| f.name == variable.name) {
+                mismatches.push(CbLayoutMismatch::MissingInRust {
+                    name: variable.name.clone(),
+                });
+            }
+        }
+
+        let rust_size = align_up(T::SIZE, REGISTER_SIZE);
+        if rust_size != desc.size as usize {
+            mismatches.push(CbLayoutMismatch::TotalSize {
+                rust: rust_size,
+                shader: desc.size as usize,
+            });
+        }
+
+        mismatches
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{Matrix2, Matrix3, Vector3, Vector4};
+
+    use super::*;
+
+    #[repr(C)]
+    #[derive(ConstantBufferLayout)]
+    struct Float3ThenFloat {
+        direction: Vector3<f32>,
+        intensity: f32,
+        color: Vector3<f32>,
+        extra: Vector2<f32>,
+    }
+
+    #[repr(C)]
+    #[derive(ConstantBufferLayout)]
+    struct Matrices {
+        scale: f32,
+        rotation: Matrix2<f32>,
+        after_rotation: f32,
+        normal: Matrix3<f32>,
+        after_normal: f32,
+    }
+
+    #[repr(C)]
+    #[derive(ConstantBufferLayout)]
+    struct Arrays {
+        weights: [f32; 3],
+        after_weights: f32,
+        offsets: [Vector3<f32>; 2],
+        count: u32,
+    }
+
+    #[repr(C)]
+    #[derive(ConstantBufferLayout)]
+    struct Light {
+        direction: Vector3<f32>,
+        intensity: f32,
+    }
+
+    #[repr(C)]
+    #[derive(ConstantBufferLayout)]
+    struct Nested {
+        enabled: bool,
+        light: Light,
+        lights: [Light; 2],
+        #[cbuffer(name = "ambient_color")]
+        ambient: Vector4<f32>,
+    }
+
+    fn offsets<T: ConstantBufferLayout>() -> Vec<(&'static str, usize, usize)> {
+        T::FIELDS
+            .iter()
+            .map(|f| (f.name, f.offset, f.size))
+            .collect()
+    }
+
+    fn f32_at(bytes: &[u8], offset: usize) -> f32 {
+        f32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn float_packs_after_float3() {
+        assert_eq!(
+            offsets::<Float3ThenFloat>(),
+            [
+                ("direction", 0, 12),
+                ("intensity", 12, 4),
+                ("color", 16, 12),
+                ("extra", 32, 8),
+            ]
+        );
+        assert_eq!(Float3ThenFloat::SIZE, 40);
+        assert_eq!(ConstantBuffer::<Float3ThenFloat>::SIZE, 256);
+    }
+
+    #[test]
+    fn matrices_start_on_registers_and_pad_rows() {
+        assert_eq!(
+            offsets::<Matrices>(),
+            [
+                ("scale", 0, 4),
+                ("rotation", 16, 24),
+                ("after_rotation", 40, 4),
+                ("normal", 48, 44),
+                ("after_normal", 92, 4),
+            ]
+        );
+    }
+
+    #[test]
+    fn array_elements_use_register_stride() {
+        assert_eq!(
+            offsets::<Arrays>(),
+            [
+                ("weights", 0, 36),
+                ("after_weights", 36, 4),
+                ("offsets", 48, 28),
+                ("count", 76, 4),
+            ]
+        );
+
+        let value = Arrays {
+            weights: [1., 2., 3.],
+            after_weights: 4.,
+            offsets: [Vector3::new(5., 6., 7.), Vector3::new(8., 9., 10.)],
+            count: 11,
+        };
+        let mut bytes = vec![0u8; Arrays::SIZE];
+        value.write_packed(&mut bytes);
+        assert_eq!(f32_at(&bytes, 0), 1.);
+        assert_eq!(f32_at(&bytes, 16), 2.);
+        assert_eq!(f32_at(&bytes, 32), 3.);
+        assert_eq!(f32_at(&bytes, 36), 4.);
+        assert_eq!(f32_at(&bytes, 48), 5.);
+        assert_eq!(f32_at(&bytes, 64), 8.);
+        assert_eq!(f32_at(&bytes, 72), 10.);
+        assert_eq!(u32::from_le_bytes(bytes[76..80].try_into().unwrap()), 11);
+    }
+
+    #[test]
+    fn nested_structs_align_and_pack() {
+        assert_eq!(
+            offsets::<Nested>(),
+            [
+                ("enabled", 0, 4),
+                ("light", 16, 16),
+                ("lights", 32, 32),
+                ("ambient_color", 64, 16),
+            ]
+        );
+
+        let light = |i: f32| Light {
+            direction: Vector3::new(i, i + 1., i + 2.),
+            intensity: i + 3.,
+        };
+        let value = Nested {
+            enabled: true,
+            light: light(1.),
+            lights: [light(10.), light(20.)],
+            ambient: Vector4::new(0.1, 0.2, 0.3, 0.4),
+        };
+        let mut bytes = vec![0u8; Nested::SIZE];
+        value.write_packed(&mut bytes);
+        assert_eq!(u32::from_le_bytes(bytes[0..4].try_into().unwrap()), 1);
+        assert_eq!(f32_at(&bytes, 28), 4.);
+        assert_eq!(f32_at(&bytes, 48), 20.);
+        assert_eq!(f32_at(&bytes, 76), 0.4);
+    }
+
+    #[test]
+    fn hlsl_offset_only_moves_on_straddle_or_alignment() {
+        assert_eq!(hlsl_offset(4, 8, false), 4);
+        assert_eq!(hlsl_offset(12, 8, false), 16);
+        assert_eq!(hlsl_offset(12, 4, false), 12);
+        assert_eq!(hlsl_offset(4, 4, true), 16);
+        assert_eq!(hlsl_offset(32, 64, true), 32);
+    }
+}
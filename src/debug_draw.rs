@@ -0,0 +1,684 @@
+use cgmath::{EuclideanSpace, Matrix4, Point3, SquareMatrix, Transform, Vector3, Vector4};
+use windows::Win32::Graphics::Direct3D12::*;
+
+use crate::command_sink::CommandSink;
+use crate::skinning::upload_vertices;
+use crate::upload_ring::UploadRing;
+use crate::vertex_layout::VertexLayout;
+
+pub const MAX_DEBUG_LINE_VERTICES: usize = 65536;
+pub const SPHERE_SEGMENTS: usize = 32;
+
+const BOX_EDGES: [[usize; 2]; 12] = [
+    [0, 1],
+    [2, 3],
+    [4, 5],
+    [6, 7],
+    [0, 2],
+    [1, 3],
+    [4, 6],
+    [5, 7],
+    [0, 4],
+    [1, 5],
+    [2, 6],
+    [3, 7],
+];
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, VertexLayout)]
+pub struct DebugLineVertex {
+    #[vertex(semantic = "POSITION")]
+    pub position: Vector3<f32>,
+    #[vertex(semantic = "COLOR")]
+    pub color: Vector4<f32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DebugDepth {
+    Tested,
+    Overlay,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DebugStyle {
+    pub color: Vector4<f32>,
+    pub duration: f32,
+    pub depth: DebugDepth,
+}
+
+impl Default for DebugStyle {
+    fn default() -> Self {
+        DebugStyle {
+            color: Vector4::new(1., 1., 1., 1.),
+            duration: 0.,
+            depth: DebugDepth::Tested,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DebugShape {
+    Line {
+        from: Point3<f32>,
+        to: Point3<f32>,
+    },
+    Box {
+        transform: Matrix4<f32>,
+    },
+    Sphere {
+        center: Point3<f32>,
+        radius: f32,
+    },
+    Frustum {
+        inverse_view_projection: Matrix4<f32>,
+    },
+    Axes {
+        transform: Matrix4<f32>,
+        size: f32,
+    },
+    Grid {
+        center: Point3<f32>,
+        size: f32,
+        divisions: u32,
+    },
+}
+
+impl DebugShape {
+    pub fn tessellate(&self, color: Vector4<f32>, vertices: &mut Vec<DebugLineVertex>) {
+        let mut line = |from: Point3<f32>, to: Point3<f32>, color: Vector4<f32>| {
+            vertices.push(DebugLineVertex {
+                position: from.to_vec(),
+                color,
+            });
+            vertices.push(DebugLineVertex {
+                position: to.to_vec(),
+                color,
+            });
+        };
+        match *self {
+            DebugShape::Line { from, to } => line(from, to, color),
+            DebugShape::Box { transform } => {
+                let corners = box_corners(|x, y, z| {
+                    transform.transform_point(Point3::new(x * 2. - 1., y * 2. - 1., z * 2. - 1.))
+                });
+                for [a, b] in BOX_EDGES {
+                    line(corners[a], corners[b], color);
+                }
+            }
+            DebugShape::Sphere { center, radius } => {
+                let axes = [
+                    (Vector3::unit_x(), Vector3::unit_y()),
+                    (Vector3::unit_y(), Vector3::unit_z()),
+                    (Vector3::unit_z(), Vector3::unit_x()),
+                ];
+                for (u, v) in axes {
+                    let point = |i: usize| {
+                        let angle = i as f32 / SPHERE_SEGMENTS as f32 * std::f32::consts::TAU;
+                        center + (u * angle.cos() + v * angle.sin()) * radius
+                    };
+                    for i in 0..SPHERE_SEGMENTS {
+                        line(point(i), point(i + 1), color);
+                    }
+                }
+            }
+            DebugShape::Frustum {
+                inverse_view_projection,
+            } => {
+                let corners = box_corners(|x, y, z| {
+                    let clip =
+                        inverse_view_projection * Vector4::new(x * 2. - 1., y * 2. - 1., z, 1.);
+                    Point3::from_homogeneous(clip)
+                });
+                for [a, b] in BOX_EDGES {
+                    line(corners[a], corners[b], color);
+                }
+            }
+            DebugShape::Axes { transform, size } => {
+                let origin = transform.transform_point(Point3::origin());
+                let axes = [
+                    (Vector3::unit_x(), Vector4::new(1., 0., 0., color.w)),
+                    (Vector3::unit_y(), Vector4::new(0., 1., 0., color.w)),
+                    (Vector3::unit_z(), Vector4::new(0., 0., 1., color.w)),
+                ];
+                for (axis, axis_color) in axes {
+                    let end = transform.transform_point(Point3::from_vec(axis * size));
+                    line(origin, end, axis_color);
+                }
+            }
+            DebugShape::Grid {
+                center,
+                size,
+                divisions,
+            } => {
+                let divisions = divisions.max(1);
+                let half = size * 0.5;
+                for i in 0..=divisions {
+                    let offset = i as f32 / divisions as f32 * size - half;
+                    line(
+                        center + Vector3::new(offset, 0., -half),
+                        center + Vector3::new(offset, 0., half),
+                        color,
+                    );
+                    line(
+                        center + Vector3::new(-half, 0., offset),
+                        center + Vector3::new(half, 0., offset),
+                        color,
+                    );
+                }
+            }
+        }
+    }
+}
+
+fn box_corners<F: Fn(f32, f32, f32) -> Point3<f32>>(corner: F) -> [Point3<f32>; 8] {
+    std::array::from_fn(|i| corner((i & 1) as f32, ((i >> 1) & 1) as f32, ((i >> 2) & 1) as f32))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DebugPrimitive {
+    pub shape: DebugShape,
+    pub style: DebugStyle,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DebugLineDraw {
+    pub depth: DebugDepth,
+    pub start_vertex: u32,
+    pub vertex_count: u32,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DebugGeometry {
+    pub vertices: Vec<DebugLineVertex>,
+    pub draws: Vec<DebugLineDraw>,
+}
+
+impl DebugGeometry {
+    pub fn upload(&self, ring: &mut UploadRing) -> Option<D3D12_VERTEX_BUFFER_VIEW> {
+        if self.vertices.is_empty() {
+            return None;
+        }
+        upload_vertices(ring, &self.vertices)
+    }
+
+    pub fn submit<S, F>(&self, sink: &S, vertex_buffer: D3D12_VERTEX_BUFFER_VIEW, mut bind: F)
+    where
+        S: CommandSink + ?Sized,
+        F: FnMut(DebugDepth),
+    {
+        sink.set_vertex_buffers(0, &[vertex_buffer]);
+        for draw in &self.draws {
+            bind(draw.depth);
+            sink.draw_instanced(draw.vertex_count, 1, draw.start_vertex, 0);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DebugDraw {
+    primitives: Vec<DebugPrimitive>,
+}
+
+impl DebugDraw {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, shape: DebugShape, style: &DebugStyle) {
+        self.primitives.push(DebugPrimitive {
+            shape,
+            style: *style,
+        });
+    }
+
+    pub fn line(&mut self, from: Point3<f32>, to: Point3<f32>, style: &DebugStyle) {
+        self.add(DebugShape::Line { from, to }, style);
+    }
+
+    pub fn aabb(&mut self, min: Point3<f32>, max: Point3<f32>, style: &DebugStyle) {
+        let transform = Matrix4::from_translation(min.midpoint(max).to_vec())
+            * Matrix4::from_nonuniform_scale(
+                (max.x - min.x) * 0.5,
+                (max.y - min.y) * 0.5,
+                (max.z - min.z) * 0.5,
+            );
+        self.add(DebugShape::Box { transform }, style);
+    }
+
+    pub fn oriented_box(
+        &mut self,
+        transform: &Matrix4<f32>,
+        half_extents: Vector3<f32>,
+        style: &DebugStyle,
+    ) {
+        let transform = transform
+            * Matrix4::from_nonuniform_scale(half_extents.x, half_extents.y, half_extents.z);
+        self.add(DebugShape::Box { transform }, style);
+    }
+
+    pub fn sphere(&mut self, center: Point3<f32>, radius: f32, style: &DebugStyle) {
+        self.add(DebugShape::Sphere { center, radius }, style);
+    }
+
+    pub fn frustum(&mut self, view_projection: &Matrix4<f32>, style: &DebugStyle) {
+        if let Some(inverse_view_projection) = view_projection.invert() {
+            self.add(
+                DebugShape::Frustum {
+                    inverse_view_projection,
+                },
+                style,
+            );
+        }
+    }
+
+    pub fn axes(&mut self, transform: &Matrix4<f32>, size: f32, style: &DebugStyle) {
+        self.add(
+            DebugShape::Axes {
+                transform: *transform,
+                size,
+            },
+            style,
+        );
+    }
+
+    pub fn grid(&mut self, center: Point3<f32>, size: f32, divisions: u32, style: &DebugStyle) {
+        self.add(
+            DebugShape::Grid {
+                center,
+                size,
+                divisions,
+            },
+            style,
+        );
+    }
+
+    pub fn len(&self) -> usize {
+        self.primitives.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.primitives.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.primitives.clear();
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        self.primitives.retain_mut(|primitive| {
+            primitive.style.duration -= dt;
+            primitive.style.duration > 0.
+        });
+    }
+
+    pub fn build(&self) -> DebugGeometry {
+        let mut geometry = DebugGeometry::default();
+        for depth in [DebugDepth::Tested, DebugDepth::Overlay] {
+            let start_vertex = geometry.vertices.len();
+            for primitive in &self.primitives {
+                if primitive.style.depth != depth {
+                    continue;
+                }
+                let mark = geometry.vertices.len();
+                primitive
+                    .shape
+                    .tessellate(primitive.style.color, &mut geometry.vertices);
+                if geometry.vertices.len() > MAX_DEBUG_LINE_VERTICES {
+                    geometry.vertices.truncate(mark);
+                    break;
+                }
+            }
+            let vertex_count = geometry.vertices.len() - start_vertex;
+            if vertex_count > 0 {
+                geometry.draws.push(DebugLineDraw {
+                    depth,
+                    start_vertex: start_vertex as u32,
+                    vertex_count: vertex_count as u32,
+                });
+            }
+        }
+        geometry
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::Camera;
+    use crate::command_sink::{RecordedCommand, RecordedCommands};
+    use cgmath::{Deg, InnerSpace, MetricSpace};
+
+    const EPSILON: f32 = 1e-4;
+    const RED: Vector4<f32> = Vector4::new(1., 0., 0., 1.);
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < EPSILON,
+            "expected {expected}, got {actual}"
+        );
+    }
+
+    fn assert_vector_close(actual: Vector3<f32>, expected: Vector3<f32>) {
+        assert!(
+            (actual - expected).magnitude() < EPSILON,
+            "expected {expected:?}, got {actual:?}"
+        );
+    }
+
+    fn tessellate(shape: DebugShape) -> Vec<DebugLineVertex> {
+        let mut vertices = Vec::new();
+        shape.tessellate(RED, &mut vertices);
+        vertices
+    }
+
+    fn segments(vertices: &[DebugLineVertex]) -> Vec<[Vector3<f32>; 2]> {
+        vertices
+            .chunks(2)
+            .map(|pair| [pair[0].position, pair[1].position])
+            .collect()
+    }
+
+    fn style(depth: DebugDepth) -> DebugStyle {
+        DebugStyle {
+            depth,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn line_emits_one_segment() {
+        let vertices = tessellate(DebugShape::Line {
+            from: Point3::new(1., 2., 3.),
+            to: Point3::new(4., 5., 6.),
+        });
+        assert_eq!(
+            vertices,
+            [
+                DebugLineVertex {
+                    position: Vector3::new(1., 2., 3.),
+                    color: RED,
+                },
+                DebugLineVertex {
+                    position: Vector3::new(4., 5., 6.),
+                    color: RED,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn aabb_outlines_twelve_axis_aligned_edges() {
+        let mut draw = DebugDraw::new();
+        let (min, max) = (Point3::new(-1., 0., 2.), Point3::new(3., 1., 4.));
+        draw.aabb(min, max, &DebugStyle::default());
+        let geometry = draw.build();
+        let edges = segments(&geometry.vertices);
+        assert_eq!(edges.len(), 12);
+
+        let mut lengths = Vec::new();
+        for [a, b] in &edges {
+            for p in [a, b] {
+                assert!([min.x, max.x].iter().any(|&x| (p.x - x).abs() < EPSILON));
+                assert!([min.y, max.y].iter().any(|&y| (p.y - y).abs() < EPSILON));
+                assert!([min.z, max.z].iter().any(|&z| (p.z - z).abs() < EPSILON));
+            }
+            let delta = b - a;
+            // Exactly one coordinate changes along each edge.
+            let changed = [delta.x, delta.y, delta.z]
+                .iter()
+                .filter(|d| d.abs() > EPSILON)
+                .count();
+            assert_eq!(changed, 1);
+            lengths.push(delta.magnitude());
+        }
+        for extent in [4., 1., 2.] {
+            let count = lengths
+                .iter()
+                .filter(|&&l| (l - extent).abs() < EPSILON)
+                .count();
+            assert_eq!(count, 4, "edges of length {extent}");
+        }
+        // No edge is emitted twice.
+        for (i, a) in edges.iter().enumerate() {
+            for b in &edges[i + 1..] {
+                assert!((a[0] - b[0]).magnitude() > EPSILON || (a[1] - b[1]).magnitude() > EPSILON);
+            }
+        }
+    }
+
+    #[test]
+    fn oriented_box_applies_transform_to_half_extents() {
+        let mut draw = DebugDraw::new();
+        let transform =
+            Matrix4::from_translation(Vector3::new(0., 0., 5.)) * Matrix4::from_angle_z(Deg(90.));
+        draw.oriented_box(
+            &transform,
+            Vector3::new(2., 1., 0.5),
+            &DebugStyle::default(),
+        );
+        let geometry = draw.build();
+        assert_eq!(geometry.vertices.len(), 24);
+        // The box's x half extent now lies along world y.
+        for vertex in &geometry.vertices {
+            let p = vertex.position;
+            assert_close(p.x.abs(), 1.);
+            assert_close(p.y.abs(), 2.);
+            assert_close((p.z - 5.).abs(), 0.5);
+        }
+    }
+
+    #[test]
+    fn sphere_draws_three_closed_great_circles() {
+        let center = Point3::new(1., -2., 3.);
+        let vertices = tessellate(DebugShape::Sphere { center, radius: 2. });
+        assert_eq!(vertices.len(), 3 * SPHERE_SEGMENTS * 2);
+        for vertex in &vertices {
+            assert_close(Point3::from_vec(vertex.position).distance(center), 2.);
+        }
+        for ring in segments(&vertices).chunks(SPHERE_SEGMENTS) {
+            assert_vector_close(ring[SPHERE_SEGMENTS - 1][1], ring[0][0]);
+            for pair in ring.windows(2) {
+                assert_vector_close(pair[0][1], pair[1][0]);
+            }
+        }
+    }
+
+    #[test]
+    fn frustum_corners_project_to_clip_space_corners() {
+        for reverse_z in [false, true] {
+            let camera = Camera {
+                reverse_z,
+                ..Default::default()
+            };
+            let view_projection = camera.view_projection();
+            let mut draw = DebugDraw::new();
+            draw.frustum(&view_projection, &DebugStyle::default());
+            let geometry = draw.build();
+            assert_eq!(geometry.vertices.len(), 24);
+            for vertex in &geometry.vertices {
+                let clip = view_projection * vertex.position.extend(1.);
+                let ndc = clip.truncate() / clip.w;
+                assert_close(ndc.x.abs(), 1.);
+                assert_close(ndc.y.abs(), 1.);
+                assert!(ndc.z.abs() < EPSILON || (ndc.z - 1.).abs() < EPSILON);
+            }
+        }
+    }
+
+    #[test]
+    fn frustum_edges_widen_away_from_the_eye() {
+        let camera = Camera::default();
+        let vertices = tessellate(DebugShape::Frustum {
+            inverse_view_projection: camera.view_projection().invert().unwrap(),
+        });
+        let edges = segments(&vertices);
+        // The first four edges run along x/y on the near and far planes.
+        let near = (edges[0][1] - edges[0][0]).magnitude();
+        let far = (edges[2][1] - edges[2][0]).magnitude();
+        assert!(far > near * 10., "near {near}, far {far}");
+
+        let mut draw = DebugDraw::new();
+        draw.frustum(&Matrix4::from_scale(0.), &DebugStyle::default());
+        assert!(draw.is_empty());
+    }
+
+    #[test]
+    fn axes_are_colored_per_axis() {
+        let transform = Matrix4::from_translation(Vector3::new(1., 1., 1.));
+        let mut vertices = Vec::new();
+        DebugShape::Axes {
+            transform,
+            size: 2.,
+        }
+        .tessellate(Vector4::new(1., 1., 1., 0.5), &mut vertices);
+        let ends: Vec<(Vector3<f32>, Vector4<f32>)> = vertices
+            .chunks(2)
+            .map(|pair| {
+                assert_eq!(pair[0].position, Vector3::new(1., 1., 1.));
+                (pair[1].position, pair[1].color)
+            })
+            .collect();
+        assert_eq!(
+            ends,
+            [
+                (Vector3::new(3., 1., 1.), Vector4::new(1., 0., 0., 0.5)),
+                (Vector3::new(1., 3., 1.), Vector4::new(0., 1., 0., 0.5)),
+                (Vector3::new(1., 1., 3.), Vector4::new(0., 0., 1., 0.5)),
+            ]
+        );
+    }
+
+    #[test]
+    fn grid_spans_size_with_divisions() {
+        let vertices = tessellate(DebugShape::Grid {
+            center: Point3::new(0., 1., 0.),
+            size: 4.,
+            divisions: 4,
+        });
+        assert_eq!(vertices.len(), 5 * 2 * 2);
+        for vertex in &vertices {
+            assert_eq!(vertex.position.y, 1.);
+            assert!(vertex.position.x.abs() <= 2. && vertex.position.z.abs() <= 2.);
+        }
+        let xs: Vec<f32> = segments(&vertices)
+            .iter()
+            .step_by(2)
+            .map(|[a, _]| a.x)
+            .collect();
+        assert_eq!(xs, [-2., -1., 0., 1., 2.]);
+
+        let single = tessellate(DebugShape::Grid {
+            center: Point3::new(0., 0., 0.),
+            size: 1.,
+            divisions: 0,
+        });
+        assert_eq!(single.len(), 2 * 2 * 2);
+    }
+
+    #[test]
+    fn build_groups_by_depth_mode() {
+        let mut draw = DebugDraw::new();
+        let from = Point3::new(0., 0., 0.);
+        draw.line(from, Point3::new(1., 0., 0.), &style(DebugDepth::Overlay));
+        draw.line(from, Point3::new(2., 0., 0.), &style(DebugDepth::Tested));
+        draw.sphere(from, 1., &style(DebugDepth::Overlay));
+        let geometry = draw.build();
+        let overlay = 2 + 3 * SPHERE_SEGMENTS * 2;
+        assert_eq!(
+            geometry.draws,
+            [
+                DebugLineDraw {
+                    depth: DebugDepth::Tested,
+                    start_vertex: 0,
+                    vertex_count: 2,
+                },
+                DebugLineDraw {
+                    depth: DebugDepth::Overlay,
+                    start_vertex: 2,
+                    vertex_count: overlay as u32,
+                },
+            ]
+        );
+        assert_eq!(geometry.vertices[1].position, Vector3::new(2., 0., 0.));
+        assert_eq!(geometry.vertices[3].position, Vector3::new(1., 0., 0.));
+        assert_eq!(DebugDraw::new().build(), DebugGeometry::default());
+    }
+
+    #[test]
+    fn build_drops_shapes_past_the_vertex_budget() {
+        let mut draw = DebugDraw::new();
+        let from = Point3::new(0., 0., 0.);
+        for _ in 0..MAX_DEBUG_LINE_VERTICES / 2 - 1 {
+            draw.line(from, from, &DebugStyle::default());
+        }
+        draw.aabb(from, Point3::new(1., 1., 1.), &DebugStyle::default());
+        draw.line(from, from, &DebugStyle::default());
+        let geometry = draw.build();
+        // The box does not fit and ends the depth group; the trailing line is dropped with it.
+        assert_eq!(geometry.vertices.len(), MAX_DEBUG_LINE_VERTICES - 2);
+        assert_eq!(geometry.draws.len(), 1);
+    }
+
+    #[test]
+    fn update_expires_timed_shapes() {
+        let mut draw = DebugDraw::new();
+        let from = Point3::new(0., 0., 0.);
+        draw.line(from, from, &DebugStyle::default());
+        draw.line(
+            from,
+            from,
+            &DebugStyle {
+                duration: 1.,
+                ..Default::default()
+            },
+        );
+        assert_eq!(draw.len(), 2);
+        draw.update(0.5);
+        assert_eq!(draw.len(), 1);
+        draw.update(0.5);
+        assert!(draw.is_empty());
+
+        draw.line(from, from, &DebugStyle::default());
+        draw.clear();
+        assert!(draw.is_empty());
+    }
+
+    #[test]
+    fn submit_binds_each_depth_mode() {
+        let mut draw = DebugDraw::new();
+        let from = Point3::new(0., 0., 0.);
+        draw.line(from, from, &style(DebugDepth::Tested));
+        draw.aabb(from, Point3::new(1., 1., 1.), &style(DebugDepth::Overlay));
+        let sink = RecordedCommands::new();
+        let vertex_buffer = D3D12_VERTEX_BUFFER_VIEW {
+            BufferLocation: 0x1000,
+            SizeInBytes: 26 * std::mem::size_of::<DebugLineVertex>() as u32,
+            StrideInBytes: std::mem::size_of::<DebugLineVertex>() as u32,
+        };
+        let mut bound = Vec::new();
+        draw.build()
+            .submit(&sink, vertex_buffer, |depth| bound.push(depth));
+        assert_eq!(bound, [DebugDepth::Tested, DebugDepth::Overlay]);
+        assert_eq!(
+            sink.take(),
+            [
+                RecordedCommand::SetVertexBuffers {
+                    start_slot: 0,
+                    views: vec![vertex_buffer],
+                },
+                RecordedCommand::DrawInstanced {
+                    vertex_count: 2,
+                    instance_count: 1,
+                    start_vertex: 0,
+                    start_instance: 0,
+                },
+                RecordedCommand::DrawInstanced {
+                    vertex_count: 24,
+                    instance_count: 1,
+                    start_vertex: 2,
+                    start_instance: 0,
+                },
+            ]
+        );
+    }
+}
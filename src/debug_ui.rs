@@ -0,0 +1,732 @@
+use std::time::Instant;
+
+use egui::epaint::Primitive;
+use egui::{ClippedPrimitive, Context, Event, Modifiers, Pos2, RawInput, Rect, TextureId, Vec2};
+use windows::Win32::Foundation::RECT;
+use windows::Win32::Graphics::Direct3D12::*;
+use winit::event::{
+    ElementState, KeyboardInput, ModifiersState, MouseButton, MouseScrollDelta, VirtualKeyCode,
+    WindowEvent,
+};
+
+use crate::command_sink::CommandSink;
+use crate::skinning::{upload_indices, upload_vertices};
+use crate::upload_ring::UploadRing;
+use crate::vertex_layout::VertexLayout;
+
+pub const MAX_UI_VERTICES: usize = 65536;
+pub const MAX_UI_INDICES: usize = MAX_UI_VERTICES * 3;
+pub const MAX_TEXTURE_SIDE: usize = 2048;
+
+const POINTS_PER_SCROLL_LINE: f32 = 50.0;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, VertexLayout)]
+pub struct UiVertex {
+    #[vertex(semantic = "POSITION")]
+    pub position: [f32; 2],
+    #[vertex(semantic = "TEXCOORD")]
+    pub uv: [f32; 2],
+    #[vertex(semantic = "COLOR", normalized)]
+    pub color: [u8; 4],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UiDraw {
+    pub texture: TextureId,
+    pub clip: RECT,
+    pub start_index: u32,
+    pub index_count: u32,
+    pub base_vertex: i32,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UiDrawList {
+    pub vertices: Vec<UiVertex>,
+    pub indices: Vec<u32>,
+    pub draws: Vec<UiDraw>,
+}
+
+impl UiDrawList {
+    pub fn from_primitives(
+        primitives: &[ClippedPrimitive],
+        pixels_per_point: f32,
+        width: u32,
+        height: u32,
+    ) -> Self {
+        let mut list = UiDrawList::default();
+        for primitive in primitives {
+            let Primitive::Mesh(mesh) = &primitive.primitive else {
+                continue;
+            };
+            if mesh.indices.is_empty()
+                || list.vertices.len() + mesh.vertices.len() > MAX_UI_VERTICES
+                || list.indices.len() + mesh.indices.len() > MAX_UI_INDICES
+            {
+                continue;
+            }
+
+            let clip = primitive.clip_rect;
+            let clip = RECT {
+                left: (clip.min.x * pixels_per_point)
+                    .round()
+                    .clamp(0., width as f32) as i32,
+                top: (clip.min.y * pixels_per_point)
+                    .round()
+                    .clamp(0., height as f32) as i32,
+                right: (clip.max.x * pixels_per_point)
+                    .round()
+                    .clamp(0., width as f32) as i32,
+                bottom: (clip.max.y * pixels_per_point)
+                    .round()
+                    .clamp(0., height as f32) as i32,
+            };
+            if clip.right <= clip.left || clip.bottom <= clip.top {
+                continue;
+            }
+
+            let base_vertex = list.vertices.len() as i32;
+            let start_index = list.indices.len() as u32;
+            list.vertices
+                .extend(mesh.vertices.iter().map(|vertex| UiVertex {
+                    position: [
+                        vertex.pos.x * pixels_per_point,
+                        vertex.pos.y * pixels_per_point,
+                    ],
+                    uv: [vertex.uv.x, vertex.uv.y],
+                    color: vertex.color.to_array(),
+                }));
+            list.indices.extend_from_slice(&mesh.indices);
+            list.draws.push(UiDraw {
+                texture: mesh.texture_id,
+                clip,
+                start_index,
+                index_count: mesh.indices.len() as u32,
+                base_vertex,
+            });
+        }
+        list
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.draws.is_empty()
+    }
+
+    pub fn upload(
+        &self,
+        ring: &mut UploadRing,
+    ) -> Option<(D3D12_VERTEX_BUFFER_VIEW, D3D12_INDEX_BUFFER_VIEW)> {
+        if self.is_empty() {
+            return None;
+        }
+        let vertex_buffer = upload_vertices(ring, &self.vertices)?;
+        let index_buffer = upload_indices(ring, &self.indices)?;
+        Some((vertex_buffer, index_buffer))
+    }
+
+    pub fn submit<S, F>(
+        &self,
+        sink: &S,
+        vertex_buffer: D3D12_VERTEX_BUFFER_VIEW,
+        index_buffer: D3D12_INDEX_BUFFER_VIEW,
+        texture_handle: F,
+    ) where
+        S: CommandSink + ?Sized,
+        F: Fn(TextureId) -> Option<D3D12_GPU_DESCRIPTOR_HANDLE>,
+    {
+        sink.set_vertex_buffers(0, &[vertex_buffer]);
+        sink.set_index_buffer(&index_buffer);
+        let mut bound_texture = None;
+        for draw in &self.draws {
+            let Some(handle) = texture_handle(draw.texture) else {
+                continue;
+            };
+            if bound_texture != Some(draw.texture) {
+                sink.set_descriptor_table(0, handle);
+                bound_texture = Some(draw.texture);
+            }
+            sink.set_scissor_rect(&draw.clip);
+            sink.draw_indexed_instanced(draw.index_count, 1, draw.start_index, draw.base_vertex, 0);
+        }
+    }
+}
+
+pub struct UiFrame {
+    pub draw_list: UiDrawList,
+    pub textures: egui::TexturesDelta,
+}
+
+pub struct DebugUi {
+    context: Context,
+    events: Vec<Event>,
+    modifiers: Modifiers,
+    pointer: Pos2,
+    pixels_per_point: f32,
+    start: Instant,
+    visible: bool,
+}
+
+impl DebugUi {
+    pub fn new(pixels_per_point: f32) -> Self {
+        DebugUi {
+            context: Context::default(),
+            events: Vec::new(),
+            modifiers: Modifiers::default(),
+            pointer: Pos2::ZERO,
+            pixels_per_point,
+            start: Instant::now(),
+            visible: true,
+        }
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    pub fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+        if !visible {
+            self.events.clear();
+        }
+    }
+
+    /// Feeds a window event to the UI, returning whether the UI consumed it.
+    /// A hidden UI keeps tracking the pointer and modifiers but queues and
+    /// consumes nothing, so its last frame cannot swallow input.
+    pub fn handle_window_event(&mut self, event: &WindowEvent) -> bool {
+        let consumed = self.queue_event(event);
+        if !self.visible {
+            self.events.clear();
+            return false;
+        }
+        consumed
+    }
+
+    fn queue_event(&mut self, event: &WindowEvent) -> bool {
+        match event {
+            WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                self.pixels_per_point = *scale_factor as f32;
+                false
+            }
+            WindowEvent::ModifiersChanged(state) => {
+                self.modifiers = modifiers(*state);
+                false
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                self.pointer = Pos2::new(
+                    position.x as f32 / self.pixels_per_point,
+                    position.y as f32 / self.pixels_per_point,
+                );
+                self.events.push(Event::PointerMoved(self.pointer));
+                false
+            }
+            WindowEvent::CursorLeft { .. } => {
+                self.events.push(Event::PointerGone);
+                false
+            }
+            WindowEvent::MouseInput { state, button, .. } => {
+                let Some(button) = pointer_button(*button) else {
+                    return false;
+                };
+                let pressed = *state == ElementState::Pressed;
+                self.events.push(Event::PointerButton {
+                    pos: self.pointer,
+                    button,
+                    pressed,
+                    modifiers: self.modifiers,
+                });
+                pressed && self.context.wants_pointer_input()
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                let delta = match delta {
+                    MouseScrollDelta::LineDelta(x, y) => Vec2::new(*x, *y) * POINTS_PER_SCROLL_LINE,
+                    MouseScrollDelta::PixelDelta(position) => {
+                        Vec2::new(position.x as f32, position.y as f32) / self.pixels_per_point
+                    }
+                };
+                self.events.push(Event::Scroll(delta));
+                self.context.wants_pointer_input()
+            }
+            WindowEvent::ReceivedCharacter(ch) => {
+                if ch.is_control() {
+                    return false;
+                }
+                self.events.push(Event::Text(ch.to_string()));
+                self.context.wants_keyboard_input()
+            }
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        virtual_keycode: Some(key),
+                        state,
+                        ..
+                    },
+                ..
+            } => {
+                let Some(key) = key_code(*key) else {
+                    return false;
+                };
+                let pressed = *state == ElementState::Pressed;
+                self.events.push(Event::Key {
+                    key,
+                    pressed,
+                    repeat: false,
+                    modifiers: self.modifiers,
+                });
+                pressed && self.context.wants_keyboard_input()
+            }
+            _ => false,
+        }
+    }
+
+    pub fn run(&mut self, width: u32, height: u32, build: impl FnOnce(&Context)) -> UiFrame {
+        let raw_input = RawInput {
+            screen_rect: Some(Rect::from_min_size(
+                Pos2::ZERO,
+                Vec2::new(width as f32, height as f32) / self.pixels_per_point,
+            )),
+            pixels_per_point: Some(self.pixels_per_point),
+            max_texture_side: Some(MAX_TEXTURE_SIDE),
+            time: Some(self.start.elapsed().as_secs_f64()),
+            modifiers: self.modifiers,
+            events: std::mem::take(&mut self.events),
+            ..Default::default()
+        };
+        let output = self.context.run(raw_input, build);
+        let primitives = self.context.tessellate(output.shapes);
+        UiFrame {
+            draw_list: UiDrawList::from_primitives(
+                &primitives,
+                self.pixels_per_point,
+                width,
+                height,
+            ),
+            textures: output.textures_delta,
+        }
+    }
+}
+
+fn modifiers(state: ModifiersState) -> Modifiers {
+    Modifiers {
+        alt: state.alt(),
+        ctrl: state.ctrl(),
+        shift: state.shift(),
+        mac_cmd: false,
+        command: state.ctrl(),
+    }
+}
+
+fn pointer_button(button: MouseButton) -> Option<egui::PointerButton> {
+    match button {
+        MouseButton::Left => Some(egui::PointerButton::Primary),
+        MouseButton::Right => Some(egui::PointerButton::Secondary),
+        MouseButton::Middle => Some(egui::PointerButton::Middle),
+        MouseButton::Other(_) => None,
+    }
+}
+
+fn key_code(key: VirtualKeyCode) -> Option<egui::Key> {
+    use egui::Key;
+    Some(match key {
+        VirtualKeyCode::Down => Key::ArrowDown,
+        VirtualKeyCode::Left => Key::ArrowLeft,
+        VirtualKeyCode::Right => Key::ArrowRight,
+        VirtualKeyCode::Up => Key::ArrowUp,
+        VirtualKeyCode::Escape => Key::Escape,
+        VirtualKeyCode::Tab => Key::Tab,
+        VirtualKeyCode::Back => Key::Backspace,
+        VirtualKeyCode::Return | VirtualKeyCode::NumpadEnter => Key::Enter,
+        VirtualKeyCode::Space => Key::Space,
+        VirtualKeyCode::Insert => Key::Insert,
+        VirtualKeyCode::Delete => Key::Delete,
+        VirtualKeyCode::Home => Key::Home,
+        VirtualKeyCode::End => Key::End,
+        VirtualKeyCode::PageUp => Key::PageUp,
+        VirtualKeyCode::PageDown => Key::PageDown,
+        VirtualKeyCode::Minus | VirtualKeyCode::NumpadSubtract => Key::Minus,
+        VirtualKeyCode::Equals | VirtualKeyCode::NumpadAdd => Key::PlusEquals,
+        VirtualKeyCode::Key0 | VirtualKeyCode::Numpad0 => Key::Num0,
+        VirtualKeyCode::Key1 | VirtualKeyCode::Numpad1 => Key::Num1,
+        VirtualKeyCode::Key2 | VirtualKeyCode::Numpad2 => Key::Num2,
+        VirtualKeyCode::Key3 | VirtualKeyCode::Numpad3 => Key::Num3,
+        VirtualKeyCode::Key4 | VirtualKeyCode::Numpad4 => Key::Num4,
+        VirtualKeyCode::Key5 | VirtualKeyCode::Numpad5 => Key::Num5,
+        VirtualKeyCode::Key6 | VirtualKeyCode::Numpad6 => Key::Num6,
+        VirtualKeyCode::Key7 | VirtualKeyCode::Numpad7 => Key::Num7,
+        VirtualKeyCode::Key8 | VirtualKeyCode::Numpad8 => Key::Num8,
+        VirtualKeyCode::Key9 | VirtualKeyCode::Numpad9 => Key::Num9,
+        VirtualKeyCode::A => Key::A,
+        VirtualKeyCode::C => Key::C,
+        VirtualKeyCode::V => Key::V,
+        VirtualKeyCode::X => Key::X,
+        VirtualKeyCode::Y => Key::Y,
+        VirtualKeyCode::Z => Key::Z,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command_sink::{RecordedCommand, RecordedCommands};
+    use egui::epaint::{Color32, Mesh};
+    use winit::dpi::PhysicalPosition;
+    use winit::event::DeviceId;
+
+    fn quad(min: Pos2, max: Pos2, texture: TextureId) -> Mesh {
+        let mut mesh = Mesh::with_texture(texture);
+        mesh.add_rect_with_uv(
+            Rect::from_min_max(min, max),
+            Rect::from_min_max(Pos2::ZERO, Pos2::new(1., 1.)),
+            Color32::RED,
+        );
+        mesh
+    }
+
+    fn clipped(clip: Rect, mesh: Mesh) -> ClippedPrimitive {
+        ClippedPrimitive {
+            clip_rect: clip,
+            primitive: Primitive::Mesh(mesh),
+        }
+    }
+
+    fn screen(width: f32, height: f32) -> Rect {
+        Rect::from_min_size(Pos2::ZERO, Vec2::new(width, height))
+    }
+
+    fn device_id() -> DeviceId {
+        // SAFETY: the dummy id is only compared, never passed to the platform.
+        unsafe { DeviceId::dummy() }
+    }
+
+    fn texture_handle(texture: TextureId) -> Option<D3D12_GPU_DESCRIPTOR_HANDLE> {
+        match texture {
+            TextureId::Managed(id) => Some(D3D12_GPU_DESCRIPTOR_HANDLE {
+                ptr: 0x100 + id * 32,
+            }),
+            TextureId::User(_) => None,
+        }
+    }
+
+    /// Asserts every draw only references vertices and indices of the list.
+    fn assert_in_bounds(list: &UiDrawList) {
+        for draw in &list.draws {
+            let indices = &list.indices
+                [draw.start_index as usize..(draw.start_index + draw.index_count) as usize];
+            for &index in indices {
+                assert!((draw.base_vertex as usize + index as usize) < list.vertices.len());
+            }
+        }
+    }
+
+    #[test]
+    fn converts_meshes_to_physical_pixels() {
+        let primitives = [
+            clipped(
+                screen(100., 100.),
+                quad(
+                    Pos2::new(10., 20.),
+                    Pos2::new(30., 40.),
+                    TextureId::Managed(0),
+                ),
+            ),
+            clipped(
+                Rect::from_min_max(Pos2::new(5.2, 5.), Pos2::new(50., 60.)),
+                quad(Pos2::new(0., 0.), Pos2::new(1., 1.), TextureId::User(3)),
+            ),
+        ];
+        let list = UiDrawList::from_primitives(&primitives, 2., 150, 100);
+
+        assert_eq!(list.vertices.len(), 8);
+        assert_eq!(list.vertices[0].position, [20., 40.]);
+        assert_eq!(list.vertices[3].position, [60., 80.]);
+        assert_eq!(list.vertices[0].color, [255, 0, 0, 255]);
+        assert_eq!(list.indices.len(), 12);
+        // Indices stay mesh-local; base_vertex offsets the second mesh.
+        assert_eq!(list.indices[..6], list.indices[6..]);
+        assert_eq!(
+            list.draws,
+            [
+                UiDraw {
+                    texture: TextureId::Managed(0),
+                    clip: RECT {
+                        left: 0,
+                        top: 0,
+                        right: 150,
+                        bottom: 100,
+                    },
+                    start_index: 0,
+                    index_count: 6,
+                    base_vertex: 0,
+                },
+                UiDraw {
+                    texture: TextureId::User(3),
+                    clip: RECT {
+                        left: 10,
+                        top: 10,
+                        right: 100,
+                        bottom: 100,
+                    },
+                    start_index: 6,
+                    index_count: 6,
+                    base_vertex: 4,
+                },
+            ]
+        );
+        assert_in_bounds(&list);
+    }
+
+    #[test]
+    fn skips_empty_and_fully_clipped_meshes() {
+        let visible = quad(Pos2::new(0., 0.), Pos2::new(4., 4.), TextureId::Managed(0));
+        let primitives = [
+            clipped(screen(10., 10.), Mesh::default()),
+            clipped(
+                Rect::from_min_max(Pos2::new(20., 20.), Pos2::new(30., 30.)),
+                visible.clone(),
+            ),
+            clipped(
+                Rect::from_min_max(Pos2::new(5., 5.), Pos2::new(5., 9.)),
+                visible.clone(),
+            ),
+            clipped(screen(10., 10.), visible),
+        ];
+        let list = UiDrawList::from_primitives(&primitives, 1., 10, 10);
+        assert_eq!(list.draws.len(), 1);
+        assert_eq!(list.draws[0].base_vertex, 0);
+        assert_eq!(list.vertices.len(), 4);
+
+        assert!(UiDrawList::from_primitives(&[], 1., 10, 10).is_empty());
+    }
+
+    #[test]
+    fn drops_meshes_past_the_vertex_budget() {
+        let mut large = Mesh::with_texture(TextureId::Managed(0));
+        for i in 0..MAX_UI_VERTICES / 4 {
+            let min = Pos2::new(i as f32 % 64., 0.);
+            large.add_colored_rect(Rect::from_min_size(min, Vec2::splat(1.)), Color32::WHITE);
+        }
+        let small = quad(Pos2::new(0., 0.), Pos2::new(1., 1.), TextureId::Managed(1));
+        let primitives = [
+            clipped(screen(64., 64.), large),
+            clipped(screen(64., 64.), small),
+        ];
+        let list = UiDrawList::from_primitives(&primitives, 1., 64, 64);
+        assert_eq!(list.vertices.len(), MAX_UI_VERTICES);
+        assert_eq!(list.draws.len(), 1);
+    }
+
+    #[test]
+    fn submit_records_scissors_and_texture_changes() {
+        let clip = screen(32., 32.);
+        let primitives = [
+            clipped(
+                clip,
+                quad(Pos2::new(0., 0.), Pos2::new(1., 1.), TextureId::Managed(0)),
+            ),
+            clipped(
+                clip,
+                quad(Pos2::new(1., 1.), Pos2::new(2., 2.), TextureId::Managed(0)),
+            ),
+            clipped(
+                clip,
+                quad(Pos2::new(2., 2.), Pos2::new(3., 3.), TextureId::User(7)),
+            ),
+            clipped(
+                clip,
+                quad(Pos2::new(3., 3.), Pos2::new(4., 4.), TextureId::Managed(1)),
+            ),
+        ];
+        let list = UiDrawList::from_primitives(&primitives, 1., 32, 32);
+        let sink = RecordedCommands::new();
+        let vertex_buffer = D3D12_VERTEX_BUFFER_VIEW {
+            BufferLocation: 0x1000,
+            SizeInBytes: 1024,
+            StrideInBytes: std::mem::size_of::<UiVertex>() as u32,
+        };
+        let index_buffer = D3D12_INDEX_BUFFER_VIEW {
+            BufferLocation: 0x2000,
+            SizeInBytes: 1024,
+            Format: windows::Win32::Graphics::Dxgi::Common::DXGI_FORMAT_R32_UINT,
+        };
+        list.submit(&sink, vertex_buffer, index_buffer, texture_handle);
+
+        let scissor = RECT {
+            left: 0,
+            top: 0,
+            right: 32,
+            bottom: 32,
+        };
+        let draw = |base_vertex| RecordedCommand::DrawIndexedInstanced {
+            index_count: 6,
+            instance_count: 1,
+            start_index: base_vertex as u32 * 6 / 4,
+            base_vertex,
+            start_instance: 0,
+        };
+        let table = |ptr| RecordedCommand::SetDescriptorTable {
+            root_index: 0,
+            handle: D3D12_GPU_DESCRIPTOR_HANDLE { ptr },
+        };
+        // The user texture has no descriptor, so its draw is dropped.
+        assert_eq!(
+            sink.take(),
+            [
+                RecordedCommand::SetVertexBuffers {
+                    start_slot: 0,
+                    views: vec![vertex_buffer],
+                },
+                RecordedCommand::SetIndexBuffer(index_buffer),
+                table(0x100),
+                RecordedCommand::SetScissorRect(scissor),
+                draw(0),
+                RecordedCommand::SetScissorRect(scissor),
+                draw(4),
+                table(0x120),
+                RecordedCommand::SetScissorRect(scissor),
+                draw(12),
+            ]
+        );
+    }
+
+    #[test]
+    fn widgets_tessellate_without_a_device() {
+        let mut ui = DebugUi::new(1.5);
+        let build = |ctx: &Context| {
+            egui::Window::new("stats").show(ctx, |ui| {
+                ui.label("描画統計");
+                let _ = ui.button("reset");
+            });
+        };
+        // The first frame uploads the font atlas and sizes the window off screen.
+        let frame = ui.run(640, 480, build);
+        assert!(frame
+            .textures
+            .set
+            .iter()
+            .any(|(id, _)| *id == TextureId::Managed(0)));
+
+        let frame = ui.run(640, 480, build);
+        let list = &frame.draw_list;
+        assert!(!list.is_empty());
+        assert_in_bounds(list);
+        for draw in &list.draws {
+            assert!(draw.clip.left >= 0 && draw.clip.right <= 640);
+            assert!(draw.clip.top >= 0 && draw.clip.bottom <= 480);
+        }
+
+        let sink = RecordedCommands::new();
+        list.submit(
+            &sink,
+            D3D12_VERTEX_BUFFER_VIEW::default(),
+            D3D12_INDEX_BUFFER_VIEW::default(),
+            texture_handle,
+        );
+        assert_eq!(sink.draw_count(), list.draws.len());
+    }
+
+    /// Runs a frame with a single button, returning its rect and whether it was clicked.
+    fn button_frame(ui: &mut DebugUi) -> (Rect, bool) {
+        let mut result = (Rect::NOTHING, false);
+        ui.run(400, 300, |ctx| {
+            egui::CentralPanel::default().show(ctx, |ui| {
+                let response = ui.button("press");
+                result = (response.rect, response.clicked());
+            });
+        });
+        result
+    }
+
+    #[test]
+    fn window_events_drive_widgets() {
+        let mut ui = DebugUi::new(2.);
+        let (button, _) = button_frame(&mut ui);
+
+        // Window positions are physical pixels; egui works in points.
+        let center = button.center();
+        #[allow(deprecated)]
+        let moved = WindowEvent::CursorMoved {
+            device_id: device_id(),
+            position: PhysicalPosition::new(center.x as f64 * 2., center.y as f64 * 2.),
+            modifiers: ModifiersState::empty(),
+        };
+        assert!(!ui.handle_window_event(&moved));
+        assert_eq!(ui.pointer, center);
+        assert!(!button_frame(&mut ui).1);
+
+        let mut clicks = 0;
+        for state in [ElementState::Pressed, ElementState::Released] {
+            #[allow(deprecated)]
+            let event = WindowEvent::MouseInput {
+                device_id: device_id(),
+                state,
+                button: MouseButton::Left,
+                modifiers: ModifiersState::empty(),
+            };
+            let consumed = ui.handle_window_event(&event);
+            assert_eq!(consumed, state == ElementState::Pressed);
+            if button_frame(&mut ui).1 {
+                clicks += 1;
+            }
+        }
+        assert_eq!(clicks, 1);
+    }
+
+    #[test]
+    fn ignores_unmapped_input() {
+        let mut ui = DebugUi::new(1.);
+        assert!(!ui.handle_window_event(&WindowEvent::ReceivedCharacter('\u{8}')));
+        #[allow(deprecated)]
+        let other_button = WindowEvent::MouseInput {
+            device_id: device_id(),
+            state: ElementState::Pressed,
+            button: MouseButton::Other(4),
+            modifiers: ModifiersState::empty(),
+        };
+        assert!(!ui.handle_window_event(&other_button));
+        assert!(ui.events.is_empty());
+        assert_eq!(
+            key_code(VirtualKeyCode::NumpadEnter),
+            Some(egui::Key::Enter)
+        );
+        assert_eq!(key_code(VirtualKeyCode::F13), None);
+    }
+
+    #[test]
+    fn hidden_ui_queues_and_consumes_nothing() {
+        let mut ui = DebugUi::new(1.);
+        let (button, _) = button_frame(&mut ui);
+        let center = button.center();
+        #[allow(deprecated)]
+        let moved = WindowEvent::CursorMoved {
+            device_id: device_id(),
+            position: PhysicalPosition::new(center.x as f64, center.y as f64),
+            modifiers: ModifiersState::empty(),
+        };
+        #[allow(deprecated)]
+        let press = WindowEvent::MouseInput {
+            device_id: device_id(),
+            state: ElementState::Pressed,
+            button: MouseButton::Left,
+            modifiers: ModifiersState::empty(),
+        };
+        ui.handle_window_event(&moved);
+        button_frame(&mut ui);
+        assert!(ui.handle_window_event(&press));
+
+        ui.set_visible(false);
+        assert!(ui.events.is_empty());
+        // The last visible frame wanted the pointer; hidden, it must not keep it.
+        assert!(!ui.handle_window_event(&press));
+        assert!(!ui.handle_window_event(&WindowEvent::ReceivedCharacter('a')));
+        for _ in 0..100 {
+            ui.handle_window_event(&moved);
+        }
+        assert!(ui.events.is_empty());
+        assert_eq!(ui.pointer, center);
+
+        ui.set_visible(true);
+        ui.handle_window_event(&moved);
+        assert_eq!(ui.events.len(), 1);
+    }
+}
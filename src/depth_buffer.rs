@@ -0,0 +1,504 @@
+use windows::{core::*, Win32::Graphics::Direct3D12::*, Win32::Graphics::Dxgi::Common::*};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DepthFormat {
+    D32,
+    D24S8,
+    D32S8,
+}
+
+impl DepthFormat {
+    pub fn dxgi_format(self) -> DXGI_FORMAT {
+        match self {
+            DepthFormat::D32 => DXGI_FORMAT_D32_FLOAT,
+            DepthFormat::D24S8 => DXGI_FORMAT_D24_UNORM_S8_UINT,
+            DepthFormat::D32S8 => DXGI_FORMAT_D32_FLOAT_S8X24_UINT,
+        }
+    }
+
+    pub fn typeless_format(self) -> DXGI_FORMAT {
+        match self {
+            DepthFormat::D32 => DXGI_FORMAT_R32_TYPELESS,
+            DepthFormat::D24S8 => DXGI_FORMAT_R24G8_TYPELESS,
+            DepthFormat::D32S8 => DXGI_FORMAT_R32G8X24_TYPELESS,
+        }
+    }
+
+    pub fn srv_format(self) -> DXGI_FORMAT {
+        match self {
+            DepthFormat::D32 => DXGI_FORMAT_R32_FLOAT,
+            DepthFormat::D24S8 => DXGI_FORMAT_R24_UNORM_X8_TYPELESS,
+            DepthFormat::D32S8 => DXGI_FORMAT_R32_FLOAT_X8X24_TYPELESS,
+        }
+    }
+
+    pub fn dsv_desc(self, sample_count: u32) -> D3D12_DEPTH_STENCIL_VIEW_DESC {
+        D3D12_DEPTH_STENCIL_VIEW_DESC {
+            Format: self.dxgi_format(),
+            ViewDimension: if sample_count > 1 {
+                D3D12_DSV_DIMENSION_TEXTURE2DMS
+            } else {
+                D3D12_DSV_DIMENSION_TEXTURE2D
+            },
+            Flags: D3D12_DSV_FLAG_NONE,
+            ..Default::default()
+        }
+    }
+
+    pub fn srv_desc(self, sample_count: u32) -> D3D12_SHADER_RESOURCE_VIEW_DESC {
+        let (dimension, anonymous) = if sample_count > 1 {
+            (
+                D3D12_SRV_DIMENSION_TEXTURE2DMS,
+                D3D12_SHADER_RESOURCE_VIEW_DESC_0 {
+                    Texture2DMS: D3D12_TEX2DMS_SRV::default(),
+                },
+            )
+        } else {
+            (
+                D3D12_SRV_DIMENSION_TEXTURE2D,
+                D3D12_SHADER_RESOURCE_VIEW_DESC_0 {
+                    Texture2D: D3D12_TEX2D_SRV {
+                        MipLevels: 1,
+                        ..Default::default()
+                    },
+                },
+            )
+        };
+        D3D12_SHADER_RESOURCE_VIEW_DESC {
+            Format: self.srv_format(),
+            ViewDimension: dimension,
+            Shader4ComponentMapping: D3D12_DEFAULT_SHADER_4_COMPONENT_MAPPING,
+            Anonymous: anonymous,
+        }
+    }
+
+    pub fn has_stencil(self) -> bool {
+        !matches!(self, DepthFormat::D32)
+    }
+
+    pub fn clear_flags(self) -> D3D12_CLEAR_FLAGS {
+        if self.has_stencil() {
+            D3D12_CLEAR_FLAG_DEPTH | D3D12_CLEAR_FLAG_STENCIL
+        } else {
+            D3D12_CLEAR_FLAG_DEPTH
+        }
+    }
+
+    pub fn quantize(self, depth: f32) -> f32 {
+        let depth = depth.clamp(0.0, 1.0);
+        match self {
+            DepthFormat::D24S8 => {
+                const MAX: f32 = ((1 << 24) - 1) as f32;
+                (depth * MAX).round() / MAX
+            }
+            DepthFormat::D32 | DepthFormat::D32S8 => depth,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DepthTest {
+    Never,
+    Less,
+    Equal,
+    LessEqual,
+    Greater,
+    NotEqual,
+    GreaterEqual,
+    Always,
+}
+
+impl DepthTest {
+    pub fn comparison_func(self) -> D3D12_COMPARISON_FUNC {
+        match self {
+            DepthTest::Never => D3D12_COMPARISON_FUNC_NEVER,
+            DepthTest::Less => D3D12_COMPARISON_FUNC_LESS,
+            DepthTest::Equal => D3D12_COMPARISON_FUNC_EQUAL,
+            DepthTest::LessEqual => D3D12_COMPARISON_FUNC_LESS_EQUAL,
+            DepthTest::Greater => D3D12_COMPARISON_FUNC_GREATER,
+            DepthTest::NotEqual => D3D12_COMPARISON_FUNC_NOT_EQUAL,
+            DepthTest::GreaterEqual => D3D12_COMPARISON_FUNC_GREATER_EQUAL,
+            DepthTest::Always => D3D12_COMPARISON_FUNC_ALWAYS,
+        }
+    }
+
+    pub fn passes(self, incoming: f32, stored: f32) -> bool {
+        match self {
+            DepthTest::Never => false,
+            DepthTest::Less => incoming < stored,
+            DepthTest::Equal => incoming == stored,
+            DepthTest::LessEqual => incoming <= stored,
+            DepthTest::Greater => incoming > stored,
+            DepthTest::NotEqual => incoming != stored,
+            DepthTest::GreaterEqual => incoming >= stored,
+            DepthTest::Always => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DepthState {
+    pub test: Option<DepthTest>,
+    pub write: bool,
+}
+
+impl DepthState {
+    pub const DISABLED: DepthState = DepthState {
+        test: None,
+        write: false,
+    };
+    pub const READ_WRITE: DepthState = DepthState {
+        test: Some(DepthTest::Less),
+        write: true,
+    };
+    pub const READ_ONLY: DepthState = DepthState {
+        test: Some(DepthTest::LessEqual),
+        write: false,
+    };
+    pub const REVERSE_Z_READ_WRITE: DepthState = DepthState {
+        test: Some(DepthTest::Greater),
+        write: true,
+    };
+    pub const REVERSE_Z_READ_ONLY: DepthState = DepthState {
+        test: Some(DepthTest::GreaterEqual),
+        write: false,
+    };
+    pub const EQUAL: DepthState = DepthState {
+        test: Some(DepthTest::Equal),
+        write: false,
+    };
+
+    pub fn read_write(reverse_z: bool) -> Self {
+        if reverse_z {
+            Self::REVERSE_Z_READ_WRITE
+        } else {
+            Self::READ_WRITE
+        }
+    }
+
+    pub fn read_only(reverse_z: bool) -> Self {
+        if reverse_z {
+            Self::REVERSE_Z_READ_ONLY
+        } else {
+            Self::READ_ONLY
+        }
+    }
+
+    pub fn desc(&self) -> D3D12_DEPTH_STENCIL_DESC {
+        let stencil_op = D3D12_DEPTH_STENCILOP_DESC {
+            StencilFailOp: D3D12_STENCIL_OP_KEEP,
+            StencilDepthFailOp: D3D12_STENCIL_OP_KEEP,
+            StencilPassOp: D3D12_STENCIL_OP_KEEP,
+            StencilFunc: D3D12_COMPARISON_FUNC_ALWAYS,
+        };
+        D3D12_DEPTH_STENCIL_DESC {
+            DepthEnable: self.test.is_some().into(),
+            DepthWriteMask: if self.write {
+                D3D12_DEPTH_WRITE_MASK_ALL
+            } else {
+                D3D12_DEPTH_WRITE_MASK_ZERO
+            },
+            DepthFunc: self.test.unwrap_or(DepthTest::Always).comparison_func(),
+            StencilEnable: false.into(),
+            StencilReadMask: D3D12_DEFAULT_STENCIL_READ_MASK as u8,
+            StencilWriteMask: D3D12_DEFAULT_STENCIL_WRITE_MASK as u8,
+            FrontFace: stencil_op,
+            BackFace: stencil_op,
+        }
+    }
+
+    pub fn resolve(&self, format: DepthFormat, incoming: f32, stored: &mut f32) -> bool {
+        let Some(test) = self.test else {
+            return true;
+        };
+        let incoming = format.quantize(incoming);
+        if !test.passes(incoming, *stored) {
+            return false;
+        }
+        if self.write {
+            *stored = incoming;
+        }
+        true
+    }
+}
+
+pub struct DepthBuffer {
+    resource: ID3D12Resource,
+    heap: ID3D12DescriptorHeap,
+    format: DepthFormat,
+    sample_desc: DXGI_SAMPLE_DESC,
+    clear_depth: f32,
+    clear_stencil: u8,
+}
+
+impl DepthBuffer {
+    pub fn new(
+        device: &ID3D12Device,
+        width: u32,
+        height: u32,
+        format: DepthFormat,
+        sample_desc: DXGI_SAMPLE_DESC,
+        clear_depth: f32,
+    ) -> Result<Self> {
+        let heap_desc = D3D12_DESCRIPTOR_HEAP_DESC {
+            Type: D3D12_DESCRIPTOR_HEAP_TYPE_DSV,
+            NodeMask: 0,
+            NumDescriptors: 1,
+            Flags: D3D12_DESCRIPTOR_HEAP_FLAG_NONE,
+        };
+        let heap: ID3D12DescriptorHeap = unsafe { device.CreateDescriptorHeap(&heap_desc) }?;
+        let resource =
+            create_depth_resource(device, width, height, format, sample_desc, clear_depth, 0)?;
+        let depth_buffer = DepthBuffer {
+            resource,
+            heap,
+            format,
+            sample_desc,
+            clear_depth,
+            clear_stencil: 0,
+        };
+        depth_buffer.create_view(device);
+        Ok(depth_buffer)
+    }
+
+    fn create_view(&self, device: &ID3D12Device) {
+        let view_desc = self.format.dsv_desc(self.sample_desc.Count);
+        unsafe {
+            device.CreateDepthStencilView(&self.resource, Some(&view_desc), self.dsv_handle())
+        };
+    }
+
+    pub fn resize(&mut self, device: &ID3D12Device, width: u32, height: u32) -> Result<()> {
+        self.resource = create_depth_resource(
+            device,
+            width,
+            height,
+            self.format,
+            self.sample_desc,
+            self.clear_depth,
+            self.clear_stencil,
+        )?;
+        self.create_view(device);
+        Ok(())
+    }
+
+    pub fn create_srv(&self, device: &ID3D12Device, handle: D3D12_CPU_DESCRIPTOR_HANDLE) {
+        let view_desc = self.format.srv_desc(self.sample_desc.Count);
+        unsafe { device.CreateShaderResourceView(&self.resource, Some(&view_desc), handle) };
+    }
+
+    pub fn resource(&self) -> &ID3D12Resource {
+        &self.resource
+    }
+
+    pub fn format(&self) -> DepthFormat {
+        self.format
+    }
+
+    pub fn clear_depth(&self) -> f32 {
+        self.clear_depth
+    }
+
+    pub fn dsv_handle(&self) -> D3D12_CPU_DESCRIPTOR_HANDLE {
+        unsafe { self.heap.GetCPUDescriptorHandleForHeapStart() }
+    }
+
+    pub fn clear(&self, command_list: &ID3D12GraphicsCommandList) {
+        unsafe {
+            command_list.ClearDepthStencilView(
+                self.dsv_handle(),
+                self.format.clear_flags(),
+                self.clear_depth,
+                self.clear_stencil,
+                &[],
+            )
+        };
+    }
+}
+
+fn create_depth_resource(
+    device: &ID3D12Device,
+    width: u32,
+    height: u32,
+    format: DepthFormat,
+    sample_desc: DXGI_SAMPLE_DESC,
+    clear_depth: f32,
+    clear_stencil: u8,
+) -> Result<ID3D12Resource> {
+    let heap_properties = D3D12_HEAP_PROPERTIES {
+        Type: D3D12_HEAP_TYPE_DEFAULT,
+        CPUPageProperty: D3D12_CPU_PAGE_PROPERTY_UNKNOWN,
+        MemoryPoolPreference: D3D12_MEMORY_POOL_UNKNOWN,
+        ..Default::default()
+    };
+    let resource_desc = D3D12_RESOURCE_DESC {
+        Dimension: D3D12_RESOURCE_DIMENSION_TEXTURE2D,
+        Width: width as u64,
+        Height: height,
+        DepthOrArraySize: 1,
+        MipLevels: 1,
+        Format: format.typeless_format(),
+        SampleDesc: sample_desc,
+        Layout: D3D12_TEXTURE_LAYOUT_UNKNOWN,
+        Flags: D3D12_RESOURCE_FLAG_ALLOW_DEPTH_STENCIL,
+        ..Default::default()
+    };
+    let clear_value = D3D12_CLEAR_VALUE {
+        Format: format.dxgi_format(),
+        Anonymous: D3D12_CLEAR_VALUE_0 {
+            DepthStencil: D3D12_DEPTH_STENCIL_VALUE {
+                Depth: clear_depth,
+                Stencil: clear_stencil,
+            },
+        },
+    };
+
+    let mut resource: Option<ID3D12Resource> = None;
+    unsafe {
+        device.CreateCommittedResource(
+            &heap_properties,
+            D3D12_HEAP_FLAG_NONE,
+            &resource_desc,
+            D3D12_RESOURCE_STATE_DEPTH_WRITE,
+            Some(&clear_value),
+            &mut resource,
+        )
+    }?;
+    Ok(resource.unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{Point3, Transform};
+
+    use super::*;
+    use crate::camera::Camera;
+
+    const FORMATS: [DepthFormat; 3] = [DepthFormat::D32, DepthFormat::D24S8, DepthFormat::D32S8];
+
+    #[test]
+    fn formats_map_to_typeless_dsv_and_srv() {
+        let mapped: Vec<_> = FORMATS
+            .iter()
+            .map(|f| (f.typeless_format(), f.dxgi_format(), f.srv_format()))
+            .collect();
+        assert_eq!(
+            mapped,
+            [
+                (
+                    DXGI_FORMAT_R32_TYPELESS,
+                    DXGI_FORMAT_D32_FLOAT,
+                    DXGI_FORMAT_R32_FLOAT
+                ),
+                (
+                    DXGI_FORMAT_R24G8_TYPELESS,
+                    DXGI_FORMAT_D24_UNORM_S8_UINT,
+                    DXGI_FORMAT_R24_UNORM_X8_TYPELESS
+                ),
+                (
+                    DXGI_FORMAT_R32G8X24_TYPELESS,
+                    DXGI_FORMAT_D32_FLOAT_S8X24_UINT,
+                    DXGI_FORMAT_R32_FLOAT_X8X24_TYPELESS
+                ),
+            ]
+        );
+        assert_eq!(DepthFormat::D32.clear_flags(), D3D12_CLEAR_FLAG_DEPTH);
+        for format in [DepthFormat::D24S8, DepthFormat::D32S8] {
+            assert!(format.has_stencil());
+            assert_eq!(
+                format.clear_flags(),
+                D3D12_CLEAR_FLAG_DEPTH | D3D12_CLEAR_FLAG_STENCIL
+            );
+        }
+    }
+
+    #[test]
+    fn view_descs_follow_sample_count() {
+        for format in FORMATS {
+            let dsv = format.dsv_desc(1);
+            assert_eq!(dsv.Format, format.dxgi_format());
+            assert_eq!(dsv.ViewDimension, D3D12_DSV_DIMENSION_TEXTURE2D);
+            assert_eq!(
+                format.dsv_desc(4).ViewDimension,
+                D3D12_DSV_DIMENSION_TEXTURE2DMS
+            );
+
+            let srv = format.srv_desc(1);
+            assert_eq!(srv.Format, format.srv_format());
+            assert_eq!(srv.ViewDimension, D3D12_SRV_DIMENSION_TEXTURE2D);
+            assert_eq!(unsafe { srv.Anonymous.Texture2D.MipLevels }, 1);
+            assert_eq!(
+                format.srv_desc(4).ViewDimension,
+                D3D12_SRV_DIMENSION_TEXTURE2DMS
+            );
+        }
+    }
+
+    #[test]
+    fn d24_quantizes_to_24_bits() {
+        let step = 1.0 / ((1 << 24) - 1) as f32;
+        assert_eq!(DepthFormat::D24S8.quantize(step * 0.4), 0.0);
+        assert_eq!(DepthFormat::D24S8.quantize(1.5), 1.0);
+        assert_eq!(DepthFormat::D32.quantize(step * 0.4), step * 0.4);
+        assert_eq!(DepthFormat::D32S8.quantize(-1.0), 0.0);
+    }
+
+    #[test]
+    fn clear_value_pairs_with_compare_direction() {
+        for reverse_z in [false, true] {
+            let camera = Camera {
+                reverse_z,
+                ..Default::default()
+            };
+            let view_projection = camera.view_projection();
+            let depth = |z: f32| view_projection.transform_point(Point3::new(0.0, 0.0, z)).z;
+            let (near, far) = (depth(-4.0), depth(50.0));
+
+            let state = DepthState::read_write(reverse_z);
+            assert!(state.test.unwrap().passes(near, far));
+            assert_eq!(
+                state.desc().DepthFunc,
+                if reverse_z {
+                    D3D12_COMPARISON_FUNC_GREATER
+                } else {
+                    D3D12_COMPARISON_FUNC_LESS
+                }
+            );
+
+            for format in FORMATS {
+                let mut stored = camera.depth_clear_value();
+                assert!(state.resolve(format, depth(500.0), &mut stored));
+                assert!(state.resolve(format, far, &mut stored));
+                assert!(state.resolve(format, near, &mut stored));
+                assert!(!state.resolve(format, far, &mut stored));
+                assert_eq!(stored, format.quantize(near));
+
+                let read_only = DepthState::read_only(reverse_z);
+                assert!(read_only.resolve(format, near, &mut stored));
+                assert!(!read_only.resolve(format, far, &mut stored));
+                assert_eq!(stored, format.quantize(near));
+            }
+        }
+    }
+
+    #[test]
+    fn presets_describe_pipeline_state() {
+        let disabled = DepthState::DISABLED.desc();
+        assert!(!disabled.DepthEnable.as_bool());
+        assert_eq!(disabled.DepthWriteMask, D3D12_DEPTH_WRITE_MASK_ZERO);
+        let mut stored = 0.25;
+        assert!(DepthState::DISABLED.resolve(DepthFormat::D32, 0.75, &mut stored));
+        assert_eq!(stored, 0.25);
+
+        let equal = DepthState::EQUAL.desc();
+        assert_eq!(equal.DepthFunc, D3D12_COMPARISON_FUNC_EQUAL);
+        assert!(DepthState::EQUAL.resolve(DepthFormat::D32, 0.25, &mut stored));
+        assert!(!DepthState::EQUAL.resolve(DepthFormat::D32, 0.5, &mut stored));
+
+        let read_only = DepthState::read_only(true).desc();
+        assert!(read_only.DepthEnable.as_bool());
+        assert_eq!(read_only.DepthWriteMask, D3D12_DEPTH_WRITE_MASK_ZERO);
+        assert_eq!(read_only.DepthFunc, D3D12_COMPARISON_FUNC_GREATER_EQUAL);
+        assert!(!read_only.StencilEnable.as_bool());
+    }
+}
@@ -0,0 +1,387 @@
+use std::path::{Path, PathBuf};
+
+use ab_glyph::{Font as _, FontVec, GlyphId, OutlineCurve, Point};
+use cgmath::{InnerSpace, Vector2};
+
+const CURVE_SEGMENTS: usize = 8;
+
+#[derive(Debug)]
+pub enum FontError {
+    Io {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    InvalidFont {
+        path: PathBuf,
+        index: u32,
+    },
+}
+
+impl std::fmt::Display for FontError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FontError::Io { path, error } => write!(f, "{}: {}", path.display(), error),
+            FontError::InvalidFont { path, index } => {
+                write!(f, "{}: invalid font (face {})", path.display(), index)
+            }
+        }
+    }
+}
+
+impl std::error::Error for FontError {}
+
+pub type Result<T> = std::result::Result<T, FontError>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LineMetrics {
+    pub ascent: f32,
+    pub descent: f32,
+    pub line_gap: f32,
+}
+
+impl LineMetrics {
+    pub fn line_height(&self) -> f32 {
+        self.ascent - self.descent + self.line_gap
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SdfGlyph {
+    pub width: u32,
+    pub height: u32,
+    pub offset: Vector2<f32>,
+    pub pixels: Vec<u8>,
+}
+
+pub struct Font {
+    font: FontVec,
+}
+
+impl Font {
+    pub fn from_bytes(data: Vec<u8>, index: u32) -> Option<Self> {
+        FontVec::try_from_vec_and_index(data, index)
+            .ok()
+            .map(|font| Font { font })
+    }
+
+    pub fn load(path: impl AsRef<Path>, index: u32) -> Result<Self> {
+        let path = path.as_ref();
+        let data = std::fs::read(path).map_err(|error| FontError::Io {
+            path: path.to_path_buf(),
+            error,
+        })?;
+        Self::from_bytes(data, index).ok_or_else(|| FontError::InvalidFont {
+            path: path.to_path_buf(),
+            index,
+        })
+    }
+
+    fn scale(&self, size: f32) -> f32 {
+        size / self.font.units_per_em().unwrap_or(1000.)
+    }
+
+    pub fn line_metrics(&self, size: f32) -> LineMetrics {
+        let scale = self.scale(size);
+        LineMetrics {
+            ascent: self.font.ascent_unscaled() * scale,
+            descent: self.font.descent_unscaled() * scale,
+            line_gap: self.font.line_gap_unscaled() * scale,
+        }
+    }
+
+    pub fn glyph_id(&self, ch: char) -> GlyphId {
+        self.font.glyph_id(ch)
+    }
+
+    pub fn has_glyph(&self, ch: char) -> bool {
+        self.font.glyph_id(ch).0 != 0
+    }
+
+    pub fn advance(&self, glyph: GlyphId, size: f32) -> f32 {
+        self.font.h_advance_unscaled(glyph) * self.scale(size)
+    }
+
+    pub fn kerning(&self, first: GlyphId, second: GlyphId, size: f32) -> f32 {
+        self.font.kern_unscaled(first, second) * self.scale(size)
+    }
+
+    pub fn segments(&self, glyph: GlyphId, size: f32) -> Vec<[Vector2<f32>; 2]> {
+        let Some(outline) = self.font.outline(glyph) else {
+            return Vec::new();
+        };
+        let scale = self.scale(size);
+        let to_pixels = |p: Point| Vector2::new(p.x * scale, -p.y * scale);
+
+        let mut segments = Vec::new();
+        for curve in &outline.curves {
+            match *curve {
+                OutlineCurve::Line(p0, p1) => segments.push([to_pixels(p0), to_pixels(p1)]),
+                OutlineCurve::Quad(p0, p1, p2) => {
+                    let (p0, p1, p2) = (to_pixels(p0), to_pixels(p1), to_pixels(p2));
+                    flatten(&mut segments, |t| {
+                        let s = 1. - t;
+                        p0 * (s * s) + p1 * (2. * s * t) + p2 * (t * t)
+                    });
+                }
+                OutlineCurve::Cubic(p0, p1, p2, p3) => {
+                    let (p0, p1, p2, p3) =
+                        (to_pixels(p0), to_pixels(p1), to_pixels(p2), to_pixels(p3));
+                    flatten(&mut segments, |t| {
+                        let s = 1. - t;
+                        p0 * (s * s * s)
+                            + p1 * (3. * s * s * t)
+                            + p2 * (3. * s * t * t)
+                            + p3 * (t * t * t)
+                    });
+                }
+            }
+        }
+        segments
+    }
+
+    pub fn sdf_glyph(&self, glyph: GlyphId, size: f32, spread: f32) -> Option<SdfGlyph> {
+        let segments = self.segments(glyph, size);
+        let first = segments.first()?[0];
+        let (min, max) = segments
+            .iter()
+            .flatten()
+            .fold((first, first), |(min, max), p| {
+                (
+                    Vector2::new(min.x.min(p.x), min.y.min(p.y)),
+                    Vector2::new(max.x.max(p.x), max.y.max(p.y)),
+                )
+            });
+
+        let border = spread.ceil();
+        let origin = Vector2::new(min.x.floor() - border, min.y.floor() - border);
+        let width = (max.x.ceil() + border - origin.x) as u32;
+        let height = (max.y.ceil() + border - origin.y) as u32;
+        Some(SdfGlyph {
+            width,
+            height,
+            offset: origin,
+            pixels: signed_distance_field(&segments, origin, width, height, spread),
+        })
+    }
+}
+
+fn flatten<F: Fn(f32) -> Vector2<f32>>(segments: &mut Vec<[Vector2<f32>; 2]>, curve: F) {
+    let mut previous = curve(0.);
+    for i in 1..=CURVE_SEGMENTS {
+        let point = curve(i as f32 / CURVE_SEGMENTS as f32);
+        segments.push([previous, point]);
+        previous = point;
+    }
+}
+
+fn segment_distance(p: Vector2<f32>, a: Vector2<f32>, b: Vector2<f32>) -> f32 {
+    let ab = b - a;
+    let length2 = ab.magnitude2();
+    let t = if length2 > 0. {
+        ((p - a).dot(ab) / length2).clamp(0., 1.)
+    } else {
+        0.
+    };
+    (p - (a + ab * t)).magnitude()
+}
+
+fn winding(p: Vector2<f32>, segments: &[[Vector2<f32>; 2]]) -> i32 {
+    let mut winding = 0;
+    for [a, b] in segments {
+        let side = (b.x - a.x) * (p.y - a.y) - (p.x - a.x) * (b.y - a.y);
+        if a.y <= p.y && b.y > p.y && side > 0. {
+            winding += 1;
+        } else if b.y <= p.y && a.y > p.y && side < 0. {
+            winding -= 1;
+        }
+    }
+    winding
+}
+
+pub fn signed_distance_field(
+    segments: &[[Vector2<f32>; 2]],
+    origin: Vector2<f32>,
+    width: u32,
+    height: u32,
+    spread: f32,
+) -> Vec<u8> {
+    let mut pixels = Vec::with_capacity((width * height) as usize);
+    for y in 0..height {
+        for x in 0..width {
+            let p = origin + Vector2::new(x as f32 + 0.5, y as f32 + 0.5);
+            let distance = segments
+                .iter()
+                .map(|[a, b]| segment_distance(p, *a, *b))
+                .fold(f32::MAX, f32::min);
+            let signed = if winding(p, segments) != 0 {
+                distance
+            } else {
+                -distance
+            };
+            let value = (0.5 + signed / (2. * spread)).clamp(0., 1.);
+            pixels.push((value * 255.).round() as u8);
+        }
+    }
+    pixels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f32 = 1e-4;
+
+    /// A font whose glyphs are rectangles on a 1000 unit em: `A` covers
+    /// (100, 0)..(600, 500), `B` covers (0, 0)..(600, 600) with a hole at
+    /// (200, 200)..(400, 400), and `日` covers (100, 0)..(900, 800).
+    fn fixture_font() -> Font {
+        Font::load(
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/font/rects.ttf"),
+            0,
+        )
+        .unwrap()
+    }
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() <= EPSILON,
+            "expected {expected}, got {actual}"
+        );
+    }
+
+    fn square(min: f32, max: f32) -> Vec<[Vector2<f32>; 2]> {
+        let corners = [
+            Vector2::new(min, min),
+            Vector2::new(max, min),
+            Vector2::new(max, max),
+            Vector2::new(min, max),
+        ];
+        (0..4).map(|i| [corners[i], corners[(i + 1) % 4]]).collect()
+    }
+
+    fn pixel(glyph: &SdfGlyph, x: u32, y: u32) -> u8 {
+        glyph.pixels[(y * glyph.width + x) as usize]
+    }
+
+    #[test]
+    fn scales_metrics_to_pixels() {
+        let font = fixture_font();
+        let metrics = font.line_metrics(10.);
+        assert_close(metrics.ascent, 8.);
+        assert_close(metrics.descent, -2.);
+        assert_close(metrics.line_gap, 1.);
+        assert_close(metrics.line_height(), 11.);
+
+        let (a, b) = (font.glyph_id('A'), font.glyph_id('B'));
+        assert_close(font.advance(a, 10.), 7.);
+        assert_close(font.advance(font.glyph_id('日'), 20.), 20.);
+        assert_close(font.kerning(a, a, 10.), -1.);
+        assert_close(font.kerning(a, b, 10.), 0.);
+    }
+
+    #[test]
+    fn maps_characters_to_glyphs() {
+        let font = fixture_font();
+        assert!(font.has_glyph('A'));
+        assert!(font.has_glyph('日'));
+        assert!(!font.has_glyph('Z'));
+        assert_eq!(font.glyph_id('Z'), GlyphId(0));
+        assert_ne!(font.glyph_id('日'), font.glyph_id('。'));
+    }
+
+    #[test]
+    fn segments_flip_y_and_close_contours() {
+        let font = fixture_font();
+        let segments = font.segments(font.glyph_id('A'), 10.);
+        let points: Vec<Vector2<f32>> = segments.iter().flatten().copied().collect();
+        for p in &points {
+            assert!(
+                (1. ..=6.).contains(&p.x) && (-5. ..=0.).contains(&p.y),
+                "{:?}",
+                p
+            );
+        }
+        // Every segment starts where another one ends.
+        for [a, _] in &segments {
+            assert!(segments.iter().any(|[_, b]| (a - b).magnitude() <= EPSILON));
+        }
+        assert!(font.segments(font.glyph_id(' '), 10.).is_empty());
+    }
+
+    #[test]
+    fn sdf_glyph_covers_outline_and_spread() {
+        let font = fixture_font();
+        let glyph = font.sdf_glyph(font.glyph_id('A'), 10., 2.).unwrap();
+        assert_eq!((glyph.width, glyph.height), (9, 9));
+        assert_eq!(glyph.offset, Vector2::new(-1., -7.));
+        assert_eq!(glyph.pixels.len(), 81);
+
+        // Row 4 crosses the middle of the square: outside, then the left edge, then inside.
+        assert_eq!(pixel(&glyph, 1, 4), 96);
+        assert_eq!(pixel(&glyph, 2, 4), 159);
+        assert_eq!(pixel(&glyph, 4, 4), 255);
+        // Corners lie further than the spread away from the outline.
+        assert_eq!(pixel(&glyph, 0, 0), 0);
+        assert_eq!(pixel(&glyph, 8, 8), 0);
+        // The field is symmetric around the square's centre.
+        for y in 0..9 {
+            for x in 0..9 {
+                assert_eq!(pixel(&glyph, x, y), pixel(&glyph, 8 - x, y));
+                assert_eq!(pixel(&glyph, x, y), pixel(&glyph, y, x));
+            }
+        }
+    }
+
+    #[test]
+    fn sdf_glyph_keeps_holes_outside() {
+        let font = fixture_font();
+        let glyph = font.sdf_glyph(font.glyph_id('B'), 20., 2.).unwrap();
+        assert_eq!(glyph.offset, Vector2::new(-2., -14.));
+        // Pixel centres in glyph space: (5.5, -5.5) is in the hole, (1.5, -5.5) in the ring.
+        assert!(pixel(&glyph, 7, 8) < 128);
+        assert!(pixel(&glyph, 3, 8) > 128);
+        assert!(font.sdf_glyph(font.glyph_id(' '), 20., 2.).is_none());
+    }
+
+    #[test]
+    fn distance_field_is_signed_and_clamped() {
+        let segments = square(2., 6.);
+        let pixels = signed_distance_field(&segments, Vector2::new(0., 0.), 8, 8, 2.);
+        let row: Vec<u8> = pixels[4 * 8..5 * 8].to_vec();
+        // Centres at 0.5, 1.5, ...: distances -1.5, -0.5, 0.5, 1.5, 1.5, 0.5, -0.5, -1.5.
+        assert_eq!(row, [32, 96, 159, 223, 223, 159, 96, 32]);
+
+        let pixels = signed_distance_field(&segments, Vector2::new(-10., -10.), 4, 4, 2.);
+        assert!(pixels.iter().all(|&value| value == 0));
+        let pixels = signed_distance_field(&square(-10., 10.), Vector2::new(-2., -2.), 4, 4, 2.);
+        assert!(pixels.iter().all(|&value| value == 255));
+    }
+
+    #[test]
+    fn distance_field_ignores_winding_direction() {
+        let segments = square(1., 5.);
+        let reversed: Vec<[Vector2<f32>; 2]> =
+            segments.iter().rev().map(|&[a, b]| [b, a]).collect();
+        let origin = Vector2::new(0., 0.);
+        assert_eq!(
+            signed_distance_field(&segments, origin, 6, 6, 3.),
+            signed_distance_field(&reversed, origin, 6, 6, 3.)
+        );
+    }
+
+    #[test]
+    fn load_reports_errors() {
+        let missing = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/font/missing.ttf");
+        assert!(matches!(
+            Font::load(&missing, 0),
+            Err(FontError::Io { path, .. }) if path == missing
+        ));
+        let not_a_font =
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/atlas/layout.json");
+        assert!(matches!(
+            Font::load(&not_a_font, 0),
+            Err(FontError::InvalidFont { index: 0, .. })
+        ));
+        assert!(Font::from_bytes(Vec::new(), 0).is_none());
+    }
+}
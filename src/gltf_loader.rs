@@ -0,0 +1,840 @@
+use std::path::{Path, PathBuf};
+
+use base64::Engine;
+use cgmath::{Matrix4, Quaternion, Vector2, Vector3, Vector4, Zero};
+use gltf::{animation::util::ReadOutputs, buffer::Source, image::Source as ImageSource};
+
+use crate::mesh_processing;
+use crate::model::{
+    AlphaMode, Animation, Channel, ChannelValues, Filter, Image, Indices, Interpolation, Material,
+    Mesh, MeshVertex, Model, Node, Primitive, Sampler, Scene, Skin, Texture, TextureRef, Topology,
+    Transform, Wrap,
+};
+
+#[derive(Debug)]
+pub enum GltfError {
+    Gltf(gltf::Error),
+    Io {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    Base64(base64::DecodeError),
+    Image(image::ImageError),
+    UnsupportedUri(String),
+    MissingBinaryChunk,
+    BufferViewOutOfRange {
+        view: usize,
+    },
+    MissingPositions {
+        mesh: usize,
+        primitive: usize,
+    },
+    InvalidAccessor {
+        mesh: usize,
+        primitive: usize,
+    },
+}
+
+impl std::fmt::Display for GltfError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GltfError::Gltf(error) => write!(f, "invalid glTF: {}", error),
+            GltfError::Io { path, error } => write!(f, "{}: {}", path.display(), error),
+            GltfError::Base64(error) => write!(f, "invalid data URI: {}", error),
+            GltfError::Image(error) => write!(f, "image decode failed: {}", error),
+            GltfError::UnsupportedUri(uri) => write!(f, "unsupported URI: {}", uri),
+            GltfError::MissingBinaryChunk => write!(f, "buffer refers to a missing GLB chunk"),
+            GltfError::BufferViewOutOfRange { view } => {
+                write!(f, "buffer view {} runs past the end of its buffer", view)
+            }
+            GltfError::MissingPositions { mesh, primitive } => {
+                write!(f, "mesh {} primitive {} has no POSITION", mesh, primitive)
+            }
+            GltfError::InvalidAccessor { mesh, primitive } => write!(
+                f,
+                "mesh {} primitive {} has mismatched attribute counts",
+                mesh, primitive
+            ),
+        }
+    }
+}
+
+impl std::error::Error for GltfError {}
+
+impl From<gltf::Error> for GltfError {
+    fn from(error: gltf::Error) -> Self {
+        GltfError::Gltf(error)
+    }
+}
+
+impl From<base64::DecodeError> for GltfError {
+    fn from(error: base64::DecodeError) -> Self {
+        GltfError::Base64(error)
+    }
+}
+
+impl From<image::ImageError> for GltfError {
+    fn from(error: image::ImageError) -> Self {
+        GltfError::Image(error)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, GltfError>;
+
+pub fn load(path: impl AsRef<Path>) -> Result<Model> {
+    let path = path.as_ref();
+    let bytes = read_file(path)?;
+    load_from_slice(&bytes, path.parent())
+}
+
+pub fn load_from_slice(bytes: &[u8], base_dir: Option<&Path>) -> Result<Model> {
+    let gltf::Gltf { document, blob } = gltf::Gltf::from_slice(bytes)?;
+
+    let buffers = document
+        .buffers()
+        .map(|buffer| match buffer.source() {
+            Source::Bin => blob.clone().ok_or(GltfError::MissingBinaryChunk),
+            Source::Uri(uri) => read_uri(uri, base_dir),
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let images = document
+        .images()
+        .map(|image| {
+            let encoded = match image.source() {
+                ImageSource::View { view, .. } => {
+                    let buffer = &buffers[view.buffer().index()];
+                    let range = view.offset()..view.offset() + view.length();
+                    buffer
+                        .get(range)
+                        .ok_or(GltfError::BufferViewOutOfRange { view: view.index() })?
+                        .to_vec()
+                }
+                ImageSource::Uri { uri, .. } => read_uri(uri, base_dir)?,
+            };
+            let decoded = image::load_from_memory(&encoded)?.to_rgba8();
+            Ok(Image {
+                name: image.name().unwrap_or_default().to_string(),
+                width: decoded.width(),
+                height: decoded.height(),
+                rgba8: decoded.into_raw(),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let textures = document
+        .textures()
+        .map(|texture| Texture {
+            name: texture.name().unwrap_or_default().to_string(),
+            image: texture.source().index(),
+            sampler: convert_sampler(&texture.sampler()),
+        })
+        .collect();
+
+    let materials = document.materials().map(|m| convert_material(&m)).collect();
+
+    let meshes = document
+        .meshes()
+        .map(|mesh| {
+            let primitives = mesh
+                .primitives()
+                .map(|primitive| read_primitive(&mesh, &primitive, &buffers))
+                .collect::<Result<Vec<_>>>()?;
+            Ok(Mesh {
+                name: mesh.name().unwrap_or_default().to_string(),
+                primitives,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut nodes: Vec<Node> = document
+        .nodes()
+        .map(|node| {
+            let (translation, rotation, scale) = node.transform().decomposed();
+            Node {
+                name: node.name().unwrap_or_default().to_string(),
+                parent: None,
+                children: node.children().map(|child| child.index()).collect(),
+                transform: Transform {
+                    translation: translation.into(),
+                    rotation: quaternion(rotation),
+                    scale: scale.into(),
+                },
+                mesh: node.mesh().map(|mesh| mesh.index()),
+                skin: node.skin().map(|skin| skin.index()),
+            }
+        })
+        .collect();
+    for parent in 0..nodes.len() {
+        for child in nodes[parent].children.clone() {
+            nodes[child].parent = Some(parent);
+        }
+    }
+
+    let scenes = document
+        .scenes()
+        .map(|scene| Scene {
+            name: scene.name().unwrap_or_default().to_string(),
+            nodes: scene.nodes().map(|node| node.index()).collect(),
+        })
+        .collect();
+
+    let skins = document
+        .skins()
+        .map(|skin| {
+            let joints: Vec<usize> = skin.joints().map(|joint| joint.index()).collect();
+            let reader = skin.reader(|buffer| Some(&buffers[buffer.index()]));
+            let inverse_bind_matrices = match reader.read_inverse_bind_matrices() {
+                Some(matrices) => matrices.map(Matrix4::from).collect(),
+                None => vec![Matrix4::from_scale(1.0); joints.len()],
+            };
+            Skin {
+                name: skin.name().unwrap_or_default().to_string(),
+                joints,
+                inverse_bind_matrices,
+                skeleton: skin.skeleton().map(|node| node.index()),
+            }
+        })
+        .collect();
+
+    let animations = document
+        .animations()
+        .map(|animation| Animation {
+            name: animation.name().unwrap_or_default().to_string(),
+            channels: animation
+                .channels()
+                .filter_map(|channel| {
+                    let reader = channel.reader(|buffer| Some(&buffers[buffer.index()]));
+                    let times = reader.read_inputs()?.collect();
+                    let values = match reader.read_outputs()? {
+                        ReadOutputs::Translations(values) => {
+                            ChannelValues::Translations(values.map(Vector3::from).collect())
+                        }
+                        ReadOutputs::Rotations(values) => {
+                            ChannelValues::Rotations(values.into_f32().map(quaternion).collect())
+                        }
+                        ReadOutputs::Scales(values) => {
+                            ChannelValues::Scales(values.map(Vector3::from).collect())
+                        }
+                        ReadOutputs::MorphTargetWeights(values) => {
+                            ChannelValues::MorphWeights(values.into_f32().collect())
+                        }
+                    };
+                    Some(Channel {
+                        node: channel.target().node().index(),
+                        interpolation: match channel.sampler().interpolation() {
+                            gltf::animation::Interpolation::Step => Interpolation::Step,
+                            gltf::animation::Interpolation::Linear => Interpolation::Linear,
+                            gltf::animation::Interpolation::CubicSpline => {
+                                Interpolation::CubicSpline
+                            }
+                        },
+                        times,
+                        values,
+                    })
+                })
+                .collect(),
+        })
+        .collect();
+
+    Ok(Model {
+        meshes,
+        materials,
+        textures,
+        images,
+        nodes,
+        scenes,
+        default_scene: document.default_scene().map(|scene| scene.index()),
+        skins,
+        animations,
+    })
+}
+
+fn read_file(path: &Path) -> Result<Vec<u8>> {
+    std::fs::read(path).map_err(|error| GltfError::Io {
+        path: path.to_path_buf(),
+        error,
+    })
+}
+
+fn read_uri(uri: &str, base_dir: Option<&Path>) -> Result<Vec<u8>> {
+    if let Some(data) = uri.strip_prefix("data:") {
+        let Some((_, payload)) = data.split_once(";base64,") else {
+            return Err(GltfError::UnsupportedUri(uri.to_string()));
+        };
+        return Ok(base64::engine::general_purpose::STANDARD.decode(payload)?);
+    }
+    if uri.contains("://") {
+        return Err(GltfError::UnsupportedUri(uri.to_string()));
+    }
+    let relative = percent_decode(uri);
+    let path = match base_dir {
+        Some(dir) => dir.join(relative),
+        None => PathBuf::from(relative),
+    };
+    read_file(&path)
+}
+
+fn percent_decode(uri: &str) -> String {
+    let bytes = uri.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn quaternion([x, y, z, w]: [f32; 4]) -> Quaternion<f32> {
+    Quaternion::new(w, x, y, z)
+}
+
+fn texture_ref(info: Option<gltf::texture::Info>) -> Option<TextureRef> {
+    info.map(|info| TextureRef {
+        texture: info.texture().index(),
+        tex_coord: info.tex_coord(),
+    })
+}
+
+fn convert_material(material: &gltf::Material) -> Material {
+    let pbr = material.pbr_metallic_roughness();
+    let normal = material.normal_texture();
+    let occlusion = material.occlusion_texture();
+    Material {
+        name: material.name().unwrap_or_default().to_string(),
+        base_color_factor: pbr.base_color_factor().into(),
+        base_color_texture: texture_ref(pbr.base_color_texture()),
+        metallic_factor: pbr.metallic_factor(),
+        roughness_factor: pbr.roughness_factor(),
+        metallic_roughness_texture: texture_ref(pbr.metallic_roughness_texture()),
+        normal_texture: normal.as_ref().map(|t| TextureRef {
+            texture: t.texture().index(),
+            tex_coord: t.tex_coord(),
+        }),
+        normal_scale: normal.as_ref().map_or(1.0, |t| t.scale()),
+        occlusion_texture: occlusion.as_ref().map(|t| TextureRef {
+            texture: t.texture().index(),
+            tex_coord: t.tex_coord(),
+        }),
+        occlusion_strength: occlusion.as_ref().map_or(1.0, |t| t.strength()),
+        emissive_texture: texture_ref(material.emissive_texture()),
+        emissive_factor: material.emissive_factor().into(),
+        alpha_mode: match material.alpha_mode() {
+            gltf::material::AlphaMode::Opaque => AlphaMode::Opaque,
+            gltf::material::AlphaMode::Mask => AlphaMode::Mask,
+            gltf::material::AlphaMode::Blend => AlphaMode::Blend,
+        },
+        alpha_cutoff: material.alpha_cutoff().unwrap_or(0.5),
+        double_sided: material.double_sided(),
+    }
+}
+
+fn convert_sampler(sampler: &gltf::texture::Sampler) -> Sampler {
+    use gltf::texture::{MagFilter, MinFilter, WrappingMode};
+
+    let wrap = |mode| match mode {
+        WrappingMode::ClampToEdge => Wrap::ClampToEdge,
+        WrappingMode::MirroredRepeat => Wrap::MirroredRepeat,
+        WrappingMode::Repeat => Wrap::Repeat,
+    };
+    let (min_filter, mipmaps) = match sampler.min_filter() {
+        Some(MinFilter::Nearest) => (Filter::Nearest, false),
+        Some(MinFilter::Linear) => (Filter::Linear, false),
+        Some(MinFilter::NearestMipmapNearest | MinFilter::NearestMipmapLinear) => {
+            (Filter::Nearest, true)
+        }
+        Some(MinFilter::LinearMipmapNearest | MinFilter::LinearMipmapLinear) | None => {
+            (Filter::Linear, true)
+        }
+    };
+    Sampler {
+        mag_filter: match sampler.mag_filter() {
+            Some(MagFilter::Nearest) => Filter::Nearest,
+            Some(MagFilter::Linear) | None => Filter::Linear,
+        },
+        min_filter,
+        mipmaps,
+        wrap_u: wrap(sampler.wrap_s()),
+        wrap_v: wrap(sampler.wrap_t()),
+    }
+}
+
+fn read_primitive(
+    mesh: &gltf::Mesh,
+    primitive: &gltf::Primitive,
+    buffers: &[Vec<u8>],
+) -> Result<Primitive> {
+    let (mesh_index, primitive_index) = (mesh.index(), primitive.index());
+    let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+
+    let positions: Vec<[f32; 3]> = reader
+        .read_positions()
+        .ok_or(GltfError::MissingPositions {
+            mesh: mesh_index,
+            primitive: primitive_index,
+        })?
+        .collect();
+    let mut vertices: Vec<MeshVertex> = positions
+        .iter()
+        .map(|&position| MeshVertex {
+            position: position.into(),
+            normal: Vector3::zero(),
+            ..Default::default()
+        })
+        .collect();
+
+    let invalid = || GltfError::InvalidAccessor {
+        mesh: mesh_index,
+        primitive: primitive_index,
+    };
+    let vertex_count = vertices.len();
+    let check = |count: usize| {
+        if count == vertex_count {
+            Ok(())
+        } else {
+            Err(invalid())
+        }
+    };
+
+    let has_normals = match reader.read_normals() {
+        Some(normals) => {
+            let normals: Vec<[f32; 3]> = normals.collect();
+            check(normals.len())?;
+            for (vertex, normal) in vertices.iter_mut().zip(normals) {
+                vertex.normal = normal.into();
+            }
+            true
+        }
+        None => false,
+    };
+    let has_uvs = match reader.read_tex_coords(0) {
+        Some(uvs) => {
+            let uvs: Vec<[f32; 2]> = uvs.into_f32().collect();
+            check(uvs.len())?;
+            for (vertex, uv) in vertices.iter_mut().zip(uvs) {
+                vertex.uv = Vector2::from(uv);
+            }
+            true
+        }
+        None => false,
+    };
+    let has_tangents = match reader.read_tangents() {
+        Some(tangents) => {
+            let tangents: Vec<[f32; 4]> = tangents.collect();
+            check(tangents.len())?;
+            for (vertex, tangent) in vertices.iter_mut().zip(tangents) {
+                vertex.tangent = Vector4::from(tangent);
+            }
+            true
+        }
+        None => false,
+    };
+    if let Some(joints) = reader.read_joints(0) {
+        let joints: Vec<[u16; 4]> = joints.into_u16().collect();
+        check(joints.len())?;
+        for (vertex, joints) in vertices.iter_mut().zip(joints) {
+            vertex.joints = joints;
+        }
+    }
+    if let Some(weights) = reader.read_weights(0) {
+        let weights: Vec<[f32; 4]> = weights.into_f32().collect();
+        check(weights.len())?;
+        for (vertex, weights) in vertices.iter_mut().zip(weights) {
+            vertex.weights = weights;
+        }
+    }
+
+    let indices: Vec<u32> = match reader.read_indices() {
+        Some(indices) => indices.into_u32().collect(),
+        None => (0..vertices.len() as u32).collect(),
+    };
+    if indices.iter().any(|&i| i as usize >= vertices.len()) {
+        return Err(invalid());
+    }
+
+    let topology = match primitive.mode() {
+        gltf::mesh::Mode::Points => Topology::Points,
+        gltf::mesh::Mode::Lines | gltf::mesh::Mode::LineLoop => Topology::Lines,
+        gltf::mesh::Mode::LineStrip => Topology::LineStrip,
+        gltf::mesh::Mode::TriangleStrip => Topology::TriangleStrip,
+        gltf::mesh::Mode::Triangles | gltf::mesh::Mode::TriangleFan => Topology::Triangles,
+    };
+    let mut indices = match primitive.mode() {
+        gltf::mesh::Mode::TriangleFan => (1..indices.len().saturating_sub(1))
+            .flat_map(|i| [indices[0], indices[i], indices[i + 1]])
+            .collect(),
+        gltf::mesh::Mode::LineLoop => (0..indices.len())
+            .flat_map(|i| [indices[i], indices[(i + 1) % indices.len()]])
+            .collect(),
+        _ => indices,
+    };
+
+    vertices = mesh_processing::strip_unused_vertices(&vertices, &mut indices);
+    if topology == Topology::Triangles {
+        // The spec asks for flat normals when NORMAL is missing and MikkTSpace tangents
+        // when TANGENT is.
+        if !has_normals {
+            let (flat, _) = mesh_processing::generate_flat_normals(&vertices, &indices);
+            (vertices, indices) = mesh_processing::weld(&flat);
+        }
+        if !has_tangents && has_uvs {
+            if let Some((tangent_vertices, tangent_indices)) =
+                mesh_processing::generate_tangents(&vertices, &indices)
+            {
+                vertices = tangent_vertices;
+                indices = tangent_indices;
+            }
+        }
+    }
+
+    Ok(Primitive {
+        vertices,
+        indices: Indices::from_u32(indices),
+        topology,
+        material: primitive.material().index(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Transform;
+    use cgmath::{InnerSpace, Point3, Rad, Rotation3, Transform as _};
+
+    const EPSILON: f32 = 1e-4;
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures/gltf")
+            .join(name)
+    }
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() <= EPSILON,
+            "expected {expected}, got {actual}"
+        );
+    }
+
+    fn assert_vector_close(actual: Vector3<f32>, expected: Vector3<f32>) {
+        assert_close(actual.x, expected.x);
+        assert_close(actual.y, expected.y);
+        assert_close(actual.z, expected.z);
+    }
+
+    fn positions(primitive: &Primitive) -> Vec<Vector3<f32>> {
+        primitive.vertices.iter().map(|v| v.position).collect()
+    }
+
+    #[test]
+    fn loads_triangle_without_indices() {
+        let model = load(fixture("triangle_without_indices.gltf")).unwrap();
+        assert_eq!(model.meshes.len(), 1);
+        let primitive = &model.meshes[0].primitives[0];
+        assert_eq!(primitive.topology, Topology::Triangles);
+        assert_eq!(primitive.indices, Indices::U16(vec![0, 1, 2]));
+        assert_eq!(
+            positions(primitive),
+            [
+                Vector3::new(0.0, 0.0, 0.0),
+                Vector3::new(1.0, 0.0, 0.0),
+                Vector3::new(0.0, 1.0, 0.0)
+            ]
+        );
+        // No NORMAL attribute: the face normal is generated.
+        for vertex in &primitive.vertices {
+            assert_vector_close(vertex.normal, Vector3::unit_z());
+        }
+        assert_eq!(primitive.material, None);
+        assert_eq!(model.root_nodes(), [0]);
+    }
+
+    #[test]
+    fn loads_external_buffer_with_percent_encoded_uri() {
+        let model = load(fixture("triangle.gltf")).unwrap();
+        let primitive = &model.meshes[0].primitives[0];
+        assert_eq!(model.meshes[0].name, "Triangle");
+        assert_eq!(primitive.indices, Indices::U16(vec![0, 1, 2]));
+        assert_eq!(primitive.vertices.len(), 3);
+        assert_eq!(model.scenes[0].name, "Scene");
+        assert_eq!(model.default_scene, Some(0));
+
+        let node = &model.nodes[0];
+        assert_eq!(node.mesh, Some(0));
+        assert_vector_close(node.transform.translation, Vector3::new(1.0, 2.0, 3.0));
+        assert_vector_close(node.transform.scale, Vector3::new(2.0, 2.0, 2.0));
+        let world = model.world_transforms()[0];
+        let corner = world.transform_point(Point3::new(1.0, 0.0, 0.0));
+        assert_vector_close(
+            Vector3::new(corner.x, corner.y, corner.z),
+            Vector3::new(3.0, 2.0, 3.0),
+        );
+    }
+
+    #[test]
+    fn external_buffer_needs_base_dir() {
+        let bytes = std::fs::read(fixture("triangle.gltf")).unwrap();
+        assert!(matches!(
+            load_from_slice(&bytes, None),
+            Err(GltfError::Io { .. })
+        ));
+        let base_dir = fixture("");
+        assert!(load_from_slice(&bytes, Some(&base_dir)).is_ok());
+    }
+
+    #[test]
+    fn loads_textured_glb() {
+        let model = load(fixture("textured_quad.glb")).unwrap();
+        let primitive = &model.meshes[0].primitives[0];
+        assert_eq!(primitive.indices, Indices::U16(vec![0, 1, 2, 0, 2, 3]));
+        assert_eq!(primitive.material, Some(0));
+        assert_eq!(primitive.vertices[1].uv, Vector2::new(1.0, 1.0));
+        assert_eq!(primitive.vertices[3].uv, Vector2::new(0.0, 0.0));
+        for vertex in &primitive.vertices {
+            assert_vector_close(vertex.normal, Vector3::unit_z());
+        }
+
+        // No TANGENT attribute: MikkTSpace tangents are generated from the uvs.
+        let tangent = primitive.vertices[0].tangent;
+        assert_eq!(tangent.w.abs(), 1.0);
+        for vertex in &primitive.vertices {
+            assert_eq!(vertex.tangent, tangent);
+            assert_close(vertex.tangent.truncate().dot(vertex.normal), 0.0);
+            assert_close(vertex.tangent.truncate().magnitude(), 1.0);
+        }
+        let du = primitive.vertices[1].uv - primitive.vertices[0].uv;
+        let edge = primitive.vertices[1].position - primitive.vertices[0].position;
+        assert!(tangent.truncate().dot(edge) * du.x > 0.0);
+
+        let material = &model.materials[0];
+        assert_eq!(material.name, "Textured");
+        assert_eq!(
+            material.base_color_factor,
+            Vector4::new(1.0, 0.5, 0.25, 1.0)
+        );
+        assert_eq!(
+            material.base_color_texture,
+            Some(TextureRef {
+                texture: 0,
+                tex_coord: 0
+            })
+        );
+        assert_eq!(material.metallic_factor, 0.0);
+        assert_eq!(material.roughness_factor, 0.75);
+        assert_vector_close(material.emissive_factor, Vector3::new(0.1, 0.2, 0.3));
+        assert_eq!(material.alpha_mode, AlphaMode::Mask);
+        assert_eq!(material.alpha_cutoff, 0.25);
+        assert!(material.double_sided);
+
+        let texture = &model.textures[0];
+        assert_eq!(texture.name, "Checker");
+        assert_eq!(texture.image, 0);
+        assert_eq!(
+            texture.sampler,
+            Sampler {
+                mag_filter: Filter::Nearest,
+                min_filter: Filter::Nearest,
+                mipmaps: true,
+                wrap_u: Wrap::ClampToEdge,
+                wrap_v: Wrap::MirroredRepeat,
+            }
+        );
+
+        let image = &model.images[0];
+        assert_eq!(image.name, "checker");
+        assert_eq!((image.width, image.height), (2, 2));
+        assert_eq!(
+            image.rgba8,
+            [255, 0, 0, 255, 0, 255, 0, 255, 0, 0, 255, 255, 255, 255, 255, 128]
+        );
+
+        let rotation = model.nodes[0].transform.rotation;
+        let expected = Quaternion::from_angle_y(Rad(std::f32::consts::FRAC_PI_2));
+        assert_close(rotation.s, expected.s);
+        assert_vector_close(rotation.v, expected.v);
+    }
+
+    #[test]
+    fn loads_skin_and_animation() {
+        let mut model = load(fixture("simple_skin.gltf")).unwrap();
+        let primitive = &model.meshes[0].primitives[0];
+        assert_eq!(primitive.vertices.len(), 6);
+        assert_eq!(primitive.indices.len(), 12);
+        assert!(primitive.vertices.iter().all(|v| v.joints == [0, 1, 0, 0]));
+        assert_eq!(primitive.vertices[0].weights, [1.0, 0.0, 0.0, 0.0]);
+        assert_eq!(primitive.vertices[2].weights, [0.5, 0.5, 0.0, 0.0]);
+        assert_eq!(primitive.vertices[5].weights, [0.0, 1.0, 0.0, 0.0]);
+
+        assert_eq!(model.nodes[0].skin, Some(0));
+        assert_eq!(model.nodes[1].children, [2]);
+        assert_eq!(model.nodes[2].parent, Some(1));
+        assert_eq!(model.root_nodes(), [0, 1]);
+
+        let skin = &model.skins[0];
+        assert_eq!(skin.name, "Armature");
+        assert_eq!(skin.joints, [1, 2]);
+        assert_eq!(skin.skeleton, Some(1));
+        assert_eq!(skin.inverse_bind_matrices[0], Matrix4::from_scale(1.0));
+        assert_eq!(
+            skin.inverse_bind_matrices[1],
+            Matrix4::from_translation(Vector3::new(0.0, -1.0, 0.0))
+        );
+        // In the bind pose every joint times its inverse bind matrix is the identity.
+        let world = model.world_transforms();
+        for (joint, inverse_bind) in skin.joints.iter().zip(&skin.inverse_bind_matrices) {
+            let skinning = world[*joint] * inverse_bind;
+            let moved = skinning.transform_point(Point3::new(1.0, 2.0, 0.0));
+            assert_vector_close(
+                Vector3::new(moved.x, moved.y, moved.z),
+                Vector3::new(1.0, 2.0, 0.0),
+            );
+        }
+
+        let animation = &model.animations[0];
+        assert_eq!(animation.name, "Bend");
+        assert_eq!(animation.channels.len(), 2);
+        assert_eq!(animation.duration(), 1.0);
+        assert_eq!(animation.channels[0].interpolation, Interpolation::Linear);
+        assert_eq!(animation.channels[1].interpolation, Interpolation::Step);
+        assert!(matches!(
+            animation.channels[0].values,
+            ChannelValues::Rotations(_)
+        ));
+
+        model.animate(0, 0.5);
+        let tip = model.nodes[2].transform;
+        let expected = Quaternion::from_angle_z(Rad(std::f32::consts::FRAC_PI_4));
+        assert_close(tip.rotation.s, expected.s);
+        assert_vector_close(tip.rotation.v, expected.v);
+        // The step channel holds the first key until the second one.
+        assert_vector_close(tip.translation, Vector3::new(0.0, 1.0, 0.0));
+        model.animate(0, 1.0);
+        assert_vector_close(
+            model.nodes[2].transform.translation,
+            Vector3::new(0.0, 1.5, 0.0),
+        );
+        assert_ne!(model.nodes[2].transform, Transform::default());
+    }
+
+    #[test]
+    fn expands_fans_and_loops() {
+        let model = load(fixture("primitive_modes.gltf")).unwrap();
+        let [fan, line_loop, strip] = &model.meshes[0].primitives[..] else {
+            panic!("expected three primitives");
+        };
+        assert_eq!(fan.topology, Topology::Triangles);
+        assert_eq!(fan.indices, Indices::U16(vec![0, 1, 2, 0, 2, 3]));
+        assert_eq!(line_loop.topology, Topology::Lines);
+        assert_eq!(
+            line_loop.indices,
+            Indices::U16(vec![0, 1, 1, 2, 2, 3, 3, 0])
+        );
+        assert_eq!(strip.topology, Topology::TriangleStrip);
+        assert_eq!(strip.indices, Indices::U16(vec![0, 1, 2, 3]));
+    }
+
+    fn triangle_json(extra_primitive: &str, buffer: &str) -> String {
+        // Three float3 positions followed by three u16 indices (0, 1, 5).
+        format!(
+            r#"{{
+                "asset": {{"version": "2.0"}},
+                "meshes": [{{"primitives": [{{"attributes": {{"POSITION": 0}}{extra_primitive}}}]}}],
+                "buffers": [{buffer}],
+                "bufferViews": [
+                    {{"buffer": 0, "byteLength": 36}},
+                    {{"buffer": 0, "byteOffset": 36, "byteLength": 6}}
+                ],
+                "accessors": [
+                    {{"bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+                      "min": [0, 0, 0], "max": [1, 1, 0]}},
+                    {{"bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR"}}
+                ]
+            }}"#
+        )
+    }
+
+    fn triangle_buffer() -> String {
+        let mut bytes = Vec::new();
+        for value in [0.0f32, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        for index in [0u16, 1, 5] {
+            bytes.extend_from_slice(&index.to_le_bytes());
+        }
+        format!(
+            r#"{{"byteLength": {}, "uri": "data:application/octet-stream;base64,{}"}}"#,
+            bytes.len(),
+            base64::engine::general_purpose::STANDARD.encode(&bytes)
+        )
+    }
+
+    #[test]
+    fn rejects_out_of_range_indices() {
+        let json = triangle_json(r#", "indices": 1"#, &triangle_buffer());
+        assert!(matches!(
+            load_from_slice(json.as_bytes(), None),
+            Err(GltfError::InvalidAccessor {
+                mesh: 0,
+                primitive: 0
+            })
+        ));
+        let json = triangle_json("", &triangle_buffer());
+        assert!(load_from_slice(json.as_bytes(), None).is_ok());
+    }
+
+    #[test]
+    fn rejects_remote_uris() {
+        let json = triangle_json(
+            "",
+            r#"{"byteLength": 42, "uri": "https://example.com/triangle.bin"}"#,
+        );
+        assert!(matches!(
+            load_from_slice(json.as_bytes(), None),
+            Err(GltfError::UnsupportedUri(uri)) if uri.starts_with("https://")
+        ));
+    }
+
+    #[test]
+    fn rejects_missing_binary_chunk() {
+        let json = triangle_json("", r#"{"byteLength": 42}"#);
+        assert!(matches!(
+            load_from_slice(json.as_bytes(), None),
+            Err(GltfError::MissingBinaryChunk)
+        ));
+    }
+
+    #[test]
+    fn rejects_image_views_past_the_binary_chunk() {
+        assert!(matches!(
+            load(fixture("truncated_image.glb")),
+            Err(GltfError::BufferViewOutOfRange { view: 0 })
+        ));
+    }
+
+    #[test]
+    fn reports_missing_files() {
+        let error = load(fixture("does_not_exist.gltf")).unwrap_err();
+        assert!(error.to_string().contains("does_not_exist.gltf"));
+        assert!(matches!(error, GltfError::Io { .. }));
+    }
+
+    #[test]
+    fn decodes_percent_escapes() {
+        assert_eq!(percent_decode("a%20b%2Fc"), "a b/c");
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%zz"), "%zz");
+    }
+}
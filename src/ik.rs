@@ -0,0 +1,675 @@
+use std::f32::consts::PI;
+
+use cgmath::{Euler, InnerSpace, Matrix4, Quaternion, Rad, Rotation3, SquareMatrix, Vector3, Zero};
+
+use crate::pmd_loader::PmdModel;
+use crate::pmx_loader::PmxModel;
+use crate::skeleton::{Pose, Skeleton};
+
+pub const KNEE_MIN: Vector3<f32> = Vector3::new(-PI, 0.0, 0.0);
+pub const KNEE_MAX: Vector3<f32> = Vector3::new(-0.008, 0.0, 0.0);
+
+const EPSILON: f32 = 1e-6;
+/// How far a straight hinge is bent to give the solver a plane to fold in.
+const HINGE_KICK: f32 = 0.1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IkMethod {
+    Ccd,
+    Fabrik,
+}
+
+impl IkMethod {
+    pub const ALL: [IkMethod; 2] = [IkMethod::Ccd, IkMethod::Fabrik];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            IkMethod::Ccd => "ccd",
+            IkMethod::Fabrik => "fabrik",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AngleLimits {
+    pub min: Vector3<f32>,
+    pub max: Vector3<f32>,
+}
+
+impl AngleLimits {
+    pub const KNEE: AngleLimits = AngleLimits {
+        min: KNEE_MIN,
+        max: KNEE_MAX,
+    };
+
+    pub fn apply(&self, rotation: Quaternion<f32>) -> Quaternion<f32> {
+        let euler = Euler::from(rotation);
+        let clamp = |angle: Rad<f32>, min: f32, max: f32| Rad(angle.0.clamp(min, max));
+        Quaternion::from(Euler {
+            x: clamp(euler.x, self.min.x, self.max.x),
+            y: clamp(euler.y, self.min.y, self.max.y),
+            z: clamp(euler.z, self.min.z, self.max.z),
+        })
+        .normalize()
+    }
+
+    /// The local axis of a hinge (exactly one axis with a non-empty range),
+    /// signed so that positive rotations bend towards the middle of the range.
+    pub fn hinge_axis(&self) -> Option<Vector3<f32>> {
+        let range = self.max - self.min;
+        let mut free = (0..3).filter(|&i| range[i] > EPSILON);
+        let axis = free.next()?;
+        if free.next().is_some() {
+            return None;
+        }
+        let mut hinge = Vector3::zero();
+        hinge[axis] = if self.min[axis] + self.max[axis] < 0.0 {
+            -1.0
+        } else {
+            1.0
+        };
+        Some(hinge)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IkLink {
+    pub bone: usize,
+    pub limits: Option<AngleLimits>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IkChain {
+    pub name: String,
+    pub ik_bone: usize,
+    pub target: usize,
+    pub iterations: u32,
+    pub limit_angle: f32,
+    pub links: Vec<IkLink>,
+}
+
+impl IkChain {
+    pub fn from_pmd(pmd: &PmdModel) -> Vec<IkChain> {
+        pmd.ik_chains
+            .iter()
+            .filter(|ik| (ik.bone as usize) < pmd.bones.len())
+            .map(|ik| IkChain {
+                name: pmd.bones[ik.bone as usize].name.clone(),
+                ik_bone: ik.bone as usize,
+                target: ik.target as usize,
+                iterations: ik.iterations as u32,
+                limit_angle: ik.limit_angle * 4.0,
+                links: ik
+                    .chain
+                    .iter()
+                    .map(|&bone| IkLink {
+                        bone: bone as usize,
+                        limits: pmd
+                            .bones
+                            .get(bone as usize)
+                            .filter(|b| b.name.contains("ひざ"))
+                            .map(|_| AngleLimits::KNEE),
+                    })
+                    .collect(),
+            })
+            .collect()
+    }
+
+    pub fn from_pmx(pmx: &PmxModel) -> Vec<IkChain> {
+        pmx.bones
+            .iter()
+            .enumerate()
+            .filter_map(|(index, bone)| {
+                let ik = bone.ik.as_ref()?;
+                Some(IkChain {
+                    name: bone.name.clone(),
+                    ik_bone: index,
+                    target: ik.target? as usize,
+                    iterations: ik.iterations.max(0) as u32,
+                    limit_angle: ik.limit_angle,
+                    links: ik
+                        .links
+                        .iter()
+                        .filter_map(|link| {
+                            Some(IkLink {
+                                bone: link.bone? as usize,
+                                limits: link.limits.map(|(min, max)| AngleLimits { min, max }),
+                            })
+                        })
+                        .collect(),
+                })
+            })
+            .collect()
+    }
+
+    fn is_valid(&self, skeleton: &Skeleton) -> bool {
+        let len = skeleton.len();
+        self.ik_bone < len && self.target < len && self.links.iter().all(|l| l.bone < len)
+    }
+
+    pub fn solve(
+        &self,
+        method: IkMethod,
+        skeleton: &Skeleton,
+        pose: &mut Pose,
+        matrices: &mut [Matrix4<f32>],
+    ) {
+        if !self.is_valid(skeleton) || self.links.is_empty() {
+            return;
+        }
+        if pose.bones.len() < skeleton.len() {
+            pose.bones.resize(skeleton.len(), Default::default());
+        }
+        match method {
+            IkMethod::Ccd => self.solve_ccd(skeleton, pose, matrices),
+            IkMethod::Fabrik => self.solve_fabrik(skeleton, pose, matrices),
+        }
+    }
+
+    fn solve_ccd(&self, skeleton: &Skeleton, pose: &mut Pose, matrices: &mut [Matrix4<f32>]) {
+        let goal = skeleton.world_position(self.ik_bone, matrices);
+        for _ in 0..self.iterations {
+            let effector = skeleton.world_position(self.target, matrices);
+            if (effector - goal).magnitude2() < EPSILON {
+                break;
+            }
+            for link in &self.links {
+                let effector = skeleton.world_position(self.target, matrices);
+                rotate_toward(
+                    skeleton,
+                    link,
+                    pose,
+                    matrices,
+                    effector,
+                    goal,
+                    self.limit_angle,
+                );
+            }
+        }
+    }
+
+    fn solve_fabrik(&self, skeleton: &Skeleton, pose: &mut Pose, matrices: &mut [Matrix4<f32>]) {
+        let goal = skeleton.world_position(self.ik_bone, matrices);
+        let links: Vec<&IkLink> = self.links.iter().rev().collect();
+        let joints: Vec<usize> = links
+            .iter()
+            .map(|link| link.bone)
+            .chain([self.target])
+            .collect();
+
+        for _ in 0..self.iterations {
+            let mut positions: Vec<Vector3<f32>> = joints
+                .iter()
+                .map(|&bone| skeleton.world_position(bone, matrices))
+                .collect();
+            let last = positions.len() - 1;
+            if (positions[last] - goal).magnitude2() < EPSILON {
+                break;
+            }
+            if self.unfold_straight_hinges(skeleton, pose, matrices, goal) {
+                positions = joints
+                    .iter()
+                    .map(|&bone| skeleton.world_position(bone, matrices))
+                    .collect();
+            }
+            let lengths: Vec<f32> = positions
+                .windows(2)
+                .map(|pair| (pair[1] - pair[0]).magnitude())
+                .collect();
+            let root = positions[0];
+
+            positions[last] = goal;
+            for i in (0..last).rev() {
+                positions[i] =
+                    positions[i + 1] + direction(positions[i + 1], positions[i]) * lengths[i];
+            }
+            positions[0] = root;
+            for i in 1..=last {
+                positions[i] =
+                    positions[i - 1] + direction(positions[i - 1], positions[i]) * lengths[i - 1];
+            }
+
+            for (i, link) in links.iter().enumerate() {
+                let child = skeleton.world_position(joints[i + 1], matrices);
+                rotate_toward(skeleton, link, pose, matrices, child, positions[i + 1], PI);
+            }
+        }
+    }
+
+    /// Bends straight hinges that the position passes would keep straight, then
+    /// swings the links above them so the joints leave the line to the goal.
+    fn unfold_straight_hinges(
+        &self,
+        skeleton: &Skeleton,
+        pose: &mut Pose,
+        matrices: &mut [Matrix4<f32>],
+        goal: Vector3<f32>,
+    ) -> bool {
+        let mut bent = false;
+        for link in &self.links {
+            let effector = skeleton.world_position(self.target, matrices);
+            if bent {
+                rotate_toward(
+                    skeleton,
+                    link,
+                    pose,
+                    matrices,
+                    effector,
+                    goal,
+                    self.limit_angle,
+                );
+            } else {
+                bent = bend_straight_hinge(
+                    skeleton,
+                    link,
+                    pose,
+                    matrices,
+                    effector,
+                    goal,
+                    self.limit_angle,
+                );
+            }
+        }
+        bent
+    }
+}
+
+pub fn solve_all(
+    chains: &[IkChain],
+    method: IkMethod,
+    skeleton: &Skeleton,
+    pose: &mut Pose,
+    matrices: &mut [Matrix4<f32>],
+    enabled: impl Fn(&IkChain) -> bool,
+) {
+    for chain in chains.iter().filter(|chain| enabled(chain)) {
+        chain.solve(method, skeleton, pose, matrices);
+    }
+}
+
+fn direction(from: Vector3<f32>, to: Vector3<f32>) -> Vector3<f32> {
+    let v = to - from;
+    if v.magnitude2() < EPSILON * EPSILON {
+        Vector3::new(0.0, 0.0, 0.0)
+    } else {
+        v.normalize()
+    }
+}
+
+/// Bone-local directions from a link to `from` and `to`, or `None` when either is degenerate.
+fn local_directions(
+    skeleton: &Skeleton,
+    link: &IkLink,
+    matrices: &[Matrix4<f32>],
+    from: Vector3<f32>,
+    to: Vector3<f32>,
+) -> Option<(Vector3<f32>, Vector3<f32>)> {
+    let inverse = matrices[link.bone].invert()?;
+    let origin = skeleton.bones[link.bone].position;
+    let local = |p: Vector3<f32>| (inverse * p.extend(1.0)).truncate() - origin;
+    let (v1, v2) = (local(from), local(to));
+    if v1.magnitude2() < EPSILON || v2.magnitude2() < EPSILON {
+        return None;
+    }
+    Some((v1, v2))
+}
+
+fn apply_delta(
+    skeleton: &Skeleton,
+    link: &IkLink,
+    pose: &mut Pose,
+    matrices: &mut [Matrix4<f32>],
+    delta: Quaternion<f32>,
+) {
+    let transform = &mut pose.bones[link.bone];
+    let rotation = (transform.rotation * delta).normalize();
+    transform.rotation = match &link.limits {
+        Some(limits) => limits.apply(rotation),
+        None => rotation,
+    };
+    skeleton.update_subtree(link.bone, pose, matrices);
+}
+
+/// Hinge angle (about `axis`) taking `v1` onto `v2` once both are projected
+/// onto the hinge plane.
+fn hinge_angle(axis: Vector3<f32>, v1: Vector3<f32>, v2: Vector3<f32>) -> Option<f32> {
+    let p1 = v1 - axis * v1.dot(axis);
+    let p2 = v2 - axis * v2.dot(axis);
+    if p1.magnitude2() < EPSILON || p2.magnitude2() < EPSILON {
+        return None;
+    }
+    Some(axis.dot(p1.cross(p2)).atan2(p1.dot(p2)))
+}
+
+/// Bends a hinge link that sees the effector and a closer goal on one line,
+/// where no rotation about the hinge brings them nearer. Returns whether it bent.
+fn bend_straight_hinge(
+    skeleton: &Skeleton,
+    link: &IkLink,
+    pose: &mut Pose,
+    matrices: &mut [Matrix4<f32>],
+    from: Vector3<f32>,
+    to: Vector3<f32>,
+    max_angle: f32,
+) -> bool {
+    let Some(axis) = link.limits.as_ref().and_then(AngleLimits::hinge_axis) else {
+        return false;
+    };
+    let Some((v1, v2)) = local_directions(skeleton, link, matrices, from, to) else {
+        return false;
+    };
+    let straight = v1.normalize().cross(v2.normalize()).magnitude2() < EPSILON;
+    if !straight || v1.dot(v2) < 0.0 || v2.magnitude2() >= v1.magnitude2() {
+        return false;
+    }
+    let delta = Quaternion::from_axis_angle(axis, Rad(HINGE_KICK.min(max_angle)));
+    apply_delta(skeleton, link, pose, matrices, delta);
+    true
+}
+
+fn rotate_toward(
+    skeleton: &Skeleton,
+    link: &IkLink,
+    pose: &mut Pose,
+    matrices: &mut [Matrix4<f32>],
+    from: Vector3<f32>,
+    to: Vector3<f32>,
+    max_angle: f32,
+) {
+    let Some((v1, v2)) = local_directions(skeleton, link, matrices, from, to) else {
+        return;
+    };
+    let delta = match link.limits.as_ref().and_then(AngleLimits::hinge_axis) {
+        // Hinges (MMD knees) only turn about their free axis.
+        Some(axis) => {
+            let Some(angle) = hinge_angle(axis, v1, v2) else {
+                return;
+            };
+            if angle.abs() < EPSILON {
+                bend_straight_hinge(skeleton, link, pose, matrices, from, to, max_angle);
+                return;
+            }
+            Quaternion::from_axis_angle(axis, Rad(angle.clamp(-max_angle, max_angle)))
+        }
+        None => {
+            let (v1, v2) = (v1.normalize(), v2.normalize());
+            let axis = v1.cross(v2);
+            if axis.magnitude2() < EPSILON * EPSILON {
+                return;
+            }
+            let angle = v1.dot(v2).clamp(-1.0, 1.0).acos().min(max_angle);
+            Quaternion::from_axis_angle(axis.normalize(), Rad(angle))
+        }
+    };
+    apply_delta(skeleton, link, pose, matrices, delta);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::skeleton::Bone;
+    use cgmath::Deg;
+
+    const TOLERANCE: f32 = 1e-2;
+
+    const SHOULDER: usize = 0;
+    const ELBOW: usize = 1;
+    const WRIST: usize = 2;
+    const GOAL: usize = 3;
+
+    /// A straight two-segment arm along +y with a free-standing IK bone at `goal`.
+    fn arm(goal: Vector3<f32>) -> Skeleton {
+        let bone = |name: &str, parent, position| Bone {
+            name: name.to_string(),
+            parent,
+            children: Vec::new(),
+            position,
+        };
+        Skeleton::new(vec![
+            bone("shoulder", None, Vector3::zero()),
+            bone("elbow", Some(SHOULDER), Vector3::new(0.0, 1.0, 0.0)),
+            bone("wrist", Some(ELBOW), Vector3::new(0.0, 2.0, 0.0)),
+            bone("ik", None, goal),
+        ])
+    }
+
+    fn chain(elbow_limits: Option<AngleLimits>) -> IkChain {
+        IkChain {
+            name: "ik".to_string(),
+            ik_bone: GOAL,
+            target: WRIST,
+            iterations: 40,
+            limit_angle: PI,
+            links: vec![
+                IkLink {
+                    bone: ELBOW,
+                    limits: elbow_limits,
+                },
+                IkLink {
+                    bone: SHOULDER,
+                    limits: None,
+                },
+            ],
+        }
+    }
+
+    fn solve(chain: &IkChain, method: IkMethod, skeleton: &Skeleton) -> (Pose, Vec<Matrix4<f32>>) {
+        let mut pose = Pose::rest(skeleton.len());
+        let mut matrices = skeleton.skinning_matrices(&pose);
+        chain.solve(method, skeleton, &mut pose, &mut matrices);
+        (pose, matrices)
+    }
+
+    fn distance(skeleton: &Skeleton, matrices: &[Matrix4<f32>], a: usize, b: usize) -> f32 {
+        (skeleton.world_position(a, matrices) - skeleton.world_position(b, matrices)).magnitude()
+    }
+
+    #[test]
+    fn both_solvers_reach_a_reachable_target() {
+        for goal in [
+            Vector3::new(1.0, 1.0, 0.0),
+            Vector3::new(0.5, 0.5, 1.0),
+            Vector3::new(-1.2, 0.3, 0.4),
+        ] {
+            let skeleton = arm(goal);
+            for method in IkMethod::ALL {
+                let (_, matrices) = solve(&chain(None), method, &skeleton);
+                let reached = skeleton.world_position(WRIST, &matrices);
+                assert!(
+                    (reached - goal).magnitude() < TOLERANCE,
+                    "{method:?} reached {reached:?} for {goal:?}"
+                );
+                // Solving only rotates, so segment lengths are preserved.
+                assert!((distance(&skeleton, &matrices, SHOULDER, ELBOW) - 1.0).abs() < 1e-4);
+                assert!((distance(&skeleton, &matrices, ELBOW, WRIST) - 1.0).abs() < 1e-4);
+            }
+        }
+    }
+
+    #[test]
+    fn both_solvers_stretch_toward_an_unreachable_target() {
+        let goal = Vector3::new(3.0, 3.0, 0.0);
+        let skeleton = arm(goal);
+        for method in IkMethod::ALL {
+            let (_, matrices) = solve(&chain(None), method, &skeleton);
+            let reached = skeleton.world_position(WRIST, &matrices);
+            let expected = goal.normalize() * 2.0;
+            assert!(
+                (reached - expected).magnitude() < TOLERANCE,
+                "{method:?} reached {reached:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn target_already_at_goal_leaves_pose_untouched() {
+        let skeleton = arm(Vector3::new(0.0, 2.0, 0.0));
+        for method in IkMethod::ALL {
+            let (pose, _) = solve(&chain(None), method, &skeleton);
+            assert_eq!(pose, Pose::rest(skeleton.len()));
+        }
+    }
+
+    #[test]
+    fn locked_joint_stays_locked() {
+        let locked = AngleLimits {
+            min: Vector3::zero(),
+            max: Vector3::zero(),
+        };
+        let goal = Vector3::new(2.0, 0.0, 0.0);
+        let skeleton = arm(goal);
+        for method in IkMethod::ALL {
+            let (pose, matrices) = solve(&chain(Some(locked)), method, &skeleton);
+            let elbow = pose.bones[ELBOW].rotation;
+            assert!((elbow.s - 1.0).abs() < 1e-4, "{method:?} bent {elbow:?}");
+            // The shoulder alone can still swing the straight arm onto the goal. FABRIK's
+            // position pass does not know about the lock, so it only closes in on it.
+            let error = (skeleton.world_position(WRIST, &matrices) - goal).magnitude();
+            let tolerance = match method {
+                IkMethod::Ccd => TOLERANCE,
+                IkMethod::Fabrik => 0.25,
+            };
+            assert!(error < tolerance, "{method:?} left the wrist {error} away");
+        }
+    }
+
+    #[test]
+    fn knee_limits_clamp_each_axis() {
+        let bent = Quaternion::from_angle_x(Deg(-60.0));
+        let applied = AngleLimits::KNEE.apply(bent);
+        assert!((applied.s - bent.s).abs() < 1e-4);
+        assert!((applied.v.x - bent.v.x).abs() < 1e-4);
+
+        // Bending forwards is clamped to the small negative stop.
+        let hyperextended = AngleLimits::KNEE.apply(Quaternion::from_angle_x(Deg(30.0)));
+        let euler = Euler::from(hyperextended);
+        assert!((euler.x.0 - KNEE_MAX.x).abs() < 1e-4);
+
+        // Twisting is removed entirely.
+        let twisted = AngleLimits::KNEE.apply(Quaternion::from_angle_y(Deg(40.0)));
+        let euler = Euler::from(twisted);
+        assert!(euler.y.0.abs() < 1e-4);
+        assert!(euler.x.0 <= KNEE_MAX.x + 1e-4);
+    }
+
+    #[test]
+    fn knee_chain_respects_its_limits() {
+        // The ankle has to move up and forward.
+        let goal = Vector3::new(0.0, 0.6, 0.8);
+        let skeleton = leg(goal);
+        let leg_chain = chain(Some(AngleLimits::KNEE));
+        for method in IkMethod::ALL {
+            let (pose, matrices) = solve(&leg_chain, method, &skeleton);
+            let knee = Euler::from(pose.bones[ELBOW].rotation);
+            assert!(knee.x.0 <= KNEE_MAX.x + 1e-4, "{method:?} knee {knee:?}");
+            assert!(knee.x.0 >= KNEE_MIN.x - 1e-4);
+            assert!(knee.y.0.abs() < 1e-4 && knee.z.0.abs() < 1e-4);
+            let error = (skeleton.world_position(WRIST, &matrices) - goal).magnitude();
+            assert!(error < 1e-3, "{method:?} left the ankle {error} away");
+        }
+    }
+
+    /// A straight leg hanging along -y from the hip, with the IK bone at `goal`.
+    fn leg(goal: Vector3<f32>) -> Skeleton {
+        let bone = |name: &str, parent, position| Bone {
+            name: name.to_string(),
+            parent,
+            children: Vec::new(),
+            position,
+        };
+        Skeleton::new(vec![
+            bone("hip", None, Vector3::new(0.0, 2.0, 0.0)),
+            bone("knee", Some(0), Vector3::new(0.0, 1.0, 0.0)),
+            bone("ankle", Some(1), Vector3::zero()),
+            bone("leg ik", None, goal),
+        ])
+    }
+
+    #[test]
+    fn straight_leg_bends_to_a_goal_straight_above_the_ankle() {
+        // A crouch from the rest pose: hip, knee, ankle and goal all lie on one line.
+        let goal = Vector3::new(0.0, 0.5, 0.0);
+        let skeleton = leg(goal);
+        let leg_chain = chain(Some(AngleLimits::KNEE));
+        for method in IkMethod::ALL {
+            let (pose, matrices) = solve(&leg_chain, method, &skeleton);
+            let error = (skeleton.world_position(WRIST, &matrices) - goal).magnitude();
+            assert!(error < 1e-3, "{method:?} left the ankle {error} away");
+            let knee = Euler::from(pose.bones[ELBOW].rotation);
+            assert!(knee.x.0 < -0.5, "{method:?} knee {knee:?}");
+            assert!(knee.y.0.abs() < 1e-4 && knee.z.0.abs() < 1e-4);
+            // The knee comes forwards; MMD models face -z.
+            assert!(skeleton.world_position(ELBOW, &matrices).z < 0.0);
+        }
+    }
+
+    #[test]
+    fn hinge_axis_follows_the_free_limit() {
+        assert_eq!(
+            AngleLimits::KNEE.hinge_axis(),
+            Some(Vector3::new(-1.0, 0.0, 0.0))
+        );
+        let elbow = AngleLimits {
+            min: Vector3::new(0.0, 0.0, 0.0),
+            max: Vector3::new(0.0, 0.0, 2.0),
+        };
+        assert_eq!(elbow.hinge_axis(), Some(Vector3::new(0.0, 0.0, 1.0)));
+        let free = AngleLimits {
+            min: Vector3::new(-1.0, -1.0, 0.0),
+            max: Vector3::new(1.0, 1.0, 0.0),
+        };
+        assert_eq!(free.hinge_axis(), None);
+        let locked = AngleLimits {
+            min: Vector3::zero(),
+            max: Vector3::zero(),
+        };
+        assert_eq!(locked.hinge_axis(), None);
+    }
+
+    #[test]
+    fn limit_angle_bounds_each_ccd_step() {
+        let goal = Vector3::new(2.0, 0.0, 0.0);
+        let skeleton = arm(goal);
+        let mut slow = chain(None);
+        slow.iterations = 1;
+        slow.limit_angle = 0.1;
+        let (pose, _) = solve(&slow, IkMethod::Ccd, &skeleton);
+        for bone in [SHOULDER, ELBOW] {
+            let angle = 2.0 * pose.bones[bone].rotation.s.clamp(-1.0, 1.0).acos();
+            assert!(angle <= 0.1 + 1e-4, "bone {bone} turned {angle}");
+        }
+    }
+
+    #[test]
+    fn solve_all_skips_disabled_and_invalid_chains() {
+        let skeleton = arm(Vector3::new(1.0, 1.0, 0.0));
+        let mut broken = chain(None);
+        broken.name = "broken".to_string();
+        broken.target = 99;
+        let chains = [chain(None), broken];
+
+        let mut pose = Pose::rest(skeleton.len());
+        let mut matrices = skeleton.skinning_matrices(&pose);
+        solve_all(
+            &chains,
+            IkMethod::Ccd,
+            &skeleton,
+            &mut pose,
+            &mut matrices,
+            |chain| chain.name != "ik",
+        );
+        assert_eq!(pose, Pose::rest(skeleton.len()));
+
+        solve_all(
+            &chains,
+            IkMethod::Ccd,
+            &skeleton,
+            &mut pose,
+            &mut matrices,
+            |_| true,
+        );
+        assert_ne!(pose, Pose::rest(skeleton.len()));
+        let reached = skeleton.world_position(WRIST, &matrices);
+        assert!((reached - Vector3::new(1.0, 1.0, 0.0)).magnitude() < TOLERANCE);
+    }
+}
@@ -0,0 +1,225 @@
+use std::collections::HashSet;
+
+use cgmath::{Point2, Vector2, Zero};
+use winit::event::{
+    DeviceEvent, ElementState, KeyboardInput, MouseButton, MouseScrollDelta, VirtualKeyCode,
+    WindowEvent,
+};
+
+const PIXELS_PER_SCROLL_LINE: f32 = 120.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InputEvent {
+    Key { key: VirtualKeyCode, pressed: bool },
+    MouseButton { button: MouseButton, pressed: bool },
+    MouseMotion { dx: f32, dy: f32 },
+    CursorMoved { x: f32, y: f32 },
+    Scroll { lines: f32 },
+    FocusLost,
+}
+
+impl InputEvent {
+    pub fn from_window_event(event: &WindowEvent) -> Option<Self> {
+        match event {
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        virtual_keycode: Some(key),
+                        state,
+                        ..
+                    },
+                ..
+            } => Some(InputEvent::Key {
+                key: *key,
+                pressed: *state == ElementState::Pressed,
+            }),
+            WindowEvent::MouseInput { state, button, .. } => Some(InputEvent::MouseButton {
+                button: *button,
+                pressed: *state == ElementState::Pressed,
+            }),
+            WindowEvent::CursorMoved { position, .. } => Some(InputEvent::CursorMoved {
+                x: position.x as f32,
+                y: position.y as f32,
+            }),
+            WindowEvent::MouseWheel { delta, .. } => Some(InputEvent::Scroll {
+                lines: match delta {
+                    MouseScrollDelta::LineDelta(_, y) => *y,
+                    MouseScrollDelta::PixelDelta(position) => {
+                        position.y as f32 / PIXELS_PER_SCROLL_LINE
+                    }
+                },
+            }),
+            WindowEvent::Focused(false) => Some(InputEvent::FocusLost),
+            _ => None,
+        }
+    }
+
+    pub fn from_device_event(event: &DeviceEvent) -> Option<Self> {
+        match event {
+            DeviceEvent::MouseMotion { delta: (dx, dy) } => Some(InputEvent::MouseMotion {
+                dx: *dx as f32,
+                dy: *dy as f32,
+            }),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct InputState {
+    keys_down: HashSet<VirtualKeyCode>,
+    keys_pressed: HashSet<VirtualKeyCode>,
+    keys_released: HashSet<VirtualKeyCode>,
+    buttons_down: HashSet<MouseButton>,
+    buttons_pressed: HashSet<MouseButton>,
+    buttons_released: HashSet<MouseButton>,
+    mouse_delta: Vector2<f32>,
+    cursor_position: Option<Point2<f32>>,
+    scroll: f32,
+}
+
+impl Default for InputState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InputState {
+    pub fn new() -> Self {
+        InputState {
+            keys_down: HashSet::new(),
+            keys_pressed: HashSet::new(),
+            keys_released: HashSet::new(),
+            buttons_down: HashSet::new(),
+            buttons_pressed: HashSet::new(),
+            buttons_released: HashSet::new(),
+            mouse_delta: Vector2::zero(),
+            cursor_position: None,
+            scroll: 0.0,
+        }
+    }
+
+    pub fn handle_event(&mut self, event: InputEvent) {
+        match event {
+            InputEvent::Key { key, pressed: true } => {
+                if self.keys_down.insert(key) {
+                    self.keys_pressed.insert(key);
+                }
+            }
+            InputEvent::Key {
+                key,
+                pressed: false,
+            } => {
+                if self.keys_down.remove(&key) {
+                    self.keys_released.insert(key);
+                }
+            }
+            InputEvent::MouseButton {
+                button,
+                pressed: true,
+            } => {
+                if self.buttons_down.insert(button) {
+                    self.buttons_pressed.insert(button);
+                }
+            }
+            InputEvent::MouseButton {
+                button,
+                pressed: false,
+            } => {
+                if self.buttons_down.remove(&button) {
+                    self.buttons_released.insert(button);
+                }
+            }
+            InputEvent::MouseMotion { dx, dy } => {
+                self.mouse_delta += Vector2::new(dx, dy);
+            }
+            InputEvent::CursorMoved { x, y } => {
+                self.cursor_position = Some(Point2::new(x, y));
+            }
+            InputEvent::Scroll { lines } => {
+                self.scroll += lines;
+            }
+            InputEvent::FocusLost => {
+                self.keys_released.extend(self.keys_down.drain());
+                self.buttons_released.extend(self.buttons_down.drain());
+            }
+        }
+    }
+
+    pub fn handle_window_event(&mut self, event: &WindowEvent) {
+        if let Some(event) = InputEvent::from_window_event(event) {
+            self.handle_event(event);
+        }
+    }
+
+    pub fn handle_device_event(&mut self, event: &DeviceEvent) {
+        if let Some(event) = InputEvent::from_device_event(event) {
+            self.handle_event(event);
+        }
+    }
+
+    pub fn end_frame(&mut self) {
+        self.keys_pressed.clear();
+        self.keys_released.clear();
+        self.buttons_pressed.clear();
+        self.buttons_released.clear();
+        self.mouse_delta = Vector2::zero();
+        self.scroll = 0.0;
+    }
+
+    pub fn is_key_down(&self, key: VirtualKeyCode) -> bool {
+        self.keys_down.contains(&key)
+    }
+
+    pub fn was_key_pressed(&self, key: VirtualKeyCode) -> bool {
+        self.keys_pressed.contains(&key)
+    }
+
+    pub fn was_key_released(&self, key: VirtualKeyCode) -> bool {
+        self.keys_released.contains(&key)
+    }
+
+    pub fn is_button_down(&self, button: MouseButton) -> bool {
+        self.buttons_down.contains(&button)
+    }
+
+    pub fn was_button_pressed(&self, button: MouseButton) -> bool {
+        self.buttons_pressed.contains(&button)
+    }
+
+    pub fn was_button_released(&self, button: MouseButton) -> bool {
+        self.buttons_released.contains(&button)
+    }
+
+    pub fn mouse_delta(&self) -> Vector2<f32> {
+        self.mouse_delta
+    }
+
+    pub fn cursor_position(&self) -> Option<Point2<f32>> {
+        self.cursor_position
+    }
+
+    pub fn scroll_delta(&self) -> f32 {
+        self.scroll
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RecordedFrame {
+    pub dt: f32,
+    pub events: Vec<InputEvent>,
+}
+
+pub fn replay<F>(frames: &[RecordedFrame], mut update: F)
+where
+    F: FnMut(&InputState, f32),
+{
+    let mut input = InputState::new();
+    for frame in frames {
+        for event in &frame.events {
+            input.handle_event(*event);
+        }
+        update(&input, frame.dt);
+        input.end_frame();
+    }
+}
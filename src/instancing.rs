@@ -0,0 +1,420 @@
+use cgmath::{Matrix4, Vector4};
+use windows::Win32::Graphics::Direct3D12::*;
+
+use crate::command_sink::CommandSink;
+use crate::model::DrawRange;
+use crate::skinning::upload_vertices;
+use crate::upload_ring::UploadRing;
+use crate::vertex_layout::{InputElement, VertexLayout};
+
+pub const MAX_INSTANCES_PER_FRAME: usize = 4096;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, VertexLayout)]
+#[vertex(slot = 1, per_instance)]
+pub struct InstanceData {
+    #[vertex(semantic = "INSTANCE_TRANSFORM")]
+    pub transform: Matrix4<f32>,
+    #[vertex(semantic = "INSTANCE_COLOR")]
+    pub color: Vector4<f32>,
+}
+
+impl InstanceData {
+    pub fn new(transform: Matrix4<f32>, color: Vector4<f32>) -> Self {
+        InstanceData { transform, color }
+    }
+}
+
+pub fn instanced_input_elements<V: VertexLayout>() -> Vec<InputElement> {
+    let mut elements = V::input_elements();
+    elements.extend(InstanceData::input_elements());
+    elements
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DrawKey {
+    pub mesh: usize,
+    pub primitive: usize,
+    pub material: usize,
+    pub start_index: u32,
+    pub index_count: u32,
+}
+
+impl DrawKey {
+    pub fn new(mesh: usize, primitive: usize, range: &DrawRange) -> Self {
+        DrawKey {
+            mesh,
+            primitive,
+            material: range.material,
+            start_index: range.start_index,
+            index_count: range.index_count,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InstanceBatch {
+    pub key: DrawKey,
+    pub first_instance: u32,
+    pub instance_count: u32,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct InstanceBatcher {
+    draws: Vec<(DrawKey, InstanceData)>,
+}
+
+impl InstanceBatcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, key: DrawKey, instance: InstanceData) {
+        self.draws.push((key, instance));
+    }
+
+    pub fn len(&self) -> usize {
+        self.draws.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.draws.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.draws.clear();
+    }
+
+    pub fn build(&self) -> InstancedDraws {
+        let mut draws = self.draws.clone();
+        draws.sort_by_key(|(key, _)| *key);
+        let overflow = draws.len().saturating_sub(MAX_INSTANCES_PER_FRAME);
+        draws.truncate(MAX_INSTANCES_PER_FRAME);
+
+        let mut batches: Vec<InstanceBatch> = Vec::new();
+        for (i, (key, _)) in draws.iter().enumerate() {
+            match batches.last_mut() {
+                Some(batch) if batch.key == *key => batch.instance_count += 1,
+                _ => batches.push(InstanceBatch {
+                    key: *key,
+                    first_instance: i as u32,
+                    instance_count: 1,
+                }),
+            }
+        }
+        InstancedDraws {
+            batches,
+            instances: draws.into_iter().map(|(_, instance)| instance).collect(),
+            overflow,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct InstancedDraws {
+    pub batches: Vec<InstanceBatch>,
+    pub instances: Vec<InstanceData>,
+    /// Instances past `MAX_INSTANCES_PER_FRAME` that were left out of the batches.
+    pub overflow: usize,
+}
+
+impl InstancedDraws {
+    pub fn upload(&self, ring: &mut UploadRing) -> Option<D3D12_VERTEX_BUFFER_VIEW> {
+        upload_vertices(ring, &self.instances)
+    }
+
+    pub fn submit<S, F, M>(
+        &self,
+        sink: &S,
+        instance_buffer: D3D12_VERTEX_BUFFER_VIEW,
+        mesh_views: F,
+        material_table: M,
+    ) where
+        S: CommandSink + ?Sized,
+        F: Fn(&DrawKey) -> (D3D12_VERTEX_BUFFER_VIEW, D3D12_INDEX_BUFFER_VIEW),
+        M: Fn(usize) -> Option<D3D12_GPU_DESCRIPTOR_HANDLE>,
+    {
+        let mut bound_mesh = None;
+        let mut bound_material = None;
+        for batch in &self.batches {
+            let mesh = (batch.key.mesh, batch.key.primitive);
+            if bound_mesh != Some(mesh) {
+                let (vertex_buffer, index_buffer) = mesh_views(&batch.key);
+                sink.set_vertex_buffers(0, &[vertex_buffer, instance_buffer]);
+                sink.set_index_buffer(&index_buffer);
+                bound_mesh = Some(mesh);
+            }
+            if let Some(handle) = material_table(batch.key.material) {
+                if bound_material != Some(handle) {
+                    sink.set_descriptor_table(0, handle);
+                    bound_material = Some(handle);
+                }
+            }
+            sink.draw_indexed_instanced(
+                batch.key.index_count,
+                batch.instance_count,
+                batch.key.start_index,
+                0,
+                batch.first_instance,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command_sink::{RecordedCommand, RecordedCommands};
+    use cgmath::{SquareMatrix, Vector3};
+    use windows::Win32::Graphics::Dxgi::Common::DXGI_FORMAT_R32_UINT;
+
+    fn key(mesh: usize, primitive: usize, material: usize) -> DrawKey {
+        DrawKey::new(
+            mesh,
+            primitive,
+            &DrawRange {
+                start_index: primitive as u32 * 6,
+                index_count: 6,
+                material,
+            },
+        )
+    }
+
+    fn instance(id: f32) -> InstanceData {
+        InstanceData::new(
+            Matrix4::from_translation(Vector3::new(id, 0.0, 0.0)),
+            Vector4::new(1.0, 1.0, 1.0, 1.0),
+        )
+    }
+
+    fn views(key: &DrawKey) -> (D3D12_VERTEX_BUFFER_VIEW, D3D12_INDEX_BUFFER_VIEW) {
+        let base = ((key.mesh * 16 + key.primitive) as u64) << 12;
+        (
+            D3D12_VERTEX_BUFFER_VIEW {
+                BufferLocation: base,
+                SizeInBytes: 256,
+                StrideInBytes: 32,
+            },
+            D3D12_INDEX_BUFFER_VIEW {
+                BufferLocation: base + 0x800,
+                SizeInBytes: 24,
+                Format: DXGI_FORMAT_R32_UINT,
+            },
+        )
+    }
+
+    fn material(material: usize) -> Option<D3D12_GPU_DESCRIPTOR_HANDLE> {
+        Some(D3D12_GPU_DESCRIPTOR_HANDLE {
+            ptr: 0x100 + material as u64 * 32,
+        })
+    }
+
+    const INSTANCES: D3D12_VERTEX_BUFFER_VIEW = D3D12_VERTEX_BUFFER_VIEW {
+        BufferLocation: 0xF000,
+        SizeInBytes: 4096,
+        StrideInBytes: 80,
+    };
+
+    fn commands_of(
+        commands: &[RecordedCommand],
+        pick: fn(&RecordedCommand) -> bool,
+    ) -> Vec<RecordedCommand> {
+        commands.iter().filter(|c| pick(c)).cloned().collect()
+    }
+
+    #[test]
+    fn batches_instances_by_key() {
+        let mut batcher = InstanceBatcher::new();
+        batcher.push(key(1, 0, 0), instance(0.0));
+        batcher.push(key(0, 0, 0), instance(1.0));
+        batcher.push(key(1, 0, 0), instance(2.0));
+        batcher.push(key(0, 1, 0), instance(3.0));
+        batcher.push(key(0, 0, 0), instance(4.0));
+        assert_eq!(batcher.len(), 5);
+
+        let draws = batcher.build();
+        let summary: Vec<(DrawKey, u32, u32)> = draws
+            .batches
+            .iter()
+            .map(|b| (b.key, b.first_instance, b.instance_count))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (key(0, 0, 0), 0, 2),
+                (key(0, 1, 0), 2, 1),
+                (key(1, 0, 0), 3, 2),
+            ]
+        );
+        // Instances are grouped per batch, keeping their push order inside it.
+        let ids: Vec<f32> = draws.instances.iter().map(|i| i.transform.w.x).collect();
+        assert_eq!(ids, [1.0, 4.0, 3.0, 0.0, 2.0]);
+
+        batcher.clear();
+        assert!(batcher.is_empty());
+        assert_eq!(batcher.build(), InstancedDraws::default());
+    }
+
+    #[test]
+    fn build_keeps_the_instances_that_fit() {
+        let mut batcher = InstanceBatcher::new();
+        for i in 0..MAX_INSTANCES_PER_FRAME + 10 {
+            batcher.push(key(i % 2, 0, 0), instance(i as f32));
+        }
+        let draws = batcher.build();
+        assert_eq!(draws.overflow, 10);
+        assert_eq!(draws.instances.len(), MAX_INSTANCES_PER_FRAME);
+        let summary: Vec<(DrawKey, u32, u32)> = draws
+            .batches
+            .iter()
+            .map(|b| (b.key, b.first_instance, b.instance_count))
+            .collect();
+        let half = (MAX_INSTANCES_PER_FRAME / 2) as u32;
+        // The first key fits whole; the last batch loses the overflow.
+        assert_eq!(
+            summary,
+            [
+                (key(0, 0, 0), 0, half + 5),
+                (key(1, 0, 0), half + 5, half - 5)
+            ]
+        );
+
+        let sink = RecordedCommands::new();
+        draws.submit(&sink, INSTANCES, views, material);
+        assert_eq!(sink.draw_count(), 2);
+
+        batcher.clear();
+        batcher.push(key(0, 0, 0), instance(0.0));
+        assert_eq!(batcher.build().overflow, 0);
+    }
+
+    #[test]
+    fn submit_draws_each_batch_once() {
+        let mut batcher = InstanceBatcher::new();
+        for i in 0..10 {
+            batcher.push(key(i % 2, 0, 0), instance(i as f32));
+        }
+        let draws = batcher.build();
+        let sink = RecordedCommands::new();
+        draws.submit(&sink, INSTANCES, views, material);
+
+        assert_eq!(sink.draw_count(), 2);
+        let draws = commands_of(&sink.commands(), |c| {
+            matches!(c, RecordedCommand::DrawIndexedInstanced { .. })
+        });
+        assert_eq!(
+            draws,
+            [
+                RecordedCommand::DrawIndexedInstanced {
+                    index_count: 6,
+                    instance_count: 5,
+                    start_index: 0,
+                    base_vertex: 0,
+                    start_instance: 0,
+                },
+                RecordedCommand::DrawIndexedInstanced {
+                    index_count: 6,
+                    instance_count: 5,
+                    start_index: 0,
+                    base_vertex: 0,
+                    start_instance: 5,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn submit_binds_meshes_only_when_they_change() {
+        let mut batcher = InstanceBatcher::new();
+        // One primitive drawn with two ranges (e.g. two LOD levels) and a second mesh.
+        batcher.push(key(0, 0, 0), instance(0.0));
+        batcher.push(
+            DrawKey {
+                start_index: 60,
+                ..key(0, 0, 0)
+            },
+            instance(1.0),
+        );
+        batcher.push(key(1, 0, 0), instance(2.0));
+        let sink = RecordedCommands::new();
+        batcher.build().submit(&sink, INSTANCES, views, |_| None);
+
+        let commands = sink.commands();
+        let vertex_bindings = commands_of(&commands, |c| {
+            matches!(c, RecordedCommand::SetVertexBuffers { .. })
+        });
+        assert_eq!(
+            vertex_bindings,
+            [
+                RecordedCommand::SetVertexBuffers {
+                    start_slot: 0,
+                    views: vec![views(&key(0, 0, 0)).0, INSTANCES],
+                },
+                RecordedCommand::SetVertexBuffers {
+                    start_slot: 0,
+                    views: vec![views(&key(1, 0, 0)).0, INSTANCES],
+                },
+            ]
+        );
+        let index_bindings = commands_of(&commands, |c| {
+            matches!(c, RecordedCommand::SetIndexBuffer(_))
+        });
+        assert_eq!(index_bindings.len(), 2);
+        // Without a material table nothing is bound.
+        assert!(!commands
+            .iter()
+            .any(|c| matches!(c, RecordedCommand::SetDescriptorTable { .. })));
+        assert_eq!(sink.draw_count(), 3);
+    }
+
+    #[test]
+    fn submit_binds_materials_only_when_they_change() {
+        let mut batcher = InstanceBatcher::new();
+        batcher.push(key(0, 0, 1), instance(0.0));
+        batcher.push(key(0, 1, 1), instance(1.0));
+        batcher.push(key(1, 0, 2), instance(2.0));
+        batcher.push(key(2, 0, 1), instance(3.0));
+        let sink = RecordedCommands::new();
+        batcher.build().submit(&sink, INSTANCES, views, material);
+
+        let bindings = commands_of(&sink.commands(), |c| {
+            matches!(c, RecordedCommand::SetDescriptorTable { .. })
+        });
+        let bound: Vec<u64> = bindings
+            .iter()
+            .map(|c| match c {
+                RecordedCommand::SetDescriptorTable { root_index, handle } => {
+                    assert_eq!(*root_index, 0);
+                    handle.ptr
+                }
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(
+            bound,
+            [material(1), material(2), material(1)].map(|h| h.unwrap().ptr)
+        );
+
+        // Each binding precedes the draw that needs it.
+        let commands = sink.commands();
+        let first_draw = commands
+            .iter()
+            .position(|c| matches!(c, RecordedCommand::DrawIndexedInstanced { .. }))
+            .unwrap();
+        let first_table = commands
+            .iter()
+            .position(|c| matches!(c, RecordedCommand::SetDescriptorTable { .. }))
+            .unwrap();
+        assert!(first_table < first_draw);
+    }
+
+    #[test]
+    fn instance_layout_follows_the_vertex_layout() {
+        let elements = instanced_input_elements::<InstanceData>();
+        assert_eq!(elements.len(), 2 * InstanceData::input_elements().len());
+        assert_eq!(
+            InstanceData::new(Matrix4::identity(), Vector4::new(0.0, 0.0, 0.0, 1.0)).transform,
+            Matrix4::identity()
+        );
+    }
+}
@@ -0,0 +1,41 @@
+// The derive macros name `::d3d12forrust`, which needs to resolve inside this crate too.
+extern crate self as d3d12forrust;
+
+pub mod atlas;
+pub mod barrier;
+pub mod binary_reader;
+pub mod camera;
+pub mod camera_controller;
+pub mod color;
+pub mod command_sink;
+pub mod constant_buffer;
+pub mod debug_draw;
+pub mod debug_ui;
+pub mod depth_buffer;
+pub mod font;
+pub mod gltf_loader;
+pub mod ik;
+pub mod input;
+pub mod instancing;
+pub mod lod;
+pub mod mesh_buffer;
+pub mod mesh_processing;
+pub mod meshlet;
+pub mod model;
+pub mod motion;
+pub mod msaa;
+pub mod obj_loader;
+pub mod pmd_loader;
+pub mod pmx_loader;
+pub mod sampler;
+pub mod shader_reflection;
+pub mod simplify;
+pub mod skeleton;
+pub mod skinning;
+pub mod sprite_batch;
+pub mod text;
+pub mod texture;
+pub mod upload_ring;
+pub mod vertex_layout;
+pub mod vmd_loader;
+pub mod window_size;
@@ -0,0 +1,263 @@
+use cgmath::{EuclideanSpace, InnerSpace, Matrix4, Point3, Transform, Vector3};
+
+use crate::camera::Camera;
+use crate::model::{DrawRange, Indices, MeshVertex};
+use crate::simplify::simplify;
+
+pub const DEFAULT_MAX_LEVELS: usize = 5;
+pub const DEFAULT_REDUCTION: f32 = 0.5;
+pub const DEFAULT_THRESHOLD_PIXELS: f32 = 1.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LodLevel {
+    pub range: DrawRange,
+    pub error: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LodChain {
+    pub indices: Vec<u32>,
+    pub levels: Vec<LodLevel>,
+    pub center: Point3<f32>,
+    pub radius: f32,
+}
+
+impl LodChain {
+    pub fn generate(
+        vertices: &[MeshVertex],
+        indices: &[u32],
+        material: usize,
+        max_levels: usize,
+        reduction: f32,
+        max_error: f32,
+    ) -> Self {
+        let (center, radius) = bounding_sphere(vertices);
+        let mut chain = LodChain {
+            indices: Vec::new(),
+            levels: Vec::new(),
+            center,
+            radius,
+        };
+        chain.push(indices, material, 0.0);
+
+        let mut current = indices.to_vec();
+        let mut error = 0.0;
+        while chain.levels.len() < max_levels.max(1) {
+            let target = ((current.len() / 3) as f32 * reduction) as usize * 3;
+            let simplified = simplify(vertices, &current, target, max_error);
+            if simplified.indices.is_empty()
+                || simplified.indices.len() as f32 > current.len() as f32 * 0.95
+            {
+                break;
+            }
+            error += simplified.error;
+            chain.push(&simplified.indices, material, error);
+            current = simplified.indices;
+        }
+        chain
+    }
+
+    fn push(&mut self, indices: &[u32], material: usize, error: f32) {
+        self.levels.push(LodLevel {
+            range: DrawRange {
+                start_index: self.indices.len() as u32,
+                index_count: indices.len() as u32,
+                material,
+            },
+            error,
+        });
+        self.indices.extend_from_slice(indices);
+    }
+
+    pub fn index_buffer(&self) -> Indices {
+        Indices::from_u32(self.indices.clone())
+    }
+
+    pub fn select(
+        &self,
+        camera: &Camera,
+        world: &Matrix4<f32>,
+        viewport_height: f32,
+        threshold_pixels: f32,
+    ) -> usize {
+        let scale = [world.x, world.y, world.z]
+            .map(|axis| axis.truncate().magnitude())
+            .into_iter()
+            .fold(0.0, f32::max);
+        let radius = self.radius * scale;
+        if radius <= 0.0 {
+            return 0;
+        }
+        let center = world.transform_point(self.center);
+        let pixels_per_unit =
+            camera.projected_size(center, radius, viewport_height) / (radius * 2.0);
+        self.levels
+            .iter()
+            .rposition(|level| level.error * scale * pixels_per_unit <= threshold_pixels)
+            .unwrap_or(0)
+    }
+}
+
+fn bounding_sphere(vertices: &[MeshVertex]) -> (Point3<f32>, f32) {
+    if vertices.is_empty() {
+        return (Point3::origin(), 0.0);
+    }
+    let (min, max) = vertices.iter().fold(
+        (vertices[0].position, vertices[0].position),
+        |(min, max), v| {
+            (
+                Vector3::new(
+                    min.x.min(v.position.x),
+                    min.y.min(v.position.y),
+                    min.z.min(v.position.z),
+                ),
+                Vector3::new(
+                    max.x.max(v.position.x),
+                    max.y.max(v.position.y),
+                    max.z.max(v.position.z),
+                ),
+            )
+        },
+    );
+    let center = (min + max) * 0.5;
+    let radius = vertices
+        .iter()
+        .map(|v| (v.position - center).magnitude())
+        .fold(0.0, f32::max);
+    (Point3::from_vec(center), radius)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::{Deg, Vector2};
+
+    fn bowl(size: u32) -> (Vec<MeshVertex>, Vec<u32>) {
+        let vertices = (0..=size)
+            .flat_map(|y| (0..=size).map(move |x| (x as f32, y as f32)))
+            .map(|(x, y)| MeshVertex {
+                position: Vector3::new(x, y, (x * x + y * y) * 0.05),
+                uv: Vector2::new(x, y),
+                ..Default::default()
+            })
+            .collect();
+        let row = size + 1;
+        let indices = (0..size)
+            .flat_map(|y| (0..size).map(move |x| y * row + x))
+            .flat_map(|i| [i, i + 1, i + row + 1, i, i + row + 1, i + row])
+            .collect();
+        (vertices, indices)
+    }
+
+    fn chain() -> (Vec<MeshVertex>, Vec<u32>, LodChain) {
+        let (vertices, indices) = bowl(16);
+        let chain = LodChain::generate(
+            &vertices,
+            &indices,
+            3,
+            DEFAULT_MAX_LEVELS,
+            DEFAULT_REDUCTION,
+            f32::MAX,
+        );
+        (vertices, indices, chain)
+    }
+
+    #[test]
+    fn levels_shrink_and_accumulate_error() {
+        let (_, indices, chain) = chain();
+        assert!(chain.levels.len() > 1 && chain.levels.len() <= DEFAULT_MAX_LEVELS);
+        assert_eq!(chain.levels[0].error, 0.0);
+        assert_eq!(
+            &chain.indices[..indices.len()],
+            &indices[..],
+            "level 0 is the source mesh"
+        );
+
+        let mut start = 0;
+        for pair in chain.levels.windows(2) {
+            assert!(pair[1].range.index_count < pair[0].range.index_count);
+            assert!(pair[1].error >= pair[0].error);
+        }
+        for level in &chain.levels {
+            assert_eq!(level.range.start_index, start);
+            assert_eq!(level.range.material, 3);
+            assert_eq!(level.range.index_count % 3, 0);
+            start += level.range.index_count;
+        }
+        assert_eq!(start as usize, chain.indices.len());
+        assert_eq!(chain.index_buffer().len(), chain.indices.len());
+    }
+
+    #[test]
+    fn generation_is_deterministic() {
+        let (vertices, indices, first) = chain();
+        for _ in 0..3 {
+            let again = LodChain::generate(
+                &vertices,
+                &indices,
+                3,
+                DEFAULT_MAX_LEVELS,
+                DEFAULT_REDUCTION,
+                f32::MAX,
+            );
+            assert_eq!(again, first);
+        }
+    }
+
+    #[test]
+    fn respects_level_and_error_limits() {
+        let (vertices, indices) = bowl(8);
+        let single = LodChain::generate(&vertices, &indices, 0, 1, 0.5, f32::MAX);
+        assert_eq!(single.levels.len(), 1);
+        let strict = LodChain::generate(&vertices, &indices, 0, 5, 0.5, 0.0);
+        assert_eq!(strict.levels.len(), 1);
+        assert_eq!(strict.indices, indices);
+    }
+
+    #[test]
+    fn bounding_sphere_contains_every_vertex() {
+        let (vertices, _, chain) = chain();
+        for vertex in &vertices {
+            let distance = (Point3::from_vec(vertex.position) - chain.center).magnitude();
+            assert!(distance <= chain.radius + 1e-4);
+        }
+        assert_eq!(bounding_sphere(&[]), (Point3::origin(), 0.0));
+    }
+
+    #[test]
+    fn select_coarsens_with_distance() {
+        let (_, _, chain) = chain();
+        let last = chain.levels.len() - 1;
+        let camera_at = |distance: f32| {
+            Camera::perspective(
+                chain.center + Vector3::new(0.0, 0.0, -distance),
+                chain.center,
+                Deg(45.0),
+                16.0 / 9.0,
+                0.1,
+                100_000.0,
+            )
+        };
+        let identity = Matrix4::from_scale(1.0);
+        let near = chain.select(&camera_at(20.0), &identity, 720.0, 1.0);
+        let far = chain.select(&camera_at(50_000.0), &identity, 720.0, 1.0);
+        assert_eq!(near, 0);
+        assert_eq!(far, last);
+
+        let mut previous = 0;
+        for distance in [20.0, 100.0, 400.0, 1600.0, 6400.0, 50_000.0] {
+            let level = chain.select(&camera_at(distance), &identity, 720.0, 1.0);
+            assert!(level >= previous);
+            previous = level;
+        }
+
+        // Scaling the model up brings the detail back.
+        let middle = chain.select(&camera_at(1600.0), &identity, 720.0, 1.0);
+        let scaled = chain.select(&camera_at(1600.0), &Matrix4::from_scale(100.0), 720.0, 1.0);
+        assert!(scaled <= middle);
+        assert_eq!(
+            chain.select(&camera_at(20.0), &Matrix4::from_scale(0.0), 720.0, 1.0),
+            0
+        );
+    }
+}
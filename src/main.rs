@@ -1,7 +1,6 @@
 use std::ffi::c_void;
 
-use cgmath::{Matrix, Matrix4, SquareMatrix, Vector2, Vector3};
-use image::{GenericImageView, ImageBuffer, Rgb, Rgba};
+use cgmath::{Matrix4, Point3, Vector2, Vector3, Vector4};
 use windows::{
     core::*,
     Win32::Foundation::*,
@@ -15,16 +14,70 @@ use windows::{
 };
 use winit::{
     dpi::LogicalSize,
-    event::{Event, WindowEvent},
+    event::{Event, VirtualKeyCode, WindowEvent},
     event_loop::EventLoop,
     platform::{run_return::EventLoopExtRunReturn, windows::WindowExtWindows},
-    window::WindowBuilder,
+    window::{Fullscreen, WindowBuilder},
 };
 
 use rand::prelude::*;
 
+use d3d12forrust::{
+    atlas, barrier, camera, camera_controller, color, constant_buffer, debug_draw, debug_ui,
+    depth_buffer, font, gltf_loader, ik, input, instancing, lod, mesh_buffer, mesh_processing,
+    meshlet, model, motion, msaa, obj_loader, pmd_loader, pmx_loader, sampler, shader_reflection,
+    skeleton, skinning, sprite_batch, text, texture, upload_ring, vertex_layout, vmd_loader,
+    window_size,
+};
+
+mod mesh_shader;
+mod ui_renderer;
+
+use atlas::{Atlas, AtlasSettings};
+use barrier::transition_barrier;
+use camera::{Camera, Projection};
+use camera_controller::{CameraController, FirstPersonController, FlyController, OrbitController};
+use color::{OutputConstants, OutputMode, Tonemap};
+use constant_buffer::{ConstantBuffer, ConstantBufferLayout};
+use debug_draw::{DebugDepth, DebugDraw, DebugLineVertex, DebugStyle};
+use debug_ui::DebugUi;
+use depth_buffer::{DepthBuffer, DepthFormat, DepthState};
+use font::Font;
+use ik::{IkChain, IkMethod};
+use input::InputState;
+use instancing::{DrawKey, InstanceBatcher, InstanceData};
+use lod::LodChain;
+use mesh_buffer::MeshBuffer;
+use mesh_shader::{MeshShaderPipeline, MeshletBuffers, MeshletConstants};
+use meshlet::MeshletMesh;
+use model::{DrawRange, Indices, MeshVertex, Topology};
+use motion::MotionPlayer;
+use msaa::{negotiate_sample_desc, next_sample_count, MsaaTarget};
+use pmd_loader::{PmdModel, PmdVertex};
+use pmx_loader::{PmxModel, PmxVertex, Weight};
+use sampler::SamplerFilter;
+use shader_reflection::{RootBinding, ShaderReflection};
+use skeleton::{Skeleton, MAX_BONES};
+use skinning::{Deform, DualQuaternion, Sdef, Skinnable, SkinningMode, MAX_INFLUENCES};
+use sprite_batch::{BlendMode, SpriteBatch, SpriteSortMode, SpriteVertex};
+use text::{GlyphTexture, TextRenderer, TextStyle};
+use texture::ImageTexture;
+use ui_renderer::UiRenderer;
+use upload_ring::UploadRing;
+use vertex_layout::VertexLayout;
+use vmd_loader::VmdMotion;
+use window_size::{DisplayMode, SizeState};
+
 const WINDOW_WIDTH: u32 = 1280;
 const WINDOW_HEIGHT: u32 = 720;
+const INSTANCE_GRID_SIZE: usize = 5;
+// Slot 0 holds the fallback texture and slot 1 the glyph page.
+const MODEL_TEXTURE_SLOT: usize = 2;
+const FONT_PATHS: [&str; 3] = [
+    "C:\\Windows\\Fonts\\meiryo.ttc",
+    "C:\\Windows\\Fonts\\YuGothM.ttc",
+    "C:\\Windows\\Fonts\\msgothic.ttc",
+];
 
 fn main() -> Result<()> {
     let mut event_loop = EventLoop::new();
@@ -78,6 +131,9 @@ fn main() -> Result<()> {
         device.CreateCommandList(0, D3D12_COMMAND_LIST_TYPE_DIRECT, &command_allocator, None)
     }
     .unwrap();
+    let mesh_command_list: Option<ID3D12GraphicsCommandList6> = command_list.cast().ok();
+    let mesh_shaders_supported =
+        mesh_shader::mesh_shader_supported(&device) && mesh_command_list.is_some();
     let command_queue_desc = D3D12_COMMAND_QUEUE_DESC {
         Flags: D3D12_COMMAND_QUEUE_FLAG_NONE,
         NodeMask: 0,
@@ -88,11 +144,14 @@ fn main() -> Result<()> {
     let command_queue: ID3D12CommandQueue =
         unsafe { device.CreateCommandQueue(&command_queue_desc) }?;
 
+    let mut output_mode = OutputMode::Sdr;
+    let paper_white_nits = 200.0;
+
     let swap_chain_desc = DXGI_SWAP_CHAIN_DESC1 {
         BufferCount: 2,
         Width: WINDOW_WIDTH,
         Height: WINDOW_HEIGHT,
-        Format: DXGI_FORMAT_R8G8B8A8_UNORM,
+        Format: output_mode.swap_chain_format(),
         Stereo: false.into(),
         BufferUsage: DXGI_USAGE_BACK_BUFFER,
         SwapEffect: DXGI_SWAP_EFFECT_FLIP_DISCARD,
@@ -109,6 +168,7 @@ fn main() -> Result<()> {
         factory.CreateSwapChainForHwnd(&command_queue, hwnd, &swap_chain_desc, None, None)?
     }
     .cast()?;
+    unsafe { factory.MakeWindowAssociation(hwnd, DXGI_MWA_NO_ALT_ENTER) }?;
 
     let rtv_heap_desc = D3D12_DESCRIPTOR_HEAP_DESC {
         Type: D3D12_DESCRIPTOR_HEAP_TYPE_RTV,
@@ -121,28 +181,8 @@ fn main() -> Result<()> {
     let rtv_descpter_size =
         unsafe { device.GetDescriptorHandleIncrementSize(D3D12_DESCRIPTOR_HEAP_TYPE_RTV) } as usize;
 
-    let rtv_handle = unsafe { rtv_heap.GetCPUDescriptorHandleForHeapStart() };
-
-    let render_target_view_desc = D3D12_RENDER_TARGET_VIEW_DESC {
-        Format: DXGI_FORMAT_R8G8B8A8_UNORM,
-        ViewDimension: D3D12_RTV_DIMENSION_TEXTURE2D,
-        ..Default::default()
-    };
-
-    let back_buffer: [ID3D12Resource; 2] =
-        array_init::try_array_init(|i| -> Result<ID3D12Resource> {
-            let render_target: ID3D12Resource = unsafe { swap_chain.GetBuffer(i as u32) }?;
-            unsafe {
-                device.CreateRenderTargetView(
-                    &render_target,
-                    Some(&render_target_view_desc),
-                    D3D12_CPU_DESCRIPTOR_HANDLE {
-                        ptr: rtv_handle.ptr + i * rtv_descpter_size,
-                    },
-                )
-            }
-            Ok(render_target)
-        })?;
+    let mut back_buffer =
+        create_back_buffers(&device, &swap_chain, &rtv_heap, output_mode.rtv_format())?;
 
     let fence: ID3D12Fence = unsafe { device.CreateFence(0, D3D12_FENCE_FLAG_NONE) }.unwrap();
 
@@ -157,70 +197,209 @@ fn main() -> Result<()> {
         Vertex {
             pos: Vector3::new(-0.4, -0.7, 0.),
             uv: Vector2::new(0., 1.),
+            ..Default::default()
         },
         Vertex {
             pos: Vector3::new(-0.4f32, 0.7, 0.),
             uv: Vector2::new(0., 0.),
+            ..Default::default()
         },
         Vertex {
             pos: Vector3::new(0.4f32, -0.7, 0.),
             uv: Vector2::new(1., 1.),
+            ..Default::default()
         },
         Vertex {
             pos: Vector3::new(0.4f32, 0.7, 0.),
             uv: Vector2::new(1., 0.),
+            ..Default::default()
         },
     ];
 
-    let vertex_heap_properties = D3D12_HEAP_PROPERTIES {
-        Type: D3D12_HEAP_TYPE_UPLOAD,
-        CPUPageProperty: D3D12_CPU_PAGE_PROPERTY_UNKNOWN,
-        MemoryPoolPreference: D3D12_MEMORY_POOL_UNKNOWN,
-        ..Default::default()
+    let quad = MeshBuffer::new(&device, &vertices, &Indices::U16(vec![0, 1, 2, 2, 1, 3]))?;
+
+    let model_path = std::env::args().nth(1);
+    let extension = model_path
+        .as_ref()
+        .and_then(|path| std::path::Path::new(path).extension())
+        .map(|extension| extension.to_string_lossy().to_ascii_lowercase());
+
+    let pmd = model_path
+        .as_ref()
+        .filter(|_| extension.as_deref() == Some("pmd"))
+        .map(|path| {
+            let pmd = PmdModel::load(path).unwrap();
+            println!(
+                "{}: {} ({} vertices, {} indices, {} materials, {} bones, {} IK chains)",
+                path,
+                pmd.header.name,
+                pmd.vertices.len(),
+                pmd.indices.len(),
+                pmd.materials.len(),
+                pmd.bones.len(),
+                pmd.ik_chains.len()
+            );
+            pmd
+        });
+    let pmx = model_path
+        .as_ref()
+        .filter(|_| extension.as_deref() == Some("pmx"))
+        .map(|path| {
+            let pmx = PmxModel::load(path).unwrap();
+            println!(
+                "{}: {} ({} vertices, {} indices, {} materials, {} bones, {} morphs, {} rigid bodies, {} joints)",
+                path,
+                pmx.header.name,
+                pmx.vertices.len(),
+                pmx.indices.len(),
+                pmx.materials.len(),
+                pmx.bones.len(),
+                pmx.morphs.len(),
+                pmx.rigid_bodies.len(),
+                pmx.joints.len()
+            );
+            pmx
+        });
+
+    let mmd_vertices: Vec<Vertex> = match (&pmd, &pmx) {
+        (Some(pmd), _) => pmd.vertices.iter().map(Vertex::from).collect(),
+        (_, Some(pmx)) => pmx.vertices.iter().map(Vertex::from).collect(),
+        _ => Vec::new(),
     };
-    let vertex_resource_desc = D3D12_RESOURCE_DESC {
-        Dimension: D3D12_RESOURCE_DIMENSION_BUFFER,
-        Width: std::mem::size_of_val(&vertices) as u64,
-        Height: 1,
-        DepthOrArraySize: 1,
-        MipLevels: 1,
-        Format: DXGI_FORMAT_UNKNOWN,
-        SampleDesc: DXGI_SAMPLE_DESC {
-            Count: 1,
-            ..Default::default()
+    let mmd_bounds = mmd_vertices.iter().fold(
+        None,
+        |bounds: Option<(Point3<f32>, Point3<f32>)>, vertex| {
+            let p = Point3::new(vertex.pos.x, vertex.pos.y, vertex.pos.z);
+            Some(match bounds {
+                Some((min, max)) => (
+                    Point3::new(min.x.min(p.x), min.y.min(p.y), min.z.min(p.z)),
+                    Point3::new(max.x.max(p.x), max.y.max(p.y), max.z.max(p.z)),
+                ),
+                None => (p, p),
+            })
         },
-        Flags: D3D12_RESOURCE_FLAG_NONE,
-        Layout: D3D12_TEXTURE_LAYOUT_ROW_MAJOR,
-        ..Default::default()
+    );
+    let mmd_mesh = if let Some(pmd) = &pmd {
+        let buffer =
+            MeshBuffer::new(&device, &mmd_vertices, &Indices::U16(pmd.indices.clone())).unwrap();
+        Some((buffer, pmd.draw_ranges().unwrap()))
+    } else if let Some(pmx) = &pmx {
+        let buffer = MeshBuffer::new(&device, &mmd_vertices, &pmx.index_buffer()).unwrap();
+        Some((buffer, pmx.draw_ranges().unwrap()))
+    } else {
+        None
     };
 
-    let mut vertex_buffer: Option<ID3D12Resource> = None;
-    unsafe {
-        device.CreateCommittedResource(
-            &vertex_heap_properties,
-            D3D12_HEAP_FLAG_NONE,
-            &vertex_resource_desc,
-            D3D12_RESOURCE_STATE_GENERIC_READ,
-            None,
-            &mut vertex_buffer,
-        )
-    }
-    .unwrap();
-    let vertex_buffer = vertex_buffer.unwrap();
-    unsafe {
-        let mut vertex_map = std::ptr::null_mut();
-        vertex_buffer.Map(0, None, Some(&mut vertex_map)).unwrap();
-        std::ptr::copy_nonoverlapping(vertices.as_ptr(), vertex_map as *mut Vertex, vertices.len());
-        vertex_buffer.Unmap(0, None);
+    let skeleton = match (&pmd, &pmx) {
+        (Some(pmd), _) => Skeleton::from_pmd(pmd),
+        (_, Some(pmx)) => Skeleton::from_pmx(pmx),
+        _ => Skeleton::default(),
+    };
+    if skeleton.bones.len() > MAX_BONES {
+        return Err(Error::new(
+            E_FAIL,
+            format!(
+                "{} bones exceed the {} bone skinning palette",
+                skeleton.bones.len(),
+                MAX_BONES
+            )
+            .into(),
+        ));
     }
-
-    let vertex_buffer_view = D3D12_VERTEX_BUFFER_VIEW {
-        BufferLocation: unsafe { vertex_buffer.GetGPUVirtualAddress() },
-        SizeInBytes: std::mem::size_of_val(&vertices) as u32,
-        StrideInBytes: std::mem::size_of_val(&vertices[0]) as u32,
+    let ik_chains = match (&pmd, &pmx) {
+        (Some(pmd), _) => IkChain::from_pmd(pmd),
+        (_, Some(pmx)) => IkChain::from_pmx(pmx),
+        _ => Vec::new(),
     };
+    let mut motion_player = std::env::args().nth(2).map(|path| {
+        let motion = VmdMotion::load(&path).unwrap();
+        println!(
+            "{}: {} bone keyframes, {} morph keyframes, {} camera keyframes, {} IK keyframes",
+            path,
+            motion.bone_keyframes.len(),
+            motion.morph_keyframes.len(),
+            motion.camera_keyframes.len(),
+            motion.ik_keyframes.len()
+        );
+        MotionPlayer::new(&motion, &skeleton)
+    });
+
+    let model = model_path
+        .filter(|_| !matches!(extension.as_deref(), Some("pmd" | "pmx")))
+        .map(|path| {
+            let mut model = match extension.as_deref() {
+                Some("obj") => obj_loader::load(&path).unwrap(),
+                _ => gltf_loader::load(&path).unwrap(),
+            };
+            println!(
+                "{}: {} meshes, {} materials, {} textures, {} nodes, {} skins, {} animations",
+                path,
+                model.meshes.len(),
+                model.materials.len(),
+                model.textures.len(),
+                model.nodes.len(),
+                model.skins.len(),
+                model.animations.len()
+            );
+            let primitives = model
+                .meshes
+                .iter_mut()
+                .flat_map(|mesh| &mut mesh.primitives);
+            for primitive in primitives.filter(|p| p.topology == Topology::Triangles) {
+                let before: Vec<u32> = primitive.indices.iter().collect();
+                let before = mesh_processing::analyze_vertex_cache(
+                    &before,
+                    mesh_processing::DEFAULT_CACHE_SIZE,
+                );
+                mesh_processing::optimize_primitive(primitive, mesh_processing::DEFAULT_CACHE_SIZE);
+                let after: Vec<u32> = primitive.indices.iter().collect();
+                let after = mesh_processing::analyze_vertex_cache(
+                    &after,
+                    mesh_processing::DEFAULT_CACHE_SIZE,
+                );
+                println!(
+                    "  ACMR {:.3} -> {:.3}, ATVR {:.3} -> {:.3}",
+                    before.acmr, after.acmr, before.atvr, after.atvr
+                );
+            }
+            model
+        });
+    let model_buffers: Vec<Vec<(MeshBuffer, LodChain, Option<MeshletBuffers>)>> = model
+        .iter()
+        .flat_map(|model| &model.meshes)
+        .map(|mesh| {
+            mesh.primitives
+                .iter()
+                .filter(|primitive| primitive.topology == Topology::Triangles)
+                .map(|primitive| {
+                    let indices: Vec<u32> = primitive.indices.iter().collect();
+                    let lods = LodChain::generate(
+                        &primitive.vertices,
+                        &indices,
+                        primitive.material.unwrap_or(0),
+                        lod::DEFAULT_MAX_LEVELS,
+                        lod::DEFAULT_REDUCTION,
+                        f32::MAX,
+                    );
+                    let vertices: Vec<Vertex> =
+                        primitive.vertices.iter().map(Vertex::from).collect();
+                    let buffer = MeshBuffer::new(&device, &vertices, &lods.index_buffer()).unwrap();
+                    let meshlets = mesh_shaders_supported.then(|| {
+                        let meshlets = MeshletMesh::build(
+                            &primitive.vertices,
+                            &indices,
+                            meshlet::MAX_MESHLET_VERTICES,
+                            meshlet::MAX_MESHLET_TRIANGLES,
+                        );
+                        println!("  {} meshlets", meshlets.meshlets.len());
+                        MeshletBuffers::new(&device, &primitive.vertices, &meshlets).unwrap()
+                    });
+                    (buffer, lods, meshlets)
+                })
+                .collect()
+        })
+        .collect();
 
-    let mut vertex_shader = None;
     let error_blob = None;
 
     let exe_path = std::env::current_exe().ok().unwrap();
@@ -228,21 +407,38 @@ fn main() -> Result<()> {
     let vertex_shaders_hlsl_path = asset_path.join("BasicVertexShader.hlsl");
     let vertex_shaders_hlsl = vertex_shaders_hlsl_path.to_str().unwrap();
     let vertex_shaders_hlsl: HSTRING = vertex_shaders_hlsl.into();
-    unsafe {
-        D3DCompileFromFile(
-            &vertex_shaders_hlsl,
-            None,
-            None,
-            s!("BasicVS"),
-            s!("vs_5_0"),
-            D3DCOMPILE_DEBUG | D3DCOMPILE_SKIP_OPTIMIZATION,
-            0,
-            &mut vertex_shader,
-            error_blob,
-        )
-    }
-    .unwrap();
-    let vertex_shader = vertex_shader.unwrap();
+    let compile_vertex_shader = |entry: PCSTR, defines: Option<*const D3D_SHADER_MACRO>| {
+        let mut vertex_shader = None;
+        unsafe {
+            D3DCompileFromFile(
+                &vertex_shaders_hlsl,
+                defines,
+                None,
+                entry,
+                s!("vs_5_0"),
+                D3DCOMPILE_DEBUG | D3DCOMPILE_SKIP_OPTIMIZATION,
+                0,
+                &mut vertex_shader,
+                error_blob,
+            )
+        }
+        .unwrap();
+        vertex_shader.unwrap()
+    };
+    let vertex_shader = compile_vertex_shader(s!("BasicVS"), None);
+    let dual_quaternion_defines = [
+        D3D_SHADER_MACRO {
+            Name: s!("DUAL_QUATERNION_SKINNING"),
+            Definition: s!("1"),
+        },
+        D3D_SHADER_MACRO::default(),
+    ];
+    let dual_quaternion_vertex_shader =
+        compile_vertex_shader(s!("BasicVS"), Some(dual_quaternion_defines.as_ptr()));
+    let instanced_vertex_shader = compile_vertex_shader(s!("InstancedVS"), None);
+    let sprite_vertex_shader = compile_vertex_shader(s!("SpriteVS"), None);
+    let ui_vertex_shader = compile_vertex_shader(s!("UiVS"), None);
+    let debug_line_vertex_shader = compile_vertex_shader(s!("DebugLineVS"), None);
 
     if let Some(e_option) = error_blob {
         let e_option_ptr = e_option.cast_const();
@@ -250,25 +446,31 @@ fn main() -> Result<()> {
         println!("{:?}", error_blob);
     }
 
-    let mut pixel_shader = None;
     let pixel_shaders_hlsl_path = asset_path.join("BasicPixelShader.hlsl");
     let pixel_shaders_hlsl = pixel_shaders_hlsl_path.to_str().unwrap();
     let pixel_shaders_hlsl: HSTRING = pixel_shaders_hlsl.into();
-    unsafe {
-        D3DCompileFromFile(
-            &pixel_shaders_hlsl,
-            None,
-            None,
-            s!("BasicPS"),
-            s!("ps_5_0"),
-            D3DCOMPILE_DEBUG | D3DCOMPILE_SKIP_OPTIMIZATION,
-            0,
-            &mut pixel_shader,
-            error_blob,
-        )
-    }
-    .unwrap();
-    let pixel_shader = pixel_shader.unwrap();
+    let compile_pixel_shader = |entry: PCSTR| {
+        let mut pixel_shader = None;
+        unsafe {
+            D3DCompileFromFile(
+                &pixel_shaders_hlsl,
+                None,
+                None,
+                entry,
+                s!("ps_5_0"),
+                D3DCOMPILE_DEBUG | D3DCOMPILE_SKIP_OPTIMIZATION,
+                0,
+                &mut pixel_shader,
+                error_blob,
+            )
+        }
+        .unwrap();
+        pixel_shader.unwrap()
+    };
+    let pixel_shader = compile_pixel_shader(s!("BasicPS"));
+    let text_pixel_shader = compile_pixel_shader(s!("SdfTextPS"));
+    let ui_pixel_shader = compile_pixel_shader(s!("UiPS"));
+    let debug_line_pixel_shader = compile_pixel_shader(s!("DebugLinePS"));
 
     if let Some(e_option) = error_blob {
         let e_option_ptr = e_option.cast_const();
@@ -276,56 +478,7 @@ fn main() -> Result<()> {
         println!("{:?}", error_blob);
     }
 
-    let indices = [0u16, 1, 2, 2, 1, 3];
-    let mut index_buffer: Option<ID3D12Resource> = None;
-
-    let index_heap_properties = D3D12_HEAP_PROPERTIES {
-        Type: D3D12_HEAP_TYPE_UPLOAD,
-        CPUPageProperty: D3D12_CPU_PAGE_PROPERTY_UNKNOWN,
-        MemoryPoolPreference: D3D12_MEMORY_POOL_UNKNOWN,
-        ..Default::default()
-    };
-    let index_resource_desc = D3D12_RESOURCE_DESC {
-        Dimension: D3D12_RESOURCE_DIMENSION_BUFFER,
-        Width: std::mem::size_of_val(&indices) as u64,
-        Height: 1,
-        DepthOrArraySize: 1,
-        MipLevels: 1,
-        Format: DXGI_FORMAT_UNKNOWN,
-        SampleDesc: DXGI_SAMPLE_DESC {
-            Count: 1,
-            ..Default::default()
-        },
-        Flags: D3D12_RESOURCE_FLAG_NONE,
-        Layout: D3D12_TEXTURE_LAYOUT_ROW_MAJOR,
-        ..Default::default()
-    };
-
-    unsafe {
-        device.CreateCommittedResource(
-            &index_heap_properties,
-            D3D12_HEAP_FLAG_NONE,
-            &index_resource_desc,
-            D3D12_RESOURCE_STATE_GENERIC_READ,
-            None,
-            &mut index_buffer,
-        )
-    }
-    .unwrap();
-    let index_buffer = index_buffer.unwrap();
-
-    unsafe {
-        let mut data = std::ptr::null_mut();
-        index_buffer.Map(0, None, Some(&mut data)).unwrap();
-        std::ptr::copy_nonoverlapping(indices.as_ptr(), data as *mut u16, indices.len());
-        index_buffer.Unmap(0, None);
-    }
-
-    let index_view = D3D12_INDEX_BUFFER_VIEW {
-        BufferLocation: unsafe { index_buffer.GetGPUVirtualAddress() },
-        Format: DXGI_FORMAT_R16_UINT,
-        SizeInBytes: std::mem::size_of_val(&indices) as u32,
-    };
+    let texture_format = color::srgb_format(DXGI_FORMAT_R8G8B8A8_UNORM);
 
     let bytes = include_bytes!("./img/textest200x200.png");
     let png_image = image::load_from_memory(bytes).unwrap();
@@ -421,7 +574,7 @@ fn main() -> Result<()> {
             PlacedFootprint: D3D12_PLACED_SUBRESOURCE_FOOTPRINT {
                 Offset: 0,
                 Footprint: D3D12_SUBRESOURCE_FOOTPRINT {
-                    Format: DXGI_FORMAT_R8G8B8A8_UNORM,
+                    Format: texture_format,
                     Width: rgba_texture.width(),
                     Height: rgba_texture.height(),
                     Depth: 1,
@@ -440,7 +593,7 @@ fn main() -> Result<()> {
     };
 
     let texture_resource_desc = D3D12_RESOURCE_DESC {
-        Format: DXGI_FORMAT_R8G8B8A8_UNORM,
+        Format: texture_format,
         Width: png_image.width() as u64,
         Height: png_image.height() as u32,
         DepthOrArraySize: 1,
@@ -510,62 +663,84 @@ fn main() -> Result<()> {
     }
     fence_val += 1;
 
+    let model_textures = model
+        .iter()
+        .flat_map(|model| {
+            model
+                .textures
+                .iter()
+                .map(|texture| &model.images[texture.image])
+        })
+        .map(|image| ImageTexture::from_image(&device, image))
+        .collect::<Result<Vec<_>>>()?;
+
+    let sprite_atlas = Atlas::build(
+        &[
+            (
+                "textest".to_string(),
+                image::load_from_memory(include_bytes!("./img/textest.png"))
+                    .unwrap()
+                    .to_rgba8(),
+            ),
+            ("textest200x200".to_string(), rgba_texture.clone()),
+        ],
+        &AtlasSettings {
+            page_width: 512,
+            page_height: 512,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    let atlas_textures = sprite_atlas
+        .pages
+        .iter()
+        .map(|page| ImageTexture::new(&device, page.width(), page.height(), page.as_raw()))
+        .collect::<Result<Vec<_>>>()?;
+    let atlas_texture_slot = MODEL_TEXTURE_SLOT + model_textures.len();
+
     let basic_descriptor_heap_desc = D3D12_DESCRIPTOR_HEAP_DESC {
         Flags: D3D12_DESCRIPTOR_HEAP_FLAG_SHADER_VISIBLE,
         NodeMask: 0,
-        NumDescriptors: 2,
+        NumDescriptors: (atlas_texture_slot + atlas_textures.len()) as u32,
         Type: D3D12_DESCRIPTOR_HEAP_TYPE_CBV_SRV_UAV,
     };
 
     let basic_descriptor_heap: ID3D12DescriptorHeap =
         unsafe { device.CreateDescriptorHeap(&basic_descriptor_heap_desc) }.unwrap();
 
-    let mut matrix = Matrix4::identity();
-
-    let const_heap_properties = D3D12_HEAP_PROPERTIES {
-        Type: D3D12_HEAP_TYPE_UPLOAD,
-        CPUPageProperty: D3D12_CPU_PAGE_PROPERTY_UNKNOWN,
-        MemoryPoolPreference: D3D12_MEMORY_POOL_UNKNOWN,
-        ..Default::default()
-    };
-    let const_resource_desc = D3D12_RESOURCE_DESC {
-        Dimension: D3D12_RESOURCE_DIMENSION_BUFFER,
-        Width: alignmented_size(std::mem::size_of_val(&matrix), 256) as u64,
-        Height: 1,
-        DepthOrArraySize: 1,
-        MipLevels: 1,
-        Format: DXGI_FORMAT_UNKNOWN,
-        SampleDesc: DXGI_SAMPLE_DESC {
-            Count: 1,
-            ..Default::default()
-        },
-        Flags: D3D12_RESOURCE_FLAG_NONE,
-        Layout: D3D12_TEXTURE_LAYOUT_ROW_MAJOR,
-        ..Default::default()
-    };
-
-    let mut const_buffer: Option<ID3D12Resource> = None;
-    unsafe {
-        device.CreateCommittedResource(
-            &const_heap_properties,
-            D3D12_HEAP_FLAG_NONE,
-            &const_resource_desc,
-            D3D12_RESOURCE_STATE_GENERIC_READ,
-            None,
-            &mut const_buffer,
-        )
-    }
-    .unwrap();
-    let const_buffer = const_buffer.unwrap();
-
-    unsafe {
-        let mut const_map = std::ptr::null_mut();
-        const_buffer.Map(0, None, Some(&mut const_map)).unwrap();
-        std::ptr::copy_nonoverlapping(matrix.as_mut_ptr(), const_map as *mut f32, 16);
-    }
+    let mut camera = Camera::default();
+    camera.set_viewport(WINDOW_WIDTH, WINDOW_HEIGHT);
+
+    let mut input = InputState::new();
+    let mut camera_controller: Box<dyn CameraController> =
+        Box::new(OrbitController::from_camera(&camera));
+    let mut last_frame = std::time::Instant::now();
+
+    let skinned_vertex_capacity =
+        constant_buffer::align_up(std::mem::size_of_val(mmd_vertices.as_slice()), 256) as u64;
+    let instance_capacity =
+        (instancing::MAX_INSTANCES_PER_FRAME * std::mem::size_of::<InstanceData>()) as u64;
+    let sprite_capacity = (sprite_batch::MAX_SPRITES_PER_FRAME
+        * (4 * std::mem::size_of::<SpriteVertex>() + 6 * std::mem::size_of::<u32>()))
+        as u64
+        + 256;
+    let debug_line_capacity =
+        (debug_draw::MAX_DEBUG_LINE_VERTICES * std::mem::size_of::<DebugLineVertex>()) as u64 + 256;
+    let mut upload_ring = UploadRing::new(
+        &device,
+        64 * 1024
+            + skinned_vertex_capacity
+            + instance_capacity
+            + 2 * sprite_capacity
+            + debug_line_capacity,
+        2,
+    )?;
+    let mut skinning_mode = SkinningMode::GpuLinear;
+    let mut scene_constants = ConstantBuffer::<SceneConstants>::new();
+    let mut bone_constants = ConstantBuffer::<BoneConstants>::new();
 
     let shader_resource_desc = D3D12_SHADER_RESOURCE_VIEW_DESC {
-        Format: DXGI_FORMAT_R8G8B8A8_UNORM,
+        Format: texture_format,
         Shader4ComponentMapping: D3D12_DEFAULT_SHADER_4_COMPONENT_MAPPING,
         ViewDimension: D3D12_SRV_DIMENSION_TEXTURE2D,
         Anonymous: D3D12_SHADER_RESOURCE_VIEW_DESC_0 {
@@ -585,51 +760,118 @@ fn main() -> Result<()> {
         )
     };
 
-    let basic_heap_handle = D3D12_CPU_DESCRIPTOR_HANDLE {
-        ptr: basic_heap_handle.ptr
-            + unsafe {
-                device.GetDescriptorHandleIncrementSize(D3D12_DESCRIPTOR_HEAP_TYPE_CBV_SRV_UAV)
-                    as usize
-            },
+    let srv_descriptor_size =
+        unsafe { device.GetDescriptorHandleIncrementSize(D3D12_DESCRIPTOR_HEAP_TYPE_CBV_SRV_UAV) }
+            as usize;
+    let mut text_renderer = FONT_PATHS
+        .iter()
+        .find_map(|path| Font::load(path, 0).ok())
+        .map(|font| TextRenderer::new(font, 1));
+    let glyph_texture = match &text_renderer {
+        Some(text_renderer) => {
+            let texture = GlyphTexture::new(
+                &device,
+                text_renderer.cache.width,
+                text_renderer.cache.height,
+            )?;
+            texture.create_shader_resource_view(
+                &device,
+                D3D12_CPU_DESCRIPTOR_HANDLE {
+                    ptr: basic_heap_handle.ptr + srv_descriptor_size,
+                },
+            );
+            Some(texture)
+        }
+        None => {
+            println!("no font found, text rendering disabled");
+            None
+        }
     };
-
-    let const_buffer_view_desc = unsafe {
-        D3D12_CONSTANT_BUFFER_VIEW_DESC {
-            BufferLocation: const_buffer.GetGPUVirtualAddress(),
-            SizeInBytes: const_buffer.GetDesc().Width as u32,
+    for (i, texture) in model_textures.iter().enumerate() {
+        texture.create_shader_resource_view(
+            &device,
+            D3D12_CPU_DESCRIPTOR_HANDLE {
+                ptr: basic_heap_handle.ptr + (MODEL_TEXTURE_SLOT + i) * srv_descriptor_size,
+            },
+        );
+    }
+    for (i, texture) in atlas_textures.iter().enumerate() {
+        texture.create_shader_resource_view(
+            &device,
+            D3D12_CPU_DESCRIPTOR_HANDLE {
+                ptr: basic_heap_handle.ptr + (atlas_texture_slot + i) * srv_descriptor_size,
+            },
+        );
+    }
+    let basic_heap_gpu_handle =
+        unsafe { basic_descriptor_heap.GetGPUDescriptorHandleForHeapStart() };
+    let material_texture = |material: usize| {
+        let slot = model
+            .as_ref()
+            .and_then(|model| model.materials.get(material)?.base_color_texture)
+            .map_or(0, |texture| MODEL_TEXTURE_SLOT + texture.texture);
+        D3D12_GPU_DESCRIPTOR_HANDLE {
+            ptr: basic_heap_gpu_handle.ptr + (slot * srv_descriptor_size) as u64,
         }
     };
 
-    unsafe { device.CreateConstantBufferView(Some(&const_buffer_view_desc), basic_heap_handle) };
-
-    let mut descriptor_ranges = [D3D12_DESCRIPTOR_RANGE::default(); 2];
-
-    descriptor_ranges[0] = D3D12_DESCRIPTOR_RANGE {
+    let descriptor_ranges = [D3D12_DESCRIPTOR_RANGE {
         NumDescriptors: 1,
         RangeType: D3D12_DESCRIPTOR_RANGE_TYPE_SRV,
         BaseShaderRegister: 0,
         OffsetInDescriptorsFromTableStart: D3D12_DESCRIPTOR_RANGE_OFFSET_APPEND,
         ..Default::default()
+    }];
+
+    let scene_constants_descriptor = D3D12_ROOT_DESCRIPTOR {
+        ShaderRegister: 0,
+        RegisterSpace: 0,
     };
 
-    descriptor_ranges[1] = D3D12_DESCRIPTOR_RANGE {
-        NumDescriptors: 1,
-        RangeType: D3D12_DESCRIPTOR_RANGE_TYPE_CBV,
-        BaseShaderRegister: 0,
-        OffsetInDescriptorsFromTableStart: D3D12_DESCRIPTOR_RANGE_OFFSET_APPEND,
-        ..Default::default()
+    let bone_constants_descriptor = D3D12_ROOT_DESCRIPTOR {
+        ShaderRegister: 2,
+        RegisterSpace: 0,
     };
 
-    let root_parameter = D3D12_ROOT_PARAMETER {
-        ParameterType: D3D12_ROOT_PARAMETER_TYPE_DESCRIPTOR_TABLE,
-        ShaderVisibility: D3D12_SHADER_VISIBILITY_ALL,
-        Anonymous: D3D12_ROOT_PARAMETER_0 {
-            DescriptorTable: D3D12_ROOT_DESCRIPTOR_TABLE {
-                NumDescriptorRanges: 2,
-                pDescriptorRanges: descriptor_ranges.as_ptr(),
+    let output_constants_desc = D3D12_ROOT_CONSTANTS {
+        ShaderRegister: 1,
+        RegisterSpace: 0,
+        Num32BitValues: OutputConstants::NUM_32BIT_VALUES,
+    };
+
+    let root_parameters = [
+        D3D12_ROOT_PARAMETER {
+            ParameterType: D3D12_ROOT_PARAMETER_TYPE_DESCRIPTOR_TABLE,
+            ShaderVisibility: D3D12_SHADER_VISIBILITY_ALL,
+            Anonymous: D3D12_ROOT_PARAMETER_0 {
+                DescriptorTable: D3D12_ROOT_DESCRIPTOR_TABLE {
+                    NumDescriptorRanges: descriptor_ranges.len() as u32,
+                    pDescriptorRanges: descriptor_ranges.as_ptr(),
+                },
             },
         },
-    };
+        D3D12_ROOT_PARAMETER {
+            ParameterType: D3D12_ROOT_PARAMETER_TYPE_CBV,
+            ShaderVisibility: D3D12_SHADER_VISIBILITY_VERTEX,
+            Anonymous: D3D12_ROOT_PARAMETER_0 {
+                Descriptor: scene_constants_descriptor,
+            },
+        },
+        D3D12_ROOT_PARAMETER {
+            ParameterType: D3D12_ROOT_PARAMETER_TYPE_32BIT_CONSTANTS,
+            ShaderVisibility: D3D12_SHADER_VISIBILITY_PIXEL,
+            Anonymous: D3D12_ROOT_PARAMETER_0 {
+                Constants: output_constants_desc,
+            },
+        },
+        D3D12_ROOT_PARAMETER {
+            ParameterType: D3D12_ROOT_PARAMETER_TYPE_CBV,
+            ShaderVisibility: D3D12_SHADER_VISIBILITY_VERTEX,
+            Anonymous: D3D12_ROOT_PARAMETER_0 {
+                Descriptor: bone_constants_descriptor,
+            },
+        },
+    ];
 
     //root_parameters[1] = D3D12_ROOT_PARAMETER {
     //    ParameterType: D3D12_ROOT_PARAMETER_TYPE_DESCRIPTOR_TABLE,
@@ -642,39 +884,80 @@ fn main() -> Result<()> {
     //    },
     //};
 
-    let sampler_desc = D3D12_STATIC_SAMPLER_DESC {
-        AddressU: D3D12_TEXTURE_ADDRESS_MODE_WRAP,
-        AddressV: D3D12_TEXTURE_ADDRESS_MODE_WRAP,
-        AddressW: D3D12_TEXTURE_ADDRESS_MODE_WRAP,
-        BorderColor: D3D12_STATIC_BORDER_COLOR_TRANSPARENT_BLACK,
-        Filter: D3D12_FILTER_MIN_MAG_MIP_LINEAR,
-        MaxLOD: D3D12_FLOAT32_MAX,
-        MinLOD: 0.0f32,
-        ShaderVisibility: D3D12_SHADER_VISIBILITY_PIXEL,
-        ComparisonFunc: D3D12_COMPARISON_FUNC_NEVER,
-        ..Default::default()
-    };
+    let static_samplers = sampler::static_samplers();
+
+    let input_layout = Vertex::input_layout();
+    let instanced_input_layout: Vec<D3D12_INPUT_ELEMENT_DESC> =
+        instancing::instanced_input_elements::<Vertex>()
+            .iter()
+            .map(|element| element.desc())
+            .collect();
+    let sprite_input_layout = SpriteVertex::input_layout();
+    let debug_line_input_layout = DebugLineVertex::input_layout();
+
+    let vertex_reflection = ShaderReflection::parse(blob_bytes(&vertex_shader)).unwrap();
+    let pixel_reflection = ShaderReflection::parse(blob_bytes(&pixel_shader)).unwrap();
+    let instanced_reflection =
+        ShaderReflection::parse(blob_bytes(&instanced_vertex_shader)).unwrap();
+    let sprite_reflection = ShaderReflection::parse(blob_bytes(&sprite_vertex_shader)).unwrap();
+
+    let mut interface_errors = Vec::new();
+    for (name, reflection, layout) in [
+        ("vertex", &vertex_reflection, &input_layout),
+        ("instanced", &instanced_reflection, &instanced_input_layout),
+        ("sprite", &sprite_reflection, &sprite_input_layout),
+    ] {
+        interface_errors.extend(
+            reflection
+                .validate_input_layout(layout)
+                .into_iter()
+                .map(|mismatch| format!("{} input layout mismatch: {:?}", name, mismatch)),
+        );
+    }
 
-    let input_layout = [
-        D3D12_INPUT_ELEMENT_DESC {
-            SemanticName: s!("POSITION"),
-            SemanticIndex: 0,
-            Format: DXGI_FORMAT_R32G32B32_FLOAT,
-            InputSlot: 0,
-            AlignedByteOffset: D3D12_APPEND_ALIGNED_ELEMENT,
-            InputSlotClass: D3D12_INPUT_CLASSIFICATION_PER_VERTEX_DATA,
-            InstanceDataStepRate: 0,
-        },
-        D3D12_INPUT_ELEMENT_DESC {
-            SemanticName: s!("TEXCOORD"),
-            SemanticIndex: 0,
-            Format: DXGI_FORMAT_R32G32_FLOAT,
-            InputSlot: 0,
-            AlignedByteOffset: D3D12_APPEND_ALIGNED_ELEMENT,
-            InputSlotClass: D3D12_INPUT_CLASSIFICATION_PER_VERTEX_DATA,
-            InstanceDataStepRate: 0,
-        },
+    let mut root_bindings = vec![
+        RootBinding::from_range(&descriptor_ranges[0]),
+        RootBinding::from_root_descriptor(
+            D3D12_ROOT_PARAMETER_TYPE_CBV,
+            &scene_constants_descriptor,
+        ),
+        RootBinding::from_root_constants(&output_constants_desc),
+        RootBinding::from_root_descriptor(
+            D3D12_ROOT_PARAMETER_TYPE_CBV,
+            &bone_constants_descriptor,
+        ),
     ];
+    root_bindings.extend(static_samplers.iter().map(RootBinding::from_static_sampler));
+    for resource in vertex_reflection
+        .unbound_resources(&root_bindings)
+        .into_iter()
+        .chain(pixel_reflection.unbound_resources(&root_bindings))
+    {
+        interface_errors.push(format!(
+            "shader resource not bound by root signature: {}",
+            resource.name
+        ));
+    }
+
+    match vertex_reflection.constant_buffer_at(0, 0) {
+        Some(desc) => interface_errors.extend(
+            ConstantBuffer::<SceneConstants>::validate(desc)
+                .into_iter()
+                .map(|mismatch| format!("constant buffer layout mismatch: {:?}", mismatch)),
+        ),
+        None => interface_errors.push("vertex shader has no constant buffer at b0".to_string()),
+    }
+    match vertex_reflection.constant_buffer_at(2, 0) {
+        Some(desc) => interface_errors.extend(
+            ConstantBuffer::<BoneConstants>::validate(desc)
+                .into_iter()
+                .map(|mismatch| format!("constant buffer layout mismatch: {:?}", mismatch)),
+        ),
+        None => interface_errors.push("vertex shader has no constant buffer at b2".to_string()),
+    }
+    if !interface_errors.is_empty() {
+        return Err(Error::new(E_FAIL, interface_errors.join("\n").into()));
+    }
 
     let mut render_target_blend_descs = [D3D12_RENDER_TARGET_BLEND_DESC::default(); 8];
     render_target_blend_descs[0] = D3D12_RENDER_TARGET_BLEND_DESC {
@@ -686,11 +969,10 @@ fn main() -> Result<()> {
 
     let root_signature_desc = D3D12_ROOT_SIGNATURE_DESC {
         Flags: D3D12_ROOT_SIGNATURE_FLAG_ALLOW_INPUT_ASSEMBLER_INPUT_LAYOUT,
-        pParameters: &root_parameter,
-        NumParameters: 1,
-        pStaticSamplers: &sampler_desc,
-        NumStaticSamplers: 1,
-        ..Default::default()
+        pParameters: root_parameters.as_ptr(),
+        NumParameters: root_parameters.len() as u32,
+        pStaticSamplers: static_samplers.as_ptr(),
+        NumStaticSamplers: static_samplers.len() as u32,
     };
 
     let mut root_signature_blob = None;
@@ -708,7 +990,7 @@ fn main() -> Result<()> {
     let root_signature: ID3D12RootSignature = unsafe {
         device.CreateRootSignature(
             0,
-            &std::slice::from_raw_parts(
+            std::slice::from_raw_parts(
                 root_signature_blob.GetBufferPointer() as _,
                 root_signature_blob.GetBufferSize(),
             ),
@@ -716,6 +998,11 @@ fn main() -> Result<()> {
     }
     .unwrap();
 
+    let depth_format = DepthFormat::D32;
+    let mut target_formats = [output_mode.rtv_format(), depth_format.dxgi_format()];
+    let mut msaa_samples = 4;
+    let mut sample_desc = negotiate_sample_desc(&device, &target_formats, msaa_samples);
+
     let mut graphic_pipeline_state_desc = unsafe {
         D3D12_GRAPHICS_PIPELINE_STATE_DESC {
             pRootSignature: std::mem::ManuallyDrop::new(Some(std::mem::transmute_copy(
@@ -731,7 +1018,7 @@ fn main() -> Result<()> {
             },
             SampleMask: D3D12_DEFAULT_SAMPLE_MASK,
             RasterizerState: D3D12_RASTERIZER_DESC {
-                MultisampleEnable: false.into(),
+                MultisampleEnable: (sample_desc.Count > 1).into(),
                 CullMode: D3D12_CULL_MODE_NONE,
                 FillMode: D3D12_FILL_MODE_SOLID,
                 DepthClipEnable: true.into(),
@@ -748,166 +1035,1144 @@ fn main() -> Result<()> {
             },
             IBStripCutValue: D3D12_INDEX_BUFFER_STRIP_CUT_VALUE_DISABLED,
             PrimitiveTopologyType: D3D12_PRIMITIVE_TOPOLOGY_TYPE_TRIANGLE,
+            DepthStencilState: DepthState::read_write(camera.reverse_z).desc(),
+            DSVFormat: depth_format.dxgi_format(),
             NumRenderTargets: 1,
-            SampleDesc: DXGI_SAMPLE_DESC {
-                Count: 1,
-                Quality: 0,
-            },
+            SampleDesc: sample_desc,
             ..Default::default()
         }
     };
-    graphic_pipeline_state_desc.RTVFormats[0] = DXGI_FORMAT_R8G8B8A8_UNORM;
-    let graphic_pipeline_state: ID3D12PipelineState =
+    graphic_pipeline_state_desc.RTVFormats[0] = output_mode.rtv_format();
+    let mut graphic_pipeline_state: ID3D12PipelineState =
         unsafe { device.CreateGraphicsPipelineState(&graphic_pipeline_state_desc) }.unwrap();
 
-    let view_port = D3D12_VIEWPORT {
-        Width: WINDOW_WIDTH as f32,
-        Height: WINDOW_HEIGHT as f32,
-        TopLeftX: 0.,
-        TopLeftY: 0.,
-        MaxDepth: 1.,
-        MinDepth: 0.,
-    };
-
-    let scissor_rect = RECT {
-        top: 0,
-        left: 0,
-        right: WINDOW_WIDTH as i32,
-        bottom: WINDOW_HEIGHT as i32,
-    };
-
-    loop {
-        event_loop.run_return(|event, _, control_flow| {
-            control_flow.set_poll();
-
-            match event {
-                Event::WindowEvent {
-                    event: WindowEvent::CloseRequested,
-                    ..
-                } => {
-                    println!("The close button was pressed; stopping");
-                    closed = true;
-                    unsafe { PostQuitMessage(0) };
-                    control_flow.set_exit();
+    let create_instanced_pipeline_state = |desc: &D3D12_GRAPHICS_PIPELINE_STATE_DESC| {
+        let mut desc = desc.clone();
+        desc.VS = D3D12_SHADER_BYTECODE {
+            pShaderBytecode: unsafe { instanced_vertex_shader.GetBufferPointer() },
+            BytecodeLength: unsafe { instanced_vertex_shader.GetBufferSize() },
+        };
+        desc.InputLayout = D3D12_INPUT_LAYOUT_DESC {
+            pInputElementDescs: instanced_input_layout.as_ptr(),
+            NumElements: instanced_input_layout.len() as u32,
+        };
+        unsafe { device.CreateGraphicsPipelineState(&desc) }.unwrap()
+    };
+    let mut instanced_pipeline_state: ID3D12PipelineState =
+        create_instanced_pipeline_state(&graphic_pipeline_state_desc);
+    let mut use_instancing = false;
+
+    let create_sprite_pipeline_state =
+        |desc: &D3D12_GRAPHICS_PIPELINE_STATE_DESC, pixel_shader: &ID3DBlob, blend: BlendMode| {
+            let mut desc = desc.clone();
+            desc.VS = D3D12_SHADER_BYTECODE {
+                pShaderBytecode: unsafe { sprite_vertex_shader.GetBufferPointer() },
+                BytecodeLength: unsafe { sprite_vertex_shader.GetBufferSize() },
+            };
+            desc.PS = D3D12_SHADER_BYTECODE {
+                pShaderBytecode: unsafe { pixel_shader.GetBufferPointer() },
+                BytecodeLength: unsafe { pixel_shader.GetBufferSize() },
+            };
+            desc.InputLayout = D3D12_INPUT_LAYOUT_DESC {
+                pInputElementDescs: sprite_input_layout.as_ptr(),
+                NumElements: sprite_input_layout.len() as u32,
+            };
+            desc.BlendState.RenderTarget[0] = blend.render_target_blend_desc();
+            desc.DepthStencilState = DepthState::DISABLED.desc();
+            unsafe { device.CreateGraphicsPipelineState(&desc) }.unwrap()
+        };
+    let mut sprite_pipeline_states: [ID3D12PipelineState; 2] =
+        [BlendMode::Alpha, BlendMode::Additive].map(|blend| {
+            create_sprite_pipeline_state(&graphic_pipeline_state_desc, &pixel_shader, blend)
+        });
+    let mut text_pipeline_state = create_sprite_pipeline_state(
+        &graphic_pipeline_state_desc,
+        &text_pixel_shader,
+        BlendMode::Alpha,
+    );
+    let reverse_z = camera.reverse_z;
+    let create_debug_line_pipeline_state =
+        |desc: &D3D12_GRAPHICS_PIPELINE_STATE_DESC, depth: DebugDepth| {
+            let mut desc = desc.clone();
+            desc.VS = D3D12_SHADER_BYTECODE {
+                pShaderBytecode: unsafe { debug_line_vertex_shader.GetBufferPointer() },
+                BytecodeLength: unsafe { debug_line_vertex_shader.GetBufferSize() },
+            };
+            desc.PS = D3D12_SHADER_BYTECODE {
+                pShaderBytecode: unsafe { debug_line_pixel_shader.GetBufferPointer() },
+                BytecodeLength: unsafe { debug_line_pixel_shader.GetBufferSize() },
+            };
+            desc.InputLayout = D3D12_INPUT_LAYOUT_DESC {
+                pInputElementDescs: debug_line_input_layout.as_ptr(),
+                NumElements: debug_line_input_layout.len() as u32,
+            };
+            desc.PrimitiveTopologyType = D3D12_PRIMITIVE_TOPOLOGY_TYPE_LINE;
+            desc.BlendState.RenderTarget[0] = BlendMode::Alpha.render_target_blend_desc();
+            desc.DepthStencilState = match depth {
+                DebugDepth::Tested => DepthState::read_only(reverse_z),
+                DebugDepth::Overlay => DepthState::DISABLED,
+            }
+            .desc();
+            unsafe { device.CreateGraphicsPipelineState(&desc) }.unwrap()
+        };
+    let mut debug_line_pipeline_states: [ID3D12PipelineState; 2] =
+        [DebugDepth::Tested, DebugDepth::Overlay]
+            .map(|depth| create_debug_line_pipeline_state(&graphic_pipeline_state_desc, depth));
+    let mut debug_draw = DebugDraw::new();
+    let mut show_debug_draw = false;
+    let mut debug_frustum = None;
+    let mut text_batch = SpriteBatch::new(SpriteSortMode::Texture);
+    let mut show_stats = true;
+    let mut frame_time = 0.0_f32;
+    let mut sprite_batch = SpriteBatch::new(SpriteSortMode::Texture);
+    let mut sprite_constants = ConstantBuffer::<SceneConstants>::new();
+    let mut show_sprites = false;
+    let mut elapsed = 0.0_f32;
+
+    let mut mesh_shader_pipeline = if mesh_shaders_supported {
+        match MeshShaderPipeline::new(
+            &device,
+            &asset_path.join("MeshletShader.hlsl"),
+            &pixel_shaders_hlsl_path,
+            output_mode.rtv_format(),
+            depth_format.dxgi_format(),
+            sample_desc,
+            camera.reverse_z,
+        ) {
+            Ok(pipeline) => Some(pipeline),
+            Err(e) => {
+                println!("mesh shader pipeline unavailable: {}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+    let mut use_mesh_shaders = false;
+
+    let mut size_state = SizeState::new(WINDOW_WIDTH, WINDOW_HEIGHT);
+    let inner_size = window.inner_size();
+    size_state.on_resized(inner_size.width, inner_size.height);
+
+    let mut depth_buffer = DepthBuffer::new(
+        &device,
+        size_state.width(),
+        size_state.height(),
+        depth_format,
+        sample_desc,
+        camera.depth_clear_value(),
+    )?;
+
+    let mut clear_color = [1.0_f32, 1.0, 0.0, 1.0];
+    let mut sampler_filter = SamplerFilter::Linear;
+    let mut tonemap = Tonemap::Clamp;
+    let mut ik_method = IkMethod::Ccd;
+    let mut msaa_target = if sample_desc.Count > 1 {
+        Some(MsaaTarget::new(
+            &device,
+            size_state.width(),
+            size_state.height(),
+            output_mode.swap_chain_format(),
+            output_mode.rtv_format(),
+            sample_desc,
+        )?)
+    } else {
+        None
+    };
+
+    let mut debug_ui = DebugUi::new(window.scale_factor() as f32);
+    let mut ui_renderer = UiRenderer::new(
+        &device,
+        ui_vertex_shader,
+        ui_pixel_shader,
+        output_mode.rtv_format(),
+        2,
+    )?;
+    let mut ui_textures_to_free = Vec::new();
+
+    let mut view_port = size_state.viewport();
+    let mut scissor_rect = size_state.scissor_rect();
+
+    event_loop.run_return(|event, _, control_flow| {
+        control_flow.set_poll();
+
+        match event {
+            Event::WindowEvent {
+                event: WindowEvent::CloseRequested,
+                ..
+            } => {
+                println!("The close button was pressed; stopping");
+                closed = true;
+                if size_state.mode() == DisplayMode::ExclusiveFullscreen {
+                    unsafe { swap_chain.SetFullscreenState(false, None) }.unwrap();
+                }
+                unsafe { PostQuitMessage(0) };
+                control_flow.set_exit();
+            }
+            Event::WindowEvent {
+                event: WindowEvent::Resized(size),
+                ..
+            } => {
+                size_state.on_resized(size.width, size.height);
+            }
+            Event::WindowEvent { event, .. } if !debug_ui.handle_window_event(&event) => {
+                input.handle_window_event(&event);
+            }
+            Event::DeviceEvent { event, .. } => input.handle_device_event(&event),
+            Event::MainEventsCleared if !closed && !size_state.is_minimized() => {
+                let now = std::time::Instant::now();
+                let dt = (now - last_frame).as_secs_f32();
+                elapsed += dt;
+                frame_time = if frame_time == 0. {
+                    dt
+                } else {
+                    frame_time * 0.95 + dt * 0.05
+                };
+                last_frame = now;
+
+                if input.was_key_pressed(VirtualKeyCode::Key1) {
+                    camera_controller = Box::new(OrbitController::from_camera(&camera));
+                } else if input.was_key_pressed(VirtualKeyCode::Key2) {
+                    camera_controller = Box::new(FlyController::from_camera(&camera));
+                } else if input.was_key_pressed(VirtualKeyCode::Key3) {
+                    camera_controller = Box::new(FirstPersonController::from_camera(&camera));
                 }
-                Event::MainEventsCleared if !closed => {
-                    let bb_idx = unsafe { swap_chain.GetCurrentBackBufferIndex() } as usize;
-                    unsafe { command_allocator.Reset().unwrap() };
-                    unsafe { command_list.Reset(&command_allocator, None) }.unwrap();
 
-                    let barrier = transition_barrier(
-                        &back_buffer[bb_idx],
-                        D3D12_RESOURCE_STATE_PRESENT,
-                        D3D12_RESOURCE_STATE_RENDER_TARGET,
+                let alt_down = input.is_key_down(VirtualKeyCode::LAlt)
+                    || input.is_key_down(VirtualKeyCode::RAlt);
+                let mode_change = if alt_down && input.was_key_pressed(VirtualKeyCode::Return) {
+                    size_state.toggle_exclusive()
+                } else if input.was_key_pressed(VirtualKeyCode::F11) {
+                    size_state.toggle_borderless()
+                } else {
+                    None
+                };
+                if let Some(change) = mode_change {
+                    if change.from == DisplayMode::ExclusiveFullscreen {
+                        unsafe { swap_chain.SetFullscreenState(false, None) }.unwrap();
+                    }
+                    match change.to {
+                        DisplayMode::Windowed => window.set_fullscreen(None),
+                        DisplayMode::BorderlessFullscreen => {
+                            window.set_fullscreen(Some(Fullscreen::Borderless(None)))
+                        }
+                        DisplayMode::ExclusiveFullscreen => {
+                            window.set_fullscreen(None);
+                            unsafe { swap_chain.SetFullscreenState(true, None) }.unwrap();
+                        }
+                    }
+                }
+
+                if let Some((width, height)) = size_state.take_resize() {
+                    let mut exclusive = BOOL::default();
+                    unsafe { swap_chain.GetFullscreenState(Some(&mut exclusive), None) }.unwrap();
+                    size_state.sync_exclusive(exclusive.as_bool());
+
+                    flush_command_queue(&command_queue, &fence, &mut fence_val);
+                    back_buffer.clear();
+                    unsafe {
+                        swap_chain.ResizeBuffers(
+                            0,
+                            width,
+                            height,
+                            DXGI_FORMAT_UNKNOWN,
+                            swap_chain_desc.Flags,
+                        )
+                    }
+                    .unwrap();
+                    back_buffer = create_back_buffers(
+                        &device,
+                        &swap_chain,
+                        &rtv_heap,
+                        output_mode.rtv_format(),
+                    )
+                    .unwrap();
+                    depth_buffer.resize(&device, width, height).unwrap();
+                    if let Some(msaa_target) = &mut msaa_target {
+                        msaa_target.resize(&device, width, height).unwrap();
+                    }
+
+                    view_port = size_state.viewport();
+                    scissor_rect = size_state.scissor_rect();
+                    camera.set_viewport(width, height);
+                }
+
+                let mut rebuild_targets = false;
+
+                if input.was_key_pressed(VirtualKeyCode::M) {
+                    msaa_samples = next_sample_count(msaa_samples);
+                    let negotiated = negotiate_sample_desc(&device, &target_formats, msaa_samples);
+                    println!("MSAA {}x (requested {}x)", negotiated.Count, msaa_samples);
+
+                    if negotiated != sample_desc {
+                        sample_desc = negotiated;
+                        rebuild_targets = true;
+                    }
+                }
+
+                if input.was_key_pressed(VirtualKeyCode::K) {
+                    flush_command_queue(&command_queue, &fence, &mut fence_val);
+
+                    skinning_mode = skinning_mode.next();
+                    println!("skinning mode {:?}", skinning_mode);
+                    let shader = match skinning_mode {
+                        SkinningMode::GpuDualQuaternion => &dual_quaternion_vertex_shader,
+                        _ => &vertex_shader,
+                    };
+                    graphic_pipeline_state_desc.VS = D3D12_SHADER_BYTECODE {
+                        pShaderBytecode: unsafe { shader.GetBufferPointer() },
+                        BytecodeLength: unsafe { shader.GetBufferSize() },
+                    };
+                    graphic_pipeline_state =
+                        unsafe { device.CreateGraphicsPipelineState(&graphic_pipeline_state_desc) }
+                            .unwrap();
+                }
+
+                if input.was_key_pressed(VirtualKeyCode::N) {
+                    use_mesh_shaders = !use_mesh_shaders && mesh_shader_pipeline.is_some();
+                    println!(
+                        "mesh shaders {}",
+                        if use_mesh_shaders { "on" } else { "off" }
                     );
+                }
 
-                    unsafe { command_list.ResourceBarrier(&[barrier]) };
+                if input.was_key_pressed(VirtualKeyCode::B) {
+                    show_sprites = !show_sprites;
+                    println!("sprites {}", if show_sprites { "on" } else { "off" });
+                }
 
-                    unsafe { command_list.SetPipelineState(&graphic_pipeline_state) };
-                    let rtv_handle = D3D12_CPU_DESCRIPTOR_HANDLE {
-                        ptr: unsafe { rtv_heap.GetCPUDescriptorHandleForHeapStart() }.ptr
-                            + bb_idx * rtv_descpter_size,
+                if input.was_key_pressed(VirtualKeyCode::T) {
+                    show_stats = !show_stats;
+                    println!("stats {}", if show_stats { "on" } else { "off" });
+                }
+
+                if input.was_key_pressed(VirtualKeyCode::G) {
+                    show_debug_draw = !show_debug_draw;
+                    // Freeze the current view so it can be inspected from elsewhere.
+                    debug_frustum = show_debug_draw.then(|| camera.view_projection());
+                    println!("debug draw {}", if show_debug_draw { "on" } else { "off" });
+                }
+
+                if input.was_key_pressed(VirtualKeyCode::U) {
+                    debug_ui.set_visible(!debug_ui.is_visible());
+                    println!(
+                        "debug ui {}",
+                        if debug_ui.is_visible() { "on" } else { "off" }
+                    );
+                }
+
+                if input.was_key_pressed(VirtualKeyCode::I) {
+                    use_instancing = !use_instancing;
+                    println!("instancing {}", if use_instancing { "on" } else { "off" });
+                }
+
+                if input.was_key_pressed(VirtualKeyCode::H) {
+                    flush_command_queue(&command_queue, &fence, &mut fence_val);
+                    back_buffer.clear();
+
+                    let next = output_mode.next();
+                    let (width, height) = (size_state.width(), size_state.height());
+                    output_mode = if color::set_output_mode(
+                        &swap_chain,
+                        next,
+                        width,
+                        height,
+                        swap_chain_desc.Flags,
+                    )
+                    .unwrap()
+                    {
+                        next
+                    } else {
+                        println!("{:?} output is not supported by this display", next);
+                        color::set_output_mode(
+                            &swap_chain,
+                            OutputMode::Sdr,
+                            width,
+                            height,
+                            swap_chain_desc.Flags,
+                        )
+                        .unwrap();
+                        OutputMode::Sdr
                     };
+                    println!("output mode {:?}", output_mode);
+
+                    back_buffer = create_back_buffers(
+                        &device,
+                        &swap_chain,
+                        &rtv_heap,
+                        output_mode.rtv_format(),
+                    )
+                    .unwrap();
+                    target_formats[0] = output_mode.rtv_format();
+                    sample_desc = negotiate_sample_desc(&device, &target_formats, msaa_samples);
+                    rebuild_targets = true;
+                }
 
-                    unsafe { command_list.OMSetRenderTargets(1, Some(&rtv_handle), true, None) };
+                if rebuild_targets {
+                    flush_command_queue(&command_queue, &fence, &mut fence_val);
+
+                    depth_buffer = DepthBuffer::new(
+                        &device,
+                        size_state.width(),
+                        size_state.height(),
+                        depth_format,
+                        sample_desc,
+                        camera.depth_clear_value(),
+                    )
+                    .unwrap();
+                    msaa_target = if sample_desc.Count > 1 {
+                        Some(
+                            MsaaTarget::new(
+                                &device,
+                                size_state.width(),
+                                size_state.height(),
+                                output_mode.swap_chain_format(),
+                                output_mode.rtv_format(),
+                                sample_desc,
+                            )
+                            .unwrap(),
+                        )
+                    } else {
+                        None
+                    };
 
-                    let clear_color = [1.0_f32, 1.0, 0.0, 1.0];
-                    unsafe {
-                        command_list.ClearRenderTargetView(
-                            rtv_handle,
-                            &*clear_color.as_ptr(),
-                            None,
+                    graphic_pipeline_state_desc.SampleDesc = sample_desc;
+                    graphic_pipeline_state_desc
+                        .RasterizerState
+                        .MultisampleEnable = (sample_desc.Count > 1).into();
+                    graphic_pipeline_state_desc.RTVFormats[0] = output_mode.rtv_format();
+                    graphic_pipeline_state =
+                        unsafe { device.CreateGraphicsPipelineState(&graphic_pipeline_state_desc) }
+                            .unwrap();
+                    instanced_pipeline_state =
+                        create_instanced_pipeline_state(&graphic_pipeline_state_desc);
+                    sprite_pipeline_states = [BlendMode::Alpha, BlendMode::Additive].map(|blend| {
+                        create_sprite_pipeline_state(
+                            &graphic_pipeline_state_desc,
+                            &pixel_shader,
+                            blend,
+                        )
+                    });
+                    text_pipeline_state = create_sprite_pipeline_state(
+                        &graphic_pipeline_state_desc,
+                        &text_pixel_shader,
+                        BlendMode::Alpha,
+                    );
+                    debug_line_pipeline_states =
+                        [DebugDepth::Tested, DebugDepth::Overlay].map(|depth| {
+                            create_debug_line_pipeline_state(&graphic_pipeline_state_desc, depth)
+                        });
+                    ui_renderer
+                        .rebuild(&device, output_mode.rtv_format())
+                        .unwrap();
+                    if let Some(pipeline) = &mut mesh_shader_pipeline {
+                        pipeline
+                            .rebuild(
+                                &device,
+                                output_mode.rtv_format(),
+                                depth_format.dxgi_format(),
+                                sample_desc,
+                                camera.reverse_z,
+                            )
+                            .unwrap();
+                    }
+                }
+
+                camera_controller.update(&input, dt, &mut camera);
+                input.end_frame();
+
+                debug_draw.update(dt);
+                if show_debug_draw {
+                    debug_draw.grid(
+                        Point3::new(0., 0., 0.),
+                        20.,
+                        20,
+                        &DebugStyle {
+                            color: Vector4::new(0.5, 0.5, 0.5, 1.),
+                            ..Default::default()
+                        },
+                    );
+                    debug_draw.axes(
+                        &Matrix4::from_scale(1.),
+                        2.,
+                        &DebugStyle {
+                            depth: DebugDepth::Overlay,
+                            ..Default::default()
+                        },
+                    );
+                    debug_draw.sphere(
+                        camera.target,
+                        0.25,
+                        &DebugStyle {
+                            color: Vector4::new(1., 0.5, 0., 1.),
+                            depth: DebugDepth::Overlay,
+                            ..Default::default()
+                        },
+                    );
+                    if let Some((min, max)) = mmd_bounds {
+                        debug_draw.aabb(
+                            min,
+                            max,
+                            &DebugStyle {
+                                color: Vector4::new(0., 1., 1., 1.),
+                                ..Default::default()
+                            },
                         );
                     }
+                    if let Some(view_projection) = &debug_frustum {
+                        debug_draw.frustum(
+                            view_projection,
+                            &DebugStyle {
+                                color: Vector4::new(1., 0., 1., 1.),
+                                ..Default::default()
+                            },
+                        );
+                    }
+                }
 
-                    unsafe { command_list.SetGraphicsRootSignature(&root_signature) };
-                    unsafe {
-                        let basic_descriptor_heaps: [Option<ID3D12DescriptorHeap>; 1] =
-                            [Some(basic_descriptor_heap.can_clone_into())];
-                        command_list.SetDescriptorHeaps(&basic_descriptor_heaps);
+                let ui_frame = debug_ui.is_visible().then(|| {
+                    debug_ui.run(size_state.width(), size_state.height(), |ctx| {
+                        egui::Window::new("Debug").show(ctx, |ui| {
+                            ui.horizontal(|ui| {
+                                ui.label("clear color");
+                                ui.color_edit_button_rgba_unmultiplied(&mut clear_color);
+                            });
+                            egui::ComboBox::from_label("sampler")
+                                .selected_text(sampler_filter.name())
+                                .show_ui(ui, |ui| {
+                                    for filter in SamplerFilter::ALL {
+                                        ui.selectable_value(
+                                            &mut sampler_filter,
+                                            filter,
+                                            filter.name(),
+                                        );
+                                    }
+                                });
+                            egui::ComboBox::from_label("tonemap")
+                                .selected_text(tonemap.name())
+                                .show_ui(ui, |ui| {
+                                    for candidate in Tonemap::ALL {
+                                        ui.selectable_value(
+                                            &mut tonemap,
+                                            candidate,
+                                            candidate.name(),
+                                        );
+                                    }
+                                });
+                            egui::ComboBox::from_label("ik solver")
+                                .selected_text(ik_method.name())
+                                .show_ui(ui, |ui| {
+                                    for method in IkMethod::ALL {
+                                        ui.selectable_value(&mut ik_method, method, method.name());
+                                    }
+                                });
+                            ui.separator();
+                            match &mut camera.projection {
+                                Projection::Perspective { fovy } => {
+                                    let mut degrees = cgmath::Deg::from(*fovy).0;
+                                    ui.add(
+                                        egui::Slider::new(&mut degrees, 10.0..=120.0).text("fovy"),
+                                    );
+                                    *fovy = cgmath::Deg(degrees).into();
+                                }
+                                Projection::Orthographic { height } => {
+                                    ui.add(egui::Slider::new(height, 0.1..=100.0).text("height"));
+                                }
+                            }
+                            ui.add(
+                                egui::Slider::new(&mut camera.near, 0.01..=10.0)
+                                    .logarithmic(true)
+                                    .text("near"),
+                            );
+                            let min_far = camera.near * 2.;
+                            ui.add(
+                                egui::Slider::new(&mut camera.far, min_far..=10000.0)
+                                    .logarithmic(true)
+                                    .text("far"),
+                            );
+                            ui.label(format!(
+                                "eye ({:.2}, {:.2}, {:.2})",
+                                camera.eye.x, camera.eye.y, camera.eye.z
+                            ));
+                        });
+                    })
+                });
+                if let Some(ui_frame) = &ui_frame {
+                    ui_renderer
+                        .update_textures(&device, &ui_frame.textures)
+                        .unwrap();
+                    ui_textures_to_free.extend_from_slice(&ui_frame.textures.free);
+                }
+
+                let bb_idx = unsafe { swap_chain.GetCurrentBackBufferIndex() } as usize;
+                upload_ring.begin_frame(bb_idx as u32);
+
+                let mut matrices = Vec::new();
+                if let Some(motion_player) = &mut motion_player {
+                    motion_player.advance(dt);
+                    let mut pose = motion_player.pose();
+                    matrices = skeleton.skinning_matrices(&pose);
+                    let frame = motion_player.frame();
+                    ik::solve_all(
+                        &ik_chains,
+                        ik_method,
+                        &skeleton,
+                        &mut pose,
+                        &mut matrices,
+                        |chain| motion_player.ik_enabled(&chain.name, frame),
+                    );
+                }
+                if show_debug_draw {
+                    // Bones follow the animated pose; without a motion they stay at rest.
+                    let bone_position = |bone: usize| {
+                        let position = if matrices.is_empty() {
+                            skeleton.bones[bone].position
+                        } else {
+                            skeleton.world_position(bone, &matrices)
+                        };
+                        Point3::new(position.x, position.y, position.z)
+                    };
+                    let bone_style = DebugStyle {
+                        color: Vector4::new(1., 1., 0., 1.),
+                        depth: DebugDepth::Overlay,
+                        ..Default::default()
+                    };
+                    for (index, bone) in skeleton.bones.iter().enumerate() {
+                        let Some(parent) = bone.parent else {
+                            continue;
+                        };
+                        debug_draw.line(bone_position(parent), bone_position(index), &bone_style);
+                    }
+                }
+                let skinned_vertex_buffer = if skinning_mode.is_cpu() && !matrices.is_empty() {
+                    let vertices =
+                        skinning::skin_vertices(skinning_mode.method(), &matrices, &mmd_vertices);
+                    skinning::upload_vertices(&mut upload_ring, &vertices)
+                } else {
+                    None
+                };
+                let bones = match skinned_vertex_buffer {
+                    Some(_) => BoneConstants::default(),
+                    None => BoneConstants::from_matrices(&matrices),
+                };
+                let constants = bone_constants
+                    .update(&mut upload_ring, &bones)
+                    .and_then(|_| {
+                        scene_constants.update(
+                            &mut upload_ring,
+                            &SceneConstants {
+                                mat: camera.view_projection(),
+                            },
+                        )
+                    });
+                if constants.is_none() {
+                    println!("upload ring exhausted, skipping frame");
+                    return;
+                }
+                unsafe { command_allocator.Reset().unwrap() };
+                unsafe { command_list.Reset(&command_allocator, None) }.unwrap();
+
+                let rtv_handle = match &msaa_target {
+                    Some(msaa_target) => msaa_target.rtv_handle(),
+                    None => {
+                        let barrier = transition_barrier(
+                            &back_buffer[bb_idx],
+                            D3D12_RESOURCE_STATE_PRESENT,
+                            D3D12_RESOURCE_STATE_RENDER_TARGET,
+                        );
+
+                        unsafe { command_list.ResourceBarrier(&[barrier]) };
+
+                        D3D12_CPU_DESCRIPTOR_HANDLE {
+                            ptr: unsafe { rtv_heap.GetCPUDescriptorHandleForHeapStart() }.ptr
+                                + bb_idx * rtv_descpter_size,
+                        }
                     }
-                    let heap_handle =
-                        unsafe { basic_descriptor_heap.GetGPUDescriptorHandleForHeapStart() };
-                    unsafe { command_list.SetGraphicsRootDescriptorTable(0, heap_handle) };
-                    //let heap_handle = D3D12_GPU_DESCRIPTOR_HANDLE {
-                    //    ptr: heap_handle.ptr
-                    //        + unsafe {
-                    //            device.GetDescriptorHandleIncrementSize(
-                    //                D3D12_DESCRIPTOR_HEAP_TYPE_CBV_SRV_UAV,
-                    //            )
-                    //        } as u64,
-                    //};
-                    //unsafe { command_list.SetGraphicsRootDescriptorTable(1, heap_handle) };
-
-                    unsafe { command_list.RSSetViewports(&[view_port]) }
-                    unsafe { command_list.RSSetScissorRects(&[scissor_rect]) }
+                };
+
+                let mesh_pipeline = mesh_shader_pipeline
+                    .as_ref()
+                    .filter(|_| use_mesh_shaders && model.is_some());
+                match mesh_pipeline {
+                    Some(pipeline) => unsafe {
+                        command_list.SetPipelineState(pipeline.pipeline_state())
+                    },
+                    None => unsafe { command_list.SetPipelineState(&graphic_pipeline_state) },
+                }
+
+                let dsv_handle = depth_buffer.dsv_handle();
+                unsafe {
+                    command_list.OMSetRenderTargets(1, Some(&rtv_handle), true, Some(&dsv_handle))
+                };
+
+                let clear_value = output_mode.shade(
+                    Vector3::new(clear_color[0], clear_color[1], clear_color[2]),
+                    paper_white_nits,
+                    tonemap,
+                );
+                let clear_value = [clear_value.x, clear_value.y, clear_value.z, clear_color[3]];
+                unsafe {
+                    command_list.ClearRenderTargetView(rtv_handle, &*clear_value.as_ptr(), None);
+                }
+                depth_buffer.clear(&command_list);
+
+                match mesh_pipeline {
+                    Some(pipeline) => unsafe {
+                        command_list.SetGraphicsRootSignature(pipeline.root_signature())
+                    },
+                    None => unsafe { command_list.SetGraphicsRootSignature(&root_signature) },
+                }
+                unsafe {
+                    let basic_descriptor_heaps: [Option<ID3D12DescriptorHeap>; 1] =
+                        [Some(basic_descriptor_heap.can_clone_into())];
+                    command_list.SetDescriptorHeaps(&basic_descriptor_heaps);
+                }
+                let heap_handle =
+                    unsafe { basic_descriptor_heap.GetGPUDescriptorHandleForHeapStart() };
+                unsafe { command_list.SetGraphicsRootDescriptorTable(0, heap_handle) };
+                unsafe {
+                    command_list.SetGraphicsRootConstantBufferView(1, scene_constants.gpu_address())
+                };
+                if mesh_pipeline.is_none() {
                     unsafe {
-                        command_list.IASetPrimitiveTopology(D3D_PRIMITIVE_TOPOLOGY_TRIANGLELIST)
+                        command_list
+                            .SetGraphicsRootConstantBufferView(3, bone_constants.gpu_address())
                     };
+                }
+                let output_constants =
+                    OutputConstants::new(output_mode, paper_white_nits, sampler_filter, tonemap);
+                unsafe {
+                    command_list.SetGraphicsRoot32BitConstants(
+                        2,
+                        OutputConstants::NUM_32BIT_VALUES,
+                        &output_constants as *const _ as *const c_void,
+                        0,
+                    )
+                };
+                //let heap_handle = D3D12_GPU_DESCRIPTOR_HANDLE {
+                //    ptr: heap_handle.ptr
+                //        + unsafe {
+                //            device.GetDescriptorHandleIncrementSize(
+                //                D3D12_DESCRIPTOR_HEAP_TYPE_CBV_SRV_UAV,
+                //            )
+                //        } as u64,
+                //};
+                //unsafe { command_list.SetGraphicsRootDescriptorTable(1, heap_handle) };
+
+                unsafe { command_list.RSSetViewports(&[view_port]) }
+                unsafe { command_list.RSSetScissorRects(&[scissor_rect]) }
+                unsafe { command_list.IASetPrimitiveTopology(D3D_PRIMITIVE_TOPOLOGY_TRIANGLELIST) };
+
+                let instanced = use_instancing && mesh_pipeline.is_none();
+                match &model {
+                    Some(model) if instanced => {
+                        let mut batcher = InstanceBatcher::new();
+                        let world = model.world_transforms();
+                        for (node, world) in model.nodes.iter().zip(world) {
+                            let Some(mesh) = node.mesh else {
+                                continue;
+                            };
+                            for (primitive, (_, lods, _)) in model_buffers[mesh].iter().enumerate()
+                            {
+                                let level = lods.select(
+                                    &camera,
+                                    &world,
+                                    size_state.height() as f32,
+                                    lod::DEFAULT_THRESHOLD_PIXELS,
+                                );
+                                batcher.push(
+                                    DrawKey::new(mesh, primitive, &lods.levels[level].range),
+                                    InstanceData::new(world, Vector4::new(1., 1., 1., 1.)),
+                                );
+                            }
+                        }
+                        let draws = batcher.build();
+                        if draws.overflow > 0 {
+                            println!("instance buffer full, dropped {} instances", draws.overflow);
+                        }
+                        if let Some(instance_buffer) = draws.upload(&mut upload_ring) {
+                            unsafe { command_list.SetPipelineState(&instanced_pipeline_state) };
+                            draws.submit(
+                                &command_list,
+                                instance_buffer,
+                                |key| {
+                                    let (buffer, _, _) = &model_buffers[key.mesh][key.primitive];
+                                    (buffer.vertex_buffer_view(), buffer.index_buffer_view())
+                                },
+                                |material| Some(material_texture(material)),
+                            );
+                        }
+                    }
+                    Some(model) => {
+                        let world = model.world_transforms();
+                        for (node, world) in model.nodes.iter().zip(world) {
+                            let Some(mesh) = node.mesh else {
+                                continue;
+                            };
+                            let mut draw_constants = ConstantBuffer::<SceneConstants>::new();
+                            let Some(draw_constants) = draw_constants.update(
+                                &mut upload_ring,
+                                &SceneConstants {
+                                    mat: camera.view_projection() * world,
+                                },
+                            ) else {
+                                continue;
+                            };
+                            unsafe {
+                                command_list.SetGraphicsRootConstantBufferView(1, draw_constants)
+                            };
+                            for (buffer, lods, meshlets) in &model_buffers[mesh] {
+                                if let (Some(_), Some(meshlets), Some(mesh_command_list)) =
+                                    (mesh_pipeline, meshlets, &mesh_command_list)
+                                {
+                                    let mut meshlet_constants =
+                                        ConstantBuffer::<MeshletConstants>::new();
+                                    if let Some(meshlet_constants) = meshlet_constants.update(
+                                        &mut upload_ring,
+                                        &MeshletConstants::new(
+                                            &camera,
+                                            &world,
+                                            meshlets.meshlet_count(),
+                                        ),
+                                    ) {
+                                        meshlets.draw(mesh_command_list, meshlet_constants);
+                                    }
+                                    continue;
+                                }
+                                let level = lods.select(
+                                    &camera,
+                                    &world,
+                                    size_state.height() as f32,
+                                    lod::DEFAULT_THRESHOLD_PIXELS,
+                                );
+                                let range = &lods.levels[level].range;
+                                unsafe {
+                                    command_list.SetGraphicsRootDescriptorTable(
+                                        0,
+                                        material_texture(range.material),
+                                    )
+                                };
+                                buffer.draw_range(&command_list, range);
+                            }
+                        }
+                    }
+                    None => match &mmd_mesh {
+                        Some((buffer, draw_ranges)) => {
+                            let vertex_buffer_view =
+                                skinned_vertex_buffer.unwrap_or(buffer.vertex_buffer_view());
+                            for range in draw_ranges {
+                                buffer.draw_range_with(&command_list, vertex_buffer_view, range);
+                            }
+                        }
+                        None if instanced => {
+                            let mut batcher = InstanceBatcher::new();
+                            let range = DrawRange {
+                                start_index: 0,
+                                index_count: quad.index_count(),
+                                material: 0,
+                            };
+                            let center = (INSTANCE_GRID_SIZE - 1) as f32 / 2.;
+                            for y in 0..INSTANCE_GRID_SIZE {
+                                for x in 0..INSTANCE_GRID_SIZE {
+                                    let (fx, fy) = (x as f32 - center, y as f32 - center);
+                                    batcher.push(
+                                        DrawKey::new(0, 0, &range),
+                                        InstanceData::new(
+                                            Matrix4::from_translation(Vector3::new(
+                                                fx,
+                                                fy * 1.6,
+                                                0.,
+                                            )),
+                                            Vector4::new(
+                                                x as f32 / center / 2.,
+                                                y as f32 / center / 2.,
+                                                1.,
+                                                1.,
+                                            ),
+                                        ),
+                                    );
+                                }
+                            }
+                            let draws = batcher.build();
+                            if let Some(instance_buffer) = draws.upload(&mut upload_ring) {
+                                unsafe { command_list.SetPipelineState(&instanced_pipeline_state) };
+                                draws.submit(
+                                    &command_list,
+                                    instance_buffer,
+                                    |_| (quad.vertex_buffer_view(), quad.index_buffer_view()),
+                                    |_| None,
+                                );
+                            }
+                        }
+                        None => quad.draw(&command_list),
+                    },
+                }
 
+                let debug_geometry = debug_draw.build();
+                if let Some(vertex_buffer) = debug_geometry.upload(&mut upload_ring) {
+                    unsafe { command_list.SetGraphicsRootSignature(&root_signature) };
                     unsafe {
-                        command_list.IASetVertexBuffers(0, Some(&[vertex_buffer_view]));
-                    }
+                        command_list
+                            .SetGraphicsRootConstantBufferView(1, scene_constants.gpu_address())
+                    };
                     unsafe {
-                        command_list.IASetIndexBuffer(Some(&index_view));
+                        command_list.SetGraphicsRoot32BitConstants(
+                            2,
+                            OutputConstants::NUM_32BIT_VALUES,
+                            &output_constants as *const _ as *const c_void,
+                            0,
+                        )
+                    };
+                    unsafe { command_list.IASetPrimitiveTopology(D3D_PRIMITIVE_TOPOLOGY_LINELIST) };
+                    debug_geometry.submit(&command_list, vertex_buffer, |depth| unsafe {
+                        command_list.SetPipelineState(&debug_line_pipeline_states[depth as usize])
+                    });
+                }
+
+                if show_sprites {
+                    sprite_batch.clear();
+                    for i in 0..8 {
+                        let entry = &sprite_atlas.layout.entries[i % 2];
+                        let mut sprite = entry.sprite(Vector2::new(96. + i as f32 * 128., 96.));
+                        sprite.size = Vector2::new(96., 96.);
+                        sprite.rotation = elapsed * (1. + i as f32 * 0.25);
+                        sprite.tint = Vector4::new(1., 1. - i as f32 / 8., i as f32 / 8., 0.75);
+                        sprite.depth = i as f32 / 8.;
+                        if i % 2 == 1 {
+                            sprite.blend = BlendMode::Additive;
+                        }
+                        sprite_batch.draw(sprite);
                     }
+                    let geometry = sprite_batch.build();
+                    let uploaded = geometry.upload(&mut upload_ring).and_then(|buffers| {
+                        sprite_constants
+                            .update(
+                                &mut upload_ring,
+                                &SceneConstants {
+                                    mat: sprite_batch::screen_projection(
+                                        size_state.width() as f32,
+                                        size_state.height() as f32,
+                                    ),
+                                },
+                            )
+                            .map(|_| buffers)
+                    });
+                    if let Some((vertex_buffer, index_buffer)) = uploaded {
+                        unsafe { command_list.SetGraphicsRootSignature(&root_signature) };
+                        unsafe {
+                            command_list.SetGraphicsRootConstantBufferView(
+                                1,
+                                sprite_constants.gpu_address(),
+                            )
+                        };
+                        unsafe {
+                            command_list.SetGraphicsRoot32BitConstants(
+                                2,
+                                OutputConstants::NUM_32BIT_VALUES,
+                                &output_constants as *const _ as *const c_void,
+                                0,
+                            )
+                        };
+                        unsafe {
+                            command_list.IASetPrimitiveTopology(D3D_PRIMITIVE_TOPOLOGY_TRIANGLELIST)
+                        };
+                        geometry.submit(
+                            &command_list,
+                            vertex_buffer,
+                            index_buffer,
+                            |blend| unsafe {
+                                command_list
+                                    .SetPipelineState(&sprite_pipeline_states[blend as usize])
+                            },
+                            |page| {
+                                Some(D3D12_GPU_DESCRIPTOR_HANDLE {
+                                    ptr: heap_handle.ptr
+                                        + ((atlas_texture_slot + page) * srv_descriptor_size)
+                                            as u64,
+                                })
+                            },
+                        );
+                    }
+                }
 
-                    unsafe {
-                        command_list.DrawIndexedInstanced(6, 1, 0, 0, 0);
+                if let (true, Some(text_renderer), Some(glyph_texture)) =
+                    (show_stats, &mut text_renderer, &glyph_texture)
+                {
+                    text_batch.clear();
+                    let stats = format!(
+                        "{:.1} fps ({:.2} ms)\n{}x{} {:?} MSAA x{}\n描画統計",
+                        1. / frame_time,
+                        frame_time * 1000.,
+                        size_state.width(),
+                        size_state.height(),
+                        output_mode,
+                        sample_desc.Count,
+                    );
+                    text_renderer.draw(
+                        &mut text_batch,
+                        &stats,
+                        Vector2::new(16., 16.),
+                        &TextStyle {
+                            size: 20.,
+                            ..Default::default()
+                        },
+                    );
+                    glyph_texture.update(&mut text_renderer.cache).unwrap();
+                    let geometry = text_batch.build();
+                    let uploaded = geometry.upload(&mut upload_ring).and_then(|buffers| {
+                        sprite_constants
+                            .update(
+                                &mut upload_ring,
+                                &SceneConstants {
+                                    mat: sprite_batch::screen_projection(
+                                        size_state.width() as f32,
+                                        size_state.height() as f32,
+                                    ),
+                                },
+                            )
+                            .map(|_| buffers)
+                    });
+                    if let Some((vertex_buffer, index_buffer)) = uploaded {
+                        unsafe { command_list.SetGraphicsRootSignature(&root_signature) };
+                        unsafe {
+                            command_list.SetGraphicsRootConstantBufferView(
+                                1,
+                                sprite_constants.gpu_address(),
+                            )
+                        };
+                        unsafe {
+                            command_list.SetGraphicsRoot32BitConstants(
+                                2,
+                                OutputConstants::NUM_32BIT_VALUES,
+                                &output_constants as *const _ as *const c_void,
+                                0,
+                            )
+                        };
+                        unsafe {
+                            command_list.IASetPrimitiveTopology(D3D_PRIMITIVE_TOPOLOGY_TRIANGLELIST)
+                        };
+                        unsafe { command_list.SetPipelineState(&text_pipeline_state) };
+                        let glyph_handle = D3D12_GPU_DESCRIPTOR_HANDLE {
+                            ptr: heap_handle.ptr + srv_descriptor_size as u64,
+                        };
+                        geometry.submit(
+                            &command_list,
+                            vertex_buffer,
+                            index_buffer,
+                            |_| {},
+                            |_| Some(glyph_handle),
+                        );
                     }
+                }
 
-                    unsafe {
-                        command_list.ResourceBarrier(&[transition_barrier(
-                            &back_buffer[bb_idx],
-                            D3D12_RESOURCE_STATE_RENDER_TARGET,
-                            D3D12_RESOURCE_STATE_PRESENT,
-                        )])
+                if let Some(msaa_target) = &msaa_target {
+                    msaa_target.resolve(&command_list, &back_buffer[bb_idx]);
+                }
+
+                if let Some(ui_frame) = &ui_frame {
+                    let back_buffer_rtv = D3D12_CPU_DESCRIPTOR_HANDLE {
+                        ptr: unsafe { rtv_heap.GetCPUDescriptorHandleForHeapStart() }.ptr
+                            + bb_idx * rtv_descpter_size,
                     };
+                    ui_renderer.render(
+                        &command_list,
+                        bb_idx as u32,
+                        &ui_frame.draw_list,
+                        &output_constants,
+                        back_buffer_rtv,
+                        size_state.width(),
+                        size_state.height(),
+                    );
+                }
 
-                    unsafe { command_list.Close() }.unwrap();
-                    let command_lists: [Option<ID3D12CommandList>; 1] =
-                        [Some(command_list.can_clone_into())];
+                unsafe {
+                    command_list.ResourceBarrier(&[transition_barrier(
+                        &back_buffer[bb_idx],
+                        D3D12_RESOURCE_STATE_RENDER_TARGET,
+                        D3D12_RESOURCE_STATE_PRESENT,
+                    )])
+                };
 
-                    unsafe { command_queue.ExecuteCommandLists(&command_lists) };
-                    unsafe { swap_chain.Present(1, 0) }.unwrap();
+                unsafe { command_list.Close() }.unwrap();
+                let command_lists: [Option<ID3D12CommandList>; 1] =
+                    [Some(command_list.can_clone_into())];
 
-                    unsafe { command_queue.Signal(&fence, fence_val) }.unwrap();
+                unsafe { command_queue.ExecuteCommandLists(&command_lists) };
+                unsafe { swap_chain.Present(1, 0) }.unwrap();
 
-                    if unsafe { fence.GetCompletedValue() } < fence_val {
-                        unsafe {
-                            let fence_event = CreateEventA(None, false, false, None).unwrap();
-                            fence.SetEventOnCompletion(fence_val, fence_event).unwrap();
-                            WaitForSingleObject(fence_event, INFINITE);
-                            CloseHandle(fence_event);
-                        }
+                unsafe { command_queue.Signal(&fence, fence_val) }.unwrap();
+
+                if unsafe { fence.GetCompletedValue() } < fence_val {
+                    unsafe {
+                        let fence_event = CreateEventA(None, false, false, None).unwrap();
+                        fence.SetEventOnCompletion(fence_val, fence_event).unwrap();
+                        WaitForSingleObject(fence_event, INFINITE);
+                        CloseHandle(fence_event);
                     }
-                    fence_val += 1;
                 }
-                _ => {}
+                fence_val += 1;
+
+                ui_renderer.free_textures(&ui_textures_to_free);
+                ui_textures_to_free.clear();
             }
-        });
-        break;
-    }
+            _ => {}
+        }
+    });
     Ok(())
 }
 
-fn transition_barrier(
-    resource: &ID3D12Resource,
-    state_before: D3D12_RESOURCE_STATES,
-    state_after: D3D12_RESOURCE_STATES,
-) -> D3D12_RESOURCE_BARRIER {
-    D3D12_RESOURCE_BARRIER {
-        Type: D3D12_RESOURCE_BARRIER_TYPE_TRANSITION,
-        Flags: D3D12_RESOURCE_BARRIER_FLAG_NONE,
-        Anonymous: D3D12_RESOURCE_BARRIER_0 {
-            Transition: std::mem::ManuallyDrop::new(D3D12_RESOURCE_TRANSITION_BARRIER {
-                pResource: unsafe { std::mem::transmute_copy(resource) },
-                StateBefore: state_before,
-                StateAfter: state_after,
-                Subresource: D3D12_RESOURCE_BARRIER_ALL_SUBRESOURCES,
-            }),
-        },
+fn create_back_buffers(
+    device: &ID3D12Device,
+    swap_chain: &IDXGISwapChain4,
+    rtv_heap: &ID3D12DescriptorHeap,
+    rtv_format: DXGI_FORMAT,
+) -> Result<Vec<ID3D12Resource>> {
+    let rtv_handle = unsafe { rtv_heap.GetCPUDescriptorHandleForHeapStart() };
+    let rtv_descpter_size =
+        unsafe { device.GetDescriptorHandleIncrementSize(D3D12_DESCRIPTOR_HEAP_TYPE_RTV) } as usize;
+
+    let render_target_view_desc = D3D12_RENDER_TARGET_VIEW_DESC {
+        Format: rtv_format,
+        ViewDimension: D3D12_RTV_DIMENSION_TEXTURE2D,
+        ..Default::default()
+    };
+
+    let mut swap_chain_desc = DXGI_SWAP_CHAIN_DESC1::default();
+    unsafe { swap_chain.GetDesc1(&mut swap_chain_desc) }?;
+
+    (0..swap_chain_desc.BufferCount as usize)
+        .map(|i| {
+            let render_target: ID3D12Resource = unsafe { swap_chain.GetBuffer(i as u32) }?;
+            unsafe {
+                device.CreateRenderTargetView(
+                    &render_target,
+                    Some(&render_target_view_desc),
+                    D3D12_CPU_DESCRIPTOR_HANDLE {
+                        ptr: rtv_handle.ptr + i * rtv_descpter_size,
+                    },
+                )
+            }
+            Ok(render_target)
+        })
+        .collect()
+}
+
+fn flush_command_queue(
+    command_queue: &ID3D12CommandQueue,
+    fence: &ID3D12Fence,
+    fence_val: &mut u64,
+) {
+    unsafe { command_queue.Signal(fence, *fence_val) }.unwrap();
+
+    if unsafe { fence.GetCompletedValue() } < *fence_val {
+        unsafe {
+            let fence_event = CreateEventA(None, false, false, None).unwrap();
+            fence.SetEventOnCompletion(*fence_val, fence_event).unwrap();
+            WaitForSingleObject(fence_event, INFINITE);
+            CloseHandle(fence_event);
+        }
     }
+    *fence_val += 1;
 }
 
 fn enable_debug_layer() -> Option<()> {
@@ -920,9 +2185,185 @@ fn enable_debug_layer() -> Option<()> {
 }
 
 #[repr(C)]
+#[derive(Clone, Copy, VertexLayout)]
 struct Vertex {
+    #[vertex(semantic = "POSITION")]
     pos: Vector3<f32>,
+    #[vertex(semantic = "NORMAL")]
+    normal: Vector3<f32>,
+    #[vertex(semantic = "TEXCOORD")]
     uv: Vector2<f32>,
+    #[vertex(semantic = "BONE_NO")]
+    bone_no: [u16; MAX_INFLUENCES],
+    #[vertex(semantic = "WEIGHT")]
+    weight: [f32; MAX_INFLUENCES],
+    #[vertex(semantic = "SDEF_C")]
+    sdef_c: Vector3<f32>,
+    #[vertex(semantic = "SDEF_R0")]
+    sdef_r0: Vector3<f32>,
+    #[vertex(semantic = "SDEF_R1")]
+    sdef_r1: Vector3<f32>,
+    #[vertex(semantic = "EDGE_FLG")]
+    edge_flag: u8,
+    #[vertex(semantic = "DEFORM")]
+    deform: u8,
+}
+
+impl Default for Vertex {
+    fn default() -> Self {
+        Vertex {
+            pos: Vector3::new(0., 0., 0.),
+            normal: Vector3::new(0., 0., -1.),
+            uv: Vector2::new(0., 0.),
+            bone_no: [0; MAX_INFLUENCES],
+            weight: [1., 0., 0., 0.],
+            sdef_c: Vector3::new(0., 0., 0.),
+            sdef_r0: Vector3::new(0., 0., 0.),
+            sdef_r1: Vector3::new(0., 0., 0.),
+            edge_flag: 0,
+            deform: Deform::Blend.shader_id(),
+        }
+    }
+}
+
+impl Vertex {
+    fn set_deform(&mut self, deform: Deform) {
+        self.deform = deform.shader_id();
+        if let Deform::Sdef(sdef) = deform {
+            self.sdef_c = sdef.c;
+            self.sdef_r0 = sdef.r0;
+            self.sdef_r1 = sdef.r1;
+        }
+    }
+}
+
+impl Skinnable for Vertex {
+    fn position(&self) -> Vector3<f32> {
+        self.pos
+    }
+
+    fn normal(&self) -> Vector3<f32> {
+        self.normal
+    }
+
+    fn influences(&self) -> [(usize, f32); MAX_INFLUENCES] {
+        std::array::from_fn(|i| (self.bone_no[i] as usize, self.weight[i]))
+    }
+
+    fn set_skinned(&mut self, position: Vector3<f32>, normal: Vector3<f32>) {
+        self.pos = position;
+        self.normal = normal;
+    }
+
+    fn deform(&self) -> Deform {
+        match self.deform {
+            1 => Deform::DualQuaternion,
+            2 => Deform::Sdef(Sdef {
+                c: self.sdef_c,
+                r0: self.sdef_r0,
+                r1: self.sdef_r1,
+            }),
+            _ => Deform::Blend,
+        }
+    }
+}
+
+impl From<&PmdVertex> for Vertex {
+    fn from(v: &PmdVertex) -> Self {
+        let [w0, w1] = v.bone_weights();
+        Vertex {
+            pos: v.position,
+            normal: v.normal,
+            uv: v.uv,
+            bone_no: [v.bones[0], v.bones[1], 0, 0],
+            weight: [w0, w1, 0., 0.],
+            edge_flag: v.edge_flag,
+            ..Default::default()
+        }
+    }
+}
+
+impl From<&PmxVertex> for Vertex {
+    fn from(v: &PmxVertex) -> Self {
+        let (bones, weights) = v.weight.bones_and_weights();
+        // BDEF4/QDEF weights are not guaranteed to sum to one.
+        let total: f32 = weights.iter().sum();
+        let weights = if total > 0. {
+            weights.map(|weight| weight / total)
+        } else {
+            weights
+        };
+        let deform = match v.weight {
+            Weight::Sdef {
+                weight, c, r0, r1, ..
+            } => Deform::Sdef(Sdef::new(c, r0, r1, weight)),
+            Weight::Qdef { .. } => Deform::DualQuaternion,
+            _ => Deform::Blend,
+        };
+        let mut vertex = Vertex {
+            pos: v.position,
+            normal: v.normal,
+            uv: v.uv,
+            bone_no: bones.map(|bone| bone as u16),
+            weight: weights,
+            edge_flag: (v.edge_scale == 0.) as u8,
+            ..Default::default()
+        };
+        vertex.set_deform(deform);
+        vertex
+    }
+}
+
+impl From<&MeshVertex> for Vertex {
+    fn from(v: &MeshVertex) -> Self {
+        Vertex {
+            pos: v.position,
+            normal: v.normal,
+            uv: v.uv,
+            bone_no: v.joints,
+            weight: v.weights,
+            ..Default::default()
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(ConstantBufferLayout)]
+struct SceneConstants {
+    mat: Matrix4<f32>,
+}
+
+#[repr(C)]
+#[derive(ConstantBufferLayout)]
+struct BoneConstants {
+    bones: [Matrix4<f32>; MAX_BONES],
+    real: [Vector4<f32>; MAX_BONES],
+    dual: [Vector4<f32>; MAX_BONES],
+}
+
+impl Default for BoneConstants {
+    fn default() -> Self {
+        let (real, dual) = DualQuaternion::identity().to_vectors();
+        BoneConstants {
+            bones: [Matrix4::from_scale(1.); MAX_BONES],
+            real: [real; MAX_BONES],
+            dual: [dual; MAX_BONES],
+        }
+    }
+}
+
+impl BoneConstants {
+    fn from_matrices(matrices: &[Matrix4<f32>]) -> Self {
+        assert!(matrices.len() <= MAX_BONES, "bone palette overflow");
+        let mut constants = BoneConstants::default();
+        for (i, matrix) in matrices.iter().enumerate() {
+            let (real, dual) = DualQuaternion::from_matrix(matrix).to_vectors();
+            constants.bones[i] = *matrix;
+            constants.real[i] = real;
+            constants.dual[i] = dual;
+        }
+        constants
+    }
 }
 
 #[repr(C)]
@@ -933,6 +2374,12 @@ struct TexRGBA {
     a: u8,
 }
 
+fn blob_bytes(blob: &ID3DBlob) -> &[u8] {
+    unsafe {
+        std::slice::from_raw_parts(blob.GetBufferPointer() as *const u8, blob.GetBufferSize())
+    }
+}
+
 fn alignmented_size(size: usize, alignment: usize) -> usize {
     let alignment = alignment - 1;
     (size + alignment) & !alignment
@@ -0,0 +1,134 @@
+use windows::{core::*, Win32::Graphics::Direct3D12::*, Win32::Graphics::Dxgi::Common::*};
+
+use crate::model::{DrawRange, Indices};
+use crate::vertex_layout::VertexLayout;
+
+pub struct MeshBuffer {
+    vertex_buffer: ID3D12Resource,
+    index_buffer: ID3D12Resource,
+    vertex_buffer_view: D3D12_VERTEX_BUFFER_VIEW,
+    index_buffer_view: D3D12_INDEX_BUFFER_VIEW,
+    index_count: u32,
+}
+
+impl MeshBuffer {
+    pub fn new<V: VertexLayout>(
+        device: &ID3D12Device,
+        vertices: &[V],
+        indices: &Indices,
+    ) -> Result<Self> {
+        let vertex_bytes = unsafe {
+            std::slice::from_raw_parts(
+                vertices.as_ptr() as *const u8,
+                std::mem::size_of_val(vertices),
+            )
+        };
+        let vertex_buffer = create_upload_buffer(device, vertex_bytes)?;
+        let index_buffer = create_upload_buffer(device, indices.as_bytes())?;
+
+        let vertex_buffer_view = D3D12_VERTEX_BUFFER_VIEW {
+            BufferLocation: unsafe { vertex_buffer.GetGPUVirtualAddress() },
+            SizeInBytes: vertex_bytes.len() as u32,
+            StrideInBytes: V::stride(),
+        };
+        let index_buffer_view = D3D12_INDEX_BUFFER_VIEW {
+            BufferLocation: unsafe { index_buffer.GetGPUVirtualAddress() },
+            SizeInBytes: indices.as_bytes().len() as u32,
+            Format: indices.format(),
+        };
+
+        Ok(MeshBuffer {
+            vertex_buffer,
+            index_buffer,
+            vertex_buffer_view,
+            index_buffer_view,
+            index_count: indices.len() as u32,
+        })
+    }
+
+    pub fn vertex_buffer(&self) -> &ID3D12Resource {
+        &self.vertex_buffer
+    }
+
+    pub fn index_buffer(&self) -> &ID3D12Resource {
+        &self.index_buffer
+    }
+
+    pub fn vertex_buffer_view(&self) -> D3D12_VERTEX_BUFFER_VIEW {
+        self.vertex_buffer_view
+    }
+
+    pub fn index_buffer_view(&self) -> D3D12_INDEX_BUFFER_VIEW {
+        self.index_buffer_view
+    }
+
+    pub fn index_count(&self) -> u32 {
+        self.index_count
+    }
+
+    pub fn draw(&self, command_list: &ID3D12GraphicsCommandList) {
+        unsafe { command_list.IASetVertexBuffers(0, Some(&[self.vertex_buffer_view])) };
+        unsafe { command_list.IASetIndexBuffer(Some(&self.index_buffer_view)) };
+        unsafe { command_list.DrawIndexedInstanced(self.index_count, 1, 0, 0, 0) };
+    }
+
+    pub fn draw_range(&self, command_list: &ID3D12GraphicsCommandList, range: &DrawRange) {
+        self.draw_range_with(command_list, self.vertex_buffer_view, range);
+    }
+
+    pub fn draw_range_with(
+        &self,
+        command_list: &ID3D12GraphicsCommandList,
+        vertex_buffer_view: D3D12_VERTEX_BUFFER_VIEW,
+        range: &DrawRange,
+    ) {
+        unsafe { command_list.IASetVertexBuffers(0, Some(&[vertex_buffer_view])) };
+        unsafe { command_list.IASetIndexBuffer(Some(&self.index_buffer_view)) };
+        unsafe { command_list.DrawIndexedInstanced(range.index_count, 1, range.start_index, 0, 0) };
+    }
+}
+
+pub fn create_upload_buffer(device: &ID3D12Device, data: &[u8]) -> Result<ID3D12Resource> {
+    let heap_properties = D3D12_HEAP_PROPERTIES {
+        Type: D3D12_HEAP_TYPE_UPLOAD,
+        CPUPageProperty: D3D12_CPU_PAGE_PROPERTY_UNKNOWN,
+        MemoryPoolPreference: D3D12_MEMORY_POOL_UNKNOWN,
+        ..Default::default()
+    };
+    let resource_desc = D3D12_RESOURCE_DESC {
+        Dimension: D3D12_RESOURCE_DIMENSION_BUFFER,
+        Width: data.len().max(1) as u64,
+        Height: 1,
+        DepthOrArraySize: 1,
+        MipLevels: 1,
+        Format: DXGI_FORMAT_UNKNOWN,
+        SampleDesc: DXGI_SAMPLE_DESC {
+            Count: 1,
+            ..Default::default()
+        },
+        Flags: D3D12_RESOURCE_FLAG_NONE,
+        Layout: D3D12_TEXTURE_LAYOUT_ROW_MAJOR,
+        ..Default::default()
+    };
+
+    let mut buffer: Option<ID3D12Resource> = None;
+    unsafe {
+        device.CreateCommittedResource(
+            &heap_properties,
+            D3D12_HEAP_FLAG_NONE,
+            &resource_desc,
+            D3D12_RESOURCE_STATE_GENERIC_READ,
+            None,
+            &mut buffer,
+        )
+    }?;
+    let buffer = buffer.unwrap();
+
+    unsafe {
+        let mut map = std::ptr::null_mut();
+        buffer.Map(0, None, Some(&mut map))?;
+        std::ptr::copy_nonoverlapping(data.as_ptr(), map as *mut u8, data.len());
+        buffer.Unmap(0, None);
+    }
+    Ok(buffer)
+}
@@ -0,0 +1,744 @@
+use std::collections::HashMap;
+
+use cgmath::{InnerSpace, Vector3, Vector4, Zero};
+
+use crate::model::{Indices, MeshVertex, Primitive, Topology};
+
+pub const DEFAULT_CACHE_SIZE: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VertexCacheStats {
+    pub vertices_transformed: usize,
+    pub acmr: f32,
+    pub atvr: f32,
+}
+
+pub fn analyze_vertex_cache(indices: &[u32], cache_size: usize) -> VertexCacheStats {
+    let mut cache: Vec<u32> = Vec::with_capacity(cache_size);
+    let mut transformed = 0;
+    let mut head = 0;
+    for &index in indices {
+        if cache.contains(&index) {
+            continue;
+        }
+        transformed += 1;
+        if cache.len() < cache_size {
+            cache.push(index);
+        } else if cache_size > 0 {
+            cache[head] = index;
+            head = (head + 1) % cache_size;
+        }
+    }
+
+    let triangles = indices.len() / 3;
+    let mut used: Vec<u32> = indices.to_vec();
+    used.sort_unstable();
+    used.dedup();
+    VertexCacheStats {
+        vertices_transformed: transformed,
+        acmr: if triangles > 0 {
+            transformed as f32 / triangles as f32
+        } else {
+            0.0
+        },
+        atvr: if used.is_empty() {
+            0.0
+        } else {
+            transformed as f32 / used.len() as f32
+        },
+    }
+}
+
+pub fn generate_flat_normals(
+    vertices: &[MeshVertex],
+    indices: &[u32],
+) -> (Vec<MeshVertex>, Vec<u32>) {
+    let mut flat = Vec::with_capacity(indices.len());
+    for triangle in indices.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|k| vertices[triangle[k] as usize]);
+        let normal = face_normal(a.position, b.position, c.position);
+        let normal = if normal.magnitude2() > 0.0 {
+            normal.normalize()
+        } else {
+            Vector3::unit_y()
+        };
+        flat.extend([a, b, c].map(|vertex| MeshVertex { normal, ..vertex }));
+    }
+    let indices = (0..flat.len() as u32).collect();
+    (flat, indices)
+}
+
+pub fn generate_smooth_normals(vertices: &mut [MeshVertex], indices: &[u32]) {
+    let key = |v: &MeshVertex| [v.position.x, v.position.y, v.position.z].map(f32::to_bits);
+    let mut accumulated: HashMap<[u32; 3], Vector3<f32>> = HashMap::new();
+    for triangle in indices.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|k| &vertices[triangle[k] as usize]);
+        let normal = face_normal(a.position, b.position, c.position);
+        for vertex in [a, b, c] {
+            *accumulated.entry(key(vertex)).or_insert_with(Vector3::zero) += normal;
+        }
+    }
+    for vertex in vertices.iter_mut() {
+        let normal = accumulated
+            .get(&key(vertex))
+            .copied()
+            .unwrap_or_else(Vector3::zero);
+        vertex.normal = if normal.magnitude2() > 0.0 {
+            normal.normalize()
+        } else {
+            Vector3::unit_y()
+        };
+    }
+}
+
+struct MikkGeometry<'a> {
+    vertices: &'a mut [MeshVertex],
+}
+
+impl bevy_mikktspace::Geometry for MikkGeometry<'_> {
+    fn num_faces(&self) -> usize {
+        self.vertices.len() / 3
+    }
+
+    fn num_vertices_of_face(&self, _face: usize) -> usize {
+        3
+    }
+
+    fn position(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.vertices[face * 3 + vert].position.into()
+    }
+
+    fn normal(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.vertices[face * 3 + vert].normal.into()
+    }
+
+    fn tex_coord(&self, face: usize, vert: usize) -> [f32; 2] {
+        self.vertices[face * 3 + vert].uv.into()
+    }
+
+    fn set_tangent_encoded(&mut self, tangent: [f32; 4], face: usize, vert: usize) {
+        self.vertices[face * 3 + vert].tangent = Vector4::from(tangent);
+    }
+}
+
+pub fn generate_tangents(
+    vertices: &[MeshVertex],
+    indices: &[u32],
+) -> Option<(Vec<MeshVertex>, Vec<u32>)> {
+    let mut unwelded: Vec<MeshVertex> = indices.iter().map(|&i| vertices[i as usize]).collect();
+    unwelded.truncate(indices.len() / 3 * 3);
+    if !bevy_mikktspace::generate_tangents(&mut MikkGeometry {
+        vertices: &mut unwelded,
+    }) {
+        return None;
+    }
+    Some(weld(&unwelded))
+}
+
+pub fn weld(vertices: &[MeshVertex]) -> (Vec<MeshVertex>, Vec<u32>) {
+    let key = |v: &MeshVertex| {
+        let floats = [
+            v.position.x,
+            v.position.y,
+            v.position.z,
+            v.normal.x,
+            v.normal.y,
+            v.normal.z,
+            v.uv.x,
+            v.uv.y,
+            v.tangent.x,
+            v.tangent.y,
+            v.tangent.z,
+            v.tangent.w,
+            v.weights[0],
+            v.weights[1],
+            v.weights[2],
+            v.weights[3],
+        ];
+        (floats.map(f32::to_bits), v.joints)
+    };
+    let mut lookup = HashMap::new();
+    let mut welded = Vec::new();
+    let indices = vertices
+        .iter()
+        .map(|vertex| {
+            *lookup.entry(key(vertex)).or_insert_with(|| {
+                welded.push(*vertex);
+                (welded.len() - 1) as u32
+            })
+        })
+        .collect();
+    (welded, indices)
+}
+
+fn face_normal(a: Vector3<f32>, b: Vector3<f32>, c: Vector3<f32>) -> Vector3<f32> {
+    (b - a).cross(c - a)
+}
+
+pub fn strip_unused_vertices<V: Copy>(vertices: &[V], indices: &mut [u32]) -> Vec<V> {
+    let mut used = vec![false; vertices.len()];
+    for &index in indices.iter() {
+        used[index as usize] = true;
+    }
+    let mut remap = vec![u32::MAX; vertices.len()];
+    let mut stripped = Vec::new();
+    for (i, vertex) in vertices.iter().enumerate() {
+        if used[i] {
+            remap[i] = stripped.len() as u32;
+            stripped.push(*vertex);
+        }
+    }
+    for index in indices.iter_mut() {
+        *index = remap[*index as usize];
+    }
+    stripped
+}
+
+pub fn optimize_vertex_fetch<V: Copy>(vertices: &[V], indices: &mut [u32]) -> Vec<V> {
+    let mut remap = vec![u32::MAX; vertices.len()];
+    let mut reordered = Vec::new();
+    for index in indices.iter_mut() {
+        let slot = &mut remap[*index as usize];
+        if *slot == u32::MAX {
+            *slot = reordered.len() as u32;
+            reordered.push(vertices[*index as usize]);
+        }
+        *index = *slot;
+    }
+    reordered
+}
+
+const CACHE_DECAY_POWER: f32 = 1.5;
+const LAST_TRIANGLE_SCORE: f32 = 0.75;
+const VALENCE_BOOST_SCALE: f32 = 2.0;
+const VALENCE_BOOST_POWER: f32 = 0.5;
+
+fn forsyth_score(cache_position: Option<usize>, remaining: usize, cache_size: usize) -> f32 {
+    if remaining == 0 {
+        return -1.0;
+    }
+    let cache_score = match cache_position {
+        Some(position) if position < 3 => LAST_TRIANGLE_SCORE,
+        Some(position) => {
+            let scale = 1.0 / (cache_size - 3) as f32;
+            (1.0 - (position - 3) as f32 * scale).powf(CACHE_DECAY_POWER)
+        }
+        None => 0.0,
+    };
+    cache_score + VALENCE_BOOST_SCALE * (remaining as f32).powf(-VALENCE_BOOST_POWER)
+}
+
+fn triangle_adjacency(indices: &[u32], vertex_count: usize) -> Vec<Vec<usize>> {
+    let mut adjacency = vec![Vec::new(); vertex_count];
+    for (triangle, corners) in indices.chunks_exact(3).enumerate() {
+        for &vertex in corners {
+            adjacency[vertex as usize].push(triangle);
+        }
+    }
+    adjacency
+}
+
+pub fn optimize_vertex_cache_forsyth(
+    indices: &[u32],
+    vertex_count: usize,
+    cache_size: usize,
+) -> Vec<u32> {
+    let cache_size = cache_size.max(4);
+    let triangle_count = indices.len() / 3;
+    let adjacency = triangle_adjacency(indices, vertex_count);
+    let mut remaining: Vec<usize> = adjacency.iter().map(Vec::len).collect();
+    let mut vertex_score: Vec<f32> = remaining
+        .iter()
+        .map(|&r| forsyth_score(None, r, cache_size))
+        .collect();
+    let triangle = |t: usize| [0, 1, 2].map(|k| indices[t * 3 + k] as usize);
+    let mut triangle_score: Vec<f32> = (0..triangle_count)
+        .map(|t| triangle(t).iter().map(|&v| vertex_score[v]).sum())
+        .collect();
+    let mut emitted = vec![false; triangle_count];
+    let mut cache: Vec<usize> = Vec::with_capacity(cache_size + 3);
+    let mut output = Vec::with_capacity(triangle_count * 3);
+    let mut scan = 0;
+    let mut best: Option<usize> = None;
+
+    for _ in 0..triangle_count {
+        let next = match best {
+            Some(t) => t,
+            None => {
+                while emitted[scan] {
+                    scan += 1;
+                }
+                (scan..triangle_count)
+                    .filter(|&t| !emitted[t])
+                    .max_by(|&a, &b| triangle_score[a].total_cmp(&triangle_score[b]))
+                    .unwrap()
+            }
+        };
+        emitted[next] = true;
+        let corners = triangle(next);
+        output.extend(corners.map(|v| v as u32));
+
+        for &vertex in &corners {
+            remaining[vertex] -= 1;
+            if let Some(position) = cache.iter().position(|&c| c == vertex) {
+                cache.remove(position);
+            }
+        }
+        for &vertex in corners.iter().rev() {
+            cache.insert(0, vertex);
+        }
+
+        let mut touched = Vec::new();
+        for (position, &vertex) in cache.iter().enumerate() {
+            let position = (position < cache_size).then_some(position);
+            vertex_score[vertex] = forsyth_score(position, remaining[vertex], cache_size);
+            touched.push(vertex);
+        }
+        let evicted: Vec<usize> = cache.drain(cache_size.min(cache.len())..).collect();
+        for &vertex in &evicted {
+            vertex_score[vertex] = forsyth_score(None, remaining[vertex], cache_size);
+        }
+
+        best = None;
+        let mut best_score = -1.0;
+        for &vertex in &touched {
+            for &t in &adjacency[vertex] {
+                if emitted[t] {
+                    continue;
+                }
+                triangle_score[t] = triangle(t).iter().map(|&v| vertex_score[v]).sum();
+                if triangle_score[t] > best_score {
+                    best_score = triangle_score[t];
+                    best = Some(t);
+                }
+            }
+        }
+    }
+    output
+}
+
+pub fn optimize_vertex_cache_tipsify(
+    indices: &[u32],
+    vertex_count: usize,
+    cache_size: usize,
+) -> Vec<u32> {
+    let triangle_count = indices.len() / 3;
+    if triangle_count == 0 {
+        return Vec::new();
+    }
+    let adjacency = triangle_adjacency(indices, vertex_count);
+    let mut live: Vec<usize> = adjacency.iter().map(Vec::len).collect();
+    let mut timestamps = vec![0usize; vertex_count];
+    let mut dead_end: Vec<usize> = Vec::new();
+    let mut emitted = vec![false; triangle_count];
+    let mut output = Vec::with_capacity(triangle_count * 3);
+    let mut time = cache_size + 1;
+    let mut cursor = 0;
+    let mut fanning = Some(indices[0] as usize);
+
+    while let Some(vertex) = fanning {
+        let mut candidates = Vec::new();
+        for &t in &adjacency[vertex] {
+            if emitted[t] {
+                continue;
+            }
+            emitted[t] = true;
+            for k in 0..3 {
+                let v = indices[t * 3 + k] as usize;
+                output.push(v as u32);
+                dead_end.push(v);
+                candidates.push(v);
+                live[v] -= 1;
+                if time - timestamps[v] > cache_size {
+                    timestamps[v] = time;
+                    time += 1;
+                }
+            }
+        }
+
+        fanning = None;
+        let mut best_priority = None;
+        for &v in &candidates {
+            if live[v] == 0 {
+                continue;
+            }
+            let priority = if time - timestamps[v] + 2 * live[v] <= cache_size {
+                time - timestamps[v]
+            } else {
+                0
+            };
+            if best_priority.is_none_or(|best| priority > best) {
+                best_priority = Some(priority);
+                fanning = Some(v);
+            }
+        }
+        if fanning.is_none() {
+            while let Some(v) = dead_end.pop() {
+                if live[v] > 0 {
+                    fanning = Some(v);
+                    break;
+                }
+            }
+        }
+        if fanning.is_none() {
+            while cursor < vertex_count {
+                if live[cursor] > 0 {
+                    fanning = Some(cursor);
+                    break;
+                }
+                cursor += 1;
+            }
+        }
+    }
+    output
+}
+
+/// Reorders for the post-transform cache with whichever of Forsyth and Tipsify scores the
+/// lower ACMR, then compacts the vertices into first-use order.
+pub fn optimize_primitive(primitive: &mut Primitive, cache_size: usize) {
+    if primitive.topology != Topology::Triangles {
+        return;
+    }
+    let indices: Vec<u32> = primitive.indices.iter().collect();
+    let vertex_count = primitive.vertices.len();
+    let forsyth = optimize_vertex_cache_forsyth(&indices, vertex_count, cache_size);
+    let tipsify = optimize_vertex_cache_tipsify(&indices, vertex_count, cache_size);
+    let acmr = |order: &[u32]| analyze_vertex_cache(order, cache_size).acmr;
+    let mut indices = if acmr(&tipsify) < acmr(&forsyth) {
+        tipsify
+    } else {
+        forsyth
+    };
+    primitive.vertices = optimize_vertex_fetch(&primitive.vertices, &mut indices);
+    primitive.indices = Indices::from_u32(indices);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::Vector2;
+
+    const EPSILON: f32 = 1e-4;
+
+    fn assert_vector_close(actual: Vector3<f32>, expected: Vector3<f32>) {
+        assert!(
+            (actual - expected).magnitude() <= EPSILON,
+            "expected {expected:?}, got {actual:?}"
+        );
+    }
+
+    fn vertex(position: [f32; 3], uv: [f32; 2]) -> MeshVertex {
+        MeshVertex {
+            position: position.into(),
+            normal: Vector3::unit_z(),
+            uv: Vector2::from(uv),
+            ..Default::default()
+        }
+    }
+
+    /// A `size` x `size` quad grid in the xy plane with uv = xy / size.
+    fn grid(size: u32) -> (Vec<MeshVertex>, Vec<u32>) {
+        let vertices = (0..=size)
+            .flat_map(|y| (0..=size).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let (x, y) = (x as f32, y as f32);
+                vertex([x, y, 0.0], [x / size as f32, y / size as f32])
+            })
+            .collect();
+        let row = size + 1;
+        let indices = (0..size)
+            .flat_map(|y| (0..size).map(move |x| y * row + x))
+            .flat_map(|i| [i, i + 1, i + row + 1, i, i + row + 1, i + row])
+            .collect();
+        (vertices, indices)
+    }
+
+    /// Reorders triangles with a fixed stride so neighbours end up far apart.
+    fn scramble(indices: &[u32]) -> Vec<u32> {
+        let triangles = indices.len() / 3;
+        let stride = 97;
+        assert_eq!(gcd(stride, triangles), 1);
+        (0..triangles)
+            .flat_map(|t| {
+                let t = t * stride % triangles;
+                indices[t * 3..t * 3 + 3].to_vec()
+            })
+            .collect()
+    }
+
+    fn gcd(a: usize, b: usize) -> usize {
+        if b == 0 {
+            a
+        } else {
+            gcd(b, a % b)
+        }
+    }
+
+    /// Triangles as rotation-normalised corner lists, sorted, for order-independent comparison.
+    fn triangle_set(indices: &[u32], key: impl Fn(u32) -> [u32; 3]) -> Vec<[[u32; 3]; 3]> {
+        let mut triangles: Vec<[[u32; 3]; 3]> = indices
+            .chunks_exact(3)
+            .map(|t| {
+                let corners = [key(t[0]), key(t[1]), key(t[2])];
+                let first = (0..3).min_by_key(|&k| corners[k]).unwrap();
+                [0, 1, 2].map(|k| corners[(first + k) % 3])
+            })
+            .collect();
+        triangles.sort_unstable();
+        triangles
+    }
+
+    fn position_key(vertices: &[MeshVertex]) -> impl Fn(u32) -> [u32; 3] + '_ {
+        move |i| {
+            let p = vertices[i as usize].position;
+            [p.x, p.y, p.z].map(f32::to_bits)
+        }
+    }
+
+    #[test]
+    fn analyzes_a_fifo_cache() {
+        let stats = analyze_vertex_cache(&[0, 1, 2, 0, 2, 3], DEFAULT_CACHE_SIZE);
+        assert_eq!(stats.vertices_transformed, 4);
+        assert_eq!(stats.acmr, 2.0);
+        assert_eq!(stats.atvr, 1.0);
+
+        // With three entries, vertex 0 is evicted by 3 before it is reused.
+        let stats = analyze_vertex_cache(&[0, 1, 2, 1, 2, 3, 0, 1, 3], 3);
+        assert_eq!(stats.vertices_transformed, 6);
+        assert_eq!(stats.atvr, 1.5);
+
+        let empty = analyze_vertex_cache(&[], DEFAULT_CACHE_SIZE);
+        assert_eq!((empty.acmr, empty.atvr), (0.0, 0.0));
+    }
+
+    #[test]
+    fn cache_optimizers_improve_acmr_and_atvr() {
+        let (vertices, indices) = grid(24);
+        let scrambled = scramble(&indices);
+        let before = analyze_vertex_cache(&scrambled, DEFAULT_CACHE_SIZE);
+        assert!(before.acmr > 2.0, "scramble too gentle: {before:?}");
+
+        for (name, optimized) in [
+            (
+                "forsyth",
+                optimize_vertex_cache_forsyth(&scrambled, vertices.len(), DEFAULT_CACHE_SIZE),
+            ),
+            (
+                "tipsify",
+                optimize_vertex_cache_tipsify(&scrambled, vertices.len(), DEFAULT_CACHE_SIZE),
+            ),
+        ] {
+            let after = analyze_vertex_cache(&optimized, DEFAULT_CACHE_SIZE);
+            assert!(after.acmr < 0.9, "{name}: {after:?}");
+            assert!(after.atvr < 1.6, "{name}: {after:?}");
+            assert!(after.acmr < before.acmr && after.atvr < before.atvr);
+            // Only the order changes, never the triangles or their winding.
+            let identity = |i: u32| [i, 0, 0];
+            assert_eq!(
+                triangle_set(&optimized, identity),
+                triangle_set(&scrambled, identity),
+                "{name}"
+            );
+        }
+    }
+
+    #[test]
+    fn optimize_primitive_keeps_the_better_order() {
+        let (vertices, indices) = grid(16);
+        let scrambled = scramble(&indices);
+        let mut primitive = Primitive {
+            vertices: vertices.clone(),
+            indices: Indices::from_u32(scrambled.clone()),
+            topology: Topology::Triangles,
+            material: None,
+        };
+        optimize_primitive(&mut primitive, DEFAULT_CACHE_SIZE);
+
+        let optimized: Vec<u32> = primitive.indices.iter().collect();
+        let acmr = analyze_vertex_cache(&optimized, DEFAULT_CACHE_SIZE).acmr;
+        let best = [
+            optimize_vertex_cache_forsyth(&scrambled, vertices.len(), DEFAULT_CACHE_SIZE),
+            optimize_vertex_cache_tipsify(&scrambled, vertices.len(), DEFAULT_CACHE_SIZE),
+        ]
+        .iter()
+        .map(|order| analyze_vertex_cache(order, DEFAULT_CACHE_SIZE).acmr)
+        .fold(f32::MAX, f32::min);
+        assert_eq!(acmr, best);
+
+        // Vertices are renumbered in first-use order.
+        let mut next = 0;
+        for &index in &optimized {
+            assert!(index <= next);
+            next = next.max(index + 1);
+        }
+        assert_eq!(
+            triangle_set(&optimized, position_key(&primitive.vertices)),
+            triangle_set(&scrambled, position_key(&vertices))
+        );
+    }
+
+    #[test]
+    fn optimize_primitive_skips_other_topologies() {
+        let mut lines = Primitive {
+            vertices: grid(1).0,
+            indices: Indices::from_u32(vec![3, 2, 1, 0]),
+            topology: Topology::Lines,
+            material: None,
+        };
+        let before = lines.clone();
+        optimize_primitive(&mut lines, DEFAULT_CACHE_SIZE);
+        assert_eq!(lines, before);
+    }
+
+    #[test]
+    fn tangents_follow_increasing_u() {
+        let (vertices, indices) = grid(2);
+        let (vertices, indices) = generate_tangents(&vertices, &indices).unwrap();
+        assert_eq!(indices.len(), 24);
+        // A flat, consistently mapped grid welds back to its original vertices.
+        assert_eq!(vertices.len(), 9);
+        for vertex in &vertices {
+            assert_vector_close(vertex.tangent.truncate(), Vector3::unit_x());
+            assert_eq!(vertex.tangent.w, 1.0);
+        }
+    }
+
+    #[test]
+    fn tangents_handle_rotated_and_mirrored_uvs() {
+        let (vertices, indices) = grid(1);
+        let rotated: Vec<MeshVertex> = vertices
+            .iter()
+            .map(|v| MeshVertex {
+                uv: Vector2::new(v.uv.y, -v.uv.x),
+                ..*v
+            })
+            .collect();
+        let (rotated, _) = generate_tangents(&rotated, &indices).unwrap();
+        for vertex in &rotated {
+            assert_vector_close(vertex.tangent.truncate(), Vector3::unit_y());
+            assert_eq!(vertex.tangent.w, 1.0);
+        }
+
+        let mirrored: Vec<MeshVertex> = vertices
+            .iter()
+            .map(|v| MeshVertex {
+                uv: Vector2::new(-v.uv.x, v.uv.y),
+                ..*v
+            })
+            .collect();
+        let (mirrored, _) = generate_tangents(&mirrored, &indices).unwrap();
+        for vertex in &mirrored {
+            assert_vector_close(vertex.tangent.truncate(), -Vector3::unit_x());
+            assert_eq!(vertex.tangent.w, -1.0);
+        }
+    }
+
+    #[test]
+    fn tangents_are_orthogonal_to_curved_normals() {
+        // A quarter cylinder around the y axis with u running around it.
+        let segments = 8;
+        let mut vertices = Vec::new();
+        for i in 0..=segments {
+            let angle = std::f32::consts::FRAC_PI_2 * i as f32 / segments as f32;
+            for y in [0.0, 1.0] {
+                vertices.push(MeshVertex {
+                    normal: Vector3::new(angle.cos(), 0.0, angle.sin()),
+                    ..vertex(
+                        [angle.cos(), y, angle.sin()],
+                        [i as f32 / segments as f32, y],
+                    )
+                });
+            }
+        }
+        let indices: Vec<u32> = (0..segments as u32)
+            .flat_map(|i| {
+                let a = i * 2;
+                [a, a + 1, a + 3, a, a + 3, a + 2]
+            })
+            .collect();
+        let (vertices, _) = generate_tangents(&vertices, &indices).unwrap();
+        for vertex in &vertices {
+            let tangent = vertex.tangent.truncate();
+            assert!((tangent.magnitude() - 1.0).abs() < 1e-3);
+            assert!(tangent.dot(vertex.normal).abs() < 1e-3);
+            // Increasing u moves around the cylinder, away from the x axis.
+            assert!(tangent.dot(Vector3::new(-vertex.normal.z, 0.0, vertex.normal.x)) > 0.9);
+        }
+    }
+
+    #[test]
+    fn flat_normals_split_every_corner() {
+        // Two faces of a roof meeting along the x axis.
+        let vertices = [
+            vertex([0.0, 0.0, 0.0], [0.0, 0.0]),
+            vertex([1.0, 0.0, 0.0], [0.0, 0.0]),
+            vertex([0.0, -1.0, 1.0], [0.0, 0.0]),
+            vertex([0.0, 1.0, 1.0], [0.0, 0.0]),
+        ];
+        let indices = [0, 2, 1, 0, 1, 3];
+        let (flat, flat_indices) = generate_flat_normals(&vertices, &indices);
+        assert_eq!(flat.len(), 6);
+        assert_eq!(flat_indices, [0, 1, 2, 3, 4, 5]);
+        let half = std::f32::consts::FRAC_1_SQRT_2;
+        for vertex in &flat[..3] {
+            assert_vector_close(vertex.normal, Vector3::new(0.0, half, half));
+        }
+        for vertex in &flat[3..] {
+            assert_vector_close(vertex.normal, Vector3::new(0.0, -half, half));
+        }
+
+        let (degenerate, _) = generate_flat_normals(&vertices, &[0, 0, 1]);
+        assert_vector_close(degenerate[0].normal, Vector3::unit_y());
+    }
+
+    #[test]
+    fn smooth_normals_average_across_seams() {
+        let mut vertices = vec![
+            vertex([0.0, 0.0, 0.0], [0.0, 0.0]),
+            vertex([1.0, 0.0, 0.0], [0.0, 0.0]),
+            vertex([0.0, -1.0, 1.0], [0.0, 0.0]),
+            // The second face uses its own copies of the ridge vertices.
+            vertex([0.0, 0.0, 0.0], [1.0, 0.0]),
+            vertex([1.0, 0.0, 0.0], [1.0, 0.0]),
+            vertex([0.0, 1.0, 1.0], [0.0, 0.0]),
+        ];
+        generate_smooth_normals(&mut vertices, &[0, 2, 1, 3, 4, 5]);
+        for ridge in [0, 1, 3, 4] {
+            assert_vector_close(vertices[ridge].normal, Vector3::unit_z());
+        }
+        let half = std::f32::consts::FRAC_1_SQRT_2;
+        assert_vector_close(vertices[2].normal, Vector3::new(0.0, half, half));
+        assert_vector_close(vertices[5].normal, Vector3::new(0.0, -half, half));
+    }
+
+    #[test]
+    fn weld_merges_identical_vertices() {
+        let a = vertex([0.0, 0.0, 0.0], [0.0, 0.0]);
+        let b = vertex([1.0, 0.0, 0.0], [0.0, 0.0]);
+        let seam = MeshVertex {
+            uv: Vector2::new(1.0, 0.0),
+            ..a
+        };
+        let (welded, indices) = weld(&[a, b, a, seam, b]);
+        assert_eq!(welded, [a, b, seam]);
+        assert_eq!(indices, [0, 1, 0, 2, 1]);
+    }
+
+    #[test]
+    fn strip_and_fetch_drop_unused_vertices() {
+        let vertices = ['a', 'b', 'c', 'd', 'e'];
+        let mut indices = vec![4, 1, 3, 3, 1, 4];
+        assert_eq!(
+            strip_unused_vertices(&vertices, &mut indices),
+            ['b', 'd', 'e']
+        );
+        assert_eq!(indices, [2, 0, 1, 1, 0, 2]);
+
+        let mut indices = vec![4, 1, 3, 3, 1, 4];
+        assert_eq!(
+            optimize_vertex_fetch(&vertices, &mut indices),
+            ['e', 'b', 'd']
+        );
+        assert_eq!(indices, [0, 1, 2, 2, 1, 0]);
+    }
+}
@@ -0,0 +1,422 @@
+use std::ffi::c_void;
+use std::path::Path;
+
+use cgmath::{EuclideanSpace, Matrix4, SquareMatrix, Transform, Vector2, Vector3, Vector4};
+use windows::{
+    core::*, Win32::Foundation::E_FAIL, Win32::Graphics::Direct3D::Dxc::*,
+    Win32::Graphics::Direct3D12::*, Win32::Graphics::Dxgi::Common::*,
+};
+
+use crate::camera::{Camera, Frustum};
+use crate::color::OutputConstants;
+use crate::constant_buffer::ConstantBufferLayout;
+use crate::depth_buffer::DepthState;
+use crate::mesh_buffer::create_upload_buffer;
+use crate::meshlet::MeshletMesh;
+use crate::model::MeshVertex;
+use crate::sampler;
+
+pub const AMPLIFICATION_GROUP_SIZE: u32 = 32;
+
+pub const ROOT_MESHLET: u32 = 3;
+pub const ROOT_BUFFERS: u32 = 4;
+
+pub fn mesh_shader_supported(device: &ID3D12Device) -> bool {
+    let mut options = D3D12_FEATURE_DATA_D3D12_OPTIONS7::default();
+    let supported = unsafe {
+        device.CheckFeatureSupport(
+            D3D12_FEATURE_D3D12_OPTIONS7,
+            &mut options as *mut _ as *mut c_void,
+            std::mem::size_of_val(&options) as u32,
+        )
+    };
+    supported.is_ok() && options.MeshShaderTier != D3D12_MESH_SHADER_TIER_NOT_SUPPORTED
+}
+
+pub fn compile_shader(path: &Path, entry: &str, target: &str) -> Result<Vec<u8>> {
+    let source = std::fs::read(path)
+        .map_err(|e| Error::new(E_FAIL, format!("{}: {}", path.display(), e).into()))?;
+    let buffer = DxcBuffer {
+        Ptr: source.as_ptr() as *const c_void,
+        Size: source.len(),
+        Encoding: DXC_CP_UTF8.0,
+    };
+    let arguments: Vec<HSTRING> = ["-E", entry, "-T", target, "-Od", "-Zi", "-Qembed_debug"]
+        .iter()
+        .map(|argument| HSTRING::from(*argument))
+        .collect();
+    let arguments: Vec<PCWSTR> = arguments
+        .iter()
+        .map(|argument| PCWSTR(argument.as_ptr()))
+        .collect();
+
+    let compiler: IDxcCompiler3 = unsafe { DxcCreateInstance(&CLSID_DxcCompiler) }?;
+    let result: IDxcResult =
+        unsafe { compiler.Compile(&buffer, Some(&arguments), None::<&IDxcIncludeHandler>) }?;
+
+    let status = unsafe { result.GetStatus() }?;
+    if status.is_err() {
+        if let Ok(errors) = unsafe { result.GetErrorBuffer() } {
+            let errors = unsafe {
+                std::slice::from_raw_parts(
+                    errors.GetBufferPointer() as *const u8,
+                    errors.GetBufferSize(),
+                )
+            };
+            println!("{}", String::from_utf8_lossy(errors));
+        }
+        return Err(status.into());
+    }
+
+    let blob = unsafe { result.GetResult() }?;
+    let bytes = unsafe {
+        std::slice::from_raw_parts(blob.GetBufferPointer() as *const u8, blob.GetBufferSize())
+    };
+    Ok(bytes.to_vec())
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct MeshletVertex {
+    position: Vector3<f32>,
+    normal: Vector3<f32>,
+    uv: Vector2<f32>,
+}
+
+#[repr(C)]
+#[derive(ConstantBufferLayout)]
+pub struct MeshletConstants {
+    planes: [Vector4<f32>; 6],
+    camera_position: Vector3<f32>,
+    meshlet_count: u32,
+}
+
+impl MeshletConstants {
+    pub fn new(camera: &Camera, world: &Matrix4<f32>, meshlet_count: u32) -> Self {
+        let frustum = Frustum::from_matrix(&(camera.view_projection() * world), camera.reverse_z);
+        let inverse = world.invert().unwrap_or(Matrix4::identity());
+        MeshletConstants {
+            planes: frustum.planes().map(|p| p.normal.extend(p.d)),
+            camera_position: inverse.transform_point(camera.eye).to_vec(),
+            meshlet_count,
+        }
+    }
+}
+
+pub struct MeshletBuffers {
+    vertices: ID3D12Resource,
+    meshlets: ID3D12Resource,
+    bounds: ID3D12Resource,
+    vertex_indices: ID3D12Resource,
+    primitive_indices: ID3D12Resource,
+    meshlet_count: u32,
+}
+
+impl MeshletBuffers {
+    pub fn new(device: &ID3D12Device, vertices: &[MeshVertex], mesh: &MeshletMesh) -> Result<Self> {
+        let vertices: Vec<MeshletVertex> = vertices
+            .iter()
+            .map(|v| MeshletVertex {
+                position: v.position,
+                normal: v.normal,
+                uv: v.uv,
+            })
+            .collect();
+        Ok(MeshletBuffers {
+            vertices: create_upload_buffer(device, as_bytes(&vertices))?,
+            meshlets: create_upload_buffer(device, as_bytes(&mesh.meshlets))?,
+            bounds: create_upload_buffer(device, as_bytes(&mesh.bounds))?,
+            vertex_indices: create_upload_buffer(device, as_bytes(&mesh.vertex_indices))?,
+            primitive_indices: create_upload_buffer(device, as_bytes(&mesh.primitive_indices))?,
+            meshlet_count: mesh.meshlets.len() as u32,
+        })
+    }
+
+    pub fn meshlet_count(&self) -> u32 {
+        self.meshlet_count
+    }
+
+    pub fn draw(&self, command_list: &ID3D12GraphicsCommandList6, meshlet_constants: u64) {
+        if self.meshlet_count == 0 {
+            return;
+        }
+        unsafe { command_list.SetGraphicsRootConstantBufferView(ROOT_MESHLET, meshlet_constants) };
+        let buffers = [
+            &self.vertices,
+            &self.meshlets,
+            &self.bounds,
+            &self.vertex_indices,
+            &self.primitive_indices,
+        ];
+        for (i, buffer) in buffers.into_iter().enumerate() {
+            unsafe {
+                command_list.SetGraphicsRootShaderResourceView(
+                    ROOT_BUFFERS + i as u32,
+                    buffer.GetGPUVirtualAddress(),
+                )
+            };
+        }
+        let groups = self.meshlet_count.div_ceil(AMPLIFICATION_GROUP_SIZE);
+        unsafe { command_list.DispatchMesh(groups, 1, 1) };
+    }
+}
+
+fn as_bytes<T>(data: &[T]) -> &[u8] {
+    unsafe { std::slice::from_raw_parts(data.as_ptr() as *const u8, std::mem::size_of_val(data)) }
+}
+
+#[repr(C, align(8))]
+struct Subobject<T> {
+    kind: D3D12_PIPELINE_STATE_SUBOBJECT_TYPE,
+    desc: T,
+}
+
+impl<T> Subobject<T> {
+    fn new(kind: D3D12_PIPELINE_STATE_SUBOBJECT_TYPE, desc: T) -> Self {
+        Subobject { kind, desc }
+    }
+}
+
+#[repr(C)]
+struct MeshPipelineStream {
+    root_signature: Subobject<Option<ID3D12RootSignature>>,
+    amplification: Subobject<D3D12_SHADER_BYTECODE>,
+    mesh: Subobject<D3D12_SHADER_BYTECODE>,
+    pixel: Subobject<D3D12_SHADER_BYTECODE>,
+    rasterizer: Subobject<D3D12_RASTERIZER_DESC>,
+    blend: Subobject<D3D12_BLEND_DESC>,
+    depth_stencil: Subobject<D3D12_DEPTH_STENCIL_DESC>,
+    depth_format: Subobject<DXGI_FORMAT>,
+    render_targets: Subobject<D3D12_RT_FORMAT_ARRAY>,
+    sample_desc: Subobject<DXGI_SAMPLE_DESC>,
+    sample_mask: Subobject<u32>,
+}
+
+pub struct MeshShaderPipeline {
+    amplification_shader: Vec<u8>,
+    mesh_shader: Vec<u8>,
+    pixel_shader: Vec<u8>,
+    root_signature: ID3D12RootSignature,
+    pipeline_state: ID3D12PipelineState,
+}
+
+impl MeshShaderPipeline {
+    pub fn new(
+        device: &ID3D12Device,
+        shader_path: &Path,
+        pixel_shader_path: &Path,
+        rtv_format: DXGI_FORMAT,
+        dsv_format: DXGI_FORMAT,
+        sample_desc: DXGI_SAMPLE_DESC,
+        reverse_z: bool,
+    ) -> Result<Self> {
+        let amplification_shader = compile_shader(shader_path, "MeshletAS", "as_6_5")?;
+        let mesh_shader = compile_shader(shader_path, "MeshletMS", "ms_6_5")?;
+        let pixel_shader = compile_shader(pixel_shader_path, "BasicPS", "ps_6_5")?;
+        let root_signature = create_root_signature(device)?;
+        let pipeline_state = create_pipeline_state(
+            device,
+            &root_signature,
+            [&amplification_shader, &mesh_shader, &pixel_shader],
+            rtv_format,
+            dsv_format,
+            sample_desc,
+            reverse_z,
+        )?;
+        Ok(MeshShaderPipeline {
+            amplification_shader,
+            mesh_shader,
+            pixel_shader,
+            root_signature,
+            pipeline_state,
+        })
+    }
+
+    pub fn rebuild(
+        &mut self,
+        device: &ID3D12Device,
+        rtv_format: DXGI_FORMAT,
+        dsv_format: DXGI_FORMAT,
+        sample_desc: DXGI_SAMPLE_DESC,
+        reverse_z: bool,
+    ) -> Result<()> {
+        self.pipeline_state = create_pipeline_state(
+            device,
+            &self.root_signature,
+            [
+                &self.amplification_shader,
+                &self.mesh_shader,
+                &self.pixel_shader,
+            ],
+            rtv_format,
+            dsv_format,
+            sample_desc,
+            reverse_z,
+        )?;
+        Ok(())
+    }
+
+    pub fn root_signature(&self) -> &ID3D12RootSignature {
+        &self.root_signature
+    }
+
+    pub fn pipeline_state(&self) -> &ID3D12PipelineState {
+        &self.pipeline_state
+    }
+}
+
+fn create_root_signature(device: &ID3D12Device) -> Result<ID3D12RootSignature> {
+    let descriptor_ranges = [D3D12_DESCRIPTOR_RANGE {
+        NumDescriptors: 1,
+        RangeType: D3D12_DESCRIPTOR_RANGE_TYPE_SRV,
+        BaseShaderRegister: 0,
+        OffsetInDescriptorsFromTableStart: D3D12_DESCRIPTOR_RANGE_OFFSET_APPEND,
+        ..Default::default()
+    }];
+    let cbv = |register| D3D12_ROOT_PARAMETER {
+        ParameterType: D3D12_ROOT_PARAMETER_TYPE_CBV,
+        ShaderVisibility: D3D12_SHADER_VISIBILITY_ALL,
+        Anonymous: D3D12_ROOT_PARAMETER_0 {
+            Descriptor: D3D12_ROOT_DESCRIPTOR {
+                ShaderRegister: register,
+                RegisterSpace: 0,
+            },
+        },
+    };
+    let srv = |register| D3D12_ROOT_PARAMETER {
+        ParameterType: D3D12_ROOT_PARAMETER_TYPE_SRV,
+        ..cbv(register)
+    };
+    let root_parameters = [
+        D3D12_ROOT_PARAMETER {
+            ParameterType: D3D12_ROOT_PARAMETER_TYPE_DESCRIPTOR_TABLE,
+            ShaderVisibility: D3D12_SHADER_VISIBILITY_PIXEL,
+            Anonymous: D3D12_ROOT_PARAMETER_0 {
+                DescriptorTable: D3D12_ROOT_DESCRIPTOR_TABLE {
+                    NumDescriptorRanges: descriptor_ranges.len() as u32,
+                    pDescriptorRanges: descriptor_ranges.as_ptr(),
+                },
+            },
+        },
+        cbv(0),
+        D3D12_ROOT_PARAMETER {
+            ParameterType: D3D12_ROOT_PARAMETER_TYPE_32BIT_CONSTANTS,
+            ShaderVisibility: D3D12_SHADER_VISIBILITY_PIXEL,
+            Anonymous: D3D12_ROOT_PARAMETER_0 {
+                Constants: D3D12_ROOT_CONSTANTS {
+                    ShaderRegister: 1,
+                    RegisterSpace: 0,
+                    Num32BitValues: OutputConstants::NUM_32BIT_VALUES,
+                },
+            },
+        },
+        cbv(3),
+        srv(1),
+        srv(2),
+        srv(3),
+        srv(4),
+        srv(5),
+    ];
+    let static_samplers = sampler::static_samplers();
+    let root_signature_desc = D3D12_ROOT_SIGNATURE_DESC {
+        Flags: D3D12_ROOT_SIGNATURE_FLAG_NONE,
+        pParameters: root_parameters.as_ptr(),
+        NumParameters: root_parameters.len() as u32,
+        pStaticSamplers: static_samplers.as_ptr(),
+        NumStaticSamplers: static_samplers.len() as u32,
+    };
+
+    let mut blob = None;
+    unsafe {
+        D3D12SerializeRootSignature(
+            &root_signature_desc,
+            D3D_ROOT_SIGNATURE_VERSION_1_0,
+            &mut blob,
+            None,
+        )
+    }?;
+    let blob = blob.unwrap();
+    unsafe {
+        device.CreateRootSignature(
+            0,
+            std::slice::from_raw_parts(blob.GetBufferPointer() as *const u8, blob.GetBufferSize()),
+        )
+    }
+}
+
+fn create_pipeline_state(
+    device: &ID3D12Device,
+    root_signature: &ID3D12RootSignature,
+    [amplification, mesh, pixel]: [&[u8]; 3],
+    rtv_format: DXGI_FORMAT,
+    dsv_format: DXGI_FORMAT,
+    sample_desc: DXGI_SAMPLE_DESC,
+    reverse_z: bool,
+) -> Result<ID3D12PipelineState> {
+    let bytecode = |code: &[u8]| D3D12_SHADER_BYTECODE {
+        pShaderBytecode: code.as_ptr() as *const c_void,
+        BytecodeLength: code.len(),
+    };
+    let mut render_target_blend_descs = [D3D12_RENDER_TARGET_BLEND_DESC::default(); 8];
+    render_target_blend_descs[0].RenderTargetWriteMask = D3D12_COLOR_WRITE_ENABLE_ALL.0 as u8;
+    let mut render_targets = D3D12_RT_FORMAT_ARRAY {
+        NumRenderTargets: 1,
+        ..Default::default()
+    };
+    render_targets.RTFormats[0] = rtv_format;
+
+    let stream = MeshPipelineStream {
+        root_signature: Subobject::new(
+            D3D12_PIPELINE_STATE_SUBOBJECT_TYPE_ROOT_SIGNATURE,
+            Some(root_signature.clone()),
+        ),
+        amplification: Subobject::new(
+            D3D12_PIPELINE_STATE_SUBOBJECT_TYPE_AS,
+            bytecode(amplification),
+        ),
+        mesh: Subobject::new(D3D12_PIPELINE_STATE_SUBOBJECT_TYPE_MS, bytecode(mesh)),
+        pixel: Subobject::new(D3D12_PIPELINE_STATE_SUBOBJECT_TYPE_PS, bytecode(pixel)),
+        rasterizer: Subobject::new(
+            D3D12_PIPELINE_STATE_SUBOBJECT_TYPE_RASTERIZER,
+            D3D12_RASTERIZER_DESC {
+                MultisampleEnable: (sample_desc.Count > 1).into(),
+                CullMode: D3D12_CULL_MODE_NONE,
+                FillMode: D3D12_FILL_MODE_SOLID,
+                DepthClipEnable: true.into(),
+                ..Default::default()
+            },
+        ),
+        blend: Subobject::new(
+            D3D12_PIPELINE_STATE_SUBOBJECT_TYPE_BLEND,
+            D3D12_BLEND_DESC {
+                AlphaToCoverageEnable: false.into(),
+                IndependentBlendEnable: false.into(),
+                RenderTarget: render_target_blend_descs,
+            },
+        ),
+        depth_stencil: Subobject::new(
+            D3D12_PIPELINE_STATE_SUBOBJECT_TYPE_DEPTH_STENCIL,
+            DepthState::read_write(reverse_z).desc(),
+        ),
+        depth_format: Subobject::new(
+            D3D12_PIPELINE_STATE_SUBOBJECT_TYPE_DEPTH_STENCIL_FORMAT,
+            dsv_format,
+        ),
+        render_targets: Subobject::new(
+            D3D12_PIPELINE_STATE_SUBOBJECT_TYPE_RENDER_TARGET_FORMATS,
+            render_targets,
+        ),
+        sample_desc: Subobject::new(D3D12_PIPELINE_STATE_SUBOBJECT_TYPE_SAMPLE_DESC, sample_desc),
+        sample_mask: Subobject::new(
+            D3D12_PIPELINE_STATE_SUBOBJECT_TYPE_SAMPLE_MASK,
+            D3D12_DEFAULT_SAMPLE_MASK,
+        ),
+    };
+    let stream_desc = D3D12_PIPELINE_STATE_STREAM_DESC {
+        SizeInBytes: std::mem::size_of_val(&stream),
+        pPipelineStateSubobjectStream: &stream as *const _ as *mut c_void,
+    };
+
+    let device: ID3D12Device2 = device.cast()?;
+    unsafe { device.CreatePipelineState(&stream_desc) }
+}
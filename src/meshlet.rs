@@ -0,0 +1,437 @@
+use cgmath::{InnerSpace, Vector3};
+
+use crate::model::MeshVertex;
+
+pub const MAX_MESHLET_VERTICES: usize = 64;
+pub const MAX_MESHLET_TRIANGLES: usize = 124;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Meshlet {
+    pub vertex_offset: u32,
+    pub vertex_count: u32,
+    pub triangle_offset: u32,
+    pub triangle_count: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MeshletBounds {
+    pub center: Vector3<f32>,
+    pub radius: f32,
+    pub cone_axis: Vector3<f32>,
+    pub cone_cutoff: f32,
+}
+
+impl MeshletBounds {
+    pub fn has_cone(&self) -> bool {
+        self.cone_cutoff < 1.0
+    }
+
+    pub fn is_backfacing(&self, camera_position: Vector3<f32>) -> bool {
+        if !self.has_cone() {
+            return false;
+        }
+        let view = self.center - camera_position;
+        view.dot(self.cone_axis) >= self.cone_cutoff * view.magnitude() + self.radius
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MeshletMesh {
+    pub meshlets: Vec<Meshlet>,
+    pub bounds: Vec<MeshletBounds>,
+    pub vertex_indices: Vec<u32>,
+    pub primitive_indices: Vec<u32>,
+}
+
+pub fn pack_triangle(a: u32, b: u32, c: u32) -> u32 {
+    a | (b << 10) | (c << 20)
+}
+
+pub fn unpack_triangle(packed: u32) -> [u32; 3] {
+    [
+        packed & 0x3FF,
+        (packed >> 10) & 0x3FF,
+        (packed >> 20) & 0x3FF,
+    ]
+}
+
+impl MeshletMesh {
+    pub fn build(
+        vertices: &[MeshVertex],
+        indices: &[u32],
+        max_vertices: usize,
+        max_triangles: usize,
+    ) -> Self {
+        let max_vertices = max_vertices.clamp(3, 1024);
+        let max_triangles = max_triangles.max(1);
+        let mut mesh = MeshletMesh::default();
+        let mut local: Vec<Option<(usize, u32)>> = vec![None; vertices.len()];
+        let mut current = Meshlet::default();
+
+        for triangle in indices.chunks_exact(3) {
+            let meshlet = mesh.meshlets.len();
+            let new_vertices = triangle
+                .iter()
+                .enumerate()
+                .filter(|&(k, &v)| {
+                    local[v as usize].is_none_or(|(m, _)| m != meshlet)
+                        && !triangle[..k].contains(&v)
+                })
+                .count();
+            if current.vertex_count as usize + new_vertices > max_vertices
+                || current.triangle_count as usize + 1 > max_triangles
+            {
+                mesh.finish(vertices, current);
+                current = Meshlet {
+                    vertex_offset: mesh.vertex_indices.len() as u32,
+                    triangle_offset: mesh.primitive_indices.len() as u32,
+                    ..Default::default()
+                };
+            }
+
+            let meshlet = mesh.meshlets.len();
+            let corners = [0, 1, 2].map(|k| {
+                let v = triangle[k];
+                match local[v as usize] {
+                    Some((m, slot)) if m == meshlet => slot,
+                    _ => {
+                        let slot = current.vertex_count;
+                        local[v as usize] = Some((meshlet, slot));
+                        mesh.vertex_indices.push(v);
+                        current.vertex_count += 1;
+                        slot
+                    }
+                }
+            });
+            mesh.primitive_indices
+                .push(pack_triangle(corners[0], corners[1], corners[2]));
+            current.triangle_count += 1;
+        }
+        if current.triangle_count > 0 {
+            mesh.finish(vertices, current);
+        }
+        mesh
+    }
+
+    fn finish(&mut self, vertices: &[MeshVertex], meshlet: Meshlet) {
+        self.meshlets.push(meshlet);
+        let bounds = compute_bounds(vertices, self, self.meshlets.len() - 1);
+        self.bounds.push(bounds);
+    }
+
+    pub fn triangles(&self, meshlet: usize) -> impl Iterator<Item = [u32; 3]> + '_ {
+        let m = self.meshlets[meshlet];
+        let local = &self.vertex_indices[m.vertex_offset as usize..];
+        self.primitive_indices
+            [m.triangle_offset as usize..(m.triangle_offset + m.triangle_count) as usize]
+            .iter()
+            .map(move |&packed| unpack_triangle(packed).map(|k| local[k as usize]))
+    }
+
+    pub fn visible(&self, camera_position: Vector3<f32>) -> Vec<usize> {
+        (0..self.meshlets.len())
+            .filter(|&i| !self.bounds[i].is_backfacing(camera_position))
+            .collect()
+    }
+}
+
+pub fn compute_bounds(
+    vertices: &[MeshVertex],
+    mesh: &MeshletMesh,
+    meshlet: usize,
+) -> MeshletBounds {
+    let triangles: Vec<[Vector3<f32>; 3]> = mesh
+        .triangles(meshlet)
+        .map(|t| t.map(|v| vertices[v as usize].position))
+        .collect();
+
+    let points = triangles.iter().flatten();
+    let (min, max) = points.clone().fold(
+        (
+            Vector3::new(f32::MAX, f32::MAX, f32::MAX),
+            Vector3::new(f32::MIN, f32::MIN, f32::MIN),
+        ),
+        |(min, max), p| {
+            (
+                Vector3::new(min.x.min(p.x), min.y.min(p.y), min.z.min(p.z)),
+                Vector3::new(max.x.max(p.x), max.y.max(p.y), max.z.max(p.z)),
+            )
+        },
+    );
+    let center = (min + max) * 0.5;
+    let radius = points.map(|p| (p - center).magnitude()).fold(0.0, f32::max);
+
+    let normals: Vec<Vector3<f32>> = triangles
+        .iter()
+        .map(|[a, b, c]| (b - a).cross(c - a))
+        .filter(|n| n.magnitude2() > 0.0)
+        .map(|n| n.normalize())
+        .collect();
+    let sum = normals
+        .iter()
+        .fold(Vector3::new(0.0, 0.0, 0.0), |s, n| s + n);
+    let no_cone = MeshletBounds {
+        center,
+        radius,
+        cone_axis: Vector3::new(0.0, 0.0, 0.0),
+        cone_cutoff: 1.0,
+    };
+    if sum.magnitude2() < 1e-12 {
+        return no_cone;
+    }
+    let axis = sum.normalize();
+    let min_dot = normals.iter().map(|n| n.dot(axis)).fold(1.0, f32::min);
+    if min_dot <= 0.0 {
+        return MeshletBounds {
+            cone_axis: axis,
+            ..no_cone
+        };
+    }
+    MeshletBounds {
+        center,
+        radius,
+        cone_axis: axis,
+        cone_cutoff: (1.0 - min_dot * min_dot).sqrt(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::Zero;
+
+    const EPSILON: f32 = 1e-4;
+
+    fn assert_vector_close(actual: Vector3<f32>, expected: Vector3<f32>) {
+        assert!(
+            (actual - expected).magnitude() <= EPSILON,
+            "expected {expected:?}, got {actual:?}"
+        );
+    }
+
+    /// Appends a triangle at `origin` whose face normal is `normal`.
+    fn push_facing(
+        vertices: &mut Vec<MeshVertex>,
+        indices: &mut Vec<u32>,
+        origin: Vector3<f32>,
+        normal: Vector3<f32>,
+    ) {
+        let normal = normal.normalize();
+        let helper = if normal.x.abs() < 0.9 {
+            Vector3::unit_x()
+        } else {
+            Vector3::unit_y()
+        };
+        let u = normal.cross(helper).normalize();
+        let v = normal.cross(u);
+        let first = vertices.len() as u32;
+        for position in [origin, origin + u, origin + v] {
+            vertices.push(MeshVertex {
+                position,
+                ..Default::default()
+            });
+        }
+        indices.extend([first, first + 1, first + 2]);
+    }
+
+    fn single(normals: &[Vector3<f32>]) -> (Vec<MeshVertex>, MeshletMesh) {
+        let (mut vertices, mut indices) = (Vec::new(), Vec::new());
+        for (i, &normal) in normals.iter().enumerate() {
+            push_facing(
+                &mut vertices,
+                &mut indices,
+                Vector3::new(i as f32, 0.0, 0.0),
+                normal,
+            );
+        }
+        let mesh = MeshletMesh::build(
+            &vertices,
+            &indices,
+            MAX_MESHLET_VERTICES,
+            MAX_MESHLET_TRIANGLES,
+        );
+        assert_eq!(mesh.meshlets.len(), 1);
+        (vertices, mesh)
+    }
+
+    /// A `size` x `size` grid in the xy plane facing +z.
+    fn grid(size: u32) -> (Vec<MeshVertex>, Vec<u32>) {
+        let vertices = (0..=size)
+            .flat_map(|y| (0..=size).map(move |x| (x as f32, y as f32)))
+            .map(|(x, y)| MeshVertex {
+                position: Vector3::new(x, y, 0.0),
+                ..Default::default()
+            })
+            .collect();
+        let row = size + 1;
+        let indices = (0..size)
+            .flat_map(|y| (0..size).map(move |x| y * row + x))
+            .flat_map(|i| [i, i + 1, i + row + 1, i, i + row + 1, i + row])
+            .collect();
+        (vertices, indices)
+    }
+
+    #[test]
+    fn packs_ten_bit_triangle_corners() {
+        for corners in [[0, 1, 2], [1023, 0, 512], [63, 1022, 1023]] {
+            let packed = pack_triangle(corners[0], corners[1], corners[2]);
+            assert_eq!(unpack_triangle(packed), corners);
+        }
+    }
+
+    #[test]
+    fn meshlets_respect_limits_and_reproduce_the_index_stream() {
+        let (vertices, indices) = grid(20);
+        for (max_vertices, max_triangles) in [(64, 124), (16, 16), (3, 124), (64, 1)] {
+            let mesh = MeshletMesh::build(&vertices, &indices, max_vertices, max_triangles);
+            let mut vertex_offset = 0;
+            let mut triangle_offset = 0;
+            for (i, meshlet) in mesh.meshlets.iter().enumerate() {
+                assert!(meshlet.vertex_count as usize <= max_vertices);
+                assert!(meshlet.triangle_count as usize <= max_triangles);
+                assert!(meshlet.triangle_count > 0);
+                assert_eq!(meshlet.vertex_offset, vertex_offset);
+                assert_eq!(meshlet.triangle_offset, triangle_offset);
+                vertex_offset += meshlet.vertex_count;
+                triangle_offset += meshlet.triangle_count;
+
+                let range = meshlet.vertex_offset as usize
+                    ..(meshlet.vertex_offset + meshlet.vertex_count) as usize;
+                let mut local = mesh.vertex_indices[range].to_vec();
+                local.sort_unstable();
+                local.dedup();
+                assert_eq!(local.len(), meshlet.vertex_count as usize, "meshlet {i}");
+            }
+            assert_eq!(vertex_offset as usize, mesh.vertex_indices.len());
+            assert_eq!(triangle_offset as usize, mesh.primitive_indices.len());
+            assert_eq!(mesh.bounds.len(), mesh.meshlets.len());
+
+            let rebuilt: Vec<u32> = (0..mesh.meshlets.len())
+                .flat_map(|m| mesh.triangles(m).flatten().collect::<Vec<_>>())
+                .collect();
+            assert_eq!(rebuilt, indices, "limits {max_vertices}/{max_triangles}");
+        }
+    }
+
+    #[test]
+    fn bounds_enclose_every_vertex() {
+        let (vertices, indices) = grid(12);
+        let mesh = MeshletMesh::build(&vertices, &indices, 32, 32);
+        assert!(mesh.meshlets.len() > 1);
+        for (m, bounds) in mesh.bounds.iter().enumerate() {
+            for v in mesh.triangles(m).flatten() {
+                let distance = (vertices[v as usize].position - bounds.center).magnitude();
+                assert!(distance <= bounds.radius + EPSILON);
+            }
+            // Flat meshlets get the tightest possible cone.
+            assert_vector_close(bounds.cone_axis, Vector3::unit_z());
+            assert!(bounds.cone_cutoff.abs() < EPSILON);
+        }
+    }
+
+    #[test]
+    fn single_triangle_bounds() {
+        let (_, mesh) = single(&[Vector3::unit_z()]);
+        let bounds = mesh.bounds[0];
+        // Corners at the origin, +y and -x.
+        assert_vector_close(bounds.center, Vector3::new(-0.5, 0.5, 0.0));
+        assert!((bounds.radius - std::f32::consts::FRAC_1_SQRT_2).abs() < EPSILON);
+        assert_vector_close(bounds.cone_axis, Vector3::unit_z());
+        assert!(bounds.has_cone());
+    }
+
+    #[test]
+    fn cone_spans_the_normals() {
+        let (_, mesh) = single(&[Vector3::unit_z(), Vector3::unit_x()]);
+        let bounds = mesh.bounds[0];
+        let half = std::f32::consts::FRAC_1_SQRT_2;
+        assert_vector_close(bounds.cone_axis, Vector3::new(half, 0.0, half));
+        assert!((bounds.cone_cutoff - half).abs() < EPSILON);
+    }
+
+    #[test]
+    fn wide_or_cancelling_normals_have_no_cone() {
+        let (_, spread) = single(&[Vector3::unit_x(), Vector3::unit_y(), -Vector3::unit_x()]);
+        assert!(!spread.bounds[0].has_cone());
+        assert_vector_close(spread.bounds[0].cone_axis, Vector3::unit_y());
+
+        let (_, opposed) = single(&[Vector3::unit_z(), -Vector3::unit_z()]);
+        let bounds = opposed.bounds[0];
+        assert!(!bounds.has_cone());
+        assert_eq!(bounds.cone_axis, Vector3::new(0.0, 0.0, 0.0));
+        for camera in [Vector3::unit_z(), -Vector3::unit_z()] {
+            assert!(!bounds.is_backfacing(camera * 100.0));
+        }
+    }
+
+    #[test]
+    fn degenerate_triangles_do_not_affect_the_cone() {
+        let vertices: Vec<MeshVertex> = [
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+            Vector3::new(2.0, 0.0, 0.0),
+        ]
+        .into_iter()
+        .map(|position| MeshVertex {
+            position,
+            ..Default::default()
+        })
+        .collect();
+        let mesh = MeshletMesh::build(&vertices, &[0, 1, 2, 0, 1, 3], 64, 124);
+        assert_vector_close(mesh.bounds[0].cone_axis, Vector3::unit_z());
+        assert!(mesh.bounds[0].cone_cutoff.abs() < EPSILON);
+
+        let collinear = MeshletMesh::build(&vertices, &[0, 1, 3], 64, 124);
+        assert!(!collinear.bounds[0].has_cone());
+    }
+
+    #[test]
+    fn backfacing_is_conservative() {
+        let (_, mesh) = single(&[Vector3::unit_z()]);
+        let bounds = mesh.bounds[0];
+        let center = bounds.center;
+        // In front of the face.
+        assert!(!bounds.is_backfacing(center + Vector3::unit_z() * 10.0));
+        // Directly behind it.
+        assert!(bounds.is_backfacing(center - Vector3::unit_z() * 10.0));
+        // Behind the plane but close enough that some point of the sphere could still be seen.
+        assert!(!bounds.is_backfacing(center - Vector3::unit_z() * 0.5));
+        // Edge-on.
+        assert!(!bounds.is_backfacing(center + Vector3::unit_x() * 10.0));
+    }
+
+    #[test]
+    fn backfacing_respects_the_cone_width() {
+        let (_, mesh) = single(&[Vector3::unit_z(), Vector3::unit_x()]);
+        let bounds = mesh.bounds[0];
+        let behind = -bounds.cone_axis * 100.0;
+        assert!(bounds.is_backfacing(bounds.center + behind));
+        // Behind one face but in front of the other.
+        assert!(!bounds.is_backfacing(bounds.center - Vector3::unit_z() * 100.0));
+    }
+
+    #[test]
+    fn visible_culls_meshlets_facing_away() {
+        let (mut vertices, mut indices) = (Vec::new(), Vec::new());
+        push_facing(
+            &mut vertices,
+            &mut indices,
+            Vector3::zero(),
+            Vector3::unit_z(),
+        );
+        push_facing(
+            &mut vertices,
+            &mut indices,
+            Vector3::new(5.0, 0.0, 0.0),
+            -Vector3::unit_z(),
+        );
+        let mesh = MeshletMesh::build(&vertices, &indices, 64, 1);
+        assert_eq!(mesh.meshlets.len(), 2);
+        assert_eq!(mesh.visible(Vector3::new(2.5, 0.0, 50.0)), [0]);
+        assert_eq!(mesh.visible(Vector3::new(2.5, 0.0, -50.0)), [1]);
+        assert_eq!(mesh.visible(Vector3::new(2.5, 50.0, 0.0)), [0, 1]);
+    }
+}
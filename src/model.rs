@@ -0,0 +1,545 @@
+use cgmath::{
+    InnerSpace, Matrix4, One, Quaternion, SquareMatrix, Vector2, Vector3, Vector4, VectorSpace,
+    Zero,
+};
+use windows::Win32::Graphics::Dxgi::Common::*;
+
+use crate::vertex_layout::VertexLayout;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, VertexLayout)]
+pub struct MeshVertex {
+    #[vertex(semantic = "POSITION")]
+    pub position: Vector3<f32>,
+    #[vertex(semantic = "NORMAL")]
+    pub normal: Vector3<f32>,
+    #[vertex(semantic = "TEXCOORD")]
+    pub uv: Vector2<f32>,
+    #[vertex(semantic = "TANGENT")]
+    pub tangent: Vector4<f32>,
+    #[vertex(semantic = "BLENDINDICES")]
+    pub joints: [u16; 4],
+    #[vertex(semantic = "BLENDWEIGHT")]
+    pub weights: [f32; 4],
+}
+
+impl Default for MeshVertex {
+    fn default() -> Self {
+        MeshVertex {
+            position: Vector3::zero(),
+            normal: Vector3::unit_y(),
+            uv: Vector2::zero(),
+            tangent: Vector4::new(1.0, 0.0, 0.0, 1.0),
+            joints: [0; 4],
+            weights: [1.0, 0.0, 0.0, 0.0],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Indices {
+    U16(Vec<u16>),
+    U32(Vec<u32>),
+}
+
+impl Indices {
+    pub fn from_u32(indices: Vec<u32>) -> Self {
+        if indices.iter().all(|&i| i < u16::MAX as u32) {
+            Indices::U16(indices.into_iter().map(|i| i as u16).collect())
+        } else {
+            Indices::U32(indices)
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Indices::U16(indices) => indices.len(),
+            Indices::U32(indices) => indices.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get(&self, i: usize) -> u32 {
+        match self {
+            Indices::U16(indices) => indices[i] as u32,
+            Indices::U32(indices) => indices[i],
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = u32> + '_ {
+        (0..self.len()).map(|i| self.get(i))
+    }
+
+    pub fn format(&self) -> DXGI_FORMAT {
+        match self {
+            Indices::U16(_) => DXGI_FORMAT_R16_UINT,
+            Indices::U32(_) => DXGI_FORMAT_R32_UINT,
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            Indices::U16(indices) => unsafe {
+                std::slice::from_raw_parts(indices.as_ptr() as *const u8, indices.len() * 2)
+            },
+            Indices::U32(indices) => unsafe {
+                std::slice::from_raw_parts(indices.as_ptr() as *const u8, indices.len() * 4)
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Topology {
+    Points,
+    Lines,
+    LineStrip,
+    Triangles,
+    TriangleStrip,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DrawRange {
+    pub start_index: u32,
+    pub index_count: u32,
+    pub material: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Primitive {
+    pub vertices: Vec<MeshVertex>,
+    pub indices: Indices,
+    pub topology: Topology,
+    pub material: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mesh {
+    pub name: String,
+    pub primitives: Vec<Primitive>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlphaMode {
+    Opaque,
+    Mask,
+    Blend,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextureRef {
+    pub texture: usize,
+    pub tex_coord: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Material {
+    pub name: String,
+    pub base_color_factor: Vector4<f32>,
+    pub base_color_texture: Option<TextureRef>,
+    pub metallic_factor: f32,
+    pub roughness_factor: f32,
+    pub metallic_roughness_texture: Option<TextureRef>,
+    pub normal_texture: Option<TextureRef>,
+    pub normal_scale: f32,
+    pub occlusion_texture: Option<TextureRef>,
+    pub occlusion_strength: f32,
+    pub emissive_texture: Option<TextureRef>,
+    pub emissive_factor: Vector3<f32>,
+    pub alpha_mode: AlphaMode,
+    pub alpha_cutoff: f32,
+    pub double_sided: bool,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Material {
+            name: String::new(),
+            base_color_factor: Vector4::new(1.0, 1.0, 1.0, 1.0),
+            base_color_texture: None,
+            metallic_factor: 1.0,
+            roughness_factor: 1.0,
+            metallic_roughness_texture: None,
+            normal_texture: None,
+            normal_scale: 1.0,
+            occlusion_texture: None,
+            occlusion_strength: 1.0,
+            emissive_texture: None,
+            emissive_factor: Vector3::zero(),
+            alpha_mode: AlphaMode::Opaque,
+            alpha_cutoff: 0.5,
+            double_sided: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Filter {
+    Nearest,
+    Linear,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Wrap {
+    Repeat,
+    MirroredRepeat,
+    ClampToEdge,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sampler {
+    pub mag_filter: Filter,
+    pub min_filter: Filter,
+    pub mipmaps: bool,
+    pub wrap_u: Wrap,
+    pub wrap_v: Wrap,
+}
+
+impl Default for Sampler {
+    fn default() -> Self {
+        Sampler {
+            mag_filter: Filter::Linear,
+            min_filter: Filter::Linear,
+            mipmaps: true,
+            wrap_u: Wrap::Repeat,
+            wrap_v: Wrap::Repeat,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Texture {
+    pub name: String,
+    pub image: usize,
+    pub sampler: Sampler,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    pub rgba8: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    pub translation: Vector3<f32>,
+    pub rotation: Quaternion<f32>,
+    pub scale: Vector3<f32>,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform {
+            translation: Vector3::zero(),
+            rotation: Quaternion::one(),
+            scale: Vector3::new(1.0, 1.0, 1.0),
+        }
+    }
+}
+
+impl Transform {
+    pub fn matrix(&self) -> Matrix4<f32> {
+        Matrix4::from_translation(self.translation)
+            * Matrix4::from(self.rotation)
+            * Matrix4::from_nonuniform_scale(self.scale.x, self.scale.y, self.scale.z)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Node {
+    pub name: String,
+    pub parent: Option<usize>,
+    pub children: Vec<usize>,
+    pub transform: Transform,
+    pub mesh: Option<usize>,
+    pub skin: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Scene {
+    pub name: String,
+    pub nodes: Vec<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Skin {
+    pub name: String,
+    pub joints: Vec<usize>,
+    pub inverse_bind_matrices: Vec<Matrix4<f32>>,
+    pub skeleton: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interpolation {
+    Step,
+    Linear,
+    CubicSpline,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChannelValues {
+    Translations(Vec<Vector3<f32>>),
+    Rotations(Vec<Quaternion<f32>>),
+    Scales(Vec<Vector3<f32>>),
+    MorphWeights(Vec<f32>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Channel {
+    pub node: usize,
+    pub interpolation: Interpolation,
+    pub times: Vec<f32>,
+    pub values: ChannelValues,
+}
+
+impl Channel {
+    fn keyframe(&self, time: f32) -> (usize, usize, f32) {
+        let last = self.times.len().saturating_sub(1);
+        if time <= self.times[0] {
+            return (0, 0, 0.0);
+        }
+        if time >= self.times[last] {
+            return (last, last, 0.0);
+        }
+        let next = self.times.partition_point(|&t| t <= time);
+        let prev = next - 1;
+        let span = self.times[next] - self.times[prev];
+        let t = if span > 0.0 {
+            (time - self.times[prev]) / span
+        } else {
+            0.0
+        };
+        match self.interpolation {
+            Interpolation::Step => (prev, prev, 0.0),
+            _ => (prev, next, t),
+        }
+    }
+
+    pub fn apply(&self, time: f32, transform: &mut Transform) {
+        if self.times.is_empty() {
+            return;
+        }
+        let (prev, next, t) = self.keyframe(time);
+        if self.interpolation == Interpolation::CubicSpline {
+            let span = self.times[next] - self.times[prev];
+            match &self.values {
+                ChannelValues::Translations(values) => {
+                    transform.translation = cubic_spline(values, prev, next, t, span);
+                }
+                ChannelValues::Rotations(values) => {
+                    transform.rotation = cubic_spline(values, prev, next, t, span).normalize();
+                }
+                ChannelValues::Scales(values) => {
+                    transform.scale = cubic_spline(values, prev, next, t, span);
+                }
+                ChannelValues::MorphWeights(_) => {}
+            }
+            return;
+        }
+        match &self.values {
+            ChannelValues::Translations(values) => {
+                transform.translation = values[prev].lerp(values[next], t);
+            }
+            ChannelValues::Rotations(values) => {
+                transform.rotation = values[prev].normalize().slerp(values[next].normalize(), t);
+            }
+            ChannelValues::Scales(values) => {
+                transform.scale = values[prev].lerp(values[next], t);
+            }
+            ChannelValues::MorphWeights(_) => {}
+        }
+    }
+}
+
+/// Cubic Hermite spline between two keyframes, with `values` laid out as glTF
+/// (in-tangent, value, out-tangent) triples. Tangents are per second, so they
+/// are scaled by the keyframe `span`.
+fn cubic_spline<V>(values: &[V], prev: usize, next: usize, t: f32, span: f32) -> V
+where
+    V: VectorSpace<Scalar = f32>,
+{
+    let (t2, t3) = (t * t, t * t * t);
+    values[prev * 3 + 1] * (2.0 * t3 - 3.0 * t2 + 1.0)
+        + values[prev * 3 + 2] * ((t3 - 2.0 * t2 + t) * span)
+        + values[next * 3 + 1] * (3.0 * t2 - 2.0 * t3)
+        + values[next * 3] * ((t3 - t2) * span)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Animation {
+    pub name: String,
+    pub channels: Vec<Channel>,
+}
+
+impl Animation {
+    pub fn duration(&self) -> f32 {
+        self.channels
+            .iter()
+            .filter_map(|c| c.times.last().copied())
+            .fold(0.0, f32::max)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Model {
+    pub meshes: Vec<Mesh>,
+    pub materials: Vec<Material>,
+    pub textures: Vec<Texture>,
+    pub images: Vec<Image>,
+    pub nodes: Vec<Node>,
+    pub scenes: Vec<Scene>,
+    pub default_scene: Option<usize>,
+    pub skins: Vec<Skin>,
+    pub animations: Vec<Animation>,
+}
+
+impl Model {
+    pub fn root_nodes(&self) -> Vec<usize> {
+        let scene = self
+            .default_scene
+            .or((!self.scenes.is_empty()).then_some(0));
+        match scene {
+            Some(scene) => self.scenes[scene].nodes.clone(),
+            None => (0..self.nodes.len())
+                .filter(|&i| self.nodes[i].parent.is_none())
+                .collect(),
+        }
+    }
+
+    pub fn world_transforms(&self) -> Vec<Matrix4<f32>> {
+        let mut world = vec![Matrix4::identity(); self.nodes.len()];
+        let mut stack: Vec<(usize, Matrix4<f32>)> = (0..self.nodes.len())
+            .filter(|&i| self.nodes[i].parent.is_none())
+            .map(|i| (i, Matrix4::identity()))
+            .collect();
+        while let Some((node, parent)) = stack.pop() {
+            world[node] = parent * self.nodes[node].transform.matrix();
+            for &child in &self.nodes[node].children {
+                stack.push((child, world[node]));
+            }
+        }
+        world
+    }
+
+    pub fn animate(&mut self, animation: usize, time: f32) {
+        let Some(animation) = self.animations.get(animation) else {
+            return;
+        };
+        for channel in &animation.channels {
+            if let Some(node) = self.nodes.get_mut(channel.node) {
+                channel.apply(time, &mut node.transform);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{Deg, Rotation3};
+
+    use super::*;
+
+    const EPSILON: f32 = 1e-4;
+
+    fn assert_vector_close(actual: Vector3<f32>, expected: Vector3<f32>) {
+        assert!(
+            (actual - expected).magnitude() < EPSILON,
+            "expected {:?}, got {:?}",
+            expected,
+            actual
+        );
+    }
+
+    fn cubic(values: ChannelValues) -> Channel {
+        Channel {
+            node: 0,
+            interpolation: Interpolation::CubicSpline,
+            times: vec![1.0, 3.0],
+            values,
+        }
+    }
+
+    fn sample(channel: &Channel, time: f32) -> Transform {
+        let mut transform = Transform::default();
+        channel.apply(time, &mut transform);
+        transform
+    }
+
+    #[test]
+    fn cubic_spline_follows_scaled_tangents() {
+        // (in-tangent, value, out-tangent) per keyframe, tangents in units per second.
+        let channel = cubic(ChannelValues::Translations(vec![
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(3.0, 0.0, 1.0),
+            Vector3::new(0.0, 0.0, -1.0),
+            Vector3::new(2.0, 0.0, 0.0),
+            Vector3::new(0.0, 0.0, 0.0),
+        ]));
+        // Halfway through a two second span the tangents weigh 0.125 * 2.
+        assert_vector_close(
+            sample(&channel, 2.0).translation,
+            Vector3::new(1.75, 0.0, 0.5),
+        );
+        assert_vector_close(
+            sample(&channel, 1.5).translation,
+            Vector3::new(1.15625, 0.0, 0.375),
+        );
+        // Keyframes themselves land on the value, not a tangent.
+        assert_vector_close(sample(&channel, 1.0).translation, Vector3::zero());
+        assert_vector_close(
+            sample(&channel, 3.0).translation,
+            Vector3::new(2.0, 0.0, 0.0),
+        );
+        assert_vector_close(
+            sample(&channel, 5.0).translation,
+            Vector3::new(2.0, 0.0, 0.0),
+        );
+    }
+
+    #[test]
+    fn cubic_spline_scales_ease_without_tangents() {
+        let one = Vector3::new(1.0, 1.0, 1.0);
+        let channel = cubic(ChannelValues::Scales(vec![
+            Vector3::zero(),
+            one,
+            Vector3::zero(),
+            Vector3::zero(),
+            one * 3.0,
+            Vector3::zero(),
+        ]));
+        // Flat tangents ease in: a quarter of the way is 15.625% of the change.
+        assert_vector_close(sample(&channel, 1.5).scale, one * 1.3125);
+        assert_vector_close(sample(&channel, 2.0).scale, one * 2.0);
+    }
+
+    #[test]
+    fn cubic_spline_rotations_stay_normalized() {
+        let quarter_turn = Quaternion::from_angle_z(Deg(90.0));
+        let spin = Quaternion::new(0.0, 0.0, 0.0, 0.5);
+        let channel = cubic(ChannelValues::Rotations(vec![
+            Quaternion::zero(),
+            Quaternion::one(),
+            spin,
+            spin,
+            quarter_turn,
+            Quaternion::zero(),
+        ]));
+        for time in [1.25, 2.0, 2.5, 2.9] {
+            let rotation = sample(&channel, time).rotation;
+            assert!((rotation.magnitude() - 1.0).abs() < EPSILON);
+        }
+
+        // Symmetric tangents keep the midpoint on the 45 degree rotation.
+        let rotated = sample(&channel, 2.0).rotation * Vector3::unit_x();
+        let half = std::f32::consts::FRAC_1_SQRT_2;
+        assert_vector_close(rotated, Vector3::new(half, half, 0.0));
+        let end = sample(&channel, 3.0).rotation * Vector3::unit_x();
+        assert_vector_close(end, Vector3::unit_y());
+    }
+}
@@ -0,0 +1,430 @@
+use std::collections::HashMap;
+
+use cgmath::{InnerSpace, Vector3, VectorSpace};
+
+use crate::skeleton::{BoneTransform, Pose, Skeleton};
+use crate::vmd_loader::{BoneKeyframe, CameraKeyframe, IkKeyframe, MorphKeyframe, VmdMotion};
+
+pub const FRAMES_PER_SECOND: f32 = 30.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CameraPose {
+    pub target: Vector3<f32>,
+    pub rotation: Vector3<f32>,
+    pub distance: f32,
+    pub fov_degrees: f32,
+    pub perspective: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MotionPlayer {
+    bone_tracks: Vec<Vec<BoneKeyframe>>,
+    morph_tracks: HashMap<String, Vec<MorphKeyframe>>,
+    camera_track: Vec<CameraKeyframe>,
+    ik_track: Vec<IkKeyframe>,
+    last_frame: u32,
+    time: f32,
+    pub looping: bool,
+}
+
+impl MotionPlayer {
+    pub fn new(motion: &VmdMotion, skeleton: &Skeleton) -> Self {
+        let mut bone_tracks = vec![Vec::new(); skeleton.len()];
+        for keyframe in &motion.bone_keyframes {
+            if let Some(bone) = skeleton.find(&keyframe.bone) {
+                bone_tracks[bone].push(keyframe.clone());
+            }
+        }
+        let mut morph_tracks: HashMap<String, Vec<MorphKeyframe>> = HashMap::new();
+        for keyframe in &motion.morph_keyframes {
+            morph_tracks
+                .entry(keyframe.morph.clone())
+                .or_default()
+                .push(keyframe.clone());
+        }
+        let mut camera_track = motion.camera_keyframes.clone();
+        let mut ik_track = motion.ik_keyframes.clone();
+
+        for track in &mut bone_tracks {
+            track.sort_by_key(|k| k.frame);
+        }
+        for track in morph_tracks.values_mut() {
+            track.sort_by_key(|k| k.frame);
+        }
+        camera_track.sort_by_key(|k| k.frame);
+        ik_track.sort_by_key(|k| k.frame);
+
+        MotionPlayer {
+            bone_tracks,
+            morph_tracks,
+            camera_track,
+            ik_track,
+            last_frame: motion.last_frame(),
+            time: 0.0,
+            looping: true,
+        }
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.last_frame as f32 / FRAMES_PER_SECOND
+    }
+
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    pub fn frame(&self) -> f32 {
+        self.time * FRAMES_PER_SECOND
+    }
+
+    pub fn set_time(&mut self, time: f32) {
+        let duration = self.duration();
+        self.time = if self.looping && duration > 0.0 {
+            time.rem_euclid(duration)
+        } else {
+            time.clamp(0.0, duration)
+        };
+    }
+
+    pub fn advance(&mut self, dt: f32) {
+        self.set_time(self.time + dt);
+    }
+
+    pub fn pose(&self) -> Pose {
+        self.evaluate(self.frame())
+    }
+
+    pub fn evaluate(&self, frame: f32) -> Pose {
+        Pose {
+            bones: self
+                .bone_tracks
+                .iter()
+                .map(|track| evaluate_bone(track, frame))
+                .collect(),
+        }
+    }
+
+    pub fn morph_weight(&self, morph: &str, frame: f32) -> f32 {
+        let Some(track) = self.morph_tracks.get(morph) else {
+            return 0.0;
+        };
+        match segment(track, frame, |k| k.frame) {
+            Segment::Hold(k) => k.weight,
+            Segment::Between(a, b, t) => a.weight + (b.weight - a.weight) * t,
+        }
+    }
+
+    pub fn morph_weights(&self, frame: f32) -> Vec<(&str, f32)> {
+        self.morph_tracks
+            .keys()
+            .map(|morph| (morph.as_str(), self.morph_weight(morph, frame)))
+            .collect()
+    }
+
+    pub fn camera(&self, frame: f32) -> Option<CameraPose> {
+        if self.camera_track.is_empty() {
+            return None;
+        }
+        Some(match segment(&self.camera_track, frame, |k| k.frame) {
+            Segment::Hold(k) => CameraPose {
+                target: k.target,
+                rotation: k.rotation,
+                distance: k.distance,
+                fov_degrees: k.fov as f32,
+                perspective: k.perspective,
+            },
+            Segment::Between(a, b, t) => {
+                let curve = b.interpolation.map(|bezier| bezier.evaluate(t));
+                let lerp = |x: f32, y: f32, t: f32| x + (y - x) * t;
+                CameraPose {
+                    target: Vector3::new(
+                        lerp(a.target.x, b.target.x, curve[0]),
+                        lerp(a.target.y, b.target.y, curve[1]),
+                        lerp(a.target.z, b.target.z, curve[2]),
+                    ),
+                    rotation: a.rotation.lerp(b.rotation, curve[3]),
+                    distance: lerp(a.distance, b.distance, curve[4]),
+                    fov_degrees: lerp(a.fov as f32, b.fov as f32, curve[5]),
+                    perspective: a.perspective,
+                }
+            }
+        })
+    }
+
+    pub fn ik_enabled(&self, ik_bone: &str, frame: f32) -> bool {
+        let index = self.ik_track.partition_point(|k| k.frame as f32 <= frame);
+        let Some(keyframe) = index.checked_sub(1).map(|i| &self.ik_track[i]) else {
+            return true;
+        };
+        keyframe
+            .enabled
+            .iter()
+            .find(|(name, _)| name == ik_bone)
+            .is_none_or(|&(_, enabled)| enabled)
+    }
+}
+
+enum Segment<'a, K> {
+    Hold(&'a K),
+    Between(&'a K, &'a K, f32),
+}
+
+fn segment<K>(track: &[K], frame: f32, key_frame: impl Fn(&K) -> u32) -> Segment<'_, K> {
+    let next = track.partition_point(|k| key_frame(k) as f32 <= frame);
+    if next == 0 {
+        return Segment::Hold(&track[0]);
+    }
+    if next == track.len() {
+        return Segment::Hold(&track[next - 1]);
+    }
+    let (a, b) = (&track[next - 1], &track[next]);
+    let span = (key_frame(b) - key_frame(a)) as f32;
+    Segment::Between(a, b, (frame - key_frame(a) as f32) / span)
+}
+
+fn evaluate_bone(track: &[BoneKeyframe], frame: f32) -> BoneTransform {
+    if track.is_empty() {
+        return BoneTransform::default();
+    }
+    match segment(track, frame, |k| k.frame) {
+        Segment::Hold(k) => BoneTransform {
+            translation: k.translation,
+            rotation: k.rotation.normalize(),
+        },
+        Segment::Between(a, b, t) => {
+            let [x, y, z, r] = b.interpolation.map(|bezier| bezier.evaluate(t));
+            BoneTransform {
+                translation: Vector3::new(
+                    a.translation.x + (b.translation.x - a.translation.x) * x,
+                    a.translation.y + (b.translation.y - a.translation.y) * y,
+                    a.translation.z + (b.translation.z - a.translation.z) * z,
+                ),
+                rotation: a.rotation.normalize().slerp(b.rotation.normalize(), r),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::skeleton::Bone;
+    use crate::vmd_loader::Bezier;
+    use cgmath::{Deg, One, Quaternion, Rotation3, Transform, Vector2, Zero};
+
+    const EPSILON: f32 = 1e-4;
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < EPSILON,
+            "expected {expected}, got {actual}"
+        );
+    }
+
+    fn assert_vector_close(actual: Vector3<f32>, expected: Vector3<f32>) {
+        assert_close(actual.x, expected.x);
+        assert_close(actual.y, expected.y);
+        assert_close(actual.z, expected.z);
+    }
+
+    fn skeleton() -> Skeleton {
+        let bone = |name: &str, parent, position| Bone {
+            name: name.to_string(),
+            parent,
+            children: Vec::new(),
+            position,
+        };
+        Skeleton::new(vec![
+            bone("root", None, Vector3::zero()),
+            bone("arm", Some(0), Vector3::new(0.0, 1.0, 0.0)),
+        ])
+    }
+
+    fn bone_key(
+        bone: &str,
+        frame: u32,
+        translation: Vector3<f32>,
+        rotation: Quaternion<f32>,
+    ) -> BoneKeyframe {
+        BoneKeyframe {
+            bone: bone.to_string(),
+            frame,
+            translation,
+            rotation,
+            interpolation: [Bezier::LINEAR; 4],
+        }
+    }
+
+    fn morph_key(frame: u32, weight: f32) -> MorphKeyframe {
+        MorphKeyframe {
+            morph: "smile".to_string(),
+            frame,
+            weight,
+        }
+    }
+
+    const EASE_IN: Bezier = Bezier {
+        p1: Vector2::new(0.5, 0.0),
+        p2: Vector2::new(1.0, 1.0),
+    };
+
+    fn motion() -> VmdMotion {
+        // Keyframes deliberately out of order; the player sorts each track.
+        VmdMotion {
+            bone_keyframes: vec![
+                bone_key(
+                    "root",
+                    30,
+                    Vector3::new(3.0, 6.0, 9.0),
+                    Quaternion::from_angle_z(Deg(90.0)),
+                ),
+                bone_key("root", 0, Vector3::zero(), Quaternion::one()),
+                bone_key(
+                    "arm",
+                    10,
+                    Vector3::zero(),
+                    Quaternion::from_angle_x(Deg(45.0)),
+                ),
+                bone_key("missing", 0, Vector3::new(1.0, 1.0, 1.0), Quaternion::one()),
+            ],
+            morph_keyframes: vec![morph_key(20, 0.0), morph_key(10, 1.0)],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn evaluates_one_transform_per_skeleton_bone() {
+        let player = MotionPlayer::new(&motion(), &skeleton());
+        assert_eq!(player.evaluate(0.0).bones.len(), 2);
+        assert_eq!(player.duration(), 1.0);
+    }
+
+    #[test]
+    fn holds_the_first_and_last_keyframes() {
+        let player = MotionPlayer::new(&motion(), &skeleton());
+        let after = player.evaluate(45.0).bones[0];
+        assert_vector_close(after.translation, Vector3::new(3.0, 6.0, 9.0));
+        let arm = player.evaluate(0.0).bones[1];
+        assert_eq!(arm.rotation, Quaternion::from_angle_x(Deg(45.0)));
+    }
+
+    #[test]
+    fn interpolates_linearly_between_keyframes() {
+        let player = MotionPlayer::new(&motion(), &skeleton());
+        let root = player.evaluate(10.0).bones[0];
+        assert_vector_close(root.translation, Vector3::new(1.0, 2.0, 3.0));
+        let expected = Quaternion::from_angle_z(Deg(30.0));
+        assert_close(root.rotation.s, expected.s);
+        assert_close(root.rotation.v.z, expected.v.z);
+    }
+
+    #[test]
+    fn applies_each_channel_curve() {
+        let mut motion = motion();
+        motion.bone_keyframes[0].interpolation = [Bezier::LINEAR, EASE_IN, EASE_IN, EASE_IN];
+        let player = MotionPlayer::new(&motion, &skeleton());
+        let root = player.evaluate(15.0).bones[0];
+        let eased = EASE_IN.evaluate(0.5);
+        assert!(eased < 0.5);
+        assert_vector_close(
+            root.translation,
+            Vector3::new(1.5, 6.0 * eased, 9.0 * eased),
+        );
+        let expected = Quaternion::from_angle_z(Deg(90.0 * eased));
+        assert_close(root.rotation.s, expected.s);
+    }
+
+    #[test]
+    fn pose_drives_the_skeleton() {
+        let skeleton = skeleton();
+        let mut player = MotionPlayer::new(&motion(), &skeleton);
+        player.set_time(1.0);
+        assert_eq!(player.time(), 0.0);
+        player.looping = false;
+        player.set_time(1.0);
+        let matrices = skeleton.skinning_matrices(&player.pose());
+        // The arm keeps its own rotation but follows the root's translation and roll.
+        let tip = matrices[1].transform_point(cgmath::Point3::new(0.0, 1.0, 0.0));
+        assert_close(tip.x, 3.0 - 1.0);
+        assert_close(tip.y, 6.0);
+        assert_close(tip.z, 9.0);
+    }
+
+    #[test]
+    fn set_time_loops_or_clamps() {
+        let mut player = MotionPlayer::new(&motion(), &skeleton());
+        player.set_time(1.25);
+        assert_close(player.time(), 0.25);
+        player.set_time(-0.25);
+        assert_close(player.time(), 0.75);
+        player.advance(0.5);
+        assert_close(player.time(), 0.25);
+        player.looping = false;
+        player.set_time(5.0);
+        assert_eq!(player.time(), 1.0);
+        player.set_time(-1.0);
+        assert_eq!(player.time(), 0.0);
+        assert_close(player.frame(), 0.0);
+    }
+
+    #[test]
+    fn interpolates_morph_weights() {
+        let player = MotionPlayer::new(&motion(), &skeleton());
+        assert_eq!(player.morph_weight("smile", 0.0), 1.0);
+        assert_close(player.morph_weight("smile", 15.0), 0.5);
+        assert_eq!(player.morph_weight("smile", 40.0), 0.0);
+        assert_eq!(player.morph_weight("blink", 15.0), 0.0);
+        assert_eq!(player.morph_weights(12.0).len(), 1);
+    }
+
+    #[test]
+    fn interpolates_the_camera() {
+        let camera_key = |frame, distance, fov| CameraKeyframe {
+            frame,
+            distance,
+            target: Vector3::new(distance, 0.0, 0.0),
+            rotation: Vector3::zero(),
+            interpolation: [Bezier::LINEAR; 6],
+            fov,
+            perspective: true,
+        };
+        let mut motion = motion();
+        assert!(MotionPlayer::new(&motion, &skeleton())
+            .camera(0.0)
+            .is_none());
+        motion.camera_keyframes = vec![camera_key(10, -20.0, 40), camera_key(0, -10.0, 30)];
+        motion.camera_keyframes[0].interpolation[4] = EASE_IN;
+        let player = MotionPlayer::new(&motion, &skeleton());
+
+        let start = player.camera(-5.0).unwrap();
+        assert_eq!(start.distance, -10.0);
+        assert!(start.perspective);
+        let middle = player.camera(5.0).unwrap();
+        assert_close(middle.target.x, -15.0);
+        assert_close(middle.fov_degrees, 35.0);
+        assert_close(middle.distance, -10.0 - 10.0 * EASE_IN.evaluate(0.5));
+    }
+
+    #[test]
+    fn ik_is_enabled_until_switched_off() {
+        let mut motion = motion();
+        motion.ik_keyframes = vec![
+            IkKeyframe {
+                frame: 20,
+                visible: true,
+                enabled: vec![("leg".to_string(), true)],
+            },
+            IkKeyframe {
+                frame: 10,
+                visible: true,
+                enabled: vec![("leg".to_string(), false)],
+            },
+        ];
+        let player = MotionPlayer::new(&motion, &skeleton());
+        assert!(player.ik_enabled("leg", 5.0));
+        assert!(!player.ik_enabled("leg", 10.0));
+        assert!(!player.ik_enabled("leg", 19.0));
+        assert!(player.ik_enabled("leg", 20.0));
+        assert!(player.ik_enabled("other", 15.0));
+    }
+}
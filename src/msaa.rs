@@ -0,0 +1,303 @@
+use std::ffi::c_void;
+
+use windows::{core::*, Win32::Graphics::Direct3D12::*, Win32::Graphics::Dxgi::Common::*};
+
+use crate::barrier::transition_barrier;
+use crate::color::typeless_format;
+
+pub const SAMPLE_COUNTS: [u32; 4] = [1, 2, 4, 8];
+
+pub trait SampleSupport {
+    fn quality_levels(&self, format: DXGI_FORMAT, sample_count: u32) -> u32;
+}
+
+impl SampleSupport for ID3D12Device {
+    fn quality_levels(&self, format: DXGI_FORMAT, sample_count: u32) -> u32 {
+        let mut levels = D3D12_FEATURE_DATA_MULTISAMPLE_QUALITY_LEVELS {
+            Format: format,
+            SampleCount: sample_count,
+            Flags: D3D12_MULTISAMPLE_QUALITY_LEVELS_FLAG_NONE,
+            NumQualityLevels: 0,
+        };
+        let supported = unsafe {
+            self.CheckFeatureSupport(
+                D3D12_FEATURE_MULTISAMPLE_QUALITY_LEVELS,
+                &mut levels as *mut _ as *mut c_void,
+                std::mem::size_of_val(&levels) as u32,
+            )
+        };
+        match supported {
+            Ok(()) => levels.NumQualityLevels,
+            Err(_) => 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CapabilityTable {
+    entries: Vec<(DXGI_FORMAT, u32, u32)>,
+}
+
+impl CapabilityTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, format: DXGI_FORMAT, sample_count: u32, quality_levels: u32) -> Self {
+        self.entries.push((format, sample_count, quality_levels));
+        self
+    }
+}
+
+impl SampleSupport for CapabilityTable {
+    fn quality_levels(&self, format: DXGI_FORMAT, sample_count: u32) -> u32 {
+        if sample_count == 1 {
+            return 1;
+        }
+        self.entries
+            .iter()
+            .find(|(f, count, _)| *f == format && *count == sample_count)
+            .map_or(0, |(_, _, levels)| *levels)
+    }
+}
+
+pub fn negotiate_sample_desc(
+    support: &impl SampleSupport,
+    formats: &[DXGI_FORMAT],
+    requested: u32,
+) -> DXGI_SAMPLE_DESC {
+    let count = SAMPLE_COUNTS
+        .iter()
+        .rev()
+        .copied()
+        .filter(|&count| count <= requested.max(1))
+        .find(|&count| {
+            count == 1
+                || formats
+                    .iter()
+                    .all(|&format| support.quality_levels(format, count) > 0)
+        })
+        .unwrap_or(1);
+    DXGI_SAMPLE_DESC {
+        Count: count,
+        Quality: 0,
+    }
+}
+
+pub fn next_sample_count(current: u32) -> u32 {
+    SAMPLE_COUNTS
+        .iter()
+        .copied()
+        .find(|&count| count > current)
+        .unwrap_or(SAMPLE_COUNTS[0])
+}
+
+pub struct MsaaTarget {
+    resource: ID3D12Resource,
+    heap: ID3D12DescriptorHeap,
+    format: DXGI_FORMAT,
+    view_format: DXGI_FORMAT,
+    sample_desc: DXGI_SAMPLE_DESC,
+}
+
+impl MsaaTarget {
+    pub fn new(
+        device: &ID3D12Device,
+        width: u32,
+        height: u32,
+        format: DXGI_FORMAT,
+        view_format: DXGI_FORMAT,
+        sample_desc: DXGI_SAMPLE_DESC,
+    ) -> Result<Self> {
+        let heap_desc = D3D12_DESCRIPTOR_HEAP_DESC {
+            Type: D3D12_DESCRIPTOR_HEAP_TYPE_RTV,
+            NodeMask: 0,
+            NumDescriptors: 1,
+            Flags: D3D12_DESCRIPTOR_HEAP_FLAG_NONE,
+        };
+        let heap: ID3D12DescriptorHeap = unsafe { device.CreateDescriptorHeap(&heap_desc) }?;
+        let resource = create_color_resource(device, width, height, format, sample_desc)?;
+        let target = MsaaTarget {
+            resource,
+            heap,
+            format,
+            view_format,
+            sample_desc,
+        };
+        target.create_view(device);
+        Ok(target)
+    }
+
+    fn create_view(&self, device: &ID3D12Device) {
+        let view_desc = D3D12_RENDER_TARGET_VIEW_DESC {
+            Format: self.view_format,
+            ViewDimension: D3D12_RTV_DIMENSION_TEXTURE2DMS,
+            ..Default::default()
+        };
+        unsafe {
+            device.CreateRenderTargetView(&self.resource, Some(&view_desc), self.rtv_handle())
+        };
+    }
+
+    pub fn resize(&mut self, device: &ID3D12Device, width: u32, height: u32) -> Result<()> {
+        self.resource =
+            create_color_resource(device, width, height, self.format, self.sample_desc)?;
+        self.create_view(device);
+        Ok(())
+    }
+
+    pub fn sample_desc(&self) -> DXGI_SAMPLE_DESC {
+        self.sample_desc
+    }
+
+    pub fn rtv_handle(&self) -> D3D12_CPU_DESCRIPTOR_HANDLE {
+        unsafe { self.heap.GetCPUDescriptorHandleForHeapStart() }
+    }
+
+    pub fn resolve(&self, command_list: &ID3D12GraphicsCommandList, target: &ID3D12Resource) {
+        let barriers = [
+            transition_barrier(
+                &self.resource,
+                D3D12_RESOURCE_STATE_RENDER_TARGET,
+                D3D12_RESOURCE_STATE_RESOLVE_SOURCE,
+            ),
+            transition_barrier(
+                target,
+                D3D12_RESOURCE_STATE_PRESENT,
+                D3D12_RESOURCE_STATE_RESOLVE_DEST,
+            ),
+        ];
+        unsafe { command_list.ResourceBarrier(&barriers) };
+
+        unsafe { command_list.ResolveSubresource(target, 0, &self.resource, 0, self.format) };
+
+        let barriers = [
+            transition_barrier(
+                &self.resource,
+                D3D12_RESOURCE_STATE_RESOLVE_SOURCE,
+                D3D12_RESOURCE_STATE_RENDER_TARGET,
+            ),
+            transition_barrier(
+                target,
+                D3D12_RESOURCE_STATE_RESOLVE_DEST,
+                D3D12_RESOURCE_STATE_RENDER_TARGET,
+            ),
+        ];
+        unsafe { command_list.ResourceBarrier(&barriers) };
+    }
+}
+
+fn create_color_resource(
+    device: &ID3D12Device,
+    width: u32,
+    height: u32,
+    format: DXGI_FORMAT,
+    sample_desc: DXGI_SAMPLE_DESC,
+) -> Result<ID3D12Resource> {
+    let heap_properties = D3D12_HEAP_PROPERTIES {
+        Type: D3D12_HEAP_TYPE_DEFAULT,
+        CPUPageProperty: D3D12_CPU_PAGE_PROPERTY_UNKNOWN,
+        MemoryPoolPreference: D3D12_MEMORY_POOL_UNKNOWN,
+        ..Default::default()
+    };
+    let resource_desc = D3D12_RESOURCE_DESC {
+        Dimension: D3D12_RESOURCE_DIMENSION_TEXTURE2D,
+        Width: width as u64,
+        Height: height,
+        DepthOrArraySize: 1,
+        MipLevels: 1,
+        Format: typeless_format(format),
+        SampleDesc: sample_desc,
+        Layout: D3D12_TEXTURE_LAYOUT_UNKNOWN,
+        Flags: D3D12_RESOURCE_FLAG_ALLOW_RENDER_TARGET,
+        ..Default::default()
+    };
+    let mut resource: Option<ID3D12Resource> = None;
+    unsafe {
+        device.CreateCommittedResource(
+            &heap_properties,
+            D3D12_HEAP_FLAG_NONE,
+            &resource_desc,
+            D3D12_RESOURCE_STATE_RENDER_TARGET,
+            // The clear color is edited at runtime and tonemapped per frame, so
+            // no single optimized clear value would match it.
+            None,
+            &mut resource,
+        )
+    }?;
+    Ok(resource.unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COLOR: DXGI_FORMAT = DXGI_FORMAT_R8G8B8A8_UNORM;
+    const HDR: DXGI_FORMAT = DXGI_FORMAT_R16G16B16A16_FLOAT;
+    const DEPTH: DXGI_FORMAT = DXGI_FORMAT_D32_FLOAT;
+
+    fn full_support() -> CapabilityTable {
+        [COLOR, HDR, DEPTH]
+            .into_iter()
+            .flat_map(|format| [2, 4, 8].map(|count| (format, count)))
+            .fold(CapabilityTable::new(), |table, (format, count)| {
+                table.with(format, count, 1)
+            })
+    }
+
+    fn count(support: &CapabilityTable, formats: &[DXGI_FORMAT], requested: u32) -> u32 {
+        negotiate_sample_desc(support, formats, requested).Count
+    }
+
+    #[test]
+    fn negotiates_requested_count_when_supported() {
+        let support = full_support();
+        for requested in SAMPLE_COUNTS {
+            let desc = negotiate_sample_desc(&support, &[COLOR, DEPTH], requested);
+            assert_eq!(desc.Count, requested);
+            assert_eq!(desc.Quality, 0);
+        }
+    }
+
+    #[test]
+    fn falls_back_to_count_every_format_supports() {
+        let support = CapabilityTable::new()
+            .with(COLOR, 8, 1)
+            .with(COLOR, 4, 1)
+            .with(COLOR, 2, 1)
+            .with(DEPTH, 4, 1)
+            .with(DEPTH, 2, 1)
+            .with(HDR, 2, 1);
+        assert_eq!(count(&support, &[COLOR], 8), 8);
+        assert_eq!(count(&support, &[COLOR, DEPTH], 8), 4);
+        assert_eq!(count(&support, &[HDR, DEPTH], 8), 2);
+        assert_eq!(count(&support, &[HDR, DEPTH, DXGI_FORMAT_R32_FLOAT], 8), 1);
+    }
+
+    #[test]
+    fn zero_quality_levels_means_unsupported() {
+        let support = CapabilityTable::new().with(COLOR, 4, 0).with(COLOR, 2, 3);
+        assert_eq!(count(&support, &[COLOR], 4), 2);
+        assert_eq!(support.quality_levels(COLOR, 1), 1);
+        assert_eq!(support.quality_levels(DEPTH, 8), 0);
+    }
+
+    #[test]
+    fn odd_and_zero_requests_round_down() {
+        let support = full_support();
+        assert_eq!(count(&support, &[COLOR], 0), 1);
+        assert_eq!(count(&support, &[COLOR], 3), 2);
+        assert_eq!(count(&support, &[COLOR], 7), 4);
+        assert_eq!(count(&support, &[COLOR], 64), 8);
+        assert_eq!(count(&CapabilityTable::new(), &[COLOR], 8), 1);
+    }
+
+    #[test]
+    fn next_sample_count_cycles() {
+        assert_eq!(next_sample_count(1), 2);
+        assert_eq!(next_sample_count(2), 4);
+        assert_eq!(next_sample_count(4), 8);
+        assert_eq!(next_sample_count(8), 1);
+        assert_eq!(next_sample_count(3), 4);
+    }
+}
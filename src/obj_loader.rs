@@ -0,0 +1,777 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use cgmath::{InnerSpace, Vector2, Vector3, Zero};
+
+use crate::mesh_processing;
+use crate::model::{
+    AlphaMode, Image, Indices, Material, Mesh, MeshVertex, Model, Node, Primitive, Sampler, Scene,
+    Texture, TextureRef, Topology, Transform,
+};
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    Image {
+        path: PathBuf,
+        error: image::ImageError,
+    },
+    InvalidNumber {
+        line: usize,
+        token: String,
+    },
+    InvalidFace {
+        line: usize,
+    },
+    IndexOutOfRange {
+        line: usize,
+        index: i64,
+    },
+    MissingMaterialName {
+        line: usize,
+    },
+}
+
+impl std::fmt::Display for ObjError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ObjError::Io { path, error } => write!(f, "{}: {}", path.display(), error),
+            ObjError::Image { path, error } => write!(f, "{}: {}", path.display(), error),
+            ObjError::InvalidNumber { line, token } => {
+                write!(f, "line {}: invalid number {:?}", line, token)
+            }
+            ObjError::InvalidFace { line } => {
+                write!(f, "line {}: face needs at least three vertices", line)
+            }
+            ObjError::IndexOutOfRange { line, index } => {
+                write!(f, "line {}: index {} out of range", line, index)
+            }
+            ObjError::MissingMaterialName { line } => {
+                write!(f, "line {}: missing material name", line)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ObjError {}
+
+pub type Result<T> = std::result::Result<T, ObjError>;
+
+pub fn load(path: impl AsRef<Path>) -> Result<Model> {
+    let path = path.as_ref();
+    let source = read_file(path)?;
+    parse(&source, path.parent())
+}
+
+pub fn parse(source: &str, base_dir: Option<&Path>) -> Result<Model> {
+    let mut builder = Builder::default();
+    let mut positions: Vec<Vector3<f32>> = Vec::new();
+    let mut uvs: Vec<Vector2<f32>> = Vec::new();
+    let mut normals: Vec<Vector3<f32>> = Vec::new();
+
+    for (number, line) in logical_lines(source) {
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+        match keyword {
+            "v" => positions.push(parse_vector3(number, &mut tokens)?),
+            "vt" => {
+                let u = parse_f32(number, tokens.next())?;
+                let v = tokens
+                    .next()
+                    .map_or(Ok(0.0), |t| parse_f32(number, Some(t)))?;
+                uvs.push(Vector2::new(u, 1.0 - v));
+            }
+            "vn" => normals.push(parse_vector3(number, &mut tokens)?),
+            "f" => {
+                let corners = tokens
+                    .map(|token| {
+                        parse_corner(number, token, positions.len(), uvs.len(), normals.len())
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                if corners.len() < 3 {
+                    return Err(ObjError::InvalidFace { line: number });
+                }
+                builder.face(&corners, &positions, &uvs, &normals);
+            }
+            "o" | "g" => builder.begin_mesh(tokens.collect::<Vec<_>>().join(" ")),
+            "s" => builder.flat = matches!(tokens.next(), Some("off" | "0")),
+            "usemtl" => {
+                let name = tokens.collect::<Vec<_>>().join(" ");
+                if name.is_empty() {
+                    return Err(ObjError::MissingMaterialName { line: number });
+                }
+                builder.use_material(&name);
+            }
+            "mtllib" => {
+                for file in tokens {
+                    let path = resolve(base_dir, file);
+                    let source = read_file(&path)?;
+                    builder.load_materials(&source, path.parent())?;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(builder.finish())
+}
+
+#[derive(Default)]
+struct Builder {
+    model: Model,
+    material_names: HashMap<String, usize>,
+    image_paths: HashMap<PathBuf, usize>,
+    mesh: Option<PendingMesh>,
+    material: Option<usize>,
+    flat: bool,
+}
+
+struct PendingMesh {
+    name: String,
+    primitives: Vec<PendingPrimitive>,
+}
+
+struct PendingPrimitive {
+    material: Option<usize>,
+    flat: bool,
+    vertices: Vec<MeshVertex>,
+    has_normal: Vec<bool>,
+    indices: Vec<u32>,
+    lookup: HashMap<Corner, u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Corner {
+    position: usize,
+    uv: Option<usize>,
+    normal: Option<usize>,
+}
+
+impl Builder {
+    fn begin_mesh(&mut self, name: String) {
+        self.flush_mesh();
+        self.mesh = Some(PendingMesh {
+            name,
+            primitives: Vec::new(),
+        });
+    }
+
+    fn use_material(&mut self, name: &str) {
+        self.material = Some(match self.material_names.get(name) {
+            Some(&index) => index,
+            None => {
+                self.model.materials.push(Material {
+                    name: name.to_string(),
+                    ..Default::default()
+                });
+                let index = self.model.materials.len() - 1;
+                self.material_names.insert(name.to_string(), index);
+                index
+            }
+        });
+    }
+
+    fn face(
+        &mut self,
+        corners: &[Corner],
+        positions: &[Vector3<f32>],
+        uvs: &[Vector2<f32>],
+        normals: &[Vector3<f32>],
+    ) {
+        let (material, flat) = (self.material, self.flat);
+        let mesh = self.mesh.get_or_insert_with(|| PendingMesh {
+            name: String::new(),
+            primitives: Vec::new(),
+        });
+        if mesh
+            .primitives
+            .last()
+            .is_none_or(|p| p.material != material || p.flat != flat)
+        {
+            mesh.primitives.push(PendingPrimitive {
+                material,
+                flat,
+                vertices: Vec::new(),
+                has_normal: Vec::new(),
+                indices: Vec::new(),
+                lookup: HashMap::new(),
+            });
+        }
+        let primitive = mesh.primitives.last_mut().unwrap();
+
+        let indices: Vec<u32> = corners
+            .iter()
+            .map(|corner| {
+                *primitive.lookup.entry(*corner).or_insert_with(|| {
+                    primitive.vertices.push(MeshVertex {
+                        position: positions[corner.position],
+                        normal: corner.normal.map_or(Vector3::zero(), |n| normals[n]),
+                        uv: corner.uv.map_or(Vector2::zero(), |t| uvs[t]),
+                        ..Default::default()
+                    });
+                    primitive.has_normal.push(corner.normal.is_some());
+                    (primitive.vertices.len() - 1) as u32
+                })
+            })
+            .collect();
+
+        for i in 1..indices.len() - 1 {
+            primitive
+                .indices
+                .extend([indices[0], indices[i], indices[i + 1]]);
+        }
+    }
+
+    fn flush_mesh(&mut self) {
+        let Some(mesh) = self.mesh.take() else {
+            return;
+        };
+        let primitives: Vec<Primitive> = mesh
+            .primitives
+            .into_iter()
+            .filter(|p| !p.indices.is_empty())
+            .map(PendingPrimitive::finish)
+            .collect();
+        if primitives.is_empty() {
+            return;
+        }
+        self.model.meshes.push(Mesh {
+            name: mesh.name.clone(),
+            primitives,
+        });
+        self.model.nodes.push(Node {
+            name: mesh.name,
+            parent: None,
+            children: Vec::new(),
+            transform: Transform::default(),
+            mesh: Some(self.model.meshes.len() - 1),
+            skin: None,
+        });
+    }
+
+    fn finish(mut self) -> Model {
+        self.flush_mesh();
+        self.model.scenes.push(Scene {
+            name: String::new(),
+            nodes: (0..self.model.nodes.len()).collect(),
+        });
+        self.model.default_scene = Some(0);
+        self.model
+    }
+
+    fn load_materials(&mut self, source: &str, base_dir: Option<&Path>) -> Result<()> {
+        let mut current: Option<usize> = None;
+        for (number, line) in logical_lines(source) {
+            let mut tokens = line.split_whitespace();
+            let Some(keyword) = tokens.next() else {
+                continue;
+            };
+            if keyword == "newmtl" {
+                let name = tokens.collect::<Vec<_>>().join(" ");
+                if name.is_empty() {
+                    return Err(ObjError::MissingMaterialName { line: number });
+                }
+                let previous = self.material;
+                self.use_material(&name);
+                current = self.material;
+                self.material = previous;
+                continue;
+            }
+            let Some(material) = current else {
+                continue;
+            };
+            match keyword {
+                "Kd" => {
+                    let color = parse_vector3(number, &mut tokens)?;
+                    let alpha = self.model.materials[material].base_color_factor.w;
+                    self.model.materials[material].base_color_factor = color.extend(alpha);
+                }
+                "d" => {
+                    let alpha = parse_f32(number, tokens.next())?;
+                    self.set_alpha(material, alpha);
+                }
+                "Tr" => {
+                    let alpha = 1.0 - parse_f32(number, tokens.next())?;
+                    self.set_alpha(material, alpha);
+                }
+                "Ke" => {
+                    self.model.materials[material].emissive_factor =
+                        parse_vector3(number, &mut tokens)?;
+                }
+                "map_Kd" | "map_Bump" | "map_bump" | "bump" | "norm" | "map_Ke" => {
+                    let Some(file) = tokens.last() else {
+                        continue;
+                    };
+                    let texture = Some(TextureRef {
+                        texture: self.texture(&resolve(base_dir, file))?,
+                        tex_coord: 0,
+                    });
+                    let material = &mut self.model.materials[material];
+                    match keyword {
+                        "map_Kd" => material.base_color_texture = texture,
+                        "map_Ke" => material.emissive_texture = texture,
+                        _ => material.normal_texture = texture,
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn set_alpha(&mut self, material: usize, alpha: f32) {
+        let material = &mut self.model.materials[material];
+        material.base_color_factor.w = alpha;
+        material.alpha_mode = if alpha < 1.0 {
+            AlphaMode::Blend
+        } else {
+            AlphaMode::Opaque
+        };
+    }
+
+    fn texture(&mut self, path: &Path) -> Result<usize> {
+        let image = match self.image_paths.get(path) {
+            Some(&image) => image,
+            None => {
+                let decoded = image::open(path)
+                    .map_err(|error| ObjError::Image {
+                        path: path.to_path_buf(),
+                        error,
+                    })?
+                    .to_rgba8();
+                self.model.images.push(Image {
+                    name: path.display().to_string(),
+                    width: decoded.width(),
+                    height: decoded.height(),
+                    rgba8: decoded.into_raw(),
+                });
+                let image = self.model.images.len() - 1;
+                self.image_paths.insert(path.to_path_buf(), image);
+                image
+            }
+        };
+        if let Some(texture) = self.model.textures.iter().position(|t| t.image == image) {
+            return Ok(texture);
+        }
+        self.model.textures.push(Texture {
+            name: self.model.images[image].name.clone(),
+            image,
+            sampler: Sampler::default(),
+        });
+        Ok(self.model.textures.len() - 1)
+    }
+}
+
+impl PendingPrimitive {
+    fn finish(mut self) -> Primitive {
+        for vertex in &mut self.vertices {
+            vertex.normal = if vertex.normal.magnitude2() > 0.0 {
+                vertex.normal.normalize()
+            } else {
+                Vector3::unit_y()
+            };
+        }
+        if self.has_normal.iter().any(|&has| !has) {
+            // Generated normals only fill in corners the file left without one.
+            if self.flat {
+                let (mut flat, _) =
+                    mesh_processing::generate_flat_normals(&self.vertices, &self.indices);
+                for (vertex, &index) in flat.iter_mut().zip(&self.indices) {
+                    if self.has_normal[index as usize] {
+                        vertex.normal = self.vertices[index as usize].normal;
+                    }
+                }
+                (self.vertices, self.indices) = mesh_processing::weld(&flat);
+            } else {
+                let mut smooth = self.vertices.clone();
+                mesh_processing::generate_smooth_normals(&mut smooth, &self.indices);
+                for ((vertex, smooth), &has) in
+                    self.vertices.iter_mut().zip(smooth).zip(&self.has_normal)
+                {
+                    if !has {
+                        vertex.normal = smooth.normal;
+                    }
+                }
+            }
+        }
+        if let Some((vertices, indices)) =
+            mesh_processing::generate_tangents(&self.vertices, &self.indices)
+        {
+            self.vertices = vertices;
+            self.indices = indices;
+        }
+        Primitive {
+            vertices: self.vertices,
+            indices: Indices::from_u32(self.indices),
+            topology: Topology::Triangles,
+            material: self.material,
+        }
+    }
+}
+
+fn read_file(path: &Path) -> Result<String> {
+    std::fs::read(path)
+        .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+        .map_err(|error| ObjError::Io {
+            path: path.to_path_buf(),
+            error,
+        })
+}
+
+fn resolve(base_dir: Option<&Path>, file: &str) -> PathBuf {
+    let file = file.replace('\\', "/");
+    match base_dir {
+        Some(dir) => dir.join(file),
+        None => PathBuf::from(file),
+    }
+}
+
+fn logical_lines(source: &str) -> Vec<(usize, String)> {
+    let mut lines = Vec::new();
+    let mut pending = String::new();
+    let mut start = 0;
+    for (i, line) in source.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default();
+        if pending.is_empty() {
+            start = i + 1;
+        }
+        match line.trim_end().strip_suffix('\\') {
+            Some(continued) => {
+                pending.push_str(continued);
+                pending.push(' ');
+            }
+            None => {
+                pending.push_str(line);
+                lines.push((start, std::mem::take(&mut pending)));
+            }
+        }
+    }
+    if !pending.is_empty() {
+        lines.push((start, pending));
+    }
+    lines
+}
+
+fn parse_f32(line: usize, token: Option<&str>) -> Result<f32> {
+    let token = token.unwrap_or_default();
+    token
+        .parse()
+        .ok()
+        .filter(|value: &f32| value.is_finite())
+        .ok_or_else(|| ObjError::InvalidNumber {
+            line,
+            token: token.to_string(),
+        })
+}
+
+fn parse_vector3<'a>(
+    line: usize,
+    tokens: &mut impl Iterator<Item = &'a str>,
+) -> Result<Vector3<f32>> {
+    Ok(Vector3::new(
+        parse_f32(line, tokens.next())?,
+        parse_f32(line, tokens.next())?,
+        parse_f32(line, tokens.next())?,
+    ))
+}
+
+fn parse_index(line: usize, token: &str, count: usize) -> Result<usize> {
+    let index: i64 = token.parse().map_err(|_| ObjError::InvalidNumber {
+        line,
+        token: token.to_string(),
+    })?;
+    let resolved = match index {
+        i if i > 0 => i - 1,
+        i if i < 0 => count as i64 + i,
+        _ => -1,
+    };
+    if (0..count as i64).contains(&resolved) {
+        Ok(resolved as usize)
+    } else {
+        Err(ObjError::IndexOutOfRange { line, index })
+    }
+}
+
+fn parse_corner(
+    line: usize,
+    token: &str,
+    positions: usize,
+    uvs: usize,
+    normals: usize,
+) -> Result<Corner> {
+    let mut parts = token.split('/');
+    let position = parse_index(line, parts.next().unwrap_or_default(), positions)?;
+    let mut optional = |count: usize| match parts.next() {
+        Some(part) if !part.is_empty() => parse_index(line, part, count).map(Some),
+        _ => Ok(None),
+    };
+    let uv = optional(uvs)?;
+    let normal = optional(normals)?;
+    Ok(Corner {
+        position,
+        uv,
+        normal,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f32 = 1e-4;
+
+    const TRIANGLE: &str = "v 0 0 0\nv 1 0 0\nv 0 1 0\n";
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures/obj")
+            .join(name)
+    }
+
+    fn assert_vector_close(actual: Vector3<f32>, expected: Vector3<f32>) {
+        assert!(
+            (actual - expected).magnitude() <= EPSILON,
+            "expected {expected:?}, got {actual:?}"
+        );
+    }
+
+    /// Corner positions in index order, independent of how vertices were deduplicated.
+    fn corners(primitive: &Primitive) -> Vec<Vector3<f32>> {
+        primitive
+            .indices
+            .iter()
+            .map(|i| primitive.vertices[i as usize].position)
+            .collect()
+    }
+
+    fn parse_error(source: &str) -> ObjError {
+        parse(source, None).expect_err("source should be rejected")
+    }
+
+    #[test]
+    fn loads_materials_and_textures() {
+        let model = load(fixture("quads.obj")).unwrap();
+        assert_eq!(model.meshes.len(), 1);
+        assert_eq!(model.meshes[0].name, "quads");
+        assert_eq!(model.nodes[0].mesh, Some(0));
+        assert_eq!(model.scenes[0].nodes, [0]);
+
+        let [red, textured] = &model.materials[..] else {
+            panic!("expected two materials, got {:?}", model.materials);
+        };
+        assert_eq!(red.name, "red");
+        assert_eq!(
+            red.base_color_factor,
+            Vector3::new(1.0, 0.0, 0.0).extend(0.5)
+        );
+        assert_eq!(red.alpha_mode, AlphaMode::Blend);
+        assert_eq!(red.base_color_texture, None);
+        assert_eq!(textured.alpha_mode, AlphaMode::Opaque);
+        let texture = textured.base_color_texture.unwrap();
+        let image = &model.images[model.textures[texture.texture].image];
+        assert_eq!((image.width, image.height), (2, 2));
+        assert_eq!(&image.rgba8[..4], [255, 0, 0, 255]);
+        assert_eq!(&image.rgba8[12..], [255, 255, 255, 128]);
+
+        let primitives = &model.meshes[0].primitives;
+        assert_eq!(primitives.len(), 2);
+        assert_eq!(primitives[0].material, Some(0));
+        assert_eq!(primitives[1].material, Some(1));
+    }
+
+    #[test]
+    fn triangulates_quads_as_fans() {
+        let model = load(fixture("quads.obj")).unwrap();
+        let quad = &model.meshes[0].primitives[0];
+        assert_eq!(quad.indices.len(), 6);
+        let corners = corners(quad);
+        for (corner, expected) in corners.iter().zip([
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(1.0, 1.0, 0.0),
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 1.0, 0.0),
+            Vector3::new(0.0, 1.0, 0.0),
+        ]) {
+            assert_vector_close(*corner, expected);
+        }
+        // Texture coordinates are flipped to a top-left origin.
+        let first = &quad.vertices[quad.indices.iter().next().unwrap() as usize];
+        assert_eq!(first.uv, Vector2::new(0.0, 1.0));
+    }
+
+    #[test]
+    fn resolves_negative_indices_against_the_current_lists() {
+        let model = load(fixture("quads.obj")).unwrap();
+        let quad = &model.meshes[0].primitives[1];
+        let corners = corners(quad);
+        for (corner, expected) in corners.iter().zip([
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(2.0, 0.0, 0.0),
+            Vector3::new(2.0, 1.0, 0.0),
+        ]) {
+            assert_vector_close(*corner, expected);
+        }
+        // No normals were given, so they come from the face winding.
+        for vertex in &quad.vertices {
+            assert_vector_close(vertex.normal, Vector3::unit_z());
+        }
+    }
+
+    #[test]
+    fn rejects_index_zero() {
+        let error = parse_error(&format!("{TRIANGLE}f 0 1 2\n"));
+        assert!(matches!(
+            error,
+            ObjError::IndexOutOfRange { line: 4, index: 0 }
+        ));
+    }
+
+    #[test]
+    fn rejects_out_of_range_indices() {
+        for (face, bad) in [
+            ("f 1 2 4", 4),
+            ("f -4 1 2", -4),
+            ("f 1/2 2/1 3/1", 2),
+            ("f 1//1 2//1 3//2", 2),
+        ] {
+            let source = format!("{TRIANGLE}vt 0 0\nvn 0 0 1\n{face}\n");
+            match parse_error(&source) {
+                ObjError::IndexOutOfRange { line: 6, index } => assert_eq!(index, bad, "{face}"),
+                error => panic!("{face}: unexpected {error}"),
+            }
+        }
+    }
+
+    type ErrorCheck = fn(&ObjError) -> bool;
+
+    #[test]
+    fn rejects_malformed_lines() {
+        let cases: [(&str, ErrorCheck); 7] = [
+            (
+                "v 1 x 0",
+                |e| matches!(e, ObjError::InvalidNumber { line: 4, token } if token == "x"),
+            ),
+            (
+                "v 1 2",
+                |e| matches!(e, ObjError::InvalidNumber { line: 4, token } if token.is_empty()),
+            ),
+            (
+                "vn 0 nan 1",
+                |e| matches!(e, ObjError::InvalidNumber { token, .. } if token == "nan"),
+            ),
+            ("vt", |e| matches!(e, ObjError::InvalidNumber { .. })),
+            ("f 1 2", |e| matches!(e, ObjError::InvalidFace { .. })),
+            (
+                "f 1 a 3",
+                |e| matches!(e, ObjError::InvalidNumber { token, .. } if token == "a"),
+            ),
+            ("usemtl", |e| {
+                matches!(e, ObjError::MissingMaterialName { .. })
+            }),
+        ];
+        for (line, check) in cases {
+            let error = parse_error(&format!("{TRIANGLE}{line}\n"));
+            assert!(check(&error), "{line}: unexpected {error}");
+        }
+    }
+
+    #[test]
+    fn reports_missing_files() {
+        let error = parse_error(&format!("mtllib missing.mtl\n{TRIANGLE}"));
+        assert!(matches!(error, ObjError::Io { .. }));
+        let error = load(fixture("missing.obj")).unwrap_err();
+        assert!(error.to_string().contains("missing.obj"));
+    }
+
+    #[test]
+    fn reports_lines_of_continued_statements() {
+        let error = parse_error("# header\nv 0 \\\n  0 \\\n  bad\n");
+        assert!(matches!(error, ObjError::InvalidNumber { line: 2, .. }));
+    }
+
+    #[test]
+    fn ignores_comments_and_unknown_statements() {
+        let source = format!("{TRIANGLE}s off\nl 1 2\n# f 9 9 9\nf 1 2 3 # trailing\n");
+        let model = parse(&source, None).unwrap();
+        let primitive = &model.meshes[0].primitives[0];
+        assert_eq!(primitive.indices.len(), 3);
+        assert_eq!(primitive.material, None);
+    }
+
+    #[test]
+    fn groups_start_new_meshes() {
+        let source = format!("{TRIANGLE}f 1 2 3\ng second\nf 3 2 1\ng empty\n");
+        let model = parse(&source, None).unwrap();
+        let names: Vec<&str> = model.meshes.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, ["", "second"]);
+        assert_eq!(model.nodes.len(), 2);
+        assert_eq!(model.scenes[0].nodes, [0, 1]);
+    }
+
+    #[test]
+    fn smoothing_groups_pick_flat_or_smooth_normals() {
+        // A roof of two faces sharing the ridge from (0, 0, 0) to (1, 0, 0).
+        let roof = "v 0 0 0\nv 1 0 0\nv 0 -1 1\nv 0 1 1\n";
+        let half = std::f32::consts::FRAC_1_SQRT_2;
+
+        let smooth = parse(&format!("{roof}f 1 3 2\nf 1 2 4\n"), None).unwrap();
+        let primitive = &smooth.meshes[0].primitives[0];
+        assert_eq!(primitive.vertices.len(), 4);
+        for vertex in &primitive.vertices {
+            if vertex.position.y == 0.0 {
+                assert_vector_close(vertex.normal, Vector3::unit_z());
+            }
+        }
+
+        let flat = parse(&format!("{roof}s off\nf 1 3 2\nf 1 2 4\n"), None).unwrap();
+        let primitive = &flat.meshes[0].primitives[0];
+        assert_eq!(primitive.vertices.len(), 6);
+        for (i, triangle) in corners(primitive).chunks_exact(3).enumerate() {
+            let expected = if i == 0 { half } else { -half };
+            for corner in triangle {
+                let vertex = primitive
+                    .vertices
+                    .iter()
+                    .find(|v| v.position == *corner && (v.normal.y - expected).abs() < EPSILON);
+                assert!(vertex.is_some(), "triangle {i} lost its face normal");
+            }
+        }
+
+        // Explicit normals still win inside a flat group.
+        let explicit = parse(&format!("{roof}vn 1 0 0\ns 0\nf 1//1 3 2\n"), None).unwrap();
+        let primitive = &explicit.meshes[0].primitives[0];
+        let first = &primitive.vertices[primitive.indices.iter().next().unwrap() as usize];
+        assert_vector_close(first.normal, Vector3::unit_x());
+    }
+
+    #[test]
+    fn generates_tangents_from_uvs() {
+        let model = load(fixture("quads.obj")).unwrap();
+        for primitive in &model.meshes[0].primitives {
+            for vertex in &primitive.vertices {
+                // u grows along +x on both quads.
+                assert_vector_close(vertex.tangent.truncate(), Vector3::unit_x());
+                assert_eq!(vertex.tangent.w.abs(), 1.0);
+            }
+        }
+    }
+
+    #[test]
+    fn reuses_materials_by_name() {
+        let source = format!("{TRIANGLE}usemtl a\nf 1 2 3\nusemtl b\nf 1 2 3\nusemtl a\nf 1 2 3\n");
+        let model = parse(&source, None).unwrap();
+        assert_eq!(model.materials.len(), 2);
+        let materials: Vec<Option<usize>> = model.meshes[0]
+            .primitives
+            .iter()
+            .map(|p| p.material)
+            .collect();
+        assert_eq!(materials, [Some(0), Some(1), Some(0)]);
+    }
+}
@@ -0,0 +1,892 @@
+use std::path::{Path, PathBuf};
+
+use cgmath::{Vector2, Vector3};
+
+use crate::binary_reader::{decode_shift_jis, BinaryReader, UnexpectedEof};
+use crate::model::DrawRange;
+
+pub const NO_BONE: u16 = 0xFFFF;
+pub const NO_TOON: u8 = 0xFF;
+pub const TOON_TEXTURE_COUNT: usize = 10;
+
+#[derive(Debug)]
+pub enum PmdError {
+    Io {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    InvalidMagic([u8; 3]),
+    UnexpectedEof(UnexpectedEof),
+    IndexOutOfRange {
+        index: u16,
+        vertex_count: usize,
+    },
+    MaterialRangeOverflow {
+        material: usize,
+    },
+    BoneOutOfRange {
+        bone: u16,
+        bone_count: usize,
+    },
+}
+
+impl std::fmt::Display for PmdError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PmdError::Io { path, error } => write!(f, "{}: {}", path.display(), error),
+            PmdError::InvalidMagic(magic) => write!(f, "not a PMD file (magic {:?})", magic),
+            PmdError::UnexpectedEof(eof) => eof.fmt(f),
+            PmdError::IndexOutOfRange {
+                index,
+                vertex_count,
+            } => write!(
+                f,
+                "index {} out of range for {} vertices",
+                index, vertex_count
+            ),
+            PmdError::MaterialRangeOverflow { material } => {
+                write!(
+                    f,
+                    "material {} draws past the end of the index list",
+                    material
+                )
+            }
+            PmdError::BoneOutOfRange { bone, bone_count } => {
+                write!(f, "bone {} out of range for {} bones", bone, bone_count)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PmdError {}
+
+impl From<UnexpectedEof> for PmdError {
+    fn from(eof: UnexpectedEof) -> Self {
+        PmdError::UnexpectedEof(eof)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, PmdError>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PmdHeader {
+    pub version: f32,
+    pub name: String,
+    pub comment: String,
+    pub english_name: Option<String>,
+    pub english_comment: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PmdVertex {
+    pub position: Vector3<f32>,
+    pub normal: Vector3<f32>,
+    pub uv: Vector2<f32>,
+    pub bones: [u16; 2],
+    pub weight: u8,
+    pub edge_flag: u8,
+}
+
+impl PmdVertex {
+    pub fn bone_weights(&self) -> [f32; 2] {
+        let weight = self.weight.min(100) as f32 / 100.0;
+        [weight, 1.0 - weight]
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PmdMaterial {
+    pub diffuse: Vector3<f32>,
+    pub alpha: f32,
+    pub specularity: f32,
+    pub specular: Vector3<f32>,
+    pub ambient: Vector3<f32>,
+    pub toon_index: u8,
+    pub edge_flag: u8,
+    pub index_count: u32,
+    pub texture_file: Option<String>,
+    pub sphere_file: Option<String>,
+}
+
+impl PmdMaterial {
+    pub fn sphere_mode(&self) -> SphereMode {
+        match &self.sphere_file {
+            Some(file) if file.to_ascii_lowercase().ends_with(".spa") => SphereMode::Add,
+            Some(_) => SphereMode::Multiply,
+            None => SphereMode::None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SphereMode {
+    None,
+    Multiply,
+    Add,
+    SubTexture,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BoneKind {
+    Rotate,
+    RotateTranslate,
+    Ik,
+    Unknown,
+    IkAffected,
+    RotateAffected,
+    IkTarget,
+    Invisible,
+    Twist,
+    RotateFollow,
+    Other(u8),
+}
+
+impl BoneKind {
+    fn from_u8(kind: u8) -> Self {
+        match kind {
+            0 => BoneKind::Rotate,
+            1 => BoneKind::RotateTranslate,
+            2 => BoneKind::Ik,
+            3 => BoneKind::Unknown,
+            4 => BoneKind::IkAffected,
+            5 => BoneKind::RotateAffected,
+            6 => BoneKind::IkTarget,
+            7 => BoneKind::Invisible,
+            8 => BoneKind::Twist,
+            9 => BoneKind::RotateFollow,
+            other => BoneKind::Other(other),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PmdBone {
+    pub name: String,
+    pub english_name: Option<String>,
+    pub parent: Option<u16>,
+    pub tail: Option<u16>,
+    pub kind: BoneKind,
+    pub ik_parent: u16,
+    pub position: Vector3<f32>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PmdIk {
+    pub bone: u16,
+    pub target: u16,
+    pub iterations: u16,
+    pub limit_angle: f32,
+    pub chain: Vec<u16>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MorphCategory {
+    Base,
+    Eyebrow,
+    Eye,
+    Lip,
+    Other,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PmdMorph {
+    pub name: String,
+    pub english_name: Option<String>,
+    pub category: MorphCategory,
+    pub offsets: Vec<(u32, Vector3<f32>)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PmdModel {
+    pub header: PmdHeader,
+    pub vertices: Vec<PmdVertex>,
+    pub indices: Vec<u16>,
+    pub materials: Vec<PmdMaterial>,
+    pub bones: Vec<PmdBone>,
+    pub ik_chains: Vec<PmdIk>,
+    pub morphs: Vec<PmdMorph>,
+    pub toon_textures: [String; TOON_TEXTURE_COUNT],
+}
+
+impl PmdModel {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path).map_err(|error| PmdError::Io {
+            path: path.to_path_buf(),
+            error,
+        })?;
+        Self::parse(&bytes)
+    }
+
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        let mut reader = BinaryReader::new(bytes);
+
+        let magic = reader.array::<3>()?;
+        if &magic != b"Pmd" {
+            return Err(PmdError::InvalidMagic(magic));
+        }
+        let mut header = PmdHeader {
+            version: reader.f32()?,
+            name: read_string(&mut reader, 20)?,
+            comment: read_string(&mut reader, 256)?,
+            english_name: None,
+            english_comment: None,
+        };
+
+        let vertex_count = reader.u32()? as usize;
+        let vertices = (0..vertex_count)
+            .map(|_| {
+                Ok(PmdVertex {
+                    position: reader.vector3()?,
+                    normal: reader.vector3()?,
+                    uv: reader.vector2()?,
+                    bones: [reader.u16()?, reader.u16()?],
+                    weight: reader.u8()?,
+                    edge_flag: reader.u8()?,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let index_count = reader.u32()? as usize;
+        let indices = (0..index_count)
+            .map(|_| reader.u16())
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        if let Some(&index) = indices.iter().find(|&&i| i as usize >= vertex_count) {
+            return Err(PmdError::IndexOutOfRange {
+                index,
+                vertex_count,
+            });
+        }
+
+        let material_count = reader.u32()? as usize;
+        let materials = (0..material_count)
+            .map(|_| {
+                let diffuse = reader.vector3()?;
+                let alpha = reader.f32()?;
+                let specularity = reader.f32()?;
+                let specular = reader.vector3()?;
+                let ambient = reader.vector3()?;
+                let toon_index = reader.u8()?;
+                let edge_flag = reader.u8()?;
+                let index_count = reader.u32()?;
+                let (texture_file, sphere_file) =
+                    split_texture_file(&read_string(&mut reader, 20)?);
+                Ok(PmdMaterial {
+                    diffuse,
+                    alpha,
+                    specularity,
+                    specular,
+                    ambient,
+                    toon_index,
+                    edge_flag,
+                    index_count,
+                    texture_file,
+                    sphere_file,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let bone_count = reader.u16()? as usize;
+        let mut bones = (0..bone_count)
+            .map(|_| {
+                Ok(PmdBone {
+                    name: read_string(&mut reader, 20)?,
+                    english_name: None,
+                    parent: optional_bone(reader.u16()?),
+                    tail: optional_bone(reader.u16()?).filter(|&tail| tail != 0),
+                    kind: BoneKind::from_u8(reader.u8()?),
+                    ik_parent: reader.u16()?,
+                    position: reader.vector3()?,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        for vertex in &vertices {
+            check_bones(&vertex.bones, bone_count)?;
+        }
+        for bone in &bones {
+            check_bones(bone.parent.iter().chain(&bone.tail), bone_count)?;
+        }
+
+        let ik_count = reader.u16()? as usize;
+        let ik_chains = (0..ik_count)
+            .map(|_| {
+                let bone = reader.u16()?;
+                let target = reader.u16()?;
+                let chain_length = reader.u8()? as usize;
+                let iterations = reader.u16()?;
+                let limit_angle = reader.f32()?;
+                let chain = (0..chain_length)
+                    .map(|_| reader.u16())
+                    .collect::<std::result::Result<Vec<_>, _>>()?;
+                check_bones([&bone, &target].into_iter().chain(&chain), bone_count)?;
+                Ok(PmdIk {
+                    bone,
+                    target,
+                    iterations,
+                    limit_angle,
+                    chain,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let morph_count = reader.u16()? as usize;
+        let mut morphs = (0..morph_count)
+            .map(|_| {
+                let name = read_string(&mut reader, 20)?;
+                let offset_count = reader.u32()? as usize;
+                let category = match reader.u8()? {
+                    0 => MorphCategory::Base,
+                    1 => MorphCategory::Eyebrow,
+                    2 => MorphCategory::Eye,
+                    3 => MorphCategory::Lip,
+                    _ => MorphCategory::Other,
+                };
+                let offsets = (0..offset_count)
+                    .map(|_| Ok((reader.u32()?, reader.vector3()?)))
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(PmdMorph {
+                    name,
+                    english_name: None,
+                    category,
+                    offsets,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let morph_display_count = reader.u8()? as usize;
+        reader.skip(morph_display_count * 2)?;
+        let bone_group_count = reader.u8()? as usize;
+        reader.skip(bone_group_count * 50)?;
+        let bone_display_count = reader.u32()? as usize;
+        reader.skip(bone_display_count * 3)?;
+
+        let mut toon_textures = default_toon_textures();
+        if reader.is_empty() {
+            return Ok(PmdModel {
+                header,
+                vertices,
+                indices,
+                materials,
+                bones,
+                ik_chains,
+                morphs,
+                toon_textures,
+            });
+        }
+
+        if reader.u8()? != 0 {
+            header.english_name = Some(read_string(&mut reader, 20)?);
+            header.english_comment = Some(read_string(&mut reader, 256)?);
+            for bone in &mut bones {
+                bone.english_name = Some(read_string(&mut reader, 20)?);
+            }
+            for morph in morphs.iter_mut().skip(1) {
+                morph.english_name = Some(read_string(&mut reader, 20)?);
+            }
+            reader.skip(bone_group_count * 50)?;
+        }
+
+        if !reader.is_empty() {
+            for toon in &mut toon_textures {
+                *toon = read_string(&mut reader, 100)?;
+            }
+        }
+
+        Ok(PmdModel {
+            header,
+            vertices,
+            indices,
+            materials,
+            bones,
+            ik_chains,
+            morphs,
+            toon_textures,
+        })
+    }
+
+    pub fn draw_ranges(&self) -> Result<Vec<DrawRange>> {
+        let mut start_index = 0u32;
+        self.materials
+            .iter()
+            .enumerate()
+            .map(|(material, m)| {
+                let range = DrawRange {
+                    start_index,
+                    index_count: m.index_count,
+                    material,
+                };
+                start_index = start_index
+                    .checked_add(m.index_count)
+                    .filter(|&end| end as usize <= self.indices.len())
+                    .ok_or(PmdError::MaterialRangeOverflow { material })?;
+                Ok(range)
+            })
+            .collect()
+    }
+
+    pub fn toon_file(&self, material: &PmdMaterial) -> Option<&str> {
+        if material.toon_index == NO_TOON {
+            return None;
+        }
+        self.toon_textures
+            .get(material.toon_index as usize)
+            .map(String::as_str)
+            .filter(|file| !file.is_empty())
+    }
+}
+
+pub fn resolve_texture_path(model_path: &Path, file: &str) -> PathBuf {
+    let file = file.replace('\\', "/");
+    match model_path.parent() {
+        Some(dir) => dir.join(file),
+        None => PathBuf::from(file),
+    }
+}
+
+fn default_toon_textures() -> [String; TOON_TEXTURE_COUNT] {
+    std::array::from_fn(|i| format!("toon{:02}.bmp", i + 1))
+}
+
+fn optional_bone(index: u16) -> Option<u16> {
+    (index != NO_BONE).then_some(index)
+}
+
+fn check_bones<'a>(bones: impl IntoIterator<Item = &'a u16>, bone_count: usize) -> Result<()> {
+    match bones.into_iter().find(|&&bone| bone as usize >= bone_count) {
+        Some(&bone) => Err(PmdError::BoneOutOfRange { bone, bone_count }),
+        None => Ok(()),
+    }
+}
+
+fn read_string(reader: &mut BinaryReader, len: usize) -> Result<String> {
+    Ok(decode_shift_jis(reader.fixed_str(len)?))
+}
+
+fn split_texture_file(file: &str) -> (Option<String>, Option<String>) {
+    let is_sphere = |name: &str| {
+        let name = name.to_ascii_lowercase();
+        name.ends_with(".sph") || name.ends_with(".spa")
+    };
+    let non_empty = |name: &str| (!name.is_empty()).then(|| name.to_string());
+    match file.split_once('*') {
+        Some((first, second)) if is_sphere(first) => (non_empty(second), non_empty(first)),
+        Some((first, second)) => (non_empty(first), non_empty(second)),
+        None if is_sphere(file) => (None, non_empty(file)),
+        None => (non_empty(file), None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn push_str(out: &mut Vec<u8>, text: &str, len: usize) {
+        let (encoded, _, _) = encoding_rs::SHIFT_JIS.encode(text);
+        let mut field = encoded.into_owned();
+        field.resize(len, 0);
+        out.extend_from_slice(&field);
+    }
+
+    fn push_f32s(out: &mut Vec<u8>, values: &[f32]) {
+        for value in values {
+            out.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+
+    struct Bone {
+        name: &'static str,
+        parent: u16,
+        tail: u16,
+        kind: u8,
+    }
+
+    struct Ik {
+        bone: u16,
+        target: u16,
+        chain: Vec<u16>,
+    }
+
+    /// Writes a PMD file with one vertex per `vertex_bones` entry.
+    struct PmdBuilder {
+        vertex_bones: Vec<([u16; 2], u8)>,
+        indices: Vec<u16>,
+        materials: Vec<(u32, &'static str, u8)>,
+        bones: Vec<Bone>,
+        iks: Vec<Ik>,
+        morphs: Vec<(&'static str, u8, Vec<u32>)>,
+        english: bool,
+        toons: bool,
+    }
+
+    impl Default for PmdBuilder {
+        fn default() -> Self {
+            PmdBuilder {
+                vertex_bones: vec![([0, 1], 100), ([0, 1], 50), ([1, 0], 0)],
+                indices: vec![0, 1, 2],
+                materials: vec![(3, "body.bmp*face.spa", 2)],
+                bones: vec![
+                    Bone {
+                        name: "センター",
+                        parent: NO_BONE,
+                        tail: 1,
+                        kind: 1,
+                    },
+                    Bone {
+                        name: "頭",
+                        parent: 0,
+                        tail: 0,
+                        kind: 0,
+                    },
+                ],
+                iks: vec![Ik {
+                    bone: 1,
+                    target: 1,
+                    chain: vec![0],
+                }],
+                morphs: vec![("base", 0, vec![0, 1]), ("まばたき", 2, vec![0])],
+                english: true,
+                toons: true,
+            }
+        }
+    }
+
+    impl PmdBuilder {
+        fn bytes(&self) -> Vec<u8> {
+            let mut out = b"Pmd".to_vec();
+            push_f32s(&mut out, &[1.0]);
+            push_str(&mut out, "初音ミク", 20);
+            push_str(&mut out, "comment", 256);
+
+            out.extend_from_slice(&(self.vertex_bones.len() as u32).to_le_bytes());
+            for (i, (bones, weight)) in self.vertex_bones.iter().enumerate() {
+                push_f32s(&mut out, &[i as f32, 1.0, 2.0, 0.0, 1.0, 0.0, 0.25, 0.75]);
+                out.extend_from_slice(&bones[0].to_le_bytes());
+                out.extend_from_slice(&bones[1].to_le_bytes());
+                out.extend_from_slice(&[*weight, 1]);
+            }
+
+            out.extend_from_slice(&(self.indices.len() as u32).to_le_bytes());
+            for index in &self.indices {
+                out.extend_from_slice(&index.to_le_bytes());
+            }
+
+            out.extend_from_slice(&(self.materials.len() as u32).to_le_bytes());
+            for (index_count, file, toon) in &self.materials {
+                push_f32s(
+                    &mut out,
+                    &[1.0, 0.5, 0.25, 0.8, 5.0, 0.1, 0.1, 0.1, 0.2, 0.2, 0.2],
+                );
+                out.extend_from_slice(&[*toon, 1]);
+                out.extend_from_slice(&index_count.to_le_bytes());
+                push_str(&mut out, file, 20);
+            }
+
+            out.extend_from_slice(&(self.bones.len() as u16).to_le_bytes());
+            for bone in &self.bones {
+                push_str(&mut out, bone.name, 20);
+                out.extend_from_slice(&bone.parent.to_le_bytes());
+                out.extend_from_slice(&bone.tail.to_le_bytes());
+                out.push(bone.kind);
+                out.extend_from_slice(&0u16.to_le_bytes());
+                push_f32s(&mut out, &[0.0, 10.0, 0.0]);
+            }
+
+            out.extend_from_slice(&(self.iks.len() as u16).to_le_bytes());
+            for ik in &self.iks {
+                out.extend_from_slice(&ik.bone.to_le_bytes());
+                out.extend_from_slice(&ik.target.to_le_bytes());
+                out.push(ik.chain.len() as u8);
+                out.extend_from_slice(&40u16.to_le_bytes());
+                push_f32s(&mut out, &[0.5]);
+                for link in &ik.chain {
+                    out.extend_from_slice(&link.to_le_bytes());
+                }
+            }
+
+            out.extend_from_slice(&(self.morphs.len() as u16).to_le_bytes());
+            for (name, category, offsets) in &self.morphs {
+                push_str(&mut out, name, 20);
+                out.extend_from_slice(&(offsets.len() as u32).to_le_bytes());
+                out.push(*category);
+                for offset in offsets {
+                    out.extend_from_slice(&offset.to_le_bytes());
+                    push_f32s(&mut out, &[0.0, 0.5, 0.0]);
+                }
+            }
+
+            // Morph display list, one bone group and one displayed bone.
+            out.extend_from_slice(&[1, 1, 0, 1]);
+            push_str(&mut out, "group", 50);
+            out.extend_from_slice(&1u32.to_le_bytes());
+            out.extend_from_slice(&[1, 0, 1]);
+
+            if self.english {
+                out.push(1);
+                push_str(&mut out, "Miku", 20);
+                push_str(&mut out, "English comment", 256);
+                for i in 0..self.bones.len() {
+                    push_str(&mut out, &format!("bone{i}"), 20);
+                }
+                for i in 1..self.morphs.len() {
+                    push_str(&mut out, &format!("morph{i}"), 20);
+                }
+                push_str(&mut out, "Group", 50);
+            }
+            if self.toons {
+                if !self.english {
+                    out.push(0);
+                }
+                for i in 0..TOON_TEXTURE_COUNT {
+                    push_str(&mut out, &format!("custom{i}.bmp"), 100);
+                }
+            }
+            out
+        }
+    }
+
+    #[test]
+    fn parses_every_section() {
+        let model = PmdModel::parse(&PmdBuilder::default().bytes()).unwrap();
+
+        assert_eq!(model.header.version, 1.0);
+        assert_eq!(model.header.name, "初音ミク");
+        assert_eq!(model.header.comment, "comment");
+        assert_eq!(model.header.english_name.as_deref(), Some("Miku"));
+        assert_eq!(
+            model.header.english_comment.as_deref(),
+            Some("English comment")
+        );
+
+        assert_eq!(model.vertices.len(), 3);
+        let vertex = model.vertices[1];
+        assert_eq!(vertex.position, Vector3::new(1.0, 1.0, 2.0));
+        assert_eq!(vertex.normal, Vector3::new(0.0, 1.0, 0.0));
+        assert_eq!(vertex.uv, Vector2::new(0.25, 0.75));
+        assert_eq!(vertex.bones, [0, 1]);
+        assert_eq!(vertex.bone_weights(), [0.5, 0.5]);
+        assert_eq!(model.vertices[0].bone_weights(), [1.0, 0.0]);
+        assert_eq!(model.indices, [0, 1, 2]);
+
+        let material = &model.materials[0];
+        assert_eq!(material.diffuse, Vector3::new(1.0, 0.5, 0.25));
+        assert_eq!(material.alpha, 0.8);
+        assert_eq!(material.specularity, 5.0);
+        assert_eq!(material.index_count, 3);
+        assert_eq!(material.texture_file.as_deref(), Some("body.bmp"));
+        assert_eq!(material.sphere_file.as_deref(), Some("face.spa"));
+        assert_eq!(material.sphere_mode(), SphereMode::Add);
+        assert_eq!(model.toon_file(material), Some("custom2.bmp"));
+
+        assert_eq!(model.bones.len(), 2);
+        assert_eq!(model.bones[0].name, "センター");
+        assert_eq!(model.bones[0].parent, None);
+        assert_eq!(model.bones[0].tail, Some(1));
+        assert_eq!(model.bones[0].kind, BoneKind::RotateTranslate);
+        assert_eq!(model.bones[0].english_name.as_deref(), Some("bone0"));
+        assert_eq!(model.bones[1].parent, Some(0));
+        // A tail of 0 means "none" in PMD.
+        assert_eq!(model.bones[1].tail, None);
+        assert_eq!(model.bones[1].position, Vector3::new(0.0, 10.0, 0.0));
+
+        assert_eq!(
+            model.ik_chains,
+            [PmdIk {
+                bone: 1,
+                target: 1,
+                iterations: 40,
+                limit_angle: 0.5,
+                chain: vec![0],
+            }]
+        );
+
+        assert_eq!(model.morphs.len(), 2);
+        assert_eq!(model.morphs[0].category, MorphCategory::Base);
+        assert_eq!(model.morphs[0].english_name, None);
+        assert_eq!(model.morphs[1].name, "まばたき");
+        assert_eq!(model.morphs[1].category, MorphCategory::Eye);
+        assert_eq!(model.morphs[1].english_name.as_deref(), Some("morph1"));
+        assert_eq!(model.morphs[1].offsets, [(0, Vector3::new(0.0, 0.5, 0.0))]);
+
+        assert_eq!(model.toon_textures[9], "custom9.bmp");
+    }
+
+    #[test]
+    fn optional_sections_fall_back_to_defaults() {
+        let model = PmdModel::parse(
+            &PmdBuilder {
+                english: false,
+                toons: false,
+                ..Default::default()
+            }
+            .bytes(),
+        )
+        .unwrap();
+        assert_eq!(model.header.english_name, None);
+        assert_eq!(model.bones[0].english_name, None);
+        assert_eq!(model.toon_textures[0], "toon01.bmp");
+        assert_eq!(model.toon_textures[9], "toon10.bmp");
+
+        let model = PmdModel::parse(
+            &PmdBuilder {
+                english: false,
+                ..Default::default()
+            }
+            .bytes(),
+        )
+        .unwrap();
+        assert_eq!(model.header.english_name, None);
+        assert_eq!(model.toon_textures[0], "custom0.bmp");
+    }
+
+    #[test]
+    fn rejects_invalid_magic() {
+        let mut bytes = PmdBuilder::default().bytes();
+        bytes[..3].copy_from_slice(b"Pmx");
+        assert!(matches!(
+            PmdModel::parse(&bytes),
+            Err(PmdError::InvalidMagic(magic)) if &magic == b"Pmx"
+        ));
+    }
+
+    #[test]
+    fn rejects_truncated_files() {
+        let bytes = PmdBuilder::default().bytes();
+        for len in [0, 2, 100, 300, 350] {
+            assert!(matches!(
+                PmdModel::parse(&bytes[..len]),
+                Err(PmdError::UnexpectedEof(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn rejects_out_of_range_indices() {
+        let bytes = PmdBuilder {
+            indices: vec![0, 1, 3],
+            ..Default::default()
+        }
+        .bytes();
+        assert!(matches!(
+            PmdModel::parse(&bytes),
+            Err(PmdError::IndexOutOfRange {
+                index: 3,
+                vertex_count: 3
+            })
+        ));
+    }
+
+    #[test]
+    fn rejects_out_of_range_vertex_bones() {
+        let bytes = PmdBuilder {
+            vertex_bones: vec![([0, 1], 100), ([0, 2], 50), ([1, 0], 0)],
+            ..Default::default()
+        }
+        .bytes();
+        assert!(matches!(
+            PmdModel::parse(&bytes),
+            Err(PmdError::BoneOutOfRange {
+                bone: 2,
+                bone_count: 2
+            })
+        ));
+    }
+
+    #[test]
+    fn rejects_out_of_range_bone_links() {
+        let mut builder = PmdBuilder::default();
+        builder.bones[1].parent = 7;
+        assert!(matches!(
+            PmdModel::parse(&builder.bytes()),
+            Err(PmdError::BoneOutOfRange { bone: 7, .. })
+        ));
+
+        let mut builder = PmdBuilder::default();
+        builder.iks[0].chain = vec![0, 5];
+        assert!(matches!(
+            PmdModel::parse(&builder.bytes()),
+            Err(PmdError::BoneOutOfRange { bone: 5, .. })
+        ));
+    }
+
+    #[test]
+    fn parses_more_bones_than_the_skinning_palette() {
+        // The skinning palette limit is enforced by the renderer, not the loader.
+        let bones = (0..300)
+            .map(|i| Bone {
+                name: "bone",
+                parent: if i == 0 { NO_BONE } else { 0 },
+                tail: 0,
+                kind: 0,
+            })
+            .collect();
+        let bytes = PmdBuilder {
+            bones,
+            english: false,
+            ..Default::default()
+        }
+        .bytes();
+        assert_eq!(PmdModel::parse(&bytes).unwrap().bones.len(), 300);
+    }
+
+    #[test]
+    fn draw_ranges_follow_material_order() {
+        let bytes = PmdBuilder {
+            vertex_bones: vec![([0, 0], 100); 4],
+            indices: vec![0, 1, 2, 0, 2, 3],
+            materials: vec![(3, "a.bmp", NO_TOON), (3, "b.sph", 0)],
+            ..Default::default()
+        }
+        .bytes();
+        let model = PmdModel::parse(&bytes).unwrap();
+        assert_eq!(
+            model.draw_ranges().unwrap(),
+            [
+                DrawRange {
+                    start_index: 0,
+                    index_count: 3,
+                    material: 0
+                },
+                DrawRange {
+                    start_index: 3,
+                    index_count: 3,
+                    material: 1
+                },
+            ]
+        );
+        assert_eq!(model.toon_file(&model.materials[0]), None);
+        assert_eq!(model.materials[1].texture_file, None);
+        assert_eq!(model.materials[1].sphere_mode(), SphereMode::Multiply);
+
+        let bytes = PmdBuilder {
+            materials: vec![(3, "", 0), (1, "", 0)],
+            ..Default::default()
+        }
+        .bytes();
+        let model = PmdModel::parse(&bytes).unwrap();
+        assert!(matches!(
+            model.draw_ranges(),
+            Err(PmdError::MaterialRangeOverflow { material: 1 })
+        ));
+    }
+
+    #[test]
+    fn splits_texture_and_sphere_files() {
+        assert_eq!(split_texture_file(""), (None, None));
+        assert_eq!(
+            split_texture_file("a.png"),
+            (Some("a.png".to_string()), None)
+        );
+        assert_eq!(
+            split_texture_file("a.SPH"),
+            (None, Some("a.SPH".to_string()))
+        );
+        assert_eq!(
+            split_texture_file("s.spa*a.png"),
+            (Some("a.png".to_string()), Some("s.spa".to_string()))
+        );
+        assert_eq!(
+            resolve_texture_path(Path::new("models/miku.pmd"), "tex\\body.png"),
+            Path::new("models/tex/body.png")
+        );
+    }
+}
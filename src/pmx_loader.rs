@@ -0,0 +1,1371 @@
+use std::path::{Path, PathBuf};
+
+use cgmath::{Quaternion, Vector2, Vector3, Vector4};
+
+use crate::binary_reader::{BinaryReader, UnexpectedEof};
+use crate::model::{DrawRange, Indices};
+use crate::pmd_loader::SphereMode;
+
+pub const MAX_ADDITIONAL_UVS: usize = 4;
+
+#[derive(Debug)]
+pub enum PmxError {
+    Io {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    InvalidMagic([u8; 4]),
+    UnsupportedVersion(f32),
+    InvalidEncoding(u8),
+    InvalidIndexSize {
+        kind: &'static str,
+        size: u8,
+    },
+    InvalidAdditionalUvCount(u8),
+    InvalidWeightType(u8),
+    InvalidMorphType(u8),
+    InvalidCount(i32),
+    IndexOutOfRange {
+        index: u32,
+        vertex_count: usize,
+    },
+    MaterialRangeOverflow {
+        material: usize,
+    },
+    BoneOutOfRange {
+        bone: u32,
+        bone_count: usize,
+    },
+    UnexpectedEof(UnexpectedEof),
+}
+
+impl std::fmt::Display for PmxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PmxError::Io { path, error } => write!(f, "{}: {}", path.display(), error),
+            PmxError::InvalidMagic(magic) => write!(f, "not a PMX file (magic {:?})", magic),
+            PmxError::UnsupportedVersion(version) => {
+                write!(f, "unsupported PMX version {}", version)
+            }
+            PmxError::InvalidEncoding(encoding) => {
+                write!(f, "unknown text encoding {}", encoding)
+            }
+            PmxError::InvalidIndexSize { kind, size } => {
+                write!(f, "invalid {} index size {}", kind, size)
+            }
+            PmxError::InvalidAdditionalUvCount(count) => {
+                write!(f, "invalid additional UV count {}", count)
+            }
+            PmxError::InvalidWeightType(kind) => write!(f, "unknown weight type {}", kind),
+            PmxError::InvalidMorphType(kind) => write!(f, "unknown morph type {}", kind),
+            PmxError::InvalidCount(count) => write!(f, "invalid element count {}", count),
+            PmxError::IndexOutOfRange {
+                index,
+                vertex_count,
+            } => write!(
+                f,
+                "index {} out of range for {} vertices",
+                index, vertex_count
+            ),
+            PmxError::MaterialRangeOverflow { material } => write!(
+                f,
+                "material {} draws past the end of the index list",
+                material
+            ),
+            PmxError::BoneOutOfRange { bone, bone_count } => {
+                write!(f, "bone {} out of range for {} bones", bone, bone_count)
+            }
+            PmxError::UnexpectedEof(eof) => eof.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for PmxError {}
+
+impl From<UnexpectedEof> for PmxError {
+    fn from(eof: UnexpectedEof) -> Self {
+        PmxError::UnexpectedEof(eof)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, PmxError>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextEncoding {
+    Utf16,
+    Utf8,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PmxGlobals {
+    pub encoding: TextEncoding,
+    pub additional_uvs: u8,
+    pub vertex_index_size: u8,
+    pub texture_index_size: u8,
+    pub material_index_size: u8,
+    pub bone_index_size: u8,
+    pub morph_index_size: u8,
+    pub rigid_body_index_size: u8,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PmxHeader {
+    pub version: f32,
+    pub globals: PmxGlobals,
+    pub name: String,
+    pub english_name: String,
+    pub comment: String,
+    pub english_comment: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Weight {
+    Bdef1 {
+        bone: u32,
+    },
+    Bdef2 {
+        bones: [u32; 2],
+        weight: f32,
+    },
+    Bdef4 {
+        bones: [u32; 4],
+        weights: [f32; 4],
+    },
+    Sdef {
+        bones: [u32; 2],
+        weight: f32,
+        c: Vector3<f32>,
+        r0: Vector3<f32>,
+        r1: Vector3<f32>,
+    },
+    Qdef {
+        bones: [u32; 4],
+        weights: [f32; 4],
+    },
+}
+
+impl Weight {
+    pub fn bones_and_weights(&self) -> ([u32; 4], [f32; 4]) {
+        match *self {
+            Weight::Bdef1 { bone } => ([bone, 0, 0, 0], [1.0, 0.0, 0.0, 0.0]),
+            Weight::Bdef2 { bones, weight } | Weight::Sdef { bones, weight, .. } => {
+                ([bones[0], bones[1], 0, 0], [weight, 1.0 - weight, 0.0, 0.0])
+            }
+            Weight::Bdef4 { bones, weights } | Weight::Qdef { bones, weights } => (bones, weights),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PmxVertex {
+    pub position: Vector3<f32>,
+    pub normal: Vector3<f32>,
+    pub uv: Vector2<f32>,
+    pub additional_uvs: [Vector4<f32>; MAX_ADDITIONAL_UVS],
+    pub weight: Weight,
+    pub edge_scale: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Toon {
+    Shared(u8),
+    Texture(Option<u32>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PmxMaterial {
+    pub name: String,
+    pub english_name: String,
+    pub diffuse: Vector4<f32>,
+    pub specular: Vector3<f32>,
+    pub specularity: f32,
+    pub ambient: Vector3<f32>,
+    pub flags: u8,
+    pub edge_color: Vector4<f32>,
+    pub edge_size: f32,
+    pub texture: Option<u32>,
+    pub sphere_texture: Option<u32>,
+    pub sphere_mode: SphereMode,
+    pub toon: Toon,
+    pub memo: String,
+    pub index_count: u32,
+}
+
+impl PmxMaterial {
+    pub const NO_CULL: u8 = 0x01;
+    pub const GROUND_SHADOW: u8 = 0x02;
+    pub const DRAW_SHADOW: u8 = 0x04;
+    pub const RECEIVE_SHADOW: u8 = 0x08;
+    pub const DRAW_EDGE: u8 = 0x10;
+    pub const VERTEX_COLOR: u8 = 0x20;
+    pub const POINT_DRAW: u8 = 0x40;
+    pub const LINE_DRAW: u8 = 0x80;
+
+    pub fn double_sided(&self) -> bool {
+        self.flags & Self::NO_CULL != 0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BoneTail {
+    Bone(Option<u32>),
+    Offset(Vector3<f32>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InheritTransform {
+    pub parent: Option<u32>,
+    pub influence: f32,
+    pub rotation: bool,
+    pub translation: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LocalAxes {
+    pub x: Vector3<f32>,
+    pub z: Vector3<f32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IkLink {
+    pub bone: Option<u32>,
+    pub limits: Option<(Vector3<f32>, Vector3<f32>)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PmxIk {
+    pub target: Option<u32>,
+    pub iterations: i32,
+    pub limit_angle: f32,
+    pub links: Vec<IkLink>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PmxBone {
+    pub name: String,
+    pub english_name: String,
+    pub position: Vector3<f32>,
+    pub parent: Option<u32>,
+    pub layer: i32,
+    pub flags: u16,
+    pub tail: BoneTail,
+    pub inherit: Option<InheritTransform>,
+    pub fixed_axis: Option<Vector3<f32>>,
+    pub local_axes: Option<LocalAxes>,
+    pub external_parent: Option<i32>,
+    pub ik: Option<PmxIk>,
+}
+
+impl PmxBone {
+    pub const INDEXED_TAIL: u16 = 0x0001;
+    pub const ROTATABLE: u16 = 0x0002;
+    pub const TRANSLATABLE: u16 = 0x0004;
+    pub const VISIBLE: u16 = 0x0008;
+    pub const ENABLED: u16 = 0x0010;
+    pub const IK: u16 = 0x0020;
+    pub const INHERIT_ROTATION: u16 = 0x0100;
+    pub const INHERIT_TRANSLATION: u16 = 0x0200;
+    pub const FIXED_AXIS: u16 = 0x0400;
+    pub const LOCAL_AXES: u16 = 0x0800;
+    pub const PHYSICS_AFTER_DEFORM: u16 = 0x1000;
+    pub const EXTERNAL_PARENT: u16 = 0x2000;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MaterialOperation {
+    Multiply,
+    Add,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MaterialMorph {
+    pub material: Option<u32>,
+    pub operation: MaterialOperation,
+    pub diffuse: Vector4<f32>,
+    pub specular: Vector3<f32>,
+    pub specularity: f32,
+    pub ambient: Vector3<f32>,
+    pub edge_color: Vector4<f32>,
+    pub edge_size: f32,
+    pub texture_tint: Vector4<f32>,
+    pub sphere_tint: Vector4<f32>,
+    pub toon_tint: Vector4<f32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ImpulseMorph {
+    pub rigid_body: Option<u32>,
+    pub local: bool,
+    pub velocity: Vector3<f32>,
+    pub torque: Vector3<f32>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MorphOffsets {
+    Group(Vec<(Option<u32>, f32)>),
+    Vertex(Vec<(u32, Vector3<f32>)>),
+    Bone(Vec<(Option<u32>, Vector3<f32>, Quaternion<f32>)>),
+    Uv {
+        channel: u8,
+        offsets: Vec<(u32, Vector4<f32>)>,
+    },
+    Material(Vec<MaterialMorph>),
+    Flip(Vec<(Option<u32>, f32)>),
+    Impulse(Vec<ImpulseMorph>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PmxMorph {
+    pub name: String,
+    pub english_name: String,
+    pub panel: u8,
+    pub offsets: MorphOffsets,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameElement {
+    Bone(Option<u32>),
+    Morph(Option<u32>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DisplayFrame {
+    pub name: String,
+    pub english_name: String,
+    pub special: bool,
+    pub elements: Vec<FrameElement>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RigidShape {
+    Sphere,
+    Box,
+    Capsule,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PhysicsMode {
+    FollowBone,
+    Physics,
+    PhysicsWithBone,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RigidBody {
+    pub name: String,
+    pub english_name: String,
+    pub bone: Option<u32>,
+    pub group: u8,
+    pub no_collision_mask: u16,
+    pub shape: RigidShape,
+    pub size: Vector3<f32>,
+    pub position: Vector3<f32>,
+    pub rotation: Vector3<f32>,
+    pub mass: f32,
+    pub linear_damping: f32,
+    pub angular_damping: f32,
+    pub restitution: f32,
+    pub friction: f32,
+    pub mode: PhysicsMode,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JointKind {
+    Spring6Dof,
+    SixDof,
+    PointToPoint,
+    ConeTwist,
+    Slider,
+    Hinge,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Joint {
+    pub name: String,
+    pub english_name: String,
+    pub kind: JointKind,
+    pub bodies: [Option<u32>; 2],
+    pub position: Vector3<f32>,
+    pub rotation: Vector3<f32>,
+    pub position_limits: (Vector3<f32>, Vector3<f32>),
+    pub rotation_limits: (Vector3<f32>, Vector3<f32>),
+    pub position_spring: Vector3<f32>,
+    pub rotation_spring: Vector3<f32>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PmxModel {
+    pub header: PmxHeader,
+    pub vertices: Vec<PmxVertex>,
+    pub indices: Vec<u32>,
+    pub textures: Vec<String>,
+    pub materials: Vec<PmxMaterial>,
+    pub bones: Vec<PmxBone>,
+    pub morphs: Vec<PmxMorph>,
+    pub display_frames: Vec<DisplayFrame>,
+    pub rigid_bodies: Vec<RigidBody>,
+    pub joints: Vec<Joint>,
+}
+
+struct PmxReader<'a> {
+    reader: BinaryReader<'a>,
+    globals: PmxGlobals,
+}
+
+impl<'a> PmxReader<'a> {
+    fn count(&mut self) -> Result<usize> {
+        let count = self.reader.i32()?;
+        if count < 0 || count as usize > self.reader.remaining() {
+            return Err(PmxError::InvalidCount(count));
+        }
+        Ok(count as usize)
+    }
+
+    fn list<T>(&mut self, mut read: impl FnMut(&mut Self) -> Result<T>) -> Result<Vec<T>> {
+        let count = self.count()?;
+        (0..count).map(|_| read(self)).collect()
+    }
+
+    fn text(&mut self) -> Result<String> {
+        let len = self.count()?;
+        let bytes = self.reader.bytes(len)?;
+        Ok(match self.globals.encoding {
+            TextEncoding::Utf16 => encoding_rs::UTF_16LE
+                .decode_without_bom_handling(bytes)
+                .0
+                .into_owned(),
+            TextEncoding::Utf8 => String::from_utf8_lossy(bytes).into_owned(),
+        })
+    }
+
+    fn signed_index(&mut self, size: u8) -> Result<Option<u32>> {
+        let index = match size {
+            1 => self.reader.i8()? as i32,
+            2 => self.reader.i16()? as i32,
+            _ => self.reader.i32()?,
+        };
+        Ok((index >= 0).then_some(index as u32))
+    }
+
+    fn vertex_index(&mut self) -> Result<u32> {
+        Ok(match self.globals.vertex_index_size {
+            1 => self.reader.u8()? as u32,
+            2 => self.reader.u16()? as u32,
+            _ => self.reader.i32()? as u32,
+        })
+    }
+
+    fn texture_index(&mut self) -> Result<Option<u32>> {
+        self.signed_index(self.globals.texture_index_size)
+    }
+
+    fn material_index(&mut self) -> Result<Option<u32>> {
+        self.signed_index(self.globals.material_index_size)
+    }
+
+    fn bone_index(&mut self) -> Result<Option<u32>> {
+        self.signed_index(self.globals.bone_index_size)
+    }
+
+    fn weight_bone(&mut self) -> Result<u32> {
+        Ok(self.bone_index()?.unwrap_or(0))
+    }
+
+    fn morph_index(&mut self) -> Result<Option<u32>> {
+        self.signed_index(self.globals.morph_index_size)
+    }
+
+    fn rigid_body_index(&mut self) -> Result<Option<u32>> {
+        self.signed_index(self.globals.rigid_body_index_size)
+    }
+
+    fn vertex(&mut self) -> Result<PmxVertex> {
+        let position = self.reader.vector3()?;
+        let normal = self.reader.vector3()?;
+        let uv = self.reader.vector2()?;
+        let mut additional_uvs = [Vector4::new(0.0, 0.0, 0.0, 0.0); MAX_ADDITIONAL_UVS];
+        for uv in additional_uvs
+            .iter_mut()
+            .take(self.globals.additional_uvs as usize)
+        {
+            *uv = self.reader.vector4()?;
+        }
+        let weight = match self.reader.u8()? {
+            0 => Weight::Bdef1 {
+                bone: self.weight_bone()?,
+            },
+            1 => Weight::Bdef2 {
+                bones: [self.weight_bone()?, self.weight_bone()?],
+                weight: self.reader.f32()?,
+            },
+            2 => Weight::Bdef4 {
+                bones: [
+                    self.weight_bone()?,
+                    self.weight_bone()?,
+                    self.weight_bone()?,
+                    self.weight_bone()?,
+                ],
+                weights: self.reader.vector4()?.into(),
+            },
+            3 => Weight::Sdef {
+                bones: [self.weight_bone()?, self.weight_bone()?],
+                weight: self.reader.f32()?,
+                c: self.reader.vector3()?,
+                r0: self.reader.vector3()?,
+                r1: self.reader.vector3()?,
+            },
+            4 => Weight::Qdef {
+                bones: [
+                    self.weight_bone()?,
+                    self.weight_bone()?,
+                    self.weight_bone()?,
+                    self.weight_bone()?,
+                ],
+                weights: self.reader.vector4()?.into(),
+            },
+            other => return Err(PmxError::InvalidWeightType(other)),
+        };
+        Ok(PmxVertex {
+            position,
+            normal,
+            uv,
+            additional_uvs,
+            weight,
+            edge_scale: self.reader.f32()?,
+        })
+    }
+
+    fn material(&mut self) -> Result<PmxMaterial> {
+        let name = self.text()?;
+        let english_name = self.text()?;
+        let diffuse = self.reader.vector4()?;
+        let specular = self.reader.vector3()?;
+        let specularity = self.reader.f32()?;
+        let ambient = self.reader.vector3()?;
+        let flags = self.reader.u8()?;
+        let edge_color = self.reader.vector4()?;
+        let edge_size = self.reader.f32()?;
+        let texture = self.texture_index()?;
+        let sphere_texture = self.texture_index()?;
+        let sphere_mode = match self.reader.u8()? {
+            1 => SphereMode::Multiply,
+            2 => SphereMode::Add,
+            3 => SphereMode::SubTexture,
+            _ => SphereMode::None,
+        };
+        let toon = match self.reader.u8()? {
+            0 => Toon::Texture(self.texture_index()?),
+            _ => Toon::Shared(self.reader.u8()?),
+        };
+        Ok(PmxMaterial {
+            name,
+            english_name,
+            diffuse,
+            specular,
+            specularity,
+            ambient,
+            flags,
+            edge_color,
+            edge_size,
+            texture,
+            sphere_texture,
+            sphere_mode,
+            toon,
+            memo: self.text()?,
+            index_count: self.reader.i32()?.max(0) as u32,
+        })
+    }
+
+    fn bone(&mut self) -> Result<PmxBone> {
+        let name = self.text()?;
+        let english_name = self.text()?;
+        let position = self.reader.vector3()?;
+        let parent = self.bone_index()?;
+        let layer = self.reader.i32()?;
+        let flags = self.reader.u16()?;
+        let tail = if flags & PmxBone::INDEXED_TAIL != 0 {
+            BoneTail::Bone(self.bone_index()?)
+        } else {
+            BoneTail::Offset(self.reader.vector3()?)
+        };
+        let inherit_rotation = flags & PmxBone::INHERIT_ROTATION != 0;
+        let inherit_translation = flags & PmxBone::INHERIT_TRANSLATION != 0;
+        let inherit = if inherit_rotation || inherit_translation {
+            Some(InheritTransform {
+                parent: self.bone_index()?,
+                influence: self.reader.f32()?,
+                rotation: inherit_rotation,
+                translation: inherit_translation,
+            })
+        } else {
+            None
+        };
+        let fixed_axis = if flags & PmxBone::FIXED_AXIS != 0 {
+            Some(self.reader.vector3()?)
+        } else {
+            None
+        };
+        let local_axes = if flags & PmxBone::LOCAL_AXES != 0 {
+            Some(LocalAxes {
+                x: self.reader.vector3()?,
+                z: self.reader.vector3()?,
+            })
+        } else {
+            None
+        };
+        let external_parent = if flags & PmxBone::EXTERNAL_PARENT != 0 {
+            Some(self.reader.i32()?)
+        } else {
+            None
+        };
+        let ik = if flags & PmxBone::IK != 0 {
+            let target = self.bone_index()?;
+            let iterations = self.reader.i32()?;
+            let limit_angle = self.reader.f32()?;
+            let links = self.list(|r| {
+                let bone = r.bone_index()?;
+                let limits = if r.reader.u8()? != 0 {
+                    Some((r.reader.vector3()?, r.reader.vector3()?))
+                } else {
+                    None
+                };
+                Ok(IkLink { bone, limits })
+            })?;
+            Some(PmxIk {
+                target,
+                iterations,
+                limit_angle,
+                links,
+            })
+        } else {
+            None
+        };
+        Ok(PmxBone {
+            name,
+            english_name,
+            position,
+            parent,
+            layer,
+            flags,
+            tail,
+            inherit,
+            fixed_axis,
+            local_axes,
+            external_parent,
+            ik,
+        })
+    }
+
+    fn morph(&mut self) -> Result<PmxMorph> {
+        let name = self.text()?;
+        let english_name = self.text()?;
+        let panel = self.reader.u8()?;
+        let offsets = match self.reader.u8()? {
+            0 => MorphOffsets::Group(self.list(|r| Ok((r.morph_index()?, r.reader.f32()?)))?),
+            1 => MorphOffsets::Vertex(self.list(|r| Ok((r.vertex_index()?, r.reader.vector3()?)))?),
+            2 => MorphOffsets::Bone(self.list(|r| {
+                let bone = r.bone_index()?;
+                let translation = r.reader.vector3()?;
+                let [x, y, z, w]: [f32; 4] = r.reader.vector4()?.into();
+                Ok((bone, translation, Quaternion::new(w, x, y, z)))
+            })?),
+            kind @ 3..=7 => MorphOffsets::Uv {
+                channel: kind - 3,
+                offsets: self.list(|r| Ok((r.vertex_index()?, r.reader.vector4()?)))?,
+            },
+            8 => MorphOffsets::Material(self.list(|r| {
+                Ok(MaterialMorph {
+                    material: r.material_index()?,
+                    operation: match r.reader.u8()? {
+                        0 => MaterialOperation::Multiply,
+                        _ => MaterialOperation::Add,
+                    },
+                    diffuse: r.reader.vector4()?,
+                    specular: r.reader.vector3()?,
+                    specularity: r.reader.f32()?,
+                    ambient: r.reader.vector3()?,
+                    edge_color: r.reader.vector4()?,
+                    edge_size: r.reader.f32()?,
+                    texture_tint: r.reader.vector4()?,
+                    sphere_tint: r.reader.vector4()?,
+                    toon_tint: r.reader.vector4()?,
+                })
+            })?),
+            9 => MorphOffsets::Flip(self.list(|r| Ok((r.morph_index()?, r.reader.f32()?)))?),
+            10 => MorphOffsets::Impulse(self.list(|r| {
+                Ok(ImpulseMorph {
+                    rigid_body: r.rigid_body_index()?,
+                    local: r.reader.u8()? != 0,
+                    velocity: r.reader.vector3()?,
+                    torque: r.reader.vector3()?,
+                })
+            })?),
+            other => return Err(PmxError::InvalidMorphType(other)),
+        };
+        Ok(PmxMorph {
+            name,
+            english_name,
+            panel,
+            offsets,
+        })
+    }
+
+    fn display_frame(&mut self) -> Result<DisplayFrame> {
+        Ok(DisplayFrame {
+            name: self.text()?,
+            english_name: self.text()?,
+            special: self.reader.u8()? != 0,
+            elements: self.list(|r| match r.reader.u8()? {
+                0 => Ok(FrameElement::Bone(r.bone_index()?)),
+                _ => Ok(FrameElement::Morph(r.morph_index()?)),
+            })?,
+        })
+    }
+
+    fn rigid_body(&mut self) -> Result<RigidBody> {
+        Ok(RigidBody {
+            name: self.text()?,
+            english_name: self.text()?,
+            bone: self.bone_index()?,
+            group: self.reader.u8()?,
+            no_collision_mask: self.reader.u16()?,
+            shape: match self.reader.u8()? {
+                0 => RigidShape::Sphere,
+                1 => RigidShape::Box,
+                _ => RigidShape::Capsule,
+            },
+            size: self.reader.vector3()?,
+            position: self.reader.vector3()?,
+            rotation: self.reader.vector3()?,
+            mass: self.reader.f32()?,
+            linear_damping: self.reader.f32()?,
+            angular_damping: self.reader.f32()?,
+            restitution: self.reader.f32()?,
+            friction: self.reader.f32()?,
+            mode: match self.reader.u8()? {
+                0 => PhysicsMode::FollowBone,
+                1 => PhysicsMode::Physics,
+                _ => PhysicsMode::PhysicsWithBone,
+            },
+        })
+    }
+
+    fn joint(&mut self) -> Result<Joint> {
+        Ok(Joint {
+            name: self.text()?,
+            english_name: self.text()?,
+            kind: match self.reader.u8()? {
+                0 => JointKind::Spring6Dof,
+                1 => JointKind::SixDof,
+                2 => JointKind::PointToPoint,
+                3 => JointKind::ConeTwist,
+                4 => JointKind::Slider,
+                _ => JointKind::Hinge,
+            },
+            bodies: [self.rigid_body_index()?, self.rigid_body_index()?],
+            position: self.reader.vector3()?,
+            rotation: self.reader.vector3()?,
+            position_limits: (self.reader.vector3()?, self.reader.vector3()?),
+            rotation_limits: (self.reader.vector3()?, self.reader.vector3()?),
+            position_spring: self.reader.vector3()?,
+            rotation_spring: self.reader.vector3()?,
+        })
+    }
+}
+
+impl PmxModel {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path).map_err(|error| PmxError::Io {
+            path: path.to_path_buf(),
+            error,
+        })?;
+        Self::parse(&bytes)
+    }
+
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        let mut reader = BinaryReader::new(bytes);
+
+        let magic = reader.array::<4>()?;
+        if &magic != b"PMX " {
+            return Err(PmxError::InvalidMagic(magic));
+        }
+        let version = reader.f32()?;
+        if version != 2.0 && version != 2.1 {
+            return Err(PmxError::UnsupportedVersion(version));
+        }
+        let global_count = reader.u8()? as usize;
+        let raw = reader.bytes(global_count)?;
+        let global = |i: usize| raw.get(i).copied().unwrap_or(0);
+        let index_size = |kind: &'static str, i: usize| match global(i) {
+            size @ (1 | 2 | 4) => Ok(size),
+            size => Err(PmxError::InvalidIndexSize { kind, size }),
+        };
+        let globals = PmxGlobals {
+            encoding: match global(0) {
+                0 => TextEncoding::Utf16,
+                1 => TextEncoding::Utf8,
+                other => return Err(PmxError::InvalidEncoding(other)),
+            },
+            additional_uvs: match global(1) {
+                count @ 0..=4 => count,
+                count => return Err(PmxError::InvalidAdditionalUvCount(count)),
+            },
+            vertex_index_size: index_size("vertex", 2)?,
+            texture_index_size: index_size("texture", 3)?,
+            material_index_size: index_size("material", 4)?,
+            bone_index_size: index_size("bone", 5)?,
+            morph_index_size: index_size("morph", 6)?,
+            rigid_body_index_size: index_size("rigid body", 7)?,
+        };
+
+        let mut r = PmxReader { reader, globals };
+        let header = PmxHeader {
+            version,
+            globals,
+            name: r.text()?,
+            english_name: r.text()?,
+            comment: r.text()?,
+            english_comment: r.text()?,
+        };
+
+        let vertices = r.list(PmxReader::vertex)?;
+        let indices = r.list(PmxReader::vertex_index)?;
+        if let Some(&index) = indices.iter().find(|&&i| i as usize >= vertices.len()) {
+            return Err(PmxError::IndexOutOfRange {
+                index,
+                vertex_count: vertices.len(),
+            });
+        }
+        let textures = r.list(PmxReader::text)?;
+        let materials = r.list(PmxReader::material)?;
+        let bones = r.list(PmxReader::bone)?;
+        for vertex in &vertices {
+            check_bones(vertex.weight.bones_and_weights().0, bones.len())?;
+        }
+        for bone in &bones {
+            let tail = match bone.tail {
+                BoneTail::Bone(tail) => tail,
+                BoneTail::Offset(_) => None,
+            };
+            let ik = bone.ik.iter().flat_map(|ik| {
+                ik.target
+                    .into_iter()
+                    .chain(ik.links.iter().filter_map(|link| link.bone))
+            });
+            let links = bone
+                .parent
+                .into_iter()
+                .chain(tail)
+                .chain(bone.inherit.and_then(|inherit| inherit.parent))
+                .chain(ik);
+            check_bones(links, bones.len())?;
+        }
+        let morphs = r.list(PmxReader::morph)?;
+        let display_frames = r.list(PmxReader::display_frame)?;
+        let rigid_bodies = r.list(PmxReader::rigid_body)?;
+        let joints = r.list(PmxReader::joint)?;
+
+        Ok(PmxModel {
+            header,
+            vertices,
+            indices,
+            textures,
+            materials,
+            bones,
+            morphs,
+            display_frames,
+            rigid_bodies,
+            joints,
+        })
+    }
+
+    pub fn index_buffer(&self) -> Indices {
+        Indices::from_u32(self.indices.clone())
+    }
+
+    pub fn draw_ranges(&self) -> Result<Vec<DrawRange>> {
+        let mut start_index = 0u32;
+        self.materials
+            .iter()
+            .enumerate()
+            .map(|(material, m)| {
+                let range = DrawRange {
+                    start_index,
+                    index_count: m.index_count,
+                    material,
+                };
+                start_index = start_index
+                    .checked_add(m.index_count)
+                    .filter(|&end| end as usize <= self.indices.len())
+                    .ok_or(PmxError::MaterialRangeOverflow { material })?;
+                Ok(range)
+            })
+            .collect()
+    }
+
+    pub fn texture_file(&self, texture: Option<u32>) -> Option<&str> {
+        texture
+            .and_then(|texture| self.textures.get(texture as usize))
+            .map(String::as_str)
+    }
+}
+
+fn check_bones(bones: impl IntoIterator<Item = u32>, bone_count: usize) -> Result<()> {
+    match bones.into_iter().find(|&bone| bone as usize >= bone_count) {
+        Some(bone) => Err(PmxError::BoneOutOfRange { bone, bone_count }),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TestBone {
+        parent: i32,
+        ik: Option<(i32, Vec<i32>)>,
+    }
+
+    impl TestBone {
+        fn root() -> Self {
+            TestBone {
+                parent: -1,
+                ik: None,
+            }
+        }
+
+        fn child(parent: i32) -> Self {
+            TestBone { parent, ik: None }
+        }
+    }
+
+    /// Writes a PMX 2.0 file; everything after the bones is left empty except one material.
+    struct PmxBuilder {
+        encoding: u8,
+        vertex_index_size: u8,
+        bone_index_size: u8,
+        weights: Vec<Weight>,
+        indices: Vec<u32>,
+        bones: Vec<TestBone>,
+    }
+
+    impl Default for PmxBuilder {
+        fn default() -> Self {
+            PmxBuilder {
+                encoding: 0,
+                vertex_index_size: 1,
+                bone_index_size: 1,
+                weights: vec![Weight::Bdef1 { bone: 0 }; 3],
+                indices: vec![0, 1, 2],
+                bones: vec![TestBone::root(), TestBone::child(0)],
+            }
+        }
+    }
+
+    impl PmxBuilder {
+        fn text(&self, out: &mut Vec<u8>, text: &str) {
+            let bytes: Vec<u8> = match self.encoding {
+                0 => text.encode_utf16().flat_map(u16::to_le_bytes).collect(),
+                _ => text.as_bytes().to_vec(),
+            };
+            out.extend_from_slice(&(bytes.len() as i32).to_le_bytes());
+            out.extend_from_slice(&bytes);
+        }
+
+        fn index(out: &mut Vec<u8>, size: u8, index: i32) {
+            match size {
+                1 => out.push(index as i8 as u8),
+                2 => out.extend_from_slice(&(index as i16).to_le_bytes()),
+                _ => out.extend_from_slice(&index.to_le_bytes()),
+            }
+        }
+
+        fn bone(&self, out: &mut Vec<u8>, bone: u32) {
+            Self::index(out, self.bone_index_size, bone as i32);
+        }
+
+        fn floats(out: &mut Vec<u8>, values: &[f32]) {
+            for value in values {
+                out.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+
+        fn count(out: &mut Vec<u8>, count: usize) {
+            out.extend_from_slice(&(count as i32).to_le_bytes());
+        }
+
+        fn bytes(&self) -> Vec<u8> {
+            let mut out = b"PMX ".to_vec();
+            Self::floats(&mut out, &[2.0]);
+            out.extend_from_slice(&[8, self.encoding, 0, self.vertex_index_size, 1, 1]);
+            out.extend_from_slice(&[self.bone_index_size, 1, 1]);
+            for text in ["モデル", "model", "", "comment"] {
+                self.text(&mut out, text);
+            }
+
+            Self::count(&mut out, self.weights.len());
+            for (i, weight) in self.weights.iter().enumerate() {
+                Self::floats(&mut out, &[i as f32, 0.0, 0.0, 0.0, 1.0, 0.0, 0.5, 0.5]);
+                match *weight {
+                    Weight::Bdef1 { bone } => {
+                        out.push(0);
+                        self.bone(&mut out, bone);
+                    }
+                    Weight::Bdef2 { bones, weight } => {
+                        out.push(1);
+                        bones.iter().for_each(|&bone| self.bone(&mut out, bone));
+                        Self::floats(&mut out, &[weight]);
+                    }
+                    Weight::Bdef4 { bones, weights } => {
+                        out.push(2);
+                        bones.iter().for_each(|&bone| self.bone(&mut out, bone));
+                        Self::floats(&mut out, &weights);
+                    }
+                    Weight::Sdef {
+                        bones,
+                        weight,
+                        c,
+                        r0,
+                        r1,
+                    } => {
+                        out.push(3);
+                        bones.iter().for_each(|&bone| self.bone(&mut out, bone));
+                        Self::floats(&mut out, &[weight, c.x, c.y, c.z]);
+                        Self::floats(&mut out, &[r0.x, r0.y, r0.z, r1.x, r1.y, r1.z]);
+                    }
+                    Weight::Qdef { bones, weights } => {
+                        out.push(4);
+                        bones.iter().for_each(|&bone| self.bone(&mut out, bone));
+                        Self::floats(&mut out, &weights);
+                    }
+                }
+                Self::floats(&mut out, &[1.0]);
+            }
+
+            Self::count(&mut out, self.indices.len());
+            for &index in &self.indices {
+                match self.vertex_index_size {
+                    1 => out.push(index as u8),
+                    2 => out.extend_from_slice(&(index as u16).to_le_bytes()),
+                    _ => out.extend_from_slice(&index.to_le_bytes()),
+                }
+            }
+
+            Self::count(&mut out, 1);
+            self.text(&mut out, "tex.png");
+
+            Self::count(&mut out, 1);
+            self.text(&mut out, "材質");
+            self.text(&mut out, "material");
+            Self::floats(
+                &mut out,
+                &[1.0, 1.0, 1.0, 1.0, 0.0, 0.0, 0.0, 5.0, 0.2, 0.2, 0.2],
+            );
+            out.push(PmxMaterial::NO_CULL);
+            Self::floats(&mut out, &[0.0, 0.0, 0.0, 1.0, 1.0]);
+            out.extend_from_slice(&[0, 0xFF, 0, 1, 3]);
+            self.text(&mut out, "");
+            out.extend_from_slice(&(self.indices.len() as i32).to_le_bytes());
+
+            Self::count(&mut out, self.bones.len());
+            for (i, bone) in self.bones.iter().enumerate() {
+                self.text(&mut out, &format!("ボーン{i}"));
+                self.text(&mut out, &format!("bone{i}"));
+                Self::floats(&mut out, &[0.0, i as f32, 0.0]);
+                Self::index(&mut out, self.bone_index_size, bone.parent);
+                out.extend_from_slice(&0i32.to_le_bytes());
+                let ik_flag = if bone.ik.is_some() { PmxBone::IK } else { 0 };
+                out.extend_from_slice(&(PmxBone::INDEXED_TAIL | ik_flag).to_le_bytes());
+                Self::index(&mut out, self.bone_index_size, -1);
+                if let Some((target, links)) = &bone.ik {
+                    Self::index(&mut out, self.bone_index_size, *target);
+                    out.extend_from_slice(&40i32.to_le_bytes());
+                    Self::floats(&mut out, &[1.0]);
+                    Self::count(&mut out, links.len());
+                    for &link in links {
+                        Self::index(&mut out, self.bone_index_size, link);
+                        out.push(0);
+                    }
+                }
+            }
+
+            // Morphs, display frames, rigid bodies and joints.
+            for _ in 0..4 {
+                Self::count(&mut out, 0);
+            }
+            out
+        }
+    }
+
+    fn every_weight_type(high_bone: u32) -> Vec<Weight> {
+        vec![
+            Weight::Bdef1 { bone: high_bone },
+            Weight::Bdef2 {
+                bones: [0, high_bone],
+                weight: 0.25,
+            },
+            Weight::Bdef4 {
+                bones: [0, 1, 2, high_bone],
+                weights: [0.1, 0.2, 0.3, 0.4],
+            },
+            Weight::Sdef {
+                bones: [high_bone, 1],
+                weight: 0.75,
+                c: Vector3::new(0.0, 1.0, 0.0),
+                r0: Vector3::new(0.0, 1.5, 0.0),
+                r1: Vector3::new(0.0, 0.5, 0.0),
+            },
+            Weight::Qdef {
+                bones: [2, 1, 0, high_bone],
+                weights: [0.5, 0.25, 0.25, 0.0],
+            },
+        ]
+    }
+
+    fn chain(count: usize) -> Vec<TestBone> {
+        (0..count)
+            .map(|i| match i {
+                0 => TestBone::root(),
+                _ => TestBone::child(i as i32 - 1),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parses_every_weight_type_with_every_bone_index_size() {
+        for (bone_index_size, bone_count) in [(1, 120), (2, 200), (4, 256)] {
+            let high_bone = bone_count as u32 - 1;
+            let weights = every_weight_type(high_bone);
+            let model = PmxModel::parse(
+                &PmxBuilder {
+                    bone_index_size,
+                    weights: weights.clone(),
+                    indices: vec![0, 1, 2, 2, 3, 4],
+                    bones: chain(bone_count),
+                    ..Default::default()
+                }
+                .bytes(),
+            )
+            .unwrap();
+            assert_eq!(model.header.globals.bone_index_size, bone_index_size);
+            let parsed: Vec<Weight> = model.vertices.iter().map(|v| v.weight).collect();
+            assert_eq!(parsed, weights, "bone index size {bone_index_size}");
+            assert_eq!(model.bones.len(), bone_count);
+            assert_eq!(model.bones[0].parent, None);
+            assert_eq!(model.bones[bone_count - 1].parent, Some(high_bone - 1));
+            assert_eq!(model.bones[1].tail, BoneTail::Bone(None));
+        }
+    }
+
+    #[test]
+    fn bones_and_weights_pad_to_four_influences() {
+        let [bdef1, bdef2, bdef4, sdef, qdef]: [Weight; 5] =
+            every_weight_type(3).try_into().unwrap();
+        assert_eq!(
+            bdef1.bones_and_weights(),
+            ([3, 0, 0, 0], [1.0, 0.0, 0.0, 0.0])
+        );
+        assert_eq!(
+            bdef2.bones_and_weights(),
+            ([0, 3, 0, 0], [0.25, 0.75, 0.0, 0.0])
+        );
+        assert_eq!(
+            bdef4.bones_and_weights(),
+            ([0, 1, 2, 3], [0.1, 0.2, 0.3, 0.4])
+        );
+        assert_eq!(
+            sdef.bones_and_weights(),
+            ([3, 1, 0, 0], [0.75, 0.25, 0.0, 0.0])
+        );
+        assert_eq!(
+            qdef.bones_and_weights(),
+            ([2, 1, 0, 3], [0.5, 0.25, 0.25, 0.0])
+        );
+    }
+
+    #[test]
+    fn reads_vertex_indices_of_every_size_unsigned() {
+        for (vertex_index_size, large) in [(1, 200), (2, 40_000), (4, 70_000)] {
+            let builder = PmxBuilder {
+                vertex_index_size,
+                ..Default::default()
+            };
+            let model = PmxModel::parse(&builder.bytes()).unwrap();
+            assert_eq!(model.indices, [0, 1, 2]);
+            assert_eq!(model.header.globals.vertex_index_size, vertex_index_size);
+
+            // Vertex indices are unsigned, so the high bit must not turn them negative.
+            let bytes = PmxBuilder {
+                vertex_index_size,
+                indices: vec![0, 1, large],
+                ..Default::default()
+            }
+            .bytes();
+            assert!(matches!(
+                PmxModel::parse(&bytes),
+                Err(PmxError::IndexOutOfRange { index, vertex_count: 3 }) if index == large
+            ));
+        }
+    }
+
+    #[test]
+    fn decodes_both_text_encodings() {
+        for encoding in [0, 1] {
+            let model = PmxModel::parse(
+                &PmxBuilder {
+                    encoding,
+                    ..Default::default()
+                }
+                .bytes(),
+            )
+            .unwrap();
+            assert_eq!(model.header.name, "モデル");
+            assert_eq!(model.header.english_name, "model");
+            assert_eq!(model.header.comment, "");
+            assert_eq!(model.bones[1].name, "ボーン1");
+            assert_eq!(model.materials[0].name, "材質");
+            assert_eq!(model.textures, ["tex.png"]);
+            assert_eq!(
+                model.texture_file(model.materials[0].texture),
+                Some("tex.png")
+            );
+            assert_eq!(model.materials[0].sphere_texture, None);
+            assert_eq!(model.materials[0].toon, Toon::Shared(3));
+            assert!(model.materials[0].double_sided());
+            assert_eq!(
+                model.draw_ranges().unwrap(),
+                [DrawRange {
+                    start_index: 0,
+                    index_count: 3,
+                    material: 0
+                }]
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_globals() {
+        let mut bytes = PmxBuilder::default().bytes();
+        // Byte 9 + 5 is the bone index size.
+        bytes[14] = 3;
+        assert!(matches!(
+            PmxModel::parse(&bytes),
+            Err(PmxError::InvalidIndexSize {
+                kind: "bone",
+                size: 3
+            })
+        ));
+
+        let mut bytes = PmxBuilder::default().bytes();
+        bytes[9] = 2;
+        assert!(matches!(
+            PmxModel::parse(&bytes),
+            Err(PmxError::InvalidEncoding(2))
+        ));
+
+        let mut bytes = PmxBuilder::default().bytes();
+        bytes[4..8].copy_from_slice(&1.0f32.to_le_bytes());
+        assert!(matches!(
+            PmxModel::parse(&bytes),
+            Err(PmxError::UnsupportedVersion(version)) if version == 1.0
+        ));
+    }
+
+    #[test]
+    fn rejects_unknown_weight_type() {
+        let builder = PmxBuilder {
+            weights: vec![Weight::Bdef1 { bone: 0 }],
+            indices: vec![0, 0, 0],
+            ..Default::default()
+        };
+        let mut bytes = builder.bytes();
+        // The weight type follows the 17-byte header, the four texts, the vertex count and
+        // eight floats of the first vertex.
+        let header_texts: usize = ["モデル", "model", "", "comment"]
+            .iter()
+            .map(|text| 4 + text.encode_utf16().count() * 2)
+            .sum();
+        let weight_type = 17 + header_texts + 4 + 32;
+        assert_eq!(bytes[weight_type], 0);
+        bytes[weight_type] = 5;
+        assert!(matches!(
+            PmxModel::parse(&bytes),
+            Err(PmxError::InvalidWeightType(5))
+        ));
+    }
+
+    #[test]
+    fn rejects_out_of_range_weight_bones() {
+        for weight in every_weight_type(2) {
+            let bytes = PmxBuilder {
+                weights: vec![weight; 3],
+                ..Default::default()
+            }
+            .bytes();
+            assert!(
+                matches!(
+                    PmxModel::parse(&bytes),
+                    Err(PmxError::BoneOutOfRange {
+                        bone: 2,
+                        bone_count: 2
+                    })
+                ),
+                "{weight:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn negative_weight_bones_fall_back_to_the_root() {
+        let mut builder = PmxBuilder::default();
+        builder.weights[0] = Weight::Bdef1 { bone: u32::MAX };
+        let model = PmxModel::parse(&builder.bytes()).unwrap();
+        assert_eq!(model.vertices[0].weight, Weight::Bdef1 { bone: 0 });
+    }
+
+    #[test]
+    fn rejects_out_of_range_bone_links() {
+        let mut builder = PmxBuilder::default();
+        builder.bones[1].parent = 9;
+        assert!(matches!(
+            PmxModel::parse(&builder.bytes()),
+            Err(PmxError::BoneOutOfRange { bone: 9, .. })
+        ));
+
+        let mut builder = PmxBuilder::default();
+        builder.bones.push(TestBone {
+            parent: 1,
+            ik: Some((1, vec![0, 4])),
+        });
+        assert!(matches!(
+            PmxModel::parse(&builder.bytes()),
+            Err(PmxError::BoneOutOfRange { bone: 4, .. })
+        ));
+
+        let mut builder = PmxBuilder::default();
+        builder.bones.push(TestBone {
+            parent: 1,
+            ik: Some((1, vec![0])),
+        });
+        let model = PmxModel::parse(&builder.bytes()).unwrap();
+        let ik = model.bones[2].ik.as_ref().unwrap();
+        assert_eq!(ik.target, Some(1));
+        assert_eq!(ik.iterations, 40);
+        assert_eq!(ik.links[0].bone, Some(0));
+    }
+
+    #[test]
+    fn parses_more_bones_than_the_skinning_palette() {
+        // The skinning palette limit is enforced by the renderer, not the loader.
+        let bytes = PmxBuilder {
+            bone_index_size: 2,
+            bones: chain(300),
+            ..Default::default()
+        }
+        .bytes();
+        let model = PmxModel::parse(&bytes).unwrap();
+        assert_eq!(model.bones.len(), 300);
+        assert_eq!(model.bones[299].parent, Some(298));
+    }
+}
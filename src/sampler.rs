@@ -0,0 +1,59 @@
+use windows::Win32::Graphics::Direct3D12::*;
+
+const MAX_ANISOTROPY: u32 = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SamplerFilter {
+    Linear,
+    Point,
+    Anisotropic,
+}
+
+impl SamplerFilter {
+    pub const ALL: [SamplerFilter; 3] = [
+        SamplerFilter::Linear,
+        SamplerFilter::Point,
+        SamplerFilter::Anisotropic,
+    ];
+
+    pub fn shader_id(self) -> u32 {
+        match self {
+            SamplerFilter::Linear => 0,
+            SamplerFilter::Point => 1,
+            SamplerFilter::Anisotropic => 2,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SamplerFilter::Linear => "linear",
+            SamplerFilter::Point => "point",
+            SamplerFilter::Anisotropic => "anisotropic",
+        }
+    }
+
+    pub fn d3d12_filter(self) -> D3D12_FILTER {
+        match self {
+            SamplerFilter::Linear => D3D12_FILTER_MIN_MAG_MIP_LINEAR,
+            SamplerFilter::Point => D3D12_FILTER_MIN_MAG_MIP_POINT,
+            SamplerFilter::Anisotropic => D3D12_FILTER_ANISOTROPIC,
+        }
+    }
+}
+
+pub fn static_samplers() -> [D3D12_STATIC_SAMPLER_DESC; 3] {
+    SamplerFilter::ALL.map(|filter| D3D12_STATIC_SAMPLER_DESC {
+        AddressU: D3D12_TEXTURE_ADDRESS_MODE_WRAP,
+        AddressV: D3D12_TEXTURE_ADDRESS_MODE_WRAP,
+        AddressW: D3D12_TEXTURE_ADDRESS_MODE_WRAP,
+        BorderColor: D3D12_STATIC_BORDER_COLOR_TRANSPARENT_BLACK,
+        Filter: filter.d3d12_filter(),
+        MaxAnisotropy: MAX_ANISOTROPY,
+        MaxLOD: D3D12_FLOAT32_MAX,
+        MinLOD: 0.0f32,
+        ShaderRegister: filter.shader_id(),
+        ShaderVisibility: D3D12_SHADER_VISIBILITY_PIXEL,
+        ComparisonFunc: D3D12_COMPARISON_FUNC_NEVER,
+        ..Default::default()
+    })
+}
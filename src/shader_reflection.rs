@@ -0,0 +1,1205 @@
+use windows::{
+    core::PCSTR, Win32::Graphics::Direct3D::*, Win32::Graphics::Direct3D12::*,
+    Win32::Graphics::Dxgi::Common::*,
+};
+
+const DXBC: u32 = fourcc(b"DXBC");
+const ISGN: u32 = fourcc(b"ISGN");
+const OSGN: u32 = fourcc(b"OSGN");
+const OSG5: u32 = fourcc(b"OSG5");
+const ISG1: u32 = fourcc(b"ISG1");
+const OSG1: u32 = fourcc(b"OSG1");
+const RDEF: u32 = fourcc(b"RDEF");
+const SHEX: u32 = fourcc(b"SHEX");
+const SHDR: u32 = fourcc(b"SHDR");
+const DXIL: u32 = fourcc(b"DXIL");
+const PSV0: u32 = fourcc(b"PSV0");
+const RD11: u32 = fourcc(b"RD11");
+
+const fn fourcc(code: &[u8; 4]) -> u32 {
+    u32::from_le_bytes(*code)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReflectionError {
+    UnexpectedEof { offset: usize },
+    InvalidMagic(u32),
+    InvalidString { offset: usize },
+    TypeNestingTooDeep,
+}
+
+impl std::fmt::Display for ReflectionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReflectionError::UnexpectedEof { offset } => {
+                write!(f, "unexpected end of shader blob at offset {}", offset)
+            }
+            ReflectionError::InvalidMagic(magic) => write!(
+                f,
+                "not a shader container: {:?}",
+                String::from_utf8_lossy(&magic.to_le_bytes())
+            ),
+            ReflectionError::InvalidString { offset } => {
+                write!(f, "unterminated string at offset {}", offset)
+            }
+            ReflectionError::TypeNestingTooDeep => {
+                write!(f, "constant buffer type nesting too deep")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReflectionError {}
+
+pub type Result<T> = std::result::Result<T, ReflectionError>;
+
+#[derive(Clone, Copy)]
+struct Chunk<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Chunk<'a> {
+    fn bytes(&self, offset: usize, len: usize) -> Result<&'a [u8]> {
+        offset
+            .checked_add(len)
+            .and_then(|end| self.data.get(offset..end))
+            .ok_or(ReflectionError::UnexpectedEof { offset })
+    }
+
+    fn u8(&self, offset: usize) -> Result<u8> {
+        Ok(self.bytes(offset, 1)?[0])
+    }
+
+    fn u16(&self, offset: usize) -> Result<u16> {
+        let b = self.bytes(offset, 2)?;
+        Ok(u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    fn u32(&self, offset: usize) -> Result<u32> {
+        let b = self.bytes(offset, 4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn i32(&self, offset: usize) -> Result<i32> {
+        Ok(self.u32(offset)? as i32)
+    }
+
+    fn cstr(&self, offset: usize) -> Result<String> {
+        let tail = self
+            .data
+            .get(offset..)
+            .ok_or(ReflectionError::UnexpectedEof { offset })?;
+        let len = tail
+            .iter()
+            .position(|&c| c == 0)
+            .ok_or(ReflectionError::InvalidString { offset })?;
+        Ok(String::from_utf8_lossy(&tail[..len]).into_owned())
+    }
+
+    fn sub(&self, offset: usize) -> Result<Chunk<'a>> {
+        Ok(Chunk {
+            data: self
+                .data
+                .get(offset..)
+                .ok_or(ReflectionError::UnexpectedEof { offset })?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContainerKind {
+    Dxbc,
+    Dxil,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShaderKind {
+    Pixel,
+    Vertex,
+    Geometry,
+    Hull,
+    Domain,
+    Compute,
+    Mesh,
+    Amplification,
+    Other(u16),
+}
+
+impl ShaderKind {
+    fn from_program_type(program_type: u16) -> Self {
+        match program_type {
+            0 => ShaderKind::Pixel,
+            1 => ShaderKind::Vertex,
+            2 => ShaderKind::Geometry,
+            3 => ShaderKind::Hull,
+            4 => ShaderKind::Domain,
+            5 => ShaderKind::Compute,
+            13 => ShaderKind::Mesh,
+            14 => ShaderKind::Amplification,
+            other => ShaderKind::Other(other),
+        }
+    }
+
+    fn from_rdef_target(program_type: u16) -> Self {
+        match program_type {
+            0xffff => ShaderKind::Pixel,
+            0xfffe => ShaderKind::Vertex,
+            0x4753 => ShaderKind::Geometry,
+            0x4853 => ShaderKind::Hull,
+            0x4453 => ShaderKind::Domain,
+            0x4353 => ShaderKind::Compute,
+            other => ShaderKind::Other(other),
+        }
+    }
+
+    fn from_psv_stage(stage: u8) -> Self {
+        match stage {
+            0 => ShaderKind::Pixel,
+            1 => ShaderKind::Vertex,
+            2 => ShaderKind::Geometry,
+            3 => ShaderKind::Hull,
+            4 => ShaderKind::Domain,
+            5 => ShaderKind::Compute,
+            13 => ShaderKind::Mesh,
+            14 => ShaderKind::Amplification,
+            other => ShaderKind::Other(other as u16),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShaderVersion {
+    pub kind: ShaderKind,
+    pub major: u8,
+    pub minor: u8,
+}
+
+impl ShaderVersion {
+    fn from_token(token: u32) -> Self {
+        ShaderVersion {
+            kind: ShaderKind::from_program_type((token >> 16) as u16),
+            major: ((token >> 4) & 0xf) as u8,
+            minor: (token & 0xf) as u8,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignatureElement {
+    pub semantic_name: String,
+    pub semantic_index: u32,
+    pub system_value: D3D_NAME,
+    pub component_type: D3D_REGISTER_COMPONENT_TYPE,
+    pub register: u32,
+    pub mask: u8,
+    pub read_write_mask: u8,
+    pub stream: u32,
+    pub min_precision: D3D_MIN_PRECISION,
+}
+
+impl SignatureElement {
+    pub fn component_count(&self) -> u32 {
+        (self.mask & 0xf).count_ones()
+    }
+
+    pub fn suggested_format(&self) -> DXGI_FORMAT {
+        match (self.component_type, self.component_count()) {
+            (D3D_REGISTER_COMPONENT_FLOAT32, 1) => DXGI_FORMAT_R32_FLOAT,
+            (D3D_REGISTER_COMPONENT_FLOAT32, 2) => DXGI_FORMAT_R32G32_FLOAT,
+            (D3D_REGISTER_COMPONENT_FLOAT32, 3) => DXGI_FORMAT_R32G32B32_FLOAT,
+            (D3D_REGISTER_COMPONENT_FLOAT32, 4) => DXGI_FORMAT_R32G32B32A32_FLOAT,
+            (D3D_REGISTER_COMPONENT_UINT32, 1) => DXGI_FORMAT_R32_UINT,
+            (D3D_REGISTER_COMPONENT_UINT32, 2) => DXGI_FORMAT_R32G32_UINT,
+            (D3D_REGISTER_COMPONENT_UINT32, 3) => DXGI_FORMAT_R32G32B32_UINT,
+            (D3D_REGISTER_COMPONENT_UINT32, 4) => DXGI_FORMAT_R32G32B32A32_UINT,
+            (D3D_REGISTER_COMPONENT_SINT32, 1) => DXGI_FORMAT_R32_SINT,
+            (D3D_REGISTER_COMPONENT_SINT32, 2) => DXGI_FORMAT_R32G32_SINT,
+            (D3D_REGISTER_COMPONENT_SINT32, 3) => DXGI_FORMAT_R32G32B32_SINT,
+            (D3D_REGISTER_COMPONENT_SINT32, 4) => DXGI_FORMAT_R32G32B32A32_SINT,
+            _ => DXGI_FORMAT_UNKNOWN,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShaderType {
+    pub name: Option<String>,
+    pub class: D3D_SHADER_VARIABLE_CLASS,
+    pub variable_type: D3D_SHADER_VARIABLE_TYPE,
+    pub rows: u16,
+    pub columns: u16,
+    pub elements: u16,
+    pub members: Vec<ShaderTypeMember>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShaderTypeMember {
+    pub name: String,
+    pub offset: u32,
+    pub member_type: ShaderType,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShaderVariable {
+    pub name: String,
+    pub start_offset: u32,
+    pub size: u32,
+    pub flags: u32,
+    pub variable_type: ShaderType,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConstantBufferDesc {
+    pub name: String,
+    pub size: u32,
+    pub flags: u32,
+    pub buffer_type: D3D_CBUFFER_TYPE,
+    pub variables: Vec<ShaderVariable>,
+}
+
+impl ConstantBufferDesc {
+    pub fn variable(&self, name: &str) -> Option<&ShaderVariable> {
+        self.variables.iter().find(|v| v.name == name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BoundResource {
+    pub name: String,
+    pub input_type: D3D_SHADER_INPUT_TYPE,
+    pub return_type: D3D_RESOURCE_RETURN_TYPE,
+    pub dimension: D3D_SRV_DIMENSION,
+    pub num_samples: u32,
+    pub bind_point: u32,
+    pub bind_count: u32,
+    pub flags: u32,
+    pub space: u32,
+}
+
+impl BoundResource {
+    pub fn descriptor_range_type(&self) -> D3D12_DESCRIPTOR_RANGE_TYPE {
+        match self.input_type {
+            D3D_SIT_CBUFFER => D3D12_DESCRIPTOR_RANGE_TYPE_CBV,
+            D3D_SIT_SAMPLER => D3D12_DESCRIPTOR_RANGE_TYPE_SAMPLER,
+            D3D_SIT_UAV_RWTYPED
+            | D3D_SIT_UAV_RWSTRUCTURED
+            | D3D_SIT_UAV_RWBYTEADDRESS
+            | D3D_SIT_UAV_APPEND_STRUCTURED
+            | D3D_SIT_UAV_CONSUME_STRUCTURED
+            | D3D_SIT_UAV_RWSTRUCTURED_WITH_COUNTER
+            | D3D_SIT_UAV_FEEDBACKTEXTURE => D3D12_DESCRIPTOR_RANGE_TYPE_UAV,
+            _ => D3D12_DESCRIPTOR_RANGE_TYPE_SRV,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PsvSignatureElement {
+    pub semantic_name: String,
+    pub semantic_indices: Vec<u32>,
+    pub rows: u8,
+    pub start_row: u8,
+    pub columns: u8,
+    pub start_column: u8,
+    pub allocated: bool,
+    pub semantic_kind: u8,
+    pub component_type: u8,
+    pub interpolation_mode: u8,
+    pub dynamic_mask: u8,
+    pub stream: u8,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PipelineStateValidation {
+    pub shader_kind: Option<ShaderKind>,
+    pub minimum_wave_lane_count: u32,
+    pub maximum_wave_lane_count: u32,
+    pub uses_view_id: bool,
+    pub num_threads: Option<[u32; 3]>,
+    pub entry_function_name: Option<String>,
+    pub inputs: Vec<PsvSignatureElement>,
+    pub outputs: Vec<PsvSignatureElement>,
+    pub patch_constant_or_primitive: Vec<PsvSignatureElement>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShaderReflection {
+    pub container: ContainerKind,
+    pub version: Option<ShaderVersion>,
+    pub creator: Option<String>,
+    pub input_signature: Vec<SignatureElement>,
+    pub output_signature: Vec<SignatureElement>,
+    pub constant_buffers: Vec<ConstantBufferDesc>,
+    pub bound_resources: Vec<BoundResource>,
+    pub instruction_tokens: Vec<u32>,
+    pub pipeline_state_validation: Option<PipelineStateValidation>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RootBinding {
+    pub range_type: D3D12_DESCRIPTOR_RANGE_TYPE,
+    pub base_register: u32,
+    pub count: u32,
+    pub space: u32,
+}
+
+impl RootBinding {
+    pub fn from_range(range: &D3D12_DESCRIPTOR_RANGE) -> Self {
+        RootBinding {
+            range_type: range.RangeType,
+            base_register: range.BaseShaderRegister,
+            count: range.NumDescriptors,
+            space: range.RegisterSpace,
+        }
+    }
+
+    pub fn from_static_sampler(sampler: &D3D12_STATIC_SAMPLER_DESC) -> Self {
+        RootBinding {
+            range_type: D3D12_DESCRIPTOR_RANGE_TYPE_SAMPLER,
+            base_register: sampler.ShaderRegister,
+            count: 1,
+            space: sampler.RegisterSpace,
+        }
+    }
+
+    pub fn from_root_descriptor(
+        parameter_type: D3D12_ROOT_PARAMETER_TYPE,
+        descriptor: &D3D12_ROOT_DESCRIPTOR,
+    ) -> Self {
+        RootBinding {
+            range_type: match parameter_type {
+                D3D12_ROOT_PARAMETER_TYPE_SRV => D3D12_DESCRIPTOR_RANGE_TYPE_SRV,
+                D3D12_ROOT_PARAMETER_TYPE_UAV => D3D12_DESCRIPTOR_RANGE_TYPE_UAV,
+                _ => D3D12_DESCRIPTOR_RANGE_TYPE_CBV,
+            },
+            base_register: descriptor.ShaderRegister,
+            count: 1,
+            space: descriptor.RegisterSpace,
+        }
+    }
+
+    pub fn from_root_constants(constants: &D3D12_ROOT_CONSTANTS) -> Self {
+        RootBinding {
+            range_type: D3D12_DESCRIPTOR_RANGE_TYPE_CBV,
+            base_register: constants.ShaderRegister,
+            count: 1,
+            space: constants.RegisterSpace,
+        }
+    }
+
+    fn covers(&self, resource: &BoundResource) -> bool {
+        if self.range_type != resource.descriptor_range_type() || self.space != resource.space {
+            return false;
+        }
+        let end = self.base_register.saturating_add(self.count);
+        let resource_end = resource
+            .bind_point
+            .saturating_add(resource.bind_count.max(1));
+        self.base_register <= resource.bind_point && (self.count == u32::MAX || resource_end <= end)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InputLayoutMismatch {
+    MissingSemantic {
+        semantic_name: String,
+        semantic_index: u32,
+    },
+    ComponentType {
+        semantic_name: String,
+        semantic_index: u32,
+        format: DXGI_FORMAT,
+        expected: DXGI_FORMAT,
+    },
+    UnfedInput {
+        semantic_name: String,
+        semantic_index: u32,
+    },
+}
+
+impl ShaderReflection {
+    pub fn parse(blob: &[u8]) -> Result<Self> {
+        let container = Chunk { data: blob };
+        let magic = container.u32(0)?;
+        if magic != DXBC {
+            return Err(ReflectionError::InvalidMagic(magic));
+        }
+        let chunk_count = container.u32(28)? as usize;
+
+        let mut reflection = ShaderReflection {
+            container: ContainerKind::Dxbc,
+            version: None,
+            creator: None,
+            input_signature: Vec::new(),
+            output_signature: Vec::new(),
+            constant_buffers: Vec::new(),
+            bound_resources: Vec::new(),
+            instruction_tokens: Vec::new(),
+            pipeline_state_validation: None,
+        };
+        let mut psv_resources = Vec::new();
+
+        for i in 0..chunk_count {
+            let offset = container.u32(32 + i * 4)? as usize;
+            let chunk_fourcc = container.u32(offset)?;
+            let size = container.u32(offset + 4)? as usize;
+            let chunk = Chunk {
+                data: container.bytes(offset + 8, size)?,
+            };
+
+            match chunk_fourcc {
+                ISGN => reflection.input_signature = parse_signature(chunk, 24, false)?,
+                ISG1 => reflection.input_signature = parse_signature(chunk, 32, true)?,
+                OSGN => reflection.output_signature = parse_signature(chunk, 24, false)?,
+                OSG5 => reflection.output_signature = parse_signature(chunk, 28, true)?,
+                OSG1 => reflection.output_signature = parse_signature(chunk, 32, true)?,
+                RDEF => {
+                    let (creator, constant_buffers, bound_resources, kind) = parse_rdef(chunk)?;
+                    reflection.creator = creator;
+                    reflection.constant_buffers = constant_buffers;
+                    reflection.bound_resources = bound_resources;
+                    if reflection.version.is_none() {
+                        reflection.version = kind;
+                    }
+                }
+                SHEX | SHDR => {
+                    let token = chunk.u32(0)?;
+                    let length = chunk.u32(4)? as usize;
+                    reflection.version = Some(ShaderVersion::from_token(token));
+                    reflection.instruction_tokens = (0..length.min(chunk.data.len() / 4))
+                        .map(|i| chunk.u32(i * 4))
+                        .collect::<Result<_>>()?;
+                }
+                DXIL => {
+                    reflection.container = ContainerKind::Dxil;
+                    reflection.version = Some(ShaderVersion::from_token(chunk.u32(0)?));
+                }
+                PSV0 => {
+                    let (psv, resources) = parse_psv0(chunk)?;
+                    reflection.pipeline_state_validation = Some(psv);
+                    psv_resources = resources;
+                }
+                _ => {}
+            }
+        }
+
+        if reflection.bound_resources.is_empty() {
+            reflection.bound_resources = psv_resources;
+        }
+
+        Ok(reflection)
+    }
+
+    pub fn constant_buffer(&self, name: &str) -> Option<&ConstantBufferDesc> {
+        self.constant_buffers.iter().find(|cb| cb.name == name)
+    }
+
+    pub fn constant_buffer_at(&self, register: u32, space: u32) -> Option<&ConstantBufferDesc> {
+        let resource = self.bound_resources.iter().find(|r| {
+            r.input_type == D3D_SIT_CBUFFER && r.bind_point == register && r.space == space
+        })?;
+        self.constant_buffer(&resource.name)
+    }
+
+    pub fn validate_input_layout(
+        &self,
+        layout: &[D3D12_INPUT_ELEMENT_DESC],
+    ) -> Vec<InputLayoutMismatch> {
+        let mut mismatches = Vec::new();
+        let mut fed = vec![false; self.input_signature.len()];
+
+        for desc in layout {
+            let semantic_name = unsafe { pcstr_to_string(desc.SemanticName) };
+            let semantic_index = desc.SemanticIndex;
+            let position = self.input_signature.iter().position(|e| {
+                e.semantic_index == semantic_index
+                    && e.semantic_name.eq_ignore_ascii_case(&semantic_name)
+            });
+            let Some(position) = position else {
+                mismatches.push(InputLayoutMismatch::MissingSemantic {
+                    semantic_name,
+                    semantic_index,
+                });
+                continue;
+            };
+            fed[position] = true;
+
+            let element = &self.input_signature[position];
+            if !format_matches_component_type(desc.Format, element.component_type) {
+                mismatches.push(InputLayoutMismatch::ComponentType {
+                    semantic_name,
+                    semantic_index,
+                    format: desc.Format,
+                    expected: element.suggested_format(),
+                });
+            }
+        }
+
+        for (element, fed) in self.input_signature.iter().zip(fed) {
+            if !fed && element.system_value == D3D_NAME_UNDEFINED {
+                mismatches.push(InputLayoutMismatch::UnfedInput {
+                    semantic_name: element.semantic_name.clone(),
+                    semantic_index: element.semantic_index,
+                });
+            }
+        }
+
+        mismatches
+    }
+
+    pub fn unbound_resources(&self, bindings: &[RootBinding]) -> Vec<&BoundResource> {
+        self.bound_resources
+            .iter()
+            .filter(|resource| !bindings.iter().any(|binding| binding.covers(resource)))
+            .collect()
+    }
+}
+
+unsafe fn pcstr_to_string(s: PCSTR) -> String {
+    if s.is_null() {
+        return String::new();
+    }
+    String::from_utf8_lossy(s.as_bytes()).into_owned()
+}
+
+fn format_matches_component_type(
+    format: DXGI_FORMAT,
+    component_type: D3D_REGISTER_COMPONENT_TYPE,
+) -> bool {
+    let integer = matches!(
+        format,
+        DXGI_FORMAT_R32_UINT
+            | DXGI_FORMAT_R32G32_UINT
+            | DXGI_FORMAT_R32G32B32_UINT
+            | DXGI_FORMAT_R32G32B32A32_UINT
+            | DXGI_FORMAT_R16_UINT
+            | DXGI_FORMAT_R16G16_UINT
+            | DXGI_FORMAT_R16G16B16A16_UINT
+            | DXGI_FORMAT_R8_UINT
+            | DXGI_FORMAT_R8G8_UINT
+            | DXGI_FORMAT_R8G8B8A8_UINT
+            | DXGI_FORMAT_R32_SINT
+            | DXGI_FORMAT_R32G32_SINT
+            | DXGI_FORMAT_R32G32B32_SINT
+            | DXGI_FORMAT_R32G32B32A32_SINT
+            | DXGI_FORMAT_R16_SINT
+            | DXGI_FORMAT_R16G16_SINT
+            | DXGI_FORMAT_R16G16B16A16_SINT
+            | DXGI_FORMAT_R8_SINT
+            | DXGI_FORMAT_R8G8_SINT
+            | DXGI_FORMAT_R8G8B8A8_SINT
+    );
+    match component_type {
+        D3D_REGISTER_COMPONENT_FLOAT32 => !integer,
+        D3D_REGISTER_COMPONENT_UINT32 | D3D_REGISTER_COMPONENT_SINT32 => integer,
+        _ => true,
+    }
+}
+
+fn parse_signature(chunk: Chunk, stride: usize, has_stream: bool) -> Result<Vec<SignatureElement>> {
+    let count = chunk.u32(0)? as usize;
+    let first = chunk.u32(4)? as usize;
+    let extended = stride == 32;
+
+    (0..count)
+        .map(|i| {
+            let mut at = first + i * stride;
+            let stream = if has_stream {
+                at += 4;
+                chunk.u32(at - 4)?
+            } else {
+                0
+            };
+            Ok(SignatureElement {
+                semantic_name: chunk.cstr(chunk.u32(at)? as usize)?,
+                semantic_index: chunk.u32(at + 4)?,
+                system_value: D3D_NAME(chunk.i32(at + 8)?),
+                component_type: D3D_REGISTER_COMPONENT_TYPE(chunk.i32(at + 12)?),
+                register: chunk.u32(at + 16)?,
+                mask: chunk.u8(at + 20)?,
+                read_write_mask: chunk.u8(at + 21)?,
+                stream,
+                min_precision: if extended {
+                    D3D_MIN_PRECISION(chunk.i32(at + 24)?)
+                } else {
+                    D3D_MIN_PRECISION_DEFAULT
+                },
+            })
+        })
+        .collect()
+}
+
+type RdefContents = (
+    Option<String>,
+    Vec<ConstantBufferDesc>,
+    Vec<BoundResource>,
+    Option<ShaderVersion>,
+);
+
+fn parse_rdef(chunk: Chunk) -> Result<RdefContents> {
+    let cbuffer_count = chunk.u32(0)? as usize;
+    let cbuffer_offset = chunk.u32(4)? as usize;
+    let resource_count = chunk.u32(8)? as usize;
+    let resource_offset = chunk.u32(12)? as usize;
+    let target = chunk.u32(16)?;
+    let creator_offset = chunk.u32(24)? as usize;
+
+    let major = ((target >> 8) & 0xff) as u8;
+    let minor = (target & 0xff) as u8;
+    let version = ShaderVersion {
+        kind: ShaderKind::from_rdef_target((target >> 16) as u16),
+        major,
+        minor,
+    };
+    let sm5 = major >= 5 && chunk.u32(28).ok() == Some(RD11);
+    let sm51 = sm5 && (major > 5 || minor >= 1);
+
+    let creator = if creator_offset != 0 {
+        Some(chunk.cstr(creator_offset)?)
+    } else {
+        None
+    };
+
+    let resource_stride = if sm51 { 40 } else { 32 };
+    let bound_resources = (0..resource_count)
+        .map(|i| {
+            let at = resource_offset + i * resource_stride;
+            Ok(BoundResource {
+                name: chunk.cstr(chunk.u32(at)? as usize)?,
+                input_type: D3D_SHADER_INPUT_TYPE(chunk.i32(at + 4)?),
+                return_type: D3D_RESOURCE_RETURN_TYPE(chunk.i32(at + 8)?),
+                dimension: D3D_SRV_DIMENSION(chunk.i32(at + 12)?),
+                num_samples: chunk.u32(at + 16)?,
+                bind_point: chunk.u32(at + 20)?,
+                bind_count: chunk.u32(at + 24)?,
+                flags: chunk.u32(at + 28)?,
+                space: if sm51 { chunk.u32(at + 32)? } else { 0 },
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let variable_stride = if sm5 { 40 } else { 24 };
+    let constant_buffers = (0..cbuffer_count)
+        .map(|i| {
+            let at = cbuffer_offset + i * 24;
+            let variable_count = chunk.u32(at + 4)? as usize;
+            let variable_offset = chunk.u32(at + 8)? as usize;
+            let variables = (0..variable_count)
+                .map(|v| {
+                    let vat = variable_offset + v * variable_stride;
+                    Ok(ShaderVariable {
+                        name: chunk.cstr(chunk.u32(vat)? as usize)?,
+                        start_offset: chunk.u32(vat + 4)?,
+                        size: chunk.u32(vat + 8)?,
+                        flags: chunk.u32(vat + 12)?,
+                        variable_type: parse_type(chunk, chunk.u32(vat + 16)? as usize, sm5, 0)?,
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?;
+            Ok(ConstantBufferDesc {
+                name: chunk.cstr(chunk.u32(at)? as usize)?,
+                variables,
+                size: chunk.u32(at + 12)?,
+                flags: chunk.u32(at + 16)?,
+                buffer_type: D3D_CBUFFER_TYPE(chunk.i32(at + 20)?),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok((creator, constant_buffers, bound_resources, Some(version)))
+}
+
+fn parse_type(chunk: Chunk, offset: usize, sm5: bool, depth: u32) -> Result<ShaderType> {
+    if depth > 32 {
+        return Err(ReflectionError::TypeNestingTooDeep);
+    }
+    let member_count = chunk.u16(offset + 10)? as usize;
+    let member_offset = chunk.u32(offset + 12)? as usize;
+    let name = if sm5 {
+        match chunk.u32(offset + 32)? {
+            0 => None,
+            name_offset => Some(chunk.cstr(name_offset as usize)?),
+        }
+    } else {
+        None
+    };
+
+    let members = (0..member_count)
+        .map(|i| {
+            let at = member_offset + i * 12;
+            Ok(ShaderTypeMember {
+                name: chunk.cstr(chunk.u32(at)? as usize)?,
+                member_type: parse_type(chunk, chunk.u32(at + 4)? as usize, sm5, depth + 1)?,
+                offset: chunk.u32(at + 8)?,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(ShaderType {
+        name,
+        class: D3D_SHADER_VARIABLE_CLASS(chunk.u16(offset)? as i32),
+        variable_type: D3D_SHADER_VARIABLE_TYPE(chunk.u16(offset + 2)? as i32),
+        rows: chunk.u16(offset + 4)?,
+        columns: chunk.u16(offset + 6)?,
+        elements: chunk.u16(offset + 8)?,
+        members,
+    })
+}
+
+fn psv_resource_input_type(resource_type: u32) -> D3D_SHADER_INPUT_TYPE {
+    match resource_type {
+        1 => D3D_SIT_SAMPLER,
+        2 => D3D_SIT_CBUFFER,
+        3 => D3D_SIT_TEXTURE,
+        4 => D3D_SIT_BYTEADDRESS,
+        5 => D3D_SIT_STRUCTURED,
+        6 => D3D_SIT_UAV_RWTYPED,
+        7 => D3D_SIT_UAV_RWBYTEADDRESS,
+        8 => D3D_SIT_UAV_RWSTRUCTURED,
+        9 => D3D_SIT_UAV_RWSTRUCTURED_WITH_COUNTER,
+        _ => D3D_SIT_TEXTURE,
+    }
+}
+
+fn parse_psv0(chunk: Chunk) -> Result<(PipelineStateValidation, Vec<BoundResource>)> {
+    let info_size = chunk.u32(0)? as usize;
+    let info = Chunk {
+        data: chunk.bytes(4, info_size)?,
+    };
+    let mut at = 4 + info_size;
+
+    let mut psv = PipelineStateValidation {
+        shader_kind: None,
+        minimum_wave_lane_count: info.u32(16)?,
+        maximum_wave_lane_count: info.u32(20)?,
+        uses_view_id: false,
+        num_threads: None,
+        entry_function_name: None,
+        inputs: Vec::new(),
+        outputs: Vec::new(),
+        patch_constant_or_primitive: Vec::new(),
+    };
+    let mut element_counts = [0usize; 3];
+    if info_size >= 36 {
+        psv.shader_kind = Some(ShaderKind::from_psv_stage(info.u8(24)?));
+        psv.uses_view_id = info.u8(25)? != 0;
+        element_counts = [
+            info.u8(28)? as usize,
+            info.u8(29)? as usize,
+            info.u8(30)? as usize,
+        ];
+    }
+    if info_size >= 48 {
+        psv.num_threads = Some([info.u32(36)?, info.u32(40)?, info.u32(44)?]);
+    }
+
+    let resource_count = chunk.u32(at)? as usize;
+    at += 4;
+    let mut resources = Vec::with_capacity(resource_count);
+    if resource_count > 0 {
+        let stride = chunk.u32(at)? as usize;
+        at += 4;
+        for _ in 0..resource_count {
+            let lower = chunk.u32(at + 8)?;
+            let upper = chunk.u32(at + 12)?;
+            resources.push(BoundResource {
+                name: String::new(),
+                input_type: psv_resource_input_type(chunk.u32(at)?),
+                return_type: D3D_RESOURCE_RETURN_TYPE(0),
+                dimension: D3D_SRV_DIMENSION_UNKNOWN,
+                num_samples: 0,
+                bind_point: lower,
+                bind_count: if upper == u32::MAX {
+                    0
+                } else {
+                    upper.wrapping_sub(lower).wrapping_add(1)
+                },
+                flags: 0,
+                space: chunk.u32(at + 4)?,
+            });
+            at += stride;
+        }
+    }
+
+    if info_size < 36 {
+        return Ok((psv, resources));
+    }
+
+    let string_table_size = chunk.u32(at)? as usize;
+    let string_table = Chunk {
+        data: chunk.bytes(at + 4, string_table_size)?,
+    };
+    at += 4 + string_table_size;
+
+    let index_count = chunk.u32(at)? as usize;
+    let semantic_indices = (0..index_count)
+        .map(|i| chunk.u32(at + 4 + i * 4))
+        .collect::<Result<Vec<_>>>()?;
+    at += 4 + index_count * 4;
+
+    if info_size >= 52 {
+        let name_offset = info.u32(48)? as usize;
+        psv.entry_function_name = Some(string_table.cstr(name_offset)?);
+    }
+
+    if element_counts.iter().any(|&c| c > 0) {
+        let stride = chunk.u32(at)? as usize;
+        at += 4;
+        let mut read_elements = |count: usize| -> Result<Vec<PsvSignatureElement>> {
+            let elements = (0..count)
+                .map(|i| {
+                    let e = chunk.sub(at + i * stride)?;
+                    let rows = e.u8(8)?;
+                    let index_start = e.u32(4)? as usize;
+                    let cols_and_start = e.u8(10)?;
+                    let mask_and_stream = e.u8(14)?;
+                    Ok(PsvSignatureElement {
+                        semantic_name: string_table.cstr(e.u32(0)? as usize)?,
+                        semantic_indices: semantic_indices
+                            .iter()
+                            .skip(index_start)
+                            .take(rows as usize)
+                            .copied()
+                            .collect(),
+                        rows,
+                        start_row: e.u8(9)?,
+                        columns: cols_and_start & 0xf,
+                        start_column: (cols_and_start >> 4) & 0x3,
+                        allocated: cols_and_start & 0x40 != 0,
+                        semantic_kind: e.u8(11)?,
+                        component_type: e.u8(12)?,
+                        interpolation_mode: e.u8(13)?,
+                        dynamic_mask: mask_and_stream & 0xf,
+                        stream: (mask_and_stream >> 4) & 0x3,
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?;
+            at += count * stride;
+            Ok(elements)
+        };
+        psv.inputs = read_elements(element_counts[0])?;
+        psv.outputs = read_elements(element_counts[1])?;
+        psv.patch_constant_or_primitive = read_elements(element_counts[2])?;
+    }
+
+    Ok((psv, resources))
+}
+
+#[cfg(test)]
+mod tests {
+    use windows::core::s;
+
+    use super::*;
+
+    // Both blobs are compiled from the .hlsl files beside them; each one names its command.
+    const BASIC_VS: &[u8] = include_bytes!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/shader_reflection/basic_vs.dxbc"
+    ));
+    const MESH_MS: &[u8] = include_bytes!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/shader_reflection/mesh_ms.dxil"
+    ));
+
+    fn element(name: PCSTR, format: DXGI_FORMAT) -> D3D12_INPUT_ELEMENT_DESC {
+        D3D12_INPUT_ELEMENT_DESC {
+            SemanticName: name,
+            SemanticIndex: 0,
+            Format: format,
+            InputSlot: 0,
+            AlignedByteOffset: D3D12_APPEND_ALIGNED_ELEMENT,
+            InputSlotClass: D3D12_INPUT_CLASSIFICATION_PER_VERTEX_DATA,
+            InstanceDataStepRate: 0,
+        }
+    }
+
+    fn basic_layout() -> Vec<D3D12_INPUT_ELEMENT_DESC> {
+        vec![
+            element(s!("POSITION"), DXGI_FORMAT_R32G32B32_FLOAT),
+            element(s!("NORMAL"), DXGI_FORMAT_R32G32B32_FLOAT),
+            element(s!("TEXCOORD"), DXGI_FORMAT_R32G32_FLOAT),
+            element(s!("BONENO"), DXGI_FORMAT_R16G16B16A16_UINT),
+            element(s!("WEIGHT"), DXGI_FORMAT_R32G32B32A32_FLOAT),
+        ]
+    }
+
+    #[test]
+    fn parses_dxbc_header_and_shex_version() {
+        let reflection = ShaderReflection::parse(BASIC_VS).unwrap();
+        assert_eq!(reflection.container, ContainerKind::Dxbc);
+        assert_eq!(
+            reflection.version,
+            Some(ShaderVersion {
+                kind: ShaderKind::Vertex,
+                major: 5,
+                minor: 0,
+            })
+        );
+        assert_eq!(
+            reflection.creator.as_deref(),
+            Some("Microsoft (R) HLSL Shader Compiler 10.1")
+        );
+        // Version and length tokens, then dcl_globalFlags first and ret last.
+        let tokens = &reflection.instruction_tokens;
+        assert_eq!(tokens[..3], [0x0001_0050, tokens.len() as u32, 0x0100_086a]);
+        assert_eq!(tokens.last(), Some(&0x0100_003e));
+        assert!(reflection.pipeline_state_validation.is_none());
+    }
+
+    #[test]
+    fn parses_isgn_and_osgn() {
+        let reflection = ShaderReflection::parse(BASIC_VS).unwrap();
+        let names: Vec<_> = reflection
+            .input_signature
+            .iter()
+            .map(|e| e.semantic_name.as_str())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "POSITION",
+                "NORMAL",
+                "TEXCOORD",
+                "BONENO",
+                "WEIGHT",
+                "SV_VertexID"
+            ]
+        );
+
+        let texcoord = &reflection.input_signature[2];
+        assert_eq!(texcoord.register, 2);
+        assert_eq!(texcoord.mask, 0x3);
+        assert_eq!(texcoord.component_count(), 2);
+        assert_eq!(texcoord.suggested_format(), DXGI_FORMAT_R32G32_FLOAT);
+
+        let bone_no = &reflection.input_signature[3];
+        assert_eq!(bone_no.component_type, D3D_REGISTER_COMPONENT_UINT32);
+        assert_eq!(bone_no.suggested_format(), DXGI_FORMAT_R32G32B32A32_UINT);
+
+        let vertex_id = &reflection.input_signature[5];
+        assert_eq!(vertex_id.system_value, D3D_NAME_VERTEX_ID);
+
+        assert_eq!(reflection.output_signature.len(), 2);
+        let position = &reflection.output_signature[0];
+        assert_eq!(position.semantic_name, "SV_POSITION");
+        assert_eq!(position.system_value, D3D_NAME_POSITION);
+        assert_eq!(position.component_count(), 4);
+        assert_eq!(reflection.output_signature[1].read_write_mask, 0xc);
+    }
+
+    #[test]
+    fn parses_rdef_constant_buffers() {
+        let reflection = ShaderReflection::parse(BASIC_VS).unwrap();
+        assert_eq!(reflection.constant_buffers.len(), 3);
+
+        let scene = reflection.constant_buffer("SceneConstants").unwrap();
+        assert_eq!(scene.size, 64);
+        let mat = scene.variable("mat").unwrap();
+        assert_eq!((mat.start_offset, mat.size), (0, 64));
+        assert_eq!(mat.variable_type.class, D3D_SVC_MATRIX_COLUMNS);
+        assert_eq!(mat.variable_type.variable_type, D3D_SVT_FLOAT);
+        assert_eq!(mat.variable_type.name.as_deref(), Some("float4x4"));
+
+        let bones = reflection.constant_buffer_at(2, 0).unwrap();
+        assert_eq!(bones.name, "BoneConstants");
+        assert_eq!(bones.variable("bones").unwrap().variable_type.elements, 256);
+
+        let light = reflection
+            .constant_buffer_at(3, 0)
+            .unwrap()
+            .variable("light")
+            .unwrap();
+        assert_eq!(light.variable_type.class, D3D_SVC_STRUCT);
+        let members: Vec<_> = light
+            .variable_type
+            .members
+            .iter()
+            .map(|m| (m.name.as_str(), m.offset, m.member_type.columns))
+            .collect();
+        assert_eq!(members, [("direction", 0, 3), ("intensity", 12, 1)]);
+
+        assert!(reflection.constant_buffer_at(1, 0).is_none());
+    }
+
+    #[test]
+    fn parses_rdef_bound_resources() {
+        let reflection = ShaderReflection::parse(BASIC_VS).unwrap();
+        let resources: Vec<_> = reflection
+            .bound_resources
+            .iter()
+            .map(|r| (r.name.as_str(), r.input_type, r.bind_point))
+            .collect();
+        assert_eq!(
+            resources,
+            [
+                ("smp", D3D_SIT_SAMPLER, 0),
+                ("tex", D3D_SIT_TEXTURE, 0),
+                ("SceneConstants", D3D_SIT_CBUFFER, 0),
+                ("BoneConstants", D3D_SIT_CBUFFER, 2),
+                ("Lighting", D3D_SIT_CBUFFER, 3),
+            ]
+        );
+        let texture = &reflection.bound_resources[1];
+        assert_eq!(texture.dimension, D3D_SRV_DIMENSION_TEXTURE2D);
+        assert_eq!(texture.return_type, D3D_RETURN_TYPE_FLOAT);
+        assert_eq!(
+            texture.descriptor_range_type(),
+            D3D12_DESCRIPTOR_RANGE_TYPE_SRV
+        );
+    }
+
+    #[test]
+    fn matching_input_layout_has_no_mismatches() {
+        let reflection = ShaderReflection::parse(BASIC_VS).unwrap();
+        assert_eq!(reflection.validate_input_layout(&basic_layout()), []);
+    }
+
+    #[test]
+    fn input_layout_mismatches_are_reported() {
+        let reflection = ShaderReflection::parse(BASIC_VS).unwrap();
+        let mut layout = basic_layout();
+        layout[3].Format = DXGI_FORMAT_R32G32_FLOAT;
+        layout.remove(4);
+        layout.push(element(s!("COLOR"), DXGI_FORMAT_R8G8B8A8_UNORM));
+
+        assert_eq!(
+            reflection.validate_input_layout(&layout),
+            [
+                InputLayoutMismatch::ComponentType {
+                    semantic_name: "BONENO".to_string(),
+                    semantic_index: 0,
+                    format: DXGI_FORMAT_R32G32_FLOAT,
+                    expected: DXGI_FORMAT_R32G32B32A32_UINT,
+                },
+                InputLayoutMismatch::MissingSemantic {
+                    semantic_name: "COLOR".to_string(),
+                    semantic_index: 0,
+                },
+                InputLayoutMismatch::UnfedInput {
+                    semantic_name: "WEIGHT".to_string(),
+                    semantic_index: 0,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn unbound_resources_respect_root_bindings() {
+        let reflection = ShaderReflection::parse(BASIC_VS).unwrap();
+        let texture_range = D3D12_DESCRIPTOR_RANGE {
+            RangeType: D3D12_DESCRIPTOR_RANGE_TYPE_SRV,
+            NumDescriptors: 1,
+            BaseShaderRegister: 0,
+            RegisterSpace: 0,
+            OffsetInDescriptorsFromTableStart: 0,
+        };
+        let sampler = D3D12_STATIC_SAMPLER_DESC {
+            ShaderRegister: 0,
+            ..Default::default()
+        };
+        let descriptor = |register| D3D12_ROOT_DESCRIPTOR {
+            ShaderRegister: register,
+            RegisterSpace: 0,
+        };
+        let bindings = [
+            RootBinding::from_range(&texture_range),
+            RootBinding::from_static_sampler(&sampler),
+            RootBinding::from_root_descriptor(D3D12_ROOT_PARAMETER_TYPE_CBV, &descriptor(0)),
+            RootBinding::from_root_descriptor(D3D12_ROOT_PARAMETER_TYPE_CBV, &descriptor(2)),
+        ];
+        let unbound: Vec<_> = reflection
+            .unbound_resources(&bindings)
+            .into_iter()
+            .map(|r| r.name.as_str())
+            .collect();
+        assert_eq!(unbound, ["Lighting"]);
+
+        let srv_at_b3 =
+            RootBinding::from_root_descriptor(D3D12_ROOT_PARAMETER_TYPE_SRV, &descriptor(3));
+        let mut bindings = bindings.to_vec();
+        bindings.push(srv_at_b3);
+        assert_eq!(reflection.unbound_resources(&bindings).len(), 1);
+    }
+
+    #[test]
+    fn parses_dxil_container_and_psv0() {
+        let reflection = ShaderReflection::parse(MESH_MS).unwrap();
+        assert_eq!(reflection.container, ContainerKind::Dxil);
+        assert_eq!(
+            reflection.version,
+            Some(ShaderVersion {
+                kind: ShaderKind::Mesh,
+                major: 6,
+                minor: 5,
+            })
+        );
+
+        let psv = reflection.pipeline_state_validation.as_ref().unwrap();
+        assert_eq!(psv.shader_kind, Some(ShaderKind::Mesh));
+        assert_eq!(
+            (psv.minimum_wave_lane_count, psv.maximum_wave_lane_count),
+            (4, 128)
+        );
+        assert_eq!(psv.num_threads, Some([128, 1, 1]));
+        assert_eq!(psv.entry_function_name.as_deref(), Some("main"));
+        assert!(psv.inputs.is_empty());
+
+        let outputs: Vec<_> = psv
+            .outputs
+            .iter()
+            .map(|e| (e.semantic_name.as_str(), e.start_row, e.columns))
+            .collect();
+        assert_eq!(outputs, [("SV_Position", 0, 4), ("TEXCOORD", 1, 2)]);
+        assert!(psv.outputs.iter().all(|e| e.allocated));
+        assert_eq!(psv.outputs[0].semantic_kind, 3);
+
+        let color = &psv.patch_constant_or_primitive[0];
+        assert_eq!(color.semantic_name, "COLOR");
+        assert_eq!(color.semantic_indices, [1]);
+        assert_eq!(color.component_type, 5);
+    }
+
+    #[test]
+    fn psv0_resources_fill_bound_resources() {
+        let reflection = ShaderReflection::parse(MESH_MS).unwrap();
+        let resources: Vec<_> = reflection
+            .bound_resources
+            .iter()
+            .map(|r| (r.input_type, r.space, r.bind_point, r.bind_count))
+            .collect();
+        assert_eq!(
+            resources,
+            [
+                (D3D_SIT_CBUFFER, 0, 0, 1),
+                (D3D_SIT_STRUCTURED, 0, 0, 1),
+                (D3D_SIT_STRUCTURED, 0, 1, 1),
+                (D3D_SIT_STRUCTURED, 0, 2, 1),
+                (D3D_SIT_TEXTURE, 1, 0, 0),
+            ]
+        );
+
+        let unbounded_table = RootBinding {
+            range_type: D3D12_DESCRIPTOR_RANGE_TYPE_SRV,
+            base_register: 0,
+            count: u32::MAX,
+            space: 1,
+        };
+        assert!(unbounded_table.covers(&reflection.bound_resources[4]));
+    }
+
+    #[test]
+    fn rejects_bad_magic_and_truncated_blobs() {
+        let mut blob = BASIC_VS.to_vec();
+        blob[0] = b'X';
+        assert_eq!(
+            ShaderReflection::parse(&blob),
+            Err(ReflectionError::InvalidMagic(u32::from_le_bytes(*b"XXBC")))
+        );
+
+        let truncated = &BASIC_VS[..BASIC_VS.len() / 2];
+        assert!(matches!(
+            ShaderReflection::parse(truncated),
+            Err(ReflectionError::UnexpectedEof { .. })
+        ));
+    }
+}
@@ -0,0 +1,421 @@
+use std::collections::HashMap;
+
+use cgmath::{InnerSpace, Vector3};
+
+use crate::model::MeshVertex;
+
+const BORDER_WEIGHT: f64 = 10.0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Simplified {
+    pub indices: Vec<u32>,
+    pub error: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum VertexKind {
+    Manifold,
+    Border,
+    Locked,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Quadric {
+    xx: f64,
+    xy: f64,
+    xz: f64,
+    xw: f64,
+    yy: f64,
+    yz: f64,
+    yw: f64,
+    zz: f64,
+    zw: f64,
+    ww: f64,
+    weight: f64,
+}
+
+impl Quadric {
+    fn from_plane(normal: Vector3<f64>, d: f64, weight: f64) -> Self {
+        let (a, b, c) = (normal.x, normal.y, normal.z);
+        Quadric {
+            xx: a * a * weight,
+            xy: a * b * weight,
+            xz: a * c * weight,
+            xw: a * d * weight,
+            yy: b * b * weight,
+            yz: b * c * weight,
+            yw: b * d * weight,
+            zz: c * c * weight,
+            zw: c * d * weight,
+            ww: d * d * weight,
+            weight,
+        }
+    }
+
+    fn add(&mut self, other: &Quadric) {
+        self.xx += other.xx;
+        self.xy += other.xy;
+        self.xz += other.xz;
+        self.xw += other.xw;
+        self.yy += other.yy;
+        self.yz += other.yz;
+        self.yw += other.yw;
+        self.zz += other.zz;
+        self.zw += other.zw;
+        self.ww += other.ww;
+        self.weight += other.weight;
+    }
+
+    fn error(&self, p: Vector3<f64>) -> f64 {
+        let (x, y, z) = (p.x, p.y, p.z);
+        let quadratic = self.xx * x * x
+            + self.yy * y * y
+            + self.zz * z * z
+            + 2.0 * (self.xy * x * y + self.xz * x * z + self.yz * y * z);
+        let linear = 2.0 * (self.xw * x + self.yw * y + self.zw * z);
+        if self.weight == 0.0 {
+            return 0.0;
+        }
+        (quadratic + linear + self.ww).abs() / self.weight
+    }
+}
+
+fn position(vertex: &MeshVertex) -> Vector3<f64> {
+    vertex.position.cast().unwrap()
+}
+
+fn edge_key(a: u32, b: u32) -> (u32, u32) {
+    (a.min(b), a.max(b))
+}
+
+pub fn simplify(
+    vertices: &[MeshVertex],
+    indices: &[u32],
+    target_index_count: usize,
+    max_error: f32,
+) -> Simplified {
+    let positions: Vec<Vector3<f64>> = vertices.iter().map(position).collect();
+    let mut result: Vec<u32> = indices[..indices.len() / 3 * 3].to_vec();
+
+    let mut first_at_position: HashMap<[u64; 3], u32> = HashMap::new();
+    let remap: Vec<u32> = positions
+        .iter()
+        .enumerate()
+        .map(|(i, p)| {
+            *first_at_position
+                .entry([p.x, p.y, p.z].map(f64::to_bits))
+                .or_insert(i as u32)
+        })
+        .collect();
+    let mut wedges = vec![0u32; vertices.len()];
+    for &r in &remap {
+        wedges[r as usize] += 1;
+    }
+
+    let mut edge_counts: HashMap<(u32, u32), u32> = HashMap::new();
+    for triangle in result.chunks_exact(3) {
+        for k in 0..3 {
+            let (a, b) = (
+                remap[triangle[k] as usize],
+                remap[triangle[(k + 1) % 3] as usize],
+            );
+            if a != b {
+                *edge_counts.entry(edge_key(a, b)).or_insert(0) += 1;
+            }
+        }
+    }
+    let is_border_edge = |a: u32, b: u32| {
+        edge_counts.get(&edge_key(remap[a as usize], remap[b as usize])) == Some(&1)
+    };
+
+    let mut kinds = vec![VertexKind::Manifold; vertices.len()];
+    for (i, kind) in kinds.iter_mut().enumerate() {
+        if wedges[remap[i] as usize] > 1 {
+            *kind = VertexKind::Locked;
+        }
+    }
+    for (&(a, b), &count) in &edge_counts {
+        for v in [a, b] {
+            let kind = &mut kinds[v as usize];
+            match (count, *kind) {
+                (_, VertexKind::Locked) => {}
+                (1, _) => *kind = VertexKind::Border,
+                (2, _) => {}
+                _ => *kind = VertexKind::Locked,
+            }
+        }
+    }
+
+    let mut quadrics = vec![Quadric::default(); vertices.len()];
+    for triangle in result.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|k| triangle[k]);
+        let [pa, pb, pc] = [a, b, c].map(|v| positions[v as usize]);
+        let cross = (pb - pa).cross(pc - pa);
+        let length = cross.magnitude();
+        if length == 0.0 {
+            continue;
+        }
+        let normal = cross / length;
+        let plane = Quadric::from_plane(normal, -normal.dot(pa), length * 0.5);
+        for v in [a, b, c] {
+            quadrics[remap[v as usize] as usize].add(&plane);
+        }
+        for (from, to) in [(a, b), (b, c), (c, a)] {
+            if !is_border_edge(from, to) {
+                continue;
+            }
+            let (p0, p1) = (positions[from as usize], positions[to as usize]);
+            let edge = p1 - p0;
+            let border_normal = edge.cross(normal);
+            if border_normal.magnitude2() == 0.0 {
+                continue;
+            }
+            let border_normal = border_normal.normalize();
+            let border = Quadric::from_plane(
+                border_normal,
+                -border_normal.dot(p0),
+                edge.magnitude2() * BORDER_WEIGHT,
+            );
+            quadrics[remap[from as usize] as usize].add(&border);
+            quadrics[remap[to as usize] as usize].add(&border);
+        }
+    }
+
+    let can_collapse = |from: u32, to: u32| match kinds[from as usize] {
+        VertexKind::Manifold => true,
+        VertexKind::Border => {
+            kinds[to as usize] != VertexKind::Manifold && is_border_edge(from, to)
+        }
+        VertexKind::Locked => false,
+    };
+
+    let mut error = 0.0f32;
+    while result.len() > target_index_count {
+        let mut adjacency: Vec<Vec<usize>> = vec![Vec::new(); vertices.len()];
+        for (t, triangle) in result.chunks_exact(3).enumerate() {
+            for &v in triangle {
+                adjacency[v as usize].push(t);
+            }
+        }
+
+        let mut candidates: Vec<(f64, u32, u32)> = Vec::new();
+        for triangle in result.chunks_exact(3) {
+            for k in 0..3 {
+                let (a, b) = (triangle[k], triangle[(k + 1) % 3]);
+                for (from, to) in [(a, b), (b, a)] {
+                    if from == to || !can_collapse(from, to) {
+                        continue;
+                    }
+                    let mut quadric = quadrics[remap[from as usize] as usize];
+                    quadric.add(&quadrics[remap[to as usize] as usize]);
+                    candidates.push((quadric.error(positions[to as usize]), from, to));
+                }
+            }
+        }
+        candidates.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)).then(a.2.cmp(&b.2)));
+        candidates.dedup_by(|a, b| a.1 == b.1 && a.2 == b.2);
+
+        let mut collapse: Vec<u32> = (0..vertices.len() as u32).collect();
+        let mut touched = vec![false; vertices.len()];
+        let mut triangle_count = result.len() / 3;
+        let mut collapsed = false;
+        for (cost, from, to) in candidates {
+            if triangle_count * 3 <= target_index_count {
+                break;
+            }
+            let collapse_error = cost.sqrt() as f32;
+            if collapse_error > max_error {
+                break;
+            }
+            if touched[from as usize] || touched[to as usize] {
+                continue;
+            }
+            let triangles = &adjacency[from as usize];
+            let flips = triangles.iter().any(|&t| {
+                let triangle = &result[t * 3..t * 3 + 3];
+                if triangle.contains(&to) {
+                    return false;
+                }
+                let [a, b, c] = [0, 1, 2].map(|k| positions[triangle[k] as usize]);
+                let moved = [0, 1, 2].map(|k| {
+                    let v = if triangle[k] == from { to } else { triangle[k] };
+                    positions[v as usize]
+                });
+                let before = (b - a).cross(c - a);
+                let after = (moved[1] - moved[0]).cross(moved[2] - moved[0]);
+                before.dot(after) <= 0.0
+            });
+            if flips {
+                continue;
+            }
+
+            collapse[from as usize] = to;
+            let merged = quadrics[remap[from as usize] as usize];
+            quadrics[remap[to as usize] as usize].add(&merged);
+            triangle_count -= triangles
+                .iter()
+                .filter(|&&t| result[t * 3..t * 3 + 3].contains(&to))
+                .count();
+            for &t in triangles {
+                for &v in &result[t * 3..t * 3 + 3] {
+                    touched[v as usize] = true;
+                }
+            }
+            error = error.max(collapse_error);
+            collapsed = true;
+        }
+        if !collapsed {
+            break;
+        }
+
+        result = result
+            .chunks_exact(3)
+            .map(|triangle| [0, 1, 2].map(|k| collapse[triangle[k] as usize]))
+            .filter(|[a, b, c]| a != b && b != c && c != a)
+            .flatten()
+            .collect();
+    }
+
+    Simplified {
+        indices: result,
+        error,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::{Vector2, Zero};
+
+    const EPSILON: f32 = 1e-4;
+
+    /// A `size` x `size` grid in the xy plane lifted by `height(x, y)`.
+    fn grid(size: u32, height: impl Fn(f32, f32) -> f32) -> (Vec<MeshVertex>, Vec<u32>) {
+        let vertices = (0..=size)
+            .flat_map(|y| (0..=size).map(move |x| (x as f32, y as f32)))
+            .map(|(x, y)| MeshVertex {
+                position: Vector3::new(x, y, height(x, y)),
+                uv: Vector2::new(x, y) / size as f32,
+                ..Default::default()
+            })
+            .collect();
+        let row = size + 1;
+        let indices = (0..size)
+            .flat_map(|y| (0..size).map(move |x| y * row + x))
+            .flat_map(|i| [i, i + 1, i + row + 1, i, i + row + 1, i + row])
+            .collect();
+        (vertices, indices)
+    }
+
+    fn flat(_: f32, _: f32) -> f32 {
+        0.0
+    }
+
+    fn bowl(x: f32, y: f32) -> f32 {
+        (x * x + y * y) * 0.1
+    }
+
+    fn normals(vertices: &[MeshVertex], indices: &[u32]) -> Vec<Vector3<f32>> {
+        indices
+            .chunks_exact(3)
+            .map(|t| {
+                let [a, b, c] = [0, 1, 2].map(|k| vertices[t[k] as usize].position);
+                (b - a).cross(c - a)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn planar_grid_collapses_without_error() {
+        let (vertices, indices) = grid(8, flat);
+        let simplified = simplify(&vertices, &indices, indices.len() / 4, f32::MAX);
+        assert!(simplified.indices.len() <= indices.len() / 4);
+        assert!(simplified.error < EPSILON, "error {}", simplified.error);
+
+        // The outline survives: same area, no flipped or degenerate triangles.
+        let normals = normals(&vertices, &simplified.indices);
+        let area: f32 = normals.iter().map(|n| n.z * 0.5).sum();
+        assert!((area - 64.0).abs() < EPSILON, "area {area}");
+        assert!(normals.iter().all(|n| n.z > 0.0));
+        for corner in [0, 8, 72, 80] {
+            assert!(simplified.indices.contains(&corner), "lost corner {corner}");
+        }
+    }
+
+    #[test]
+    fn simplification_is_deterministic() {
+        let (vertices, indices) = grid(12, bowl);
+        let first = simplify(&vertices, &indices, indices.len() / 3, f32::MAX);
+        // Every call builds fresh, randomly seeded hash maps.
+        for _ in 0..4 {
+            assert_eq!(
+                simplify(&vertices, &indices, indices.len() / 3, f32::MAX),
+                first
+            );
+        }
+    }
+
+    #[test]
+    fn max_error_bounds_curved_collapses() {
+        let (vertices, indices) = grid(8, bowl);
+        let untouched = simplify(&vertices, &indices, 0, 0.0);
+        assert_eq!(untouched.indices, indices);
+        assert_eq!(untouched.error, 0.0);
+
+        let limit = 0.05;
+        let bounded = simplify(&vertices, &indices, 0, limit);
+        assert!(bounded.indices.len() < indices.len());
+        assert!(bounded.error > 0.0 && bounded.error <= limit);
+
+        let loose = simplify(&vertices, &indices, 0, f32::MAX);
+        assert!(loose.indices.len() < bounded.indices.len());
+        assert!(loose.error >= bounded.error);
+    }
+
+    #[test]
+    fn seams_are_locked() {
+        // Split the grid down the middle column with a uv seam.
+        let (mut vertices, mut indices) = grid(4, flat);
+        let seam: Vec<u32> = (0..5).map(|y| y * 5 + 2).collect();
+        let mut copies = HashMap::new();
+        for &v in &seam {
+            copies.insert(v, vertices.len() as u32);
+            vertices.push(MeshVertex {
+                uv: Vector2::zero(),
+                ..vertices[v as usize]
+            });
+        }
+        for triangle in indices.chunks_exact_mut(3) {
+            let right = triangle
+                .iter()
+                .any(|&v| vertices[v as usize].position.x > 2.0);
+            if right {
+                for v in triangle.iter_mut() {
+                    if let Some(&copy) = copies.get(v) {
+                        *v = copy;
+                    }
+                }
+            }
+        }
+
+        let simplified = simplify(&vertices, &indices, 0, f32::MAX);
+        for v in seam.iter().chain(copies.values()) {
+            assert!(simplified.indices.contains(v), "seam vertex {v} collapsed");
+        }
+    }
+
+    #[test]
+    fn handles_empty_and_partial_input() {
+        assert_eq!(
+            simplify(&[], &[], 0, f32::MAX),
+            Simplified {
+                indices: Vec::new(),
+                error: 0.0,
+            }
+        );
+        let (vertices, mut indices) = grid(1, flat);
+        indices.push(0);
+        let simplified = simplify(&vertices, &indices, 6, f32::MAX);
+        assert_eq!(simplified.indices, indices[..6]);
+    }
+}
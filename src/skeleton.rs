@@ -0,0 +1,146 @@
+use cgmath::{Matrix4, One, Quaternion, SquareMatrix, Vector3, Zero};
+
+use crate::pmd_loader::PmdModel;
+use crate::pmx_loader::PmxModel;
+
+pub const MAX_BONES: usize = 256;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bone {
+    pub name: String,
+    pub parent: Option<usize>,
+    pub children: Vec<usize>,
+    pub position: Vector3<f32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoneTransform {
+    pub translation: Vector3<f32>,
+    pub rotation: Quaternion<f32>,
+}
+
+impl Default for BoneTransform {
+    fn default() -> Self {
+        BoneTransform {
+            translation: Vector3::zero(),
+            rotation: Quaternion::one(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pose {
+    pub bones: Vec<BoneTransform>,
+}
+
+impl Pose {
+    pub fn rest(bone_count: usize) -> Self {
+        Pose {
+            bones: vec![BoneTransform::default(); bone_count],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Skeleton {
+    pub bones: Vec<Bone>,
+}
+
+impl Skeleton {
+    pub fn new(mut bones: Vec<Bone>) -> Self {
+        for bone in &mut bones {
+            bone.children.clear();
+        }
+        for i in 0..bones.len() {
+            if let Some(parent) = bones[i].parent.filter(|&p| p < bones.len() && p != i) {
+                bones[parent].children.push(i);
+            } else {
+                bones[i].parent = None;
+            }
+        }
+        Skeleton { bones }
+    }
+
+    pub fn from_pmd(pmd: &PmdModel) -> Self {
+        Self::new(
+            pmd.bones
+                .iter()
+                .map(|bone| Bone {
+                    name: bone.name.clone(),
+                    parent: bone.parent.map(|p| p as usize),
+                    children: Vec::new(),
+                    position: bone.position,
+                })
+                .collect(),
+        )
+    }
+
+    pub fn from_pmx(pmx: &PmxModel) -> Self {
+        Self::new(
+            pmx.bones
+                .iter()
+                .map(|bone| Bone {
+                    name: bone.name.clone(),
+                    parent: bone.parent.map(|p| p as usize),
+                    children: Vec::new(),
+                    position: bone.position,
+                })
+                .collect(),
+        )
+    }
+
+    pub fn len(&self) -> usize {
+        self.bones.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bones.is_empty()
+    }
+
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.bones.iter().position(|bone| bone.name == name)
+    }
+
+    pub fn roots(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.bones.len()).filter(|&i| self.bones[i].parent.is_none())
+    }
+
+    pub fn local_matrix(&self, bone: usize, transform: &BoneTransform) -> Matrix4<f32> {
+        let position = self.bones[bone].position;
+        Matrix4::from_translation(position + transform.translation)
+            * Matrix4::from(transform.rotation)
+            * Matrix4::from_translation(-position)
+    }
+
+    pub fn skinning_matrices(&self, pose: &Pose) -> Vec<Matrix4<f32>> {
+        let mut matrices = vec![Matrix4::identity(); self.bones.len()];
+        let mut stack: Vec<(usize, Matrix4<f32>)> =
+            self.roots().map(|i| (i, Matrix4::identity())).collect();
+        while let Some((bone, parent)) = stack.pop() {
+            let transform = pose.bones.get(bone).copied().unwrap_or_default();
+            matrices[bone] = parent * self.local_matrix(bone, &transform);
+            for &child in &self.bones[bone].children {
+                stack.push((child, matrices[bone]));
+            }
+        }
+        matrices
+    }
+
+    pub fn update_subtree(&self, root: usize, pose: &Pose, matrices: &mut [Matrix4<f32>]) {
+        let parent = self.bones[root]
+            .parent
+            .map_or(Matrix4::identity(), |parent| matrices[parent]);
+        let mut stack = vec![(root, parent)];
+        while let Some((bone, parent)) = stack.pop() {
+            let transform = pose.bones.get(bone).copied().unwrap_or_default();
+            matrices[bone] = parent * self.local_matrix(bone, &transform);
+            for &child in &self.bones[bone].children {
+                stack.push((child, matrices[bone]));
+            }
+        }
+    }
+
+    pub fn world_position(&self, bone: usize, matrices: &[Matrix4<f32>]) -> Vector3<f32> {
+        (matrices[bone] * self.bones[bone].position.extend(1.0)).truncate()
+    }
+}
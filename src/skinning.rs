@@ -0,0 +1,693 @@
+use cgmath::{InnerSpace, Matrix3, Matrix4, Quaternion, Vector3, Vector4, Zero};
+use windows::Win32::Graphics::Direct3D12::{D3D12_INDEX_BUFFER_VIEW, D3D12_VERTEX_BUFFER_VIEW};
+use windows::Win32::Graphics::Dxgi::Common::DXGI_FORMAT_R32_UINT;
+
+use crate::model::MeshVertex;
+use crate::skeleton::MAX_BONES;
+use crate::upload_ring::UploadRing;
+use crate::vertex_layout::VertexLayout;
+
+pub const MAX_INFLUENCES: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkinningMethod {
+    Linear,
+    DualQuaternion,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkinningMode {
+    GpuLinear,
+    GpuDualQuaternion,
+    CpuLinear,
+    CpuDualQuaternion,
+}
+
+impl SkinningMode {
+    pub fn next(self) -> Self {
+        match self {
+            SkinningMode::GpuLinear => SkinningMode::GpuDualQuaternion,
+            SkinningMode::GpuDualQuaternion => SkinningMode::CpuLinear,
+            SkinningMode::CpuLinear => SkinningMode::CpuDualQuaternion,
+            SkinningMode::CpuDualQuaternion => SkinningMode::GpuLinear,
+        }
+    }
+
+    pub fn method(self) -> SkinningMethod {
+        match self {
+            SkinningMode::GpuLinear | SkinningMode::CpuLinear => SkinningMethod::Linear,
+            SkinningMode::GpuDualQuaternion | SkinningMode::CpuDualQuaternion => {
+                SkinningMethod::DualQuaternion
+            }
+        }
+    }
+
+    pub fn is_cpu(self) -> bool {
+        matches!(
+            self,
+            SkinningMode::CpuLinear | SkinningMode::CpuDualQuaternion
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DualQuaternion {
+    pub real: Quaternion<f32>,
+    pub dual: Quaternion<f32>,
+}
+
+impl DualQuaternion {
+    pub fn identity() -> Self {
+        DualQuaternion {
+            real: Quaternion::new(1.0, 0.0, 0.0, 0.0),
+            dual: Quaternion::new(0.0, 0.0, 0.0, 0.0),
+        }
+    }
+
+    pub fn from_rotation_translation(rotation: Quaternion<f32>, translation: Vector3<f32>) -> Self {
+        let real = rotation.normalize();
+        DualQuaternion {
+            real,
+            dual: Quaternion::from_sv(0.0, translation) * real * 0.5,
+        }
+    }
+
+    pub fn from_matrix(matrix: &Matrix4<f32>) -> Self {
+        let rotation = Matrix3::from_cols(
+            matrix.x.truncate(),
+            matrix.y.truncate(),
+            matrix.z.truncate(),
+        );
+        Self::from_rotation_translation(Quaternion::from(rotation), matrix.w.truncate())
+    }
+
+    pub fn translation(&self) -> Vector3<f32> {
+        (self.dual * self.real.conjugate() * 2.0).v
+    }
+
+    pub fn transform_point(&self, point: Vector3<f32>) -> Vector3<f32> {
+        self.transform_vector(point) + self.translation()
+    }
+
+    pub fn transform_vector(&self, vector: Vector3<f32>) -> Vector3<f32> {
+        let (r, w) = (self.real.v, self.real.s);
+        vector + r.cross(r.cross(vector) + vector * w) * 2.0
+    }
+
+    pub fn blend(influences: &[(DualQuaternion, f32)]) -> Self {
+        let Some(&(pivot, _)) = influences.first() else {
+            return Self::identity();
+        };
+        let mut real = Quaternion::new(0.0, 0.0, 0.0, 0.0);
+        let mut dual = Quaternion::new(0.0, 0.0, 0.0, 0.0);
+        for &(dq, weight) in influences {
+            let weight = if dq.real.dot(pivot.real) < 0.0 {
+                -weight
+            } else {
+                weight
+            };
+            real += dq.real * weight;
+            dual += dq.dual * weight;
+        }
+        let length = real.magnitude();
+        if length < f32::EPSILON {
+            return Self::identity();
+        }
+        DualQuaternion {
+            real: real / length,
+            dual: dual / length,
+        }
+    }
+
+    pub fn to_vectors(self) -> (Vector4<f32>, Vector4<f32>) {
+        let pack = |q: Quaternion<f32>| Vector4::new(q.v.x, q.v.y, q.v.z, q.s);
+        (pack(self.real), pack(self.dual))
+    }
+}
+
+/// Spherical deformation between the first two influences (PMX SDEF).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sdef {
+    pub c: Vector3<f32>,
+    pub r0: Vector3<f32>,
+    pub r1: Vector3<f32>,
+}
+
+impl Sdef {
+    /// Moves R0/R1 so that their weighted average lies on C and stores the midpoints between
+    /// C and each of them, which is what the deformation consumes.
+    pub fn new(c: Vector3<f32>, r0: Vector3<f32>, r1: Vector3<f32>, weight: f32) -> Self {
+        let rw = r0 * weight + r1 * (1.0 - weight);
+        Sdef {
+            c,
+            r0: c + (r0 - rw) * 0.5,
+            r1: c + (r1 - rw) * 0.5,
+        }
+    }
+}
+
+/// How a vertex combines its influences.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Deform {
+    /// Follows the selected `SkinningMethod`.
+    Blend,
+    DualQuaternion,
+    Sdef(Sdef),
+}
+
+impl Deform {
+    pub fn shader_id(self) -> u8 {
+        match self {
+            Deform::Blend => 0,
+            Deform::DualQuaternion => 1,
+            Deform::Sdef(_) => 2,
+        }
+    }
+}
+
+pub trait Skinnable: Copy {
+    fn position(&self) -> Vector3<f32>;
+    fn normal(&self) -> Vector3<f32>;
+    fn influences(&self) -> [(usize, f32); MAX_INFLUENCES];
+    fn set_skinned(&mut self, position: Vector3<f32>, normal: Vector3<f32>);
+
+    fn deform(&self) -> Deform {
+        Deform::Blend
+    }
+}
+
+impl Skinnable for MeshVertex {
+    fn position(&self) -> Vector3<f32> {
+        self.position
+    }
+
+    fn normal(&self) -> Vector3<f32> {
+        self.normal
+    }
+
+    fn influences(&self) -> [(usize, f32); MAX_INFLUENCES] {
+        std::array::from_fn(|i| (self.joints[i] as usize, self.weights[i]))
+    }
+
+    fn set_skinned(&mut self, position: Vector3<f32>, normal: Vector3<f32>) {
+        self.position = position;
+        self.normal = normal;
+    }
+}
+
+pub fn skin_vertex<V: Skinnable>(
+    method: SkinningMethod,
+    matrices: &[Matrix4<f32>],
+    dual_quaternions: &[DualQuaternion],
+    vertex: &V,
+) -> V {
+    let influences = vertex
+        .influences()
+        .into_iter()
+        .filter(|&(bone, weight)| weight != 0.0 && bone < matrices.len());
+    let (position, normal) = (vertex.position(), vertex.normal());
+
+    let (position, normal) = match (vertex.deform(), method) {
+        (Deform::Sdef(sdef), _) => {
+            let [(b0, w0), (b1, w1), ..] = vertex.influences();
+            if b0 >= matrices.len() || b1 >= matrices.len() {
+                return *vertex;
+            }
+            skin_sdef(
+                &sdef,
+                [&matrices[b0], &matrices[b1]],
+                [dual_quaternions[b0].real, dual_quaternions[b1].real],
+                [w0, w1],
+                position,
+                normal,
+            )
+        }
+        (Deform::Blend, SkinningMethod::Linear) => {
+            let mut blended = Matrix4::zero();
+            let mut total = 0.0;
+            for (bone, weight) in influences {
+                blended += matrices[bone] * weight;
+                total += weight;
+            }
+            if total == 0.0 {
+                return *vertex;
+            }
+            (
+                (blended * position.extend(1.0)).truncate(),
+                (blended * normal.extend(0.0)).truncate(),
+            )
+        }
+        (Deform::Blend, SkinningMethod::DualQuaternion) | (Deform::DualQuaternion, _) => {
+            let influences: Vec<(DualQuaternion, f32)> = influences
+                .map(|(bone, weight)| (dual_quaternions[bone], weight))
+                .collect();
+            if influences.is_empty() {
+                return *vertex;
+            }
+            let dq = DualQuaternion::blend(&influences);
+            (dq.transform_point(position), dq.transform_vector(normal))
+        }
+    };
+
+    let mut skinned = *vertex;
+    let normal = if normal.magnitude2() > 0.0 {
+        normal.normalize()
+    } else {
+        normal
+    };
+    skinned.set_skinned(position, normal);
+    skinned
+}
+
+/// Rotates around C by the normalised blend of both bone rotations, the same nlerp the vertex
+/// shader uses, and carries C along with the linearly skinned R0/R1 midpoints.
+fn skin_sdef(
+    sdef: &Sdef,
+    matrices: [&Matrix4<f32>; 2],
+    rotations: [Quaternion<f32>; 2],
+    weights: [f32; 2],
+    position: Vector3<f32>,
+    normal: Vector3<f32>,
+) -> (Vector3<f32>, Vector3<f32>) {
+    let [q0, q1] = rotations;
+    let q1 = if q0.dot(q1) < 0.0 { -q1 } else { q1 };
+    let rotation = (q0 * weights[0] + q1 * weights[1]).normalize();
+    let r0 = (matrices[0] * sdef.r0.extend(1.0)).truncate();
+    let r1 = (matrices[1] * sdef.r1.extend(1.0)).truncate();
+    (
+        rotation * (position - sdef.c) + r0 * weights[0] + r1 * weights[1],
+        rotation * normal,
+    )
+}
+
+pub fn skin_vertices<V: Skinnable>(
+    method: SkinningMethod,
+    matrices: &[Matrix4<f32>],
+    vertices: &[V],
+) -> Vec<V> {
+    // The GPU palette holds MAX_BONES entries; skinning more on the CPU would diverge from it.
+    assert!(matrices.len() <= MAX_BONES, "bone palette overflow");
+    let dual_quaternions: Vec<DualQuaternion> =
+        matrices.iter().map(DualQuaternion::from_matrix).collect();
+    vertices
+        .iter()
+        .map(|vertex| skin_vertex(method, matrices, &dual_quaternions, vertex))
+        .collect()
+}
+
+pub fn upload_vertices<V: VertexLayout>(
+    ring: &mut UploadRing,
+    vertices: &[V],
+) -> Option<D3D12_VERTEX_BUFFER_VIEW> {
+    let bytes = unsafe {
+        std::slice::from_raw_parts(
+            vertices.as_ptr() as *const u8,
+            std::mem::size_of_val(vertices),
+        )
+    };
+    let allocation = ring.allocate(bytes.len() as u64, 16)?;
+    allocation.data.copy_from_slice(bytes);
+    Some(D3D12_VERTEX_BUFFER_VIEW {
+        BufferLocation: allocation.gpu_address,
+        SizeInBytes: bytes.len() as u32,
+        StrideInBytes: V::stride(),
+    })
+}
+
+pub fn upload_indices(ring: &mut UploadRing, indices: &[u32]) -> Option<D3D12_INDEX_BUFFER_VIEW> {
+    let bytes = unsafe {
+        std::slice::from_raw_parts(
+            indices.as_ptr() as *const u8,
+            std::mem::size_of_val(indices),
+        )
+    };
+    let allocation = ring.allocate(bytes.len() as u64, 4)?;
+    allocation.data.copy_from_slice(bytes);
+    Some(D3D12_INDEX_BUFFER_VIEW {
+        BufferLocation: allocation.gpu_address,
+        SizeInBytes: bytes.len() as u32,
+        Format: DXGI_FORMAT_R32_UINT,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::{Deg, Rotation3, Transform};
+
+    const EPSILON: f32 = 1e-4;
+
+    fn assert_vector_close(actual: Vector3<f32>, expected: Vector3<f32>) {
+        assert!(
+            (actual - expected).magnitude() <= EPSILON,
+            "expected {expected:?}, got {actual:?}"
+        );
+    }
+
+    fn vertex(position: Vector3<f32>, joints: [u16; 4], weights: [f32; 4]) -> MeshVertex {
+        MeshVertex {
+            position,
+            normal: Vector3::unit_y(),
+            joints,
+            weights,
+            ..Default::default()
+        }
+    }
+
+    fn rotation_z(degrees: f32) -> Matrix4<f32> {
+        Matrix4::from_angle_z(Deg(degrees))
+    }
+
+    fn skin(method: SkinningMethod, matrices: &[Matrix4<f32>], v: MeshVertex) -> MeshVertex {
+        skin_vertices(method, matrices, &[v])[0]
+    }
+
+    #[test]
+    fn single_bone_matches_matrix_for_both_methods() {
+        let matrix = Matrix4::from_translation(Vector3::new(1.0, 2.0, 3.0)) * rotation_z(90.0);
+        let matrices = [Matrix4::from_scale(1.0), matrix];
+        let v = vertex(
+            Vector3::new(1.0, 0.0, 0.0),
+            [1, 0, 0, 0],
+            [1.0, 0.0, 0.0, 0.0],
+        );
+        for method in [SkinningMethod::Linear, SkinningMethod::DualQuaternion] {
+            let skinned = skin(method, &matrices, v);
+            assert_vector_close(skinned.position, Vector3::new(1.0, 3.0, 3.0));
+            assert_vector_close(skinned.normal, Vector3::new(-1.0, 0.0, 0.0));
+        }
+    }
+
+    #[test]
+    fn linear_blends_all_four_influences() {
+        let matrices: Vec<Matrix4<f32>> = (0..4)
+            .map(|i| Matrix4::from_translation(Vector3::new(i as f32, 0.0, 0.0)))
+            .collect();
+        let v = vertex(Vector3::zero(), [0, 1, 2, 3], [0.1, 0.2, 0.3, 0.4]);
+        let skinned = skin(SkinningMethod::Linear, &matrices, v);
+        // 0 * 0.1 + 1 * 0.2 + 2 * 0.3 + 3 * 0.4
+        assert_vector_close(skinned.position, Vector3::new(2.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn linear_matches_weighted_matrix_sum() {
+        let matrices = [rotation_z(0.0), rotation_z(90.0)];
+        let v = vertex(
+            Vector3::new(1.0, 0.0, 0.0),
+            [0, 1, 0, 0],
+            [0.5, 0.5, 0.0, 0.0],
+        );
+        let skinned = skin(SkinningMethod::Linear, &matrices, v);
+        let reference = (matrices[0] * 0.5 + matrices[1] * 0.5)
+            .transform_point(cgmath::Point3::new(1.0, 0.0, 0.0));
+        assert_vector_close(
+            skinned.position,
+            Vector3::new(reference.x, reference.y, reference.z),
+        );
+        // Linear blending shortens the arm: the candy-wrapper artefact.
+        assert!((skinned.position.magnitude() - std::f32::consts::FRAC_1_SQRT_2).abs() < 1e-3);
+    }
+
+    #[test]
+    fn dual_quaternion_preserves_length() {
+        let matrices = [rotation_z(0.0), rotation_z(90.0)];
+        let v = vertex(
+            Vector3::new(1.0, 0.0, 0.0),
+            [0, 1, 0, 0],
+            [0.5, 0.5, 0.0, 0.0],
+        );
+        let skinned = skin(SkinningMethod::DualQuaternion, &matrices, v);
+        let half = std::f32::consts::FRAC_1_SQRT_2;
+        assert_vector_close(skinned.position, Vector3::new(half, half, 0.0));
+    }
+
+    #[test]
+    fn dual_quaternion_blends_translations() {
+        let matrices = [
+            Matrix4::from_translation(Vector3::new(2.0, 0.0, 0.0)),
+            Matrix4::from_translation(Vector3::new(0.0, 4.0, 0.0)),
+        ];
+        let v = vertex(
+            Vector3::new(0.0, 0.0, 1.0),
+            [0, 1, 0, 0],
+            [0.25, 0.75, 0.0, 0.0],
+        );
+        let skinned = skin(SkinningMethod::DualQuaternion, &matrices, v);
+        assert_vector_close(skinned.position, Vector3::new(0.5, 3.0, 1.0));
+    }
+
+    #[test]
+    fn zero_weights_and_unknown_bones_are_ignored() {
+        let matrices = [Matrix4::from_translation(Vector3::new(1.0, 0.0, 0.0))];
+        let v = vertex(Vector3::zero(), [0, 7, 9, 0], [1.0, 0.5, 0.5, 0.0]);
+        for method in [SkinningMethod::Linear, SkinningMethod::DualQuaternion] {
+            assert_vector_close(
+                skin(method, &matrices, v).position,
+                Vector3::new(1.0, 0.0, 0.0),
+            );
+        }
+
+        let unbound = vertex(
+            Vector3::new(3.0, 0.0, 0.0),
+            [5, 6, 0, 0],
+            [0.5, 0.5, 0.0, 0.0],
+        );
+        for method in [SkinningMethod::Linear, SkinningMethod::DualQuaternion] {
+            assert_eq!(skin(method, &matrices, unbound), unbound);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "bone palette overflow")]
+    fn rejects_more_bones_than_the_gpu_palette() {
+        let matrices = vec![Matrix4::from_scale(1.0); MAX_BONES + 1];
+        skin_vertices(SkinningMethod::Linear, &matrices, &[MeshVertex::default()]);
+    }
+
+    #[test]
+    fn blend_handles_antipodal_quaternions() {
+        let rotation = Quaternion::from_angle_z(Deg(30.0));
+        let translation = Vector3::new(1.0, 2.0, 3.0);
+        let dq = DualQuaternion::from_rotation_translation(rotation, translation);
+        // Same rigid transform with the opposite sign.
+        let flipped = DualQuaternion {
+            real: -dq.real,
+            dual: -dq.dual,
+        };
+        let blended = DualQuaternion::blend(&[(dq, 0.5), (flipped, 0.5)]);
+        let point = Vector3::new(1.0, 0.0, 0.0);
+        assert_vector_close(blended.transform_point(point), dq.transform_point(point));
+        assert!((blended.real.magnitude() - 1.0).abs() < EPSILON);
+
+        // The pivot's hemisphere does not change the result either.
+        let a = DualQuaternion::from_rotation_translation(
+            Quaternion::from_angle_z(Deg(10.0)),
+            Vector3::zero(),
+        );
+        let b = DualQuaternion::from_rotation_translation(
+            Quaternion::from_angle_z(Deg(70.0)),
+            Vector3::zero(),
+        );
+        let b_flipped = DualQuaternion {
+            real: -b.real,
+            dual: -b.dual,
+        };
+        let expected = DualQuaternion::blend(&[(a, 0.5), (b, 0.5)]);
+        let actual = DualQuaternion::blend(&[(a, 0.5), (b_flipped, 0.5)]);
+        assert_vector_close(
+            actual.transform_point(point),
+            expected.transform_point(point),
+        );
+        let angle = Deg(40.0_f32);
+        assert_vector_close(
+            expected.transform_point(point),
+            Vector3::new(cgmath::Angle::cos(angle), cgmath::Angle::sin(angle), 0.0),
+        );
+    }
+
+    #[test]
+    fn blend_of_nothing_is_identity() {
+        assert_eq!(DualQuaternion::blend(&[]), DualQuaternion::identity());
+        let dq = DualQuaternion::from_rotation_translation(
+            Quaternion::from_angle_x(Deg(45.0)),
+            Vector3::unit_x(),
+        );
+        assert_eq!(
+            DualQuaternion::blend(&[(dq, 0.0)]),
+            DualQuaternion::identity()
+        );
+    }
+
+    #[test]
+    fn dual_quaternion_round_trips_matrices() {
+        let matrix = Matrix4::from_translation(Vector3::new(-1.0, 0.5, 2.0))
+            * Matrix4::from_axis_angle(Vector3::new(1.0, 1.0, 0.0).normalize(), Deg(120.0));
+        let dq = DualQuaternion::from_matrix(&matrix);
+        assert_vector_close(dq.translation(), Vector3::new(-1.0, 0.5, 2.0));
+        for point in [Vector3::unit_x(), Vector3::new(0.3, -2.0, 5.0)] {
+            let expected = matrix.transform_point(cgmath::Point3::new(point.x, point.y, point.z));
+            assert_vector_close(
+                dq.transform_point(point),
+                Vector3::new(expected.x, expected.y, expected.z),
+            );
+        }
+        let (real, dual) = dq.to_vectors();
+        assert_eq!(
+            real,
+            Vector4::new(dq.real.v.x, dq.real.v.y, dq.real.v.z, dq.real.s)
+        );
+        assert_eq!(dual.w, dq.dual.s);
+    }
+
+    #[derive(Clone, Copy)]
+    struct DeformVertex {
+        vertex: MeshVertex,
+        deform: Deform,
+    }
+
+    impl Skinnable for DeformVertex {
+        fn position(&self) -> Vector3<f32> {
+            self.vertex.position
+        }
+
+        fn normal(&self) -> Vector3<f32> {
+            self.vertex.normal
+        }
+
+        fn influences(&self) -> [(usize, f32); MAX_INFLUENCES] {
+            self.vertex.influences()
+        }
+
+        fn set_skinned(&mut self, position: Vector3<f32>, normal: Vector3<f32>) {
+            self.vertex.set_skinned(position, normal);
+        }
+
+        fn deform(&self) -> Deform {
+            self.deform
+        }
+    }
+
+    fn sdef_vertex(position: Vector3<f32>, weight: f32) -> DeformVertex {
+        let c = Vector3::new(0.0, 1.0, 0.0);
+        DeformVertex {
+            vertex: vertex(position, [0, 1, 0, 0], [weight, 1.0 - weight, 0.0, 0.0]),
+            deform: Deform::Sdef(Sdef::new(
+                c,
+                Vector3::new(0.0, 1.5, 0.0),
+                Vector3::new(0.0, 0.5, 0.0),
+                weight,
+            )),
+        }
+    }
+
+    #[test]
+    fn sdef_midpoints_average_to_the_center() {
+        let sdef = Sdef::new(
+            Vector3::new(1.0, 1.0, 0.0),
+            Vector3::new(1.0, 3.0, 0.0),
+            Vector3::new(1.0, -1.0, 0.0),
+            0.25,
+        );
+        assert_vector_close(sdef.c, Vector3::new(1.0, 1.0, 0.0));
+        assert_vector_close(sdef.r0 * 0.25 + sdef.r1 * 0.75, sdef.c);
+    }
+
+    #[test]
+    fn sdef_rest_pose_is_unchanged() {
+        let matrices = [Matrix4::from_scale(1.0); 2];
+        let v = sdef_vertex(Vector3::new(0.3, 1.2, -0.4), 0.6);
+        for method in [SkinningMethod::Linear, SkinningMethod::DualQuaternion] {
+            let skinned = skin_vertices(method, &matrices, &[v])[0];
+            assert_vector_close(skinned.vertex.position, v.vertex.position);
+            assert_vector_close(skinned.vertex.normal, Vector3::unit_y());
+        }
+    }
+
+    #[test]
+    fn sdef_with_one_bone_is_rigid() {
+        let matrix = Matrix4::from_translation(Vector3::new(0.0, 1.0, 0.0))
+            * rotation_z(90.0)
+            * Matrix4::from_translation(Vector3::new(0.0, -1.0, 0.0));
+        let matrices = [matrix, Matrix4::from_scale(1.0)];
+        let v = sdef_vertex(Vector3::new(0.5, 1.0, 0.0), 1.0);
+        let skinned = skin_vertices(SkinningMethod::Linear, &matrices, &[v])[0];
+        let expected = matrix.transform_point(cgmath::Point3::new(0.5, 1.0, 0.0));
+        assert_vector_close(
+            skinned.vertex.position,
+            Vector3::new(expected.x, expected.y, expected.z),
+        );
+        assert_vector_close(skinned.vertex.normal, Vector3::new(-1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn sdef_keeps_volume_at_a_bent_joint() {
+        // Bone 0 stays, bone 1 bends 90 degrees around the joint at C.
+        let joint = Vector3::new(0.0, 1.0, 0.0);
+        let bend =
+            Matrix4::from_translation(joint) * rotation_z(90.0) * Matrix4::from_translation(-joint);
+        let matrices = [Matrix4::from_scale(1.0), bend];
+        let v = sdef_vertex(Vector3::new(0.5, 1.0, 0.0), 0.5);
+
+        let sdef = skin_vertices(SkinningMethod::Linear, &matrices, &[v])[0];
+        let half = std::f32::consts::FRAC_1_SQRT_2;
+        // Rotated by 45 degrees around C and carried by the averaged R0/R1 midpoints.
+        let rw = (Vector3::new(0.0, 1.25, 0.0) + Vector3::new(0.25, 1.0, 0.0)) * 0.5;
+        let expected = Vector3::new(0.5 * half, 0.5 * half, 0.0) + rw;
+        assert_vector_close(sdef.vertex.position, expected);
+
+        // BDEF2 on the same vertex pulls it towards the joint instead.
+        let mut bdef = v;
+        bdef.deform = Deform::Blend;
+        let linear = skin_vertices(SkinningMethod::Linear, &matrices, &[bdef])[0];
+        assert!(
+            (linear.vertex.position - joint).magnitude() < (sdef.vertex.position - rw).magnitude()
+        );
+    }
+
+    #[test]
+    fn dual_quaternion_deform_ignores_the_selected_method() {
+        let matrices = [rotation_z(0.0), rotation_z(90.0)];
+        let v = DeformVertex {
+            vertex: vertex(
+                Vector3::new(1.0, 0.0, 0.0),
+                [0, 1, 0, 0],
+                [0.5, 0.5, 0.0, 0.0],
+            ),
+            deform: Deform::DualQuaternion,
+        };
+        let skinned = skin_vertices(SkinningMethod::Linear, &matrices, &[v])[0];
+        let half = std::f32::consts::FRAC_1_SQRT_2;
+        assert_vector_close(skinned.vertex.position, Vector3::new(half, half, 0.0));
+        assert_eq!(Deform::Blend.shader_id(), 0);
+        assert_eq!(v.deform.shader_id(), 1);
+        assert_eq!(sdef_vertex(Vector3::zero(), 0.5).deform.shader_id(), 2);
+    }
+
+    #[test]
+    fn skinning_modes_cycle() {
+        let mut mode = SkinningMode::GpuLinear;
+        let mut seen = Vec::new();
+        for _ in 0..4 {
+            seen.push((mode, mode.method(), mode.is_cpu()));
+            mode = mode.next();
+        }
+        assert_eq!(mode, SkinningMode::GpuLinear);
+        assert_eq!(
+            seen,
+            [
+                (SkinningMode::GpuLinear, SkinningMethod::Linear, false),
+                (
+                    SkinningMode::GpuDualQuaternion,
+                    SkinningMethod::DualQuaternion,
+                    false
+                ),
+                (SkinningMode::CpuLinear, SkinningMethod::Linear, true),
+                (
+                    SkinningMode::CpuDualQuaternion,
+                    SkinningMethod::DualQuaternion,
+                    true
+                ),
+            ]
+        );
+    }
+}
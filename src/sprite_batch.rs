@@ -0,0 +1,527 @@
+use cgmath::{Matrix4, Vector2, Vector3, Vector4};
+use windows::Win32::Graphics::Direct3D12::*;
+
+use crate::command_sink::CommandSink;
+use crate::skinning::{upload_indices, upload_vertices};
+use crate::upload_ring::UploadRing;
+use crate::vertex_layout::VertexLayout;
+
+pub const MAX_SPRITES_PER_FRAME: usize = 2048;
+
+const QUAD_CORNERS: [Vector2<f32>; 4] = [
+    Vector2::new(0., 0.),
+    Vector2::new(1., 0.),
+    Vector2::new(0., 1.),
+    Vector2::new(1., 1.),
+];
+const QUAD_INDICES: [u32; 6] = [0, 1, 2, 2, 1, 3];
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, VertexLayout)]
+pub struct SpriteVertex {
+    #[vertex(semantic = "POSITION")]
+    pub position: Vector3<f32>,
+    #[vertex(semantic = "TEXCOORD")]
+    pub uv: Vector2<f32>,
+    #[vertex(semantic = "COLOR")]
+    pub color: Vector4<f32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum BlendMode {
+    Alpha,
+    Additive,
+}
+
+impl BlendMode {
+    pub fn render_target_blend_desc(self) -> D3D12_RENDER_TARGET_BLEND_DESC {
+        let (dest_blend, src_blend_alpha, dest_blend_alpha) = match self {
+            BlendMode::Alpha => (
+                D3D12_BLEND_INV_SRC_ALPHA,
+                D3D12_BLEND_ONE,
+                D3D12_BLEND_INV_SRC_ALPHA,
+            ),
+            BlendMode::Additive => (D3D12_BLEND_ONE, D3D12_BLEND_ZERO, D3D12_BLEND_ONE),
+        };
+        D3D12_RENDER_TARGET_BLEND_DESC {
+            BlendEnable: true.into(),
+            LogicOpEnable: false.into(),
+            SrcBlend: D3D12_BLEND_SRC_ALPHA,
+            DestBlend: dest_blend,
+            BlendOp: D3D12_BLEND_OP_ADD,
+            SrcBlendAlpha: src_blend_alpha,
+            DestBlendAlpha: dest_blend_alpha,
+            BlendOpAlpha: D3D12_BLEND_OP_ADD,
+            LogicOp: D3D12_LOGIC_OP_NOOP,
+            RenderTargetWriteMask: D3D12_COLOR_WRITE_ENABLE_ALL.0 as u8,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpriteSortMode {
+    Texture,
+    BackToFront,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UvRect {
+    pub min: Vector2<f32>,
+    pub max: Vector2<f32>,
+}
+
+impl UvRect {
+    pub const FULL: UvRect = UvRect {
+        min: Vector2::new(0., 0.),
+        max: Vector2::new(1., 1.),
+    };
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sprite {
+    pub texture: usize,
+    pub position: Vector2<f32>,
+    pub size: Vector2<f32>,
+    pub origin: Vector2<f32>,
+    pub rotation: f32,
+    pub scale: Vector2<f32>,
+    pub uv: UvRect,
+    pub tint: Vector4<f32>,
+    pub depth: f32,
+    pub blend: BlendMode,
+}
+
+impl Sprite {
+    pub fn new(texture: usize, position: Vector2<f32>, size: Vector2<f32>) -> Self {
+        Sprite {
+            texture,
+            position,
+            size,
+            origin: Vector2::new(0.5, 0.5),
+            rotation: 0.,
+            scale: Vector2::new(1., 1.),
+            uv: UvRect::FULL,
+            tint: Vector4::new(1., 1., 1., 1.),
+            depth: 0.,
+            blend: BlendMode::Alpha,
+        }
+    }
+
+    pub fn vertices(&self) -> [SpriteVertex; 4] {
+        let (sin, cos) = self.rotation.sin_cos();
+        QUAD_CORNERS.map(|corner| {
+            let local = corner - self.origin;
+            let x = local.x * self.size.x * self.scale.x;
+            let y = local.y * self.size.y * self.scale.y;
+            SpriteVertex {
+                position: Vector3::new(
+                    self.position.x + x * cos - y * sin,
+                    self.position.y + x * sin + y * cos,
+                    self.depth,
+                ),
+                uv: Vector2::new(
+                    self.uv.min.x + (self.uv.max.x - self.uv.min.x) * corner.x,
+                    self.uv.min.y + (self.uv.max.y - self.uv.min.y) * corner.y,
+                ),
+                color: self.tint,
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpriteDraw {
+    pub blend: BlendMode,
+    pub texture: usize,
+    pub start_index: u32,
+    pub index_count: u32,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SpriteGeometry {
+    pub vertices: Vec<SpriteVertex>,
+    pub indices: Vec<u32>,
+    pub draws: Vec<SpriteDraw>,
+}
+
+impl SpriteGeometry {
+    pub fn upload(
+        &self,
+        ring: &mut UploadRing,
+    ) -> Option<(D3D12_VERTEX_BUFFER_VIEW, D3D12_INDEX_BUFFER_VIEW)> {
+        if self.indices.is_empty() {
+            return None;
+        }
+        let vertex_buffer = upload_vertices(ring, &self.vertices)?;
+        let index_buffer = upload_indices(ring, &self.indices)?;
+        Some((vertex_buffer, index_buffer))
+    }
+
+    pub fn submit<S, F, T>(
+        &self,
+        sink: &S,
+        vertex_buffer: D3D12_VERTEX_BUFFER_VIEW,
+        index_buffer: D3D12_INDEX_BUFFER_VIEW,
+        mut bind_blend: F,
+        texture_handle: T,
+    ) where
+        S: CommandSink + ?Sized,
+        F: FnMut(BlendMode),
+        T: Fn(usize) -> Option<D3D12_GPU_DESCRIPTOR_HANDLE>,
+    {
+        sink.set_vertex_buffers(0, &[vertex_buffer]);
+        sink.set_index_buffer(&index_buffer);
+        let mut bound_blend = None;
+        let mut bound_texture = None;
+        for draw in &self.draws {
+            if bound_blend != Some(draw.blend) {
+                bind_blend(draw.blend);
+                bound_blend = Some(draw.blend);
+            }
+            if let Some(handle) = texture_handle(draw.texture) {
+                if bound_texture != Some(handle) {
+                    sink.set_descriptor_table(0, handle);
+                    bound_texture = Some(handle);
+                }
+            }
+            sink.draw_indexed_instanced(draw.index_count, 1, draw.start_index, 0, 0);
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SpriteBatch {
+    sort_mode: SpriteSortMode,
+    sprites: Vec<Sprite>,
+}
+
+impl SpriteBatch {
+    pub fn new(sort_mode: SpriteSortMode) -> Self {
+        SpriteBatch {
+            sort_mode,
+            sprites: Vec::new(),
+        }
+    }
+
+    pub fn draw(&mut self, sprite: Sprite) {
+        self.sprites.push(sprite);
+    }
+
+    pub fn len(&self) -> usize {
+        self.sprites.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sprites.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.sprites.clear();
+    }
+
+    pub fn sorted(&self) -> Vec<Sprite> {
+        let mut sprites = self.sprites.clone();
+        match self.sort_mode {
+            SpriteSortMode::Texture => sprites.sort_by(|a, b| {
+                a.blend
+                    .cmp(&b.blend)
+                    .then(a.texture.cmp(&b.texture))
+                    .then(b.depth.total_cmp(&a.depth))
+            }),
+            SpriteSortMode::BackToFront => sprites.sort_by(|a, b| b.depth.total_cmp(&a.depth)),
+        }
+        sprites
+    }
+
+    pub fn build(&self) -> SpriteGeometry {
+        let mut geometry = SpriteGeometry::default();
+        for sprite in self.sorted().iter().take(MAX_SPRITES_PER_FRAME) {
+            let base = geometry.vertices.len() as u32;
+            geometry.vertices.extend(sprite.vertices());
+            geometry
+                .indices
+                .extend(QUAD_INDICES.iter().map(|index| base + index));
+
+            match geometry.draws.last_mut() {
+                Some(draw) if draw.blend == sprite.blend && draw.texture == sprite.texture => {
+                    draw.index_count += QUAD_INDICES.len() as u32;
+                }
+                _ => geometry.draws.push(SpriteDraw {
+                    blend: sprite.blend,
+                    texture: sprite.texture,
+                    start_index: geometry.indices.len() as u32 - QUAD_INDICES.len() as u32,
+                    index_count: QUAD_INDICES.len() as u32,
+                }),
+            }
+        }
+        geometry
+    }
+}
+
+pub fn screen_projection(width: f32, height: f32) -> Matrix4<f32> {
+    Matrix4::from_translation(Vector3::new(-1., 1., 0.))
+        * Matrix4::from_nonuniform_scale(2. / width, -2. / height, 1.)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command_sink::{RecordedCommand, RecordedCommands};
+    use windows::Win32::Graphics::Dxgi::Common::DXGI_FORMAT_R32_UINT;
+
+    const EPSILON: f32 = 1e-4;
+
+    fn assert_vector_close(actual: Vector3<f32>, expected: Vector3<f32>) {
+        assert!(
+            (actual - expected).x.abs() < EPSILON
+                && (actual - expected).y.abs() < EPSILON
+                && (actual - expected).z.abs() < EPSILON,
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+    }
+
+    fn sprite(texture: usize, depth: f32, blend: BlendMode) -> Sprite {
+        let mut sprite = Sprite::new(texture, Vector2::new(0., 0.), Vector2::new(1., 1.));
+        sprite.depth = depth;
+        sprite.blend = blend;
+        sprite
+    }
+
+    fn keys(sprites: &[Sprite]) -> Vec<(BlendMode, usize, f32)> {
+        sprites
+            .iter()
+            .map(|s| (s.blend, s.texture, s.depth))
+            .collect()
+    }
+
+    fn mixed_batch(sort_mode: SpriteSortMode) -> SpriteBatch {
+        let mut batch = SpriteBatch::new(sort_mode);
+        batch.draw(sprite(1, 0.2, BlendMode::Alpha));
+        batch.draw(sprite(0, 0.5, BlendMode::Additive));
+        batch.draw(sprite(0, 0.1, BlendMode::Alpha));
+        batch.draw(sprite(1, 0.9, BlendMode::Alpha));
+        batch.draw(sprite(0, 0.7, BlendMode::Alpha));
+        batch
+    }
+
+    #[test]
+    fn texture_sort_groups_by_blend_then_texture() {
+        let batch = mixed_batch(SpriteSortMode::Texture);
+        assert_eq!(
+            keys(&batch.sorted()),
+            [
+                (BlendMode::Alpha, 0, 0.7),
+                (BlendMode::Alpha, 0, 0.1),
+                (BlendMode::Alpha, 1, 0.9),
+                (BlendMode::Alpha, 1, 0.2),
+                (BlendMode::Additive, 0, 0.5),
+            ]
+        );
+    }
+
+    #[test]
+    fn back_to_front_sort_orders_by_depth_only() {
+        let batch = mixed_batch(SpriteSortMode::BackToFront);
+        assert_eq!(
+            keys(&batch.sorted()),
+            [
+                (BlendMode::Alpha, 1, 0.9),
+                (BlendMode::Alpha, 0, 0.7),
+                (BlendMode::Additive, 0, 0.5),
+                (BlendMode::Alpha, 1, 0.2),
+                (BlendMode::Alpha, 0, 0.1),
+            ]
+        );
+    }
+
+    #[test]
+    fn sort_is_stable_for_equal_keys() {
+        let mut batch = SpriteBatch::new(SpriteSortMode::BackToFront);
+        for i in 0..4 {
+            let mut s = sprite(0, 0.5, BlendMode::Alpha);
+            s.position.x = i as f32;
+            batch.draw(s);
+        }
+        let order: Vec<f32> = batch.sorted().iter().map(|s| s.position.x).collect();
+        assert_eq!(order, [0., 1., 2., 3.]);
+    }
+
+    #[test]
+    fn build_merges_draws_with_equal_state() {
+        let geometry = mixed_batch(SpriteSortMode::Texture).build();
+        assert_eq!(
+            geometry.draws,
+            [
+                SpriteDraw {
+                    blend: BlendMode::Alpha,
+                    texture: 0,
+                    start_index: 0,
+                    index_count: 12,
+                },
+                SpriteDraw {
+                    blend: BlendMode::Alpha,
+                    texture: 1,
+                    start_index: 12,
+                    index_count: 12,
+                },
+                SpriteDraw {
+                    blend: BlendMode::Additive,
+                    texture: 0,
+                    start_index: 24,
+                    index_count: 6,
+                },
+            ]
+        );
+        assert_eq!(geometry.vertices.len(), 20);
+        assert_eq!(geometry.indices.len(), 30);
+        assert_eq!(&geometry.indices[6..12], &[4, 5, 6, 6, 5, 7]);
+    }
+
+    #[test]
+    fn back_to_front_splits_interleaved_state() {
+        let geometry = mixed_batch(SpriteSortMode::BackToFront).build();
+        let states: Vec<(BlendMode, usize, u32)> = geometry
+            .draws
+            .iter()
+            .map(|d| (d.blend, d.texture, d.index_count))
+            .collect();
+        assert_eq!(
+            states,
+            [
+                (BlendMode::Alpha, 1, 6),
+                (BlendMode::Alpha, 0, 6),
+                (BlendMode::Additive, 0, 6),
+                (BlendMode::Alpha, 1, 6),
+                (BlendMode::Alpha, 0, 6),
+            ]
+        );
+    }
+
+    #[test]
+    fn build_caps_sprites_per_frame() {
+        let mut batch = SpriteBatch::new(SpriteSortMode::Texture);
+        for _ in 0..MAX_SPRITES_PER_FRAME + 10 {
+            batch.draw(sprite(0, 0., BlendMode::Alpha));
+        }
+        let geometry = batch.build();
+        assert_eq!(geometry.vertices.len(), MAX_SPRITES_PER_FRAME * 4);
+        assert_eq!(geometry.draws.len(), 1);
+        assert_eq!(
+            geometry.draws[0].index_count as usize,
+            MAX_SPRITES_PER_FRAME * 6
+        );
+    }
+
+    #[test]
+    fn vertices_apply_origin_rotation_and_uv() {
+        let mut s = Sprite::new(0, Vector2::new(10., 20.), Vector2::new(4., 2.));
+        s.rotation = std::f32::consts::FRAC_PI_2;
+        s.depth = 0.25;
+        s.uv = UvRect {
+            min: Vector2::new(0.5, 0.),
+            max: Vector2::new(1., 0.5),
+        };
+        let vertices = s.vertices();
+        // The top-left corner (-2, -1) rotates a quarter turn to (1, -2).
+        assert_vector_close(vertices[0].position, Vector3::new(11., 18., 0.25));
+        assert_vector_close(vertices[3].position, Vector3::new(9., 22., 0.25));
+        assert_eq!(vertices[0].uv, Vector2::new(0.5, 0.));
+        assert_eq!(vertices[3].uv, Vector2::new(1., 0.5));
+        assert!(vertices.iter().all(|v| v.color == s.tint));
+    }
+
+    #[test]
+    fn screen_projection_maps_corners_to_clip_space() {
+        let projection = screen_projection(800., 600.);
+        let top_left = projection * Vector4::new(0., 0., 0., 1.);
+        let bottom_right = projection * Vector4::new(800., 600., 0., 1.);
+        assert_vector_close(top_left.truncate(), Vector3::new(-1., 1., 0.));
+        assert_vector_close(bottom_right.truncate(), Vector3::new(1., -1., 0.));
+    }
+
+    fn texture_handle(texture: usize) -> Option<D3D12_GPU_DESCRIPTOR_HANDLE> {
+        Some(D3D12_GPU_DESCRIPTOR_HANDLE {
+            ptr: 0x100 + texture as u64 * 32,
+        })
+    }
+
+    fn submit(
+        geometry: &SpriteGeometry,
+        texture_handle: fn(usize) -> Option<D3D12_GPU_DESCRIPTOR_HANDLE>,
+    ) -> (Vec<RecordedCommand>, Vec<BlendMode>) {
+        let sink = RecordedCommands::new();
+        let mut blends = Vec::new();
+        geometry.submit(
+            &sink,
+            D3D12_VERTEX_BUFFER_VIEW {
+                BufferLocation: 0x1000,
+                SizeInBytes: 256,
+                StrideInBytes: std::mem::size_of::<SpriteVertex>() as u32,
+            },
+            D3D12_INDEX_BUFFER_VIEW {
+                BufferLocation: 0x2000,
+                SizeInBytes: 64,
+                Format: DXGI_FORMAT_R32_UINT,
+            },
+            |blend| blends.push(blend),
+            texture_handle,
+        );
+        (sink.take(), blends)
+    }
+
+    #[test]
+    fn submit_binds_each_texture_when_it_changes() {
+        let (commands, blends) = submit(
+            &mixed_batch(SpriteSortMode::Texture).build(),
+            texture_handle,
+        );
+        let tables: Vec<u64> = commands
+            .iter()
+            .filter_map(|c| match c {
+                RecordedCommand::SetDescriptorTable {
+                    root_index: 0,
+                    handle,
+                } => Some(handle.ptr),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(tables, [0x100, 0x120, 0x100]);
+        assert_eq!(blends, [BlendMode::Alpha, BlendMode::Additive]);
+        assert_eq!(
+            commands
+                .iter()
+                .filter(|c| matches!(c, RecordedCommand::DrawIndexedInstanced { .. }))
+                .count(),
+            3
+        );
+        assert!(matches!(
+            commands[0],
+            RecordedCommand::SetVertexBuffers { .. }
+        ));
+        assert!(matches!(commands[1], RecordedCommand::SetIndexBuffer(_)));
+    }
+
+    #[test]
+    fn submit_skips_redundant_state() {
+        // Two textures that share a descriptor, e.g. two sprites on the same atlas page.
+        let mut batch = SpriteBatch::new(SpriteSortMode::BackToFront);
+        batch.draw(sprite(0, 0.9, BlendMode::Alpha));
+        batch.draw(sprite(1, 0.5, BlendMode::Alpha));
+        batch.draw(sprite(0, 0.1, BlendMode::Alpha));
+        let (commands, blends) = submit(&batch.build(), |_| texture_handle(7));
+        let tables = commands
+            .iter()
+            .filter(|c| matches!(c, RecordedCommand::SetDescriptorTable { .. }))
+            .count();
+        assert_eq!(tables, 1);
+        assert_eq!(blends, [BlendMode::Alpha]);
+
+        let (commands, _) = submit(&batch.build(), |_| None);
+        assert!(!commands
+            .iter()
+            .any(|c| matches!(c, RecordedCommand::SetDescriptorTable { .. })));
+        assert_eq!(commands.len(), 2 + 3);
+    }
+}
@@ -0,0 +1,751 @@
+use std::collections::HashMap;
+
+use ab_glyph::GlyphId;
+use cgmath::{Vector2, Vector4};
+use windows::core::Result;
+use windows::Win32::Graphics::Direct3D12::*;
+use windows::Win32::Graphics::Dxgi::Common::*;
+
+use crate::atlas::{AtlasPacker, AtlasSettings, PackingAlgorithm, Rect};
+use crate::font::Font;
+use crate::sprite_batch::{Sprite, SpriteBatch, UvRect};
+
+pub const GLYPH_SIZE: f32 = 32.;
+pub const GLYPH_SPREAD: f32 = 4.;
+pub const GLYPH_PAGE_SIZE: u32 = 2048;
+
+const NO_LINE_START: &str = "、。，．・：；？！ー」』）】〕〉》’”ぁぃぅぇぉっゃゅょゎァィゥェォッャュョヮヵヶ々ゝゞヽヾ,.!?:;)]}";
+const NO_LINE_END: &str = "「『（【〔〈《‘“([{";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextAlign {
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextStyle {
+    pub size: f32,
+    pub color: Vector4<f32>,
+    pub align: TextAlign,
+    pub max_width: Option<f32>,
+    pub line_spacing: f32,
+}
+
+impl Default for TextStyle {
+    fn default() -> Self {
+        TextStyle {
+            size: 16.,
+            color: Vector4::new(1., 1., 1., 1.),
+            align: TextAlign::Left,
+            max_width: None,
+            line_spacing: 1.,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LayoutGlyph {
+    pub ch: char,
+    pub glyph: GlyphId,
+    pub position: Vector2<f32>,
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LineLayout {
+    pub start: usize,
+    pub end: usize,
+    pub width: f32,
+    pub baseline: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextLayout {
+    pub glyphs: Vec<LayoutGlyph>,
+    pub lines: Vec<LineLayout>,
+    pub size: Vector2<f32>,
+}
+
+fn is_cjk(ch: char) -> bool {
+    matches!(
+        ch as u32,
+        0x3000..=0x30FF | 0x31F0..=0x31FF | 0x3400..=0x4DBF | 0x4E00..=0x9FFF | 0xF900..=0xFAFF | 0xFF00..=0xFFEF
+    )
+}
+
+fn can_break_before(previous: char, ch: char) -> bool {
+    if ch.is_whitespace() || NO_LINE_START.contains(ch) || NO_LINE_END.contains(previous) {
+        return false;
+    }
+    previous.is_whitespace() || is_cjk(previous) || is_cjk(ch)
+}
+
+fn break_lines(
+    font: &Font,
+    chars: &[(char, GlyphId)],
+    style: &TextStyle,
+) -> Vec<std::ops::Range<usize>> {
+    let width_of = |range: std::ops::Range<usize>| {
+        let mut width = 0.;
+        for i in range.clone() {
+            if i > range.start {
+                width += font.kerning(chars[i - 1].1, chars[i].1, style.size);
+            }
+            width += font.advance(chars[i].1, style.size);
+        }
+        width
+    };
+
+    let mut lines = Vec::new();
+    let mut start = 0;
+    let mut last_break = None;
+    for i in 0..chars.len() {
+        if chars[i].0 == '\n' {
+            lines.push(start..i);
+            start = i + 1;
+            last_break = None;
+            continue;
+        }
+        if i > start && can_break_before(chars[i - 1].0, chars[i].0) {
+            last_break = Some(i);
+        }
+        let Some(max_width) = style.max_width else {
+            continue;
+        };
+        if i > start && !chars[i].0.is_whitespace() && width_of(start..i + 1) > max_width {
+            let end = last_break.take().unwrap_or(i);
+            lines.push(start..end);
+            start = end;
+        }
+    }
+    lines.push(start..chars.len());
+    lines
+}
+
+pub fn layout(font: &Font, text: &str, style: &TextStyle) -> TextLayout {
+    let chars: Vec<(char, GlyphId)> = text.chars().map(|ch| (ch, font.glyph_id(ch))).collect();
+    let metrics = font.line_metrics(style.size);
+    let line_height = metrics.line_height() * style.line_spacing;
+
+    let mut layout = TextLayout {
+        glyphs: Vec::new(),
+        lines: Vec::new(),
+        size: Vector2::new(0., 0.),
+    };
+    for (line, range) in break_lines(font, &chars, style).into_iter().enumerate() {
+        let baseline = metrics.ascent + line as f32 * line_height;
+        let start = layout.glyphs.len();
+        let mut pen = 0.;
+        let mut width = 0.;
+        let mut previous = None;
+        for &(ch, glyph) in &chars[range] {
+            if let Some(previous) = previous {
+                pen += font.kerning(previous, glyph, style.size);
+            }
+            layout.glyphs.push(LayoutGlyph {
+                ch,
+                glyph,
+                position: Vector2::new(pen, baseline),
+                line,
+            });
+            pen += font.advance(glyph, style.size);
+            if !ch.is_whitespace() {
+                width = pen;
+            }
+            previous = Some(glyph);
+        }
+        layout.lines.push(LineLayout {
+            start,
+            end: layout.glyphs.len(),
+            width,
+            baseline,
+        });
+    }
+
+    let block_width = style.max_width.unwrap_or_else(|| {
+        layout
+            .lines
+            .iter()
+            .map(|line| line.width)
+            .fold(0., f32::max)
+    });
+    for line in &layout.lines {
+        let offset = match style.align {
+            TextAlign::Left => 0.,
+            TextAlign::Center => (block_width - line.width) / 2.,
+            TextAlign::Right => block_width - line.width,
+        };
+        for glyph in &mut layout.glyphs[line.start..line.end] {
+            glyph.position.x += offset;
+        }
+    }
+    layout.size = Vector2::new(
+        block_width,
+        layout.lines.len() as f32 * line_height - metrics.line_gap * style.line_spacing,
+    );
+    layout
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CachedGlyph {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub offset: Vector2<f32>,
+}
+
+pub struct GlyphCache {
+    pub width: u32,
+    pub height: u32,
+    pixels: Vec<u8>,
+    packer: AtlasPacker,
+    glyphs: HashMap<GlyphId, Option<CachedGlyph>>,
+    dirty: Option<Rect>,
+}
+
+impl GlyphCache {
+    pub fn new(width: u32, height: u32) -> Self {
+        let settings = AtlasSettings {
+            page_width: width,
+            page_height: height,
+            padding: 1,
+            extrude: 0,
+            algorithm: PackingAlgorithm::Skyline,
+        };
+        GlyphCache {
+            width,
+            height,
+            pixels: vec![0; (width * height) as usize],
+            packer: AtlasPacker::new(settings).with_page_limit(1),
+            glyphs: HashMap::new(),
+            dirty: None,
+        }
+    }
+
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    pub fn len(&self) -> usize {
+        self.glyphs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.glyphs.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.pixels.fill(0);
+        self.packer.clear();
+        self.glyphs.clear();
+        self.dirty = Some(Rect {
+            x: 0,
+            y: 0,
+            width: self.width,
+            height: self.height,
+        });
+    }
+
+    pub fn glyph(&mut self, font: &Font, glyph: GlyphId) -> Option<CachedGlyph> {
+        if let Some(cached) = self.glyphs.get(&glyph) {
+            return *cached;
+        }
+        let Some(sdf) = font.sdf_glyph(glyph, GLYPH_SIZE, GLYPH_SPREAD) else {
+            self.glyphs.insert(glyph, None);
+            return None;
+        };
+        let placement = self.packer.insert(sdf.width, sdf.height)?;
+        for (row, pixels) in sdf.pixels.chunks(sdf.width as usize).enumerate() {
+            let start = ((placement.y + row as u32) * self.width + placement.x) as usize;
+            self.pixels[start..start + pixels.len()].copy_from_slice(pixels);
+        }
+
+        let rect = Rect {
+            x: placement.x,
+            y: placement.y,
+            width: sdf.width,
+            height: sdf.height,
+        };
+        self.dirty = Some(match self.dirty {
+            Some(dirty) => {
+                let x = dirty.x.min(rect.x);
+                let y = dirty.y.min(rect.y);
+                Rect {
+                    x,
+                    y,
+                    width: (dirty.x + dirty.width).max(rect.x + rect.width) - x,
+                    height: (dirty.y + dirty.height).max(rect.y + rect.height) - y,
+                }
+            }
+            None => rect,
+        });
+
+        let cached = CachedGlyph {
+            x: placement.x,
+            y: placement.y,
+            width: sdf.width,
+            height: sdf.height,
+            offset: sdf.offset,
+        };
+        self.glyphs.insert(glyph, Some(cached));
+        Some(cached)
+    }
+
+    pub fn uv_rect(&self, glyph: &CachedGlyph) -> UvRect {
+        let (width, height) = (self.width as f32, self.height as f32);
+        UvRect {
+            min: Vector2::new(glyph.x as f32 / width, glyph.y as f32 / height),
+            max: Vector2::new(
+                (glyph.x + glyph.width) as f32 / width,
+                (glyph.y + glyph.height) as f32 / height,
+            ),
+        }
+    }
+
+    pub fn take_dirty(&mut self) -> Option<Rect> {
+        self.dirty.take()
+    }
+}
+
+pub struct TextRenderer {
+    pub font: Font,
+    pub cache: GlyphCache,
+    texture: usize,
+}
+
+impl TextRenderer {
+    pub fn new(font: Font, texture: usize) -> Self {
+        TextRenderer {
+            font,
+            cache: GlyphCache::new(GLYPH_PAGE_SIZE, GLYPH_PAGE_SIZE),
+            texture,
+        }
+    }
+
+    pub fn layout(&self, text: &str, style: &TextStyle) -> TextLayout {
+        layout(&self.font, text, style)
+    }
+
+    pub fn draw(
+        &mut self,
+        batch: &mut SpriteBatch,
+        text: &str,
+        position: Vector2<f32>,
+        style: &TextStyle,
+    ) -> TextLayout {
+        let layout = self.layout(text, style);
+        let scale = style.size / GLYPH_SIZE;
+        for glyph in &layout.glyphs {
+            let Some(cached) = self.cache.glyph(&self.font, glyph.glyph) else {
+                continue;
+            };
+            let mut sprite = Sprite::new(
+                self.texture,
+                position + glyph.position + cached.offset * scale,
+                Vector2::new(cached.width as f32, cached.height as f32) * scale,
+            );
+            sprite.origin = Vector2::new(0., 0.);
+            sprite.uv = self.cache.uv_rect(&cached);
+            sprite.tint = style.color;
+            batch.draw(sprite);
+        }
+        layout
+    }
+}
+
+pub struct GlyphTexture {
+    resource: ID3D12Resource,
+}
+
+impl GlyphTexture {
+    pub fn new(device: &ID3D12Device, width: u32, height: u32) -> Result<Self> {
+        let heap_properties = D3D12_HEAP_PROPERTIES {
+            Type: D3D12_HEAP_TYPE_CUSTOM,
+            CPUPageProperty: D3D12_CPU_PAGE_PROPERTY_WRITE_BACK,
+            MemoryPoolPreference: D3D12_MEMORY_POOL_L0,
+            CreationNodeMask: 0,
+            VisibleNodeMask: 0,
+        };
+        let resource_desc = D3D12_RESOURCE_DESC {
+            Format: DXGI_FORMAT_R8_UNORM,
+            Width: width as u64,
+            Height: height,
+            DepthOrArraySize: 1,
+            SampleDesc: DXGI_SAMPLE_DESC {
+                Count: 1,
+                Quality: 0,
+            },
+            MipLevels: 1,
+            Dimension: D3D12_RESOURCE_DIMENSION_TEXTURE2D,
+            Layout: D3D12_TEXTURE_LAYOUT_UNKNOWN,
+            Flags: D3D12_RESOURCE_FLAG_NONE,
+            ..Default::default()
+        };
+        let mut resource: Option<ID3D12Resource> = None;
+        unsafe {
+            device.CreateCommittedResource(
+                &heap_properties,
+                D3D12_HEAP_FLAG_NONE,
+                &resource_desc,
+                D3D12_RESOURCE_STATE_PIXEL_SHADER_RESOURCE,
+                None,
+                &mut resource,
+            )
+        }?;
+        let resource = resource.unwrap();
+        unsafe { resource.Map(0, None, None) }?;
+        Ok(GlyphTexture { resource })
+    }
+
+    pub fn create_shader_resource_view(
+        &self,
+        device: &ID3D12Device,
+        handle: D3D12_CPU_DESCRIPTOR_HANDLE,
+    ) {
+        let desc = D3D12_SHADER_RESOURCE_VIEW_DESC {
+            Format: DXGI_FORMAT_R8_UNORM,
+            Shader4ComponentMapping: D3D12_DEFAULT_SHADER_4_COMPONENT_MAPPING,
+            ViewDimension: D3D12_SRV_DIMENSION_TEXTURE2D,
+            Anonymous: D3D12_SHADER_RESOURCE_VIEW_DESC_0 {
+                Texture2D: D3D12_TEX2D_SRV {
+                    MipLevels: 1,
+                    ..Default::default()
+                },
+            },
+        };
+        unsafe {
+            device.CreateShaderResourceView(&self.resource, Some(&desc), handle);
+        }
+    }
+
+    pub fn update(&self, cache: &mut GlyphCache) -> Result<()> {
+        let Some(dirty) = cache.take_dirty() else {
+            return Ok(());
+        };
+        let dst_box = D3D12_BOX {
+            left: dirty.x,
+            top: dirty.y,
+            front: 0,
+            right: dirty.x + dirty.width,
+            bottom: dirty.y + dirty.height,
+            back: 1,
+        };
+        let offset = (dirty.y * cache.width + dirty.x) as usize;
+        unsafe {
+            self.resource.WriteToSubresource(
+                0,
+                Some(&dst_box),
+                cache.pixels()[offset..].as_ptr() as *const _,
+                cache.width,
+                cache.width * cache.height,
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sprite_batch::SpriteSortMode;
+    use std::path::Path;
+
+    const EPSILON: f32 = 1e-4;
+
+    /// See `font::tests::fixture_font`: at size 10 `A` advances 7 pixels
+    /// (kerning -1 against another `A`), `B` 7, space 3 and CJK glyphs 10.
+    /// Lines are 11 pixels apart with the first baseline at 8.
+    fn fixture_font() -> Font {
+        Font::load(
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/font/rects.ttf"),
+            0,
+        )
+        .unwrap()
+    }
+
+    fn style(max_width: Option<f32>) -> TextStyle {
+        TextStyle {
+            size: 10.,
+            max_width,
+            ..Default::default()
+        }
+    }
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() <= EPSILON,
+            "expected {expected}, got {actual}"
+        );
+    }
+
+    fn lines(layout: &TextLayout) -> Vec<String> {
+        layout
+            .lines
+            .iter()
+            .map(|line| {
+                layout.glyphs[line.start..line.end]
+                    .iter()
+                    .map(|glyph| glyph.ch)
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn xs(layout: &TextLayout) -> Vec<f32> {
+        layout.glyphs.iter().map(|glyph| glyph.position.x).collect()
+    }
+
+    #[test]
+    fn lays_out_characters_not_bytes() {
+        let font = fixture_font();
+        let text = "日日。";
+        assert_eq!(text.len(), 9);
+        let layout = layout(&font, text, &style(None));
+        assert_eq!(layout.glyphs.len(), 3);
+        assert_eq!(layout.glyphs.iter().map(|g| g.ch).collect::<String>(), text);
+        assert_eq!(layout.glyphs[0].glyph, font.glyph_id('日'));
+        assert_eq!(xs(&layout), [0., 10., 20.]);
+        assert!(layout
+            .glyphs
+            .iter()
+            .all(|g| g.position.y == 8. && g.line == 0));
+        assert_eq!(layout.size, Vector2::new(30., 10.));
+    }
+
+    #[test]
+    fn applies_kerning_between_pairs() {
+        let font = fixture_font();
+        assert_eq!(xs(&layout(&font, "AAB", &style(None))), [0., 6., 13.]);
+        assert_close(layout(&font, "AAB", &style(None)).lines[0].width, 20.);
+    }
+
+    #[test]
+    fn mixes_latin_and_cjk_on_one_line() {
+        let font = fixture_font();
+        let layout = layout(&font, "A日 B", &style(None));
+        assert_eq!(xs(&layout), [0., 7., 17., 20.]);
+        assert_eq!(lines(&layout), ["A日 B"]);
+    }
+
+    #[test]
+    fn breaks_on_newlines() {
+        let font = fixture_font();
+        let layout = layout(&font, "AA\n\nB", &style(None));
+        assert_eq!(lines(&layout), ["AA", "", "B"]);
+        let baselines: Vec<f32> = layout.lines.iter().map(|l| l.baseline).collect();
+        assert_eq!(baselines, [8., 19., 30.]);
+        assert_eq!(layout.glyphs[2].line, 2);
+        assert_eq!(layout.glyphs[2].position, Vector2::new(0., 30.));
+        // Three lines of 11 minus the trailing line gap.
+        assert_eq!(layout.size, Vector2::new(13., 32.));
+    }
+
+    #[test]
+    fn wraps_latin_text_at_spaces() {
+        let font = fixture_font();
+        let layout = layout(&font, "AB BA B", &style(Some(16.)));
+        assert_eq!(lines(&layout), ["AB ", "BA ", "B"]);
+        // Trailing spaces do not count towards the line width.
+        let widths: Vec<f32> = layout.lines.iter().map(|l| l.width).collect();
+        assert_eq!(widths, [14., 14., 7.]);
+        assert_eq!(layout.size.x, 16.);
+    }
+
+    #[test]
+    fn wraps_long_words_mid_word() {
+        let font = fixture_font();
+        let layout = layout(&font, "BBBB", &style(Some(15.)));
+        assert_eq!(lines(&layout), ["BB", "BB"]);
+    }
+
+    #[test]
+    fn wraps_cjk_between_any_characters() {
+        let font = fixture_font();
+        let layout = layout(&font, "日日日日日", &style(Some(25.)));
+        assert_eq!(lines(&layout), ["日日", "日日", "日"]);
+    }
+
+    #[test]
+    fn keeps_closing_punctuation_off_line_starts() {
+        let font = fixture_font();
+        let layout = layout(&font, "日日。", &style(Some(25.)));
+        assert_eq!(lines(&layout), ["日", "日。"]);
+    }
+
+    #[test]
+    fn keeps_opening_brackets_off_line_ends() {
+        let font = fixture_font();
+        let layout = layout(&font, "日「日", &style(Some(25.)));
+        assert_eq!(lines(&layout), ["日", "「日"]);
+    }
+
+    #[test]
+    fn aligns_lines_within_the_block() {
+        let font = fixture_font();
+        let centered = layout(
+            &font,
+            "A",
+            &TextStyle {
+                align: TextAlign::Center,
+                ..style(Some(20.))
+            },
+        );
+        assert_eq!(xs(&centered), [6.5]);
+
+        // Without a maximum width the widest line sets the block width.
+        let right = layout(
+            &font,
+            "AA\nA",
+            &TextStyle {
+                align: TextAlign::Right,
+                ..style(None)
+            },
+        );
+        assert_eq!(xs(&right), [0., 6., 6.]);
+        assert_eq!(right.size.x, 13.);
+    }
+
+    #[test]
+    fn line_spacing_scales_line_advance() {
+        let font = fixture_font();
+        let layout = layout(
+            &font,
+            "A\nA",
+            &TextStyle {
+                line_spacing: 2.,
+                ..style(None)
+            },
+        );
+        assert_eq!(layout.lines[1].baseline, 30.);
+        assert_eq!(layout.size.y, 42.);
+    }
+
+    #[test]
+    fn empty_text_has_one_empty_line() {
+        let font = fixture_font();
+        let layout = layout(&font, "", &style(None));
+        assert!(layout.glyphs.is_empty());
+        assert_eq!(layout.lines.len(), 1);
+        assert_eq!(layout.size, Vector2::new(0., 10.));
+    }
+
+    #[test]
+    fn glyph_cache_rasterizes_each_glyph_once() {
+        let font = fixture_font();
+        let mut cache = GlyphCache::new(128, 128);
+        let a = font.glyph_id('A');
+        let cached = cache.glyph(&font, a).unwrap();
+        let sdf = font.sdf_glyph(a, GLYPH_SIZE, GLYPH_SPREAD).unwrap();
+        assert_eq!((cached.width, cached.height), (sdf.width, sdf.height));
+        assert_eq!(cached.offset, sdf.offset);
+        for row in 0..sdf.height {
+            let start = ((cached.y + row) * cache.width + cached.x) as usize;
+            assert_eq!(
+                &cache.pixels()[start..start + sdf.width as usize],
+                &sdf.pixels[(row * sdf.width) as usize..((row + 1) * sdf.width) as usize]
+            );
+        }
+        assert_eq!(
+            cache.take_dirty(),
+            Some(Rect {
+                x: cached.x,
+                y: cached.y,
+                width: cached.width,
+                height: cached.height,
+            })
+        );
+
+        assert_eq!(cache.glyph(&font, a), Some(cached));
+        assert_eq!(cache.take_dirty(), None);
+        assert_eq!(cache.glyph(&font, font.glyph_id(' ')), None);
+        assert_eq!(cache.len(), 2);
+
+        let uv = cache.uv_rect(&cached);
+        assert_eq!(
+            uv.min,
+            Vector2::new(cached.x as f32, cached.y as f32) / 128.
+        );
+        assert_eq!(
+            uv.max,
+            Vector2::new(
+                (cached.x + cached.width) as f32,
+                (cached.y + cached.height) as f32
+            ) / 128.
+        );
+    }
+
+    #[test]
+    fn glyph_cache_merges_dirty_regions() {
+        let font = fixture_font();
+        let mut cache = GlyphCache::new(128, 128);
+        let a = cache.glyph(&font, font.glyph_id('A')).unwrap();
+        let b = cache.glyph(&font, font.glyph_id('日')).unwrap();
+        let dirty = cache.take_dirty().unwrap();
+        for glyph in [a, b] {
+            assert!(glyph.x >= dirty.x && glyph.x + glyph.width <= dirty.x + dirty.width);
+            assert!(glyph.y >= dirty.y && glyph.y + glyph.height <= dirty.y + dirty.height);
+        }
+
+        cache.clear();
+        assert!(cache.is_empty());
+        assert!(cache.pixels().iter().all(|&p| p == 0));
+        assert_eq!(
+            cache.take_dirty(),
+            Some(Rect {
+                x: 0,
+                y: 0,
+                width: 128,
+                height: 128,
+            })
+        );
+    }
+
+    #[test]
+    fn glyph_cache_returns_none_when_full() {
+        let font = fixture_font();
+        let mut cache = GlyphCache::new(48, 48);
+        assert!(cache.glyph(&font, font.glyph_id('日')).is_some());
+        assert_eq!(cache.glyph(&font, font.glyph_id('B')), None);
+        // A full page is not remembered, so the glyph can be retried after a clear.
+        cache.clear();
+        assert!(cache.glyph(&font, font.glyph_id('B')).is_some());
+    }
+
+    #[test]
+    fn renderer_emits_one_sprite_per_visible_glyph() {
+        let mut renderer = TextRenderer::new(fixture_font(), 3);
+        let mut batch = SpriteBatch::new(SpriteSortMode::Texture);
+        let text_style = TextStyle {
+            size: 16.,
+            color: Vector4::new(1., 0., 0., 1.),
+            ..Default::default()
+        };
+        let layout = renderer.draw(&mut batch, "A 日", Vector2::new(100., 50.), &text_style);
+        assert_eq!(layout.glyphs.len(), 3);
+        assert_eq!(batch.len(), 2);
+
+        let sprites = batch.sorted();
+        let cached = renderer
+            .cache
+            .glyph(&renderer.font, renderer.font.glyph_id('A'))
+            .unwrap();
+        let scale = 16. / GLYPH_SIZE;
+        let sprite = sprites
+            .iter()
+            .find(|s| s.uv == renderer.cache.uv_rect(&cached))
+            .unwrap();
+        assert_eq!(sprite.texture, 3);
+        assert_eq!(sprite.tint, text_style.color);
+        assert_eq!(sprite.origin, Vector2::new(0., 0.));
+        assert_eq!(
+            sprite.position,
+            Vector2::new(100., 50.) + layout.glyphs[0].position + cached.offset * scale
+        );
+        assert_eq!(
+            sprite.size,
+            Vector2::new(cached.width as f32, cached.height as f32) * scale
+        );
+    }
+}
@@ -0,0 +1,95 @@
+use windows::core::Result;
+use windows::Win32::Graphics::Direct3D12::*;
+use windows::Win32::Graphics::Dxgi::Common::*;
+
+use crate::model::Image;
+
+const FORMAT: DXGI_FORMAT = DXGI_FORMAT_R8G8B8A8_UNORM_SRGB;
+
+/// An sRGB RGBA8 texture in a CPU-writable heap, filled once at creation.
+pub struct ImageTexture {
+    resource: ID3D12Resource,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl ImageTexture {
+    pub fn new(device: &ID3D12Device, width: u32, height: u32, rgba8: &[u8]) -> Result<Self> {
+        assert_eq!(rgba8.len(), (width * height * 4) as usize);
+        let heap_properties = D3D12_HEAP_PROPERTIES {
+            Type: D3D12_HEAP_TYPE_CUSTOM,
+            CPUPageProperty: D3D12_CPU_PAGE_PROPERTY_WRITE_BACK,
+            MemoryPoolPreference: D3D12_MEMORY_POOL_L0,
+            CreationNodeMask: 0,
+            VisibleNodeMask: 0,
+        };
+        let resource_desc = D3D12_RESOURCE_DESC {
+            Format: FORMAT,
+            Width: width as u64,
+            Height: height,
+            DepthOrArraySize: 1,
+            SampleDesc: DXGI_SAMPLE_DESC {
+                Count: 1,
+                Quality: 0,
+            },
+            MipLevels: 1,
+            Dimension: D3D12_RESOURCE_DIMENSION_TEXTURE2D,
+            Layout: D3D12_TEXTURE_LAYOUT_UNKNOWN,
+            Flags: D3D12_RESOURCE_FLAG_NONE,
+            ..Default::default()
+        };
+        let mut resource: Option<ID3D12Resource> = None;
+        unsafe {
+            device.CreateCommittedResource(
+                &heap_properties,
+                D3D12_HEAP_FLAG_NONE,
+                &resource_desc,
+                D3D12_RESOURCE_STATE_PIXEL_SHADER_RESOURCE,
+                None,
+                &mut resource,
+            )
+        }?;
+        let resource = resource.unwrap();
+        unsafe {
+            resource.Map(0, None, None)?;
+            resource.WriteToSubresource(
+                0,
+                None,
+                rgba8.as_ptr() as *const _,
+                width * 4,
+                width * height * 4,
+            )?;
+            resource.Unmap(0, None);
+        }
+        Ok(ImageTexture {
+            resource,
+            width,
+            height,
+        })
+    }
+
+    pub fn from_image(device: &ID3D12Device, image: &Image) -> Result<Self> {
+        Self::new(device, image.width, image.height, &image.rgba8)
+    }
+
+    pub fn create_shader_resource_view(
+        &self,
+        device: &ID3D12Device,
+        handle: D3D12_CPU_DESCRIPTOR_HANDLE,
+    ) {
+        let desc = D3D12_SHADER_RESOURCE_VIEW_DESC {
+            Format: FORMAT,
+            Shader4ComponentMapping: D3D12_DEFAULT_SHADER_4_COMPONENT_MAPPING,
+            ViewDimension: D3D12_SRV_DIMENSION_TEXTURE2D,
+            Anonymous: D3D12_SHADER_RESOURCE_VIEW_DESC_0 {
+                Texture2D: D3D12_TEX2D_SRV {
+                    MipLevels: 1,
+                    ..Default::default()
+                },
+            },
+        };
+        unsafe {
+            device.CreateShaderResourceView(&self.resource, Some(&desc), handle);
+        }
+    }
+}
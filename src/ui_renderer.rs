@@ -0,0 +1,425 @@
+use std::collections::HashMap;
+use std::ffi::c_void;
+
+use egui::{ImageData, TextureId, TexturesDelta};
+use windows::{
+    core::*, Win32::Foundation::E_FAIL, Win32::Foundation::RECT, Win32::Graphics::Direct3D::*,
+    Win32::Graphics::Direct3D12::*, Win32::Graphics::Dxgi::Common::*,
+};
+
+use crate::color::OutputConstants;
+use crate::debug_ui::{UiDrawList, UiVertex, MAX_UI_INDICES, MAX_UI_VERTICES};
+use crate::sampler;
+use crate::sprite_batch::screen_projection;
+use crate::upload_ring::UploadRing;
+use crate::vertex_layout::VertexLayout;
+
+pub const MAX_UI_TEXTURES: u32 = 16;
+
+const ROOT_PROJECTION: u32 = 1;
+const ROOT_OUTPUT: u32 = 2;
+const PROJECTION_32BIT_VALUES: u32 = 16;
+
+struct UiTexture {
+    resource: ID3D12Resource,
+    slot: u32,
+    width: u32,
+    height: u32,
+}
+
+pub struct UiRenderer {
+    vertex_shader: ID3DBlob,
+    pixel_shader: ID3DBlob,
+    root_signature: ID3D12RootSignature,
+    pipeline_state: ID3D12PipelineState,
+    upload_ring: UploadRing,
+    descriptor_heap: ID3D12DescriptorHeap,
+    descriptor_size: u32,
+    free_slots: Vec<u32>,
+    textures: HashMap<TextureId, UiTexture>,
+}
+
+impl UiRenderer {
+    pub fn new(
+        device: &ID3D12Device,
+        vertex_shader: ID3DBlob,
+        pixel_shader: ID3DBlob,
+        rtv_format: DXGI_FORMAT,
+        frame_count: u32,
+    ) -> Result<Self> {
+        let root_signature = create_root_signature(device)?;
+        let pipeline_state = create_pipeline_state(
+            device,
+            &root_signature,
+            &vertex_shader,
+            &pixel_shader,
+            rtv_format,
+        )?;
+        let frame_capacity = (MAX_UI_VERTICES * std::mem::size_of::<UiVertex>()
+            + MAX_UI_INDICES * std::mem::size_of::<u32>()) as u64
+            + 256;
+        let upload_ring = UploadRing::new(device, frame_capacity, frame_count)?;
+        let descriptor_heap: ID3D12DescriptorHeap = unsafe {
+            device.CreateDescriptorHeap(&D3D12_DESCRIPTOR_HEAP_DESC {
+                Type: D3D12_DESCRIPTOR_HEAP_TYPE_CBV_SRV_UAV,
+                NumDescriptors: MAX_UI_TEXTURES,
+                Flags: D3D12_DESCRIPTOR_HEAP_FLAG_SHADER_VISIBLE,
+                NodeMask: 0,
+            })
+        }?;
+        let descriptor_size = unsafe {
+            device.GetDescriptorHandleIncrementSize(D3D12_DESCRIPTOR_HEAP_TYPE_CBV_SRV_UAV)
+        };
+        Ok(UiRenderer {
+            vertex_shader,
+            pixel_shader,
+            root_signature,
+            pipeline_state,
+            upload_ring,
+            descriptor_heap,
+            descriptor_size,
+            free_slots: (0..MAX_UI_TEXTURES).rev().collect(),
+            textures: HashMap::new(),
+        })
+    }
+
+    pub fn rebuild(&mut self, device: &ID3D12Device, rtv_format: DXGI_FORMAT) -> Result<()> {
+        self.pipeline_state = create_pipeline_state(
+            device,
+            &self.root_signature,
+            &self.vertex_shader,
+            &self.pixel_shader,
+            rtv_format,
+        )?;
+        Ok(())
+    }
+
+    pub fn texture_handle(&self, id: TextureId) -> Option<D3D12_GPU_DESCRIPTOR_HANDLE> {
+        let texture = self.textures.get(&id)?;
+        let start = unsafe { self.descriptor_heap.GetGPUDescriptorHandleForHeapStart() };
+        Some(D3D12_GPU_DESCRIPTOR_HANDLE {
+            ptr: start.ptr + (texture.slot * self.descriptor_size) as u64,
+        })
+    }
+
+    pub fn update_textures(&mut self, device: &ID3D12Device, delta: &TexturesDelta) -> Result<()> {
+        for (id, image_delta) in &delta.set {
+            let [width, height] = image_delta.image.size();
+            let pixels: Vec<u8> = match &image_delta.image {
+                ImageData::Color(image) => image
+                    .pixels
+                    .iter()
+                    .flat_map(|color| color.to_array())
+                    .collect(),
+                ImageData::Font(image) => image
+                    .srgba_pixels(None)
+                    .flat_map(|color| color.to_array())
+                    .collect(),
+            };
+            let (x, y) = match image_delta.pos {
+                Some([x, y]) => (x as u32, y as u32),
+                None => {
+                    self.create_texture(device, *id, width as u32, height as u32)?;
+                    (0, 0)
+                }
+            };
+            let Some(texture) = self.textures.get(id) else {
+                continue;
+            };
+            if x + width as u32 > texture.width || y + height as u32 > texture.height {
+                continue;
+            }
+            let dst_box = D3D12_BOX {
+                left: x,
+                top: y,
+                front: 0,
+                right: x + width as u32,
+                bottom: y + height as u32,
+                back: 1,
+            };
+            unsafe {
+                texture.resource.WriteToSubresource(
+                    0,
+                    Some(&dst_box),
+                    pixels.as_ptr() as *const c_void,
+                    (width * 4) as u32,
+                    (width * height * 4) as u32,
+                )
+            }?;
+        }
+        Ok(())
+    }
+
+    pub fn free_textures(&mut self, ids: &[TextureId]) {
+        for id in ids {
+            if let Some(texture) = self.textures.remove(id) {
+                self.free_slots.push(texture.slot);
+            }
+        }
+    }
+
+    fn create_texture(
+        &mut self,
+        device: &ID3D12Device,
+        id: TextureId,
+        width: u32,
+        height: u32,
+    ) -> Result<()> {
+        let slot = match self.textures.remove(&id) {
+            Some(texture) => texture.slot,
+            None => self
+                .free_slots
+                .pop()
+                .ok_or_else(|| Error::new(E_FAIL, "ui descriptor heap is full".into()))?,
+        };
+        let heap_properties = D3D12_HEAP_PROPERTIES {
+            Type: D3D12_HEAP_TYPE_CUSTOM,
+            CPUPageProperty: D3D12_CPU_PAGE_PROPERTY_WRITE_BACK,
+            MemoryPoolPreference: D3D12_MEMORY_POOL_L0,
+            CreationNodeMask: 0,
+            VisibleNodeMask: 0,
+        };
+        let resource_desc = D3D12_RESOURCE_DESC {
+            Format: DXGI_FORMAT_R8G8B8A8_UNORM_SRGB,
+            Width: width as u64,
+            Height: height,
+            DepthOrArraySize: 1,
+            SampleDesc: DXGI_SAMPLE_DESC {
+                Count: 1,
+                Quality: 0,
+            },
+            MipLevels: 1,
+            Dimension: D3D12_RESOURCE_DIMENSION_TEXTURE2D,
+            Layout: D3D12_TEXTURE_LAYOUT_UNKNOWN,
+            Flags: D3D12_RESOURCE_FLAG_NONE,
+            ..Default::default()
+        };
+        let mut resource: Option<ID3D12Resource> = None;
+        let created = unsafe {
+            device.CreateCommittedResource(
+                &heap_properties,
+                D3D12_HEAP_FLAG_NONE,
+                &resource_desc,
+                D3D12_RESOURCE_STATE_PIXEL_SHADER_RESOURCE,
+                None,
+                &mut resource,
+            )
+        }
+        .and_then(|_| {
+            let resource = resource.unwrap();
+            unsafe { resource.Map(0, None, None) }?;
+            Ok(resource)
+        });
+        let resource = match created {
+            Ok(resource) => resource,
+            Err(error) => {
+                self.free_slots.push(slot);
+                return Err(error);
+            }
+        };
+
+        let desc = D3D12_SHADER_RESOURCE_VIEW_DESC {
+            Format: DXGI_FORMAT_R8G8B8A8_UNORM_SRGB,
+            Shader4ComponentMapping: D3D12_DEFAULT_SHADER_4_COMPONENT_MAPPING,
+            ViewDimension: D3D12_SRV_DIMENSION_TEXTURE2D,
+            Anonymous: D3D12_SHADER_RESOURCE_VIEW_DESC_0 {
+                Texture2D: D3D12_TEX2D_SRV {
+                    MipLevels: 1,
+                    ..Default::default()
+                },
+            },
+        };
+        let start = unsafe { self.descriptor_heap.GetCPUDescriptorHandleForHeapStart() };
+        let handle = D3D12_CPU_DESCRIPTOR_HANDLE {
+            ptr: start.ptr + (slot * self.descriptor_size) as usize,
+        };
+        unsafe { device.CreateShaderResourceView(&resource, Some(&desc), handle) };
+
+        self.textures.insert(
+            id,
+            UiTexture {
+                resource,
+                slot,
+                width,
+                height,
+            },
+        );
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn render(
+        &mut self,
+        command_list: &ID3D12GraphicsCommandList,
+        frame_index: u32,
+        draw_list: &UiDrawList,
+        output_constants: &OutputConstants,
+        rtv_handle: D3D12_CPU_DESCRIPTOR_HANDLE,
+        width: u32,
+        height: u32,
+    ) {
+        self.upload_ring.begin_frame(frame_index);
+        let Some((vertex_buffer, index_buffer)) = draw_list.upload(&mut self.upload_ring) else {
+            return;
+        };
+        let projection = screen_projection(width as f32, height as f32);
+        unsafe {
+            let descriptor_heaps: [Option<ID3D12DescriptorHeap>; 1] =
+                [Some(self.descriptor_heap.clone())];
+            command_list.SetDescriptorHeaps(&descriptor_heaps);
+            command_list.SetGraphicsRootSignature(&self.root_signature);
+            command_list.SetPipelineState(&self.pipeline_state);
+            command_list.OMSetRenderTargets(1, Some(&rtv_handle), true, None);
+            command_list.IASetPrimitiveTopology(D3D_PRIMITIVE_TOPOLOGY_TRIANGLELIST);
+            command_list.SetGraphicsRoot32BitConstants(
+                ROOT_PROJECTION,
+                PROJECTION_32BIT_VALUES,
+                &projection as *const _ as *const c_void,
+                0,
+            );
+            command_list.SetGraphicsRoot32BitConstants(
+                ROOT_OUTPUT,
+                OutputConstants::NUM_32BIT_VALUES,
+                output_constants as *const _ as *const c_void,
+                0,
+            );
+        }
+        draw_list.submit(command_list, vertex_buffer, index_buffer, |id| {
+            self.texture_handle(id)
+        });
+        let full = RECT {
+            left: 0,
+            top: 0,
+            right: width as i32,
+            bottom: height as i32,
+        };
+        unsafe { command_list.RSSetScissorRects(&[full]) };
+    }
+}
+
+fn create_root_signature(device: &ID3D12Device) -> Result<ID3D12RootSignature> {
+    let descriptor_ranges = [D3D12_DESCRIPTOR_RANGE {
+        NumDescriptors: 1,
+        RangeType: D3D12_DESCRIPTOR_RANGE_TYPE_SRV,
+        BaseShaderRegister: 0,
+        OffsetInDescriptorsFromTableStart: D3D12_DESCRIPTOR_RANGE_OFFSET_APPEND,
+        ..Default::default()
+    }];
+    let constants = |register, visibility, count| D3D12_ROOT_PARAMETER {
+        ParameterType: D3D12_ROOT_PARAMETER_TYPE_32BIT_CONSTANTS,
+        ShaderVisibility: visibility,
+        Anonymous: D3D12_ROOT_PARAMETER_0 {
+            Constants: D3D12_ROOT_CONSTANTS {
+                ShaderRegister: register,
+                RegisterSpace: 0,
+                Num32BitValues: count,
+            },
+        },
+    };
+    let root_parameters = [
+        D3D12_ROOT_PARAMETER {
+            ParameterType: D3D12_ROOT_PARAMETER_TYPE_DESCRIPTOR_TABLE,
+            ShaderVisibility: D3D12_SHADER_VISIBILITY_PIXEL,
+            Anonymous: D3D12_ROOT_PARAMETER_0 {
+                DescriptorTable: D3D12_ROOT_DESCRIPTOR_TABLE {
+                    NumDescriptorRanges: descriptor_ranges.len() as u32,
+                    pDescriptorRanges: descriptor_ranges.as_ptr(),
+                },
+            },
+        },
+        constants(0, D3D12_SHADER_VISIBILITY_VERTEX, PROJECTION_32BIT_VALUES),
+        constants(
+            1,
+            D3D12_SHADER_VISIBILITY_PIXEL,
+            OutputConstants::NUM_32BIT_VALUES,
+        ),
+    ];
+    let static_samplers = sampler::static_samplers();
+    let root_signature_desc = D3D12_ROOT_SIGNATURE_DESC {
+        Flags: D3D12_ROOT_SIGNATURE_FLAG_ALLOW_INPUT_ASSEMBLER_INPUT_LAYOUT,
+        pParameters: root_parameters.as_ptr(),
+        NumParameters: root_parameters.len() as u32,
+        pStaticSamplers: static_samplers.as_ptr(),
+        NumStaticSamplers: static_samplers.len() as u32,
+    };
+
+    let mut blob = None;
+    unsafe {
+        D3D12SerializeRootSignature(
+            &root_signature_desc,
+            D3D_ROOT_SIGNATURE_VERSION_1_0,
+            &mut blob,
+            None,
+        )
+    }?;
+    let blob = blob.unwrap();
+    unsafe {
+        device.CreateRootSignature(
+            0,
+            std::slice::from_raw_parts(blob.GetBufferPointer() as *const u8, blob.GetBufferSize()),
+        )
+    }
+}
+
+fn create_pipeline_state(
+    device: &ID3D12Device,
+    root_signature: &ID3D12RootSignature,
+    vertex_shader: &ID3DBlob,
+    pixel_shader: &ID3DBlob,
+    rtv_format: DXGI_FORMAT,
+) -> Result<ID3D12PipelineState> {
+    let input_layout = UiVertex::input_layout();
+    let mut render_target_blend_descs = [D3D12_RENDER_TARGET_BLEND_DESC::default(); 8];
+    render_target_blend_descs[0] = D3D12_RENDER_TARGET_BLEND_DESC {
+        BlendEnable: true.into(),
+        LogicOpEnable: false.into(),
+        SrcBlend: D3D12_BLEND_ONE,
+        DestBlend: D3D12_BLEND_INV_SRC_ALPHA,
+        BlendOp: D3D12_BLEND_OP_ADD,
+        SrcBlendAlpha: D3D12_BLEND_INV_DEST_ALPHA,
+        DestBlendAlpha: D3D12_BLEND_ONE,
+        BlendOpAlpha: D3D12_BLEND_OP_ADD,
+        LogicOp: D3D12_LOGIC_OP_NOOP,
+        RenderTargetWriteMask: D3D12_COLOR_WRITE_ENABLE_ALL.0 as u8,
+    };
+    let mut desc = D3D12_GRAPHICS_PIPELINE_STATE_DESC {
+        pRootSignature: std::mem::ManuallyDrop::new(Some(root_signature.clone())),
+        VS: D3D12_SHADER_BYTECODE {
+            pShaderBytecode: unsafe { vertex_shader.GetBufferPointer() },
+            BytecodeLength: unsafe { vertex_shader.GetBufferSize() },
+        },
+        PS: D3D12_SHADER_BYTECODE {
+            pShaderBytecode: unsafe { pixel_shader.GetBufferPointer() },
+            BytecodeLength: unsafe { pixel_shader.GetBufferSize() },
+        },
+        SampleMask: D3D12_DEFAULT_SAMPLE_MASK,
+        RasterizerState: D3D12_RASTERIZER_DESC {
+            CullMode: D3D12_CULL_MODE_NONE,
+            FillMode: D3D12_FILL_MODE_SOLID,
+            DepthClipEnable: true.into(),
+            ..Default::default()
+        },
+        BlendState: D3D12_BLEND_DESC {
+            AlphaToCoverageEnable: false.into(),
+            IndependentBlendEnable: false.into(),
+            RenderTarget: render_target_blend_descs,
+        },
+        InputLayout: D3D12_INPUT_LAYOUT_DESC {
+            pInputElementDescs: input_layout.as_ptr(),
+            NumElements: input_layout.len() as u32,
+        },
+        IBStripCutValue: D3D12_INDEX_BUFFER_STRIP_CUT_VALUE_DISABLED,
+        PrimitiveTopologyType: D3D12_PRIMITIVE_TOPOLOGY_TYPE_TRIANGLE,
+        DSVFormat: DXGI_FORMAT_UNKNOWN,
+        NumRenderTargets: 1,
+        SampleDesc: DXGI_SAMPLE_DESC {
+            Count: 1,
+            Quality: 0,
+        },
+        ..Default::default()
+    };
+    desc.RTVFormats[0] = rtv_format;
+    let pipeline_state = unsafe { device.CreateGraphicsPipelineState(&desc) };
+    unsafe { std::mem::ManuallyDrop::drop(&mut desc.pRootSignature) };
+    pipeline_state
+}
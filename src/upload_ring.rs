@@ -0,0 +1,103 @@
+use windows::{core::*, Win32::Graphics::Direct3D12::*, Win32::Graphics::Dxgi::Common::*};
+
+pub struct UploadAllocation<'a> {
+    pub data: &'a mut [u8],
+    pub gpu_address: u64,
+    pub offset: u64,
+}
+
+pub struct UploadRing {
+    buffer: ID3D12Resource,
+    cpu_base: *mut u8,
+    gpu_base: u64,
+    frame_capacity: u64,
+    frame_count: u32,
+    frame_start: u64,
+    cursor: u64,
+}
+
+impl UploadRing {
+    pub fn new(device: &ID3D12Device, frame_capacity: u64, frame_count: u32) -> Result<Self> {
+        let heap_properties = D3D12_HEAP_PROPERTIES {
+            Type: D3D12_HEAP_TYPE_UPLOAD,
+            CPUPageProperty: D3D12_CPU_PAGE_PROPERTY_UNKNOWN,
+            MemoryPoolPreference: D3D12_MEMORY_POOL_UNKNOWN,
+            ..Default::default()
+        };
+        let resource_desc = D3D12_RESOURCE_DESC {
+            Dimension: D3D12_RESOURCE_DIMENSION_BUFFER,
+            Width: frame_capacity * frame_count as u64,
+            Height: 1,
+            DepthOrArraySize: 1,
+            MipLevels: 1,
+            Format: DXGI_FORMAT_UNKNOWN,
+            SampleDesc: DXGI_SAMPLE_DESC {
+                Count: 1,
+                ..Default::default()
+            },
+            Flags: D3D12_RESOURCE_FLAG_NONE,
+            Layout: D3D12_TEXTURE_LAYOUT_ROW_MAJOR,
+            ..Default::default()
+        };
+
+        let mut buffer: Option<ID3D12Resource> = None;
+        unsafe {
+            device.CreateCommittedResource(
+                &heap_properties,
+                D3D12_HEAP_FLAG_NONE,
+                &resource_desc,
+                D3D12_RESOURCE_STATE_GENERIC_READ,
+                None,
+                &mut buffer,
+            )
+        }?;
+        let buffer = buffer.unwrap();
+
+        let mut cpu_base = std::ptr::null_mut();
+        unsafe { buffer.Map(0, None, Some(&mut cpu_base)) }?;
+        let gpu_base = unsafe { buffer.GetGPUVirtualAddress() };
+
+        Ok(UploadRing {
+            buffer,
+            cpu_base: cpu_base as *mut u8,
+            gpu_base,
+            frame_capacity,
+            frame_count,
+            frame_start: 0,
+            cursor: 0,
+        })
+    }
+
+    pub fn resource(&self) -> &ID3D12Resource {
+        &self.buffer
+    }
+
+    pub fn begin_frame(&mut self, frame_index: u32) {
+        self.frame_start = (frame_index % self.frame_count) as u64 * self.frame_capacity;
+        self.cursor = 0;
+    }
+
+    pub fn allocate(&mut self, size: u64, alignment: u64) -> Option<UploadAllocation<'_>> {
+        let offset = (self.cursor + alignment - 1) & !(alignment - 1);
+        if offset + size > self.frame_capacity {
+            return None;
+        }
+        self.cursor = offset + size;
+
+        let offset = self.frame_start + offset;
+        let data = unsafe {
+            std::slice::from_raw_parts_mut(self.cpu_base.add(offset as usize), size as usize)
+        };
+        Some(UploadAllocation {
+            data,
+            gpu_address: self.gpu_base + offset,
+            offset,
+        })
+    }
+}
+
+impl Drop for UploadRing {
+    fn drop(&mut self) {
+        unsafe { self.buffer.Unmap(0, None) };
+    }
+}
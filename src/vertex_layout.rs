@@ -0,0 +1,454 @@
+use cgmath::{Matrix4, Point2, Point3, Vector2, Vector3, Vector4};
+use windows::{core::PCSTR, Win32::Graphics::Direct3D12::*, Win32::Graphics::Dxgi::Common::*};
+
+pub use d3d12forrust_derive::VertexLayout;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InputElement {
+    pub semantic_name: &'static str,
+    pub semantic_index: u32,
+    pub format: DXGI_FORMAT,
+    pub input_slot: u32,
+    pub aligned_byte_offset: u32,
+    pub input_slot_class: D3D12_INPUT_CLASSIFICATION,
+    pub instance_data_step_rate: u32,
+}
+
+impl InputElement {
+    pub fn semantic_name(&self) -> &'static str {
+        self.semantic_name.trim_end_matches('\0')
+    }
+
+    pub fn desc(&self) -> D3D12_INPUT_ELEMENT_DESC {
+        D3D12_INPUT_ELEMENT_DESC {
+            SemanticName: PCSTR(self.semantic_name.as_ptr()),
+            SemanticIndex: self.semantic_index,
+            Format: self.format,
+            InputSlot: self.input_slot,
+            AlignedByteOffset: self.aligned_byte_offset,
+            InputSlotClass: self.input_slot_class,
+            InstanceDataStepRate: self.instance_data_step_rate,
+        }
+    }
+}
+
+/// Derive with `#[derive(VertexLayout)]`. Every field must match its DXGI
+/// format in both size and component kind, checked at compile time.
+///
+/// ```
+/// # use d3d12forrust::vertex_layout::VertexLayout;
+/// # use windows::Win32::Graphics::Dxgi::Common::*;
+/// #[repr(C)]
+/// #[derive(VertexLayout)]
+/// struct Vertex {
+///     position: [f32; 3],
+///     #[vertex(semantic = "BONENO", format = R16G16B16A16_UINT)]
+///     bone_no: [u16; 4],
+///     #[vertex(semantic = "COLOR", normalized)]
+///     color: [u8; 4],
+/// }
+/// # fn main() {
+/// let elements = Vertex::input_elements();
+/// assert_eq!(elements[1].format, DXGI_FORMAT_R16G16B16A16_UINT);
+/// assert_eq!(elements[2].format, DXGI_FORMAT_R8G8B8A8_UNORM);
+/// assert_eq!(elements[2].aligned_byte_offset, 20);
+/// # }
+/// ```
+///
+/// A float format on an integer field:
+///
+/// ```compile_fail,E0080
+/// # use d3d12forrust::vertex_layout::VertexLayout;
+/// #[repr(C)]
+/// #[derive(VertexLayout)]
+/// struct Vertex {
+///     #[vertex(format = R32_FLOAT)]
+///     id: u32,
+/// }
+/// # fn main() {}
+/// ```
+///
+/// An integer format on a float field:
+///
+/// ```compile_fail,E0080
+/// # use d3d12forrust::vertex_layout::VertexLayout;
+/// #[repr(C)]
+/// #[derive(VertexLayout)]
+/// struct Vertex {
+///     #[vertex(format = R32_UINT)]
+///     weight: f32,
+/// }
+/// # fn main() {}
+/// ```
+///
+/// A signed-normalized format on unsigned storage:
+///
+/// ```compile_fail,E0080
+/// # use d3d12forrust::vertex_layout::VertexLayout;
+/// #[repr(C)]
+/// #[derive(VertexLayout)]
+/// struct Vertex {
+///     #[vertex(format = R8G8B8A8_SNORM)]
+///     color: [u8; 4],
+/// }
+/// # fn main() {}
+/// ```
+///
+/// A format of the wrong size:
+///
+/// ```compile_fail,E0080
+/// # use d3d12forrust::vertex_layout::VertexLayout;
+/// #[repr(C)]
+/// #[derive(VertexLayout)]
+/// struct Vertex {
+///     #[vertex(format = R32G32_FLOAT)]
+///     uv: [f32; 3],
+/// }
+/// # fn main() {}
+/// ```
+///
+/// `normalized` on a type without a normalized format:
+///
+/// ```compile_fail,E0080
+/// # use d3d12forrust::vertex_layout::VertexLayout;
+/// #[repr(C)]
+/// #[derive(VertexLayout)]
+/// struct Vertex {
+///     #[vertex(normalized)]
+///     weight: f32,
+/// }
+/// # fn main() {}
+/// ```
+///
+/// A struct without `#[repr(C)]`:
+///
+/// ```compile_fail
+/// # use d3d12forrust::vertex_layout::VertexLayout;
+/// #[derive(VertexLayout)]
+/// struct Vertex {
+///     position: [f32; 3],
+/// }
+/// # fn main() {}
+/// ```
+pub trait VertexLayout: Sized {
+    fn input_elements() -> Vec<InputElement>;
+
+    fn input_layout() -> Vec<D3D12_INPUT_ELEMENT_DESC> {
+        Self::input_elements()
+            .iter()
+            .map(InputElement::desc)
+            .collect()
+    }
+
+    fn stride() -> u32 {
+        std::mem::size_of::<Self>() as u32
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ComponentKind {
+    Float,
+    Uint,
+    Sint,
+    Unorm,
+    Snorm,
+}
+
+pub trait VertexAttribute {
+    const FORMAT: DXGI_FORMAT;
+    const NORMALIZED_FORMAT: Option<DXGI_FORMAT> = None;
+    const ROWS: u32 = 1;
+    const KIND: ComponentKind = match format_kind(Self::FORMAT) {
+        Some(kind) => kind,
+        None => panic!("vertex attribute format has no component kind"),
+    };
+}
+
+macro_rules! vertex_attribute {
+    ($($ty:ty => $format:ident $(, $normalized:ident)?;)*) => {
+        $(
+            impl VertexAttribute for $ty {
+                const FORMAT: DXGI_FORMAT = $format;
+                $(const NORMALIZED_FORMAT: Option<DXGI_FORMAT> = Some($normalized);)?
+            }
+        )*
+    };
+}
+
+vertex_attribute! {
+    f32 => DXGI_FORMAT_R32_FLOAT;
+    [f32; 2] => DXGI_FORMAT_R32G32_FLOAT;
+    [f32; 3] => DXGI_FORMAT_R32G32B32_FLOAT;
+    [f32; 4] => DXGI_FORMAT_R32G32B32A32_FLOAT;
+    Vector2<f32> => DXGI_FORMAT_R32G32_FLOAT;
+    Vector3<f32> => DXGI_FORMAT_R32G32B32_FLOAT;
+    Vector4<f32> => DXGI_FORMAT_R32G32B32A32_FLOAT;
+    Point2<f32> => DXGI_FORMAT_R32G32_FLOAT;
+    Point3<f32> => DXGI_FORMAT_R32G32B32_FLOAT;
+    u32 => DXGI_FORMAT_R32_UINT;
+    [u32; 2] => DXGI_FORMAT_R32G32_UINT;
+    [u32; 3] => DXGI_FORMAT_R32G32B32_UINT;
+    [u32; 4] => DXGI_FORMAT_R32G32B32A32_UINT;
+    i32 => DXGI_FORMAT_R32_SINT;
+    [i32; 2] => DXGI_FORMAT_R32G32_SINT;
+    [i32; 3] => DXGI_FORMAT_R32G32B32_SINT;
+    [i32; 4] => DXGI_FORMAT_R32G32B32A32_SINT;
+    u16 => DXGI_FORMAT_R16_UINT, DXGI_FORMAT_R16_UNORM;
+    [u16; 2] => DXGI_FORMAT_R16G16_UINT, DXGI_FORMAT_R16G16_UNORM;
+    [u16; 4] => DXGI_FORMAT_R16G16B16A16_UINT, DXGI_FORMAT_R16G16B16A16_UNORM;
+    i16 => DXGI_FORMAT_R16_SINT, DXGI_FORMAT_R16_SNORM;
+    [i16; 2] => DXGI_FORMAT_R16G16_SINT, DXGI_FORMAT_R16G16_SNORM;
+    [i16; 4] => DXGI_FORMAT_R16G16B16A16_SINT, DXGI_FORMAT_R16G16B16A16_SNORM;
+    u8 => DXGI_FORMAT_R8_UINT, DXGI_FORMAT_R8_UNORM;
+    [u8; 2] => DXGI_FORMAT_R8G8_UINT, DXGI_FORMAT_R8G8_UNORM;
+    [u8; 4] => DXGI_FORMAT_R8G8B8A8_UINT, DXGI_FORMAT_R8G8B8A8_UNORM;
+    i8 => DXGI_FORMAT_R8_SINT, DXGI_FORMAT_R8_SNORM;
+    [i8; 2] => DXGI_FORMAT_R8G8_SINT, DXGI_FORMAT_R8G8_SNORM;
+    [i8; 4] => DXGI_FORMAT_R8G8B8A8_SINT, DXGI_FORMAT_R8G8B8A8_SNORM;
+}
+
+impl VertexAttribute for Matrix4<f32> {
+    const FORMAT: DXGI_FORMAT = DXGI_FORMAT_R32G32B32A32_FLOAT;
+    const ROWS: u32 = 4;
+}
+
+pub const fn format_size(format: DXGI_FORMAT) -> usize {
+    match format {
+        DXGI_FORMAT_R32G32B32A32_FLOAT
+        | DXGI_FORMAT_R32G32B32A32_UINT
+        | DXGI_FORMAT_R32G32B32A32_SINT => 16,
+        DXGI_FORMAT_R32G32B32_FLOAT | DXGI_FORMAT_R32G32B32_UINT | DXGI_FORMAT_R32G32B32_SINT => 12,
+        DXGI_FORMAT_R32G32_FLOAT
+        | DXGI_FORMAT_R32G32_UINT
+        | DXGI_FORMAT_R32G32_SINT
+        | DXGI_FORMAT_R16G16B16A16_FLOAT
+        | DXGI_FORMAT_R16G16B16A16_UINT
+        | DXGI_FORMAT_R16G16B16A16_SINT
+        | DXGI_FORMAT_R16G16B16A16_UNORM
+        | DXGI_FORMAT_R16G16B16A16_SNORM => 8,
+        DXGI_FORMAT_R32_FLOAT
+        | DXGI_FORMAT_R32_UINT
+        | DXGI_FORMAT_R32_SINT
+        | DXGI_FORMAT_R16G16_FLOAT
+        | DXGI_FORMAT_R16G16_UINT
+        | DXGI_FORMAT_R16G16_SINT
+        | DXGI_FORMAT_R16G16_UNORM
+        | DXGI_FORMAT_R16G16_SNORM
+        | DXGI_FORMAT_R8G8B8A8_UINT
+        | DXGI_FORMAT_R8G8B8A8_SINT
+        | DXGI_FORMAT_R8G8B8A8_UNORM
+        | DXGI_FORMAT_R8G8B8A8_UNORM_SRGB
+        | DXGI_FORMAT_R8G8B8A8_SNORM
+        | DXGI_FORMAT_B8G8R8A8_UNORM
+        | DXGI_FORMAT_R10G10B10A2_UNORM
+        | DXGI_FORMAT_R10G10B10A2_UINT
+        | DXGI_FORMAT_R11G11B10_FLOAT => 4,
+        DXGI_FORMAT_R16_FLOAT
+        | DXGI_FORMAT_R16_UINT
+        | DXGI_FORMAT_R16_SINT
+        | DXGI_FORMAT_R16_UNORM
+        | DXGI_FORMAT_R16_SNORM
+        | DXGI_FORMAT_R8G8_UINT
+        | DXGI_FORMAT_R8G8_SINT
+        | DXGI_FORMAT_R8G8_UNORM
+        | DXGI_FORMAT_R8G8_SNORM => 2,
+        DXGI_FORMAT_R8_UINT | DXGI_FORMAT_R8_SINT | DXGI_FORMAT_R8_UNORM | DXGI_FORMAT_R8_SNORM => {
+            1
+        }
+        _ => 0,
+    }
+}
+
+pub const fn format_kind(format: DXGI_FORMAT) -> Option<ComponentKind> {
+    match format {
+        DXGI_FORMAT_R32G32B32A32_FLOAT
+        | DXGI_FORMAT_R32G32B32_FLOAT
+        | DXGI_FORMAT_R32G32_FLOAT
+        | DXGI_FORMAT_R32_FLOAT
+        | DXGI_FORMAT_R16G16B16A16_FLOAT
+        | DXGI_FORMAT_R16G16_FLOAT
+        | DXGI_FORMAT_R16_FLOAT
+        | DXGI_FORMAT_R11G11B10_FLOAT => Some(ComponentKind::Float),
+        DXGI_FORMAT_R32G32B32A32_UINT
+        | DXGI_FORMAT_R32G32B32_UINT
+        | DXGI_FORMAT_R32G32_UINT
+        | DXGI_FORMAT_R32_UINT
+        | DXGI_FORMAT_R16G16B16A16_UINT
+        | DXGI_FORMAT_R16G16_UINT
+        | DXGI_FORMAT_R16_UINT
+        | DXGI_FORMAT_R8G8B8A8_UINT
+        | DXGI_FORMAT_R8G8_UINT
+        | DXGI_FORMAT_R8_UINT
+        | DXGI_FORMAT_R10G10B10A2_UINT => Some(ComponentKind::Uint),
+        DXGI_FORMAT_R32G32B32A32_SINT
+        | DXGI_FORMAT_R32G32B32_SINT
+        | DXGI_FORMAT_R32G32_SINT
+        | DXGI_FORMAT_R32_SINT
+        | DXGI_FORMAT_R16G16B16A16_SINT
+        | DXGI_FORMAT_R16G16_SINT
+        | DXGI_FORMAT_R16_SINT
+        | DXGI_FORMAT_R8G8B8A8_SINT
+        | DXGI_FORMAT_R8G8_SINT
+        | DXGI_FORMAT_R8_SINT => Some(ComponentKind::Sint),
+        DXGI_FORMAT_R16G16B16A16_UNORM
+        | DXGI_FORMAT_R16G16_UNORM
+        | DXGI_FORMAT_R16_UNORM
+        | DXGI_FORMAT_R8G8B8A8_UNORM
+        | DXGI_FORMAT_R8G8B8A8_UNORM_SRGB
+        | DXGI_FORMAT_B8G8R8A8_UNORM
+        | DXGI_FORMAT_R8G8_UNORM
+        | DXGI_FORMAT_R8_UNORM
+        | DXGI_FORMAT_R10G10B10A2_UNORM => Some(ComponentKind::Unorm),
+        DXGI_FORMAT_R16G16B16A16_SNORM
+        | DXGI_FORMAT_R16G16_SNORM
+        | DXGI_FORMAT_R16_SNORM
+        | DXGI_FORMAT_R8G8B8A8_SNORM
+        | DXGI_FORMAT_R8G8_SNORM
+        | DXGI_FORMAT_R8_SNORM => Some(ComponentKind::Snorm),
+        _ => None,
+    }
+}
+
+// Unsigned storage may also carry unorm data or bit-packed half/small floats.
+pub const fn kind_matches(field: ComponentKind, format: DXGI_FORMAT) -> bool {
+    match (field, format_kind(format)) {
+        (ComponentKind::Float, Some(ComponentKind::Float)) => matches!(
+            format,
+            DXGI_FORMAT_R32G32B32A32_FLOAT
+                | DXGI_FORMAT_R32G32B32_FLOAT
+                | DXGI_FORMAT_R32G32_FLOAT
+                | DXGI_FORMAT_R32_FLOAT
+        ),
+        (ComponentKind::Uint, Some(ComponentKind::Float)) => !matches!(
+            format,
+            DXGI_FORMAT_R32G32B32A32_FLOAT
+                | DXGI_FORMAT_R32G32B32_FLOAT
+                | DXGI_FORMAT_R32G32_FLOAT
+                | DXGI_FORMAT_R32_FLOAT
+        ),
+        (ComponentKind::Uint, Some(ComponentKind::Uint | ComponentKind::Unorm))
+        | (ComponentKind::Sint, Some(ComponentKind::Sint | ComponentKind::Snorm))
+        | (ComponentKind::Unorm, Some(ComponentKind::Unorm))
+        | (ComponentKind::Snorm, Some(ComponentKind::Snorm)) => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[repr(C)]
+    #[derive(VertexLayout)]
+    struct Mixed {
+        position: Vector3<f32>,
+        #[vertex(semantic = "TEXCOORD", index = 1)]
+        uv: [f32; 2],
+        #[vertex(semantic = "BONENO")]
+        bone_no: [u16; 4],
+        #[vertex(semantic = "COLOR", normalized)]
+        color: [u8; 4],
+        #[vertex(semantic = "PACKED", format = R10G10B10A2_UNORM)]
+        packed: u32,
+    }
+
+    #[repr(C)]
+    #[derive(VertexLayout)]
+    #[vertex(slot = 2, per_instance, step_rate = 3)]
+    struct Instance {
+        #[vertex(semantic = "WORLD")]
+        world: Matrix4<f32>,
+        tint: [i8; 4],
+    }
+
+    #[test]
+    fn derived_offsets_match_repr_c() {
+        let elements = Mixed::input_elements();
+        let offsets: Vec<_> = elements
+            .iter()
+            .map(|e| (e.semantic_name(), e.semantic_index, e.aligned_byte_offset))
+            .collect();
+        assert_eq!(
+            offsets,
+            [
+                ("POSITION", 0, 0),
+                ("TEXCOORD", 1, 12),
+                ("BONENO", 0, 20),
+                ("COLOR", 0, 28),
+                ("PACKED", 0, 32),
+            ]
+        );
+        assert_eq!(Mixed::stride(), 36);
+        assert!(elements.iter().all(|e| e.input_slot == 0
+            && e.input_slot_class == D3D12_INPUT_CLASSIFICATION_PER_VERTEX_DATA
+            && e.instance_data_step_rate == 0));
+    }
+
+    #[test]
+    fn derived_formats_follow_field_types() {
+        let formats: Vec<_> = Mixed::input_elements().iter().map(|e| e.format).collect();
+        assert_eq!(
+            formats,
+            [
+                DXGI_FORMAT_R32G32B32_FLOAT,
+                DXGI_FORMAT_R32G32_FLOAT,
+                DXGI_FORMAT_R16G16B16A16_UINT,
+                DXGI_FORMAT_R8G8B8A8_UNORM,
+                DXGI_FORMAT_R10G10B10A2_UNORM,
+            ]
+        );
+    }
+
+    #[test]
+    fn matrix_fields_expand_to_consecutive_rows() {
+        let elements = Instance::input_elements();
+        assert_eq!(elements.len(), 5);
+        for (row, element) in elements[..4].iter().enumerate() {
+            assert_eq!(element.semantic_name(), "WORLD");
+            assert_eq!(element.semantic_index, row as u32);
+            assert_eq!(element.aligned_byte_offset, row as u32 * 16);
+            assert_eq!(element.format, DXGI_FORMAT_R32G32B32A32_FLOAT);
+        }
+        assert_eq!(elements[4].semantic_name(), "TINT");
+        assert_eq!(elements[4].aligned_byte_offset, 64);
+        assert_eq!(elements[4].format, DXGI_FORMAT_R8G8B8A8_SINT);
+        assert!(elements.iter().all(|e| e.input_slot == 2
+            && e.input_slot_class == D3D12_INPUT_CLASSIFICATION_PER_INSTANCE_DATA
+            && e.instance_data_step_rate == 3));
+    }
+
+    #[test]
+    fn descs_carry_nul_terminated_semantics() {
+        let layout = Mixed::input_layout();
+        let name = unsafe { layout[1].SemanticName.to_string() }.unwrap();
+        assert_eq!(name, "TEXCOORD");
+        assert_eq!(layout[1].SemanticIndex, 1);
+        assert_eq!(layout[1].AlignedByteOffset, 12);
+    }
+
+    #[test]
+    fn component_kinds() {
+        assert!(kind_matches(ComponentKind::Float, DXGI_FORMAT_R32G32_FLOAT));
+        assert!(!kind_matches(
+            ComponentKind::Float,
+            DXGI_FORMAT_R16G16_FLOAT
+        ));
+        assert!(!kind_matches(ComponentKind::Float, DXGI_FORMAT_R32_UINT));
+        assert!(kind_matches(ComponentKind::Uint, DXGI_FORMAT_R16G16_FLOAT));
+        assert!(kind_matches(
+            ComponentKind::Uint,
+            DXGI_FORMAT_R8G8B8A8_UNORM
+        ));
+        assert!(!kind_matches(ComponentKind::Uint, DXGI_FORMAT_R32_FLOAT));
+        assert!(!kind_matches(
+            ComponentKind::Uint,
+            DXGI_FORMAT_R8G8B8A8_SNORM
+        ));
+        assert!(!kind_matches(ComponentKind::Uint, DXGI_FORMAT_R32_SINT));
+        assert!(kind_matches(ComponentKind::Sint, DXGI_FORMAT_R16G16_SNORM));
+        assert!(!kind_matches(ComponentKind::Sint, DXGI_FORMAT_R16G16_UINT));
+        assert!(!kind_matches(ComponentKind::Uint, DXGI_FORMAT_UNKNOWN));
+        assert_eq!(<[u16; 2]>::KIND, ComponentKind::Uint);
+        assert_eq!(<Matrix4<f32>>::KIND, ComponentKind::Float);
+    }
+}
@@ -0,0 +1,499 @@
+use std::path::{Path, PathBuf};
+
+use cgmath::{Quaternion, Vector2, Vector3};
+
+use crate::binary_reader::{decode_shift_jis, BinaryReader, UnexpectedEof};
+
+#[derive(Debug)]
+pub enum VmdError {
+    Io {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    InvalidMagic,
+    UnexpectedEof(UnexpectedEof),
+}
+
+impl std::fmt::Display for VmdError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VmdError::Io { path, error } => write!(f, "{}: {}", path.display(), error),
+            VmdError::InvalidMagic => write!(f, "not a VMD file"),
+            VmdError::UnexpectedEof(eof) => eof.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for VmdError {}
+
+impl From<UnexpectedEof> for VmdError {
+    fn from(eof: UnexpectedEof) -> Self {
+        VmdError::UnexpectedEof(eof)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, VmdError>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bezier {
+    pub p1: Vector2<f32>,
+    pub p2: Vector2<f32>,
+}
+
+impl Bezier {
+    pub const LINEAR: Bezier = Bezier {
+        p1: Vector2::new(0.25, 0.25),
+        p2: Vector2::new(0.75, 0.75),
+    };
+
+    fn from_bytes(x1: u8, y1: u8, x2: u8, y2: u8) -> Self {
+        Bezier {
+            p1: Vector2::new(x1 as f32 / 127.0, y1 as f32 / 127.0),
+            p2: Vector2::new(x2 as f32 / 127.0, y2 as f32 / 127.0),
+        }
+    }
+
+    pub fn is_linear(&self) -> bool {
+        self.p1.x == self.p1.y && self.p2.x == self.p2.y
+    }
+
+    pub fn evaluate(&self, x: f32) -> f32 {
+        let x = x.clamp(0.0, 1.0);
+        if self.is_linear() {
+            return x;
+        }
+        let curve = |t: f32, a: f32, b: f32| {
+            let s = 1.0 - t;
+            3.0 * s * s * t * a + 3.0 * s * t * t * b + t * t * t
+        };
+        let (mut lo, mut hi) = (0.0f32, 1.0f32);
+        let mut t = 0.5;
+        for _ in 0..24 {
+            t = (lo + hi) * 0.5;
+            if curve(t, self.p1.x, self.p2.x) < x {
+                lo = t;
+            } else {
+                hi = t;
+            }
+        }
+        curve(t, self.p1.y, self.p2.y)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BoneKeyframe {
+    pub bone: String,
+    pub frame: u32,
+    pub translation: Vector3<f32>,
+    pub rotation: Quaternion<f32>,
+    pub interpolation: [Bezier; 4],
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MorphKeyframe {
+    pub morph: String,
+    pub frame: u32,
+    pub weight: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CameraKeyframe {
+    pub frame: u32,
+    pub distance: f32,
+    pub target: Vector3<f32>,
+    pub rotation: Vector3<f32>,
+    pub interpolation: [Bezier; 6],
+    pub fov: u32,
+    pub perspective: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LightKeyframe {
+    pub frame: u32,
+    pub color: Vector3<f32>,
+    pub direction: Vector3<f32>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IkKeyframe {
+    pub frame: u32,
+    pub visible: bool,
+    pub enabled: Vec<(String, bool)>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VmdMotion {
+    pub model_name: String,
+    pub bone_keyframes: Vec<BoneKeyframe>,
+    pub morph_keyframes: Vec<MorphKeyframe>,
+    pub camera_keyframes: Vec<CameraKeyframe>,
+    pub light_keyframes: Vec<LightKeyframe>,
+    pub ik_keyframes: Vec<IkKeyframe>,
+}
+
+impl VmdMotion {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path).map_err(|error| VmdError::Io {
+            path: path.to_path_buf(),
+            error,
+        })?;
+        Self::parse(&bytes)
+    }
+
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        let mut reader = BinaryReader::new(bytes);
+
+        let magic = reader.fixed_str(30)?;
+        let name_len = match magic {
+            b"Vocaloid Motion Data 0002" => 20,
+            b"Vocaloid Motion Data file" => 10,
+            _ => return Err(VmdError::InvalidMagic),
+        };
+        let mut motion = VmdMotion {
+            model_name: decode_shift_jis(reader.fixed_str(name_len)?),
+            ..Default::default()
+        };
+
+        let count = reader.u32()?;
+        for _ in 0..count {
+            let bone = decode_shift_jis(reader.fixed_str(15)?);
+            let frame = reader.u32()?;
+            let translation = reader.vector3()?;
+            let [x, y, z, w]: [f32; 4] = reader.vector4()?.into();
+            let bytes = reader.bytes(64)?;
+            let interpolation = std::array::from_fn(|channel| {
+                Bezier::from_bytes(
+                    bytes[channel],
+                    bytes[channel + 4],
+                    bytes[channel + 8],
+                    bytes[channel + 12],
+                )
+            });
+            motion.bone_keyframes.push(BoneKeyframe {
+                bone,
+                frame,
+                translation,
+                rotation: Quaternion::new(w, x, y, z),
+                interpolation,
+            });
+        }
+
+        if reader.is_empty() {
+            return Ok(motion);
+        }
+        let count = reader.u32()?;
+        for _ in 0..count {
+            motion.morph_keyframes.push(MorphKeyframe {
+                morph: decode_shift_jis(reader.fixed_str(15)?),
+                frame: reader.u32()?,
+                weight: reader.f32()?,
+            });
+        }
+
+        if reader.is_empty() {
+            return Ok(motion);
+        }
+        let count = reader.u32()?;
+        for _ in 0..count {
+            let frame = reader.u32()?;
+            let distance = reader.f32()?;
+            let target = reader.vector3()?;
+            let rotation = reader.vector3()?;
+            let bytes = reader.bytes(24)?;
+            let interpolation = std::array::from_fn(|channel| {
+                let b = &bytes[channel * 4..channel * 4 + 4];
+                Bezier::from_bytes(b[0], b[2], b[1], b[3])
+            });
+            motion.camera_keyframes.push(CameraKeyframe {
+                frame,
+                distance,
+                target,
+                rotation,
+                interpolation,
+                fov: reader.u32()?,
+                perspective: reader.u8()? == 0,
+            });
+        }
+
+        if reader.is_empty() {
+            return Ok(motion);
+        }
+        let count = reader.u32()?;
+        for _ in 0..count {
+            motion.light_keyframes.push(LightKeyframe {
+                frame: reader.u32()?,
+                color: reader.vector3()?,
+                direction: reader.vector3()?,
+            });
+        }
+
+        if reader.is_empty() {
+            return Ok(motion);
+        }
+        let count = reader.u32()? as usize;
+        reader.skip(count * 9)?;
+
+        if reader.is_empty() {
+            return Ok(motion);
+        }
+        let count = reader.u32()?;
+        for _ in 0..count {
+            let frame = reader.u32()?;
+            let visible = reader.u8()? != 0;
+            let ik_count = reader.u32()?;
+            let enabled = (0..ik_count)
+                .map(|_| Ok((decode_shift_jis(reader.fixed_str(20)?), reader.u8()? != 0)))
+                .collect::<Result<Vec<_>>>()?;
+            motion.ik_keyframes.push(IkKeyframe {
+                frame,
+                visible,
+                enabled,
+            });
+        }
+
+        Ok(motion)
+    }
+
+    pub fn last_frame(&self) -> u32 {
+        let bones = self.bone_keyframes.iter().map(|k| k.frame);
+        let morphs = self.morph_keyframes.iter().map(|k| k.frame);
+        let cameras = self.camera_keyframes.iter().map(|k| k.frame);
+        bones.chain(morphs).chain(cameras).max().unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f32 = 1e-4;
+
+    fn push_str(out: &mut Vec<u8>, text: &str, len: usize) {
+        let (encoded, _, _) = encoding_rs::SHIFT_JIS.encode(text);
+        let mut field = encoded.into_owned();
+        field.resize(len, 0);
+        out.extend_from_slice(&field);
+    }
+
+    fn push_f32s(out: &mut Vec<u8>, values: &[f32]) {
+        for value in values {
+            out.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+
+    fn push_u32(out: &mut Vec<u8>, value: u32) {
+        out.extend_from_slice(&value.to_le_bytes());
+    }
+
+    /// Bone interpolation block: channel `c` stores x1, y1, x2, y2 at c, c + 4, c + 8, c + 12.
+    fn bone_interpolation(curves: [[u8; 4]; 4]) -> [u8; 64] {
+        let mut bytes = [0u8; 64];
+        for (channel, [x1, y1, x2, y2]) in curves.into_iter().enumerate() {
+            bytes[channel] = x1;
+            bytes[channel + 4] = y1;
+            bytes[channel + 8] = x2;
+            bytes[channel + 12] = y2;
+        }
+        bytes
+    }
+
+    const LINEAR: [u8; 4] = [20, 20, 107, 107];
+    const EASE: [u8; 4] = [127, 0, 0, 127];
+
+    fn header(out: &mut Vec<u8>) {
+        push_str(out, "Vocaloid Motion Data 0002", 30);
+        push_str(out, "初音ミク", 20);
+    }
+
+    fn full_motion() -> Vec<u8> {
+        let mut out = Vec::new();
+        header(&mut out);
+
+        push_u32(&mut out, 2);
+        push_str(&mut out, "センター", 15);
+        push_u32(&mut out, 30);
+        push_f32s(&mut out, &[1.0, 2.0, 3.0, 0.0, 0.0, 0.0, 1.0]);
+        out.extend_from_slice(&bone_interpolation([LINEAR, EASE, LINEAR, EASE]));
+        push_str(&mut out, "頭", 15);
+        push_u32(&mut out, 0);
+        push_f32s(&mut out, &[0.0; 3]);
+        push_f32s(&mut out, &[0.0, 0.0, 0.0, 1.0]);
+        out.extend_from_slice(&bone_interpolation([LINEAR; 4]));
+
+        push_u32(&mut out, 1);
+        push_str(&mut out, "まばたき", 15);
+        push_u32(&mut out, 10);
+        push_f32s(&mut out, &[0.5]);
+
+        push_u32(&mut out, 1);
+        push_u32(&mut out, 60);
+        push_f32s(&mut out, &[-45.0, 0.0, 10.0, 0.0, 0.1, 0.2, 0.3]);
+        // Camera curves are stored x1, x2, y1, y2.
+        let mut camera = [0u8; 24];
+        camera[..4].copy_from_slice(&[127, 0, 0, 127]);
+        for channel in 1..6 {
+            camera[channel * 4..channel * 4 + 4].copy_from_slice(&[20, 107, 20, 107]);
+        }
+        out.extend_from_slice(&camera);
+        push_u32(&mut out, 30);
+        out.push(1);
+
+        push_u32(&mut out, 1);
+        push_u32(&mut out, 5);
+        push_f32s(&mut out, &[0.6, 0.6, 0.6, -0.5, -1.0, 0.5]);
+
+        // Self shadow keyframes are skipped.
+        push_u32(&mut out, 1);
+        out.extend_from_slice(&[0; 9]);
+
+        push_u32(&mut out, 1);
+        push_u32(&mut out, 15);
+        out.push(1);
+        push_u32(&mut out, 2);
+        push_str(&mut out, "左足ＩＫ", 20);
+        out.push(0);
+        push_str(&mut out, "右足ＩＫ", 20);
+        out.push(1);
+        out
+    }
+
+    #[test]
+    fn parses_every_section() {
+        let motion = VmdMotion::parse(&full_motion()).unwrap();
+        assert_eq!(motion.model_name, "初音ミク");
+
+        let center = &motion.bone_keyframes[0];
+        assert_eq!(center.bone, "センター");
+        assert_eq!(center.frame, 30);
+        assert_eq!(center.translation, Vector3::new(1.0, 2.0, 3.0));
+        assert_eq!(center.rotation, Quaternion::new(1.0, 0.0, 0.0, 0.0));
+        assert!(center.interpolation[0].is_linear());
+        assert_eq!(
+            center.interpolation[1],
+            Bezier {
+                p1: Vector2::new(1.0, 0.0),
+                p2: Vector2::new(0.0, 1.0),
+            }
+        );
+        assert!(center.interpolation[2].is_linear());
+        assert!(!center.interpolation[3].is_linear());
+        assert_eq!(motion.bone_keyframes[1].bone, "頭");
+
+        assert_eq!(
+            motion.morph_keyframes,
+            [MorphKeyframe {
+                morph: "まばたき".to_string(),
+                frame: 10,
+                weight: 0.5,
+            }]
+        );
+
+        let camera = &motion.camera_keyframes[0];
+        assert_eq!(camera.frame, 60);
+        assert_eq!(camera.distance, -45.0);
+        assert_eq!(camera.target, Vector3::new(0.0, 10.0, 0.0));
+        assert_eq!(camera.rotation, Vector3::new(0.1, 0.2, 0.3));
+        assert_eq!(camera.interpolation[0], center.interpolation[1]);
+        assert!(camera.interpolation[5].is_linear());
+        assert_eq!(camera.fov, 30);
+        assert!(!camera.perspective);
+
+        assert_eq!(motion.light_keyframes[0].frame, 5);
+        assert_eq!(
+            motion.light_keyframes[0].direction,
+            Vector3::new(-0.5, -1.0, 0.5)
+        );
+
+        assert_eq!(
+            motion.ik_keyframes,
+            [IkKeyframe {
+                frame: 15,
+                visible: true,
+                enabled: vec![
+                    ("左足ＩＫ".to_string(), false),
+                    ("右足ＩＫ".to_string(), true)
+                ],
+            }]
+        );
+        assert_eq!(motion.last_frame(), 60);
+    }
+
+    #[test]
+    fn optional_sections_may_be_missing() {
+        let mut out = Vec::new();
+        header(&mut out);
+        push_u32(&mut out, 0);
+        let motion = VmdMotion::parse(&out).unwrap();
+        assert!(motion.bone_keyframes.is_empty());
+        assert!(motion.morph_keyframes.is_empty());
+        assert!(motion.camera_keyframes.is_empty());
+        assert_eq!(motion.last_frame(), 0);
+    }
+
+    #[test]
+    fn accepts_the_old_header() {
+        let mut out = Vec::new();
+        push_str(&mut out, "Vocaloid Motion Data file", 30);
+        push_str(&mut out, "model", 10);
+        push_u32(&mut out, 0);
+        assert_eq!(VmdMotion::parse(&out).unwrap().model_name, "model");
+    }
+
+    #[test]
+    fn rejects_invalid_and_truncated_files() {
+        let mut out = Vec::new();
+        push_str(&mut out, "Vocaloid Motion Data 0003", 30);
+        assert!(matches!(
+            VmdMotion::parse(&out),
+            Err(VmdError::InvalidMagic)
+        ));
+
+        let bytes = full_motion();
+        for len in [10, 60, 100, 200, bytes.len() - 1] {
+            assert!(matches!(
+                VmdMotion::parse(&bytes[..len]),
+                Err(VmdError::UnexpectedEof(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn linear_bezier_is_identity() {
+        for x in [0.0, 0.1, 0.5, 0.9, 1.0] {
+            assert_eq!(Bezier::LINEAR.evaluate(x), x);
+        }
+        assert_eq!(Bezier::LINEAR.evaluate(-1.0), 0.0);
+        assert_eq!(Bezier::LINEAR.evaluate(2.0), 1.0);
+    }
+
+    #[test]
+    fn bezier_hits_endpoints_and_is_monotonic() {
+        // p2 mirrors p1 through the centre, so the curve is point-symmetric.
+        let ease = Bezier::from_bytes(64, 0, 63, 127);
+        assert!(ease.evaluate(0.0).abs() < EPSILON);
+        assert!((ease.evaluate(1.0) - 1.0).abs() < EPSILON);
+        assert!((ease.evaluate(0.5) - 0.5).abs() < 1e-3);
+        assert!(ease.evaluate(0.25) < 0.25);
+        assert!(ease.evaluate(0.75) > 0.75);
+        let mut previous = 0.0;
+        for step in 1..=20 {
+            let y = ease.evaluate(step as f32 / 20.0);
+            assert!(y >= previous - EPSILON);
+            previous = y;
+        }
+    }
+
+    #[test]
+    fn bezier_matches_a_reference_point() {
+        // Ease-in: p1 = (0.5, 0), p2 = (1, 1). At t = 0.5, x = 0.5 and y = 0.125 + 0.375 * 0.5.
+        let ease_in = Bezier {
+            p1: Vector2::new(0.5, 0.0),
+            p2: Vector2::new(1.0, 1.0),
+        };
+        let s: f32 = 0.5;
+        let x = 3.0 * s * s * s * 0.5 + 3.0 * s * s * s + s * s * s;
+        let y = 3.0 * s * s * s + s * s * s;
+        assert!((ease_in.evaluate(x) - y).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,269 @@
+use windows::Win32::{Foundation::RECT, Graphics::Direct3D12::D3D12_VIEWPORT};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisplayMode {
+    Windowed,
+    BorderlessFullscreen,
+    ExclusiveFullscreen,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModeChange {
+    pub from: DisplayMode,
+    pub to: DisplayMode,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SizeState {
+    width: u32,
+    height: u32,
+    pending: Option<(u32, u32)>,
+    minimized: bool,
+    mode: DisplayMode,
+}
+
+impl SizeState {
+    pub fn new(width: u32, height: u32) -> Self {
+        SizeState {
+            width,
+            height,
+            pending: None,
+            minimized: false,
+            mode: DisplayMode::Windowed,
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn mode(&self) -> DisplayMode {
+        self.mode
+    }
+
+    pub fn is_minimized(&self) -> bool {
+        self.minimized
+    }
+
+    pub fn on_resized(&mut self, width: u32, height: u32) {
+        if width == 0 || height == 0 {
+            self.minimized = true;
+            return;
+        }
+        self.minimized = false;
+        self.pending = if (width, height) == (self.width, self.height) {
+            None
+        } else {
+            Some((width, height))
+        };
+    }
+
+    pub fn take_resize(&mut self) -> Option<(u32, u32)> {
+        if self.minimized {
+            return None;
+        }
+        let (width, height) = self.pending.take()?;
+        self.width = width;
+        self.height = height;
+        Some((width, height))
+    }
+
+    fn switch(&mut self, to: DisplayMode) -> Option<ModeChange> {
+        if self.mode == to {
+            return None;
+        }
+        let change = ModeChange {
+            from: self.mode,
+            to,
+        };
+        self.mode = to;
+        Some(change)
+    }
+
+    pub fn toggle_borderless(&mut self) -> Option<ModeChange> {
+        match self.mode {
+            DisplayMode::BorderlessFullscreen => self.switch(DisplayMode::Windowed),
+            _ => self.switch(DisplayMode::BorderlessFullscreen),
+        }
+    }
+
+    pub fn toggle_exclusive(&mut self) -> Option<ModeChange> {
+        match self.mode {
+            DisplayMode::ExclusiveFullscreen => self.switch(DisplayMode::Windowed),
+            _ => self.switch(DisplayMode::ExclusiveFullscreen),
+        }
+    }
+
+    pub fn sync_exclusive(&mut self, exclusive: bool) -> Option<ModeChange> {
+        match (self.mode, exclusive) {
+            (DisplayMode::ExclusiveFullscreen, false) => self.switch(DisplayMode::Windowed),
+            (_, true) => self.switch(DisplayMode::ExclusiveFullscreen),
+            _ => None,
+        }
+    }
+
+    pub fn viewport(&self) -> D3D12_VIEWPORT {
+        D3D12_VIEWPORT {
+            Width: self.width as f32,
+            Height: self.height as f32,
+            TopLeftX: 0.,
+            TopLeftY: 0.,
+            MaxDepth: 1.,
+            MinDepth: 0.,
+        }
+    }
+
+    pub fn scissor_rect(&self) -> RECT {
+        RECT {
+            top: 0,
+            left: 0,
+            right: self.width as i32,
+            bottom: self.height as i32,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resize_is_reported_once() {
+        let mut size = SizeState::new(800, 600);
+        assert_eq!(size.take_resize(), None);
+        size.on_resized(1024, 768);
+        assert_eq!(size.take_resize(), Some((1024, 768)));
+        assert_eq!((size.width(), size.height()), (1024, 768));
+        assert_eq!(size.take_resize(), None);
+    }
+
+    #[test]
+    fn resize_to_the_same_size_is_ignored() {
+        let mut size = SizeState::new(800, 600);
+        size.on_resized(800, 600);
+        assert_eq!(size.take_resize(), None);
+        // A pending resize undone before it is taken is dropped as well.
+        size.on_resized(640, 480);
+        size.on_resized(800, 600);
+        assert_eq!(size.take_resize(), None);
+    }
+
+    #[test]
+    fn minimize_holds_resizes_until_restore() {
+        let mut size = SizeState::new(800, 600);
+        size.on_resized(0, 0);
+        assert!(size.is_minimized());
+        assert_eq!(size.take_resize(), None);
+        assert_eq!((size.width(), size.height()), (800, 600));
+
+        // Restoring to the old size needs no swap chain resize.
+        size.on_resized(800, 600);
+        assert!(!size.is_minimized());
+        assert_eq!(size.take_resize(), None);
+
+        size.on_resized(1280, 0);
+        assert!(size.is_minimized());
+        size.on_resized(1280, 720);
+        assert!(!size.is_minimized());
+        assert_eq!(size.take_resize(), Some((1280, 720)));
+    }
+
+    #[test]
+    fn borderless_toggles_back_to_windowed() {
+        let mut size = SizeState::new(800, 600);
+        assert_eq!(size.mode(), DisplayMode::Windowed);
+        assert_eq!(
+            size.toggle_borderless(),
+            Some(ModeChange {
+                from: DisplayMode::Windowed,
+                to: DisplayMode::BorderlessFullscreen,
+            })
+        );
+        assert_eq!(
+            size.toggle_borderless(),
+            Some(ModeChange {
+                from: DisplayMode::BorderlessFullscreen,
+                to: DisplayMode::Windowed,
+            })
+        );
+        assert_eq!(size.mode(), DisplayMode::Windowed);
+    }
+
+    #[test]
+    fn exclusive_toggles_from_any_mode() {
+        let mut size = SizeState::new(800, 600);
+        size.toggle_borderless();
+        assert_eq!(
+            size.toggle_exclusive(),
+            Some(ModeChange {
+                from: DisplayMode::BorderlessFullscreen,
+                to: DisplayMode::ExclusiveFullscreen,
+            })
+        );
+        assert_eq!(
+            size.toggle_borderless(),
+            Some(ModeChange {
+                from: DisplayMode::ExclusiveFullscreen,
+                to: DisplayMode::BorderlessFullscreen,
+            })
+        );
+        size.toggle_exclusive();
+        assert_eq!(
+            size.toggle_exclusive(),
+            Some(ModeChange {
+                from: DisplayMode::ExclusiveFullscreen,
+                to: DisplayMode::Windowed,
+            })
+        );
+    }
+
+    #[test]
+    fn sync_exclusive_follows_the_swap_chain() {
+        let mut size = SizeState::new(800, 600);
+        // Already in the reported state: nothing to do.
+        assert_eq!(size.sync_exclusive(false), None);
+        assert_eq!(
+            size.sync_exclusive(true),
+            Some(ModeChange {
+                from: DisplayMode::Windowed,
+                to: DisplayMode::ExclusiveFullscreen,
+            })
+        );
+        assert_eq!(size.sync_exclusive(true), None);
+        // Alt-tab out of exclusive fullscreen drops back to a window.
+        assert_eq!(
+            size.sync_exclusive(false),
+            Some(ModeChange {
+                from: DisplayMode::ExclusiveFullscreen,
+                to: DisplayMode::Windowed,
+            })
+        );
+
+        size.toggle_borderless();
+        assert_eq!(size.sync_exclusive(false), None);
+        assert_eq!(size.mode(), DisplayMode::BorderlessFullscreen);
+    }
+
+    #[test]
+    fn viewport_and_scissor_cover_the_current_size() {
+        let mut size = SizeState::new(800, 600);
+        size.on_resized(1920, 1080);
+        // Not applied until the resize is taken.
+        assert_eq!(size.viewport().Width, 800.0);
+        size.take_resize();
+
+        let viewport = size.viewport();
+        assert_eq!((viewport.TopLeftX, viewport.TopLeftY), (0.0, 0.0));
+        assert_eq!((viewport.Width, viewport.Height), (1920.0, 1080.0));
+        assert_eq!((viewport.MinDepth, viewport.MaxDepth), (0.0, 1.0));
+        let rect = size.scissor_rect();
+        assert_eq!(
+            (rect.left, rect.top, rect.right, rect.bottom),
+            (0, 0, 1920, 1080)
+        );
+    }
+}
@@ -0,0 +1,59 @@
+//! Derives `ConstantBufferLayout` from outside the crate, where
+//! `crate::constant_buffer` does not exist and the generated paths must name
+//! `d3d12forrust` directly.
+
+use cgmath::{Vector3, Vector4};
+use d3d12forrust::constant_buffer::{ConstantBufferLayout, HlslType};
+
+#[repr(C)]
+#[derive(ConstantBufferLayout)]
+struct Light {
+    direction: Vector3<f32>,
+    intensity: f32,
+}
+
+#[repr(C)]
+#[derive(ConstantBufferLayout)]
+struct Scene {
+    light: Light,
+    #[cbuffer(name = "ambient_color")]
+    ambient: Vector4<f32>,
+    exposure: f32,
+}
+
+fn f32_at(bytes: &[u8], offset: usize) -> f32 {
+    f32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+#[test]
+fn derives_outside_the_crate() {
+    let fields: Vec<_> = Scene::FIELDS
+        .iter()
+        .map(|f| (f.name, f.offset, f.size))
+        .collect();
+    assert_eq!(
+        fields,
+        [
+            ("light", 0, 16),
+            ("ambient_color", 16, 16),
+            ("exposure", 32, 4)
+        ]
+    );
+    assert_eq!(Light::SIZE, 16);
+    assert_eq!(Scene::SIZE, 36);
+
+    let scene = Scene {
+        light: Light {
+            direction: Vector3::new(0.0, -1.0, 0.0),
+            intensity: 2.0,
+        },
+        ambient: Vector4::new(0.1, 0.2, 0.3, 1.0),
+        exposure: 0.5,
+    };
+    let mut bytes = vec![0; Scene::SIZE];
+    scene.write_packed(&mut bytes);
+    assert_eq!(f32_at(&bytes, 4), -1.0);
+    assert_eq!(f32_at(&bytes, 12), 2.0);
+    assert_eq!(f32_at(&bytes, 24), 0.3);
+    assert_eq!(f32_at(&bytes, 32), 0.5);
+}
@@ -0,0 +1,51 @@
+// basic_vs.dxbc is compiled from this file (shader model 5.0, so fxc rather than dxc):
+//
+//   fxc /nologo /T vs_5_0 /E main /Fo basic_vs.dxbc basic_vs.hlsl
+//
+// Every resource, constant buffer and input below is referenced so that none
+// is stripped from RDEF/ISGN; shader_reflection's tests check them by name.
+
+struct Output {
+    float4 svpos:SV_POSITION;
+    float2 uv:TEXCOORD;
+};
+
+struct Light {
+    float3 direction;
+    float intensity;
+};
+
+cbuffer SceneConstants: register(b0) {
+    float4x4 mat;
+}
+
+cbuffer BoneConstants: register(b2) {
+    float4x4 bones[256];
+}
+
+cbuffer Lighting: register(b3) {
+    Light light;
+}
+
+SamplerState smp: register(s0);
+Texture2D<float4> tex: register(t0);
+
+Output main(
+    float3 pos:POSITION,
+    float3 normal:NORMAL,
+    float2 uv:TEXCOORD,
+    uint4 boneno:BONENO,
+    float4 weight:WEIGHT,
+    uint vertexId:SV_VertexID
+) {
+    float4x4 bm = bones[boneno.x] * weight.x + bones[boneno.y] * weight.y
+        + bones[boneno.z] * weight.z + bones[boneno.w] * weight.w;
+    float3 n = normalize(mul((float3x3)bm, normal));
+    float height = tex.SampleLevel(smp, uv, 0).r;
+    float shade = saturate(dot(n, -light.direction)) * light.intensity;
+
+    Output output;
+    output.svpos = mul(mat, mul(bm, float4(pos + n * height, 1)));
+    output.uv = uv * shade + float2(vertexId & 1, 0);
+    return output;
+}
@@ -0,0 +1,75 @@
+// mesh_ms.dxil is compiled from this file:
+//
+//   dxc -T ms_6_5 -E main -Fo mesh_ms.dxil mesh_ms.hlsl
+//
+// shader_reflection's tests read the PSV0 part: thread group size, the vertex
+// (SV_Position, TEXCOORD) and primitive (COLOR1) outputs, and the resource
+// bindings, including the unbounded texture table in space1.
+
+#define MAX_VERTICES 64
+#define MAX_TRIANGLES 126
+
+struct Output {
+    float4 svpos:SV_Position;
+    float2 uv:TEXCOORD;
+};
+
+struct PrimitiveOutput {
+    uint4 color:COLOR1;
+};
+
+struct Vertex {
+    float3 position;
+    float2 uv;
+};
+
+struct Meshlet {
+    uint vertexOffset;
+    uint vertexCount;
+    uint triangleOffset;
+    uint triangleCount;
+};
+
+cbuffer cbuff0: register(b0) {
+    matrix mat;
+    uint textureIndex;
+}
+
+StructuredBuffer<Meshlet> meshlets: register(t0);
+StructuredBuffer<Vertex> meshletVertices: register(t1);
+StructuredBuffer<uint> meshletTriangles: register(t2);
+Texture2D<float4> textures[]: register(t0, space1);
+
+// 4096 bytes of group shared memory.
+groupshared float4 positions[256];
+
+[numthreads(128, 1, 1)]
+[outputtopology("triangle")]
+void main(
+    uint gtid:SV_GroupThreadID,
+    uint gid:SV_GroupID,
+    out vertices Output verts[MAX_VERTICES],
+    out primitives PrimitiveOutput prims[MAX_TRIANGLES],
+    out indices uint3 tris[MAX_TRIANGLES]
+) {
+    Meshlet meshlet = meshlets[gid];
+    SetMeshOutputCounts(meshlet.vertexCount, meshlet.triangleCount);
+
+    for (uint i = gtid; i < 256; i += 128) {
+        positions[i] = float4(0, 0, 0, 0);
+    }
+    GroupMemoryBarrierWithGroupSync();
+
+    if (gtid < meshlet.vertexCount) {
+        Vertex v = meshletVertices[meshlet.vertexOffset + gtid];
+        positions[gtid] = mul(mat, float4(v.position, 1));
+        verts[gtid].svpos = positions[gtid];
+        verts[gtid].uv = v.uv;
+    }
+    if (gtid < meshlet.triangleCount) {
+        uint packed = meshletTriangles[meshlet.triangleOffset + gtid];
+        tris[gtid] = uint3(packed & 0xFF, (packed >> 8) & 0xFF, (packed >> 16) & 0xFF);
+        float4 color = textures[NonUniformResourceIndex(textureIndex)].Load(int3(gid, 0, 0));
+        prims[gtid].color = uint4(color * 255);
+    }
+}
@@ -0,0 +1,59 @@
+//! Derives `VertexLayout` from outside the crate, where `crate::vertex_layout`
+//! does not exist and the generated paths must name `d3d12forrust` directly.
+
+use cgmath::Matrix4;
+use d3d12forrust::vertex_layout::VertexLayout;
+use windows::Win32::Graphics::Direct3D12::*;
+use windows::Win32::Graphics::Dxgi::Common::*;
+
+#[repr(C)]
+#[derive(VertexLayout)]
+struct Vertex {
+    position: [f32; 3],
+    #[vertex(semantic = "COLOR", normalized)]
+    color: [u8; 4],
+    #[vertex(semantic = "PACKED", format = R10G10B10A2_UNORM)]
+    packed: u32,
+}
+
+#[repr(C)]
+#[derive(VertexLayout)]
+#[vertex(slot = 1, per_instance)]
+struct Instance {
+    #[vertex(semantic = "WORLD")]
+    world: Matrix4<f32>,
+}
+
+#[test]
+fn derives_outside_the_crate() {
+    let elements = Vertex::input_elements();
+    let layout: Vec<_> = elements
+        .iter()
+        .map(|e| (e.semantic_name(), e.format, e.aligned_byte_offset))
+        .collect();
+    assert_eq!(
+        layout,
+        [
+            ("POSITION", DXGI_FORMAT_R32G32B32_FLOAT, 0),
+            ("COLOR", DXGI_FORMAT_R8G8B8A8_UNORM, 12),
+            ("PACKED", DXGI_FORMAT_R10G10B10A2_UNORM, 16),
+        ]
+    );
+    assert_eq!(Vertex::stride(), 20);
+}
+
+#[test]
+fn per_instance_layouts_derive_outside_the_crate() {
+    let elements = Instance::input_elements();
+    assert_eq!(elements.len(), 4);
+    for (row, element) in elements.iter().enumerate() {
+        assert_eq!(element.semantic_index, row as u32);
+        assert_eq!(element.aligned_byte_offset, row as u32 * 16);
+        assert_eq!(element.input_slot, 1);
+        assert_eq!(
+            element.input_slot_class,
+            D3D12_INPUT_CLASSIFICATION_PER_INSTANCE_DATA
+        );
+        assert_eq!(element.instance_data_step_rate, 1);
+    }
+}